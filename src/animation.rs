@@ -0,0 +1,158 @@
+//! Animated block textures. A texture whose PNG is a strip of square
+//! frames, top to bottom, plays through them; a `.ron` file beside it with
+//! the same name can set how long each frame shows, play them in another
+//! order, and scroll the texture for things that flow. Each animated
+//! texture's layer is rewritten whenever what it shows changes.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::{
+    error::{EngineError, Result},
+    texture::{self, Texture},
+};
+
+fn default_frame_time() -> f32 {
+    0.1
+}
+
+/// How a texture animates, from the `.ron` file beside it.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationDef {
+    /// Seconds each frame shows for, unless the frame says otherwise.
+    #[serde(default = "default_frame_time")]
+    pub frame_time: f32,
+    /// The frames to play, by their place in the strip from 0; every frame
+    /// in order if empty.
+    #[serde(default)]
+    pub frames: Vec<FrameDef>,
+    /// Pixels a second to scroll the texture by, across and down, wrapping
+    /// around at the edges.
+    #[serde(default)]
+    pub scroll: [f32 ; 2],
+}
+
+impl Default for AnimationDef {
+    fn default() -> Self {
+        Self { frame_time: default_frame_time(), frames: Vec::new(), scroll: [0.0 ; 2] }
+    }
+}
+
+/// A frame on its own, or with how many seconds it shows for.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FrameDef {
+    Index(usize),
+    Timed(usize, f32),
+}
+
+struct Animation {
+    layer: u32,
+    frames: Vec<image::RgbaImage>,
+    // Which frame shows for how many seconds, in playing order.
+    sequence: Vec<(usize, f32)>,
+    duration: f32,
+    scroll: [f32 ; 2],
+    // The frame and scroll offset in the layer now.
+    shown: (usize, [u32 ; 2]),
+}
+
+impl Animation {
+    // What it shows `time` seconds in: a frame, and how many pixels it's
+    // scrolled across and down.
+    fn at(&self, time: f32) -> (usize, [u32 ; 2]) {
+        let mut left = time % self.duration;
+        let frame = self.sequence.iter()
+            .find(|&&(_, seconds)| {
+                left -= seconds;
+                left < 0.0
+            })
+            .map_or(self.sequence[0].0, |&(frame, _)| frame);
+        let (width, height) = self.frames[0].dimensions();
+        let offset = |speed: f32, size: u32| (time * speed).floor().rem_euclid(size as f32) as u32;
+        (frame, [offset(self.scroll[0], width), offset(self.scroll[1], height)])
+    }
+}
+
+/// Plays every animated texture in a texture array.
+pub struct TextureAnimator {
+    animations: Vec<Animation>,
+    time: f32,
+}
+
+impl TextureAnimator {
+    /// Finds the animated textures among `layers`, the PNG bytes of each
+    /// layer in order. `defs` gives the animation of the layers that have
+    /// one written out; any other strip of frames plays them in order.
+    pub fn new(layers: &[&[u8]], defs: &HashMap<u32, AnimationDef>, label: &str) -> Result<Self> {
+        let invalid = |layer: u32, reason: String| EngineError::InvalidTexture {
+            label: label.to_string(),
+            reason: format!("layer {layer}: {reason}"),
+        };
+
+        let mut animations = Vec::new();
+        for (layer, bytes) in (0..).zip(layers) {
+            let image = image::load_from_memory(bytes)
+                .map_err(|source| EngineError::Texture { label: label.to_string(), source })?
+                .to_rgba8();
+            let frames = texture::split_frames(&image);
+            let def = defs.get(&layer);
+            if frames.len() == 1 && def.is_none() {
+                continue;
+            }
+            let def = def.cloned().unwrap_or_default();
+
+            let sequence = if def.frames.is_empty() {
+                (0..frames.len()).map(|frame| (frame, def.frame_time)).collect()
+            } else {
+                def.frames.iter()
+                    .map(|&frame| match frame {
+                        FrameDef::Index(frame) => (frame, def.frame_time),
+                        FrameDef::Timed(frame, seconds) => (frame, seconds),
+                    })
+                    .collect::<Vec<_>>()
+            };
+            if let Some(&(frame, _)) = sequence.iter().find(|&&(frame, _)| frame >= frames.len()) {
+                return Err(invalid(layer, format!("frame {frame} is past the last of {}", frames.len())));
+            }
+            if let Some(&(_, seconds)) = sequence.iter().find(|&&(_, seconds)| !seconds.is_finite() || seconds <= 0.0) {
+                return Err(invalid(layer, format!("a frame can't show for {seconds} seconds")));
+            }
+            if def.scroll.iter().any(|speed| !speed.is_finite()) {
+                return Err(invalid(layer, "scroll speeds have to be finite".to_string()));
+            }
+
+            let duration = sequence.iter().map(|&(_, seconds)| seconds).sum();
+            let shown = (sequence[0].0, [0 ; 2]);
+            animations.push(Animation { layer, frames, sequence, duration, scroll: def.scroll, shown });
+        }
+        Ok(Self { animations, time: 0.0 })
+    }
+
+    /// Moves the animations on by `dt` seconds, rewriting the layers of
+    /// `texture` that show something new.
+    pub fn update(&mut self, queue: &wgpu::Queue, texture: &Texture, dt: f32) {
+        self.time += dt;
+        for animation in &mut self.animations {
+            let shown = animation.at(self.time);
+            if shown == animation.shown {
+                continue;
+            }
+            animation.shown = shown;
+            let (frame, [x, y]) = shown;
+            let mut image = animation.frames[frame].clone();
+            // Scrolling moves the texture right and down, so each pixel
+            // comes from up and to the left.
+            let (width, height) = image.dimensions();
+            if x != 0 || y != 0 {
+                let source = &animation.frames[frame];
+                for (px, py, pixel) in image.enumerate_pixels_mut() {
+                    *pixel = *source.get_pixel((px + width - x) % width, (py + height - y) % height);
+                }
+            }
+            texture.write_layer(queue, animation.layer, &image);
+        }
+    }
+}
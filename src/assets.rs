@@ -0,0 +1,77 @@
+//! Finds game files across resource packs. A pack is a directory under
+//! `resourcepacks/` laid out like `assets/`; each file is looked up in the
+//! selected packs in order, then in any mods, and then in `assets/`, so a
+//! pack only needs the files it changes.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+
+pub const PACKS_DIR: &str = "resourcepacks";
+const BUILT_IN_DIR: &str = "assets";
+
+#[derive(Clone, Debug)]
+pub struct Assets {
+    // Searched first to last, ending with the built-in assets.
+    roots: Vec<PathBuf>,
+}
+
+impl Assets {
+    /// Searches the named packs, highest priority first, before the
+    /// built-in assets. Packs that don't exist are skipped with a warning.
+    pub fn new(packs: &[String]) -> Self {
+        let mut roots = Vec::new();
+        for pack in packs {
+            let root = Path::new(PACKS_DIR).join(pack);
+            if root.is_dir() {
+                info!("using resource pack `{pack}`");
+                roots.push(root);
+            } else {
+                warn!("resource pack `{pack}` not found in {PACKS_DIR}/");
+            }
+        }
+        roots.push(PathBuf::from(BUILT_IN_DIR));
+        Self { roots }
+    }
+
+    /// Searches `root` after the packs and mods added before it, but
+    /// before the built-in assets, for the files a mod brings.
+    pub fn add_root(&mut self, root: PathBuf) {
+        self.roots.insert(self.roots.len() - 1, root);
+    }
+
+    /// Where `relative` is found first, if anywhere.
+    pub fn resolve(&self, relative: impl AsRef<Path>) -> Option<PathBuf> {
+        let relative = relative.as_ref();
+        self.roots.iter().map(|root| root.join(relative)).find(|path| path.is_file())
+    }
+
+    /// Every file under the directory `relative`, by its path inside that
+    /// directory and in that order, along with where it was found. A file
+    /// in a pack hides the file at the same path in lower ones.
+    pub fn files(&self, relative: impl AsRef<Path>) -> Vec<(PathBuf, PathBuf)> {
+        let mut files = BTreeMap::new();
+        for root in self.roots.iter().rev() {
+            let dir = root.join(relative.as_ref());
+            collect_files(&dir, &dir, &mut files);
+        }
+        files.into_iter().collect()
+    }
+}
+
+fn collect_files(base: &Path, dir: &Path, files: &mut BTreeMap<PathBuf, PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if path.is_dir() {
+            collect_files(base, &path, files);
+        } else if let Ok(relative) = path.strip_prefix(base) {
+            files.insert(relative.to_path_buf(), path.clone());
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Sound effects and music. Sounds are loaded from `sounds/` in the assets
+//! at startup and music is streamed from `music/`; both are optional,
+//! and a missing file just means silence. Without an output device the
+//! game runs silently as well.
+
+mod music;
+mod registry;
+
+use std::time::Duration;
+
+use cgmath::{InnerSpace, MetricSpace, Point3, Vector3};
+use log::{info, warn};
+use rodio::{source::Spatial, OutputStream, OutputStreamHandle, Sink, Source};
+
+use crate::{assets::Assets, block::SoundGroup, config::AudioConfig};
+
+use self::{music::Music, registry::SoundRegistry};
+
+pub use self::music::MusicContext;
+
+const SOUND_DIR: &str = "sounds";
+const MUSIC_DIR: &str = "music";
+// Sounds further away than this, in blocks, aren't played at all; closer
+// ones fade out linearly towards it.
+const MAX_DISTANCE: f32 = 24.0;
+// Half the distance between the listener's ears, in blocks. Only the
+// direction to a sound is used for panning, so this sets how strongly
+// it pans.
+const EAR_OFFSET: f32 = 0.3;
+// Each play is pitched up or down by up to this fraction, so repeated
+// sounds don't drone.
+const PITCH_VARIATION: f32 = 0.1;
+// Walked distance between footsteps.
+const STEP_DISTANCE: f32 = 1.8;
+const AMBIENT_FADE: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sound {
+    Break(SoundGroup),
+    Place(SoundGroup),
+    Step(SoundGroup),
+    Eat,
+    // Lighting TNT.
+    Fuse,
+    Explosion,
+    Thunder,
+    // Coming out of a portal.
+    Portal,
+}
+
+impl Sound {
+    fn key(self) -> Option<String> {
+        let (kind, group) = match self {
+            Sound::Break(group) => ("break", group),
+            Sound::Place(group) => ("place", group),
+            Sound::Step(group) => ("step", group),
+            Sound::Eat => return Some("eat".to_string()),
+            Sound::Fuse => return Some("fuse".to_string()),
+            Sound::Explosion => return Some("explosion".to_string()),
+            Sound::Thunder => return Some("thunder".to_string()),
+            Sound::Portal => return Some("portal".to_string()),
+        };
+        (group != SoundGroup::None).then(|| format!("{kind}/{}", group.name()))
+    }
+
+    fn volume(self) -> f32 {
+        match self {
+            Sound::Step(_) => 0.4,
+            _ => 1.0,
+        }
+    }
+}
+
+/// The background loop for where the listener is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ambient {
+    Surface,
+    Cave,
+    Underwater,
+    Rain,
+}
+
+impl Ambient {
+    fn key(self) -> &'static str {
+        match self {
+            Ambient::Surface => "ambient/surface",
+            Ambient::Cave => "ambient/cave",
+            Ambient::Underwater => "ambient/underwater",
+            Ambient::Rain => "ambient/rain",
+        }
+    }
+}
+
+pub struct Audio {
+    // Dropping the stream stops all sound, so it's kept even though only
+    // the handle is used.
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    sounds: SoundRegistry,
+    settings: AudioConfig,
+    listener: Point3<f32>,
+    // Points from the listener's left ear to its right.
+    right: Vector3<f32>,
+    ambient: Option<(Ambient, Sink)>,
+    music: Music,
+    // Scales every volume; lower while the game is in the background.
+    focus_volume: f32,
+    // State of the generator used to vary sounds.
+    seed: u32,
+}
+
+impl Audio {
+    pub fn new(settings: AudioConfig, assets: &Assets) -> Self {
+        let output = match OutputStream::try_default() {
+            Ok(output) => Some(output),
+            Err(e) => {
+                warn!("no audio output, sound is disabled: {e}");
+                None
+            },
+        };
+        let sounds = if output.is_some() { SoundRegistry::load(assets, SOUND_DIR) } else { SoundRegistry::default() };
+        let music = Music::load(assets, MUSIC_DIR);
+        if output.is_some() {
+            info!("loaded {} sounds and {} music tracks", sounds.len(), music.track_count());
+        }
+
+        Self {
+            output,
+            sounds,
+            settings,
+            listener: Point3::new(0.0, 0.0, 0.0),
+            right: Vector3::unit_x(),
+            ambient: None,
+            music,
+            focus_volume: 1.0,
+            seed: 0x9e37_79b9,
+        }
+    }
+
+    /// Applies changed volumes, including to the sounds already playing.
+    pub fn set_settings(&mut self, settings: AudioConfig) {
+        self.settings = settings;
+        self.apply_volumes();
+    }
+
+    /// Turns the game down to the background volume while another window
+    /// has focus, and back up when it's back.
+    pub fn set_background(&mut self, background: bool) {
+        self.focus_volume = if background { self.settings.background_volume.clamp(0.0, 1.0) } else { 1.0 };
+        self.apply_volumes();
+    }
+
+    // Sounds already playing would otherwise keep their volume until they
+    // end, and music until the next frame it's updated on, which a paused
+    // game doesn't have.
+    fn apply_volumes(&mut self) {
+        if let Some((_, sink)) = &self.ambient {
+            sink.set_volume(self.settings.ambient() * self.focus_volume);
+        }
+        self.music.set_volume(self.settings.music() * self.focus_volume);
+    }
+
+    /// Moves the listener to the camera.
+    pub fn set_listener(&mut self, position: Point3<f32>, forward: Vector3<f32>) {
+        self.listener = position;
+        let right = forward.cross(Vector3::unit_y());
+        if right.magnitude2() > 1e-6 {
+            self.right = right.normalize();
+        }
+    }
+
+    /// Plays `sound` coming from `position`, panned to the side it's on
+    /// and quieter with distance.
+    pub fn play_at(&mut self, sound: Sound, position: Point3<f32>) {
+        let distance = self.listener.distance(position);
+        if distance >= MAX_DISTANCE {
+            return;
+        }
+        let Some(clip) = self.clip(sound) else {
+            return;
+        };
+
+        // The spatial source attenuates by the inverse square of the
+        // distance, far too quickly at the scale of blocks, so it's given
+        // only the direction and the falloff is applied here instead.
+        let direction = if distance > 1e-3 { (position - self.listener) / distance } else { Vector3::unit_z() };
+        let left_ear = -self.right * EAR_OFFSET;
+        let right_ear = self.right * EAR_OFFSET;
+        let falloff = 1.0 - distance / MAX_DISTANCE;
+        let volume = sound.volume() * self.settings.effects() * self.focus_volume * falloff * falloff;
+
+        let source = Spatial::new(clip.amplify(volume), direction.into(), left_ear.into(), right_ear.into());
+        let source = source.speed(self.pitch()).convert_samples();
+        let Some((_, handle)) = &self.output else {
+            return;
+        };
+        if let Err(e) = handle.play_raw(source) {
+            warn!("could not play a sound: {e}");
+        }
+    }
+
+    /// Switches the background loop, fading the new one in. `None` stops
+    /// it.
+    pub fn set_ambient(&mut self, ambient: Option<Ambient>) {
+        if self.ambient.as_ref().map(|(current, _)| *current) == ambient {
+            return;
+        }
+        self.ambient = None;
+
+        let (Some(ambient), Some((_, handle))) = (ambient, &self.output) else {
+            return;
+        };
+        let Some(clip) = self.sounds.get(ambient.key(), 0) else {
+            return;
+        };
+        match Sink::try_new(handle) {
+            Ok(sink) => {
+                sink.set_volume(self.settings.ambient() * self.focus_volume);
+                sink.append(clip.repeat_infinite().fade_in(AMBIENT_FADE));
+                self.ambient = Some((ambient, sink));
+            },
+            Err(e) => warn!("could not play ambient sound: {e}"),
+        }
+    }
+
+    /// Keeps music playing for `context`, crossfading to it if it
+    /// changed. Called every frame with the frame time.
+    pub fn update_music(&mut self, context: MusicContext, dt: f32) {
+        let pick = self.next_random();
+        if let Some((_, handle)) = &self.output {
+            self.music.update(handle, context, self.settings.music() * self.focus_volume, dt, pick);
+        }
+    }
+
+    fn clip(&mut self, sound: Sound) -> Option<registry::Clip> {
+        let key = sound.key()?;
+        let pick = self.next_random();
+        self.sounds.get(&key, pick)
+    }
+
+    fn pitch(&mut self) -> f32 {
+        let t = self.next_random() as f32 / u32::MAX as f32;
+        1.0 + (t * 2.0 - 1.0) * PITCH_VARIATION
+    }
+
+    // Xorshift; sound variety doesn't need anything better.
+    fn next_random(&mut self) -> u32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed
+    }
+}
+
+/// Counts the distance the player walks on the ground and says when the
+/// next footstep falls.
+#[derive(Default)]
+pub struct Footsteps {
+    last: Option<Point3<f32>>,
+    distance: f32,
+}
+
+impl Footsteps {
+    pub fn update(&mut self, position: Point3<f32>, walking: bool) -> bool {
+        let last = self.last.replace(position);
+        if !walking {
+            // The first step after landing or stopping comes half a stride in.
+            self.distance = STEP_DISTANCE * 0.5;
+            return false;
+        }
+        let Some(last) = last else {
+            return false;
+        };
+
+        self.distance += Vector3::new(position.x - last.x, 0.0, position.z - last.z).magnitude();
+        if self.distance < STEP_DISTANCE {
+            return false;
+        }
+        self.distance = 0.0;
+        true
+    }
+
+    /// How far through the current step the player is, from 0 just after
+    /// a footstep to 1 at the next.
+    pub fn stride(&self) -> f32 {
+        (self.distance / STEP_DISTANCE).min(1.0)
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use rodio::{Decoder, OutputStreamHandle, Sink};
+
+use crate::assets::Assets;
+
+// How long one track takes to fade into the next when the context changes.
+const CROSSFADE: Duration = Duration::from_secs(4);
+// Quiet between one track ending and the next starting in the same context.
+const TRACK_GAP: Duration = Duration::from_secs(30);
+
+/// What kind of music fits what the player is doing. Each has a directory
+/// of tracks under `music/` in the assets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MusicContext {
+    Menu,
+    Day,
+    Night,
+    Underground,
+}
+
+impl MusicContext {
+    const ALL: [MusicContext ; 4] = [MusicContext::Menu, MusicContext::Day, MusicContext::Night, MusicContext::Underground];
+
+    fn dir(self) -> &'static str {
+        match self {
+            MusicContext::Menu => "menu",
+            MusicContext::Day => "day",
+            MusicContext::Night => "night",
+            MusicContext::Underground => "underground",
+        }
+    }
+}
+
+struct Track {
+    path: PathBuf,
+    sink: Sink,
+    // From 0 to 1 while fading in, and back to 0 while fading out.
+    fade: f32,
+}
+
+/// Streams music tracks from disk, one at a time, crossfading when the
+/// context changes.
+pub struct Music {
+    tracks: HashMap<MusicContext, Vec<PathBuf>>,
+    context: Option<MusicContext>,
+    playing: Option<Track>,
+    fading_out: Vec<Track>,
+    // Set when a track ends by itself; nothing new starts before then.
+    next_track: Option<Instant>,
+    last_played: Option<PathBuf>,
+}
+
+impl Music {
+    pub fn load(assets: &Assets, dir: &str) -> Self {
+        let mut tracks = HashMap::new();
+        for context in MusicContext::ALL {
+            let paths = assets.files(Path::new(dir).join(context.dir()))
+                .into_iter()
+                .map(|(_, path)| path)
+                .filter(|path| path.extension().is_some_and(|ext| ext == "ogg"))
+                .collect::<Vec<_>>();
+            tracks.insert(context, paths);
+        }
+
+        Self {
+            tracks,
+            context: None,
+            playing: None,
+            fading_out: Vec::new(),
+            next_track: None,
+            last_played: None,
+        }
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.tracks.values().map(Vec::len).sum()
+    }
+
+    /// Advances fades and starts the next track when one is due. `pick`
+    /// chooses among the context's tracks.
+    pub fn update(&mut self, handle: &OutputStreamHandle, context: MusicContext, volume: f32, dt: f32, pick: u32) {
+        if self.context != Some(context) {
+            self.context = Some(context);
+            self.next_track = None;
+            self.fading_out.extend(self.playing.take());
+        }
+        if self.playing.as_ref().is_some_and(|track| track.sink.empty()) {
+            self.playing = None;
+            self.next_track = Some(Instant::now() + TRACK_GAP);
+        }
+        if self.playing.is_none() && self.next_track.is_none_or(|at| Instant::now() >= at) {
+            self.next_track = None;
+            self.playing = self.start(handle, context, pick);
+            match &self.playing {
+                Some(track) => self.last_played = Some(track.path.clone()),
+                // Don't retry a broken or missing track every frame.
+                None => self.next_track = Some(Instant::now() + TRACK_GAP),
+            }
+        }
+
+        let step = dt / CROSSFADE.as_secs_f32();
+        if let Some(track) = &mut self.playing {
+            track.fade = (track.fade + step).min(1.0);
+            track.sink.set_volume(track.fade * volume);
+        }
+        self.fading_out.retain_mut(|track| {
+            track.fade -= step;
+            track.sink.set_volume(track.fade.max(0.0) * volume);
+            track.fade > 0.0
+        });
+    }
+
+    /// Changes the volume of what's playing straight away, rather than at
+    /// the next `update`.
+    pub fn set_volume(&mut self, volume: f32) {
+        for track in self.playing.iter().chain(&self.fading_out) {
+            track.sink.set_volume(track.fade.max(0.0) * volume);
+        }
+    }
+
+    fn start(&self, handle: &OutputStreamHandle, context: MusicContext, pick: u32) -> Option<Track> {
+        let tracks = self.tracks.get(&context).filter(|tracks| !tracks.is_empty())?;
+        // Avoid playing the same track twice in a row where there's a
+        // choice.
+        let mut path = &tracks[pick as usize % tracks.len()];
+        if tracks.len() > 1 && Some(path) == self.last_played.as_ref() {
+            path = &tracks[(pick as usize + 1) % tracks.len()];
+        }
+
+        let source = File::open(path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| Decoder::new(BufReader::new(file)).map_err(|e| e.to_string()));
+        let sink = Sink::try_new(handle).map_err(|e| e.to_string());
+        match (source, sink) {
+            (Ok(source), Ok(sink)) => {
+                sink.set_volume(0.0);
+                sink.append(source);
+                Some(Track { path: path.clone(), sink, fade: 0.0 })
+            },
+            (Err(e), _) | (_, Err(e)) => {
+                warn!("could not play {}: {e}", path.display());
+                None
+            },
+        }
+    }
+}
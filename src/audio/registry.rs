@@ -0,0 +1,60 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Cursor,
+    sync::Arc,
+};
+
+use log::warn;
+use rodio::{source::Buffered, Decoder, Source};
+
+use crate::assets::Assets;
+
+pub type Clip = Buffered<Decoder<Cursor<Arc<[u8]>>>>;
+
+/// Every sound under a directory of the assets, keyed by its path without
+/// the extension or a trailing number: `step/stone1.ogg` and
+/// `step/stone2.ogg` are both variants of `step/stone`. Clips are decoded
+/// the first time they play and shared after that.
+#[derive(Default)]
+pub struct SoundRegistry {
+    sounds: HashMap<String, Vec<Clip>>,
+}
+
+impl SoundRegistry {
+    pub fn load(assets: &Assets, dir: &str) -> Self {
+        let mut registry = Self::default();
+        for (relative, path) in assets.files(dir) {
+            if !path.extension().is_some_and(|ext| ext == "ogg" || ext == "wav") {
+                continue;
+            }
+
+            let clip = fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| Decoder::new(Cursor::new(Arc::from(bytes))).map_err(|e| e.to_string()));
+            let clip = match clip {
+                Ok(decoder) => decoder.buffered(),
+                Err(e) => {
+                    warn!("could not load sound {}: {e}", path.display());
+                    continue;
+                },
+            };
+
+            let key = relative.with_extension("");
+            let key = key.to_string_lossy().replace('\\', "/");
+            let key = key.trim_end_matches(|c: char| c.is_ascii_digit());
+            registry.sounds.entry(key.to_string()).or_default().push(clip);
+        }
+        registry
+    }
+
+    pub fn len(&self) -> usize {
+        self.sounds.len()
+    }
+
+    /// One of the variants of `key`, chosen by `pick`.
+    pub fn get(&self, key: &str, pick: u32) -> Option<Clip> {
+        let variants = self.sounds.get(key)?;
+        Some(variants[pick as usize % variants.len()].clone())
+    }
+}
@@ -0,0 +1,388 @@
+//! A headless benchmark. `--bench [seconds]` flies a camera in a straight
+//! line over a fresh world for that much simulated time, at a fixed step,
+//! generating, meshing and drawing terrain offscreen the way the game does,
+//! then prints how fast each part went. The flight only depends on the
+//! settings, so runs can be compared between commits and machines.
+//!
+//! Only terrain is drawn: no sky, entities, shadows or post-processing.
+//! Without a GPU adapter, generation and meshing are still measured.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    block,
+    camera::{Camera, CameraUniform, ViewOffset},
+    chunk::ChunkPos,
+    chunk_debug::RemeshAges,
+    config::Config,
+    debug::RenderStats,
+    error::Result,
+    fog::{self, FrameUniform},
+    indirect::{ChunkDraws, DrawList, DrawMode},
+    mesher::{self, ChunkMeshBuffers, MeshPool},
+    post,
+    renderer::{FrameTargets, PassDesc, RenderGraph, Target},
+    shadow::ShadowRenderer,
+    texture::Texture,
+    world::World,
+};
+
+pub const DEFAULT_SECONDS: f32 = 30.0;
+// Simulated time per frame.
+const STEP: f32 = 1.0 / 60.0;
+// How fast the camera flies, in blocks a second; about as fast as chunks
+// stream in while flying in the game.
+const SPEED: f32 = 20.0;
+const START: [f32 ; 3] = [0.0, 100.0, 0.0];
+// Looking ahead and down at the terrain coming into view.
+const PITCH: f32 = -0.35;
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+
+#[derive(Default)]
+struct Stats {
+    generated: u32,
+    generate_time: Duration,
+    meshed: u32,
+    triangles: u64,
+    mesh_time: Duration,
+    frames: u32,
+    frame_time: Duration,
+    drawn: RenderStats,
+}
+
+/// Runs the benchmark for `seconds` of simulated time and prints the
+/// results.
+pub fn run(settings: &Config, seconds: f32) -> Result<()> {
+    let mut world = World::new(&settings.world);
+    let mut renderer = Offscreen::new(settings)?;
+    if renderer.is_none() {
+        warn!("no GPU adapter; only generation and meshing are measured");
+    }
+
+    let (radius, vertical) = (settings.graphics.render_distance, settings.graphics.vertical_distance);
+    let mut camera = Camera {
+        eye: START.into(),
+        yaw: cgmath::Rad(0.0),
+        pitch: cgmath::Rad(PITCH),
+        up: cgmath::Vector3::unit_y(),
+        aspect: WIDTH as f32 / HEIGHT as f32,
+        fov: settings.graphics.fov,
+        near: 0.1,
+        far: 1000.0,
+        offset: ViewOffset::default(),
+    };
+    let direction = cgmath::Vector3::new(1.0, 0.0, 0.0);
+    let mut chunk_buffers = HashMap::new();
+    let mut stats = Stats::default();
+
+    let frames = (seconds / STEP).ceil() as u32;
+    for _ in 0..frames {
+        let start = Instant::now();
+        camera.eye += direction * SPEED * STEP;
+        let [x, y, z] = [camera.eye.x, camera.eye.y, camera.eye.z].map(|v| v.floor() as i32);
+        stream(&mut world, &mut chunk_buffers, renderer.as_mut(), ChunkPos::from_block(x, y, z), (radius, vertical), &mut stats);
+
+        for pos in world.take_dirty() {
+            let mesh_start = Instant::now();
+            let meshes = mesher::mesh_chunk(&world, pos, settings.graphics.smooth_lighting);
+            stats.mesh_time += mesh_start.elapsed();
+            stats.meshed += 1;
+            let Some(meshes) = meshes else {
+                continue;
+            };
+            stats.triangles += [&meshes.opaque, &meshes.cutout, &meshes.translucent]
+                .iter()
+                .map(|mesh| mesh.indices.len() as u64 / 3)
+                .sum::<u64>();
+            if let Some(renderer) = &mut renderer {
+                let buffers = meshes.upload(&renderer.device, &renderer.queue, &mut renderer.mesh_pool);
+                if let Some(old) = chunk_buffers.insert(pos, buffers) {
+                    renderer.mesh_pool.free(old);
+                }
+            }
+        }
+
+        if let Some(renderer) = &mut renderer {
+            let drawn = renderer.render(&camera, &chunk_buffers);
+            stats.drawn.draw_calls += drawn.draw_calls;
+            stats.drawn.vertices += drawn.vertices;
+        }
+        stats.frame_time += start.elapsed();
+        stats.frames += 1;
+    }
+
+    print_results(&stats, frames as f32 * STEP, renderer.is_some());
+    Ok(())
+}
+
+// Generates the chunks within `radius` across and `vertical` up or down
+// of `center` that are missing and drops those past them, like the game
+// streaming around the player.
+fn stream(
+    world: &mut World,
+    chunk_buffers: &mut HashMap<ChunkPos, ChunkMeshBuffers>,
+    mut renderer: Option<&mut Offscreen>,
+    center: ChunkPos,
+    (radius, vertical): (i32, i32),
+    stats: &mut Stats,
+) {
+    let out_of_range = world.chunks.keys().copied()
+        .filter(|&pos| {
+            let (across, up) = pos.distance(center);
+            across > radius + 1 || up > vertical + 1
+        })
+        .collect::<Vec<_>>();
+    for pos in out_of_range {
+        world.unload_chunk(pos);
+        if let Some(old) = chunk_buffers.remove(&pos) {
+            if let Some(renderer) = &mut renderer {
+                renderer.mesh_pool.free(old);
+            }
+        }
+    }
+
+    for pos in center.around(radius, vertical) {
+        if world.chunks.contains_key(&pos) {
+            continue;
+        }
+        let start = Instant::now();
+        world.generate_chunk(pos);
+        stats.generate_time += start.elapsed();
+        stats.generated += 1;
+    }
+}
+
+fn per_second(count: f64, time: Duration) -> f64 {
+    count / time.as_secs_f64().max(f64::EPSILON)
+}
+
+fn print_results(stats: &Stats, seconds: f32, rendered: bool) {
+    println!("benchmark: {seconds:.1} s simulated in {} frames", stats.frames);
+    println!(
+        "  generation: {} chunks in {:.2} s, {:.1} chunks/s",
+        stats.generated,
+        stats.generate_time.as_secs_f32(),
+        per_second(stats.generated as f64, stats.generate_time),
+    );
+    println!(
+        "  meshing:    {} chunks in {:.2} s, {:.1} chunks/s, {:.0} triangles/s",
+        stats.meshed,
+        stats.mesh_time.as_secs_f32(),
+        per_second(stats.meshed as f64, stats.mesh_time),
+        per_second(stats.triangles as f64, stats.mesh_time),
+    );
+    let frames = stats.frames.max(1) as f32;
+    let average = stats.frame_time.as_secs_f32() * 1000.0 / frames;
+    println!("  frames:     {average:.2} ms average, {:.1} fps", 1000.0 / average.max(f32::EPSILON));
+    if rendered {
+        println!(
+            "  drawn:      {:.0} draw calls and {:.0} vertices a frame",
+            stats.drawn.draw_calls as f32 / frames,
+            stats.drawn.vertices as f32 / frames,
+        );
+    }
+}
+
+// The terrain pipelines drawing into a texture instead of a window.
+struct Offscreen {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipelines: [wgpu::RenderPipeline ; 3],
+    block_bind_group: wgpu::BindGroup,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    frame_uniform: FrameUniform,
+    frame_buffer: wgpu::Buffer,
+    frame_bind_group: wgpu::BindGroup,
+    shadows: ShadowRenderer,
+    chunk_draws: ChunkDraws,
+    mesh_pool: MeshPool,
+    color: wgpu::TextureView,
+    depth: wgpu::TextureView,
+}
+
+impl Offscreen {
+    // `None` if there's no adapter to render with.
+    fn new(settings: &Config) -> Result<Option<Self>> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::GL,
+            ..Default::default()
+        });
+        let Some(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        })) else {
+            return Ok(None);
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                required_features: adapter.features() & DrawMode::optional_features(),
+                required_limits: wgpu::Limits::default(),
+                label: None,
+                memory_hints: Default::default(),
+            },
+            None,
+        ))?;
+
+        let block_textures = block::registry().textures.iter()
+            .map(|(_, bytes)| bytes.as_slice())
+            .collect::<Vec<_>>();
+        let diffuse_texture = Texture::from_layers(&device, &queue, &block_textures, "block_textures")?;
+
+        let block_layout = crate::create_block_texture_bind_group_layout(&device);
+        let camera_layout = crate::create_camera_bind_group_layout(&device);
+        let frame_layout = crate::create_frame_bind_group_layout(&device);
+        let shadows = ShadowRenderer::new(&device, &block_layout, false, settings.graphics.shadow_map_size);
+
+        let block_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Benchmark Block Bind Group"),
+            layout: &block_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&diffuse_texture.view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler) },
+            ],
+        });
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Benchmark Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Benchmark Camera Bind Group"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: camera_buffer.as_entire_binding() }],
+        });
+        let frame_uniform = FrameUniform::new(settings.graphics.fog, settings.graphics.render_distance);
+        let frame_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Benchmark Frame Buffer"),
+            contents: bytemuck::cast_slice(&[frame_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // Nothing is tinted, but the shader still reads the remesh ages.
+        let remesh_ages = RemeshAges::new(&device);
+        let frame_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Benchmark Frame Bind Group"),
+            layout: &frame_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: frame_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&remesh_ages.view) },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Benchmark Pipeline Layout"),
+            bind_group_layouts: &[&block_layout, &camera_layout, &frame_layout, &shadows.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let format = post::scene_format(&adapter);
+        let (pipelines, _) = crate::create_terrain_pipelines(&device, &layout, &shader, format, 1);
+
+        let target = |format, label| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d { width: WIDTH, height: HEIGHT, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            }).create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let color = target(format, "Benchmark Color Target");
+        let depth = target(Texture::DEPTH_FORMAT, "Benchmark Depth Target");
+
+        let chunk_draws = ChunkDraws::new(&device, DrawMode::detect(&adapter, &device), None);
+
+        Ok(Some(Self {
+            device,
+            queue,
+            pipelines,
+            block_bind_group,
+            camera_buffer,
+            camera_bind_group,
+            frame_uniform,
+            frame_buffer,
+            frame_bind_group,
+            shadows,
+            chunk_draws,
+            mesh_pool: MeshPool::new(),
+            color,
+            depth,
+        }))
+    }
+
+    // Draws the loaded chunks from `camera` and waits for the GPU to
+    // finish, so the frame's time includes it.
+    fn render(&mut self, camera: &Camera, chunk_buffers: &HashMap<ChunkPos, ChunkMeshBuffers>) -> RenderStats {
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(camera);
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+        self.frame_uniform.update_camera(camera);
+        self.queue.write_buffer(&self.frame_buffer, 0, bytemuck::cast_slice(&[self.frame_uniform]));
+        self.chunk_draws.prepare(
+            &self.device,
+            &self.queue,
+            chunk_buffers,
+            &HashMap::new(),
+            &camera.frustum(),
+            camera.eye,
+        );
+
+        let [opaque, cutout, translucent] = &self.pipelines;
+        let bind = |pass: &mut wgpu::RenderPass| {
+            pass.set_bind_group(0, &self.block_bind_group, &[]);
+            pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            pass.set_bind_group(2, &self.frame_bind_group, &[]);
+            pass.set_bind_group(3, &self.shadows.bind_group, &[]);
+        };
+        let [r, g, b] = fog::FOG_COLOR.map(f64::from);
+        let mut graph = RenderGraph::default();
+        graph.add_pass(
+            PassDesc::new("Opaque Pass")
+                .color(Target::Scene, wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a: 1.0 }))
+                .depth(Target::Depth, wgpu::LoadOp::Clear(1.0)),
+            |pass, stats| {
+                pass.set_pipeline(opaque);
+                bind(pass);
+                self.chunk_draws.draw(pass, &self.mesh_pool, DrawList::Opaque, stats);
+                pass.set_pipeline(cutout);
+                self.chunk_draws.draw(pass, &self.mesh_pool, DrawList::Cutout, stats);
+            },
+        );
+        graph.add_pass(
+            PassDesc::new("Transparent Pass")
+                .color(Target::Scene, wgpu::LoadOp::Load)
+                .depth(Target::Depth, wgpu::LoadOp::Load),
+            |pass, stats| {
+                pass.set_pipeline(translucent);
+                bind(pass);
+                self.chunk_draws.draw(pass, &self.mesh_pool, DrawList::Translucent, stats);
+            },
+        );
+
+        let targets = FrameTargets {
+            surface: &self.color,
+            scene: &self.color,
+            scene_msaa: None,
+            depth: &self.depth,
+            shadows: &self.shadows,
+        };
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Benchmark Encoder"),
+        });
+        let stats = graph.execute(&mut encoder, &targets, None);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.device.poll(wgpu::Maintain::Wait);
+        stats
+    }
+}
@@ -0,0 +1,305 @@
+mod registry;
+mod shape;
+mod state;
+
+use serde::{Deserialize, Serialize};
+
+pub use self::{
+    registry::{load as load_registry, registry, BlockDef},
+    shape::{Cuboid, BED_HEIGHT},
+    state::{Axis, BlockState, Facing, Half, Properties, Property},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Face {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl Face {
+    pub const ALL: [Face ; 6] = [
+        Face::PosX, Face::NegX,
+        Face::PosY, Face::NegY,
+        Face::PosZ, Face::NegZ,
+    ];
+
+    pub fn normal(self) -> [i32 ; 3] {
+        match self {
+            Face::PosX => [1, 0, 0],
+            Face::NegX => [-1, 0, 0],
+            Face::PosY => [0, 1, 0],
+            Face::NegY => [0, -1, 0],
+            Face::PosZ => [0, 0, 1],
+            Face::NegZ => [0, 0, -1],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlockId(pub u16);
+
+impl BlockId {
+    pub const AIR: BlockId          = BlockId(0);
+    pub const GRASS: BlockId        = BlockId(1);
+    pub const DIRT: BlockId         = BlockId(2);
+    pub const STONE: BlockId        = BlockId(3);
+    pub const COBBLESTONE: BlockId  = BlockId(4);
+    pub const PLANKS: BlockId       = BlockId(5);
+    pub const LOG: BlockId          = BlockId(6);
+    pub const LEAVES: BlockId       = BlockId(7);
+    pub const SAND: BlockId         = BlockId(8);
+    pub const BRICKS: BlockId       = BlockId(9);
+    pub const TALL_GRASS: BlockId   = BlockId(10);
+    pub const FLOWER_RED: BlockId   = BlockId(11);
+    pub const FLOWER_YELLOW: BlockId = BlockId(12);
+    pub const WATER: BlockId        = BlockId(13);
+    pub const STONE_SLAB: BlockId   = BlockId(14);
+    pub const PLANKS_SLAB: BlockId  = BlockId(15);
+    pub const COBBLESTONE_STAIRS: BlockId = BlockId(16);
+    pub const PLANKS_STAIRS: BlockId = BlockId(17);
+    pub const FENCE: BlockId        = BlockId(18);
+    pub const BERRY_BUSH: BlockId   = BlockId(19);
+    pub const CRAFTING_TABLE: BlockId = BlockId(20);
+    pub const CHEST: BlockId        = BlockId(21);
+    pub const FURNACE: BlockId      = BlockId(22);
+    pub const PORKCHOP: BlockId     = BlockId(23);
+    pub const TNT: BlockId          = BlockId(24);
+    pub const TORCH: BlockId        = BlockId(25);
+    pub const GRAVEL: BlockId       = BlockId(26);
+
+    pub fn info(self) -> &'static BlockInfo {
+        &registry().blocks[self.0 as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<BlockId> {
+        registry().blocks.iter()
+            .position(|info| info.name == name)
+            .map(|index| BlockId(index as u16))
+    }
+
+    pub fn is_air(self) -> bool {
+        self == BlockId::AIR
+    }
+
+    pub fn is_solid(self) -> bool {
+        self.info().solid
+    }
+
+    pub fn is_fluid(self) -> bool {
+        self.info().model == BlockModel::Fluid
+    }
+
+    // Whether the block fully hides the faces of its neighbors.
+    pub fn is_opaque(self) -> bool {
+        let info = self.info();
+        info.model == BlockModel::Cube && info.layer == RenderLayer::Opaque
+    }
+
+    pub fn is_climbable(self) -> bool {
+        self.info().model == BlockModel::Ladder
+    }
+
+    pub fn is_bed(self) -> bool {
+        self.info().model == BlockModel::Bed
+    }
+
+    // Whether it's a lever or button, which using switches on.
+    pub fn is_switch(self) -> bool {
+        matches!(self.info().model, BlockModel::Lever | BlockModel::Button)
+    }
+
+    // Whether it takes part in signal circuits: wire, a switch, or a block
+    // that responds to signals.
+    pub fn carries_signals(self) -> bool {
+        self.info().model == BlockModel::Wire || self.is_switch() || self.info().properties.contains(&Property::Powered)
+    }
+
+    // Whether using it opens and closes it, like a door.
+    pub fn opens(self) -> bool {
+        self.info().properties.contains(&Property::Open)
+    }
+
+    // Fences join each other, gates and any full block beside them.
+    pub fn joins_fences(self) -> bool {
+        matches!(self.info().model, BlockModel::Fence | BlockModel::Gate) || self.is_opaque()
+    }
+
+    pub fn texture(self, face: Face) -> u32 {
+        let textures = &self.info().textures;
+        match face {
+            Face::PosY => textures.top,
+            Face::NegY => textures.bottom,
+            _ => textures.side,
+        }
+    }
+}
+
+// Saved by name so reordering the registry doesn't corrupt saves.
+impl Serialize for BlockId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.info().name)
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        BlockId::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown block `{name}`")))
+    }
+}
+
+pub struct FaceTextures {
+    pub top: u32,
+    pub side: u32,
+    pub bottom: u32,
+    // The side a block with the Facing property looks out of, normally and
+    // while lit. Lit blocks without Facing show the lit one on every side.
+    pub front: u32,
+    pub front_lit: u32,
+}
+
+impl FaceTextures {
+    fn all(texture: u32) -> Self {
+        Self { top: texture, side: texture, bottom: texture, front: texture, front_lit: texture }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum BlockModel {
+    None,
+    Cube,
+    // Two diagonal quads crossing in the middle of the block, used for
+    // plants.
+    Cross,
+    // A thin stick standing in the middle of the block.
+    Torch,
+    // A cube whose top face sits at a height given by the fluid level.
+    Fluid,
+    // Half a block, in the bottom or top half.
+    Slab,
+    // A slab with a step on the half away from the way it faces.
+    Stairs,
+    // A post with rails joining neighbouring fences and blocks.
+    Fence,
+    // A thin panel against the side opposite the way it faces, which
+    // entities inside the block climb.
+    Ladder,
+    // A low platform to sleep on, which sets where the player respawns.
+    Bed,
+    // A thin panel that swings aside when opened. Doors are two blocks
+    // tall, a bottom and top half that open and close together.
+    Door,
+    // A thin hatch in the bottom or top of the block that swings up
+    // against its back when opened.
+    Trapdoor,
+    // A stretch of fence across the block that can be opened to walk
+    // through.
+    Gate,
+    // A flat trace on the ground that carries signals.
+    Wire,
+    // A switch with a handle, on the floor or against the side it faces
+    // out from, that stays on or off.
+    Lever,
+    // A small switch, placed like a lever, that stays on for a moment
+    // after it's pressed.
+    Button,
+    // A block that pushes what's in front of it along when powered, and
+    // with its head out while it is.
+    Piston,
+    // The plate and rod in front of an extended piston.
+    PistonHead,
+    // Holds the place a piston is moving a block into until it arrives;
+    // the block itself is drawn sliding there.
+    Moving,
+    // A glowing sheet across the block, which carries whoever stands in it
+    // to another dimension.
+    Portal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum RenderLayer {
+    Opaque,
+    // Alpha tested: fully transparent texels are discarded.
+    Cutout,
+    // Alpha blended and drawn last, without writing depth.
+    Translucent,
+}
+
+/// Which set of break, place and footstep sounds a block uses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum SoundGroup {
+    None,
+    Stone,
+    Wood,
+    Grass,
+    Dirt,
+    Sand,
+    Plant,
+    Water,
+}
+
+impl SoundGroup {
+    pub fn name(self) -> &'static str {
+        match self {
+            SoundGroup::None => "none",
+            SoundGroup::Stone => "stone",
+            SoundGroup::Wood => "wood",
+            SoundGroup::Grass => "grass",
+            SoundGroup::Dirt => "dirt",
+            SoundGroup::Sand => "sand",
+            SoundGroup::Plant => "plant",
+            SoundGroup::Water => "water",
+        }
+    }
+}
+
+/// A block's definition, as loaded from the game data files.
+pub struct BlockInfo {
+    pub name: String,
+    pub solid: bool,
+    pub model: BlockModel,
+    pub layer: RenderLayer,
+    pub textures: FaceTextures,
+    pub sound: SoundGroup,
+    // The properties placed copies of the block can vary by.
+    pub properties: Vec<Property>,
+    // Roughly how many seconds it takes to break by hand.
+    pub hardness: f32,
+    // How much of an explosion's strength it soaks up before breaking.
+    pub resistance: f32,
+    // What breaking it leaves behind, if anything.
+    pub drops: Option<BlockId>,
+    // Half drumsticks eating it restores; 0 if it isn't food.
+    pub food: u32,
+    // Seconds it keeps a furnace burning; 0 if it doesn't burn.
+    pub fuel: f32,
+    // The red, green and blue light it gives off, each up to
+    // `light::MAX_LIGHT`.
+    pub light: [u8 ; 3],
+    // How many light levels passing through it lose on top of the usual
+    // one per block; `light::MAX_LIGHT` stops light altogether.
+    pub opacity: u8,
+    // Whether it falls when there's nothing under it, like sand.
+    pub falls: bool,
+}
+
+pub const ATLAS_COLUMNS: u32 = 8;
+
+pub fn atlas_rows() -> u32 {
+    (registry().textures.len() as u32).div_ceil(ATLAS_COLUMNS)
+}
+
+// Returns the min and max texture coordinates of an atlas tile.
+pub fn atlas_uv(tile: u32) -> ([f32 ; 2], [f32 ; 2]) {
+    let tile_w = 1.0 / ATLAS_COLUMNS as f32;
+    let tile_h = 1.0 / atlas_rows() as f32;
+    let u = (tile % ATLAS_COLUMNS) as f32 * tile_w;
+    let v = (tile / ATLAS_COLUMNS) as f32 * tile_h;
+
+    ([u, v], [u + tile_w, v + tile_h])
+}
@@ -0,0 +1,414 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use log::info;
+use serde::Deserialize;
+
+use crate::{
+    animation::AnimationDef,
+    assets::Assets,
+    error::{EngineError, Result},
+    light::MAX_LIGHT,
+};
+
+use super::{BlockId, BlockInfo, BlockModel, FaceTextures, Property, RenderLayer, SoundGroup};
+
+const DATA_DIR: &str = "data";
+const BLOCKS_FILE: &str = "blocks.ron";
+const TEXTURE_DIR: &str = "textures/block";
+// Textures for mobs and other models, which share the blocks' texture
+// array so they draw through the same pipelines.
+const ENTITY_TEXTURE_DIR: &str = "textures/entity";
+// Textures for particles that aren't pieces of a block, like smoke.
+const PARTICLE_TEXTURE_DIR: &str = "textures/particle";
+
+// The blocks the code names through `BlockId`'s constants, which have to
+// be defined first and in this order for those ids to line up.
+const BUILT_IN: &[(BlockId, &str)] = &[
+    (BlockId::AIR, "air"),
+    (BlockId::GRASS, "grass"),
+    (BlockId::DIRT, "dirt"),
+    (BlockId::STONE, "stone"),
+    (BlockId::COBBLESTONE, "cobblestone"),
+    (BlockId::PLANKS, "planks"),
+    (BlockId::LOG, "log"),
+    (BlockId::LEAVES, "leaves"),
+    (BlockId::SAND, "sand"),
+    (BlockId::BRICKS, "bricks"),
+    (BlockId::TALL_GRASS, "tall_grass"),
+    (BlockId::FLOWER_RED, "red_flower"),
+    (BlockId::FLOWER_YELLOW, "yellow_flower"),
+    (BlockId::WATER, "water"),
+    (BlockId::STONE_SLAB, "stone_slab"),
+    (BlockId::PLANKS_SLAB, "planks_slab"),
+    (BlockId::COBBLESTONE_STAIRS, "cobblestone_stairs"),
+    (BlockId::PLANKS_STAIRS, "planks_stairs"),
+    (BlockId::FENCE, "fence"),
+    (BlockId::BERRY_BUSH, "berry_bush"),
+    (BlockId::CRAFTING_TABLE, "crafting_table"),
+    (BlockId::CHEST, "chest"),
+    (BlockId::FURNACE, "furnace"),
+    (BlockId::PORKCHOP, "porkchop"),
+    (BlockId::TNT, "tnt"),
+    (BlockId::TORCH, "torch"),
+    (BlockId::GRAVEL, "gravel"),
+];
+
+// Saves store names behind a one-byte length.
+const MAX_NAME_LENGTH: usize = u8::MAX as usize;
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// Every block definition and the textures they use, loaded once at
+/// startup from the `data` directory of the assets.
+pub struct Registry {
+    pub blocks: Vec<BlockInfo>,
+    /// Where each texture came from within the assets and its PNG bytes,
+    /// in layer order.
+    pub textures: Vec<(PathBuf, Vec<u8>)>,
+    /// How the textures with a `.ron` file beside them animate, by layer.
+    pub animations: HashMap<u32, AnimationDef>,
+    // Layers of the entity textures, by file name without the `.png`.
+    entity_textures: HashMap<String, u32>,
+    particle_textures: HashMap<String, u32>,
+}
+
+impl Registry {
+    /// The layer of the entity texture called `name`, if there is one.
+    pub fn entity_texture(&self, name: &str) -> Option<u32> {
+        self.entity_textures.get(name).copied()
+    }
+
+    /// The layer of the particle texture called `name`, if there is one.
+    pub fn particle_texture(&self, name: &str) -> Option<u32> {
+        self.particle_textures.get(name).copied()
+    }
+
+    /// A hash of every block's name, items' included, in id order. Two
+    /// games with the same one agree on which block each id is.
+    pub fn names_hash(&self) -> u64 {
+        // FNV-1a, which unlike the standard hasher is stable across
+        // versions, with a zero byte after each name so they can't run
+        // together.
+        self.blocks.iter()
+            .flat_map(|info| info.name.bytes().chain([0]))
+            .fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+    }
+}
+
+/// The loaded registry. Panics if `load` hasn't succeeded yet, as nothing
+/// about blocks can be answered without it.
+pub fn registry() -> &'static Registry {
+    REGISTRY.get().expect("block registry used before it was loaded")
+}
+
+/// Reads the block definitions and their textures, then adds `extra`,
+/// the ones mods registered, each with the script it came from. Does
+/// nothing if they were already loaded.
+pub fn load(assets: &Assets, extra: Vec<(PathBuf, BlockDef)>) -> Result<()> {
+    if REGISTRY.get().is_some() {
+        return Ok(());
+    }
+    let registry = Registry::load(assets, extra)?;
+    info!("loaded {} blocks and {} textures", registry.blocks.len(), registry.textures.len());
+    let _ = REGISTRY.set(registry);
+    Ok(())
+}
+
+/// A block as the game data files and mods describe it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockDef {
+    name: String,
+    #[serde(default = "default_solid")]
+    solid: bool,
+    #[serde(default = "default_model")]
+    model: BlockModel,
+    #[serde(default = "default_layer")]
+    layer: RenderLayer,
+    #[serde(default)]
+    textures: Option<TextureDef>,
+    #[serde(default = "default_sound")]
+    sound: SoundGroup,
+    #[serde(default)]
+    properties: Vec<Property>,
+    #[serde(default = "default_hardness")]
+    hardness: f32,
+    // Defaults to the hardness.
+    #[serde(default)]
+    resistance: Option<f32>,
+    #[serde(default)]
+    drops: DropDef,
+    #[serde(default)]
+    food: u32,
+    #[serde(default)]
+    fuel: f32,
+    #[serde(default)]
+    light: LightDef,
+    // Defaults to stopping light for opaque cubes and letting it through
+    // for everything else.
+    #[serde(default)]
+    opacity: Option<u8>,
+    #[serde(default)]
+    falls: bool,
+}
+
+fn default_solid() -> bool {
+    true
+}
+
+fn default_model() -> BlockModel {
+    BlockModel::Cube
+}
+
+fn default_layer() -> RenderLayer {
+    RenderLayer::Opaque
+}
+
+fn default_sound() -> SoundGroup {
+    SoundGroup::None
+}
+
+fn default_hardness() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+enum TextureDef {
+    All(String),
+    Faces {
+        top: String,
+        side: String,
+        bottom: String,
+        #[serde(default)]
+        front: Option<String>,
+        #[serde(default)]
+        front_lit: Option<String>,
+    },
+}
+
+// A single level is white light; tinted light gives red, green and blue.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(untagged)]
+enum LightDef {
+    Level(u8),
+    Color(u8, u8, u8),
+}
+
+impl Default for LightDef {
+    fn default() -> Self {
+        LightDef::Level(0)
+    }
+}
+
+impl LightDef {
+    fn color(self) -> [u8 ; 3] {
+        match self {
+            LightDef::Level(level) => [level ; 3],
+            LightDef::Color(red, green, blue) => [red, green, blue],
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+enum DropDef {
+    #[default]
+    Itself,
+    Nothing,
+    Block(String),
+}
+
+impl Registry {
+    fn load(assets: &Assets, extra: Vec<(PathBuf, BlockDef)>) -> Result<Self> {
+        // Lets optional fields be written without wrapping them in `Some`.
+        let options = ron::Options::default().with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME);
+
+        let mut defs = Vec::new();
+        for path in data_files(assets)? {
+            let text = fs::read_to_string(&path).map_err(|source| EngineError::Io { path: path.clone(), source })?;
+            let file_defs: Vec<BlockDef> = options.from_str(&text).map_err(|e| invalid(&path, e.to_string()))?;
+            defs.extend(file_defs.into_iter().map(|def| (path.clone(), def)));
+        }
+        defs.extend(extra);
+        Self::from_defs(assets, defs)
+    }
+
+    fn from_defs(assets: &Assets, defs: Vec<(PathBuf, BlockDef)>) -> Result<Self> {
+        if defs.len() > u16::MAX as usize {
+            return Err(invalid(Path::new(DATA_DIR), format!("{} blocks is more than the {} allowed", defs.len(), u16::MAX)));
+        }
+
+        let mut ids = HashMap::new();
+        for (index, (path, def)) in defs.iter().enumerate() {
+            if def.name.is_empty() || def.name.len() > MAX_NAME_LENGTH {
+                return Err(invalid(path, format!("block names must be 1 to {MAX_NAME_LENGTH} bytes long, not `{}`", def.name)));
+            }
+            if ids.insert(def.name.as_str(), BlockId(index as u16)).is_some() {
+                return Err(invalid(path, format!("block `{}` is defined twice", def.name)));
+            }
+        }
+        for &(id, name) in BUILT_IN {
+            let found = defs.get(id.0 as usize).map(|(_, def)| def.name.as_str());
+            if found != Some(name) {
+                let path = defs.first().map_or_else(|| Path::new(DATA_DIR).join(BLOCKS_FILE), |(path, _)| path.clone());
+                return Err(invalid(&path, format!(
+                    "block {} must be `{name}`, found {}; the built-in blocks have to come first and in order",
+                    id.0,
+                    found.map_or("nothing".to_string(), |found| format!("`{found}`")),
+                )));
+            }
+        }
+
+        let mut textures = Vec::new();
+        let mut texture_ids = HashMap::new();
+        let mut texture = |path: &Path, name: &str| -> Result<u32> {
+            if let Some(&id) = texture_ids.get(name) {
+                return Ok(id);
+            }
+            let relative = texture_path(name);
+            let file = assets.resolve(&relative)
+                .ok_or_else(|| invalid(path, format!("texture `{name}` not found at {}", relative.display())))?;
+            let bytes = fs::read(&file)
+                .map_err(|e| invalid(path, format!("texture `{name}` could not be read from {}: {e}", file.display())))?;
+            let id = textures.len() as u32;
+            textures.push((relative, bytes));
+            texture_ids.insert(name.to_string(), id);
+            Ok(id)
+        };
+
+        let mut blocks = Vec::with_capacity(defs.len());
+        for (path, def) in &defs {
+            let textures = match &def.textures {
+                Some(TextureDef::All(name)) => FaceTextures::all(texture(path, name)?),
+                Some(TextureDef::Faces { top, side, bottom, front, front_lit }) => {
+                    let side = texture(path, side)?;
+                    let front = front.as_deref().map_or(Ok(side), |front| texture(path, front))?;
+                    FaceTextures {
+                        top: texture(path, top)?,
+                        side,
+                        bottom: texture(path, bottom)?,
+                        front,
+                        front_lit: front_lit.as_deref().map_or(Ok(front), |front_lit| texture(path, front_lit))?,
+                    }
+                },
+                None if matches!(def.model, BlockModel::None) => FaceTextures::all(0),
+                None => return Err(invalid(path, format!("block `{}` is drawn but has no textures", def.name))),
+            };
+            let drops = match &def.drops {
+                DropDef::Itself => Some(ids[def.name.as_str()]),
+                DropDef::Nothing => None,
+                DropDef::Block(name) => Some(*ids.get(name.as_str()).ok_or_else(|| {
+                    invalid(path, format!("block `{}` drops `{name}`, which isn't a block", def.name))
+                })?),
+            };
+            if let Some(repeated) = def.properties.iter().enumerate().find_map(|(i, p)| def.properties[..i].contains(p).then_some(p)) {
+                return Err(invalid(path, format!("block `{}` lists the {repeated:?} property twice", def.name)));
+            }
+            if !def.hardness.is_finite() || def.hardness < 0.0 {
+                return Err(invalid(path, format!("block `{}` has a hardness of {}", def.name, def.hardness)));
+            }
+            let resistance = def.resistance.unwrap_or(def.hardness);
+            if !resistance.is_finite() || resistance < 0.0 {
+                return Err(invalid(path, format!("block `{}` has a blast resistance of {resistance}", def.name)));
+            }
+            if !def.fuel.is_finite() || def.fuel < 0.0 {
+                return Err(invalid(path, format!("block `{}` burns for {} seconds", def.name, def.fuel)));
+            }
+            let opaque = def.model == BlockModel::Cube && def.layer == RenderLayer::Opaque;
+            let opacity = def.opacity.unwrap_or(if opaque { MAX_LIGHT } else { 0 });
+            let light = def.light.color();
+            for (what, level) in light.iter().map(|&level| ("light", level)).chain([("opacity", opacity)]) {
+                if level > MAX_LIGHT {
+                    return Err(invalid(path, format!("block `{}` has a {what} of {level}, more than {MAX_LIGHT}", def.name)));
+                }
+            }
+
+            blocks.push(BlockInfo {
+                name: def.name.clone(),
+                solid: def.solid,
+                model: def.model,
+                layer: def.layer,
+                textures,
+                sound: def.sound,
+                properties: def.properties.clone(),
+                hardness: def.hardness,
+                resistance,
+                drops,
+                food: def.food,
+                fuel: def.fuel,
+                light,
+                opacity,
+                falls: def.falls,
+            });
+        }
+
+        let entity_textures = named_textures(assets, ENTITY_TEXTURE_DIR, &mut textures)?;
+        let particle_textures = named_textures(assets, PARTICLE_TEXTURE_DIR, &mut textures)?;
+        let animations = texture_animations(assets, &textures)?;
+
+        Ok(Self { blocks, textures, animations, entity_textures, particle_textures })
+    }
+}
+
+// Reads the `.ron` file beside each texture that has one, which says how
+// it animates.
+fn texture_animations(assets: &Assets, textures: &[(PathBuf, Vec<u8>)]) -> Result<HashMap<u32, AnimationDef>> {
+    let mut animations = HashMap::new();
+    for (layer, (relative, _)) in (0..).zip(textures) {
+        let Some(path) = assets.resolve(relative.with_extension("ron")) else {
+            continue;
+        };
+        let text = fs::read_to_string(&path).map_err(|source| EngineError::Io { path: path.clone(), source })?;
+        let def = ron::from_str(&text).map_err(|e| invalid(&path, e.to_string()))?;
+        animations.insert(layer, def);
+    }
+    Ok(animations)
+}
+
+// Appends every PNG directly inside `dir` to `textures`, returning their
+// layers by file name without the `.png`.
+fn named_textures(assets: &Assets, dir: &str, textures: &mut Vec<(PathBuf, Vec<u8>)>) -> Result<HashMap<String, u32>> {
+    let mut layers = HashMap::new();
+    for (relative, file) in assets.files(dir) {
+        if relative.extension().is_none_or(|ext| ext != "png") || relative.parent() != Some(Path::new("")) {
+            continue;
+        }
+        let Some(name) = relative.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let bytes = fs::read(&file).map_err(|source| EngineError::Io { path: file.clone(), source })?;
+        layers.insert(name.to_string(), textures.len() as u32);
+        textures.push((Path::new(dir).join(&relative), bytes));
+    }
+    Ok(layers)
+}
+
+// The block files to read: `BLOCKS_FILE` first, which has to exist, then
+// any others in name order. Files in subdirectories are other data.
+fn data_files(assets: &Assets) -> Result<Vec<PathBuf>> {
+    let mut blocks_file = None;
+    let mut others = Vec::new();
+    for (relative, path) in assets.files(DATA_DIR) {
+        if relative == Path::new(BLOCKS_FILE) {
+            blocks_file = Some(path);
+        } else if relative.extension().is_some_and(|ext| ext == "ron") && relative.parent() == Some(Path::new("")) {
+            others.push(path);
+        }
+    }
+
+    let blocks_file = blocks_file.ok_or_else(|| invalid(&Path::new(DATA_DIR).join(BLOCKS_FILE), "file not found".to_string()))?;
+    Ok(std::iter::once(blocks_file).chain(others).collect())
+}
+
+// Where the block texture called `name` lives inside the assets.
+fn texture_path(name: &str) -> PathBuf {
+    Path::new(TEXTURE_DIR).join(format!("{name}.png"))
+}
+
+fn invalid(path: &Path, reason: String) -> EngineError {
+    EngineError::GameData { path: path.to_path_buf(), reason }
+}
@@ -0,0 +1,266 @@
+use super::{
+    state::{Axis, Facing, Half},
+    BlockModel, BlockState, Face,
+};
+
+// Fence parts, in sixteenths of a block across.
+const FENCE_POST: [f32 ; 2] = [6.0, 10.0];
+const FENCE_RAIL: [f32 ; 2] = [7.0, 9.0];
+const FENCE_RAIL_HEIGHTS: [[f32 ; 2] ; 2] = [[6.0, 9.0], [12.0, 15.0]];
+// Fences are taller to bump into than they look, so they can't be jumped.
+const FENCE_COLLISION_HEIGHT: f32 = 1.5;
+const TORCH_WIDTH: [f32 ; 2] = [7.0, 9.0];
+const TORCH_HEIGHT: f32 = 10.0;
+// How far a ladder stands out from the block it hangs on.
+const LADDER_DEPTH: f32 = 1.0;
+// How thick doors and trapdoors are.
+const DOOR_DEPTH: f32 = 3.0;
+// Gate parts, in sixteenths: the posts at either end, and how far the
+// rails reach back from them once swung open.
+const GATE_POSTS: [[f32 ; 2] ; 2] = [[0.0, 2.0], [14.0, 16.0]];
+const GATE_POST_HEIGHT: [f32 ; 2] = [5.0, 16.0];
+const GATE_SWING: f32 = 7.0;
+// Wire lies this many sixteenths deep on the ground.
+const WIRE_HEIGHT: f32 = 1.0;
+// Piston parts, in sixteenths: how thick the plate on the front of the
+// head is, and how far across its rod is.
+const PISTON_PLATE: f32 = 4.0;
+const PISTON_ROD: [f32 ; 2] = [6.0, 10.0];
+// How far back from its front a portal's sheet is, in sixteenths.
+const PORTAL_DEPTH: [f32 ; 2] = [6.0, 10.0];
+/// How tall a bed is, in blocks; players sleep and respawn on top of it.
+pub const BED_HEIGHT: f32 = 9.0 / 16.0;
+
+/// An axis-aligned box inside a block, in block units from the block's
+/// lower corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cuboid {
+    pub min: [f32 ; 3],
+    pub max: [f32 ; 3],
+}
+
+impl Cuboid {
+    pub const FULL: Cuboid = Cuboid { min: [0.0 ; 3], max: [1.0 ; 3] };
+
+    /// Whether `face` of the box lies on the block's boundary, where the
+    /// neighbouring block can hide it.
+    pub fn on_boundary(&self, face: Face) -> bool {
+        match face {
+            Face::PosX => self.max[0] >= 1.0,
+            Face::NegX => self.min[0] <= 0.0,
+            Face::PosY => self.max[1] >= 1.0,
+            Face::NegY => self.min[1] <= 0.0,
+            Face::PosZ => self.max[2] >= 1.0,
+            Face::NegZ => self.min[2] <= 0.0,
+        }
+    }
+}
+
+impl BlockState {
+    /// The boxes the block is drawn with, which aiming and the selection
+    /// outline follow too. A fence reaches out to the neighbours set in
+    /// `connections`, in `Facing::ALL` order; other blocks ignore it.
+    pub fn shape(self, connections: [bool ; 4]) -> Vec<Cuboid> {
+        let half = self.properties.half();
+        match self.block.info().model {
+            BlockModel::None => Vec::new(),
+            BlockModel::Cube | BlockModel::Cross | BlockModel::Fluid | BlockModel::Moving => vec![Cuboid::FULL],
+            BlockModel::Torch => {
+                let [low, high] = TORCH_WIDTH;
+                vec![pixels([low, 0.0, low], [high, TORCH_HEIGHT, high])]
+            },
+            BlockModel::Slab => vec![half_block(half)],
+            BlockModel::Stairs => {
+                // The step rises at the back, away from the way the stairs
+                // face, and hangs down instead when they're upside down.
+                let mut step = half_block(if half == Half::Top { Half::Bottom } else { Half::Top });
+                let [dx, _, dz] = self.properties.facing().offset();
+                for (axis, towards) in [(0, dx), (2, dz)] {
+                    if towards < 0 {
+                        step.min[axis] = 0.5;
+                    } else if towards > 0 {
+                        step.max[axis] = 0.5;
+                    }
+                }
+                vec![half_block(half), step]
+            },
+            BlockModel::Fence => {
+                let mut boxes = vec![pixels([FENCE_POST[0], 0.0, FENCE_POST[0]], [FENCE_POST[1], 16.0, FENCE_POST[1]])];
+                for (facing, _) in Facing::ALL.into_iter().zip(connections).filter(|(_, joined)| *joined) {
+                    for [bottom, top] in FENCE_RAIL_HEIGHTS {
+                        boxes.push(arm(facing, FENCE_RAIL, [bottom / 16.0, top / 16.0]));
+                    }
+                }
+                boxes
+            },
+            // Against the block it hangs on, behind the way it faces.
+            BlockModel::Ladder => vec![panel(self.properties.facing(), LADDER_DEPTH)],
+            BlockModel::Bed => vec![Cuboid { min: [0.0 ; 3], max: [1.0, BED_HEIGHT, 1.0] }],
+            // Closed across the back of the block, and open against the side
+            // it swings round to.
+            BlockModel::Door => {
+                let facing = self.properties.facing();
+                vec![panel(if self.properties.open() { facing.clockwise() } else { facing }, DOOR_DEPTH)]
+            },
+            BlockModel::Trapdoor if self.properties.open() => vec![panel(self.properties.facing(), DOOR_DEPTH)],
+            BlockModel::Trapdoor => {
+                let depth = DOOR_DEPTH / 16.0;
+                vec![match half {
+                    Half::Bottom => Cuboid { min: [0.0 ; 3], max: [1.0, depth, 1.0] },
+                    Half::Top => Cuboid { min: [0.0, 1.0 - depth, 0.0], max: [1.0 ; 3] },
+                }]
+            },
+            BlockModel::Wire => vec![pixels([0.0 ; 3], [16.0, WIRE_HEIGHT, 16.0])],
+            // Drawn on the floor, then stood up against the wall if that's
+            // where it was put.
+            BlockModel::Lever => {
+                // The handle leans one way when off and the other when on.
+                let handle = if self.properties.powered() { [8.0, 11.0] } else { [5.0, 8.0] };
+                [
+                    pixels([5.0, 0.0, 4.0], [11.0, 2.0, 12.0]),
+                    pixels([7.0, 2.0, handle[0]], [9.0, 10.0, handle[1]]),
+                ].map(|cuboid| self.mounted(cuboid)).to_vec()
+            },
+            BlockModel::Button => {
+                let height = if self.properties.powered() { 1.0 } else { 2.0 };
+                vec![self.mounted(pixels([5.0, 0.0, 6.0], [11.0, height, 10.0]))]
+            },
+            BlockModel::Gate => {
+                // Spans the block side to side as the player saw it placed.
+                // Opened, the rails at each end swing back away from them.
+                let facing = self.properties.facing();
+                let mut boxes = GATE_POSTS.map(|span| across(facing, span, FENCE_RAIL, GATE_POST_HEIGHT)).to_vec();
+                for heights in FENCE_RAIL_HEIGHTS {
+                    if self.properties.open() {
+                        let back = [FENCE_RAIL[1], FENCE_RAIL[1] + GATE_SWING];
+                        boxes.extend(GATE_POSTS.map(|span| across(facing, span, back, heights)));
+                    } else {
+                        boxes.push(across(facing, [0.0, 16.0], FENCE_RAIL, heights));
+                    }
+                }
+                boxes
+            },
+            // Extended, the base draws back to make room for the plate of
+            // the head, which is in the block in front.
+            BlockModel::Piston if self.properties.powered() => {
+                vec![panel(self.properties.facing(), 16.0 - PISTON_PLATE)]
+            },
+            BlockModel::Piston => vec![Cuboid::FULL],
+            BlockModel::PistonHead => {
+                let facing = self.properties.facing();
+                vec![
+                    panel(facing.opposite(), PISTON_PLATE),
+                    across(facing, PISTON_ROD, [PISTON_PLATE, 16.0], PISTON_ROD),
+                ]
+            },
+            BlockModel::Portal => vec![across(self.properties.facing(), [0.0, 16.0], PORTAL_DEPTH, [0.0, 16.0])],
+        }
+    }
+
+    // Turns `cuboid`, laid out on the floor of the block, to stand out from
+    // the wall behind the way the block faces if it was put on one, with
+    // what was up from the floor pointing out of the wall.
+    fn mounted(self, cuboid: Cuboid) -> Cuboid {
+        if self.properties.axis() == Axis::Y {
+            return cuboid;
+        }
+        let [dx, _, dz] = self.properties.facing().offset();
+        let (out, across, towards) = if dz != 0 { (2, 0, dz) } else { (0, 2, dx) };
+        let mut turned = Cuboid { min: [0.0 ; 3], max: [0.0 ; 3] };
+        turned.min[1] = cuboid.min[2];
+        turned.max[1] = cuboid.max[2];
+        turned.min[across] = cuboid.min[0];
+        turned.max[across] = cuboid.max[0];
+        if towards > 0 {
+            turned.min[out] = cuboid.min[1];
+            turned.max[out] = cuboid.max[1];
+        } else {
+            turned.min[out] = 1.0 - cuboid.max[1];
+            turned.max[out] = 1.0 - cuboid.min[1];
+        }
+        turned
+    }
+
+    /// The boxes entities collide with, which for most blocks are the ones
+    /// they're drawn with. Blocks that aren't solid have none.
+    pub fn collision_shape(self, connections: [bool ; 4]) -> Vec<Cuboid> {
+        if !self.block.is_solid() {
+            return Vec::new();
+        }
+        match self.block.info().model {
+            BlockModel::Fence => (),
+            // Open gates let everything through; closed ones are as tall
+            // as fences to bump into.
+            BlockModel::Gate if self.properties.open() => return Vec::new(),
+            BlockModel::Gate => {
+                let facing = self.properties.facing();
+                return vec![across(facing, [0.0, 16.0], FENCE_POST, [0.0, FENCE_COLLISION_HEIGHT * 16.0])];
+            },
+            _ => return self.shape(connections),
+        }
+
+        let [low, high] = FENCE_POST.map(|v| v / 16.0);
+        let mut boxes = vec![Cuboid { min: [low, 0.0, low], max: [high, FENCE_COLLISION_HEIGHT, high] }];
+        for (facing, _) in Facing::ALL.into_iter().zip(connections).filter(|(_, joined)| *joined) {
+            boxes.push(arm(facing, FENCE_POST, [0.0, FENCE_COLLISION_HEIGHT]));
+        }
+        boxes
+    }
+}
+
+fn pixels(min: [f32 ; 3], max: [f32 ; 3]) -> Cuboid {
+    Cuboid { min: min.map(|v| v / 16.0), max: max.map(|v| v / 16.0) }
+}
+
+fn half_block(half: Half) -> Cuboid {
+    match half {
+        Half::Bottom => Cuboid { min: [0.0 ; 3], max: [1.0, 0.5, 1.0] },
+        Half::Top => Cuboid { min: [0.0, 0.5, 0.0], max: [1.0 ; 3] },
+    }
+}
+
+// A slab `depth` sixteenths thick against the side of the block opposite
+// the way `facing` points.
+fn panel(facing: Facing, depth: f32) -> Cuboid {
+    let mut panel = Cuboid::FULL;
+    let [dx, _, dz] = facing.offset();
+    for (axis, towards) in [(0, dx), (2, dz)] {
+        if towards < 0 {
+            panel.min[axis] = 1.0 - depth / 16.0;
+        } else if towards > 0 {
+            panel.max[axis] = depth / 16.0;
+        }
+    }
+    panel
+}
+
+// A box `span` sixteenths along the width of a block facing `facing`,
+// `depth` sixteenths back from its front, and `heights` sixteenths up.
+fn across(facing: Facing, span: [f32 ; 2], depth: [f32 ; 2], heights: [f32 ; 2]) -> Cuboid {
+    let [dx, _, dz] = facing.offset();
+    let (span_axis, depth_axis, towards) = if dz != 0 { (0, 2, dz) } else { (2, 0, dx) };
+    let depth = if towards > 0 { [16.0 - depth[1], 16.0 - depth[0]] } else { depth };
+    let mut min = [0.0, heights[0], 0.0];
+    let mut max = [0.0, heights[1], 0.0];
+    min[span_axis] = span[0];
+    max[span_axis] = span[1];
+    min[depth_axis] = depth[0];
+    max[depth_axis] = depth[1];
+    pixels(min, max)
+}
+
+// A bar from the middle of the block out to the side `facing` points at,
+// `width` sixteenths across and spanning `heights` in blocks.
+fn arm(facing: Facing, width: [f32 ; 2], heights: [f32 ; 2]) -> Cuboid {
+    let [low, high] = width.map(|v| v / 16.0);
+    let mut arm = Cuboid { min: [low, heights[0], low], max: [high, heights[1], high] };
+    let [dx, _, dz] = facing.offset();
+    for (axis, towards) in [(0, dx), (2, dz)] {
+        if towards < 0 {
+            arm.min[axis] = 0.0;
+        } else if towards > 0 {
+            arm.max[axis] = 1.0;
+        }
+    }
+    arm
+}
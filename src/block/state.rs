@@ -0,0 +1,351 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use super::{BlockId, BlockModel, Face};
+
+/// A property a block can vary by. The registry lists which ones each
+/// block has; the rest are always left at their defaults.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Property {
+    Facing,
+    Axis,
+    Half,
+    // Whether something like a furnace is burning, or a lamp is on.
+    Lit,
+    // Whether a door, trapdoor or gate is open.
+    Open,
+    // Whether a lever or button is switched on, or whether something that
+    // responds to signals is receiving one.
+    Powered,
+}
+
+/// The horizontal direction a block's front looks towards.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Facing {
+    #[default]
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Facing {
+    pub const ALL: [Facing ; 4] = [Facing::North, Facing::East, Facing::South, Facing::West];
+
+    /// The step to the neighbouring block in this direction. North is -z.
+    pub fn offset(self) -> [i32 ; 3] {
+        match self {
+            Facing::North => [0, 0, -1],
+            Facing::East => [1, 0, 0],
+            Facing::South => [0, 0, 1],
+            Facing::West => [-1, 0, 0],
+        }
+    }
+
+    /// The direction a quarter turn clockwise, seen from above.
+    pub fn clockwise(self) -> Self {
+        Self::ALL[(self as usize + 1) % 4]
+    }
+
+    pub fn opposite(self) -> Self {
+        self.clockwise().clockwise()
+    }
+
+    /// The direction with `offset` as its step, if it's a horizontal one.
+    pub fn from_offset(offset: [i32 ; 3]) -> Option<Self> {
+        Self::ALL.into_iter().find(|facing| facing.offset() == offset)
+    }
+
+    /// The direction facing back towards someone looking along `yaw`,
+    /// measured like the camera's.
+    pub fn towards_viewer(yaw: f32) -> Self {
+        let (sin, cos) = yaw.sin_cos();
+        if cos.abs() > sin.abs() {
+            if cos > 0.0 { Facing::West } else { Facing::East }
+        } else if sin > 0.0 {
+            Facing::North
+        } else {
+            Facing::South
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Facing::North => "north",
+            Facing::East => "east",
+            Facing::South => "south",
+            Facing::West => "west",
+        }
+    }
+}
+
+/// The axis a block such as a log runs along.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    #[default]
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub fn of_normal(normal: [i32 ; 3]) -> Self {
+        match normal {
+            [x, _, _] if x != 0 => Axis::X,
+            [_, _, z] if z != 0 => Axis::Z,
+            _ => Axis::Y,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        }
+    }
+}
+
+/// Which half of the block space a block such as a slab sits in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Half {
+    #[default]
+    Bottom,
+    Top,
+}
+
+impl Half {
+    pub fn name(self) -> &'static str {
+        match self {
+            Half::Bottom => "bottom",
+            Half::Top => "top",
+        }
+    }
+}
+
+// Bit layout of `Properties`.
+const FACING_SHIFT: u8 = 0;
+const AXIS_SHIFT: u8 = 2;
+const HALF_SHIFT: u8 = 4;
+const LIT_SHIFT: u8 = 5;
+const OPEN_SHIFT: u8 = 6;
+const POWERED_SHIFT: u8 = 7;
+
+/// A block's property values packed into a byte, which is how they're
+/// stored, saved and sent. All zero is every property's default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Properties(u8);
+
+impl Properties {
+    /// Unpacks saved or received bits. Pass the result through
+    /// `BlockState::new` to drop any the block doesn't have.
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn facing(self) -> Facing {
+        Facing::ALL[(self.0 >> FACING_SHIFT & 0b11) as usize]
+    }
+
+    pub fn axis(self) -> Axis {
+        match self.0 >> AXIS_SHIFT & 0b11 {
+            1 => Axis::X,
+            2 => Axis::Z,
+            _ => Axis::Y,
+        }
+    }
+
+    pub fn half(self) -> Half {
+        if self.0 >> HALF_SHIFT & 1 == 1 { Half::Top } else { Half::Bottom }
+    }
+
+    pub fn lit(self) -> bool {
+        self.0 >> LIT_SHIFT & 1 == 1
+    }
+
+    pub fn open(self) -> bool {
+        self.0 >> OPEN_SHIFT & 1 == 1
+    }
+
+    pub fn powered(self) -> bool {
+        self.0 >> POWERED_SHIFT & 1 == 1
+    }
+
+    pub fn with_facing(self, facing: Facing) -> Self {
+        let bits = Facing::ALL.iter().position(|&f| f == facing).unwrap() as u8;
+        Self(self.0 & !(0b11 << FACING_SHIFT) | bits << FACING_SHIFT)
+    }
+
+    pub fn with_axis(self, axis: Axis) -> Self {
+        let bits = match axis {
+            Axis::Y => 0,
+            Axis::X => 1,
+            Axis::Z => 2,
+        };
+        Self(self.0 & !(0b11 << AXIS_SHIFT) | bits << AXIS_SHIFT)
+    }
+
+    pub fn with_half(self, half: Half) -> Self {
+        Self(self.0 & !(1 << HALF_SHIFT) | ((half == Half::Top) as u8) << HALF_SHIFT)
+    }
+
+    pub fn with_lit(self, lit: bool) -> Self {
+        Self(self.0 & !(1 << LIT_SHIFT) | (lit as u8) << LIT_SHIFT)
+    }
+
+    pub fn with_open(self, open: bool) -> Self {
+        Self(self.0 & !(1 << OPEN_SHIFT) | (open as u8) << OPEN_SHIFT)
+    }
+
+    pub fn with_powered(self, powered: bool) -> Self {
+        Self(self.0 & !(1 << POWERED_SHIFT) | (powered as u8) << POWERED_SHIFT)
+    }
+}
+
+/// What's stored at a block position: which block it is, and the values
+/// of whatever properties it has.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlockState {
+    pub block: BlockId,
+    pub properties: Properties,
+}
+
+impl From<BlockId> for BlockState {
+    fn from(block: BlockId) -> Self {
+        Self { block, properties: Properties::default() }
+    }
+}
+
+impl BlockState {
+    /// Keeps only the properties `block` has, so anything read from a save
+    /// or the network compares equal to the same state made locally.
+    pub fn new(block: BlockId, properties: Properties) -> Self {
+        let has = |property| block.info().properties.contains(&property);
+        let mut kept = Properties::default();
+        if has(Property::Facing) {
+            kept = kept.with_facing(properties.facing());
+        }
+        if has(Property::Axis) {
+            kept = kept.with_axis(properties.axis());
+        }
+        if has(Property::Half) {
+            kept = kept.with_half(properties.half());
+        }
+        if has(Property::Lit) {
+            kept = kept.with_lit(properties.lit());
+        }
+        if has(Property::Open) {
+            kept = kept.with_open(properties.open());
+        }
+        if has(Property::Powered) {
+            kept = kept.with_powered(properties.powered());
+        }
+        Self { block, properties: kept }
+    }
+
+    /// The state for `block` placed against the face of another block with
+    /// `normal`, by a player looking along `yaw` whose aim hit `hit_y` of the
+    /// way up that face.
+    pub fn placed(block: BlockId, normal: [i32 ; 3], yaw: f32, hit_y: f32) -> Self {
+        // Clicking the underside, or high up a side, puts it in the top half.
+        // Doors go down by their bottom half, with the top above it.
+        let half = match normal[1] {
+            _ if block.info().model == BlockModel::Door => Half::Bottom,
+            -1 => Half::Top,
+            0 if hit_y > 0.5 => Half::Top,
+            _ => Half::Bottom,
+        };
+        // Ladders, levers and buttons face out from the side they were put
+        // on.
+        let mounted = block.is_climbable() || block.is_switch();
+        let facing = Facing::from_offset(normal)
+            .filter(|_| mounted)
+            .unwrap_or_else(|| Facing::towards_viewer(yaw));
+        let properties = Properties::default()
+            .with_facing(facing)
+            .with_axis(Axis::of_normal(normal))
+            .with_half(half);
+        Self::new(block, properties)
+    }
+
+    /// Where the other part of a two-block thing at `block` is, for keeping
+    /// the two in step: the other half of a door, the head of an extended
+    /// piston, or the piston behind a head. None for anything else.
+    pub fn other_part(self, [x, y, z]: [i32 ; 3]) -> Option<[i32 ; 3]> {
+        let [dx, _, dz] = self.properties.facing().offset();
+        match self.block.info().model {
+            BlockModel::Door if self.properties.half() == Half::Bottom => Some([x, y + 1, z]),
+            BlockModel::Door => Some([x, y - 1, z]),
+            BlockModel::Piston if self.properties.powered() => Some([x + dx, y, z + dz]),
+            BlockModel::PistonHead => Some([x - dx, y, z - dz]),
+            _ => None,
+        }
+    }
+
+    /// The red, green and blue light the block gives off. Blocks with the
+    /// Lit property only glow while they're lit.
+    pub fn emission(self) -> [u8 ; 3] {
+        let info = self.block.info();
+        if info.properties.contains(&Property::Lit) && !self.properties.lit() {
+            return [0 ; 3];
+        }
+        info.light
+    }
+
+    /// The texture on `face`, and how many quarter turns to rotate it by.
+    /// Blocks that face a way show their front texture on that side, and
+    /// lit blocks that don't show their lit texture all over. Blocks
+    /// lying along X or Z show their end texture on those faces and turn
+    /// their side texture to run the same way.
+    pub fn texture(self, face: Face) -> (u32, u8) {
+        let info = self.block.info();
+        let textures = &info.textures;
+        if info.properties.contains(&Property::Facing) && face.normal() == self.properties.facing().offset() {
+            return (if self.properties.lit() { textures.front_lit } else { textures.front }, 0);
+        }
+        if !info.properties.contains(&Property::Facing) && self.properties.lit() {
+            return (textures.front_lit, 0);
+        }
+        let axis = self.properties.axis();
+        let along = match face {
+            Face::PosX | Face::NegX => Axis::X,
+            Face::PosY | Face::NegY => Axis::Y,
+            Face::PosZ | Face::NegZ => Axis::Z,
+        };
+        if axis == Axis::Y {
+            return (self.block.texture(face), 0);
+        }
+        if along == axis {
+            let end = if matches!(face, Face::PosX | Face::PosZ) { textures.top } else { textures.bottom };
+            return (end, 0);
+        }
+        // Side faces tile their texture upright, and the top and bottom
+        // faces along Z, so only those need turning to match the axis.
+        let turned = along != Axis::Y || axis == Axis::X;
+        (textures.side, turned as u8)
+    }
+}
+
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let info = self.block.info();
+        write!(f, "{}", info.name)?;
+        if info.properties.is_empty() {
+            return Ok(());
+        }
+        let values = info.properties.iter().map(|property| match property {
+            Property::Facing => format!("facing={}", self.properties.facing().name()),
+            Property::Axis => format!("axis={}", self.properties.axis().name()),
+            Property::Half => format!("half={}", self.properties.half().name()),
+            Property::Lit => format!("lit={}", self.properties.lit()),
+            Property::Open => format!("open={}", self.properties.open()),
+            Property::Powered => format!("powered={}", self.properties.powered()),
+        });
+        write!(f, "[{}]", values.collect::<Vec<_>>().join(","))
+    }
+}
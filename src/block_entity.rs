@@ -0,0 +1,115 @@
+//! Extra data kept for single block positions, for blocks that need more
+//! than their state, like a chest's contents. Block entities live in the
+//! chunk holding their block and are saved along with it.
+
+use crate::{
+    block::BlockId,
+    codec::{self, Reader, Writer},
+    furnace::{Furnace, FURNACE_SLOTS},
+    inventory::{Inventory, ItemStack, MAX_STACK},
+};
+
+/// Slots in a chest, three rows of nine.
+pub const CHEST_SLOTS: usize = 27;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockEntity {
+    Chest(Inventory),
+    Furnace(Furnace),
+}
+
+impl BlockEntity {
+    /// The entity a newly placed `block` starts with, if it has one.
+    pub fn for_block(block: BlockId) -> Option<Self> {
+        match block {
+            BlockId::CHEST => Some(BlockEntity::Chest(Inventory::with_slots(CHEST_SLOTS))),
+            BlockId::FURNACE => Some(BlockEntity::Furnace(Furnace::new())),
+            _ => None,
+        }
+    }
+
+    /// The slots players move items in and out of.
+    pub fn items(&self) -> &Inventory {
+        match self {
+            BlockEntity::Chest(items) => items,
+            BlockEntity::Furnace(furnace) => &furnace.slots,
+        }
+    }
+
+    pub fn items_mut(&mut self) -> &mut Inventory {
+        match self {
+            BlockEntity::Chest(items) => items,
+            BlockEntity::Furnace(furnace) => &mut furnace.slots,
+        }
+    }
+
+    /// Everything inside, which is dropped when the block is broken.
+    pub fn contents(&self) -> Vec<ItemStack> {
+        let items = self.items();
+        (0..items.slot_count()).filter_map(|slot| items.get(slot)).collect()
+    }
+
+    // Items are stored by name, like blocks in chunks:
+    //
+    //   kind: u8, then
+    //   chest:   items
+    //   furnace: items, burn left: f32, burn total: f32, progress: f32
+    //
+    // where items are a stack count: u8, then for each stack
+    //   slot: u8, name: string, count: u8
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        match self {
+            BlockEntity::Chest(items) => {
+                bytes.put_u8(0);
+                encode_items(items, bytes);
+            },
+            BlockEntity::Furnace(furnace) => {
+                bytes.put_u8(1);
+                encode_items(&furnace.slots, bytes);
+                bytes.put_f32(furnace.burn_left);
+                bytes.put_f32(furnace.burn_total);
+                bytes.put_f32(furnace.progress);
+            },
+        }
+    }
+
+    pub fn decode(reader: &mut Reader) -> codec::Result<Self> {
+        match reader.u8()? {
+            0 => Ok(BlockEntity::Chest(decode_items(reader, CHEST_SLOTS)?)),
+            1 => {
+                let slots = decode_items(reader, FURNACE_SLOTS)?;
+                let (burn_left, burn_total, progress) = (reader.f32()?, reader.f32()?, reader.f32()?);
+                if ![burn_left, burn_total, progress].iter().all(|time| time.is_finite() && *time >= 0.0) {
+                    return Err(format!("bad furnace times {burn_left}, {burn_total} and {progress}"));
+                }
+                Ok(BlockEntity::Furnace(Furnace { slots, burn_left, burn_total, progress }))
+            },
+            kind => Err(format!("unknown block entity kind {kind}")),
+        }
+    }
+}
+
+fn encode_items(items: &Inventory, bytes: &mut Vec<u8>) {
+    let stacks = (0..items.slot_count()).filter_map(|slot| Some((slot, items.get(slot)?))).collect::<Vec<_>>();
+    bytes.put_u8(stacks.len() as u8);
+    for (slot, stack) in stacks {
+        bytes.put_u8(slot as u8);
+        bytes.put_string(&stack.block.info().name);
+        bytes.put_u8(stack.count as u8);
+    }
+}
+
+fn decode_items(reader: &mut Reader, slots: usize) -> codec::Result<Inventory> {
+    let mut items = Inventory::with_slots(slots);
+    for _ in 0..reader.u8()? {
+        let slot = reader.u8()? as usize;
+        let name = reader.string()?;
+        let block = BlockId::from_name(&name).ok_or_else(|| format!("unknown item `{name}`"))?;
+        let count = reader.u8()? as u32;
+        if slot >= slots || !(1..=MAX_STACK).contains(&count) {
+            return Err(format!("bad slot {slot} holding {count}"));
+        }
+        items.set(slot, Some(ItemStack::new(block, count)));
+    }
+    Ok(items)
+}
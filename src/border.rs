@@ -0,0 +1,210 @@
+//! The world border: a square around the middle of the world that nothing
+//! moves past and nothing is built beyond. Getting close to it brings up
+//! a translucent wall, striped and scrolling, which fades in the nearer
+//! the camera is.
+
+use cgmath::Point3;
+use serde::{Deserialize, Serialize};
+
+use crate::texture;
+
+/// The border of a new world, in blocks from the middle to each side.
+pub const DEFAULT_RADIUS: u32 = 16384;
+/// Any closer and the world would be smaller than a chunk or two.
+pub const MIN_RADIUS: u32 = 16;
+/// About as far as positions stay precise enough to play at.
+pub const MAX_RADIUS: u32 = 1 << 22;
+
+// The wall shows from this many blocks away, and reaches this far along
+// and up and down from the camera.
+const SHOW_DISTANCE: f32 = 24.0;
+const MAX_ALPHA: f32 = 0.6;
+// Blocks a second the stripes scroll along the wall, and how far they go
+// before starting over, a multiple of the stripe spacing in the shader so
+// it doesn't show.
+const SCROLL_SPEED: f32 = 0.5;
+const SCROLL_WRAP: f32 = 64.0;
+const MAX_VERTICES: usize = 4 * 6;
+
+/// How far the world reaches out from its middle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldBorder {
+    /// Blocks from the middle of the world to each side of the border.
+    pub radius: u32,
+}
+
+impl Default for WorldBorder {
+    fn default() -> Self {
+        Self { radius: DEFAULT_RADIUS }
+    }
+}
+
+impl WorldBorder {
+    pub fn new(radius: u32) -> Self {
+        Self { radius: radius.clamp(MIN_RADIUS, MAX_RADIUS) }
+    }
+
+    /// Whether the column of blocks at `x`, `z` is inside the border.
+    pub fn contains_block(self, x: i32, z: i32) -> bool {
+        let radius = self.radius as i32;
+        (-radius..radius).contains(&x) && (-radius..radius).contains(&z)
+    }
+
+    pub fn contains(self, [x, _, z]: [f32 ; 3]) -> bool {
+        let radius = self.radius as f32;
+        x.abs() <= radius && z.abs() <= radius
+    }
+
+    /// Pulls a box `half_width` either side of `position` back inside the
+    /// border, returning whether it had to along the x and z axes.
+    pub fn clamp(self, position: &mut Point3<f32>, half_width: f32) -> [bool ; 2] {
+        let limit = (self.radius as f32 - half_width).max(0.0);
+        [&mut position.x, &mut position.z].map(|v| {
+            let clamped = v.clamp(-limit, limit);
+            let moved = clamped != *v;
+            *v = clamped;
+            moved
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct WallVertex {
+    position: [f32 ; 3],
+    // Blocks along the wall and up it, offset by how far the stripes have
+    // scrolled.
+    wall_coords: [f32 ; 2],
+    alpha: f32,
+}
+
+/// Draws the parts of the border near the camera.
+pub struct BorderRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    scroll: f32,
+}
+
+impl BorderRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("border.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Border Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Border Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<WallVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                // Seen from either side, for anyone who got out anyway.
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Border Vertex Buffer"),
+            size: (MAX_VERTICES * std::mem::size_of::<WallVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            vertex_count: 0,
+            scroll: 0.0,
+        }
+    }
+
+    /// Moves the stripes on by `dt` and puts up the walls of `border`
+    /// within sight of `eye`.
+    pub fn update(&mut self, queue: &wgpu::Queue, border: WorldBorder, eye: Point3<f32>, dt: f32) {
+        self.scroll = (self.scroll + dt * SCROLL_SPEED) % SCROLL_WRAP;
+        let radius = border.radius as f32;
+        let (bottom, top) = (eye.y - SHOW_DISTANCE, eye.y + SHOW_DISTANCE);
+        let mut vertices = Vec::with_capacity(MAX_VERTICES);
+        // Each wall is at `radius` times `sign` on one axis, and spans the
+        // other.
+        for (axis, sign) in [(0, 1.0), (0, -1.0), (2, 1.0), (2, -1.0)] {
+            let (at, along) = if axis == 0 { (eye.x, eye.z) } else { (eye.z, eye.x) };
+            let distance = (radius - at * sign).abs();
+            if distance >= SHOW_DISTANCE {
+                continue;
+            }
+            let alpha = MAX_ALPHA * (1.0 - distance / SHOW_DISTANCE);
+            let start = (along - SHOW_DISTANCE).max(-radius);
+            let end = (along + SHOW_DISTANCE).min(radius);
+            let corner = |a: f32, y: f32| {
+                let position = if axis == 0 { [radius * sign, y, a] } else { [a, y, radius * sign] };
+                WallVertex { position, wall_coords: [a + self.scroll, y], alpha }
+            };
+            let quad = [corner(start, bottom), corner(end, bottom), corner(end, top), corner(start, top)];
+            vertices.extend([0, 1, 2, 0, 2, 3].map(|i| quad[i]));
+        }
+
+        self.vertex_count = vertices.len() as u32;
+        if !vertices.is_empty() {
+            queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        }
+    }
+
+    /// Takes the walls down, for when there's no world.
+    pub fn clear(&mut self) {
+        self.vertex_count = 0;
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,42 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+const COLOR: vec3<f32> = vec3<f32>(0.3, 0.6, 1.0);
+// Blocks between the starts of one diagonal stripe and the next, and the
+// share of that each stripe covers.
+const STRIPE_SPACING: f32 = 2.0;
+const STRIPE_WIDTH: f32 = 0.5;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) wall_coords: vec2<f32>,
+    @location(2) alpha: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) wall_coords: vec2<f32>,
+    @location(1) alpha: f32,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(in.position, 1.0);
+    out.wall_coords = in.wall_coords;
+    out.alpha = in.alpha;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let stripe = fract((in.wall_coords.x + in.wall_coords.y) / STRIPE_SPACING);
+    if stripe > STRIPE_WIDTH {
+        discard;
+    }
+    return vec4<f32>(COLOR, in.alpha);
+}
@@ -0,0 +1,87 @@
+/// How many steps the crack overlay goes through on the way to breaking.
+pub const CRACK_STAGES: u32 = 10;
+
+// Keep swinging while the button's held, a little slower than the swing
+// itself so each one reads on its own.
+const SWING_INTERVAL: f32 = 0.35;
+// A pause after each block breaks before the next one starts, so holding
+// the button on blocks that break instantly doesn't clear a row of them a
+// frame apiece.
+const BREAK_COOLDOWN: f32 = 0.25;
+
+/// What happened while breaking this frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BreakStep {
+    pub swing: bool,
+    /// The block that finished breaking.
+    pub broken: Option<[i32 ; 3]>,
+}
+
+/// Progress on breaking the aimed-at block while the break button is held.
+/// Looking away or letting go starts over, and holding on moves on to the
+/// next block after a short pause.
+#[derive(Debug, Default)]
+pub struct BlockBreaking {
+    held: bool,
+    target: Option<[i32 ; 3]>,
+    // From 0 to 1, where the block breaks.
+    progress: f32,
+    until_swing: f32,
+    cooldown: f32,
+}
+
+impl BlockBreaking {
+    pub fn set_held(&mut self, held: bool) {
+        self.held = held;
+        if !held {
+            self.cancel();
+            self.cooldown = 0.0;
+        }
+    }
+
+    /// Stops and forgets any progress, leaving the button held or not.
+    pub fn cancel(&mut self) {
+        self.target = None;
+        self.progress = 0.0;
+        self.until_swing = 0.0;
+    }
+
+    /// Works on breaking `target`, the aimed-at block and its hardness,
+    /// which takes `hardness` seconds.
+    pub fn update(&mut self, target: Option<([i32 ; 3], f32)>, dt: f32) -> BreakStep {
+        let Some((block, hardness)) = target.filter(|_| self.held) else {
+            self.cancel();
+            return BreakStep::default();
+        };
+        let mut step = BreakStep::default();
+        if self.cooldown > 0.0 {
+            self.cooldown -= dt;
+            return step;
+        }
+        if self.target != Some(block) {
+            self.target = Some(block);
+            self.progress = 0.0;
+        }
+        self.until_swing -= dt;
+        if self.until_swing <= 0.0 {
+            self.until_swing = SWING_INTERVAL;
+            step.swing = true;
+        }
+
+        self.progress += if hardness > 0.0 { dt / hardness } else { 1.0 };
+        if self.progress >= 1.0 {
+            self.target = None;
+            self.progress = 0.0;
+            self.cooldown = BREAK_COOLDOWN;
+            step.broken = Some(block);
+        }
+        step
+    }
+
+    /// The block being broken and how far along its cracks are, from 0 to
+    /// `CRACK_STAGES - 1`.
+    pub fn crack(&self) -> Option<([i32 ; 3], u32)> {
+        let stage = ((self.progress * CRACK_STAGES as f32) as u32).min(CRACK_STAGES - 1);
+        self.target.map(|block| (block, stage))
+    }
+}
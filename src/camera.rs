@@ -1,6 +1,13 @@
-use winit::{
-    event::{WindowEvent, KeyEvent, ElementState},
-    keyboard::{KeyCode, PhysicalKey},
+use std::time::{Duration, Instant};
+
+use cgmath::{Point3, Vector3};
+
+use crate::{
+    config::CameraEffectsConfig,
+    input::{Action, ActionEvent},
+    ecs::{Ecs, Entity, Velocity},
+    player,
+    world::World,
 };
 
 #[repr(C)]
@@ -24,12 +31,32 @@ impl CameraUniform {
 
 pub struct Camera {
     pub eye: cgmath::Point3<f32>,
-    pub target: cgmath::Point3<f32>,
+    pub yaw: cgmath::Rad<f32>,
+    pub pitch: cgmath::Rad<f32>,
     pub up: cgmath::Vector3<f32>,
     pub aspect: f32,
     pub fov: f32,
     pub near: f32,
     pub far: f32,
+    /// Where camera effects move the view on top of the above. Only what's
+    /// drawn moves; aiming still goes from `eye` along `forward`.
+    pub offset: ViewOffset,
+}
+
+/// How far camera effects move and turn the view this frame.
+#[derive(Clone, Copy, Debug)]
+pub struct ViewOffset {
+    pub position: cgmath::Vector3<f32>,
+    /// Yaw, pitch and roll in radians.
+    pub rotation: [f32 ; 3],
+    /// Degrees added to the field of view.
+    pub fov: f32,
+}
+
+impl Default for ViewOffset {
+    fn default() -> Self {
+        Self { position: Vector3::new(0.0, 0.0, 0.0), rotation: [0.0 ; 3], fov: 0.0 }
+    }
 }
 
 impl Camera {
@@ -41,97 +68,498 @@ impl Camera {
         0.0, 0.0, 0.0, 1.0,
     );
 
-    fn build_view_proj_matrix(&self) -> cgmath::Matrix4<f32> {
-        let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
-        let proj = cgmath::perspective(cgmath::Deg(self.fov), self.aspect, self.near, self.far);
+    pub fn forward(&self) -> cgmath::Vector3<f32> {
+        direction(self.yaw.0, self.pitch.0)
+    }
+
+    /// The field of view drawn with, camera effects included.
+    pub fn view_fov(&self) -> f32 {
+        self.fov + self.offset.fov
+    }
+
+    pub fn build_view_proj_matrix(&self) -> cgmath::Matrix4<f32> {
+        let [yaw, pitch, roll] = self.offset.rotation;
+        let forward = direction(self.yaw.0 + yaw, (self.pitch.0 + pitch).clamp(-SAFE_PITCH, SAFE_PITCH));
+        let up = cgmath::Matrix3::from_axis_angle(forward, cgmath::Rad(roll)) * self.up;
+        let view = cgmath::Matrix4::look_to_rh(self.eye + self.offset.position, forward, up);
+        let proj = cgmath::perspective(cgmath::Deg(self.view_fov()), self.aspect, self.near, self.far);
         
         Self::OPENGL_TO_WGPU_MATRIX * proj * view
     }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.build_view_proj_matrix())
+    }
+}
+
+fn direction(yaw: f32, pitch: f32) -> cgmath::Vector3<f32> {
+    let (sin_yaw, cos_yaw) = yaw.sin_cos();
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+    cgmath::Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw)
+}
+
+/// The six planes bounding what a view-projection matrix can see, each
+/// stored as `(normal, distance)` with the normal facing inwards.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub planes: [cgmath::Vector4<f32> ; 6],
+}
+
+impl Frustum {
+    // Gribb and Hartmann's plane extraction, for wgpu's 0..1 clip depth.
+    pub fn from_matrix(matrix: cgmath::Matrix4<f32>) -> Self {
+        use cgmath::{InnerSpace, Matrix};
+
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| matrix.row(i));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            plane / plane.truncate().magnitude()
+        });
+        Self { planes }
+    }
+
+    /// Whether any part of the box may be visible. Boxes near a corner can
+    /// pass without being on screen, which only costs a wasted draw.
+    pub fn intersects_aabb(&self, min: cgmath::Point3<f32>, max: cgmath::Point3<f32>) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane normal.
+            let x = if plane.x >= 0.0 { max.x } else { min.x };
+            let y = if plane.y >= 0.0 { max.y } else { min.y };
+            let z = if plane.z >= 0.0 { max.z } else { min.z };
+            plane.x * x + plane.y * y + plane.z * z + plane.w >= 0.0
+        })
+    }
+}
+
+/// Where the camera sits relative to the player.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraMode {
+    #[default]
+    FirstPerson,
+    ThirdPerson,
+}
+
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            CameraMode::ThirdPerson => CameraMode::FirstPerson,
+        }
+    }
+}
+
+// How far behind the player's eyes the third-person camera sits, and how
+// far it stays from terrain in the way.
+const BOOM_LENGTH: f32 = 4.0;
+const BOOM_MARGIN: f32 = 0.25;
+
+/// Where the camera goes to look along `forward` from a player whose eyes
+/// are at `head`. In third person the boom is cut short where terrain is in
+/// the way, so the camera never ends up inside a block.
+pub fn camera_position(world: &World, mode: CameraMode, head: Point3<f32>, forward: Vector3<f32>) -> Point3<f32> {
+    match mode {
+        CameraMode::FirstPerson => head,
+        CameraMode::ThirdPerson => {
+            let length = world.raycast(head, -forward, BOOM_LENGTH)
+                .map_or(BOOM_LENGTH, |hit| (hit.distance - BOOM_MARGIN).max(0.0));
+            head - forward * length
+        },
+    }
+}
+
+/// A camera effect that can be turned on and off in the settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraEffect {
+    /// The view bobs up and down and sways with each footstep.
+    ViewBobbing,
+    /// The field of view widens while sprinting.
+    SprintFov,
+    /// The view shakes when the player is hurt.
+    DamageShake,
+    /// The field of view eases into changes rather than jumping.
+    SmoothFov,
+}
+
+impl CameraEffect {
+    pub const ALL: [CameraEffect ; 4] = [
+        CameraEffect::ViewBobbing,
+        CameraEffect::SprintFov,
+        CameraEffect::DamageShake,
+        CameraEffect::SmoothFov,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CameraEffect::ViewBobbing => "View Bobbing",
+            CameraEffect::SprintFov => "Sprint FOV",
+            CameraEffect::DamageShake => "Damage Shake",
+            CameraEffect::SmoothFov => "Smooth FOV",
+        }
+    }
 }
 
+// Metres the view rises and sways sideways in the middle of a stride, and
+// the degrees it rolls with the sway.
+const BOB_HEIGHT: f32 = 0.05;
+const BOB_SWAY: f32 = 0.035;
+const BOB_ROLL: f32 = 0.5;
+// Seconds bobbing takes to fade in or out as the player starts or stops
+// walking.
+const BOB_FADE_TIME: f32 = 0.2;
+// Degrees the view widens by while sprinting.
+const SPRINT_FOV: f32 = 10.0;
+// How quickly a smooth field of view closes the gap to where it's going,
+// as a fraction a second.
+const FOV_EASE_RATE: f32 = 8.0;
+// Shake, from 0 to 1, added by each half heart lost and worn off a
+// second. What's seen goes with its square, so small hits barely shake.
+const SHAKE_PER_POINT: f32 = 0.25;
+const SHAKE_DECAY: f32 = 1.5;
+// Degrees of yaw, pitch and roll at the most shake, and how fast it
+// wobbles in radians a second.
+const SHAKE_ANGLES: [f32 ; 3] = [2.0, 2.0, 4.0];
+const SHAKE_SPEED: f32 = 30.0;
+
+/// Blocks a second the player has to be going across the ground for the
+/// camera to treat them as moving rather than standing still.
+pub const MOVING_SPEED: f32 = 0.5;
+
+/// What the player is doing, for the camera effects to follow.
+#[derive(Clone, Copy, Debug)]
+pub struct EffectInputs {
+    /// Whether the player is walking along the ground.
+    pub walking: bool,
+    /// How far through their current step they are, from 0 just after a
+    /// footstep to 1 at the next.
+    pub stride: f32,
+    pub sprinting: bool,
+    pub first_person: bool,
+}
+
+/// View bobbing, the sprinting field of view and shaking when hurt, worked
+/// out each frame into the camera's `offset`.
+#[derive(Debug, Default)]
+pub struct CameraEffects {
+    // How far bobbing has faded in, from 0 to 1.
+    bob: f32,
+    stride: f32,
+    // Which way the view sways this step; flips with every footstep.
+    left_foot: bool,
+    // Degrees the field of view is widened by now.
+    fov: f32,
+    shake: f32,
+    time: f32,
+}
+
+impl CameraEffects {
+    pub fn update(&mut self, camera: &mut Camera, settings: &CameraEffectsConfig, inputs: EffectInputs, dt: f32) {
+        use std::f32::consts::PI;
+
+        self.time += dt;
+
+        let bobbing = settings.enabled(CameraEffect::ViewBobbing) && inputs.walking && inputs.first_person;
+        let fade = dt / BOB_FADE_TIME;
+        self.bob = (self.bob + if bobbing { fade } else { -fade }).clamp(0.0, 1.0);
+        if inputs.stride < self.stride - 0.5 {
+            self.left_foot = !self.left_foot;
+        }
+        self.stride = inputs.stride;
+        // Highest mid-stride and lowest as each foot comes down, swaying
+        // over the foot that's down.
+        let step = (PI * self.stride).sin() * self.bob;
+        let sway = if self.left_foot { -step } else { step };
+        let (sin_yaw, cos_yaw) = camera.yaw.0.sin_cos();
+        let right = cgmath::Vector3::new(-sin_yaw, 0.0, cos_yaw);
+        let position = right * (sway * BOB_SWAY) + Vector3::unit_y() * ((step - 0.5 * self.bob) * BOB_HEIGHT);
+
+        let target = if settings.enabled(CameraEffect::SprintFov) && inputs.sprinting { SPRINT_FOV } else { 0.0 };
+        if settings.enabled(CameraEffect::SmoothFov) {
+            self.fov += (target - self.fov) * (1.0 - (-FOV_EASE_RATE * dt).exp());
+        } else {
+            self.fov = target;
+        }
+
+        if !settings.enabled(CameraEffect::DamageShake) {
+            self.shake = 0.0;
+        }
+        self.shake = (self.shake - SHAKE_DECAY * dt).max(0.0);
+        // Each axis wobbles at its own rate so the shake doesn't repeat.
+        let amount = self.shake * self.shake;
+        let wobble = [1.0, 1.37, 0.71].map(|rate: f32| (self.time * SHAKE_SPEED * rate).sin() * amount);
+        let mut rotation = [0, 1, 2].map(|axis| (wobble[axis] * SHAKE_ANGLES[axis]).to_radians());
+        rotation[2] += (sway * BOB_ROLL).to_radians();
+
+        camera.offset = ViewOffset { position, rotation, fov: self.fov };
+    }
+
+    /// Shakes the view for the player losing `amount` half hearts.
+    pub fn hurt(&mut self, amount: u32) {
+        self.shake = (self.shake + amount as f32 * SHAKE_PER_POINT).min(1.0);
+    }
+
+    /// Stops every effect at once, leaving the camera where it really is.
+    pub fn reset(&mut self, camera: &mut Camera) {
+        *self = Self::default();
+        camera.offset = ViewOffset::default();
+    }
+}
+
+const SAFE_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.001;
+const DOUBLE_TAP_WINDOW: Duration = Duration::from_millis(300);
+
+// Radians turned per pixel of mouse movement at sensitivity 1.0.
+const MOUSE_RADIANS_PER_PIXEL: f32 = 0.0025;
+
 pub struct CameraController {
     pub speed: f32,
+    pub turn_speed: f32,
+    pub mouse_sensitivity: f32,
+    pub invert_mouse: bool,
     pub is_forward_pressed: bool,
     pub is_backward_pressed: bool,
     pub is_left_pressed: bool,
     pub is_right_pressed: bool,
+    pub is_up_pressed: bool,
+    pub is_down_pressed: bool,
+    pub is_sprint_pressed: bool,
+    pub is_turn_left_pressed: bool,
+    pub is_turn_right_pressed: bool,
+    pub is_look_up_pressed: bool,
+    pub is_look_down_pressed: bool,
+    // Gamepad sticks: movement as (strafe, forward) from -1 to 1, and
+    // looking as turn rates in radians per second.
+    pub analog_move: [f32 ; 2],
+    pub analog_look: [f32 ; 2],
+
+    mouse_delta: (f32, f32),
+    toggle_fly_requested: bool,
+    last_jump_tap: Option<Instant>,
 }
 
 impl CameraController {
-    pub fn new(speed: f32) -> Self {
+    pub fn new(speed: f32, mouse_sensitivity: f32, invert_mouse: bool) -> Self {
         Self {
             speed,
+            turn_speed: 2.0,
+            mouse_sensitivity,
+            invert_mouse,
             is_forward_pressed: false,
             is_backward_pressed: false,
             is_left_pressed: false,
             is_right_pressed: false,
+            is_up_pressed: false,
+            is_down_pressed: false,
+            is_sprint_pressed: false,
+            is_turn_left_pressed: false,
+            is_turn_right_pressed: false,
+            is_look_up_pressed: false,
+            is_look_down_pressed: false,
+            analog_move: [0.0, 0.0],
+            analog_look: [0.0, 0.0],
+
+            mouse_delta: (0.0, 0.0),
+            toggle_fly_requested: false,
+            last_jump_tap: None,
         }
     }
 
-    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state,
-                        physical_key: PhysicalKey::Code(keycode),
-                        ..
-                    },
-                ..
-            } => {
-                let is_pressed = *state == ElementState::Pressed;
-                match keycode {KeyCode::KeyW | KeyCode::ArrowUp => {
-                        self.is_forward_pressed = is_pressed;
-                        true
-                    }
-                    KeyCode::KeyA | KeyCode::ArrowLeft => {
-                        self.is_left_pressed = is_pressed;
-                        true
-                    }
-                    KeyCode::KeyS | KeyCode::ArrowDown => {
-                        self.is_backward_pressed = is_pressed;
-                        true
-                    }
-                    KeyCode::KeyD | KeyCode::ArrowRight => {
-                        self.is_right_pressed = is_pressed;
-                        true
+    pub fn process_action(&mut self, event: ActionEvent) -> bool {
+        let ActionEvent { action, pressed, repeat } = event;
+        match action {
+            Action::MoveForward => self.is_forward_pressed = pressed,
+            Action::MoveBackward => self.is_backward_pressed = pressed,
+            Action::MoveLeft => self.is_left_pressed = pressed,
+            Action::MoveRight => self.is_right_pressed = pressed,
+            Action::Jump => {
+                if pressed && !repeat {
+                    // Double-tapping jump toggles fly mode.
+                    let now = Instant::now();
+                    match self.last_jump_tap {
+                        Some(last) if now - last < DOUBLE_TAP_WINDOW => {
+                            self.toggle_fly_requested = true;
+                            self.last_jump_tap = None;
+                        }
+                        _ => self.last_jump_tap = Some(now),
                     }
-                    _ => false,
                 }
+                self.is_up_pressed = pressed;
             }
-            _ => false,
+            Action::Descend => self.is_down_pressed = pressed,
+            Action::Sprint => self.is_sprint_pressed = pressed,
+            Action::ToggleFly => {
+                if pressed && !repeat {
+                    self.toggle_fly_requested = true;
+                }
+            }
+            Action::LookLeft => self.is_turn_left_pressed = pressed,
+            Action::LookRight => self.is_turn_right_pressed = pressed,
+            Action::LookUp => self.is_look_up_pressed = pressed,
+            Action::LookDown => self.is_look_down_pressed = pressed,
+            _ => return false,
         }
+
+        true
     }
 
-    pub fn update_camera(&self, camera: &mut Camera) {
-        use cgmath::InnerSpace;
-        let forward = camera.target - camera.eye;
-        let forward_norm = forward.normalize();
-        let forward_mag = forward.magnitude();
+    // Forget held keys and mouse movement not yet turned into looking,
+    // e.g. when another widget starts taking keyboard input and the
+    // release events won't reach us.
+    pub fn release_all(&mut self) {
+        self.is_forward_pressed = false;
+        self.is_backward_pressed = false;
+        self.is_left_pressed = false;
+        self.is_right_pressed = false;
+        self.is_up_pressed = false;
+        self.is_down_pressed = false;
+        self.is_sprint_pressed = false;
+        self.is_turn_left_pressed = false;
+        self.is_turn_right_pressed = false;
+        self.is_look_up_pressed = false;
+        self.is_look_down_pressed = false;
+        self.analog_move = [0.0, 0.0];
+        self.analog_look = [0.0, 0.0];
+        self.mouse_delta = (0.0, 0.0);
+    }
+
+    /// Takes raw mouse motion, in the device's own units rather than
+    /// accelerated cursor pixels.
+    pub fn process_mouse(&mut self, dx: f32, dy: f32) {
+        self.mouse_delta.0 += dx;
+        self.mouse_delta.1 += dy;
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: f32) {
+        let turn = self.turn_speed * dt;
 
-        // Prevents glitching when the camera gets too close to the
-        // center of the scene.
-        if self.is_forward_pressed && forward_mag > self.speed {
-            camera.eye += forward_norm * self.speed;
+        if self.is_turn_left_pressed {
+            camera.yaw -= cgmath::Rad(turn);
         }
-        if self.is_backward_pressed {
-            camera.eye -= forward_norm * self.speed;
+        if self.is_turn_right_pressed {
+            camera.yaw += cgmath::Rad(turn);
         }
+        if self.is_look_up_pressed {
+            camera.pitch += cgmath::Rad(turn);
+        }
+        if self.is_look_down_pressed {
+            camera.pitch -= cgmath::Rad(turn);
+        }
+
+        camera.yaw += cgmath::Rad(self.analog_look[0] * dt);
+        camera.pitch += cgmath::Rad(self.analog_look[1] * dt);
+
+        let (dx, dy) = std::mem::take(&mut self.mouse_delta);
+        let dy = if self.invert_mouse { -dy } else { dy };
+        let mouse_scale = MOUSE_RADIANS_PER_PIXEL * self.mouse_sensitivity;
+        camera.yaw += cgmath::Rad(dx * mouse_scale);
+        camera.pitch -= cgmath::Rad(dy * mouse_scale);
+
+        camera.pitch.0 = camera.pitch.0.clamp(-SAFE_PITCH, SAFE_PITCH);
+    }
+    // Turns input into the player's velocity; the physics system does the
+    // actual moving.
+    pub fn update_player(&mut self, ecs: &mut Ecs, entity: Entity, camera: &Camera, dt: f32) {
+        use cgmath::InnerSpace;
 
-        let right = forward_norm.cross(camera.up);
+        let Some((flying, can_fly)) = ecs.players.get(entity).map(|p| (p.flying, p.can_fly)) else {
+            return;
+        };
+        let flying = if std::mem::take(&mut self.toggle_fly_requested) && can_fly {
+            player::set_flying(ecs, entity, !flying);
+            !flying
+        } else {
+            flying
+        };
 
-        // Redo radius calc in case the forward/backward is pressed.
-        let forward = camera.target - camera.eye;
-        let forward_mag = forward.magnitude();
+        // Movement is relative to where the camera faces, flattened
+        // onto the ground plane.
+        let (sin_yaw, cos_yaw) = camera.yaw.0.sin_cos();
+        let forward = cgmath::Vector3::new(cos_yaw, 0.0, sin_yaw);
+        let right = cgmath::Vector3::new(-sin_yaw, 0.0, cos_yaw);
 
+        let mut wish = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        if self.is_forward_pressed {
+            wish += forward;
+        }
+        if self.is_backward_pressed {
+            wish -= forward;
+        }
         if self.is_right_pressed {
-            // Rescale the distance between the target and the eye so 
-            // that it doesn't change. The eye, therefore, still 
-            // lies on the circle made by the target and eye.
-            camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_mag;
+            wish += right;
         }
         if self.is_left_pressed {
-            camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
+            wish -= right;
+        }
+        if wish.magnitude2() > 0.0 {
+            wish = wish.normalize();
+        }
+        // A stick part way over walks slower; the keys override it.
+        if wish.magnitude2() == 0.0 {
+            wish = forward * self.analog_move[1] + right * self.analog_move[0];
+        }
+
+        // Sprinting only goes forwards, and not on an empty stomach. The
+        // key that flies down sneaks on foot, and dives in a fluid.
+        let fed = ecs.hungers.get(entity).is_none_or(|hunger| hunger.can_sprint());
+        let swimming = !flying && ecs.colliders.get(entity).is_some_and(|collider| collider.submerged > player::SWIM_DEPTH);
+        let sneaking = self.is_down_pressed && !flying && !swimming;
+        let Some(player) = ecs.players.get_mut(entity) else {
+            return;
+        };
+        player.sprinting = self.is_sprint_pressed && !flying && !sneaking && fed && wish.dot(forward) > 0.0;
+        player.sneaking = sneaking;
+        player.swimming = swimming;
+        player.update_eye_height(dt);
+        if let Some(collider) = ecs.colliders.get_mut(entity) {
+            collider.sneaking = sneaking;
+        }
+        let (fly_speed, ground_speed) = (player.fly_speed, player.ground_speed(self.speed));
+        let Some(Velocity(velocity)) = ecs.velocities.get_mut(entity) else {
+            return;
+        };
+
+        if flying {
+            let mut vertical = 0.0;
+            if self.is_up_pressed {
+                vertical += 1.0;
+            }
+            if self.is_down_pressed {
+                vertical -= 1.0;
+            }
+
+            *velocity = wish * fly_speed;
+            velocity.y = vertical * fly_speed;
+        } else {
+            velocity.x = wish.x * ground_speed;
+            velocity.z = wish.z * ground_speed;
+
+            if swimming && self.is_up_pressed {
+                velocity.y = velocity.y.max(player::SWIM_SPEED);
+            }
+            if swimming && self.is_down_pressed {
+                velocity.y = velocity.y.min(-player::SWIM_SPEED);
+            }
+            if self.is_up_pressed {
+                player::jump(ecs, entity);
+            }
+            // On a ladder, walking forwards climbs the way the camera
+            // looks and jump climbs up; sneaking holds on, and otherwise
+            // the player slowly slides down.
+            let climbing = ecs.colliders.get(entity).is_some_and(|collider| collider.climbing);
+            if climbing && !swimming {
+                let Some(Velocity(velocity)) = ecs.velocities.get_mut(entity) else {
+                    return;
+                };
+                velocity.y = if self.is_up_pressed {
+                    player::CLIMB_SPEED
+                } else if sneaking {
+                    0.0
+                } else if wish.dot(forward) > 0.0 && camera.pitch.0 < player::CLIMB_DOWN_PITCH {
+                    -player::CLIMB_SPEED
+                } else if wish.dot(forward) > 0.0 {
+                    player::CLIMB_SPEED
+                } else {
+                    -player::LADDER_SLIDE_SPEED
+                };
+            }
         }
     }
-}
\ No newline at end of file
+}
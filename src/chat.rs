@@ -0,0 +1,130 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    text::TextRenderer,
+    ui::{Rect, UiBatch},
+};
+
+const MAX_HISTORY: usize = 100;
+const VISIBLE_LINES: usize = 10;
+const TEXT_SIZE: f32 = 16.0;
+const PADDING: f32 = 4.0;
+const WIDTH: f32 = 480.0;
+// Keeps the panel clear of the hotbar and the selected item's name.
+const BOTTOM_MARGIN: f32 = 110.0;
+// Lines stay up this long once they arrive, then fade out over
+// `FADE_TIME`. With the console open the recent history shows regardless.
+const SHOW_TIME: Duration = Duration::from_secs(10);
+const FADE_TIME: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChatKind {
+    Message,
+    Whisper,
+    /// Joins, leaves and anything else the game says.
+    Notice,
+}
+
+impl ChatKind {
+    fn color(self) -> [f32 ; 3] {
+        match self {
+            ChatKind::Message => [1.0, 1.0, 1.0],
+            ChatKind::Whisper => [0.85, 0.6, 1.0],
+            ChatKind::Notice => [1.0, 0.9, 0.4],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatLine {
+    pub kind: ChatKind,
+    pub text: String,
+}
+
+impl ChatLine {
+    pub fn new(kind: ChatKind, text: impl Into<String>) -> Self {
+        Self { kind, text: text.into() }
+    }
+}
+
+/// Chat and game notices in the bottom-left corner. New lines fade out
+/// after a few seconds; opening the console brings the history back.
+pub struct ChatHud {
+    lines: VecDeque<(ChatLine, Instant)>,
+}
+
+impl ChatHud {
+    pub fn new() -> Self {
+        Self { lines: VecDeque::new() }
+    }
+
+    pub fn push(&mut self, line: ChatLine) {
+        if self.lines.len() == MAX_HISTORY {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((line, Instant::now()));
+    }
+
+    pub fn draw(&self, batch: &mut UiBatch, text: &mut TextRenderer, screen_size: (u32, u32), console_open: bool) {
+        let line_height = text.line_height(TEXT_SIZE);
+        let mut y = screen_size.1 as f32 - BOTTOM_MARGIN;
+        let mut drawn = 0;
+
+        'lines: for (line, received) in self.lines.iter().rev() {
+            let alpha = if console_open { 1.0 } else { fade(received.elapsed()) };
+            if alpha <= 0.0 {
+                // Older lines have faded out as well.
+                break;
+            }
+
+            let [r, g, b] = line.kind.color();
+            for wrapped in wrap(text, &line.text).into_iter().rev() {
+                if drawn == VISIBLE_LINES {
+                    break 'lines;
+                }
+                y -= line_height;
+                batch.quad(Rect::new(0.0, y, WIDTH + PADDING * 2.0, line_height), [0.0, 0.0, 0.0, 0.4 * alpha]);
+                text.queue(wrapped, [PADDING, y], TEXT_SIZE, [r, g, b, alpha]);
+                drawn += 1;
+            }
+        }
+    }
+}
+
+fn fade(age: Duration) -> f32 {
+    match age.checked_sub(SHOW_TIME) {
+        None => 1.0,
+        Some(fading) => 1.0 - fading.as_secs_f32() / FADE_TIME.as_secs_f32(),
+    }
+}
+
+// Breaks a line at spaces to fit the panel, splitting words that are
+// wider than the whole panel.
+fn wrap(text: &TextRenderer, line: &str) -> Vec<String> {
+    let fits = |s: &str| text.measure(s, TEXT_SIZE).0 <= WIDTH;
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in line.split(' ') {
+        let candidate = if current.is_empty() { word.to_string() } else { format!("{current} {word}") };
+        if fits(&candidate) {
+            current = candidate;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        for c in word.chars() {
+            current.push(c);
+            if !fits(&current) && current.chars().count() > 1 {
+                current.pop();
+                lines.push(std::mem::replace(&mut current, c.to_string()));
+            }
+        }
+    }
+    lines.push(current);
+    lines
+}
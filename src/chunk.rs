@@ -0,0 +1,239 @@
+mod palette;
+
+use std::collections::HashMap;
+
+use crate::{
+    block::{BlockId, BlockState},
+    block_entity::BlockEntity,
+    fluid,
+    light::Light,
+};
+
+use self::palette::PalettedContainer;
+
+/// Chunks are cubes this many blocks across, stacked without limit up and
+/// down, so the open sky above the terrain and solid rock below it are
+/// only loaded near the player.
+pub const CHUNK_SIZE: usize = 16;
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn from_block(x: i32, y: i32, z: i32) -> Self {
+        Self {
+            x: x.div_euclid(CHUNK_SIZE as i32),
+            y: y.div_euclid(CHUNK_SIZE as i32),
+            z: z.div_euclid(CHUNK_SIZE as i32),
+        }
+    }
+
+    // World-space coordinates of the chunk's minimum corner.
+    pub fn origin(self) -> [i32 ; 3] {
+        [self.x, self.y, self.z].map(|v| v * CHUNK_SIZE as i32)
+    }
+
+    pub fn offset(self, dx: i32, dy: i32, dz: i32) -> Self {
+        Self::new(self.x + dx, self.y + dy, self.z + dz)
+    }
+
+    /// The stack of chunks this one is in.
+    pub fn column(self) -> ColumnPos {
+        ColumnPos::new(self.x, self.z)
+    }
+
+    /// How many chunks away `other` is across, the further of its x and z
+    /// distances, and how many up or down.
+    pub fn distance(self, other: ChunkPos) -> (i32, i32) {
+        ((self.x - other.x).abs().max((self.z - other.z).abs()), (self.y - other.y).abs())
+    }
+
+    /// The square of the straight-line distance to `other`, in chunks, for
+    /// putting chunks in order from the nearest.
+    pub fn distance_squared(self, other: ChunkPos) -> i32 {
+        let (dx, dy, dz) = (self.x - other.x, self.y - other.y, self.z - other.z);
+        dx * dx + dy * dy + dz * dz
+    }
+
+    /// Every chunk at most `radius` chunks across and `vertical` up or
+    /// down from this one.
+    pub fn around(self, radius: i32, vertical: i32) -> impl Iterator<Item = ChunkPos> {
+        (-vertical..=vertical).flat_map(move |dy| {
+            (-radius..=radius).flat_map(move |dz| (-radius..=radius).map(move |dx| self.offset(dx, dy, dz)))
+        })
+    }
+}
+
+/// A stack of chunks, by its x and z in chunks: what the map draws a tile
+/// of and far-off terrain is meshed by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ColumnPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ColumnPos {
+    pub fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    pub fn from_block(x: i32, z: i32) -> Self {
+        Self {
+            x: x.div_euclid(CHUNK_SIZE as i32),
+            z: z.div_euclid(CHUNK_SIZE as i32),
+        }
+    }
+
+    // World-space x and z of the column's minimum corner.
+    pub fn origin(self) -> (i32, i32) {
+        (self.x * CHUNK_SIZE as i32, self.z * CHUNK_SIZE as i32)
+    }
+
+    /// The chunk `y` chunks up the column.
+    pub fn chunk(self, y: i32) -> ChunkPos {
+        ChunkPos::new(self.x, y, self.z)
+    }
+}
+
+// Everything stored per block position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Voxel {
+    state: BlockState,
+    // Zero for anything that isn't a fluid, except wire, which keeps the
+    // strength of its signal here.
+    fluid_level: u8,
+}
+
+#[derive(Clone)]
+pub struct Chunk {
+    pub pos: ChunkPos,
+    voxels: PalettedContainer<Voxel>,
+    // Each packed into two bytes. Worked out again whenever the chunk is
+    // loaded rather than saved.
+    light: PalettedContainer<u16>,
+    // By voxel index, for the few blocks that have one.
+    block_entities: HashMap<usize, BlockEntity>,
+}
+
+impl Chunk {
+    pub fn new(pos: ChunkPos) -> Self {
+        Self {
+            pos,
+            voxels: PalettedContainer::new(CHUNK_VOLUME, Voxel { state: BlockState::default(), fluid_level: 0 }),
+            light: PalettedContainer::new(CHUNK_VOLUME, 0),
+            block_entities: HashMap::new(),
+        }
+    }
+
+    // Layer by layer from the bottom.
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        (y * CHUNK_SIZE + z) * CHUNK_SIZE + x
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> Voxel {
+        self.voxels.get(Self::index(x, y, z))
+    }
+
+    /// Whether the chunk is nothing but air, so there's nothing in it to
+    /// draw. One that had blocks taken out of it may only count once it's
+    /// compacted.
+    pub fn is_empty(&self) -> bool {
+        self.voxels.uniform().is_some_and(|voxel| voxel.state.block.is_air())
+    }
+
+    pub fn in_bounds(x: i32, y: i32, z: i32) -> bool {
+        [x, y, z].iter().all(|v| (0..CHUNK_SIZE as i32).contains(v))
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        self.voxel(x, y, z).state.block
+    }
+
+    pub fn get_state(&self, x: usize, y: usize, z: usize) -> BlockState {
+        self.voxel(x, y, z).state
+    }
+
+    // Placing a fluid block directly always makes a source.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: BlockId) {
+        let level = if block.is_fluid() { fluid::SOURCE_LEVEL } else { 0 };
+        self.set_voxel(x, y, z, block.into(), level);
+    }
+
+    pub fn fluid_level(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.voxel(x, y, z).fluid_level
+    }
+
+    /// Sets a block and its fluid level. A different block replaces any
+    /// block entity at the position with the new block's own, if it has
+    /// one.
+    pub fn set_voxel(&mut self, x: usize, y: usize, z: usize, state: BlockState, level: u8) {
+        let index = Self::index(x, y, z);
+        let replaced = self.voxels.get(index).state.block != state.block;
+        self.voxels.set(index, Voxel { state, fluid_level: level });
+        if replaced {
+            match BlockEntity::for_block(state.block) {
+                Some(entity) => self.block_entities.insert(index, entity),
+                None => self.block_entities.remove(&index),
+            };
+        }
+    }
+
+    pub fn light(&self, x: usize, y: usize, z: usize) -> Light {
+        Light::from_bits(self.light.get(Self::index(x, y, z)))
+    }
+
+    /// Only stores the light; `light` works out where it comes from.
+    pub fn set_light(&mut self, x: usize, y: usize, z: usize, light: Light) {
+        self.light.set(Self::index(x, y, z), light.bits());
+    }
+
+    pub fn block_entity(&self, x: usize, y: usize, z: usize) -> Option<&BlockEntity> {
+        self.block_entities.get(&Self::index(x, y, z))
+    }
+
+    /// Replaces the block entity at a position, which only takes if the
+    /// block there has one of the same kind.
+    pub fn set_block_entity(&mut self, x: usize, y: usize, z: usize, entity: BlockEntity) -> bool {
+        let index = Self::index(x, y, z);
+        match self.block_entities.get_mut(&index) {
+            Some(existing) if std::mem::discriminant(existing) == std::mem::discriminant(&entity) => {
+                *existing = entity;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Every block entity with its position in the chunk.
+    pub fn block_entities(&self) -> impl Iterator<Item = ([usize ; 3], &BlockEntity)> {
+        self.block_entities.iter().map(|(&index, entity)| {
+            ([index % CHUNK_SIZE, index / (CHUNK_SIZE * CHUNK_SIZE), index / CHUNK_SIZE % CHUNK_SIZE], entity)
+        })
+    }
+
+    /// Forgets all of the light, to light the chunk again from scratch.
+    pub fn clear_light(&mut self) {
+        self.light = PalettedContainer::new(CHUNK_VOLUME, 0);
+    }
+
+    /// Drops palette entries for blocks and light that are gone, e.g. once
+    /// generation has finished overwriting things.
+    pub fn compact(&mut self) {
+        self.voxels.compact();
+        self.light.compact();
+    }
+
+    /// Heap memory used by the block and light data, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.voxels.memory_usage() + self.light.memory_usage()
+    }
+}
@@ -0,0 +1,211 @@
+/// A fixed-length array that stores each distinct value once, in a
+/// palette, and the elements as bit-packed indices into it. The index
+/// width grows with the palette, and a container holding a single value
+/// stores no indices at all, so mostly uniform data takes very little
+/// memory.
+#[derive(Clone)]
+pub struct PalettedContainer<T> {
+    len: usize,
+    palette: Vec<T>,
+    // Bits per index: 0, 1, 2, 4, 8 or 16, so indices never straddle two
+    // words.
+    bits: u32,
+    words: Vec<u64>,
+}
+
+impl<T: Copy + Eq> PalettedContainer<T> {
+    pub fn new(len: usize, value: T) -> Self {
+        Self {
+            len,
+            palette: vec![value],
+            bits: 0,
+            words: Vec::new(),
+        }
+    }
+
+    fn bits_for(palette_len: usize) -> u32 {
+        match palette_len {
+            0..=1 => 0,
+            2 => 1,
+            3..=4 => 2,
+            5..=16 => 4,
+            17..=256 => 8,
+            _ => 16,
+        }
+    }
+
+    fn palette_index(&self, index: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = 64 / self.bits as usize;
+        let shift = (index % per_word) as u32 * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        ((self.words[index / per_word] >> shift) & mask) as usize
+    }
+
+    fn set_palette_index(&mut self, index: usize, palette_index: usize) {
+        let per_word = 64 / self.bits as usize;
+        let shift = (index % per_word) as u32 * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        let word = &mut self.words[index / per_word];
+        *word = (*word & !(mask << shift)) | ((palette_index as u64) << shift);
+    }
+
+    /// The value every element holds, if they're all the same and nothing
+    /// else has been stored since the last compaction.
+    pub fn uniform(&self) -> Option<T> {
+        (self.palette.len() == 1).then(|| self.palette[0])
+    }
+
+    pub fn get(&self, index: usize) -> T {
+        self.palette[self.palette_index(index)]
+    }
+
+    pub fn set(&mut self, index: usize, value: T) {
+        debug_assert!(index < self.len);
+
+        let palette_index = match self.palette.iter().position(|&v| v == value) {
+            Some(palette_index) => palette_index,
+            None => {
+                if self.palette.len() >= 1 << self.bits {
+                    // Values that were overwritten stay in the palette until
+                    // it fills up; dropping them may avoid a wider index.
+                    self.compact();
+                    if self.palette.len() >= 1 << self.bits {
+                        self.repack(Self::bits_for(self.palette.len() + 1), None);
+                    }
+                }
+                self.palette.push(value);
+                self.palette.len() - 1
+            },
+        };
+
+        if self.bits > 0 {
+            self.set_palette_index(index, palette_index);
+        }
+    }
+
+    /// Removes palette entries no element refers to any more, narrowing
+    /// the indices if the palette got small enough.
+    pub fn compact(&mut self) {
+        let mut used = vec![false ; self.palette.len()];
+        for index in 0..self.len {
+            used[self.palette_index(index)] = true;
+        }
+        if used.iter().all(|&used| used) {
+            return;
+        }
+
+        let mut remap = vec![0 ; self.palette.len()];
+        let mut palette = Vec::with_capacity(self.palette.len());
+        for (old, value) in self.palette.iter().enumerate() {
+            if used[old] {
+                remap[old] = palette.len();
+                palette.push(*value);
+            }
+        }
+
+        let bits = Self::bits_for(palette.len());
+        self.repack(bits, Some(&remap));
+        self.palette = palette;
+    }
+
+    // Rewrites the indices at a new width, optionally renumbering them.
+    fn repack(&mut self, bits: u32, remap: Option<&[usize]>) {
+        let indices = (0..self.len)
+            .map(|index| {
+                let palette_index = self.palette_index(index);
+                remap.map_or(palette_index, |remap| remap[palette_index])
+            })
+            .collect::<Vec<_>>();
+
+        self.bits = bits;
+        self.words = if bits == 0 {
+            Vec::new()
+        } else {
+            vec![0 ; self.len.div_ceil(64 / bits as usize)]
+        };
+        if bits > 0 {
+            for (index, palette_index) in indices.into_iter().enumerate() {
+                self.set_palette_index(index, palette_index);
+            }
+        }
+    }
+
+    /// Heap memory used, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.palette.capacity() * std::mem::size_of::<T>() + self.words.capacity() * std::mem::size_of::<u64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PalettedContainer;
+
+    const LEN: usize = 4096;
+
+    // Checks every element against what was stored in it.
+    fn assert_matches(container: &PalettedContainer<u16>, expected: &[u16]) {
+        for (index, &value) in expected.iter().enumerate() {
+            assert_eq!(container.get(index), value, "element {index}");
+        }
+    }
+
+    #[test]
+    fn set_and_get_across_widths() {
+        let mut container = PalettedContainer::new(LEN, 0);
+        let mut expected = vec![0 ; LEN];
+        // Each round brings in more distinct values, widening the indices
+        // through every width up to 16 bits.
+        for distinct in [2, 3, 5, 17, 257, 1000] {
+            for (index, value) in expected.iter_mut().enumerate() {
+                *value = (index * 7 % distinct) as u16;
+                container.set(index, *value);
+            }
+            assert_matches(&container, &expected);
+        }
+    }
+
+    #[test]
+    fn compact_drops_overwritten_values() {
+        let mut container = PalettedContainer::new(LEN, 0);
+        for index in 0..LEN {
+            container.set(index, (index % 20) as u16);
+        }
+        assert_eq!(container.uniform(), None);
+
+        let mut expected = vec![5 ; LEN];
+        expected[100] = 9;
+        expected[4000] = 12;
+        for (index, &value) in expected.iter().enumerate() {
+            container.set(index, value);
+        }
+        container.compact();
+        assert_eq!(container.palette.len(), 3);
+        assert_eq!(container.bits, 2);
+        assert_matches(&container, &expected);
+
+        for index in 0..LEN {
+            container.set(index, 7);
+        }
+        container.compact();
+        assert_eq!(container.uniform(), Some(7));
+        assert!(container.words.is_empty());
+    }
+
+    #[test]
+    fn full_palette_reuses_overwritten_entries() {
+        let mut container = PalettedContainer::new(LEN, 0);
+        container.set(0, 1);
+        assert_eq!(container.bits, 1);
+        // 1 is gone, so 2 takes its place rather than widening the
+        // indices.
+        container.set(0, 0);
+        container.set(1, 2);
+        assert_eq!(container.bits, 1);
+        assert_eq!(container.get(0), 0);
+        assert_eq!(container.get(1), 2);
+        assert_eq!(container.get(LEN - 1), 0);
+    }
+}
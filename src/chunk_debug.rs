@@ -0,0 +1,295 @@
+//! Debug views of the chunk grid. Borders outline the chunk the camera is
+//! in, with a grid on its walls, and mark the corners of the chunks around
+//! it; on top of that terrain can be tinted by which chunk it belongs to,
+//! to see where meshes meet, or by how recently each chunk was remeshed,
+//! to see what streaming and lighting updates touch.
+
+use std::time::Instant;
+
+use crate::{
+    chunk::{ChunkPos, CHUNK_SIZE},
+    texture,
+};
+
+/// Chunks whose remesh times are kept, across and along. Chunks this far
+/// apart share a slot, which is plenty for the largest render distance.
+const AGE_GRID: u32 = 64;
+// And up, in grids stacked one under the other in the texture.
+const AGE_LAYERS: u32 = 16;
+// Spacing of the grid on the walls of the camera's chunk, in blocks.
+const GRID_SPACING: usize = 2;
+// How many chunks out from the camera's one have their corners marked.
+const CORNER_RADIUS: i32 = 2;
+
+const WALL_COLOR: [f32 ; 4] = [1.0, 0.9, 0.2, 0.6];
+// The top and bottom of the chunk stand out from the grid.
+const EDGE_COLOR: [f32 ; 4] = [0.2, 0.5, 1.0, 0.9];
+const CORNER_COLOR: [f32 ; 4] = [1.0, 0.2, 0.2, 0.8];
+
+/// What the chunk debug view shows, cycled through with its key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkDebugMode {
+    #[default]
+    Off,
+    Borders,
+    /// Borders, with each chunk's terrain tinted its own color.
+    Coordinates,
+    /// Borders, with terrain tinted from red when its chunk was just
+    /// remeshed to blue after a while.
+    RemeshAge,
+}
+
+impl ChunkDebugMode {
+    pub fn next(self) -> Self {
+        match self {
+            ChunkDebugMode::Off => ChunkDebugMode::Borders,
+            ChunkDebugMode::Borders => ChunkDebugMode::Coordinates,
+            ChunkDebugMode::Coordinates => ChunkDebugMode::RemeshAge,
+            ChunkDebugMode::RemeshAge => ChunkDebugMode::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChunkDebugMode::Off => "off",
+            ChunkDebugMode::Borders => "borders",
+            ChunkDebugMode::Coordinates => "borders, colored by chunk",
+            ChunkDebugMode::RemeshAge => "borders, colored by remesh age",
+        }
+    }
+
+    /// How the terrain shader tints chunks in this mode.
+    pub fn tint_id(self) -> u32 {
+        match self {
+            ChunkDebugMode::Off | ChunkDebugMode::Borders => 0,
+            ChunkDebugMode::Coordinates => 1,
+            ChunkDebugMode::RemeshAge => 2,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32 ; 3],
+    color: [f32 ; 4],
+}
+
+fn line_count() -> usize {
+    let walls = 4 * (CHUNK_SIZE / GRID_SPACING) + 4 * (CHUNK_SIZE / GRID_SPACING + 1);
+    let side = (2 * CORNER_RADIUS + 2) as usize;
+    walls + side * side
+}
+
+pub struct ChunkBorderRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    // The chunk the lines are around now.
+    center: Option<ChunkPos>,
+}
+
+impl ChunkBorderRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("chunk_debug.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Chunk Border Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Chunk Border Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk Border Vertex Buffer"),
+            size: (line_count() * 2 * std::mem::size_of::<LineVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            center: None,
+        }
+    }
+
+    /// Outlines `center`, the chunk the camera is in, or nothing when the
+    /// view is off.
+    pub fn set_center(&mut self, queue: &wgpu::Queue, center: Option<ChunkPos>) {
+        if center == self.center {
+            return;
+        }
+        self.center = center;
+
+        let Some(center) = center else {
+            return;
+        };
+
+        let [x0, y0, z0] = center.origin().map(|v| v as f32);
+        let size = CHUNK_SIZE as f32;
+        let y1 = y0 + size;
+        let mut vertices = Vec::with_capacity(line_count() * 2);
+        let mut line = |a: [f32 ; 3], b: [f32 ; 3], color: [f32 ; 4]| {
+            vertices.push(LineVertex { position: a, color });
+            vertices.push(LineVertex { position: b, color });
+        };
+
+        // Up the walls, each starting from a corner so the corners are
+        // covered once.
+        for i in (0..CHUNK_SIZE).step_by(GRID_SPACING) {
+            let t = i as f32;
+            line([x0 + t, y0, z0], [x0 + t, y1, z0], WALL_COLOR);
+            line([x0 + size, y0, z0 + t], [x0 + size, y1, z0 + t], WALL_COLOR);
+            line([x0 + size - t, y0, z0 + size], [x0 + size - t, y1, z0 + size], WALL_COLOR);
+            line([x0, y0, z0 + size - t], [x0, y1, z0 + size - t], WALL_COLOR);
+        }
+        // Around them.
+        for y in (0..=CHUNK_SIZE).step_by(GRID_SPACING) {
+            let color = if y % CHUNK_SIZE == 0 { EDGE_COLOR } else { WALL_COLOR };
+            let y = y0 + y as f32;
+            line([x0, y, z0], [x0 + size, y, z0], color);
+            line([x0 + size, y, z0], [x0 + size, y, z0 + size], color);
+            line([x0 + size, y, z0 + size], [x0, y, z0 + size], color);
+            line([x0, y, z0 + size], [x0, y, z0], color);
+        }
+        // The corners of the chunks around it, as high as it.
+        for dz in -CORNER_RADIUS..=CORNER_RADIUS + 1 {
+            for dx in -CORNER_RADIUS..=CORNER_RADIUS + 1 {
+                let (x, z) = (x0 + dx as f32 * size, z0 + dz as f32 * size);
+                line([x, y0, z], [x, y1, z], CORNER_COLOR);
+            }
+        }
+
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if self.center.is_none() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..(line_count() * 2) as u32, 0..1);
+    }
+}
+
+/// When each chunk was last remeshed, kept in a texture the terrain shader
+/// reads to tint chunks by it. Each texel holds a chunk's coordinates, so
+/// the shader can tell a slot's chunk from others sharing it, and the time,
+/// which is negative in slots no chunk has used yet.
+pub struct RemeshAges {
+    texels: Vec<[f32 ; 4]>,
+    texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    started: Instant,
+    changed: bool,
+}
+
+impl RemeshAges {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Remesh Age Texture"),
+            size: wgpu::Extent3d { width: AGE_GRID, height: AGE_GRID * AGE_LAYERS, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            texels: vec![[0.0, 0.0, 0.0, -1.0] ; (AGE_GRID * AGE_GRID * AGE_LAYERS) as usize],
+            texture,
+            view,
+            started: Instant::now(),
+            changed: false,
+        }
+    }
+
+    /// Seconds since this was created, which remesh times count from.
+    pub fn now(&self) -> f32 {
+        self.started.elapsed().as_secs_f32()
+    }
+
+    pub fn record(&mut self, pos: ChunkPos) {
+        let slot = |v: i32| v.rem_euclid(AGE_GRID as i32) as usize;
+        let row = pos.y.rem_euclid(AGE_LAYERS as i32) as usize * AGE_GRID as usize + slot(pos.z);
+        self.texels[row * AGE_GRID as usize + slot(pos.x)] = [pos.x as f32, pos.y as f32, pos.z as f32, self.now()];
+        self.changed = true;
+    }
+
+    /// Writes the times recorded since the last upload to the texture.
+    pub fn upload(&mut self, queue: &wgpu::Queue) {
+        if !self.changed {
+            return;
+        }
+        self.changed = false;
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&self.texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(AGE_GRID * std::mem::size_of::<[f32 ; 4]>() as u32),
+                rows_per_image: Some(AGE_GRID * AGE_LAYERS),
+            },
+            wgpu::Extent3d { width: AGE_GRID, height: AGE_GRID * AGE_LAYERS, depth_or_array_layers: 1 },
+        );
+    }
+}
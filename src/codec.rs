@@ -0,0 +1,124 @@
+//! Little-endian reading and writing for the binary formats: chunk files
+//! and network messages.
+
+pub type Result<T> = std::result::Result<T, String>;
+
+/// Reads values off the front of a byte slice. Every read fails cleanly on
+/// truncated input instead of panicking, since the bytes come from disk or
+/// the network.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err("unexpected end of data".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8 ; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    pub fn i32(&mut self) -> Result<i32> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    pub fn f32(&mut self) -> Result<f32> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    /// A string prefixed with its length in bytes as a u16.
+    pub fn string(&mut self) -> Result<String> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "string is not UTF-8".to_string())
+    }
+
+    /// A byte string prefixed with its length as a u32.
+    pub fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
+
+/// The writing side of `Reader`.
+pub trait Writer {
+    fn put_u8(&mut self, value: u8);
+    fn put_u16(&mut self, value: u16);
+    fn put_u32(&mut self, value: u32);
+    fn put_u64(&mut self, value: u64);
+    fn put_i32(&mut self, value: i32);
+    fn put_f32(&mut self, value: f32);
+    fn put_string(&mut self, value: &str);
+    fn put_bytes(&mut self, value: &[u8]);
+}
+
+impl Writer for Vec<u8> {
+    fn put_u8(&mut self, value: u8) {
+        self.push(value);
+    }
+
+    fn put_u16(&mut self, value: u16) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(&mut self, value: u64) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_i32(&mut self, value: i32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_f32(&mut self, value: f32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Longer strings are cut at a character boundary rather than corrupting
+    // the length prefix.
+    fn put_string(&mut self, value: &str) {
+        let mut len = value.len().min(u16::MAX as usize);
+        while !value.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.put_u16(len as u16);
+        self.extend_from_slice(&value.as_bytes()[..len]);
+    }
+
+    fn put_bytes(&mut self, value: &[u8]) {
+        self.put_u32(value.len() as u32);
+        self.extend_from_slice(value);
+    }
+}
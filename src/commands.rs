@@ -0,0 +1,538 @@
+use thiserror::Error;
+
+use crate::{
+    block::BlockId,
+    border::{WorldBorder, MAX_RADIUS, MIN_RADIUS},
+    dimension::Dimension,
+    ecs::{Ecs, Entity, Velocity},
+    error::EngineError,
+    game_mode::GameMode,
+    history::EditHistory,
+    inventory::{Inventory, INVENTORY_SLOTS, MAX_STACK},
+    mob::{self, MobKind},
+    net::Client,
+    player,
+    save::WorldSave,
+    schematic::{self, Schematic},
+    weather::Weather,
+    world::{World, DAY_LENGTH},
+    world_edit::WorldEdit,
+};
+
+// Keeps a mistyped `/fill` from stalling the game for seconds.
+const MAX_FILL_VOLUME: i64 = 32 * 32 * 32;
+// Structures are saved whole, so they can be bigger, but not without end.
+const MAX_STRUCTURE_VOLUME: i64 = 64 * 64 * 64;
+// A day still takes a second at the fastest.
+const MAX_TIME_SPEED: f32 = 1200.0;
+const TIME_USAGE: &str = "/time <set <day|noon|night|midnight|ticks> | speed <x> | cycle <on|off>>";
+const STRUCT_USAGE: &str = "/struct <save <name> <x1> <y1> <z1> <x2> <y2> <z2> | place <name> [x y z]>";
+
+const HELP: &str = "\
+/tp <x> <y> <z>               teleport; ~ is relative to you
+/time set <day|noon|night|midnight|ticks>
+/time speed <x>               how many times faster days go by
+/time cycle <on|off>          stop or restart the daylight cycle
+/weather <clear|rain|storm> [seconds]
+/seed                         show the world seed
+/fill [x1 y1 z1 x2 y2 z2] <block>
+/replace [x1 y1 z1 x2 y2 z2] <from> <to>
+/copy                         copy the blocks in the selection
+/paste [x y z]                put down what was copied; defaults to here
+/undo [n]                     take back the last edit, or the last n
+/redo [n]                     put back what was undone
+/struct save <name> <x1> <y1> <z1> <x2> <y2> <z2>
+/struct place <name> [x y z]  paste a saved structure; defaults to here
+/backup                       zip up the world; the last 5 are kept
+/give <block> [count]         add blocks to your inventory
+/gamemode <survival|creative>
+/spawnpoint [x y z]           set where you respawn; defaults to here
+/border [radius]              show or set how far the world reaches
+/summon <pig|zombie> [x y z]  spawn a mob; defaults to here
+/msg <player> <message>       whisper to another player";
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("unknown command '/{0}', try /help")]
+    Unknown(String),
+    #[error("usage: {0}")]
+    Usage(&'static str),
+    #[error("'{0}' is not a valid number")]
+    InvalidNumber(String),
+    #[error("unknown block '{0}'")]
+    UnknownBlock(String),
+    #[error("region of {volume} blocks exceeds the limit of {limit}")]
+    RegionTooLarge { volume: i64, limit: i64 },
+    #[error("'{0}' can't be held as an item")]
+    NotAnItem(String),
+    #[error("/{0} only works on a server")]
+    NotOnline(&'static str),
+    #[error("/{0} is up to the server")]
+    Online(&'static str),
+    #[error("/{0} only works in the overworld")]
+    NotInOverworld(&'static str),
+    #[error("unknown game mode '{0}'")]
+    UnknownGameMode(String),
+    #[error("unknown mob '{0}'")]
+    UnknownMob(String),
+    #[error("unknown weather '{0}'")]
+    UnknownWeather(String),
+    #[error("the border has to be {MIN_RADIUS} to {MAX_RADIUS} blocks out")]
+    InvalidBorder,
+    #[error("'{0}' is not a valid structure name; use letters, digits, '_' and '-'")]
+    InvalidStructureName(String),
+    #[error("no structure named '{0}'")]
+    UnknownStructure(String),
+    #[error("nothing is selected; mark the corners with the wand")]
+    NoSelection,
+    #[error("nothing has been copied")]
+    EmptyClipboard,
+    #[error("nothing to undo")]
+    NothingToUndo,
+    #[error("nothing to redo")]
+    NothingToRedo,
+    #[error(transparent)]
+    Engine(Box<EngineError>),
+}
+
+impl From<EngineError> for CommandError {
+    fn from(error: EngineError) -> Self {
+        CommandError::Engine(Box::new(error))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, CommandError>;
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    Help,
+    Teleport([f32 ; 3]),
+    SetTime(u64),
+    SetTimeSpeed(f32),
+    SetDaylightCycle(bool),
+    SetWeather { weather: Weather, duration: Option<f32> },
+    Seed,
+    /// `corners` are the selection's when not given.
+    Fill { corners: Option<([i32 ; 3], [i32 ; 3])>, block: BlockId },
+    Replace { corners: Option<([i32 ; 3], [i32 ; 3])>, from: BlockId, to: BlockId },
+    Copy,
+    Paste([i32 ; 3]),
+    Undo(usize),
+    Redo(usize),
+    SaveStructure { name: String, from: [i32 ; 3], to: [i32 ; 3] },
+    PlaceStructure { name: String, origin: [i32 ; 3] },
+    Backup,
+    Give { block: BlockId, count: u32 },
+    SetGameMode(GameMode),
+    SetSpawn([f32 ; 3]),
+    Border(Option<u32>),
+    Summon { kind: MobKind, position: [f32 ; 3] },
+    Message { to: String, text: String },
+}
+
+/// The game state commands are allowed to touch.
+pub struct CommandContext<'a> {
+    pub world: &'a mut World,
+    pub ecs: &'a mut Ecs,
+    pub player: Entity,
+    pub inventory: &'a mut Inventory,
+    pub edit: &'a mut WorldEdit,
+    pub history: &'a mut EditHistory,
+    pub save: &'a WorldSave,
+    /// `None` in singleplayer.
+    pub client: Option<&'a Client>,
+}
+
+impl Command {
+    /// Parses a command line without its leading '/'. `origin` is what `~`
+    /// coordinates are relative to.
+    pub fn parse(line: &str, origin: [f32 ; 3]) -> Result<Self> {
+        let mut args = line.split_whitespace();
+        let name = args.next().unwrap_or_default();
+        let args = args.collect::<Vec<_>>();
+
+        match name {
+            "help" => Ok(Command::Help),
+            "tp" => {
+                let [x, y, z] = args[..] else {
+                    return Err(CommandError::Usage("/tp <x> <y> <z>"));
+                };
+                Ok(Command::Teleport([
+                    parse_coordinate(x, origin[0])?,
+                    parse_coordinate(y, origin[1])?,
+                    parse_coordinate(z, origin[2])?,
+                ]))
+            },
+            "time" => match args[..] {
+                ["set", value] => {
+                    let time = match value {
+                        "day" => 1000,
+                        "noon" => 6000,
+                        "night" => 13000,
+                        "midnight" => 18000,
+                        _ => value.parse().map_err(|_| CommandError::InvalidNumber(value.to_string()))?,
+                    };
+                    Ok(Command::SetTime(time))
+                },
+                ["speed", value] => {
+                    let speed = value.parse::<f32>()
+                        .ok()
+                        .filter(|speed| (0.0..=MAX_TIME_SPEED).contains(speed))
+                        .ok_or_else(|| CommandError::InvalidNumber(value.to_string()))?;
+                    Ok(Command::SetTimeSpeed(speed))
+                },
+                ["cycle", "on"] => Ok(Command::SetDaylightCycle(true)),
+                ["cycle", "off"] => Ok(Command::SetDaylightCycle(false)),
+                _ => Err(CommandError::Usage(TIME_USAGE)),
+            },
+            "weather" => {
+                let (weather, duration) = match args[..] {
+                    [weather] => (weather, None),
+                    [weather, seconds] => {
+                        let seconds = seconds.parse::<f32>()
+                            .ok()
+                            .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                            .ok_or_else(|| CommandError::InvalidNumber(seconds.to_string()))?;
+                        (weather, Some(seconds))
+                    },
+                    _ => return Err(CommandError::Usage("/weather <clear|rain|storm> [seconds]")),
+                };
+                let weather = Weather::from_name(weather).ok_or_else(|| CommandError::UnknownWeather(weather.to_string()))?;
+                Ok(Command::SetWeather { weather, duration })
+            },
+            "seed" => Ok(Command::Seed),
+            "backup" => Ok(Command::Backup),
+            "fill" => match args[..] {
+                [block] => Ok(Command::Fill { corners: None, block: parse_block(block)? }),
+                [x1, y1, z1, x2, y2, z2, block] => Ok(Command::Fill {
+                    corners: Some((parse_block_position([x1, y1, z1], origin)?, parse_block_position([x2, y2, z2], origin)?)),
+                    block: parse_block(block)?,
+                }),
+                _ => Err(CommandError::Usage("/fill [x1 y1 z1 x2 y2 z2] <block>")),
+            },
+            "replace" => {
+                let (corners, from, to) = match args[..] {
+                    [from, to] => (None, from, to),
+                    [x1, y1, z1, x2, y2, z2, from, to] => {
+                        let corners = (parse_block_position([x1, y1, z1], origin)?, parse_block_position([x2, y2, z2], origin)?);
+                        (Some(corners), from, to)
+                    },
+                    _ => return Err(CommandError::Usage("/replace [x1 y1 z1 x2 y2 z2] <from> <to>")),
+                };
+                Ok(Command::Replace { corners, from: parse_block(from)?, to: parse_block(to)? })
+            },
+            "copy" => Ok(Command::Copy),
+            "paste" => match args[..] {
+                [] => Ok(Command::Paste(parse_block_position(["~", "~", "~"], origin)?)),
+                [x, y, z] => Ok(Command::Paste(parse_block_position([x, y, z], origin)?)),
+                _ => Err(CommandError::Usage("/paste [x y z]")),
+            },
+            "undo" | "redo" => {
+                let count = match args[..] {
+                    [] => 1,
+                    [count] => count.parse::<usize>()
+                        .ok()
+                        .filter(|&count| count > 0)
+                        .ok_or_else(|| CommandError::InvalidNumber(count.to_string()))?,
+                    _ => return Err(CommandError::Usage(if name == "undo" { "/undo [n]" } else { "/redo [n]" })),
+                };
+                Ok(if name == "undo" { Command::Undo(count) } else { Command::Redo(count) })
+            },
+            "struct" => match args[..] {
+                ["save", name, x1, y1, z1, x2, y2, z2] => Ok(Command::SaveStructure {
+                    name: parse_structure_name(name)?,
+                    from: parse_block_position([x1, y1, z1], origin)?,
+                    to: parse_block_position([x2, y2, z2], origin)?,
+                }),
+                ["place", name] => Ok(Command::PlaceStructure {
+                    name: parse_structure_name(name)?,
+                    origin: parse_block_position(["~", "~", "~"], origin)?,
+                }),
+                ["place", name, x, y, z] => Ok(Command::PlaceStructure {
+                    name: parse_structure_name(name)?,
+                    origin: parse_block_position([x, y, z], origin)?,
+                }),
+                _ => Err(CommandError::Usage(STRUCT_USAGE)),
+            },
+            "give" => {
+                let (block, count) = match args[..] {
+                    [block] => (block, 1),
+                    [block, count] => {
+                        let count = count.parse().map_err(|_| CommandError::InvalidNumber(count.to_string()))?;
+                        (block, count)
+                    },
+                    _ => return Err(CommandError::Usage("/give <block> [count]")),
+                };
+                let block = parse_block(block)?;
+                if block.is_air() {
+                    return Err(CommandError::NotAnItem(block.info().name.to_string()));
+                }
+                Ok(Command::Give { block, count })
+            },
+            "gamemode" => {
+                let [mode] = args[..] else {
+                    return Err(CommandError::Usage("/gamemode <survival|creative>"));
+                };
+                let mode = GameMode::from_name(mode).ok_or_else(|| CommandError::UnknownGameMode(mode.to_string()))?;
+                Ok(Command::SetGameMode(mode))
+            },
+            "spawnpoint" => match args[..] {
+                [] => Ok(Command::SetSpawn(origin)),
+                [x, y, z] => Ok(Command::SetSpawn([
+                    parse_coordinate(x, origin[0])?,
+                    parse_coordinate(y, origin[1])?,
+                    parse_coordinate(z, origin[2])?,
+                ])),
+                _ => Err(CommandError::Usage("/spawnpoint [x y z]")),
+            },
+            "border" => match args[..] {
+                [] => Ok(Command::Border(None)),
+                [radius] => {
+                    let radius = radius.parse::<u32>().map_err(|_| CommandError::InvalidNumber(radius.to_string()))?;
+                    if !(MIN_RADIUS..=MAX_RADIUS).contains(&radius) {
+                        return Err(CommandError::InvalidBorder);
+                    }
+                    Ok(Command::Border(Some(radius)))
+                },
+                _ => Err(CommandError::Usage("/border [radius]")),
+            },
+            "summon" => {
+                let (kind, position) = match args[..] {
+                    [kind] => (kind, origin),
+                    [kind, x, y, z] => (kind, [
+                        parse_coordinate(x, origin[0])?,
+                        parse_coordinate(y, origin[1])?,
+                        parse_coordinate(z, origin[2])?,
+                    ]),
+                    _ => return Err(CommandError::Usage("/summon <pig|zombie> [x y z]")),
+                };
+                let kind = MobKind::from_name(kind).ok_or_else(|| CommandError::UnknownMob(kind.to_string()))?;
+                Ok(Command::Summon { kind, position })
+            },
+            "msg" => {
+                let [to, _, ..] = args[..] else {
+                    return Err(CommandError::Usage("/msg <player> <message>"));
+                };
+                Ok(Command::Message { to: to.to_string(), text: args[1..].join(" ") })
+            },
+            _ => Err(CommandError::Unknown(name.to_string())),
+        }
+    }
+
+    /// Runs the command and returns the feedback to show the user.
+    pub fn execute(self, context: &mut CommandContext) -> Result<String> {
+        match self {
+            Command::Help => Ok(HELP.to_string()),
+            Command::Teleport([x, y, z]) => {
+                if let Some(transform) = context.ecs.transforms.get_mut(context.player) {
+                    transform.teleport((x, y, z).into());
+                }
+                if let Some(velocity) = context.ecs.velocities.get_mut(context.player) {
+                    *velocity = Velocity::default();
+                }
+                if let Some(health) = context.ecs.healths.get_mut(context.player) {
+                    health.reset_fall();
+                }
+                Ok(format!("Teleported to {x:.1} {y:.1} {z:.1}"))
+            },
+            Command::SetTime(time) => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("time"));
+                }
+                context.world.time = time % DAY_LENGTH;
+                Ok(format!("Time set to {}", context.world.time))
+            },
+            Command::SetTimeSpeed(speed) => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("time"));
+                }
+                context.world.time_speed = speed;
+                Ok(format!("Time speed set to {speed}x"))
+            },
+            Command::SetDaylightCycle(cycle) => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("time"));
+                }
+                context.world.daylight_cycle = cycle;
+                Ok(format!("Daylight cycle {}", if cycle { "on" } else { "off" }))
+            },
+            Command::SetWeather { weather, duration } => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("weather"));
+                }
+                context.world.weather.set(weather, duration);
+                Ok(format!("Weather set to {}", weather.name().to_lowercase()))
+            },
+            Command::Seed => Ok(format!("Seed: {}", context.world.generator.seed)),
+            Command::Fill { corners, block } => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("fill"));
+                }
+                let (from, to) = corners.or_else(|| context.edit.selection()).ok_or(CommandError::NoSelection)?;
+                let (min, max) = region(from, to, MAX_FILL_VOLUME)?;
+                let changed = context.history.record(context.edit.fill(context.world, min, max, block));
+                Ok(format!("Filled {changed} blocks with {}", block.info().name))
+            },
+            Command::Replace { corners, from, to } => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("replace"));
+                }
+                let (first, second) = corners.or_else(|| context.edit.selection()).ok_or(CommandError::NoSelection)?;
+                let (min, max) = region(first, second, MAX_FILL_VOLUME)?;
+                let changed = context.history.record(context.edit.replace(context.world, min, max, from, to));
+                Ok(format!("Replaced {changed} {} blocks with {}", from.info().name, to.info().name))
+            },
+            Command::Copy => {
+                let (from, to) = context.edit.selection().ok_or(CommandError::NoSelection)?;
+                let (min, max) = region(from, to, MAX_STRUCTURE_VOLUME)?;
+                let [x, y, z] = context.edit.copy(context.world, min, max);
+                Ok(format!("Copied {x}x{y}x{z} blocks"))
+            },
+            Command::Paste(origin) => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("paste"));
+                }
+                let previous = context.edit.paste(context.world, origin).ok_or(CommandError::EmptyClipboard)?;
+                let changed = context.history.record(previous);
+                Ok(format!("Pasted {changed} blocks"))
+            },
+            Command::Undo(count) => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("undo"));
+                }
+                match context.history.undo(context.world, count) {
+                    (0, _) => Err(CommandError::NothingToUndo),
+                    (edits, changed) => Ok(format!("Undid {}, changing {changed} blocks", edit_count(edits))),
+                }
+            },
+            Command::Redo(count) => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("redo"));
+                }
+                match context.history.redo(context.world, count) {
+                    (0, _) => Err(CommandError::NothingToRedo),
+                    (edits, changed) => Ok(format!("Redid {}, changing {changed} blocks", edit_count(edits))),
+                }
+            },
+            Command::SaveStructure { name, from, to } => {
+                let (min, max) = region(from, to, MAX_STRUCTURE_VOLUME)?;
+                let structure = Schematic::capture(context.world, min, max);
+                structure.save(&name)?;
+                let [x, y, z] = structure.size();
+                Ok(format!("Saved {x}x{y}x{z} structure '{name}'"))
+            },
+            Command::PlaceStructure { name, origin } => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("struct"));
+                }
+                if !Schematic::exists(&name) {
+                    return Err(CommandError::UnknownStructure(name));
+                }
+                let placed = Schematic::load(&name)?.place(context.world, origin);
+                let changed = context.history.record(placed);
+                Ok(format!("Placed {changed} blocks of '{name}'"))
+            },
+            Command::Backup => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("backup"));
+                }
+                let backup = context.save.backup()?;
+                Ok(format!("Backed up the world as {}", backup.file_name()))
+            },
+            Command::Give { block, count } => {
+                // Large counts would only overflow into nothing.
+                let count = count.min(MAX_STACK * INVENTORY_SLOTS as u32);
+                let left = context.inventory.add(block, count);
+                Ok(format!("Gave {} {}", count - left, block.info().name))
+            },
+            Command::SetGameMode(mode) => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("gamemode"));
+                }
+                context.world.game_mode = mode;
+                player::set_game_mode(context.ecs, context.player, mode);
+                Ok(format!("Game mode set to {}", mode.name()))
+            },
+            Command::SetSpawn([x, y, z]) => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("spawnpoint"));
+                }
+                // Players respawn in the overworld, wherever they died.
+                if context.world.dimension() != Dimension::Overworld {
+                    return Err(CommandError::NotInOverworld("spawnpoint"));
+                }
+                context.world.spawn_point = [x, y, z];
+                Ok(format!("Spawn point set to {x:.1} {y:.1} {z:.1}"))
+            },
+            Command::Border(None) => Ok(format!("The border is {} blocks out", context.world.border.radius)),
+            Command::Border(Some(radius)) => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("border"));
+                }
+                // Anything left outside is pulled back in as it next moves.
+                context.world.border = WorldBorder::new(radius);
+                Ok(format!("Border set to {radius} blocks out"))
+            },
+            Command::Summon { kind, position: [x, y, z] } => {
+                if context.client.is_some() {
+                    return Err(CommandError::Online("summon"));
+                }
+                // Summons are seeded by where they land, so each comes out
+                // a little different.
+                let seed = (x.to_bits() ^ y.to_bits().rotate_left(11) ^ z.to_bits().rotate_left(22)).wrapping_add(context.world.time as u32);
+                mob::spawn(context.ecs, kind, (x, y, z).into(), seed);
+                Ok(format!("Summoned a {} at {x:.1} {y:.1} {z:.1}", kind.name()))
+            },
+            Command::Message { to, text } => {
+                let client = context.client.ok_or(CommandError::NotOnline("msg"))?;
+                client.send_whisper(&to, &text);
+                // The server echoes the whisper back into the chat.
+                Ok(String::new())
+            },
+        }
+    }
+}
+
+fn parse_coordinate(value: &str, origin: f32) -> Result<f32> {
+    let invalid = || CommandError::InvalidNumber(value.to_string());
+    match value.strip_prefix('~') {
+        Some("") => Ok(origin),
+        Some(offset) => offset.parse::<f32>().map(|offset| origin + offset).map_err(|_| invalid()),
+        None => value.parse().map_err(|_| invalid()),
+    }
+}
+
+fn parse_block_position(values: [&str ; 3], origin: [f32 ; 3]) -> Result<[i32 ; 3]> {
+    Ok([
+        parse_coordinate(values[0], origin[0])?.floor() as i32,
+        parse_coordinate(values[1], origin[1])?.floor() as i32,
+        parse_coordinate(values[2], origin[2])?.floor() as i32,
+    ])
+}
+
+fn parse_structure_name(name: &str) -> Result<String> {
+    if !schematic::is_valid_name(name) {
+        return Err(CommandError::InvalidStructureName(name.to_string()));
+    }
+    Ok(name.to_string())
+}
+
+// The corners of the box between `from` and `to`, lowest first, as long
+// as it holds no more than `limit` blocks.
+fn region(from: [i32 ; 3], to: [i32 ; 3], limit: i64) -> Result<([i32 ; 3], [i32 ; 3])> {
+    let min = [from[0].min(to[0]), from[1].min(to[1]), from[2].min(to[2])];
+    let max = [from[0].max(to[0]), from[1].max(to[1]), from[2].max(to[2])];
+    let volume = (0..3).map(|axis| (max[axis] - min[axis] + 1) as i64).product::<i64>();
+    if volume > limit {
+        return Err(CommandError::RegionTooLarge { volume, limit });
+    }
+    Ok((min, max))
+}
+
+fn edit_count(edits: usize) -> String {
+    if edits == 1 { "1 edit".to_string() } else { format!("{edits} edits") }
+}
+
+fn parse_block(name: &str) -> Result<BlockId> {
+    BlockId::from_name(name).ok_or_else(|| CommandError::UnknownBlock(name.to_string()))
+}
@@ -0,0 +1,459 @@
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    border,
+    camera::CameraEffect,
+    error::{EngineError, Result},
+    fog::FogMode,
+    frame_limiter::FrameLimit,
+    game_mode::GameMode,
+    input::{Action, Binding},
+    ui::HudElement,
+    window::FullscreenMode,
+    worldgen::WorldType,
+};
+
+pub const CONFIG_PATH: &str = "config.toml";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub graphics: GraphicsConfig,
+    pub controls: ControlsConfig,
+    pub hud: HudConfig,
+    pub world: WorldConfig,
+    pub simulation: SimulationConfig,
+    pub network: NetworkConfig,
+    pub audio: AudioConfig,
+    pub resources: ResourcesConfig,
+    pub dev: DevConfig,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: FullscreenMode,
+    // The mode F11 switches to from windowed.
+    pub preferred_fullscreen: FullscreenMode,
+    // Show the frame rate in the title bar.
+    pub title_fps: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            fullscreen: FullscreenMode::Windowed,
+            preferred_fullscreen: FullscreenMode::Borderless,
+            title_fps: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsConfig {
+    pub vsync: bool,
+    pub render_distance: i32,
+    // How many chunks up and down from the player's are loaded and drawn.
+    pub vertical_distance: i32,
+    // Chunks beyond the render distance and up to this one are drawn as
+    // low-detail terrain; anything not above the render distance disables it.
+    pub lod_distance: i32,
+    pub fov: f32,
+    pub fog: FogMode,
+    pub shadows: bool,
+    // Resolution of each shadow cascade, in texels per side.
+    pub shadow_map_size: u32,
+    // 1 disables multisampling; 2, 4 and 8 are used if the adapter
+    // supports them.
+    pub msaa_samples: u32,
+    // Frustum cull chunks in a compute shader where the adapter can.
+    pub gpu_culling: bool,
+    // The most particles alive at once; 0 turns them off.
+    pub max_particles: u32,
+    // Blend light and ambient occlusion across block faces instead of
+    // lighting each face evenly.
+    pub smooth_lighting: bool,
+    // Applies with or without vsync.
+    pub frame_limit: FrameLimit,
+    // Frames drawn a second while the window is in the background or
+    // minimized; 0 draws them as fast as in the foreground.
+    pub background_fps: u32,
+    pub post_process: PostProcessConfig,
+    pub camera_effects: CameraEffectsConfig,
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        Self {
+            vsync: true,
+            render_distance: 4,
+            vertical_distance: 4,
+            lod_distance: 16,
+            fov: 70.0,
+            fog: FogMode::Linear,
+            shadows: true,
+            shadow_map_size: 2048,
+            msaa_samples: 1,
+            gpu_culling: true,
+            max_particles: 4000,
+            smooth_lighting: true,
+            frame_limit: FrameLimit::Unlimited,
+            background_fps: 10,
+            post_process: PostProcessConfig::default(),
+            camera_effects: CameraEffectsConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostProcessConfig {
+    pub tonemapping: bool,
+    pub exposure: f32,
+    // Applied on top of the display's own encoding; above 1 brightens.
+    pub gamma: f32,
+    pub vignette: bool,
+    pub vignette_strength: f32,
+    pub fxaa: bool,
+}
+
+impl Default for PostProcessConfig {
+    fn default() -> Self {
+        Self {
+            tonemapping: true,
+            exposure: 1.0,
+            gamma: 1.0,
+            vignette: true,
+            vignette_strength: 0.25,
+            fxaa: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraEffectsConfig {
+    pub view_bobbing: bool,
+    pub sprint_fov: bool,
+    pub damage_shake: bool,
+    pub smooth_fov: bool,
+}
+
+impl CameraEffectsConfig {
+    pub fn enabled(&self, effect: CameraEffect) -> bool {
+        match effect {
+            CameraEffect::ViewBobbing => self.view_bobbing,
+            CameraEffect::SprintFov => self.sprint_fov,
+            CameraEffect::DamageShake => self.damage_shake,
+            CameraEffect::SmoothFov => self.smooth_fov,
+        }
+    }
+
+    pub fn toggle(&mut self, effect: CameraEffect) {
+        let enabled = match effect {
+            CameraEffect::ViewBobbing => &mut self.view_bobbing,
+            CameraEffect::SprintFov => &mut self.sprint_fov,
+            CameraEffect::DamageShake => &mut self.damage_shake,
+            CameraEffect::SmoothFov => &mut self.smooth_fov,
+        };
+        *enabled = !*enabled;
+    }
+}
+
+impl Default for CameraEffectsConfig {
+    fn default() -> Self {
+        Self {
+            view_bobbing: true,
+            sprint_fov: true,
+            damage_shake: true,
+            smooth_fov: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudConfig {
+    pub minimap: bool,
+    // Which of `map::MINIMAP_ZOOMS` the minimap is at.
+    pub minimap_zoom: usize,
+    pub compass: bool,
+}
+
+impl HudConfig {
+    pub fn enabled(&self, element: HudElement) -> bool {
+        match element {
+            HudElement::Minimap => self.minimap,
+            HudElement::Compass => self.compass,
+        }
+    }
+
+    pub fn toggle(&mut self, element: HudElement) {
+        let enabled = match element {
+            HudElement::Minimap => &mut self.minimap,
+            HudElement::Compass => &mut self.compass,
+        };
+        *enabled = !*enabled;
+    }
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        Self {
+            minimap: true,
+            minimap_zoom: 1,
+            compass: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+    // Directory under `saves/`; an existing save keeps its own seed.
+    pub name: String,
+    pub seed: u32,
+    // Like the seed, only used when the world is created.
+    pub world_type: WorldType,
+    pub game_mode: GameMode,
+    // Scales how much of the underground is hollowed out; 0 disables caves.
+    pub cave_density: f64,
+    // Blocks from the middle of the world to its border on each side; like
+    // the seed, only used when the world is created.
+    pub border_radius: u32,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            name: "world".to_string(),
+            seed: 0,
+            world_type: WorldType::Normal,
+            game_mode: GameMode::Survival,
+            cave_density: 1.0,
+            border_radius: border::DEFAULT_RADIUS,
+        }
+    }
+}
+
+/// How often the world is simulated, in singleplayer and on a server.
+/// Frames are drawn in between, however fast they come.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    // Ticks a second.
+    pub tick_rate: u32,
+}
+
+impl SimulationConfig {
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.tick_rate.clamp(1, 1000)
+    }
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate: 20,
+        }
+    }
+}
+
+/// Which resource packs to use, from directories under `resourcepacks/`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourcesConfig {
+    // Searched in order, before the built-in assets.
+    pub packs: Vec<String>,
+}
+
+/// Conveniences for working on the game itself.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DevConfig {
+    // Reloads the terrain shader and block textures when they change.
+    pub hot_reload: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    // Each volume is from 0 to 1; the others are scaled by the master
+    // volume.
+    pub master_volume: f32,
+    pub effects_volume: f32,
+    pub ambient_volume: f32,
+    pub music_volume: f32,
+    // What every volume is scaled by while another window has focus; 0
+    // mutes the game.
+    pub background_volume: f32,
+}
+
+impl AudioConfig {
+    pub fn effects(&self) -> f32 {
+        self.master_volume.clamp(0.0, 1.0) * self.effects_volume.clamp(0.0, 1.0)
+    }
+
+    pub fn ambient(&self) -> f32 {
+        self.master_volume.clamp(0.0, 1.0) * self.ambient_volume.clamp(0.0, 1.0)
+    }
+
+    pub fn music(&self) -> f32 {
+        self.master_volume.clamp(0.0, 1.0) * self.music_volume.clamp(0.0, 1.0)
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            effects_volume: 1.0,
+            ambient_volume: 0.5,
+            music_volume: 0.5,
+            background_volume: 0.25,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    // What other players see you as on a server.
+    pub player_name: String,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            player_name: "Player".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlsConfig {
+    pub mouse_sensitivity: f32,
+    // Pushing the mouse away looks down instead of up.
+    pub invert_mouse: bool,
+    // Blocks a second flown, where the game mode lets the player fly.
+    pub fly_speed: f32,
+    // Actions missing from the file keep their default binding.
+    pub bindings: BTreeMap<Action, Binding>,
+    pub gamepad: GamepadConfig,
+}
+
+impl ControlsConfig {
+    pub fn binding(&self, action: Action) -> Binding {
+        self.bindings.get(&action).copied().unwrap_or_else(|| action.default_binding())
+    }
+
+    pub fn default_bindings() -> BTreeMap<Action, Binding> {
+        Action::ALL.into_iter().map(|action| (action, action.default_binding())).collect()
+    }
+}
+
+impl Default for ControlsConfig {
+    fn default() -> Self {
+        Self {
+            mouse_sensitivity: 1.0,
+            invert_mouse: false,
+            fly_speed: 10.0,
+            bindings: Self::default_bindings(),
+            gamepad: GamepadConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    pub enabled: bool,
+    // Stick deflection, from 0 to 1, ignored as drift.
+    pub deadzone: f32,
+    // Exponent applied to stick deflection past the deadzone; above 1
+    // gives finer control near the centre.
+    pub response_curve: f32,
+    // Radians per second turned at full deflection of the look stick.
+    pub look_speed: f32,
+    pub invert_look: bool,
+    pub buttons: BTreeMap<Action, gilrs::Button>,
+    pub hotbar_next: gilrs::Button,
+    pub hotbar_previous: gilrs::Button,
+    pub pause: gilrs::Button,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        use gilrs::Button;
+
+        Self {
+            enabled: true,
+            deadzone: 0.15,
+            response_curve: 2.0,
+            look_speed: 3.0,
+            invert_look: false,
+            buttons: BTreeMap::from([
+                (Action::Jump, Button::South),
+                (Action::Descend, Button::East),
+                (Action::Sprint, Button::LeftThumb),
+                (Action::ToggleFly, Button::North),
+                (Action::Inventory, Button::West),
+                (Action::Break, Button::RightTrigger2),
+                (Action::Place, Button::LeftTrigger2),
+            ]),
+            hotbar_next: Button::RightTrigger,
+            hotbar_previous: Button::LeftTrigger,
+            pause: Button::Start,
+        }
+    }
+}
+
+impl Config {
+    // Missing files are created with the defaults; unreadable ones are
+    // left untouched so a typo doesn't wipe the user's settings.
+    pub fn load_or_create(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            let config = Self::default();
+            match config.save(path) {
+                Ok(()) => info!("wrote default config to {}", path.display()),
+                Err(e) => warn!("could not write default config: {e}"),
+            }
+            return config;
+        }
+
+        match Self::load(path) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("{e}, using defaults");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|source| EngineError::Io { path: path.to_path_buf(), source })?;
+        toml::from_str(&contents)
+            .map_err(|source| EngineError::ConfigParse { path: path.to_path_buf(), source })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let contents = toml::to_string_pretty(self)?;
+        fs::write(path, contents)
+            .map_err(|source| EngineError::Io { path: path.to_path_buf(), source })
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+
+use winit::{
+    event::{ElementState, KeyEvent, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::{
+    input::Binding,
+    text::TextRenderer,
+    ui::{Rect, UiBatch},
+};
+
+const MAX_SCROLLBACK: usize = 200;
+const VISIBLE_LINES: usize = 14;
+const TEXT_SIZE: f32 = 16.0;
+const PADDING: f32 = 8.0;
+
+const TEXT_COLOR: [f32 ; 4] = [1.0, 1.0, 1.0, 1.0];
+const ERROR_COLOR: [f32 ; 4] = [1.0, 0.45, 0.4, 1.0];
+
+pub struct Console {
+    pub open: bool,
+    pub toggle: Binding,
+
+    input: String,
+    scrollback: VecDeque<(String, [f32 ; 4])>,
+    history: Vec<String>,
+    // Position while browsing `history` with the arrow keys.
+    history_index: Option<usize>,
+    submitted: Option<String>,
+}
+
+impl Console {
+    pub fn new(toggle: Binding) -> Self {
+        Self {
+            open: false,
+            toggle,
+
+            input: String::new(),
+            scrollback: VecDeque::new(),
+            history: Vec::new(),
+            history_index: None,
+            submitted: None,
+        }
+    }
+
+    // While open, the console swallows every keyboard event so typing
+    // doesn't also move the player.
+    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
+        if self.toggle.just_pressed(event) {
+            self.open = !self.open;
+            return true;
+        }
+        let WindowEvent::KeyboardInput { event: key_event, .. } = event else {
+            return false;
+        };
+        let KeyEvent { state, physical_key, text, .. } = key_event;
+
+        if !self.open {
+            return false;
+        }
+        if *state == ElementState::Released {
+            return true;
+        }
+
+        match physical_key {
+            PhysicalKey::Code(KeyCode::Escape) => self.open = false,
+            PhysicalKey::Code(KeyCode::Enter | KeyCode::NumpadEnter) => self.submit(),
+            PhysicalKey::Code(KeyCode::Backspace) => {
+                self.input.pop();
+            },
+            PhysicalKey::Code(KeyCode::ArrowUp) => self.browse_history(-1),
+            PhysicalKey::Code(KeyCode::ArrowDown) => self.browse_history(1),
+            _ => {
+                if let Some(text) = text {
+                    self.input.extend(text.chars().filter(|c| !c.is_control()));
+                }
+            },
+        }
+
+        true
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        let line = line.trim();
+        self.history_index = None;
+        if line.is_empty() {
+            return;
+        }
+
+        if self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_string());
+        }
+        self.submitted = Some(line.to_string());
+    }
+
+    fn browse_history(&mut self, step: i32) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let index = match self.history_index {
+            Some(index) => index as i32 + step,
+            None if step < 0 => self.history.len() as i32 - 1,
+            None => return,
+        };
+
+        if index >= self.history.len() as i32 {
+            self.history_index = None;
+            self.input.clear();
+            return;
+        }
+
+        let index = index.max(0) as usize;
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    /// The line entered since the last call, if any.
+    pub fn take_submitted(&mut self) -> Option<String> {
+        self.submitted.take()
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.push_line(line.into(), TEXT_COLOR);
+    }
+
+    pub fn print_error(&mut self, line: impl Into<String>) {
+        self.push_line(line.into(), ERROR_COLOR);
+    }
+
+    fn push_line(&mut self, line: String, color: [f32 ; 4]) {
+        for line in line.lines() {
+            if self.scrollback.len() == MAX_SCROLLBACK {
+                self.scrollback.pop_front();
+            }
+            self.scrollback.push_back((line.to_string(), color));
+        }
+    }
+
+    pub fn draw(&self, batch: &mut UiBatch, text: &mut TextRenderer, screen_size: (u32, u32)) {
+        let line_height = text.line_height(TEXT_SIZE);
+        let height = (VISIBLE_LINES + 1) as f32 * line_height + PADDING * 3.0;
+        batch.quad(Rect::new(0.0, 0.0, screen_size.0 as f32, height), [0.0, 0.0, 0.0, 0.6]);
+
+        let input_top = height - PADDING - line_height;
+        batch.quad(
+            Rect::new(0.0, input_top - PADDING / 2.0, screen_size.0 as f32, line_height + PADDING),
+            [0.0, 0.0, 0.0, 0.4],
+        );
+
+        // Newest lines sit just above the input line.
+        let visible = self.scrollback.iter().rev().take(VISIBLE_LINES);
+        for (i, (line, color)) in visible.enumerate() {
+            let y = input_top - PADDING - (i + 1) as f32 * line_height;
+            text.queue(line.clone(), [PADDING, y], TEXT_SIZE, *color);
+        }
+
+        text.queue(format!("> {}_", self.input), [PADDING, input_top], TEXT_SIZE, TEXT_COLOR);
+    }
+}
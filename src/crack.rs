@@ -0,0 +1,171 @@
+use crate::{block::Cuboid, breaking, texture};
+
+// Lifts the cracks off the block's faces so they don't z-fight with them.
+const OUTSET: f32 = 0.002;
+// Enough for a fence joined on every side, the most boxes any block has.
+const MAX_CUBOIDS: usize = 16;
+const VERTICES_PER_CUBOID: usize = 6 * 6;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CrackVertex {
+    position: [f32 ; 3],
+    // The position on the face, in blocks, lined up across every box of
+    // the block so the cracks run on from one to the next.
+    face_coords: [f32 ; 2],
+    // How far the cracks have spread, from just started at above 0 to 1.
+    spread: f32,
+}
+
+/// Draws cracks over the faces of the block being broken, spreading as the
+/// break progresses.
+pub struct CrackRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    target: Option<([i32 ; 3], u32)>,
+}
+
+impl CrackRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("crack.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Crack Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Crack Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<CrackVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Crack Vertex Buffer"),
+            size: (MAX_CUBOIDS * VERTICES_PER_CUBOID * std::mem::size_of::<CrackVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            vertex_count: 0,
+            target: None,
+        }
+    }
+
+    /// Cracks the block at `target`, drawn with `shape`, up to the given
+    /// stage out of `breaking::CRACK_STAGES`.
+    pub fn set_target(&mut self, queue: &wgpu::Queue, target: Option<([i32 ; 3], u32)>, shape: &[Cuboid]) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+
+        let Some(([x, y, z], stage)) = target else {
+            self.vertex_count = 0;
+            return;
+        };
+
+        let origin = [x as f32, y as f32, z as f32];
+        let vertices = shape.iter()
+            .take(MAX_CUBOIDS)
+            .flat_map(cuboid_faces)
+            .map(|(local, face_coords)| CrackVertex {
+                position: [0, 1, 2].map(|axis| origin[axis] + local[axis]),
+                face_coords,
+                spread: (stage + 1) as f32 / breaking::CRACK_STAGES as f32,
+            })
+            .collect::<Vec<_>>();
+        self.vertex_count = vertices.len() as u32;
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+// Two outward-facing triangles for each side of the box, nudged out by
+// `OUTSET`, with each corner's position inside the block and on its face.
+fn cuboid_faces(cuboid: &Cuboid) -> Vec<([f32 ; 3], [f32 ; 2])> {
+    let min = cuboid.min.map(|v| v - OUTSET);
+    let max = cuboid.max.map(|v| v + OUTSET);
+
+    let mut vertices = Vec::with_capacity(VERTICES_PER_CUBOID);
+    for axis in 0..3 {
+        // The face's other two axes, in the order that winds it
+        // counter-clockwise seen from the positive side.
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for positive in [false, true] {
+            let corner = |a: f32, b: f32| {
+                let mut position = [0.0 ; 3];
+                position[axis] = if positive { max[axis] } else { min[axis] };
+                position[u] = a;
+                position[v] = b;
+                (position, [a, b])
+            };
+            let quad = [
+                corner(min[u], min[v]),
+                corner(max[u], min[v]),
+                corner(max[u], max[v]),
+                corner(min[u], max[v]),
+            ];
+            let order = if positive { [0, 1, 2, 0, 2, 3] } else { [0, 2, 1, 0, 3, 2] };
+            vertices.extend(order.map(|i| quad[i]));
+        }
+    }
+    vertices
+}
@@ -0,0 +1,73 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Pixels per block face, matching the block textures so the cracks look
+// like part of them.
+const PIXELS: f32 = 16.0;
+// Cells the face is split into; cracks run along the cell borders.
+const CELLS: f32 = 3.0;
+const CRACK_WIDTH: f32 = 0.07;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) face_coords: vec2<f32>,
+    @location(2) spread: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) face_coords: vec2<f32>,
+    @location(1) spread: f32,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(in.position, 1.0);
+    out.face_coords = in.face_coords;
+    out.spread = in.spread;
+    return out;
+}
+
+fn hash(p: vec2<f32>) -> vec2<f32> {
+    let q = vec2<f32>(dot(p, vec2<f32>(127.1, 311.7)), dot(p, vec2<f32>(269.5, 183.3)));
+    return fract(sin(q) * 43758.5453);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Snapped to the texture's pixels, then wrapped to the block so each
+    // face cracks the same way.
+    let pixel = (floor(fract(in.face_coords) * PIXELS) + 0.5) / PIXELS;
+    let p = pixel * CELLS;
+
+    // Distance to the nearest and second nearest cell centre; close to
+    // equal means close to the border between two cells.
+    let cell = floor(p);
+    var nearest = 8.0;
+    var second = 8.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = cell + vec2<f32>(f32(x), f32(y));
+            let d = distance(p, neighbour + hash(neighbour));
+            if d < nearest {
+                second = nearest;
+                nearest = d;
+            } else if d < second {
+                second = d;
+            }
+        }
+    }
+
+    // Cracks start from the middle of the face and reach the edges as the
+    // block is about to break.
+    let from_centre = length(pixel - 0.5) * 2.0;
+    if second - nearest > CRACK_WIDTH || from_centre > in.spread * 1.5 {
+        discard;
+    }
+    return vec4<f32>(0.0, 0.0, 0.0, 0.6);
+}
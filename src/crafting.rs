@@ -0,0 +1,272 @@
+//! Turning items into other items. Recipes are loaded once at startup from
+//! `data/recipes/` in the assets. Crafting recipes are matched against
+//! what's laid out in a crafting grid, two by two in the inventory and
+//! three by three at a crafting table; smelting recipes say what a furnace
+//! turns each item into.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use log::info;
+use serde::Deserialize;
+
+use crate::{
+    assets::Assets,
+    block::BlockId,
+    error::{EngineError, Result},
+    inventory::{Inventory, ItemStack, MAX_STACK},
+};
+
+const RECIPE_DIR: &str = "data/recipes";
+/// The largest grid there is, at a crafting table.
+pub const MAX_GRID_SIZE: usize = 3;
+/// The grid in the inventory screen.
+pub const INVENTORY_GRID_SIZE: usize = 2;
+
+static RECIPES: OnceLock<Vec<Recipe>> = OnceLock::new();
+static SMELTING: OnceLock<Vec<SmeltingRecipe>> = OnceLock::new();
+
+/// What a recipe needs laid out in the grid.
+#[derive(Clone, Debug)]
+pub enum Ingredients {
+    /// Blocks in this arrangement, row by row, anywhere in the grid and
+    /// either way round.
+    Shaped { width: usize, height: usize, cells: Vec<Option<BlockId>> },
+    /// Blocks in any arrangement, sorted by id.
+    Shapeless(Vec<BlockId>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Recipe {
+    pub ingredients: Ingredients,
+    pub result: ItemStack,
+}
+
+impl Recipe {
+    // Whether `grid`, a square of the given size, holds exactly what the
+    // recipe needs.
+    fn matches(&self, grid: &[Option<BlockId>], size: usize) -> bool {
+        match &self.ingredients {
+            Ingredients::Shaped { width, height, cells } => {
+                let Some((left, top, used_width, used_height)) = used_area(grid, size) else {
+                    return false;
+                };
+                if (used_width, used_height) != (*width, *height) {
+                    return false;
+                }
+                let cell = |x: usize, y: usize| grid[(top + y) * size + left + x];
+                let fits = |mirrored: bool| (0..*height).all(|y| (0..*width).all(|x| {
+                    let pattern_x = if mirrored { width - 1 - x } else { x };
+                    cells[y * width + pattern_x] == cell(x, y)
+                }));
+                fits(false) || fits(true)
+            },
+            Ingredients::Shapeless(ingredients) => {
+                let mut laid_out = grid.iter().flatten().copied().collect::<Vec<_>>();
+                laid_out.sort_by_key(|block| block.0);
+                laid_out == *ingredients
+            },
+        }
+    }
+}
+
+/// What a furnace makes from one `input`.
+#[derive(Clone, Debug)]
+pub struct SmeltingRecipe {
+    pub input: BlockId,
+    pub result: ItemStack,
+}
+
+// The left, top, width and height of the smallest rectangle holding
+// everything in the grid, or `None` if it's empty.
+fn used_area(grid: &[Option<BlockId>], size: usize) -> Option<(usize, usize, usize, usize)> {
+    let used = (0..grid.len()).filter(|&i| grid[i].is_some()).map(|i| (i % size, i / size));
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+    let mut any = false;
+    for (x, y) in used {
+        any = true;
+        (min_x, min_y) = (min_x.min(x), min_y.min(y));
+        (max_x, max_y) = (max_x.max(x), max_y.max(y));
+    }
+    any.then(|| (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+}
+
+/// Every loaded crafting recipe. Panics if `load` hasn't succeeded yet.
+pub fn recipes() -> &'static [Recipe] {
+    RECIPES.get().expect("recipes used before they were loaded")
+}
+
+/// What smelting one `input` makes, if it smelts.
+pub fn smelting_result(input: BlockId) -> Option<ItemStack> {
+    let smelting = SMELTING.get().expect("recipes used before they were loaded");
+    smelting.iter().find(|recipe| recipe.input == input).map(|recipe| recipe.result)
+}
+
+/// Reads the recipes, which name blocks and so need the block registry
+/// loaded first. Does nothing if they were already loaded.
+pub fn load(assets: &Assets) -> Result<()> {
+    if RECIPES.get().is_some() {
+        return Ok(());
+    }
+    let (recipes, smelting) = load_recipes(assets)?;
+    info!("loaded {} crafting and {} smelting recipes", recipes.len(), smelting.len());
+    let _ = RECIPES.set(recipes);
+    let _ = SMELTING.set(smelting);
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum RecipeDef {
+    Shaped {
+        pattern: Vec<String>,
+        key: HashMap<char, String>,
+        result: ResultDef,
+    },
+    Shapeless {
+        ingredients: Vec<String>,
+        result: ResultDef,
+    },
+    Smelting {
+        input: String,
+        result: ResultDef,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ResultDef {
+    item: String,
+    #[serde(default = "default_count")]
+    count: u32,
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+fn load_recipes(assets: &Assets) -> Result<(Vec<Recipe>, Vec<SmeltingRecipe>)> {
+    let mut recipes = Vec::new();
+    let mut smelting = Vec::new();
+    for (relative, path) in assets.files(RECIPE_DIR) {
+        if relative.extension().is_none_or(|ext| ext != "ron") {
+            continue;
+        }
+        let text = fs::read_to_string(&path).map_err(|source| EngineError::Io { path: path.clone(), source })?;
+        let defs: Vec<RecipeDef> = ron::from_str(&text).map_err(|e| invalid(&path, e.to_string()))?;
+        for def in defs {
+            match def {
+                RecipeDef::Smelting { input, result } => {
+                    let input = block(&path, &input)?;
+                    if smelting.iter().any(|recipe: &SmeltingRecipe| recipe.input == input) {
+                        return Err(invalid(&path, format!("`{}` is smelted by more than one recipe", input.info().name)));
+                    }
+                    smelting.push(SmeltingRecipe { input, result: parse_result(&path, result)? });
+                },
+                def => recipes.push(parse_recipe(&path, def)?),
+            }
+        }
+    }
+    Ok((recipes, smelting))
+}
+
+fn block(path: &Path, name: &str) -> Result<BlockId> {
+    BlockId::from_name(name).ok_or_else(|| invalid(path, format!("`{name}` isn't a block")))
+}
+
+fn parse_result(path: &Path, result: ResultDef) -> Result<ItemStack> {
+    if !(1..=MAX_STACK).contains(&result.count) {
+        return Err(invalid(path, format!("a recipe makes 1 to {MAX_STACK} items, not {}", result.count)));
+    }
+    Ok(ItemStack::new(block(path, &result.item)?, result.count))
+}
+
+fn parse_recipe(path: &Path, def: RecipeDef) -> Result<Recipe> {
+    let block = |name: &str| block(path, name);
+
+    let (ingredients, result) = match def {
+        RecipeDef::Shaped { pattern, key, result } => {
+            let height = pattern.len();
+            let width = pattern.first().map_or(0, |row| row.chars().count());
+            if !(1..=MAX_GRID_SIZE).contains(&height) || !(1..=MAX_GRID_SIZE).contains(&width) {
+                return Err(invalid(path, format!("a pattern has to be 1 to {MAX_GRID_SIZE} rows and columns, not {pattern:?}")));
+            }
+            if pattern.iter().any(|row| row.chars().count() != width) {
+                return Err(invalid(path, format!("the rows of pattern {pattern:?} aren't all the same length")));
+            }
+            let mut cells = Vec::with_capacity(width * height);
+            for c in pattern.iter().flat_map(|row| row.chars()) {
+                cells.push(match c {
+                    ' ' => None,
+                    c => Some(block(key.get(&c).ok_or_else(|| invalid(path, format!("`{c}` in pattern {pattern:?} isn't in its key")))?)?),
+                });
+            }
+            // Blank edges would stop the pattern from matching anything,
+            // since it's compared with only the used part of the grid.
+            if used_area(&cells, width) != Some((0, 0, width, height)) {
+                return Err(invalid(path, format!("pattern {pattern:?} has an empty row or column at its edge")));
+            }
+            (Ingredients::Shaped { width, height, cells }, result)
+        },
+        RecipeDef::Shapeless { ingredients, result } => {
+            if !(1..=MAX_GRID_SIZE * MAX_GRID_SIZE).contains(&ingredients.len()) {
+                return Err(invalid(path, format!("a shapeless recipe takes 1 to {} ingredients", MAX_GRID_SIZE * MAX_GRID_SIZE)));
+            }
+            let mut blocks = ingredients.iter().map(|name| block(name)).collect::<Result<Vec<_>>>()?;
+            blocks.sort_by_key(|block| block.0);
+            (Ingredients::Shapeless(blocks), result)
+        },
+        RecipeDef::Smelting { .. } => unreachable!("smelting recipes are parsed on their own"),
+    };
+    Ok(Recipe { ingredients, result: parse_result(path, result)? })
+}
+
+fn invalid(path: &Path, reason: String) -> EngineError {
+    EngineError::GameData { path: PathBuf::from(path), reason }
+}
+
+/// A square of slots items are laid out in to be crafted.
+pub struct CraftingGrid {
+    size: usize,
+    pub slots: Inventory,
+}
+
+impl CraftingGrid {
+    pub fn new(size: usize) -> Self {
+        Self { size, slots: Inventory::with_slots(size * size) }
+    }
+
+    /// Slots along each side.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// What the items laid out would make.
+    pub fn result(&self) -> Option<ItemStack> {
+        let grid = (0..self.slots.slot_count()).map(|slot| self.slots.get(slot).map(|stack| stack.block)).collect::<Vec<_>>();
+        recipes().iter().find(|recipe| recipe.matches(&grid, self.size)).map(|recipe| recipe.result)
+    }
+
+    /// Makes the result once, using up one item from every slot.
+    pub fn craft(&mut self) -> Option<ItemStack> {
+        let result = self.result()?;
+        for slot in 0..self.slots.slot_count() {
+            self.slots.remove_one(slot);
+        }
+        Some(result)
+    }
+
+    /// Moves everything laid out into `inventory`. Items that don't fit are
+    /// lost.
+    pub fn clear_into(&mut self, inventory: &mut Inventory) {
+        for slot in 0..self.slots.slot_count() {
+            if let Some(stack) = self.slots.take(slot) {
+                inventory.add(stack.block, stack.count);
+            }
+        }
+    }
+}
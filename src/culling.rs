@@ -0,0 +1,230 @@
+use crate::{
+    camera::Frustum,
+    gpu::{reserve, write_growing},
+    indirect::DrawArgs,
+};
+
+pub const CULL: u32 = 1;
+pub const COMPACT: u32 = 2;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullUniform {
+    planes: [[f32 ; 4] ; 6],
+    draw_count: u32,
+    _padding: [u32 ; 3],
+}
+
+/// One candidate draw: its arguments, the batch it belongs to, `CULL` and
+/// `COMPACT` flags, and the bounds it's tested with.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawInput {
+    pub args: DrawArgs,
+    pub batch: u32,
+    pub flags: u32,
+    _padding: u32,
+    pub min: [f32 ; 3],
+    _padding_min: f32,
+    pub max: [f32 ; 3],
+    _padding_max: f32,
+}
+
+impl DrawInput {
+    pub fn new(args: DrawArgs, batch: u32, flags: u32, min: [f32 ; 3], max: [f32 ; 3]) -> Self {
+        Self { args, batch, flags, _padding: 0, min, _padding_min: 0.0, max, _padding_max: 0.0 }
+    }
+}
+
+/// Frustum culls chunk draws in a compute shader and writes the visible
+/// ones to an indirect buffer, so the CPU never walks the chunk list for
+/// visibility. Compacted batches put their survivors first and record how
+/// many there are in `counts`, ready for `multi_draw_indexed_indirect_count`.
+pub struct GpuCuller {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    inputs: Option<wgpu::Buffer>,
+    batch_starts: Option<wgpu::Buffer>,
+    outputs: Option<wgpu::Buffer>,
+    counts: Option<wgpu::Buffer>,
+    bind_group: Option<wgpu::BindGroup>,
+    draw_count: u32,
+}
+
+impl GpuCuller {
+    /// Needs compute shaders on top of indirect draws.
+    pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+        let flags = adapter.get_downlevel_capabilities().flags;
+        flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION)
+    }
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("culling.wgsl"));
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, false),
+                storage(4, false),
+            ],
+            label: Some("culling_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Culling Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Culling Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Culling Uniform Buffer"),
+            size: std::mem::size_of::<CullUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            inputs: None,
+            batch_starts: None,
+            outputs: None,
+            counts: None,
+            bind_group: None,
+            draw_count: 0,
+        }
+    }
+
+    /// Uploads this frame's candidate draws and the frustum to test them
+    /// against. `batch_starts` holds the first draw index of each batch.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        inputs: &[DrawInput],
+        batch_starts: &[u32],
+        frustum: &Frustum,
+    ) {
+        self.draw_count = inputs.len() as u32;
+        if inputs.is_empty() {
+            return;
+        }
+
+        let uniform = CullUniform {
+            planes: frustum.planes.map(Into::into),
+            draw_count: self.draw_count,
+            _padding: [0 ; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let mut replaced = write_growing(
+            device,
+            queue,
+            &mut self.inputs,
+            inputs,
+            wgpu::BufferUsages::STORAGE,
+            "Culling Input Buffer",
+        );
+        replaced |= write_growing(
+            device,
+            queue,
+            &mut self.batch_starts,
+            batch_starts,
+            wgpu::BufferUsages::STORAGE,
+            "Culling Batch Buffer",
+        );
+        // The outputs and counts are cleared on the GPU each frame, so only
+        // their size matters here.
+        replaced |= reserve(
+            device,
+            &mut self.outputs,
+            (inputs.len() * std::mem::size_of::<DrawArgs>()) as wgpu::BufferAddress,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+            "Culled Indirect Buffer",
+        );
+        replaced |= reserve(
+            device,
+            &mut self.counts,
+            std::mem::size_of_val(batch_starts) as wgpu::BufferAddress,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+            "Culled Count Buffer",
+        );
+
+        if replaced || self.bind_group.is_none() {
+            self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: self.uniform_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: self.inputs.as_ref().unwrap().as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: self.batch_starts.as_ref().unwrap().as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 3, resource: self.outputs.as_ref().unwrap().as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 4, resource: self.counts.as_ref().unwrap().as_entire_binding() },
+                ],
+                label: Some("culling_bind_group"),
+            }));
+        }
+    }
+
+    /// Records the culling dispatch. Must run before the passes that draw
+    /// from `outputs`.
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        let (Some(outputs), Some(counts), Some(bind_group)) = (&self.outputs, &self.counts, &self.bind_group) else {
+            return;
+        };
+        if self.draw_count == 0 {
+            return;
+        }
+
+        encoder.clear_buffer(outputs, 0, None);
+        encoder.clear_buffer(counts, 0, None);
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Culling Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch_workgroups(self.draw_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    pub fn outputs(&self) -> Option<&wgpu::Buffer> {
+        self.outputs.as_ref()
+    }
+
+    pub fn counts(&self) -> Option<&wgpu::Buffer> {
+        self.counts.as_ref()
+    }
+}
@@ -0,0 +1,76 @@
+// Frustum culls chunk draws and writes the survivors' arguments to the
+// indirect buffer the terrain passes draw from.
+
+const CULL: u32 = 1u;
+const COMPACT: u32 = 2u;
+
+struct CullUniform {
+    planes: array<vec4<f32>, 6>,
+    draw_count: u32,
+}
+
+struct DrawInput {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+    batch: u32,
+    flags: u32,
+    min: vec3<f32>,
+    max: vec3<f32>,
+}
+
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0) var<uniform> cull: CullUniform;
+@group(0) @binding(1) var<storage, read> inputs: array<DrawInput>;
+// The first draw of each batch.
+@group(0) @binding(2) var<storage, read> batch_starts: array<u32>;
+@group(0) @binding(3) var<storage, read_write> outputs: array<DrawArgs>;
+// Draws written to each compacted batch.
+@group(0) @binding(4) var<storage, read_write> counts: array<atomic<u32>>;
+
+fn intersects_frustum(box_min: vec3<f32>, box_max: vec3<f32>) -> bool {
+    for (var i = 0u; i < 6u; i++) {
+        let plane = cull.planes[i];
+        let corner = select(box_min, box_max, plane.xyz >= vec3<f32>(0.0));
+        if dot(plane.xyz, corner) + plane.w < 0.0 {
+            return false;
+        }
+    }
+    return true;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= cull.draw_count {
+        return;
+    }
+
+    let draw = inputs[index];
+    if (draw.flags & CULL) != 0u && !intersects_frustum(draw.min, draw.max) {
+        return;
+    }
+
+    // Compacted batches pack their visible draws at the front; ordered
+    // ones keep every draw in place and leave culled slots zeroed.
+    var slot = index;
+    if (draw.flags & COMPACT) != 0u {
+        slot = batch_starts[draw.batch] + atomicAdd(&counts[draw.batch], 1u);
+    }
+    outputs[slot] = DrawArgs(
+        draw.index_count,
+        draw.instance_count,
+        draw.first_index,
+        draw.base_vertex,
+        draw.first_instance,
+    );
+}
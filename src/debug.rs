@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use winit::event::WindowEvent;
+
+use crate::{
+    block::BlockState,
+    camera::Camera,
+    chunk::ChunkPos,
+    chunk_debug::ChunkDebugMode,
+    input::Binding,
+    light::Light,
+    text::TextRenderer,
+};
+
+const SAMPLE_WINDOW: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub vertices: u32,
+}
+
+pub struct FrameInfo<'a> {
+    pub camera: &'a Camera,
+    pub biome: &'static str,
+    // At the camera.
+    pub light: Light,
+    // The block under the crosshair, if any is in reach.
+    pub target: Option<([i32 ; 3], BlockState)>,
+    pub loaded_chunks: usize,
+    pub lod_chunks: usize,
+    pub chunk_debug: ChunkDebugMode,
+    // None when chunks are culled on the GPU.
+    pub visible_chunks: Option<usize>,
+    pub chunk_memory: usize,
+    // Bytes used and reserved in the chunk mesh pool.
+    pub mesh_memory: (u64, u64),
+    // Particles alive and the most there can be.
+    pub particles: (usize, usize),
+    pub present_mode: wgpu::PresentMode,
+    pub render_stats: RenderStats,
+}
+
+pub struct DebugOverlay {
+    pub visible: bool,
+    pub toggle: Binding,
+
+    frames: u32,
+    elapsed: Duration,
+    fps: f32,
+    frame_time_ms: f32,
+}
+
+impl DebugOverlay {
+    pub fn new(toggle: Binding) -> Self {
+        Self {
+            visible: false,
+            toggle,
+
+            frames: 0,
+            elapsed: Duration::ZERO,
+            fps: 0.0,
+            frame_time_ms: 0.0,
+        }
+    }
+
+    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
+        if !self.toggle.just_pressed(event) {
+            return false;
+        }
+        self.visible = !self.visible;
+        true
+    }
+
+    // Averages frame timings over a short window so the numbers are
+    // readable instead of flickering every frame.
+    pub fn record_frame(&mut self, dt: Duration) {
+        self.frames += 1;
+        self.elapsed += dt;
+
+        if self.elapsed >= SAMPLE_WINDOW {
+            let seconds = self.elapsed.as_secs_f32();
+            self.fps = self.frames as f32 / seconds;
+            self.frame_time_ms = seconds * 1000.0 / self.frames as f32;
+            self.frames = 0;
+            self.elapsed = Duration::ZERO;
+        }
+    }
+
+    /// The frame rate, averaged over the last sample window.
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    pub fn draw(&self, text: &mut TextRenderer, info: &FrameInfo) {
+        if !self.visible {
+            return;
+        }
+
+        let camera = info.camera;
+        let eye = camera.eye;
+        let (block_x, block_y, block_z) = (eye.x.floor() as i32, eye.y.floor() as i32, eye.z.floor() as i32);
+        let chunk = ChunkPos::from_block(block_x, block_y, block_z);
+
+        let lines = [
+            format!("{:.0} fps ({:.2} ms) {:?}", self.fps, self.frame_time_ms, info.present_mode),
+            format!("XYZ: {:.2} / {:.2} / {:.2}", eye.x, eye.y, eye.z),
+            format!("Block: {block_x} {block_y} {block_z}"),
+            format!("Chunk: {} {} {}", chunk.x, chunk.y, chunk.z),
+            format!("Biome: {}", info.biome),
+            format!(
+                "Light: {} sky, {}/{}/{} block",
+                info.light.sky, info.light.block[0], info.light.block[1], info.light.block[2],
+            ),
+            match info.target {
+                Some(([x, y, z], state)) => format!("Looking at: {state} ({x} {y} {z})"),
+                None => "Looking at: nothing".to_string(),
+            },
+            format!(
+                "Facing: yaw {:.1}° pitch {:.1}°",
+                cgmath::Deg::from(camera.yaw).0.rem_euclid(360.0),
+                cgmath::Deg::from(camera.pitch).0,
+            ),
+            format!(
+                "Loaded chunks: {} ({:.1} MiB)",
+                info.loaded_chunks,
+                info.chunk_memory as f32 / (1024.0 * 1024.0),
+            ),
+            format!("LOD chunks: {}", info.lod_chunks),
+            format!("Chunk borders: {}", info.chunk_debug.name()),
+            match info.visible_chunks {
+                Some(visible) => format!("Visible chunks: {visible}"),
+                None => "Visible chunks: culled on GPU".to_string(),
+            },
+            format!(
+                "Mesh pool: {:.1} / {:.1} MiB",
+                info.mesh_memory.0 as f32 / (1024.0 * 1024.0),
+                info.mesh_memory.1 as f32 / (1024.0 * 1024.0),
+            ),
+            format!("Particles: {} / {}", info.particles.0, info.particles.1),
+            format!("Draw calls: {}", info.render_stats.draw_calls),
+            format!("Vertices: {}", info.render_stats.vertices),
+        ];
+
+        let size = 16.0;
+        let line_height = text.line_height(size);
+        for (i, line) in lines.into_iter().enumerate() {
+            let position = [8.0, 8.0 + i as f32 * line_height];
+            // Drop shadow keeps the text legible over bright terrain.
+            text.queue(line.clone(), [position[0] + 1.0, position[1] + 1.0], size, [0.0, 0.0, 0.0, 0.8]);
+            text.queue(line, position, size, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Dimensions: separate worlds within one game, each with its own terrain,
+//! sky and chunks on disk, joined by portals. The clock, game mode, spawn
+//! point, border and weather belong to the game as a whole and go along
+//! with the player between them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{fog, sky::{self, SkySettings}};
+
+/// Which of a game's worlds something is in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dimension {
+    /// Where games start: open sky, days and nights, and weather.
+    #[default]
+    Overworld,
+    /// A closed world of caverns under a rock roof, lit only by what glows
+    /// in it. Each block across it is eight in the overworld.
+    Nether,
+}
+
+impl Dimension {
+    pub const ALL: [Dimension ; 2] = [Dimension::Overworld, Dimension::Nether];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Dimension::Overworld => "Overworld",
+            Dimension::Nether => "Nether",
+        }
+    }
+
+    /// Where a portal here leads.
+    pub fn portal_destination(self) -> Dimension {
+        match self {
+            Dimension::Overworld => Dimension::Nether,
+            Dimension::Nether => Dimension::Overworld,
+        }
+    }
+
+    /// How many overworld blocks one block across here stands for.
+    pub fn scale(self) -> f32 {
+        match self {
+            Dimension::Overworld => 1.0,
+            Dimension::Nether => 8.0,
+        }
+    }
+
+    /// Whether there's open sky overhead, with a sun, weather, and a
+    /// surface worth mapping and drawing far off.
+    pub fn has_sky(self) -> bool {
+        self == Dimension::Overworld
+    }
+
+    pub fn sky(self) -> SkySettings {
+        match self {
+            Dimension::Overworld => SkySettings {
+                zenith_color: sky::ZENITH_COLOR,
+                horizon_color: fog::FOG_COLOR,
+                sun: true,
+                ambient_light: 0.0,
+                fog_start: fog::FOG_START,
+            },
+            Dimension::Nether => SkySettings {
+                zenith_color: [0.2, 0.04, 0.03],
+                horizon_color: [0.32, 0.08, 0.05],
+                sun: false,
+                ambient_light: 0.3,
+                fog_start: 0.1,
+            },
+        }
+    }
+
+    /// The directory its chunks are saved in, under the world's. The
+    /// overworld's are at the top, where saves from before there were
+    /// dimensions keep theirs.
+    pub fn save_dir(self) -> Option<&'static str> {
+        match self {
+            Dimension::Overworld => None,
+            Dimension::Nether => Some("nether"),
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use cgmath::{Point3, Vector3};
+
+use crate::{
+    block::{BlockId, BlockState},
+    events::EventBus,
+    explosion::Fuse,
+    falling::FallingBlock,
+    health::Health,
+    hunger::Hunger,
+    item::DroppedItem,
+    mob::{Mob, MobKind},
+    physics::{self, Aabb},
+    piston::MovingBlock,
+    player::Player,
+    player_model::PlayerAnimation,
+    world::World,
+};
+
+const GRAVITY: f32 = 28.0;
+const TERMINAL_VELOCITY: f32 = 60.0;
+// How much of a swimming entity floats below the surface once it settles;
+// fluids push up harder the deeper it goes, so it bobs there a little
+// first. The drag is the fraction of speed lost a second in a fluid.
+const FLOAT_DEPTH: f32 = 0.7;
+const BUOYANCY: f32 = GRAVITY / FLOAT_DEPTH;
+const FLUID_DRAG: f32 = 4.0;
+// How far down a sneaking entity still finds ground to step onto, enough
+// for slabs and stairs; any further is an edge it won't walk off.
+const SNEAK_DROP: f32 = 0.6;
+
+/// A handle to a game object. Handles to despawned entities stay invalid
+/// even after their slot is reused, thanks to the generation counter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+/// Sparse storage for one component type, indexed by entity slot.
+pub struct ComponentStore<T> {
+    slots: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Default for ComponentStore<T> {
+    fn default() -> Self {
+        Self { slots: Vec::new() }
+    }
+}
+
+impl<T> ComponentStore<T> {
+    pub fn insert(&mut self, entity: Entity, component: T) {
+        let index = entity.index as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+        self.slots[index] = Some((entity.generation, component));
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slots.get_mut(entity.index as usize)?;
+        match slot {
+            Some((generation, _)) if *generation == entity.generation => slot.take().map(|(_, c)| c),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.slots.get(entity.index as usize)? {
+            Some((generation, component)) if *generation == entity.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index as usize)? {
+            Some((generation, component)) if *generation == entity.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref().map(|(generation, component)| {
+                (Entity { index: index as u32, generation: *generation }, component)
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(index, slot)| {
+            slot.as_mut().map(|(generation, component)| {
+                (Entity { index: index as u32, generation: *generation }, component)
+            })
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    /// Bottom center of the entity.
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    /// Where the entity was at the start of the current tick; frames
+    /// between ticks are drawn part of the way from here to `position`.
+    pub previous: Point3<f32>,
+}
+
+impl Transform {
+    pub fn new(position: Point3<f32>) -> Self {
+        Self { position, yaw: 0.0, previous: position }
+    }
+
+    /// Moves the entity to `position` at once, without being drawn in
+    /// between.
+    pub fn teleport(&mut self, position: Point3<f32>) {
+        self.position = position;
+        self.previous = position;
+    }
+
+    /// Where the entity is drawn, `alpha` of the way through the current
+    /// tick.
+    pub fn interpolated(&self, alpha: f32) -> Point3<f32> {
+        self.previous + (self.position - self.previous) * alpha
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Velocity(pub Vector3<f32>);
+
+impl Default for Velocity {
+    fn default() -> Self {
+        Self(Vector3::new(0.0, 0.0, 0.0))
+    }
+}
+
+/// An upright box standing on the entity's position, moved through the
+/// world by `physics_system`.
+#[derive(Clone, Copy, Debug)]
+pub struct Collider {
+    pub half_width: f32,
+    pub height: f32,
+    pub gravity: bool,
+    // With collisions off the entity moves freely through blocks.
+    pub collides: bool,
+    // How high a ledge the entity walks up without jumping.
+    pub step_height: f32,
+    pub on_ground: bool,
+    // Whether fluids hold the entity up and slow it down.
+    pub swims: bool,
+    /// How much of the entity is in a fluid, from 0 to 1, as of the last
+    /// physics step; only measured for entities that swim.
+    pub submerged: f32,
+    // Keeps the entity from walking off the edge of what it stands on.
+    pub sneaking: bool,
+    // Whether ladders hold the entity up, and whether it's on one as of
+    // the last physics step; something else moves it up and down them.
+    pub climbs: bool,
+    pub climbing: bool,
+}
+
+impl Collider {
+    pub fn new(half_width: f32, height: f32) -> Self {
+        Self {
+            half_width,
+            height,
+            gravity: true,
+            collides: true,
+            step_height: 0.0,
+            on_ground: false,
+            swims: false,
+            submerged: 0.0,
+            sneaking: false,
+            climbs: false,
+            climbing: false,
+        }
+    }
+
+    pub fn aabb(&self, position: Point3<f32>) -> Aabb {
+        Aabb::new(
+            position + Vector3::new(-self.half_width, 0.0, -self.half_width),
+            position + Vector3::new(self.half_width, self.height, self.half_width),
+        )
+    }
+}
+
+/// What to draw for an entity.
+#[derive(Clone, Copy, Debug)]
+pub enum MeshRef {
+    // A scaled-down cube with the block's textures.
+    Block(BlockId),
+    // A cube the size of a block, like lit TNT.
+    FullBlock(BlockId),
+    // A block in its own shape, like one a piston is pushing.
+    State(BlockState),
+    // A player, drawn with the player model.
+    Player,
+    Mob(MobKind),
+}
+
+/// Owns every entity and its components. Each component type has its own
+/// store; systems are plain functions over the stores they need.
+#[derive(Default)]
+pub struct Ecs {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+
+    pub transforms: ComponentStore<Transform>,
+    pub velocities: ComponentStore<Velocity>,
+    pub colliders: ComponentStore<Collider>,
+    pub meshes: ComponentStore<MeshRef>,
+    pub players: ComponentStore<Player>,
+    pub items: ComponentStore<DroppedItem>,
+    pub animations: ComponentStore<PlayerAnimation>,
+    pub healths: ComponentStore<Health>,
+    pub hungers: ComponentStore<Hunger>,
+    pub mobs: ComponentStore<Mob>,
+    pub fuses: ComponentStore<Fuse>,
+    pub falling_blocks: ComponentStore<FallingBlock>,
+    pub moving_blocks: ComponentStore<MovingBlock>,
+
+    /// Kept with the entities so any system can publish without being
+    /// handed the bus.
+    pub events: EventBus,
+}
+
+impl Ecs {
+    pub fn spawn(&mut self) -> Entity {
+        if let Some(index) = self.free.pop() {
+            self.alive[index as usize] = true;
+            return Entity { index, generation: self.generations[index as usize] };
+        }
+
+        let index = self.generations.len() as u32;
+        self.generations.push(0);
+        self.alive.push(true);
+        Entity { index, generation: 0 }
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index as usize;
+        self.alive.get(index) == Some(&true) && self.generations[index] == entity.generation
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        self.transforms.remove(entity);
+        self.velocities.remove(entity);
+        self.colliders.remove(entity);
+        self.meshes.remove(entity);
+        self.players.remove(entity);
+        self.items.remove(entity);
+        self.animations.remove(entity);
+        self.healths.remove(entity);
+        self.hungers.remove(entity);
+        self.mobs.remove(entity);
+        self.fuses.remove(entity);
+        self.falling_blocks.remove(entity);
+        self.moving_blocks.remove(entity);
+
+        let index = entity.index as usize;
+        self.alive[index] = false;
+        self.generations[index] += 1;
+        self.free.push(entity.index);
+    }
+
+    /// Remembers where every entity is before a tick moves them.
+    pub fn start_tick(&mut self) {
+        for (_, transform) in self.transforms.iter_mut() {
+            transform.previous = transform.position;
+        }
+    }
+}
+
+/// Applies gravity and moves every entity with a collider, resolving
+/// collisions against the world and keeping it inside the world border.
+pub fn physics_system(ecs: &mut Ecs, world: &World, dt: f32) {
+    let Ecs { transforms, velocities, colliders, .. } = ecs;
+
+    for (entity, collider) in colliders.iter_mut() {
+        let (Some(transform), Some(Velocity(velocity))) = (transforms.get_mut(entity), velocities.get_mut(entity)) else {
+            continue;
+        };
+
+        collider.submerged = if collider.swims {
+            physics::submersion(world, &collider.aabb(transform.position))
+        } else {
+            0.0
+        };
+        collider.climbing = collider.climbs
+            && physics::touches(world, &collider.aabb(transform.position), BlockId::is_climbable);
+        if collider.climbing {
+            // Whatever steers the entity sets how it moves on the ladder.
+        } else if collider.gravity && collider.submerged > 0.0 {
+            velocity.y += (BUOYANCY * collider.submerged - GRAVITY) * dt;
+            *velocity *= (-FLUID_DRAG * dt).exp();
+        } else if collider.gravity {
+            velocity.y = (velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+        }
+
+        if !collider.collides {
+            transform.position += *velocity * dt;
+            collider.on_ground = false;
+            stop_at_border(world, collider, &mut transform.position, velocity);
+            continue;
+        }
+
+        // Only something standing on the ground can step up onto a ledge.
+        let step_height = if collider.on_ground { collider.step_height } else { 0.0 };
+        let aabb = collider.aabb(transform.position);
+        let mut delta = *velocity * dt;
+        if collider.sneaking && collider.on_ground {
+            delta = physics::keep_on_edge(world, aabb, delta, SNEAK_DROP);
+        }
+        let result = physics::move_and_step(world, aabb, delta, step_height);
+        transform.position += result.offset;
+
+        collider.on_ground = result.blocked[1] && velocity.y < 0.0;
+        for axis in 0..3 {
+            if result.blocked[axis] {
+                velocity[axis] = 0.0;
+            }
+        }
+        stop_at_border(world, collider, &mut transform.position, velocity);
+    }
+}
+
+// The border stops everything, even what goes through blocks.
+fn stop_at_border(world: &World, collider: &Collider, position: &mut Point3<f32>, velocity: &mut Vector3<f32>) {
+    let [x, z] = world.border.clamp(position, collider.half_width);
+    if x {
+        velocity.x = 0.0;
+    }
+    if z {
+        velocity.z = 0.0;
+    }
+}
@@ -0,0 +1,161 @@
+use cgmath::{EuclideanSpace, Matrix4, Rad, Vector3};
+
+use crate::{
+    block::Face,
+    debug::RenderStats,
+    ecs::{Ecs, Entity, MeshRef, Transform},
+    explosion::Fuse,
+    gpu::write_growing,
+    item::ITEM_SIZE,
+    light::Light,
+    mesher::ChunkMesh,
+    mob,
+    player_model::{self, Skin},
+    world::World,
+};
+
+// Lit TNT switches between flashing and not this often, and grows by this
+// fraction over its last moments.
+const FUSE_BLINK_TIME: f32 = 0.25;
+const FUSE_FLASH_BRIGHTNESS: f32 = 2.5;
+const FUSE_SWELL_TIME: f32 = 0.5;
+const FUSE_SWELL: f32 = 0.3;
+
+/// Draws every entity with a `MeshRef` through the terrain pipeline. The
+/// geometry is rebuilt on the CPU each frame into buffers that only grow.
+pub struct EntityRenderer {
+    mesh: ChunkMesh,
+    vertex_buffer: Option<wgpu::Buffer>,
+    index_buffer: Option<wgpu::Buffer>,
+    num_indices: u32,
+    index_format: wgpu::IndexFormat,
+}
+
+impl EntityRenderer {
+    pub fn new() -> Self {
+        Self {
+            mesh: ChunkMesh::default(),
+            vertex_buffer: None,
+            index_buffer: None,
+            num_indices: 0,
+            index_format: wgpu::IndexFormat::Uint16,
+        }
+    }
+
+    /// Rebuilds the geometry for every entity except `hidden`, which is
+    /// the local player while the camera is inside their head, `alpha` of
+    /// the way through the current tick. Each is lit by the light in the
+    /// block around its middle, if there's a world.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        world: Option<&World>,
+        ecs: &Ecs,
+        hidden: Option<Entity>,
+        alpha: f32,
+    ) {
+        self.mesh.vertices.clear();
+        self.mesh.indices.clear();
+
+        for (entity, mesh) in ecs.meshes.iter() {
+            if Some(entity) == hidden {
+                continue;
+            }
+            let Some(&transform) = ecs.transforms.get(entity) else {
+                continue;
+            };
+            let transform = Transform { position: transform.interpolated(alpha), ..transform };
+            let transform = &transform;
+
+            let first = self.mesh.vertices.len();
+            match mesh {
+                MeshRef::Block(block) => {
+                    let bob = ecs.items.get(entity).map_or(0.0, |item| item.bob());
+                    let position = transform.position + Vector3::new(0.0, bob, 0.0);
+                    // Scale around the cube's bottom center so it rests on
+                    // the entity's position.
+                    let model = Matrix4::from_translation(position.to_vec())
+                        * Matrix4::from_angle_y(Rad(transform.yaw))
+                        * Matrix4::from_scale(ITEM_SIZE)
+                        * Matrix4::from_translation(Vector3::new(-0.5, 0.0, -0.5));
+                    self.mesh.push_transformed_cube(*block, model, 1.0);
+                },
+                MeshRef::FullBlock(block) => {
+                    let (scale, brightness) = ecs.fuses.get(entity).map_or((1.0, 1.0), fuse_flash);
+                    let model = Matrix4::from_translation(transform.position.to_vec())
+                        * Matrix4::from_scale(scale)
+                        * Matrix4::from_translation(Vector3::new(-0.5, 0.0, -0.5));
+                    self.mesh.push_transformed_cube(*block, model, brightness);
+                },
+                MeshRef::State(state) => {
+                    let corner = transform.position.to_vec() + Vector3::new(-0.5, 0.0, -0.5);
+                    for cuboid in state.shape([false ; 4]) {
+                        let [sx, sy, sz] = [0, 1, 2].map(|axis| cuboid.max[axis] - cuboid.min[axis]);
+                        let model = Matrix4::from_translation(corner + Vector3::from(cuboid.min))
+                            * Matrix4::from_nonuniform_scale(sx, sy, sz);
+                        self.mesh.push_transformed_box(Face::ALL.map(|face| state.texture(face).0), model);
+                    }
+                },
+                MeshRef::Player => {
+                    player_model::push_model(&mut self.mesh, transform, ecs.animations.get(entity), &Skin::player(), false);
+                },
+                MeshRef::Mob(kind) => {
+                    mob::push_model(&mut self.mesh, *kind, transform, ecs.animations.get(entity));
+                },
+            }
+
+            let height = ecs.colliders.get(entity).map_or(0.0, |collider| collider.height);
+            let middle = transform.position + Vector3::new(0.0, height / 2.0, 0.0);
+            let [x, y, z] = [middle.x, middle.y, middle.z].map(|v| v.floor() as i32);
+            self.mesh.relight(first, world.map_or(Light::SKY, |world| world.light(x, y, z)));
+        }
+
+        self.num_indices = self.mesh.indices.len() as u32;
+        self.index_format = self.mesh.index_format();
+        if self.num_indices == 0 {
+            return;
+        }
+
+        write_growing(
+            device,
+            queue,
+            &mut self.vertex_buffer,
+            &self.mesh.vertices,
+            wgpu::BufferUsages::VERTEX,
+            "Entity Vertex Buffer",
+        );
+        write_growing(
+            device,
+            queue,
+            &mut self.index_buffer,
+            &self.mesh.index_bytes(),
+            wgpu::BufferUsages::INDEX,
+            "Entity Index Buffer",
+        );
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, stats: &mut RenderStats) {
+        let (Some(vertex_buffer), Some(index_buffer)) = (&self.vertex_buffer, &self.index_buffer) else {
+            return;
+        };
+        if self.num_indices == 0 {
+            return;
+        }
+
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), self.index_format);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+
+        stats.draw_calls += 1;
+        stats.vertices += self.mesh.vertices.len() as u32;
+    }
+}
+
+// How big and bright lit TNT is drawn: it blinks while the fuse burns and
+// swells just before it goes off.
+fn fuse_flash(fuse: &Fuse) -> (f32, f32) {
+    let blink = (fuse.remaining / FUSE_BLINK_TIME) as i32 % 2 == 0;
+    let swell = (1.0 - fuse.remaining / FUSE_SWELL_TIME).clamp(0.0, 1.0);
+    (1.0 + swell.powi(4) * FUSE_SWELL, if blink { FUSE_FLASH_BRIGHTNESS } else { 1.0 })
+}
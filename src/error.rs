@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EngineError {
+    #[error("could not start the event loop: {0}")]
+    EventLoop(#[from] winit::error::EventLoopError),
+
+    #[error("could not create the game window: {0}")]
+    Window(#[from] winit::error::OsError),
+
+    #[error("could not create a rendering surface: {0}")]
+    Surface(#[from] wgpu::CreateSurfaceError),
+
+    #[error("no compatible graphics adapter was found")]
+    NoAdapter,
+
+    #[error("the graphics adapter cannot present to this window")]
+    UnsupportedSurface,
+
+    #[error("could not open the graphics device: {0}")]
+    Device(#[from] wgpu::RequestDeviceError),
+
+    #[error("could not load texture `{label}`: {source}")]
+    Texture {
+        label: String,
+        source: image::ImageError,
+    },
+
+    #[error("invalid texture `{label}`: {reason}")]
+    InvalidTexture {
+        label: String,
+        reason: String,
+    },
+
+    #[error("could not load font: {0}")]
+    Font(String),
+
+    #[error("could not read or write {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("could not parse {path}: {source}")]
+    ConfigParse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("invalid game data in {path}: {reason}")]
+    GameData {
+        path: PathBuf,
+        reason: String,
+    },
+
+    #[error("mod `{name}` failed to load: {reason}")]
+    Mod {
+        name: String,
+        reason: String,
+    },
+
+    #[error("could not serialize config: {0}")]
+    ConfigSerialize(#[from] toml::ser::Error),
+
+    #[error("corrupt save file {path}: {source}")]
+    SaveParse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("corrupt chunk file {path}: {reason}")]
+    CorruptChunk {
+        path: PathBuf,
+        reason: String,
+    },
+
+    #[error("corrupt map file {path}: {reason}")]
+    CorruptMap {
+        path: PathBuf,
+        reason: String,
+    },
+
+    #[error("corrupt schematic file {path}: {reason}")]
+    CorruptSchematic {
+        path: PathBuf,
+        reason: String,
+    },
+
+    #[error("{path} was saved by a newer version of the game, in save format {version}; this one reads up to {supported}")]
+    FutureSave {
+        path: PathBuf,
+        version: u32,
+        supported: u32,
+    },
+
+    #[error("could not read or write backup {path}: {source}")]
+    Backup {
+        path: PathBuf,
+        source: zip::result::ZipError,
+    },
+
+    #[error("could not serialize {path}: {source}")]
+    SaveSerialize {
+        path: PathBuf,
+        source: toml::ser::Error,
+    },
+
+    #[error("could not reach {address}: {source}")]
+    Network {
+        address: String,
+        source: std::io::Error,
+    },
+
+    #[error("disconnected from the server: {0}")]
+    Disconnected(String),
+}
+
+pub type Result<T> = std::result::Result<T, EngineError>;
@@ -0,0 +1,54 @@
+//! Things that happen in the game, for whatever cares about them to react
+//! to without the code that made them happen calling it. Systems publish
+//! to a bus as they go, and once a frame the events are taken and handed
+//! to each subscriber in turn: sounds, particles, camera effects and mods.
+
+use crate::{
+    block::BlockId,
+    chunk::ChunkPos,
+    ecs::Entity,
+    health::DamageSource,
+    input::Action,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// The player put a block down.
+    BlockPlaced { block: [i32 ; 3], id: BlockId },
+    /// The player broke a block, which was `id`.
+    BlockBroken { block: [i32 ; 3], id: BlockId },
+    ChunkLoaded { pos: ChunkPos },
+    /// A player lost `amount` half hearts.
+    PlayerDamaged { entity: Entity, amount: u32, source: DamageSource },
+    /// A bound action was pressed or released, not counting key repeats.
+    KeyAction { action: Action, pressed: bool },
+}
+
+impl Event {
+    /// What mods subscribe to it as.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::BlockPlaced { .. } => "block_placed",
+            Event::BlockBroken { .. } => "block_broken",
+            Event::ChunkLoaded { .. } => "chunk_loaded",
+            Event::PlayerDamaged { .. } => "player_damaged",
+            Event::KeyAction { .. } => "key_action",
+        }
+    }
+}
+
+/// Events published since they were last taken, oldest first.
+#[derive(Debug, Default)]
+pub struct EventBus {
+    events: Vec<Event>,
+}
+
+impl EventBus {
+    pub fn publish(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    pub fn take(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+}
@@ -0,0 +1,219 @@
+//! Explosions and the TNT that sets them off. A blast sends rays out in
+//! every direction that weaken as they travel and as blocks soak them up,
+//! breaking whatever they still have the strength for, then throws back
+//! and hurts everything nearby that it can see.
+
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::{
+    block::BlockId,
+    ecs::{Collider, Ecs, Entity, MeshRef, Transform, Velocity},
+    health::{self, DamageSource},
+    item,
+    random::Random,
+    world::World,
+};
+
+/// How strong a TNT blast is.
+pub const TNT_POWER: f32 = 4.0;
+// Seconds from lighting TNT to it going off, and for TNT set off by
+// another blast, which goes sooner and not all at once.
+const TNT_FUSE: f32 = 4.0;
+const CHAIN_FUSE: (f32, f32) = (0.5, 1.5);
+// Lit TNT hops up a little as it's lit.
+const PRIME_HOP: f32 = 4.0;
+
+// Rays leave through this many points along each edge of a cube around
+// the center, and take steps this long.
+const RAYS_PER_EDGE: i32 = 16;
+const RAY_STEP: f32 = 0.3;
+// Each ray starts at the power times a random factor in this range, and
+// loses this much strength per block travelled on top of what blocks
+// soak up.
+const RAY_STRENGTH: (f32, f32) = (0.7, 1.3);
+const RAY_FALLOFF: f32 = 0.75;
+// Entities are hurt and thrown out to this many times the power away.
+const REACH_PER_POWER: f32 = 2.0;
+// Speed given to an entity right at the center with nothing in the way.
+const KNOCKBACK: f32 = 16.0;
+// Half hearts taken from an entity at the center, per unit of power.
+const DAMAGE_PER_POWER: f32 = 14.0;
+
+/// Lit TNT burning down to its explosion.
+#[derive(Clone, Copy, Debug)]
+pub struct Fuse {
+    /// Seconds left before it goes off.
+    pub remaining: f32,
+    power: f32,
+}
+
+/// Lights the TNT at `[x, y, z]` with the usual fuse, turning it into an
+/// entity that goes off once the fuse burns down. The block itself has to
+/// be removed by the caller.
+pub fn light(ecs: &mut Ecs, block: [i32 ; 3]) -> Entity {
+    prime(ecs, block, TNT_FUSE)
+}
+
+fn prime(ecs: &mut Ecs, [x, y, z]: [i32 ; 3], fuse: f32) -> Entity {
+    let entity = ecs.spawn();
+    let position = Point3::new(x as f32 + 0.5, y as f32, z as f32 + 0.5);
+    ecs.transforms.insert(entity, Transform::new(position));
+    ecs.velocities.insert(entity, Velocity(Vector3::new(0.0, PRIME_HOP, 0.0)));
+    ecs.colliders.insert(entity, Collider::new(0.49, 0.98));
+    ecs.meshes.insert(entity, MeshRef::FullBlock(BlockId::TNT));
+    ecs.fuses.insert(entity, Fuse { remaining: fuse, power: TNT_POWER });
+    entity
+}
+
+/// Burns every fuse down by `dt` seconds, removing the ones that ran out
+/// and returning where they went off and how strongly.
+pub fn fuse_system(ecs: &mut Ecs, dt: f32) -> Vec<(Point3<f32>, f32)> {
+    let mut spent = Vec::new();
+    for (entity, fuse) in ecs.fuses.iter_mut() {
+        fuse.remaining -= dt;
+        if fuse.remaining <= 0.0 {
+            spent.push((entity, fuse.power));
+        }
+    }
+
+    let mut explosions = Vec::new();
+    for (entity, power) in spent {
+        if let (Some(transform), Some(collider)) = (ecs.transforms.get(entity), ecs.colliders.get(entity)) {
+            explosions.push((transform.position + Vector3::new(0.0, collider.height / 2.0, 0.0), power));
+        }
+        ecs.despawn(entity);
+    }
+    explosions
+}
+
+/// Blows up `power` worth of explosion at `center`: breaks the blocks it's
+/// strong enough for, lights any TNT among them, and throws back and hurts
+/// the entities around it. Each broken block drops with a chance of one
+/// in `power`, and containers always spill what they held.
+pub fn explode(world: &mut World, ecs: &mut Ecs, center: Point3<f32>, power: f32) {
+    // Seeded from where it happens, so the same blast breaks the same
+    // blocks.
+    let seed = center.x.to_bits() ^ center.y.to_bits().rotate_left(11) ^ center.z.to_bits().rotate_left(22);
+    let mut random = Random::new(seed);
+
+    let broken = blocks_in_reach(world, center, power, &mut random);
+    for &[x, y, z] in &broken {
+        let block = world.get_block(x, y, z);
+        let contents = world.block_entity(x, y, z).map(|entity| entity.contents());
+        if !world.set_block(x, y, z, BlockId::AIR) {
+            continue;
+        }
+        if block == BlockId::TNT {
+            prime(ecs, [x, y, z], random.range(CHAIN_FUSE));
+            continue;
+        }
+        if world.game_mode.infinite_blocks() {
+            continue;
+        }
+        if let Some(drop) = block.info().drops.filter(|_| random.float() * power < 1.0) {
+            item::spawn_dropped_item(ecs, drop, x, y, z);
+        }
+        item::spawn_dropped_stacks(ecs, &contents.unwrap_or_default(), x, y, z);
+    }
+
+    push_entities(world, ecs, center, power);
+}
+
+// Every block one of the rays is still strong enough to break when it
+// gets there.
+fn blocks_in_reach(world: &World, center: Point3<f32>, power: f32, random: &mut Random) -> Vec<[i32 ; 3]> {
+    let last = RAYS_PER_EDGE - 1;
+    let mut broken = Vec::new();
+    for i in 0..RAYS_PER_EDGE {
+        for j in 0..RAYS_PER_EDGE {
+            for k in 0..RAYS_PER_EDGE {
+                // Only the points on the cube's surface.
+                if ![i, j, k].iter().any(|&n| n == 0 || n == last) {
+                    continue;
+                }
+                let direction = Vector3::new(i, j, k)
+                    .map(|n| n as f32 / last as f32 * 2.0 - 1.0)
+                    .normalize();
+
+                let mut strength = power * random.range(RAY_STRENGTH);
+                let mut position = center;
+                while strength > 0.0 {
+                    let block = [position.x, position.y, position.z].map(|v| v.floor() as i32);
+                    let [x, y, z] = block;
+                    let id = world.get_block(x, y, z);
+                    if !id.is_air() {
+                        strength -= (id.info().resistance + RAY_STEP) * RAY_STEP;
+                        if strength > 0.0 && !id.is_fluid() {
+                            broken.push(block);
+                        }
+                    }
+                    position += direction * RAY_STEP;
+                    strength -= RAY_FALLOFF * RAY_STEP;
+                }
+            }
+        }
+    }
+    broken.sort_unstable();
+    broken.dedup();
+    broken
+}
+
+// Throws every entity in reach away from the center and hurts those with
+// health, each by how close it is and how much of it the blast can see.
+fn push_entities(world: &World, ecs: &mut Ecs, center: Point3<f32>, power: f32) {
+    let reach = power * REACH_PER_POWER;
+    let in_reach = ecs.colliders.iter()
+        .filter_map(|(entity, collider)| {
+            let aabb = collider.aabb(ecs.transforms.get(entity)?.position);
+            let middle = aabb.min + (aabb.max - aabb.min) / 2.0;
+            let offset = middle - center;
+            let distance = offset.magnitude();
+            if distance >= reach {
+                return None;
+            }
+            let impact = (1.0 - distance / reach) * exposure(world, center, aabb.min, aabb.max);
+            let direction = if distance > 0.0 { offset / distance } else { Vector3::unit_y() };
+            Some((entity, direction, impact))
+        })
+        .collect::<Vec<_>>();
+
+    for (entity, direction, impact) in in_reach {
+        if impact <= 0.0 {
+            continue;
+        }
+        if let Some(Velocity(velocity)) = ecs.velocities.get_mut(entity) {
+            *velocity += direction * impact * KNOCKBACK;
+        }
+        // Players only get hurt where the game mode lets them.
+        let is_player = ecs.players.get(entity).is_some();
+        if is_player && !world.game_mode.takes_damage() {
+            continue;
+        }
+        let amount = ((impact * impact + impact) / 2.0 * DAMAGE_PER_POWER * power + 1.0) as u32;
+        health::damage(ecs, entity, amount, DamageSource::Explosion);
+    }
+}
+
+// The fraction of points spread through the box that have a clear line
+// to the center, from 0 when it's fully behind cover to 1 in the open.
+fn exposure(world: &World, center: Point3<f32>, min: Point3<f32>, max: Point3<f32>) -> f32 {
+    const STEPS: i32 = 3;
+    let mut seen = 0;
+    for i in 0..STEPS {
+        for j in 0..STEPS {
+            for k in 0..STEPS {
+                let t = Vector3::new(i, j, k).map(|n| n as f32 / (STEPS - 1) as f32);
+                let point = Point3::new(
+                    min.x + (max.x - min.x) * t.x,
+                    min.y + (max.y - min.y) * t.y,
+                    min.z + (max.z - min.z) * t.z,
+                );
+                let to_center = center - point;
+                if world.raycast(point, to_center, to_center.magnitude()).is_none() {
+                    seen += 1;
+                }
+            }
+        }
+    }
+    seen as f32 / (STEPS * STEPS * STEPS) as f32
+}
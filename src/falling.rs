@@ -0,0 +1,104 @@
+//! Blocks that fall, like sand and gravel. One left with nothing solid
+//! under it turns into an entity that drops with the usual physics, and
+//! becomes a block again where it lands; if it comes down on something it
+//! can't sit on top of, such as a slab or a torch, it breaks into its drop
+//! instead.
+
+use cgmath::{Point3, Vector3};
+
+use crate::{
+    block::{BlockId, BlockModel, BlockState},
+    ecs::{Collider, Ecs, MeshRef, Transform, Velocity},
+    item,
+    world::World,
+};
+
+/// A block on its way down.
+#[derive(Clone, Copy, Debug)]
+pub struct FallingBlock {
+    pub state: BlockState,
+}
+
+// Whether a block gives way to one falling into it.
+fn can_fall_into(block: BlockId) -> bool {
+    block == BlockId::AIR || block.is_fluid() || block.info().model == BlockModel::Cross
+}
+
+// Whether the top of `block` is whole, so a block can come to rest on it.
+fn can_land_on(block: BlockId) -> bool {
+    block.is_solid() && block.info().model == BlockModel::Cube
+}
+
+// The blocks changed near since the last tick that now have to fall.
+fn unsupported(world: &mut World) -> Vec<[i32 ; 3]> {
+    let mut blocks = world.take_fall_checks();
+    blocks.retain(|&[x, y, z]| y > 0 && world.get_block(x, y, z).info().falls && !world.get_block(x, y - 1, z).is_solid());
+    // Bottom up, so a column comes down in order.
+    blocks.sort_by_key(|&[_, y, _]| y);
+    blocks
+}
+
+/// Turns blocks that lost their support into falling entities, and lands
+/// the falling ones that have come to rest.
+pub fn falling_system(ecs: &mut Ecs, world: &mut World) {
+    for [x, y, z] in unsupported(world) {
+        let state = world.get_state(x, y, z);
+        if !world.set_block(x, y, z, BlockId::AIR) {
+            continue;
+        }
+        let entity = ecs.spawn();
+        ecs.transforms.insert(entity, Transform::new(Point3::new(x as f32 + 0.5, y as f32, z as f32 + 0.5)));
+        ecs.velocities.insert(entity, Velocity(Vector3::new(0.0, 0.0, 0.0)));
+        ecs.colliders.insert(entity, Collider::new(0.49, 0.98));
+        ecs.meshes.insert(entity, MeshRef::FullBlock(state.block));
+        ecs.falling_blocks.insert(entity, FallingBlock { state });
+    }
+
+    let mut landed = Vec::new();
+    for (entity, falling) in ecs.falling_blocks.iter() {
+        let (Some(transform), Some(collider)) = (ecs.transforms.get(entity), ecs.colliders.get(entity)) else {
+            continue;
+        };
+        if collider.on_ground || transform.position.y < 0.0 {
+            landed.push((entity, falling.state, transform.position));
+        }
+    }
+
+    for (entity, state, position) in landed {
+        ecs.despawn(entity);
+        if position.y < 0.0 {
+            continue;
+        }
+        // It rests on whatever is under its bottom, which may be partway
+        // up a block, so it goes in the block its middle is in.
+        let [x, y, z] = [position.x.floor() as i32, (position.y + 0.5).floor() as i32, position.z.floor() as i32];
+        if can_fall_into(world.get_block(x, y, z)) && can_land_on(world.get_block(x, y - 1, z)) {
+            world.set_voxel(x, y, z, state, 0);
+            continue;
+        }
+        if !world.game_mode.infinite_blocks() {
+            if let Some(drop) = state.block.info().drops {
+                item::spawn_dropped_item(ecs, drop, x, y, z);
+            }
+        }
+    }
+}
+
+/// Drops blocks that lost their support straight to where they'd land,
+/// for the server, which has no entities to let them fall as. Those that
+/// can't land there are lost, as there's nothing to drop them as either.
+pub fn settle(world: &mut World) {
+    for [x, y, z] in unsupported(world) {
+        let state = world.get_state(x, y, z);
+        if !world.set_block(x, y, z, BlockId::AIR) {
+            continue;
+        }
+        let mut bottom = y;
+        while bottom > 0 && !world.get_block(x, bottom - 1, z).is_solid() {
+            bottom -= 1;
+        }
+        if bottom > 0 && can_fall_into(world.get_block(x, bottom, z)) && can_land_on(world.get_block(x, bottom - 1, z)) {
+            world.set_voxel(x, bottom, z, state, 0);
+        }
+    }
+}
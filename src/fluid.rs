@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    block::BlockId,
+    chunk::ChunkPos,
+    world::World,
+};
+
+/// Level of a fluid source block; flowing fluid counts down from here.
+pub const SOURCE_LEVEL: u8 = 8;
+
+// Fluid pouring straight down. It spreads at close to full reach once it
+// lands instead of having lost a level per block fallen.
+const FALLING_LEVEL: u8 = 7;
+
+const TICK_INTERVAL: f32 = 0.2;
+const UPDATES_PER_TICK: usize = 256;
+// Don't try to catch up on more than this many ticks after a long frame.
+const MAX_TICKS_PER_UPDATE: u32 = 4;
+
+/// Height of a fluid block's surface for a given level, in blocks.
+pub fn surface_height(level: u8) -> f32 {
+    level as f32 / (SOURCE_LEVEL + 1) as f32
+}
+
+/// Positions of fluid blocks that need to be re-evaluated, grouped by chunk
+/// so each tick works through a few chunks at a time.
+#[derive(Default)]
+pub struct FluidQueue {
+    chunks: HashMap<ChunkPos, HashSet<[i32 ; 3]>>,
+    // Chunks in the order they'll be serviced; round-robin so one busy
+    // chunk can't starve the rest.
+    order: VecDeque<ChunkPos>,
+}
+
+impl FluidQueue {
+    pub fn schedule(&mut self, x: i32, y: i32, z: i32) {
+        let pos = ChunkPos::from_block(x, y, z);
+        let updates = self.chunks.entry(pos).or_insert_with(|| {
+            self.order.push_back(pos);
+            HashSet::new()
+        });
+        updates.insert([x, y, z]);
+    }
+
+    pub fn take(&mut self, budget: usize) -> Vec<[i32 ; 3]> {
+        let mut batch = Vec::new();
+
+        while batch.len() < budget {
+            let Some(pos) = self.order.pop_front() else {
+                break;
+            };
+            let Some(updates) = self.chunks.get_mut(&pos) else {
+                continue;
+            };
+
+            let count = (budget - batch.len()).min(updates.len());
+            let taken = updates.iter().take(count).copied().collect::<Vec<_>>();
+            for update in &taken {
+                updates.remove(update);
+            }
+            batch.extend(taken);
+
+            if updates.is_empty() {
+                self.chunks.remove(&pos);
+            } else {
+                self.order.push_back(pos);
+            }
+        }
+
+        batch
+    }
+}
+
+/// Steps fluid flow at a fixed rate with a bounded amount of work per tick.
+#[derive(Default)]
+pub struct FluidSimulator {
+    accumulator: f32,
+}
+
+impl FluidSimulator {
+    pub fn update(&mut self, world: &mut World, dt: f32) {
+        self.accumulator = (self.accumulator + dt).min(TICK_INTERVAL * MAX_TICKS_PER_UPDATE as f32);
+
+        while self.accumulator >= TICK_INTERVAL {
+            self.accumulator -= TICK_INTERVAL;
+            // Blocks changed during this tick schedule their neighbours for
+            // the next one, so flow advances one block per tick.
+            for [x, y, z] in world.take_fluid_updates(UPDATES_PER_TICK) {
+                update_block(world, x, y, z);
+            }
+        }
+    }
+}
+
+// Fluids wash away plants as well as filling air.
+fn can_flow_into(block: BlockId) -> bool {
+    !block.is_solid() && !block.is_fluid()
+}
+
+// Fluid only spreads sideways once it rests on something that holds it: a
+// solid block or a pool of sources. Otherwise it just pours down.
+fn is_supported(world: &World, x: i32, y: i32, z: i32) -> bool {
+    let below = world.get_block(x, y - 1, z);
+    below.is_solid() || (below.is_fluid() && world.fluid_level(x, y - 1, z) == SOURCE_LEVEL)
+}
+
+const HORIZONTAL: [(i32, i32) ; 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+// The level a flowing block should have given its surroundings; zero means
+// it should drain away.
+fn expected_level(world: &World, fluid: BlockId, x: i32, y: i32, z: i32) -> u8 {
+    if world.get_block(x, y + 1, z) == fluid {
+        return FALLING_LEVEL;
+    }
+
+    let mut sources = 0;
+    let mut level = 0;
+    for (dx, dz) in HORIZONTAL {
+        let (nx, nz) = (x + dx, z + dz);
+        if world.get_block(nx, y, nz) != fluid {
+            continue;
+        }
+
+        let neighbor_level = world.fluid_level(nx, y, nz);
+        if neighbor_level == SOURCE_LEVEL {
+            sources += 1;
+        }
+        if is_supported(world, nx, y, nz) {
+            level = level.max(neighbor_level.saturating_sub(1));
+        }
+    }
+
+    // Flowing fluid between two sources merges into a new source.
+    if sources >= 2 && is_supported(world, x, y, z) {
+        return SOURCE_LEVEL;
+    }
+
+    level
+}
+
+fn update_block(world: &mut World, x: i32, y: i32, z: i32) {
+    let fluid = world.get_block(x, y, z);
+    if !fluid.is_fluid() {
+        return;
+    }
+
+    let mut level = world.fluid_level(x, y, z);
+    if level != SOURCE_LEVEL {
+        let expected = expected_level(world, fluid, x, y, z);
+        if expected == 0 {
+            world.set_block(x, y, z, BlockId::AIR);
+            return;
+        }
+        if expected != level {
+            world.set_block_with_level(x, y, z, fluid, expected);
+            level = expected;
+        }
+    }
+
+    if can_flow_into(world.get_block(x, y - 1, z)) {
+        world.set_block_with_level(x, y - 1, z, fluid, FALLING_LEVEL);
+        return;
+    }
+
+    if !is_supported(world, x, y, z) {
+        return;
+    }
+
+    let next = level - 1;
+    if next == 0 {
+        return;
+    }
+
+    for (dx, dz) in HORIZONTAL {
+        let (nx, nz) = (x + dx, z + dz);
+        let neighbor = world.get_block(nx, y, nz);
+        let fills = can_flow_into(neighbor)
+            || (neighbor == fluid && world.fluid_level(nx, y, nz) < next);
+        if fills {
+            world.set_block_with_level(nx, y, nz, fluid, next);
+        }
+    }
+}
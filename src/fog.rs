@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{chunk::CHUNK_SIZE, sky::SkySettings};
+
+/// Sky color the terrain fades into; the sky's horizon uses the same color
+/// so the fogged edge and the background meet without a seam.
+pub const FOG_COLOR: [f32 ; 3] = [0.62, 0.76, 0.92];
+/// What the fog and the horizon turn to under rain clouds.
+pub const RAIN_FOG_COLOR: [f32 ; 3] = [0.5, 0.54, 0.6];
+/// Where fog starts in the overworld, as a fraction of the way to where
+/// it's thickest, in clear weather.
+pub const FOG_START: f32 = 0.6;
+// Where it starts in full rain.
+const RAIN_FOG_START: f32 = 0.2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FogMode {
+    #[default]
+    Linear,
+    Exponential,
+}
+
+impl FogMode {
+    fn id(self) -> u32 {
+        match self {
+            FogMode::Linear => 0,
+            FogMode::Exponential => 1,
+        }
+    }
+}
+
+/// Per-frame shading parameters shared by the terrain pipelines.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FrameUniform {
+    pub camera_position: [f32 ; 3],
+    pub fog_mode: u32,
+    pub fog_color: [f32 ; 3],
+    pub fog_start: f32,
+    pub fog_end: f32,
+    pub fog_density: f32,
+    pub daylight: f32,
+    // The chunk debug view's tint and clock, for the terrain shader.
+    pub chunk_tint: u32,
+    pub debug_time: f32,
+    /// The least light anything has, as a fraction of the brightest.
+    pub ambient_light: f32,
+    _padding: [f32 ; 2],
+}
+
+/// Distance in blocks at which terrain `render_distance` chunks away is
+/// fully fogged: one chunk before the edge, so missing neighbours are never
+/// visible.
+pub fn view_distance(render_distance: i32) -> f32 {
+    ((render_distance - 1).max(1) * CHUNK_SIZE as i32) as f32
+}
+
+impl FrameUniform {
+    pub fn new(mode: FogMode, render_distance: i32) -> Self {
+        let fog_end = view_distance(render_distance);
+
+        Self {
+            camera_position: [0.0; 3],
+            fog_mode: mode.id(),
+            fog_color: FOG_COLOR,
+            fog_start: fog_end * FOG_START,
+            fog_end,
+            fog_density: clear_density(fog_end),
+            daylight: 1.0,
+            chunk_tint: 0,
+            debug_time: 0.0,
+            ambient_light: 0.0,
+            _padding: [0.0; 2],
+        }
+    }
+
+    pub fn update_camera(&mut self, camera: &crate::camera::Camera) {
+        self.camera_position = camera.eye.into();
+    }
+
+    /// Colors the fog and sets the ambient light for the sky in `sky`,
+    /// dims sky light and the fog with it, to match the sky, and greys and
+    /// thickens the fog as far as rain has set in, by `wetness`.
+    pub fn update_weather(&mut self, sky: &SkySettings, daylight: f32, wetness: f32) {
+        self.daylight = daylight;
+        self.ambient_light = sky.ambient_light;
+        self.fog_color = rain_tinted(sky.horizon_color, wetness).map(|channel| channel * daylight);
+        self.fog_start = self.fog_end * (sky.fog_start + (RAIN_FOG_START - sky.fog_start) * wetness);
+        self.fog_density = clear_density(self.fog_end) * (1.0 + wetness);
+    }
+}
+
+/// `color` mixed towards the rain clouds' grey by `wetness`.
+pub fn rain_tinted(color: [f32 ; 3], wetness: f32) -> [f32 ; 3] {
+    let mut tinted = color;
+    for (channel, rain) in tinted.iter_mut().zip(RAIN_FOG_COLOR) {
+        *channel += (rain - *channel) * wetness;
+    }
+    tinted
+}
+
+// Exponential fog this dense, exp(-(d * density)^2), drops below 1% at
+// `fog_end`.
+fn clear_density(fog_end: f32) -> f32 {
+    (100.0f32).ln().sqrt() / fog_end
+}
@@ -0,0 +1,138 @@
+//! Caps the frame rate whether or not vsync is on, so a fast GPU doesn't
+//! draw hundreds of frames nobody sees. Frames are paced against a running
+//! deadline rather than the time since the last one, so the rate holds on
+//! average even when single frames run long.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+// Sleeps overshoot by up to a millisecond or so, depending on the
+// platform's timer, so the end of each wait is spun out instead.
+const SPIN_MARGIN: Duration = Duration::from_micros(1500);
+// Caps the options screen goes through, in order.
+const STEPS: [FrameLimit ; 7] = [
+    FrameLimit::Monitor,
+    FrameLimit::Fps(30),
+    FrameLimit::Fps(60),
+    FrameLimit::Fps(120),
+    FrameLimit::Fps(144),
+    FrameLimit::Fps(240),
+    FrameLimit::Unlimited,
+];
+
+/// How many frames a second are drawn at most. In the settings file it's
+/// `"unlimited"`, `"monitor"` or a number.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "SavedFrameLimit", into = "SavedFrameLimit")]
+pub enum FrameLimit {
+    #[default]
+    Unlimited,
+    /// The refresh rate of the monitor the window is on, or unlimited
+    /// where that isn't known.
+    Monitor,
+    Fps(u32),
+}
+
+impl FrameLimit {
+    /// The same word the settings file uses for it.
+    pub fn name(self) -> String {
+        match self {
+            FrameLimit::Unlimited => "Unlimited".to_string(),
+            FrameLimit::Monitor => "Monitor".to_string(),
+            FrameLimit::Fps(fps) => fps.to_string(),
+        }
+    }
+
+    /// The cap after this one on the options screen, going round. A
+    /// number from the settings file that isn't one of its steps goes on
+    /// to the next step above it.
+    pub fn next(self) -> Self {
+        if let Some(index) = STEPS.iter().position(|&limit| limit == self) {
+            return STEPS[(index + 1) % STEPS.len()];
+        }
+        let FrameLimit::Fps(fps) = self else {
+            return self;
+        };
+        STEPS.into_iter().find(|limit| matches!(limit, FrameLimit::Fps(n) if *n > fps)).unwrap_or(FrameLimit::Unlimited)
+    }
+
+    /// The shortest a frame may take, given the monitor's refresh rate in
+    /// millihertz if it's known; `None` is no limit.
+    pub fn frame_time(self, refresh_millihertz: Option<u32>) -> Option<Duration> {
+        match self {
+            FrameLimit::Unlimited => None,
+            FrameLimit::Monitor => refresh_millihertz
+                .filter(|&millihertz| millihertz > 0)
+                .map(|millihertz| Duration::from_secs_f64(1000.0 / millihertz as f64)),
+            FrameLimit::Fps(fps) => Some(Duration::from_secs(1) / fps.max(1)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SavedFrameLimit {
+    Fps(u32),
+    Name(String),
+}
+
+impl TryFrom<SavedFrameLimit> for FrameLimit {
+    type Error = String;
+
+    fn try_from(saved: SavedFrameLimit) -> Result<Self, Self::Error> {
+        match saved {
+            SavedFrameLimit::Fps(0) => Ok(FrameLimit::Unlimited),
+            SavedFrameLimit::Fps(fps) => Ok(FrameLimit::Fps(fps)),
+            SavedFrameLimit::Name(name) => match name.as_str() {
+                "unlimited" => Ok(FrameLimit::Unlimited),
+                "monitor" => Ok(FrameLimit::Monitor),
+                _ => Err(format!("unknown frame limit `{name}`, expected \"unlimited\", \"monitor\" or a number")),
+            },
+        }
+    }
+}
+
+impl From<FrameLimit> for SavedFrameLimit {
+    fn from(limit: FrameLimit) -> Self {
+        match limit {
+            FrameLimit::Unlimited => SavedFrameLimit::Name("unlimited".to_string()),
+            FrameLimit::Monitor => SavedFrameLimit::Name("monitor".to_string()),
+            FrameLimit::Fps(fps) => SavedFrameLimit::Fps(fps),
+        }
+    }
+}
+
+/// Holds each frame back until its time comes.
+#[derive(Debug, Default)]
+pub struct FrameLimiter {
+    next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// Waits, after a frame is presented, until the next may start at most
+    /// `frame_time` after the last did. `None` doesn't wait.
+    pub fn wait(&mut self, frame_time: Option<Duration>) {
+        let Some(frame_time) = frame_time else {
+            self.next_frame = None;
+            return;
+        };
+        let now = Instant::now();
+        // After a stall, start over rather than rushing frames out to
+        // catch up.
+        let deadline = match self.next_frame {
+            Some(deadline) if deadline + frame_time > now => deadline,
+            _ => now,
+        };
+
+        if let Some(rest) = deadline.checked_duration_since(now) {
+            if rest > SPIN_MARGIN {
+                std::thread::sleep(rest - SPIN_MARGIN);
+            }
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+        self.next_frame = Some(deadline + frame_time);
+    }
+}
@@ -0,0 +1,149 @@
+//! Furnaces, which burn fuel to smelt one item at a time into another.
+//! Their state is a block entity, and they keep working wherever their
+//! chunk is loaded, whether or not anyone is looking.
+
+use crate::{
+    block::BlockState,
+    block_entity::BlockEntity,
+    crafting,
+    inventory::{Inventory, ItemStack, MAX_STACK},
+    world::World,
+};
+
+pub const INPUT_SLOT: usize = 0;
+pub const FUEL_SLOT: usize = 1;
+pub const OUTPUT_SLOT: usize = 2;
+pub const FURNACE_SLOTS: usize = 3;
+
+/// Seconds it takes to smelt one item.
+pub const SMELT_TIME: f32 = 10.0;
+// How much faster a half-smelted item cools back down than it heated up
+// once the fire goes out.
+const COOL_RATE: f32 = 2.0;
+// How finely progress and the flame are shown. Furnaces are only sent to
+// players when what they show moves by a step.
+const DISPLAY_STEPS: f32 = 32.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Furnace {
+    pub slots: Inventory,
+    /// Seconds the fuel burning now has left.
+    pub burn_left: f32,
+    /// How long the fuel burning now lasts in all.
+    pub burn_total: f32,
+    /// Seconds spent smelting the item in the input slot.
+    pub progress: f32,
+}
+
+impl Furnace {
+    pub fn new() -> Self {
+        Self {
+            slots: Inventory::with_slots(FURNACE_SLOTS),
+            burn_left: 0.0,
+            burn_total: 0.0,
+            progress: 0.0,
+        }
+    }
+
+    pub fn is_burning(&self) -> bool {
+        self.burn_left > 0.0
+    }
+
+    /// How much of the fuel burning now is left, from 1 down to 0.
+    pub fn burn_fraction(&self) -> f32 {
+        if self.burn_total > 0.0 { (self.burn_left / self.burn_total).min(1.0) } else { 0.0 }
+    }
+
+    /// How far along smelting the input is, from 0 up to 1.
+    pub fn progress_fraction(&self) -> f32 {
+        (self.progress / SMELT_TIME).min(1.0)
+    }
+
+    // What smelting the input makes, if the output slot has room for it.
+    fn result(&self) -> Option<ItemStack> {
+        let input = self.slots.get(INPUT_SLOT)?;
+        let result = crafting::smelting_result(input.block)?;
+        match self.slots.get(OUTPUT_SLOT) {
+            Some(output) if output.block != result.block || output.count + result.count > MAX_STACK => None,
+            _ => Some(result),
+        }
+    }
+
+    /// Burns and smelts for `dt` seconds. Fuel is only lit when there's
+    /// something to smelt, but once lit burns out whether or not there
+    /// still is. Returns whether anything a player would see changed.
+    pub fn tick(&mut self, dt: f32) -> bool {
+        let shown = self.shown();
+        let result = self.result();
+        let mut items_changed = false;
+
+        if !self.is_burning() && result.is_some() {
+            let fuel = self.slots.get(FUEL_SLOT).map_or(0.0, |stack| stack.block.info().fuel);
+            if fuel > 0.0 {
+                self.slots.remove_one(FUEL_SLOT);
+                (self.burn_left, self.burn_total) = (fuel, fuel);
+                items_changed = true;
+            }
+        }
+
+        let burning = self.is_burning();
+        self.burn_left = (self.burn_left - dt).max(0.0);
+        match result {
+            Some(result) if burning => {
+                self.progress += dt;
+                if self.progress >= SMELT_TIME {
+                    self.progress = 0.0;
+                    self.slots.remove_one(INPUT_SLOT);
+                    self.slots.merge(OUTPUT_SLOT, result);
+                    items_changed = true;
+                }
+            },
+            Some(_) => self.progress = (self.progress - dt * COOL_RATE).max(0.0),
+            None => self.progress = 0.0,
+        }
+
+        items_changed || self.shown() != shown
+    }
+
+    // The flame and progress arrow as drawn, in whole steps.
+    fn shown(&self) -> (bool, u32, u32) {
+        let step = |fraction: f32| (fraction * DISPLAY_STEPS).ceil() as u32;
+        (self.is_burning(), step(self.burn_fraction()), step(self.progress_fraction()))
+    }
+}
+
+/// Ticks every furnace in the loaded chunks, lighting and putting out their
+/// blocks to match. Returns where furnaces changed in a way players would
+/// see, for the server to send on.
+pub fn furnace_system(world: &mut World, dt: f32) -> Vec<[i32 ; 3]> {
+    let furnaces = world.chunks.values()
+        .flat_map(|chunk| {
+            let [origin_x, origin_y, origin_z] = chunk.pos.origin();
+            chunk.block_entities()
+                .filter(|(_, entity)| matches!(entity, BlockEntity::Furnace(_)))
+                .map(move |([x, y, z], _)| [origin_x + x as i32, origin_y + y as i32, origin_z + z as i32])
+        })
+        .collect::<Vec<_>>();
+
+    let mut shown_changes = Vec::new();
+    for [x, y, z] in furnaces {
+        let Some(BlockEntity::Furnace(before)) = world.block_entity(x, y, z) else {
+            continue;
+        };
+        let mut furnace = before.clone();
+        if furnace.tick(dt) {
+            shown_changes.push([x, y, z]);
+        }
+        if furnace == *before {
+            continue;
+        }
+
+        let state = world.get_state(x, y, z);
+        if state.properties.lit() != furnace.is_burning() {
+            let properties = state.properties.with_lit(furnace.is_burning());
+            world.set_state(x, y, z, BlockState { properties, ..state });
+        }
+        world.set_block_entity(x, y, z, BlockEntity::Furnace(furnace));
+    }
+    shown_changes
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+/// The rules a world is played by. Chosen when the world is created and
+/// saved with it, and changed with `/gamemode`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    /// Blocks take time to break and are used up by placing them, the
+    /// player can be hurt, and can't fly.
+    #[default]
+    Survival,
+    /// Blocks break at once and never run out, and the player can fly and
+    /// can't be hurt.
+    Creative,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode ; 2] = [GameMode::Survival, GameMode::Creative];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Survival => "Survival",
+            GameMode::Creative => "Creative",
+        }
+    }
+
+    /// Looks a mode up by its name in any case, as typed in commands.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    /// Whether blocks break as soon as they're hit, ignoring hardness.
+    pub fn instant_break(self) -> bool {
+        self == GameMode::Creative
+    }
+
+    /// Whether placing and breaking leave the inventory alone, with nothing
+    /// used up and nothing dropped.
+    pub fn infinite_blocks(self) -> bool {
+        self == GameMode::Creative
+    }
+
+    pub fn can_fly(self) -> bool {
+        self == GameMode::Creative
+    }
+
+    /// Whether falls, the void and hunger hurt the player.
+    pub fn takes_damage(self) -> bool {
+        self == GameMode::Survival
+    }
+
+    /// Whether waypoints on the world map can be teleported to.
+    pub fn map_teleport(self) -> bool {
+        self == GameMode::Creative
+    }
+
+    /// Whether the world-edit wand marks out a selection rather than
+    /// sitting in the hand doing nothing, and the undo and redo keys work.
+    pub fn world_edit(self) -> bool {
+        self == GameMode::Creative
+    }
+}
@@ -0,0 +1,189 @@
+use std::ops::Range;
+
+/// A region handed out by a `BufferPool`. It stays valid until it is given
+/// back with `BufferPool::free`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Allocation {
+    page: usize,
+    range: Range<wgpu::BufferAddress>,
+}
+
+impl Allocation {
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    pub fn offset(&self) -> wgpu::BufferAddress {
+        self.range.start
+    }
+
+    pub fn size(&self) -> wgpu::BufferAddress {
+        self.range.end - self.range.start
+    }
+}
+
+struct Page {
+    buffer: wgpu::Buffer,
+    // Sorted by offset, with neighbouring ranges always merged.
+    free: Vec<Range<wgpu::BufferAddress>>,
+}
+
+/// Sub-allocates many small pieces of data from a few large buffers, so
+/// data that is replaced often reuses memory instead of creating and
+/// destroying a buffer each time. Pages are never released; freed regions
+/// go on a first-fit free list.
+pub struct BufferPool {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    page_size: wgpu::BufferAddress,
+    alignment: wgpu::BufferAddress,
+    pages: Vec<Page>,
+    used: wgpu::BufferAddress,
+}
+
+impl BufferPool {
+    /// Every offset and size handed out is a multiple of `alignment`, which
+    /// must itself be a multiple of four so the data can be written.
+    pub fn new(
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        page_size: wgpu::BufferAddress,
+        alignment: wgpu::BufferAddress,
+    ) -> Self {
+        assert!(alignment.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT), "pool alignment must be a multiple of 4");
+        Self {
+            label,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            page_size: page_size.next_multiple_of(alignment),
+            alignment,
+            pages: Vec::new(),
+            used: 0,
+        }
+    }
+
+    /// Copies `data` into a free region, adding a page when none has room.
+    pub fn allocate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[u8]) -> Allocation {
+        let size = (data.len() as wgpu::BufferAddress).next_multiple_of(self.alignment).max(self.alignment);
+
+        let found = self.pages.iter().enumerate().find_map(|(page, entry)| {
+            entry.free.iter().position(|free| free.end - free.start >= size).map(|slot| (page, slot))
+        });
+        let (page, slot) = found.unwrap_or_else(|| {
+            // Data bigger than a page gets a page of its own.
+            let page_size = self.page_size.max(size);
+            let whole_page = 0..page_size;
+            self.pages.push(Page {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(self.label),
+                    size: page_size,
+                    usage: self.usage,
+                    mapped_at_creation: false,
+                }),
+                free: vec![whole_page],
+            });
+            (self.pages.len() - 1, 0)
+        });
+
+        let free = &mut self.pages[page].free;
+        let start = free[slot].start;
+        free[slot].start += size;
+        if free[slot].is_empty() {
+            free.remove(slot);
+        }
+        self.used += size;
+
+        let buffer = &self.pages[page].buffer;
+        if data.len() as wgpu::BufferAddress == size {
+            queue.write_buffer(buffer, start, data);
+        } else {
+            let mut padded = data.to_vec();
+            padded.resize(size as usize, 0);
+            queue.write_buffer(buffer, start, &padded);
+        }
+
+        Allocation { page, range: start..start + size }
+    }
+
+    /// Returns a region to its page, merging it with free neighbours.
+    pub fn free(&mut self, allocation: Allocation) {
+        self.used -= allocation.size();
+
+        let free = &mut self.pages[allocation.page].free;
+        let Range { mut start, mut end } = allocation.range;
+        let index = free.partition_point(|range| range.start < start);
+        let mut insert = index;
+
+        if index < free.len() && free[index].start == end {
+            end = free.remove(index).end;
+        }
+        if index > 0 && free[index - 1].end == start {
+            start = free.remove(index - 1).start;
+            insert -= 1;
+        }
+        free.insert(insert, start..end);
+    }
+
+    pub fn page(&self, page: usize) -> &wgpu::Buffer {
+        &self.pages[page].buffer
+    }
+
+    /// Bytes currently handed out.
+    pub fn used(&self) -> wgpu::BufferAddress {
+        self.used
+    }
+
+    /// Bytes reserved on the GPU across all pages.
+    pub fn capacity(&self) -> wgpu::BufferAddress {
+        self.pages.iter().map(|page| page.buffer.size()).sum()
+    }
+}
+
+// Reuses the buffer while the data fits, otherwise replaces it with one
+// twice the needed size so steady growth doesn't reallocate every frame.
+// Returns whether the buffer was replaced.
+pub fn write_growing<T: bytemuck::Pod>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &mut Option<wgpu::Buffer>,
+    data: &[T],
+    usage: wgpu::BufferUsages,
+    label: &str,
+) -> bool {
+    let bytes = bytemuck::cast_slice::<T, u8>(data);
+    // Buffer writes must be a multiple of four bytes.
+    let size = (bytes.len() as wgpu::BufferAddress).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+    let replaced = reserve(device, buffer, size, usage, label);
+
+    let buffer = buffer.as_ref().unwrap();
+    if bytes.len() as wgpu::BufferAddress == size {
+        queue.write_buffer(buffer, 0, bytes);
+    } else {
+        let mut padded = bytes.to_vec();
+        padded.resize(size as usize, 0);
+        queue.write_buffer(buffer, 0, &padded);
+    }
+    replaced
+}
+
+/// Makes sure `buffer` holds at least `size` bytes, growing it the same way
+/// as `write_growing`. Returns whether the buffer was replaced.
+pub fn reserve(
+    device: &wgpu::Device,
+    buffer: &mut Option<wgpu::Buffer>,
+    size: wgpu::BufferAddress,
+    usage: wgpu::BufferUsages,
+    label: &str,
+) -> bool {
+    let size = size.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+    if buffer.as_ref().is_some_and(|buffer| buffer.size() >= size) {
+        return false;
+    }
+
+    *buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size * 2,
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    }));
+    true
+}
@@ -0,0 +1,189 @@
+use crate::{
+    ecs::{Ecs, Entity, Velocity},
+    events::Event,
+    mob::MobKind,
+    world::World,
+};
+
+/// Full health, in half hearts.
+pub const MAX_HEALTH: u32 = 20;
+
+// Falls up to this many blocks are free; each block past it costs a half
+// heart.
+const SAFE_FALL_DISTANCE: f32 = 3.0;
+const VOID_DAMAGE: u32 = 4;
+// After being hurt nothing else can hurt again for a moment, so standing
+// in the void takes health in steps instead of all at once.
+const HURT_COOLDOWN: f32 = 0.5;
+
+/// What hurt an entity, for telling the player how they died.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageSource {
+    Fall,
+    Void,
+    Starvation,
+    Mob(MobKind),
+    Player,
+    Explosion,
+}
+
+impl DamageSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            DamageSource::Fall => "fall",
+            DamageSource::Void => "void",
+            DamageSource::Starvation => "starvation",
+            DamageSource::Mob(kind) => kind.name(),
+            DamageSource::Player => "player",
+            DamageSource::Explosion => "explosion",
+        }
+    }
+
+    pub fn death_message(self) -> &'static str {
+        match self {
+            DamageSource::Fall => "You hit the ground too hard",
+            DamageSource::Void => "You fell out of the world",
+            DamageSource::Starvation => "You starved to death",
+            DamageSource::Mob(kind) => kind.death_message(),
+            DamageSource::Player => "You were killed by another player",
+            DamageSource::Explosion => "You blew up",
+        }
+    }
+}
+
+/// Hit points, in half hearts, of something that can be hurt and die.
+#[derive(Clone, Copy, Debug)]
+pub struct Health {
+    pub points: u32,
+    /// What last took health away, kept after death to explain it.
+    pub last_damage: Option<DamageSource>,
+    // The highest point of the current fall, while in the air.
+    fall_start: Option<f32>,
+    hurt_cooldown: f32,
+}
+
+impl Health {
+    pub fn new(points: u32) -> Self {
+        Self {
+            points: points.min(MAX_HEALTH),
+            last_damage: None,
+            fall_start: None,
+            hurt_cooldown: 0.0,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.points == 0
+    }
+
+    pub fn heal(&mut self, points: u32) {
+        if !self.is_dead() {
+            self.points = (self.points + points).min(MAX_HEALTH);
+        }
+    }
+
+    /// Forgets the fall under way, for when the entity is moved rather
+    /// than falls.
+    pub fn reset_fall(&mut self) {
+        self.fall_start = None;
+    }
+
+    /// Back to full health with no fall under way, as after respawning.
+    pub fn restore(&mut self) {
+        *self = Self::new(MAX_HEALTH);
+    }
+}
+
+/// Takes `amount` half hearts from `entity` unless it was hurt too
+/// recently. Returns whether it was hurt.
+pub fn damage(ecs: &mut Ecs, entity: Entity, amount: u32, source: DamageSource) -> bool {
+    let Some(health) = ecs.healths.get_mut(entity) else {
+        return false;
+    };
+    if amount == 0 || health.is_dead() || health.hurt_cooldown > 0.0 {
+        return false;
+    }
+    let lost = health.points.min(amount);
+    health.points -= lost;
+    health.last_damage = Some(source);
+    health.hurt_cooldown = HURT_COOLDOWN;
+    if ecs.players.get(entity).is_some() {
+        ecs.events.publish(Event::PlayerDamaged { entity, amount: lost, source });
+    }
+    true
+}
+
+/// Tracks falls and the void for everything with health. Falls are
+/// measured from the highest point reached, and landing in a fluid or
+/// flying breaks them off without harm.
+pub fn health_system(ecs: &mut Ecs, world: &World, dt: f32) {
+    let entities = ecs.healths.iter().map(|(entity, _)| entity).collect::<Vec<_>>();
+
+    for entity in entities {
+        let (Some(transform), Some(collider)) = (ecs.transforms.get(entity), ecs.colliders.get(entity)) else {
+            continue;
+        };
+        let position = transform.position;
+        let in_fluid = world.get_block(position.x.floor() as i32, position.y.floor() as i32, position.z.floor() as i32).is_fluid();
+        let (on_ground, falls) = (collider.on_ground, collider.gravity && !in_fluid && !collider.climbing);
+
+        let health = ecs.healths.get_mut(entity).unwrap();
+        health.hurt_cooldown = (health.hurt_cooldown - dt).max(0.0);
+
+        let mut fall_damage = 0;
+        if !falls {
+            health.fall_start = None;
+        } else if on_ground {
+            if let Some(start) = health.fall_start.take() {
+                fall_damage = (start - position.y - SAFE_FALL_DISTANCE).ceil().max(0.0) as u32;
+            }
+        } else {
+            health.fall_start = Some(health.fall_start.map_or(position.y, |start| start.max(position.y)));
+        }
+
+        if !world.game_mode.takes_damage() {
+            continue;
+        }
+        damage(ecs, entity, fall_damage, DamageSource::Fall);
+        if in_void(world, position.y) {
+            damage(ecs, entity, VOID_DAMAGE, DamageSource::Void);
+        }
+    }
+}
+
+// Whether a height is far enough under the bottom of the world for the
+// void to eat away at whatever fell in, or take it away at once if it
+// can't be hurt.
+fn in_void(world: &World, y: f32) -> bool {
+    world.generator.void_depth().is_some_and(|depth| y < depth as f32)
+}
+
+/// Deals with whatever fell into the void that it can't hurt. Players are
+/// put back at the spawn point, and anything else is gone for good.
+pub fn void_system(ecs: &mut Ecs, world: &World) {
+    let fallen = ecs.colliders.iter()
+        .filter_map(|(entity, _)| Some(entity).zip(ecs.transforms.get(entity)))
+        .filter(|(_, transform)| in_void(world, transform.position.y))
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+
+    let hurts = world.game_mode.takes_damage();
+    for entity in fallen {
+        if ecs.players.get(entity).is_some() {
+            if hurts {
+                continue;
+            }
+            if let Some(transform) = ecs.transforms.get_mut(entity) {
+                transform.teleport(world.spawn_point.into());
+            }
+            if let Some(velocity) = ecs.velocities.get_mut(entity) {
+                *velocity = Velocity::default();
+            }
+            if let Some(health) = ecs.healths.get_mut(entity) {
+                health.reset_fall();
+            }
+        } else if !(hurts && ecs.healths.get(entity).is_some()) {
+            ecs.despawn(entity);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{physics::Aabb, texture};
+
+// Pushes the outline slightly outside the block so it doesn't z-fight
+// with the block's own faces.
+const OUTSET: f32 = 0.002;
+
+const EDGES: [([usize ; 3], [usize ; 3]) ; 12] = [
+    ([0, 0, 0], [1, 0, 0]), ([1, 0, 0], [1, 0, 1]), ([1, 0, 1], [0, 0, 1]), ([0, 0, 1], [0, 0, 0]),
+    ([0, 1, 0], [1, 1, 0]), ([1, 1, 0], [1, 1, 1]), ([1, 1, 1], [0, 1, 1]), ([0, 1, 1], [0, 1, 0]),
+    ([0, 0, 0], [0, 1, 0]), ([1, 0, 0], [1, 1, 0]), ([1, 0, 1], [1, 1, 1]), ([0, 0, 1], [0, 1, 1]),
+];
+
+/// What an outline is drawn around, which sets how it looks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outline {
+    /// The aimed-at block, in faint black.
+    Target,
+    /// The world-edit selection, in bright yellow so it shows from inside
+    /// the box and across it.
+    Selection,
+}
+
+impl Outline {
+    fn entry_point(self) -> &'static str {
+        match self {
+            Outline::Target => "fs_target",
+            Outline::Selection => "fs_selection",
+        }
+    }
+}
+
+pub struct HighlightRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    target: Option<Aabb>,
+}
+
+impl HighlightRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        outline: Outline,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("highlight.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Highlight Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Highlight Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32 ; 3]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(outline.entry_point()),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Highlight Vertex Buffer"),
+            size: (EDGES.len() * 2 * std::mem::size_of::<[f32 ; 3]>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            target: None,
+        }
+    }
+
+    /// Outlines `target`, such as the bounds of the aimed-at block's shape.
+    pub fn set_target(&mut self, queue: &wgpu::Queue, target: Option<Aabb>) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+
+        let Some(bounds) = target else {
+            return;
+        };
+
+        let min = [bounds.min.x - OUTSET, bounds.min.y - OUTSET, bounds.min.z - OUTSET];
+        let max = [bounds.max.x + OUTSET, bounds.max.y + OUTSET, bounds.max.z + OUTSET];
+        let corner = |c: [usize ; 3]| -> [f32 ; 3] {
+            [
+                if c[0] == 0 { min[0] } else { max[0] },
+                if c[1] == 0 { min[1] } else { max[1] },
+                if c[2] == 0 { min[2] } else { max[2] },
+            ]
+        };
+
+        let vertices = EDGES.iter()
+            .flat_map(|(a, b)| [corner(*a), corner(*b)])
+            .collect::<Vec<_>>();
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if self.target.is_none() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..(EDGES.len() * 2) as u32, 0..1);
+    }
+}
@@ -0,0 +1,90 @@
+//! The edit history: what each edit to the world's blocks changed, kept so
+//! it can be undone and redone. An edit is everything one action changed,
+//! whether a block placed or broken, a door with both its halves, or a
+//! whole `/fill`. Edits are stored as the blocks were before them, and
+//! undoing one stores the blocks as they were before that, to redo it.
+
+use std::collections::VecDeque;
+
+use crate::world::{BlockSnapshot, World};
+
+/// The most edits kept to undo.
+pub const MAX_EDITS: usize = 100;
+/// The most changed blocks kept across every edit, which bounds the memory
+/// the history takes. The latest edit is kept however big it is.
+pub const MAX_BLOCKS: usize = 512 * 1024;
+
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    // Oldest first.
+    undo: VecDeque<Vec<BlockSnapshot>>,
+    // Most recently undone last.
+    redo: Vec<Vec<BlockSnapshot>>,
+    // Across both.
+    blocks: usize,
+}
+
+impl EditHistory {
+    /// Keeps an edit, given as the blocks it changed were before, to undo,
+    /// and returns how many blocks it changed. Whatever was undone before
+    /// it can't be redone any more.
+    pub fn record(&mut self, previous: Vec<BlockSnapshot>) -> usize {
+        let changed = previous.len();
+        if changed == 0 {
+            return 0;
+        }
+        self.blocks -= self.redo.drain(..).map(|edit| edit.len()).sum::<usize>();
+        self.blocks += changed;
+        self.undo.push_back(previous);
+        while (self.undo.len() > MAX_EDITS || self.blocks > MAX_BLOCKS) && self.undo.len() > 1 {
+            if let Some(oldest) = self.undo.pop_front() {
+                self.blocks -= oldest.len();
+            }
+        }
+        changed
+    }
+
+    /// Undoes up to `count` edits, latest first, and returns how many it
+    /// undid and how many blocks that changed.
+    pub fn undo(&mut self, world: &mut World, count: usize) -> (usize, usize) {
+        step(world, count, &mut self.blocks, || self.undo.pop_back(), |edit| self.redo.push(edit))
+    }
+
+    /// Redoes up to `count` undone edits, latest undone first, and returns
+    /// how many it redid and how many blocks that changed.
+    pub fn redo(&mut self, world: &mut World, count: usize) -> (usize, usize) {
+        step(world, count, &mut self.blocks, || self.redo.pop(), |edit| self.undo.push_back(edit))
+    }
+
+    /// Forgets everything, as when leaving the world it's about.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+// Puts back up to `count` edits taken from one side of the history, and
+// keeps what each changed on the other. Blocks in chunks that aren't
+// loaded stay as they are, and drop out of the edit.
+fn step(
+    world: &mut World,
+    count: usize,
+    blocks: &mut usize,
+    mut take: impl FnMut() -> Option<Vec<BlockSnapshot>>,
+    mut keep: impl FnMut(Vec<BlockSnapshot>),
+) -> (usize, usize) {
+    let (mut edits, mut changed) = (0, 0);
+    while edits < count {
+        let Some(edit) = take() else {
+            break;
+        };
+        let stored = edit.len();
+        let previous = world.set_voxels(edit);
+        *blocks = *blocks - stored + previous.len();
+        edits += 1;
+        changed += previous.len();
+        if !previous.is_empty() {
+            keep(previous);
+        }
+    }
+    (edits, changed)
+}
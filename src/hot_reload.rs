@@ -0,0 +1,106 @@
+//! Watches the terrain shader and the block textures while working on the
+//! game, so edits to them show up without restarting. Files are polled
+//! for changed modification times rather than watched through the OS.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::info;
+
+use crate::{
+    assets::Assets,
+    block,
+    error::{EngineError, Result},
+};
+
+/// The shader in the source tree, which is only there in a checkout.
+pub const SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl");
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Changes {
+    pub shader: bool,
+    pub textures: bool,
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    fn new(path: PathBuf) -> Self {
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    // Whether the file changed since this was last called. A file that's
+    // briefly missing while an editor saves it counts once it's back.
+    fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+pub struct HotReload {
+    shader: WatchedFile,
+    // One per texture layer, in layer order.
+    textures: Vec<WatchedFile>,
+    last_poll: Instant,
+}
+
+impl HotReload {
+    /// Watches the shader and the file each texture was loaded from.
+    pub fn new(assets: &Assets) -> Self {
+        let textures = block::registry().textures.iter()
+            .filter_map(|(path, _)| assets.resolve(path))
+            .map(WatchedFile::new)
+            .collect();
+        info!("hot reload is watching {SHADER_PATH} and the textures");
+
+        Self {
+            shader: WatchedFile::new(PathBuf::from(SHADER_PATH)),
+            textures,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// What changed since the last poll. Checks at most every
+    /// `POLL_INTERVAL`, reporting nothing in between.
+    pub fn poll(&mut self) -> Changes {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Changes::default();
+        }
+        self.last_poll = Instant::now();
+
+        // Every file is checked so one edit isn't reported twice.
+        let textures = self.textures.iter_mut().fold(false, |changed, file| file.changed() | changed);
+        Changes {
+            shader: self.shader.changed(),
+            textures,
+        }
+    }
+
+    pub fn read_shader(&self) -> Result<String> {
+        fs::read_to_string(&self.shader.path)
+            .map_err(|source| EngineError::Io { path: self.shader.path.clone(), source })
+    }
+
+    /// The bytes of every texture, in layer order.
+    pub fn read_textures(&self) -> Result<Vec<Vec<u8>>> {
+        self.textures.iter()
+            .map(|file| fs::read(&file.path).map_err(|source| EngineError::Io { path: file.path.clone(), source }))
+            .collect()
+    }
+}
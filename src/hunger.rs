@@ -0,0 +1,140 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::{
+    ecs::{Ecs, Entity},
+    health::{self, DamageSource, MAX_HEALTH},
+    world::World,
+};
+
+/// A full stomach, in half drumsticks.
+pub const MAX_FOOD: u32 = 20;
+/// Sprinting needs more food than this.
+pub const SPRINT_FOOD: u32 = 6;
+
+/// Exhaustion from a jump, and from a jump while sprinting.
+pub const JUMP_EXHAUSTION: f32 = 0.05;
+pub const SPRINT_JUMP_EXHAUSTION: f32 = 0.2;
+/// Exhaustion from each block broken.
+pub const BREAK_EXHAUSTION: f32 = 0.005;
+/// Exhaustion from each hit on a mob.
+pub const ATTACK_EXHAUSTION: f32 = 0.1;
+
+// Exhaustion per block walked and sprinted along the ground.
+const WALK_EXHAUSTION: f32 = 0.01;
+const SPRINT_EXHAUSTION: f32 = 0.1;
+// Moves further than this in one update are teleports, not walking.
+const MAX_STEP: f32 = 2.0;
+// This much exhaustion costs a point of food.
+const EXHAUSTION_PER_FOOD: f32 = 4.0;
+// At this much food health slowly comes back, which is tiring in turn.
+const HEAL_FOOD: u32 = 18;
+const HEAL_EXHAUSTION: f32 = 6.0;
+// Seconds between points healed while fed, or lost while starving.
+const FOOD_TICK: f32 = 4.0;
+
+/// How fed something is, in half drumsticks. Activity wears it down, food
+/// builds it back up; well fed heals and empty starves.
+#[derive(Clone, Copy, Debug)]
+pub struct Hunger {
+    pub food: u32,
+    // Built up by moving, jumping and breaking, and paid for in food.
+    exhaustion: f32,
+    // Counts towards the next point healed or starved.
+    timer: f32,
+    // Where the entity was last update, for how far it's walked.
+    last_position: Option<Point3<f32>>,
+}
+
+impl Hunger {
+    pub fn new(food: u32) -> Self {
+        Self {
+            food: food.min(MAX_FOOD),
+            exhaustion: 0.0,
+            timer: 0.0,
+            last_position: None,
+        }
+    }
+
+    pub fn can_sprint(&self) -> bool {
+        self.food > SPRINT_FOOD
+    }
+
+    /// Eats something worth `food` points. Returns false, eating nothing,
+    /// if already full.
+    pub fn eat(&mut self, food: u32) -> bool {
+        if self.food >= MAX_FOOD {
+            return false;
+        }
+        self.food = (self.food + food).min(MAX_FOOD);
+        true
+    }
+
+    /// Full and rested, as after respawning.
+    pub fn restore(&mut self) {
+        *self = Self::new(MAX_FOOD);
+    }
+}
+
+/// Tires `entity` by `amount`, for activity the hunger system can't see
+/// for itself.
+pub fn exhaust(ecs: &mut Ecs, entity: Entity, amount: f32) {
+    if let Some(hunger) = ecs.hungers.get_mut(entity) {
+        hunger.exhaustion += amount;
+    }
+}
+
+/// Wears down the food of everything with hunger as it walks about, then
+/// heals the well fed and hurts the starving. Nothing gets hungry in game
+/// modes that can't be hurt.
+pub fn hunger_system(ecs: &mut Ecs, world: &World, dt: f32) {
+    let entities = ecs.hungers.iter().map(|(entity, _)| entity).collect::<Vec<_>>();
+
+    for entity in entities {
+        let Some(position) = ecs.transforms.get(entity).map(|t| t.position) else {
+            continue;
+        };
+        let on_ground = ecs.colliders.get(entity).is_some_and(|collider| collider.on_ground);
+        let sprinting = ecs.players.get(entity).is_some_and(|player| player.sprinting);
+        let hurt = ecs.healths.get(entity).is_some_and(|health| !health.is_dead() && health.points < MAX_HEALTH);
+
+        let hunger = ecs.hungers.get_mut(entity).unwrap();
+        let moved = hunger.last_position.map_or(0.0, |last| {
+            let step = position - last;
+            Vector3::new(step.x, 0.0, step.z).magnitude()
+        });
+        let moved = if moved > MAX_STEP { 0.0 } else { moved };
+        hunger.last_position = Some(position);
+        if !world.game_mode.takes_damage() {
+            hunger.exhaustion = 0.0;
+            hunger.timer = 0.0;
+            continue;
+        }
+
+        if on_ground {
+            hunger.exhaustion += moved * if sprinting { SPRINT_EXHAUSTION } else { WALK_EXHAUSTION };
+        }
+        while hunger.exhaustion >= EXHAUSTION_PER_FOOD {
+            hunger.exhaustion -= EXHAUSTION_PER_FOOD;
+            hunger.food = hunger.food.saturating_sub(1);
+        }
+
+        let (heals, starves) = (hunger.food >= HEAL_FOOD && hurt, hunger.food == 0);
+        if !heals && !starves {
+            hunger.timer = 0.0;
+            continue;
+        }
+        hunger.timer += dt;
+        if hunger.timer < FOOD_TICK {
+            continue;
+        }
+        hunger.timer -= FOOD_TICK;
+        if heals {
+            hunger.exhaustion += HEAL_EXHAUSTION;
+            if let Some(health) = ecs.healths.get_mut(entity) {
+                health.heal(1);
+            }
+        } else {
+            health::damage(ecs, entity, 1, DamageSource::Starvation);
+        }
+    }
+}
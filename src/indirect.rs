@@ -0,0 +1,320 @@
+use std::{collections::HashMap, ops::Range};
+
+use cgmath::Point3;
+
+use crate::{
+    camera::Frustum,
+    chunk::{ChunkPos, ColumnPos, CHUNK_SIZE},
+    culling::{self, DrawInput, GpuCuller},
+    debug::RenderStats,
+    gpu::write_growing,
+    mesher::{ChunkBuffers, ChunkMeshBuffers, MeshPool},
+};
+
+/// The argument layout `draw_indexed_indirect` reads.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawArgs {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+const ARGS_SIZE: wgpu::BufferAddress = std::mem::size_of::<DrawArgs>() as wgpu::BufferAddress;
+
+// How high far-off terrain's meshes are taken to reach up from y 0, as
+// they're never culled against anything tighter.
+const LOD_HEIGHT: f32 = 256.0;
+
+// The corners of the box a mesh is inside, for culling it.
+type Bounds = ([f32 ; 3], [f32 ; 3]);
+
+/// The chunk geometry a pass draws. The view lists only hold chunks inside
+/// the camera frustum; shadow casters can be anywhere, so theirs hold all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawList {
+    Opaque,
+    Cutout,
+    Translucent,
+    ShadowOpaque,
+    ShadowCutout,
+}
+
+impl DrawList {
+    const COUNT: usize = 5;
+}
+
+/// How the adapter lets us issue the draws, best first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawMode {
+    /// One `multi_draw_indexed_indirect` per batch.
+    MultiIndirect,
+    /// One `draw_indexed_indirect` per chunk, all from the same buffer.
+    Indirect,
+    /// Plain `draw_indexed` calls for adapters without indirect draws.
+    Direct,
+}
+
+impl DrawMode {
+    /// Features worth requesting from the device for the best mode.
+    pub fn optional_features() -> wgpu::Features {
+        wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::MULTI_DRAW_INDIRECT_COUNT
+    }
+
+    pub fn detect(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Self {
+        let indirect = adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::INDIRECT_EXECUTION);
+        if !indirect {
+            DrawMode::Direct
+        } else if device.features().contains(wgpu::Features::MULTI_DRAW_INDIRECT) {
+            DrawMode::MultiIndirect
+        } else {
+            DrawMode::Indirect
+        }
+    }
+}
+
+// Draws that can share one set of vertex and index buffer bindings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BatchKey {
+    vertex_page: usize,
+    index_page: usize,
+    index_format: wgpu::IndexFormat,
+}
+
+impl BatchKey {
+    fn of(buffers: &ChunkBuffers) -> Self {
+        Self {
+            vertex_page: buffers.vertices.page(),
+            index_page: buffers.indices.page(),
+            index_format: buffers.index_format,
+        }
+    }
+
+    fn sort_key(&self) -> (usize, usize, bool) {
+        (self.vertex_page, self.index_page, self.index_format == wgpu::IndexFormat::Uint32)
+    }
+}
+
+struct Batch {
+    key: BatchKey,
+    // Position among all of the frame's batches, which is where the GPU
+    // culler keeps its draw count.
+    index: u32,
+    draws: Range<u32>,
+    vertices: u32,
+    // Whether the GPU culler packs visible draws at the front.
+    compact: bool,
+}
+
+/// Per-frame draw arguments for every chunk mesh, written to one indirect
+/// buffer and grouped into batches by the pool pages they live in. With
+/// multi-draw support each batch is a single draw call.
+///
+/// Culling happens on the CPU unless a `GpuCuller` is given, in which case
+/// every chunk is uploaded and the compute pass picks the visible ones.
+pub struct ChunkDraws {
+    mode: DrawMode,
+    culler: Option<GpuCuller>,
+    // Whether compacted batches can read their draw count from the GPU.
+    indirect_count: bool,
+    args: Vec<DrawArgs>,
+    inputs: Vec<DrawInput>,
+    lists: [Vec<Batch> ; DrawList::COUNT],
+    batch_starts: Vec<u32>,
+    buffer: Option<wgpu::Buffer>,
+    visible_chunks: Option<usize>,
+}
+
+impl ChunkDraws {
+    pub fn new(device: &wgpu::Device, mode: DrawMode, culler: Option<GpuCuller>) -> Self {
+        Self {
+            mode,
+            culler,
+            indirect_count: device.features().contains(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT),
+            args: Vec::new(),
+            inputs: Vec::new(),
+            lists: Default::default(),
+            batch_starts: Vec::new(),
+            buffer: None,
+            visible_chunks: None,
+        }
+    }
+
+    /// Chunks that passed frustum culling this frame, if culled on the CPU.
+    pub fn visible_chunks(&self) -> Option<usize> {
+        self.visible_chunks
+    }
+
+    /// Culls the chunks against `frustum`, or hands them to the GPU culler,
+    /// and rebuilds every draw list. Translucent chunks are ordered far to
+    /// near from `eye`, which is enough sorting for flat fluid surfaces.
+    /// Distant `lod_chunks` are drawn but cast no shadows.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        chunks: &HashMap<ChunkPos, ChunkMeshBuffers>,
+        lod_chunks: &HashMap<ColumnPos, ChunkMeshBuffers>,
+        frustum: &Frustum,
+        eye: Point3<f32>,
+    ) {
+        let gpu_culling = self.culler.is_some();
+        let all = chunks.iter().map(|(&pos, buffers)| (chunk_bounds(pos), buffers)).collect::<Vec<_>>();
+        let mut visible = all.iter()
+            .copied()
+            .chain(lod_chunks.iter().map(|(&column, buffers)| (column_bounds(column), buffers)))
+            .filter(|((min, max), _)| gpu_culling || frustum.intersects_aabb((*min).into(), (*max).into()))
+            .collect::<Vec<_>>();
+        self.visible_chunks = (!gpu_culling).then_some(visible.len());
+
+        let eye = [eye.x, eye.y, eye.z];
+        let distance = |(min, max): &Bounds| {
+            (0..3).map(|axis| ((min[axis] + max[axis]) / 2.0 - eye[axis]).powi(2)).sum::<f32>()
+        };
+        visible.sort_by(|(a, _), (b, _)| distance(b).total_cmp(&distance(a)));
+
+        self.args.clear();
+        self.inputs.clear();
+        self.batch_starts.clear();
+        let lists = [
+            (DrawList::Opaque, layer(&visible, |b| &b.opaque), true, gpu_culling),
+            (DrawList::Cutout, layer(&visible, |b| &b.cutout), true, gpu_culling),
+            (DrawList::Translucent, layer(&visible, |b| &b.translucent), false, gpu_culling),
+            (DrawList::ShadowOpaque, layer(&all, |b| &b.opaque), true, false),
+            (DrawList::ShadowCutout, layer(&all, |b| &b.cutout), true, false),
+        ];
+        for (list, meshes, reorder, cull) in lists {
+            self.lists[list as usize] = self.batch(meshes, reorder, cull);
+        }
+
+        if let Some(culler) = &mut self.culler {
+            culler.prepare(device, queue, &self.inputs, &self.batch_starts, frustum);
+        } else if self.mode != DrawMode::Direct && !self.args.is_empty() {
+            write_growing(
+                device,
+                queue,
+                &mut self.buffer,
+                &self.args,
+                wgpu::BufferUsages::INDIRECT,
+                "Chunk Indirect Buffer",
+            );
+        }
+    }
+
+    /// Records the GPU culling pass, if there is one. Must run before any
+    /// pass that draws chunks.
+    pub fn cull(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(culler) = &self.culler {
+            culler.dispatch(encoder);
+        }
+    }
+
+    // Appends the meshes' arguments and groups runs sharing buffers into
+    // batches. Unordered lists are sorted first so each page pair forms a
+    // single batch; ordered ones keep their order and may split more.
+    fn batch(&mut self, mut meshes: Vec<(Bounds, &ChunkBuffers)>, reorder: bool, cull: bool) -> Vec<Batch> {
+        if reorder {
+            meshes.sort_by_key(|(_, buffers)| BatchKey::of(buffers).sort_key());
+        }
+        // Compacting reorders draws, so ordered lists are culled in place.
+        let compact = cull && reorder;
+        let flags = if cull { culling::CULL } else { 0 } | if compact { culling::COMPACT } else { 0 };
+
+        let mut batches = Vec::<Batch>::new();
+        for ((min, max), buffers) in meshes {
+            let key = BatchKey::of(buffers);
+            let draw = self.args.len() as u32;
+            match batches.last_mut() {
+                Some(batch) if batch.key == key => {
+                    batch.draws.end = draw + 1;
+                    batch.vertices += buffers.num_vertices;
+                },
+                _ => {
+                    batches.push(Batch {
+                        key,
+                        index: self.batch_starts.len() as u32,
+                        draws: draw..draw + 1,
+                        vertices: buffers.num_vertices,
+                        compact,
+                    });
+                    self.batch_starts.push(draw);
+                },
+            }
+
+            let args = buffers.draw_args();
+            self.args.push(args);
+            if self.culler.is_some() {
+                let batch = batches.last().unwrap().index;
+                self.inputs.push(DrawInput::new(args, batch, flags, min, max));
+            }
+        }
+        batches
+    }
+
+    pub fn draw(&self, pass: &mut wgpu::RenderPass, pool: &MeshPool, list: DrawList, stats: &mut RenderStats) {
+        for batch in &self.lists[list as usize] {
+            pass.set_vertex_buffer(0, pool.vertex_page(batch.key.vertex_page).slice(..));
+            pass.set_index_buffer(pool.index_page(batch.key.index_page).slice(..), batch.key.index_format);
+
+            let draws = batch.draws.clone();
+            let buffer = match &self.culler {
+                Some(culler) => culler.outputs(),
+                None => self.buffer.as_ref(),
+            };
+            match (self.mode, buffer) {
+                (DrawMode::MultiIndirect, Some(buffer)) if batch.compact && self.indirect_count => {
+                    pass.multi_draw_indexed_indirect_count(
+                        buffer,
+                        draws.start as u64 * ARGS_SIZE,
+                        self.culler.as_ref().and_then(GpuCuller::counts).unwrap(),
+                        batch.index as u64 * 4,
+                        draws.len() as u32,
+                    );
+                    stats.draw_calls += 1;
+                },
+                // Culled draws are left zeroed, which draws nothing.
+                (DrawMode::MultiIndirect, Some(buffer)) => {
+                    pass.multi_draw_indexed_indirect(buffer, draws.start as u64 * ARGS_SIZE, draws.len() as u32);
+                    stats.draw_calls += 1;
+                },
+                (DrawMode::Indirect, Some(buffer)) => {
+                    for draw in draws {
+                        pass.draw_indexed_indirect(buffer, draw as u64 * ARGS_SIZE);
+                        stats.draw_calls += 1;
+                    }
+                },
+                _ => {
+                    for args in &self.args[draws.start as usize..draws.end as usize] {
+                        pass.draw_indexed(args.first_index..args.first_index + args.index_count, args.base_vertex, 0..1);
+                        stats.draw_calls += 1;
+                    }
+                },
+            }
+            // With GPU culling this counts what was submitted, not what
+            // survived.
+            stats.vertices += batch.vertices;
+        }
+    }
+}
+
+fn layer<'a>(
+    chunks: &[(Bounds, &'a ChunkMeshBuffers)],
+    layer: fn(&ChunkMeshBuffers) -> &Option<ChunkBuffers>,
+) -> Vec<(Bounds, &'a ChunkBuffers)> {
+    chunks.iter().filter_map(|(bounds, buffers)| layer(buffers).as_ref().map(|buffers| (*bounds, buffers))).collect()
+}
+
+fn chunk_bounds(pos: ChunkPos) -> Bounds {
+    let min = pos.origin();
+    (min.map(|v| v as f32), min.map(|v| (v + CHUNK_SIZE as i32) as f32))
+}
+
+fn column_bounds(column: ColumnPos) -> Bounds {
+    let (x, z) = column.origin();
+    (
+        [x as f32, 0.0, z as f32],
+        [(x + CHUNK_SIZE as i32) as f32, LOD_HEIGHT, (z + CHUNK_SIZE as i32) as f32],
+    )
+}
@@ -0,0 +1,259 @@
+//! Maps keys and mouse buttons to the named actions the game responds to,
+//! so every control can be rebound.
+
+mod gamepad;
+
+use std::{collections::HashMap, fmt};
+
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::config::ControlsConfig;
+
+pub use self::gamepad::{Gamepads, PadEvent};
+
+/// Something the player can do that's bound to a key or button.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Descend,
+    Sprint,
+    ToggleFly,
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+    Break,
+    Place,
+    Inventory,
+    Console,
+    DebugOverlay,
+    Profiler,
+    Screenshot,
+    ToggleVsync,
+    ToggleFullscreen,
+    ToggleWireframe,
+    ChunkBorders,
+    CycleCamera,
+    MinimapZoom,
+    WorldMap,
+    Undo,
+    Redo,
+}
+
+impl Action {
+    pub const ALL: [Action ; 28] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Descend,
+        Action::Sprint,
+        Action::ToggleFly,
+        Action::LookLeft,
+        Action::LookRight,
+        Action::LookUp,
+        Action::LookDown,
+        Action::Break,
+        Action::Place,
+        Action::Inventory,
+        Action::Console,
+        Action::DebugOverlay,
+        Action::Profiler,
+        Action::Screenshot,
+        Action::ToggleVsync,
+        Action::ToggleFullscreen,
+        Action::ToggleWireframe,
+        Action::ChunkBorders,
+        Action::CycleCamera,
+        Action::MinimapZoom,
+        Action::WorldMap,
+        Action::Undo,
+        Action::Redo,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "Move Forward",
+            Action::MoveBackward => "Move Backward",
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::Jump => "Jump / Fly Up",
+            Action::Descend => "Sneak / Fly Down",
+            Action::Sprint => "Sprint",
+            Action::ToggleFly => "Toggle Flying",
+            Action::LookLeft => "Look Left",
+            Action::LookRight => "Look Right",
+            Action::LookUp => "Look Up",
+            Action::LookDown => "Look Down",
+            Action::Break => "Break Block",
+            Action::Place => "Place Block",
+            Action::Inventory => "Inventory",
+            Action::Console => "Console",
+            Action::DebugOverlay => "Debug Overlay",
+            Action::Profiler => "Profiler",
+            Action::Screenshot => "Screenshot",
+            Action::ToggleVsync => "Toggle VSync",
+            Action::ToggleFullscreen => "Toggle Fullscreen",
+            Action::ToggleWireframe => "Toggle Wireframe",
+            Action::ChunkBorders => "Chunk Borders",
+            Action::CycleCamera => "Change Camera",
+            Action::MinimapZoom => "Zoom Minimap",
+            Action::WorldMap => "World Map",
+            Action::Undo => "Undo Edit",
+            Action::Redo => "Redo Edit",
+        }
+    }
+
+    pub fn default_binding(self) -> Binding {
+        match self {
+            Action::MoveForward => Binding::Key(KeyCode::KeyW),
+            Action::MoveBackward => Binding::Key(KeyCode::KeyS),
+            Action::MoveLeft => Binding::Key(KeyCode::KeyA),
+            Action::MoveRight => Binding::Key(KeyCode::KeyD),
+            Action::Jump => Binding::Key(KeyCode::Space),
+            Action::Descend => Binding::Key(KeyCode::ShiftLeft),
+            Action::Sprint => Binding::Key(KeyCode::ControlLeft),
+            Action::ToggleFly => Binding::Key(KeyCode::KeyF),
+            Action::LookLeft => Binding::Key(KeyCode::ArrowLeft),
+            Action::LookRight => Binding::Key(KeyCode::ArrowRight),
+            Action::LookUp => Binding::Key(KeyCode::ArrowUp),
+            Action::LookDown => Binding::Key(KeyCode::ArrowDown),
+            Action::Break => Binding::Mouse(MouseButton::Left),
+            Action::Place => Binding::Mouse(MouseButton::Right),
+            Action::Inventory => Binding::Key(KeyCode::KeyE),
+            Action::Console => Binding::Key(KeyCode::Backquote),
+            Action::DebugOverlay => Binding::Key(KeyCode::F3),
+            Action::Profiler => Binding::Key(KeyCode::F7),
+            Action::Screenshot => Binding::Key(KeyCode::F2),
+            Action::ToggleVsync => Binding::Key(KeyCode::F9),
+            Action::ToggleFullscreen => Binding::Key(KeyCode::F11),
+            Action::ToggleWireframe => Binding::Key(KeyCode::F4),
+            Action::ChunkBorders => Binding::Key(KeyCode::F6),
+            Action::CycleCamera => Binding::Key(KeyCode::F5),
+            Action::MinimapZoom => Binding::Key(KeyCode::KeyN),
+            Action::WorldMap => Binding::Key(KeyCode::KeyM),
+            Action::Undo => Binding::Key(KeyCode::KeyZ),
+            Action::Redo => Binding::Key(KeyCode::KeyY),
+        }
+    }
+}
+
+/// A key, by its position on the keyboard, or a mouse button. In the
+/// config keys use winit's names (`KeyW`, `Space`) and mouse buttons are
+/// `MouseLeft`, `MouseRight`, `MouseMiddle`, `MouseBack`, `MouseForward` or
+/// `Mouse` followed by a button number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    /// The binding an event presses or releases, with whether it was
+    /// pressed and whether it's a key repeat.
+    pub fn from_event(event: &WindowEvent) -> Option<(Binding, bool, bool)> {
+        match event {
+            WindowEvent::KeyboardInput {
+                event: KeyEvent { state, physical_key: PhysicalKey::Code(keycode), repeat, .. },
+                ..
+            } => Some((Binding::Key(*keycode), *state == ElementState::Pressed, *repeat)),
+            WindowEvent::MouseInput { state, button, .. } => {
+                Some((Binding::Mouse(*button), *state == ElementState::Pressed, false))
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether `event` is a fresh press of this binding.
+    pub fn just_pressed(self, event: &WindowEvent) -> bool {
+        Binding::from_event(event) == Some((self, true, false))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(keycode) => write!(f, "{keycode:?}"),
+            Binding::Mouse(MouseButton::Left) => write!(f, "MouseLeft"),
+            Binding::Mouse(MouseButton::Right) => write!(f, "MouseRight"),
+            Binding::Mouse(MouseButton::Middle) => write!(f, "MouseMiddle"),
+            Binding::Mouse(MouseButton::Back) => write!(f, "MouseBack"),
+            Binding::Mouse(MouseButton::Forward) => write!(f, "MouseForward"),
+            Binding::Mouse(MouseButton::Other(button)) => write!(f, "Mouse{button}"),
+        }
+    }
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> Self {
+        binding.to_string()
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        let button = match name.strip_prefix("Mouse") {
+            Some("Left") => Some(MouseButton::Left),
+            Some("Right") => Some(MouseButton::Right),
+            Some("Middle") => Some(MouseButton::Middle),
+            Some("Back") => Some(MouseButton::Back),
+            Some("Forward") => Some(MouseButton::Forward),
+            Some(number) => number.parse().ok().map(MouseButton::Other),
+            None => None,
+        };
+        if let Some(button) = button {
+            return Ok(Binding::Mouse(button));
+        }
+
+        let key: Result<KeyCode, serde::de::value::Error> = KeyCode::deserialize(name.as_str().into_deserializer());
+        key.map(Binding::Key).map_err(|_| format!("unknown key or button `{name}`"))
+    }
+}
+
+/// An action's binding being pressed or released.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ActionEvent {
+    pub action: Action,
+    pub pressed: bool,
+    pub repeat: bool,
+}
+
+/// Looks up which actions an event triggers.
+pub struct InputMap {
+    actions: HashMap<Binding, Vec<Action>>,
+}
+
+impl InputMap {
+    pub fn new(controls: &ControlsConfig) -> Self {
+        let mut actions = HashMap::<Binding, Vec<Action>>::new();
+        for action in Action::ALL {
+            actions.entry(controls.binding(action)).or_default().push(action);
+        }
+        Self { actions }
+    }
+
+    /// Every action bound to what `event` pressed or released.
+    pub fn events(&self, event: &WindowEvent) -> Vec<ActionEvent> {
+        let Some((binding, pressed, repeat)) = Binding::from_event(event) else {
+            return Vec::new();
+        };
+        self.actions.get(&binding)
+            .into_iter()
+            .flatten()
+            .map(|&action| ActionEvent { action, pressed, repeat })
+            .collect()
+    }
+}
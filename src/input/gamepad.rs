@@ -0,0 +1,136 @@
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use log::{info, warn};
+
+use crate::config::GamepadConfig;
+
+use super::ActionEvent;
+
+/// Something a gamepad did that the game responds to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PadEvent {
+    Action(ActionEvent),
+    HotbarNext,
+    HotbarPrevious,
+    Pause,
+    Connected(String),
+    Disconnected(String),
+}
+
+/// Where the sticks point after the deadzone and response curve, each
+/// axis from -1 to 1 with up positive.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sticks {
+    pub movement: [f32 ; 2],
+    pub look: [f32 ; 2],
+}
+
+/// Reads whichever gamepad was used last. Gamepads can be plugged in and
+/// out at any time; without gamepad support on the platform this does
+/// nothing.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+    active: Option<GamepadId>,
+    settings: GamepadConfig,
+}
+
+impl Gamepads {
+    pub fn new(settings: GamepadConfig) -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                warn!("no gamepad support: {e}");
+                None
+            },
+        };
+        let active = gilrs.as_ref().and_then(|gilrs| {
+            let (id, gamepad) = gilrs.gamepads().next()?;
+            info!("using gamepad {}", gamepad.name());
+            Some(id)
+        });
+        Self { gilrs, active, settings }
+    }
+
+    pub fn set_settings(&mut self, settings: GamepadConfig) {
+        self.settings = settings;
+    }
+
+    /// Takes everything that happened since the last call. Disabled
+    /// gamepads still report being plugged in and out.
+    pub fn poll(&mut self) -> Vec<PadEvent> {
+        let Some(gilrs) = &mut self.gilrs else {
+            return Vec::new();
+        };
+
+        let mut events = Vec::new();
+        while let Some(event) = gilrs.next_event() {
+            let name = || gilrs.gamepad(event.id).name().to_string();
+            match event.event {
+                EventType::Connected => {
+                    info!("gamepad connected: {}", name());
+                    self.active.get_or_insert(event.id);
+                    events.push(PadEvent::Connected(name()));
+                },
+                EventType::Disconnected => {
+                    info!("gamepad disconnected: {}", name());
+                    if self.active == Some(event.id) {
+                        self.active = gilrs.gamepads().map(|(id, _)| id).find(|&id| id != event.id);
+                    }
+                    events.push(PadEvent::Disconnected(name()));
+                },
+                EventType::ButtonPressed(button, _) if self.settings.enabled => {
+                    self.active = Some(event.id);
+                    events.extend(button_event(&self.settings, button, true));
+                },
+                EventType::ButtonReleased(button, _) if self.settings.enabled => {
+                    events.extend(button_event(&self.settings, button, false));
+                },
+                _ => (),
+            }
+        }
+        events
+    }
+
+    pub fn sticks(&self) -> Sticks {
+        let (Some(gilrs), Some(id), true) = (&self.gilrs, self.active, self.settings.enabled) else {
+            return Sticks::default();
+        };
+        let Some(gamepad) = gilrs.connected_gamepad(id) else {
+            return Sticks::default();
+        };
+
+        let stick = |x, y| self.shape([gamepad.value(x), gamepad.value(y)]);
+        let mut look = stick(Axis::RightStickX, Axis::RightStickY);
+        if self.settings.invert_look {
+            look[1] = -look[1];
+        }
+        Sticks { movement: stick(Axis::LeftStickX, Axis::LeftStickY), look }
+    }
+
+    // Applies the deadzone to the stick as a whole rather than per axis,
+    // so diagonals aren't snapped to the axes, then rescales what's left
+    // to start from zero and bends it by the response curve.
+    fn shape(&self, [x, y]: [f32 ; 2]) -> [f32 ; 2] {
+        let magnitude = (x * x + y * y).sqrt();
+        let deadzone = self.settings.deadzone.clamp(0.0, 0.99);
+        if magnitude <= deadzone {
+            return [0.0, 0.0];
+        }
+        let scaled = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0);
+        let curved = scaled.powf(self.settings.response_curve.max(0.1));
+        [x / magnitude * curved, y / magnitude * curved]
+    }
+}
+
+fn button_event(settings: &GamepadConfig, button: Button, pressed: bool) -> Option<PadEvent> {
+    if pressed && button == settings.pause {
+        return Some(PadEvent::Pause);
+    }
+    if pressed && button == settings.hotbar_next {
+        return Some(PadEvent::HotbarNext);
+    }
+    if pressed && button == settings.hotbar_previous {
+        return Some(PadEvent::HotbarPrevious);
+    }
+    let (&action, _) = settings.buttons.iter().find(|(_, &bound)| bound == button)?;
+    Some(PadEvent::Action(ActionEvent { action, pressed, repeat: false }))
+}
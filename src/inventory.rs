@@ -0,0 +1,302 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block::BlockId;
+
+pub const HOTBAR_SLOTS: usize = 9;
+pub const MAIN_SLOTS: usize = 27;
+pub const INVENTORY_SLOTS: usize = HOTBAR_SLOTS + MAIN_SLOTS;
+
+pub const MAX_STACK: u32 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub block: BlockId,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(block: BlockId, count: u32) -> Self {
+        Self { block, count }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClickButton {
+    // Picks up, puts down, merges or swaps whole stacks.
+    Primary,
+    // Splits a stack in half or puts down a single item.
+    Secondary,
+}
+
+/// The player's items. Slots `0..HOTBAR_SLOTS` are the hotbar, the rest
+/// the main inventory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SavedInventory", into = "SavedInventory")]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::with_slots(INVENTORY_SLOTS)
+    }
+}
+
+impl Inventory {
+    /// An empty set of `count` slots, for holding items somewhere other
+    /// than the player's inventory.
+    pub fn with_slots(count: usize) -> Self {
+        Self { slots: vec![None ; count] }
+    }
+
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// What a new player spawns with, filling the hotbar and then the main
+    /// inventory.
+    pub fn starter() -> Self {
+        let mut inventory = Self::default();
+        let blocks = [
+            BlockId::GRASS,
+            BlockId::DIRT,
+            BlockId::STONE,
+            BlockId::COBBLESTONE,
+            BlockId::PLANKS,
+            BlockId::LOG,
+            BlockId::LEAVES,
+            BlockId::WATER,
+            BlockId::BRICKS,
+            BlockId::STONE_SLAB,
+            BlockId::PLANKS_SLAB,
+            BlockId::COBBLESTONE_STAIRS,
+            BlockId::PLANKS_STAIRS,
+            BlockId::FENCE,
+        ];
+        for (slot, block) in blocks.into_iter().enumerate() {
+            inventory.slots[slot] = Some(ItemStack::new(block, MAX_STACK));
+        }
+        inventory
+    }
+
+    pub fn get(&self, slot: usize) -> Option<ItemStack> {
+        self.slots.get(slot).copied().flatten()
+    }
+
+    pub fn set(&mut self, slot: usize, stack: Option<ItemStack>) {
+        if let Some(target) = self.slots.get_mut(slot) {
+            *target = stack;
+        }
+    }
+
+    /// Adds items, filling matching stacks before empty slots and the
+    /// hotbar before the main inventory. Returns how many didn't fit.
+    pub fn add(&mut self, block: BlockId, mut count: u32) -> u32 {
+        for slot in self.slots.iter_mut().flatten() {
+            if slot.block == block && slot.count < MAX_STACK {
+                let moved = count.min(MAX_STACK - slot.count);
+                slot.count += moved;
+                count -= moved;
+                if count == 0 {
+                    return 0;
+                }
+            }
+        }
+
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            let moved = count.min(MAX_STACK);
+            *slot = Some(ItemStack::new(block, moved));
+            count -= moved;
+            if count == 0 {
+                return 0;
+            }
+        }
+
+        count
+    }
+
+    /// How many of `block` `add` would take.
+    pub fn room_for(&self, block: BlockId) -> u32 {
+        self.slots.iter()
+            .map(|slot| match slot {
+                None => MAX_STACK,
+                Some(stack) if stack.block == block => MAX_STACK - stack.count,
+                Some(_) => 0,
+            })
+            .sum()
+    }
+
+    /// Removes one item from a slot and returns its block.
+    pub fn remove_one(&mut self, slot: usize) -> Option<BlockId> {
+        let stack = self.slots.get_mut(slot)?;
+        let block = stack.as_ref()?.block;
+        if let Some(s) = stack.as_mut() {
+            s.count -= 1;
+            if s.count == 0 {
+                *stack = None;
+            }
+        }
+        Some(block)
+    }
+
+    pub fn take(&mut self, slot: usize) -> Option<ItemStack> {
+        self.slots.get_mut(slot)?.take()
+    }
+
+    /// Takes the larger half of a stack, leaving the rest in the slot.
+    pub fn split(&mut self, slot: usize) -> Option<ItemStack> {
+        let stack = self.slots.get_mut(slot)?;
+        let s = stack.as_mut()?;
+        let taken = s.count.div_ceil(2);
+        let block = s.block;
+        s.count -= taken;
+        if s.count == 0 {
+            *stack = None;
+        }
+        Some(ItemStack::new(block, taken))
+    }
+
+    /// Puts as much of `stack` into the slot as fits. An empty slot takes
+    /// the whole stack and a slot holding a different block swaps with it;
+    /// whatever remains is returned.
+    pub fn merge(&mut self, slot: usize, stack: ItemStack) -> Option<ItemStack> {
+        let Some(target) = self.slots.get_mut(slot) else {
+            return Some(stack);
+        };
+
+        match target {
+            None => {
+                *target = Some(stack);
+                None
+            },
+            Some(existing) if existing.block == stack.block => {
+                let moved = stack.count.min(MAX_STACK - existing.count);
+                existing.count += moved;
+                let left = stack.count - moved;
+                (left > 0).then_some(ItemStack::new(stack.block, left))
+            },
+            Some(existing) => Some(std::mem::replace(existing, stack)),
+        }
+    }
+
+    // Puts a single item from `stack` into the slot if it's empty or holds
+    // the same block with room to spare.
+    fn place_one(&mut self, slot: usize, stack: &mut ItemStack) -> bool {
+        let Some(target) = self.slots.get_mut(slot) else {
+            return false;
+        };
+
+        match target {
+            None => *target = Some(ItemStack::new(stack.block, 1)),
+            Some(existing) if existing.block == stack.block && existing.count < MAX_STACK => {
+                existing.count += 1;
+            },
+            Some(_) => return false,
+        }
+        stack.count -= 1;
+        true
+    }
+
+    /// Moves a whole stack between slots, merging into a matching stack or
+    /// swapping with a different one.
+    pub fn move_stack(&mut self, from: usize, to: usize) {
+        if from == to {
+            return;
+        }
+        let Some(stack) = self.take(from) else {
+            return;
+        };
+        if let Some(rest) = self.merge(to, stack) {
+            self.slots[from] = Some(rest);
+        }
+    }
+
+    /// Moves as much of the stack in `slot` as fits into `other`, filling
+    /// matching stacks first, and leaves the rest where it was.
+    pub fn transfer(&mut self, slot: usize, other: &mut Inventory) {
+        let Some(stack) = self.take(slot) else {
+            return;
+        };
+        let left = other.add(stack.block, stack.count);
+        if left > 0 {
+            self.slots[slot] = Some(ItemStack::new(stack.block, left));
+        }
+    }
+
+    /// Shift-click: sends a stack from the hotbar to the main inventory or
+    /// the other way around, into the first slots that take it.
+    pub fn quick_move(&mut self, slot: usize) {
+        let Some(mut stack) = self.take(slot) else {
+            return;
+        };
+
+        let targets = if slot < HOTBAR_SLOTS { HOTBAR_SLOTS..INVENTORY_SLOTS } else { 0..HOTBAR_SLOTS };
+        let (matching, empty): (Vec<_>, Vec<_>) = targets
+            .filter(|&target| self.get(target).is_none_or(|s| s.block == stack.block))
+            .partition(|&target| self.get(target).is_some());
+
+        for target in matching.into_iter().chain(empty) {
+            match self.merge(target, stack) {
+                Some(rest) => stack = rest,
+                None => return,
+            }
+        }
+        self.slots[slot] = Some(stack);
+    }
+
+    /// Applies a click on `slot` while `cursor` holds whatever the player
+    /// has picked up.
+    pub fn click(&mut self, slot: usize, cursor: &mut Option<ItemStack>, button: ClickButton) {
+        match (button, cursor.take()) {
+            (ClickButton::Primary, None) => *cursor = self.take(slot),
+            (ClickButton::Primary, Some(stack)) => *cursor = self.merge(slot, stack),
+            (ClickButton::Secondary, None) => *cursor = self.split(slot),
+            (ClickButton::Secondary, Some(mut stack)) => {
+                if !self.place_one(slot, &mut stack) {
+                    // A different block is in the way; swap like a primary
+                    // click would.
+                    *cursor = self.merge(slot, stack);
+                    return;
+                }
+                *cursor = (stack.count > 0).then_some(stack);
+            },
+        }
+    }
+}
+
+// TOML has no null, so only occupied slots are written, each with its
+// index.
+#[derive(Serialize, Deserialize)]
+struct SavedInventory {
+    #[serde(default)]
+    items: Vec<SavedSlot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedSlot {
+    slot: usize,
+    #[serde(flatten)]
+    stack: ItemStack,
+}
+
+impl From<SavedInventory> for Inventory {
+    fn from(saved: SavedInventory) -> Self {
+        let mut inventory = Inventory::default();
+        for SavedSlot { slot, stack } in saved.items {
+            if slot < INVENTORY_SLOTS && stack.count > 0 {
+                inventory.slots[slot] = Some(ItemStack::new(stack.block, stack.count.min(MAX_STACK)));
+            }
+        }
+        inventory
+    }
+}
+
+impl From<Inventory> for SavedInventory {
+    fn from(inventory: Inventory) -> Self {
+        let items = inventory.slots.into_iter()
+            .enumerate()
+            .filter_map(|(slot, stack)| Some(SavedSlot { slot, stack: stack? }))
+            .collect();
+        SavedInventory { items }
+    }
+}
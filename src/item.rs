@@ -0,0 +1,110 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::{
+    block::BlockId,
+    ecs::{Collider, Ecs, Entity, MeshRef, Transform, Velocity},
+    inventory::{Inventory, ItemStack},
+    player::PLAYER_HEIGHT,
+};
+
+pub const ITEM_SIZE: f32 = 0.25;
+
+const PICKUP_DELAY: f32 = 0.5;
+const PICKUP_RADIUS: f32 = 1.5;
+const DESPAWN_AGE: f32 = 300.0;
+const SPIN_SPEED: f32 = 1.5;
+
+/// A block lying in the world waiting to be picked up.
+pub struct DroppedItem {
+    pub block: BlockId,
+    pub count: u32,
+    pub age: f32,
+}
+
+impl DroppedItem {
+    /// Vertical offset of the gently bobbing model above the entity's
+    /// position.
+    pub fn bob(&self) -> f32 {
+        0.1 + (self.age * 2.5).sin() * 0.06
+    }
+}
+
+/// Spawns an item at the center of a broken block with a small pop
+/// upwards in a direction derived from the block position.
+pub fn spawn_dropped_item(ecs: &mut Ecs, block: BlockId, x: i32, y: i32, z: i32) -> Entity {
+    spawn_dropped_stack(ecs, ItemStack::new(block, 1), x, y, z, 0)
+}
+
+/// Spawns whole stacks, such as a broken chest's contents, popping each
+/// out in a different direction.
+pub fn spawn_dropped_stacks(ecs: &mut Ecs, stacks: &[ItemStack], x: i32, y: i32, z: i32) {
+    for (index, &stack) in stacks.iter().enumerate() {
+        spawn_dropped_stack(ecs, stack, x, y, z, index);
+    }
+}
+
+// The `index`th item out of a block turns a golden angle on from the one
+// before, which spreads any number of them evenly.
+fn spawn_dropped_stack(ecs: &mut Ecs, stack: ItemStack, x: i32, y: i32, z: i32, index: usize) -> Entity {
+    let hash = (x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663) ^ z.wrapping_mul(83_492_791)) as u32;
+    let angle = (hash % 360) as f32 * std::f32::consts::PI / 180.0 + index as f32 * 2.4;
+    let velocity = Vector3::new(angle.cos() * 1.2, 4.0, angle.sin() * 1.2);
+    let ItemStack { block, count } = stack;
+
+    let entity = ecs.spawn();
+    let position = Point3::new(x as f32 + 0.5, y as f32 + 0.25, z as f32 + 0.5);
+    ecs.transforms.insert(entity, Transform::new(position));
+    ecs.velocities.insert(entity, Velocity(velocity));
+    ecs.colliders.insert(entity, Collider::new(ITEM_SIZE / 2.0, ITEM_SIZE));
+    ecs.meshes.insert(entity, MeshRef::Block(block));
+    ecs.items.insert(entity, DroppedItem { block, count, age: 0.0 });
+    entity
+}
+
+/// Ages, spins and slows dropped items, and hands them to the player when
+/// they walk close enough. Whatever doesn't fit in the inventory stays on
+/// the ground.
+pub fn item_system(ecs: &mut Ecs, player: Entity, inventory: &mut Inventory, dt: f32) {
+    let player_center = ecs.transforms.get(player)
+        .map(|t| t.position + Vector3::unit_y() * (PLAYER_HEIGHT / 2.0));
+
+    let mut expired = Vec::new();
+    for (entity, item) in ecs.items.iter_mut() {
+        item.age += dt;
+
+        let (Some(transform), Some(collider), Some(Velocity(velocity))) = (
+            ecs.transforms.get_mut(entity),
+            ecs.colliders.get(entity),
+            ecs.velocities.get_mut(entity),
+        ) else {
+            continue;
+        };
+
+        transform.yaw = item.age * SPIN_SPEED;
+        // Ground friction so items don't slide forever after the pop.
+        if collider.on_ground {
+            let damping = (1.0 - 8.0 * dt).max(0.0);
+            velocity.x *= damping;
+            velocity.z *= damping;
+        }
+
+        if item.age > DESPAWN_AGE {
+            expired.push(entity);
+            continue;
+        }
+
+        let near_player = player_center.is_some_and(|center| {
+            (transform.position - center).magnitude() < PICKUP_RADIUS
+        });
+        if item.age > PICKUP_DELAY && near_player {
+            item.count = inventory.add(item.block, item.count);
+            if item.count == 0 {
+                expired.push(entity);
+            }
+        }
+    }
+
+    for entity in expired {
+        ecs.despawn(entity);
+    }
+}
@@ -0,0 +1,456 @@
+//! Light levels kept for every block: sky light falling from above and
+//! block light glowing out of blocks like torches. Block light has a red,
+//! green and blue level that each spread on their own, so tinted lights
+//! mix where they meet. Light loses a level for each block it spreads to,
+//! plus the opacity of the block it enters, but full sky light shines
+//! straight down through clear blocks without fading. Chunks are lit as
+//! they're added and every block change relights just the area it affects,
+//! remeshing whatever chunks that touches. The sky light coming down into
+//! a chunk is taken from the chunk above it, or guessed from the terrain's
+//! height while that isn't loaded, and fixed up once it is.
+
+use std::{
+    cmp::Reverse,
+    collections::{HashSet, VecDeque},
+};
+
+use crate::{
+    block::BlockState,
+    chunk::{ChunkPos, CHUNK_SIZE},
+    world::World,
+};
+
+/// The brightest light gets, from full sky or the brightest blocks.
+pub const MAX_LIGHT: u8 = 15;
+
+const NEIGHBORS: [[i32 ; 3] ; 6] = [[1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+const SIDES: [(i32, i32) ; 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DOWN: [i32 ; 3] = [0, -1, 0];
+
+/// The sky light and the red, green and blue block light at a position,
+/// each from 0 to `MAX_LIGHT`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Light {
+    pub sky: u8,
+    pub block: [u8 ; 3],
+}
+
+impl Light {
+    /// Open sky with nothing glowing nearby.
+    pub const SKY: Light = Light { sky: MAX_LIGHT, block: [0 ; 3] };
+
+    /// Unpacks a light stored by `bits`.
+    pub fn from_bits(bits: u16) -> Self {
+        let level = |shift: u16| (bits >> shift & 0xf) as u8;
+        Self { sky: level(12), block: [level(8), level(4), level(0)] }
+    }
+
+    /// All four levels packed four bits each, sky light in the highest
+    /// and then red, green and blue.
+    pub fn bits(self) -> u16 {
+        let [red, green, blue] = self.block.map(u16::from);
+        (self.sky as u16) << 12 | red << 8 | green << 4 | blue
+    }
+
+    /// The levels scaled to a byte each, sky light then red, green and
+    /// blue, so the shader reads them as fractions of `MAX_LIGHT`.
+    pub fn vertex(self) -> [u8 ; 4] {
+        let scale = |level: u8| level * (u8::MAX / MAX_LIGHT);
+        let [red, green, blue] = self.block;
+        [scale(self.sky), scale(red), scale(green), scale(blue)]
+    }
+
+    /// The brightest of the block light's colors.
+    pub fn block_level(self) -> u8 {
+        self.block.into_iter().max().unwrap_or(0)
+    }
+
+    /// How lit the spot is overall with the sky at `daylight` of its full
+    /// brightness.
+    pub fn level(self, daylight: f32) -> u8 {
+        ((self.sky as f32 * daylight).round() as u8).max(self.block_level())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Sky,
+    // The red, green or blue block light, by its index in `Light::block`.
+    Block(usize),
+}
+
+impl Channel {
+    const ALL: [Channel ; 4] = [Channel::Sky, Channel::Block(0), Channel::Block(1), Channel::Block(2)];
+
+    // Where the channel's queue goes in a set of one per channel.
+    fn index(self) -> usize {
+        match self {
+            Channel::Sky => 0,
+            Channel::Block(color) => 1 + color,
+        }
+    }
+
+    fn get(self, light: Light) -> u8 {
+        match self {
+            Channel::Sky => light.sky,
+            Channel::Block(color) => light.block[color],
+        }
+    }
+
+    fn with(self, mut light: Light, level: u8) -> Light {
+        match self {
+            Channel::Sky => light.sky = level,
+            Channel::Block(color) => light.block[color] = level,
+        }
+        light
+    }
+
+    // The light a block makes itself, before any reaches it from around.
+    fn emitted(self, state: BlockState) -> u8 {
+        match self {
+            Channel::Sky => 0,
+            Channel::Block(color) => state.emission()[color],
+        }
+    }
+}
+
+// Where each channel spreads from, by `Channel::index`.
+type Queues = [VecDeque<[i32 ; 3]> ; 4];
+
+/// Lights a chunk that was just added: sky light down each column, block
+/// light out of whatever glows, and both across the borders of the
+/// neighbours that are loaded, in each direction. The chunk below is fixed
+/// up for what this one lets through.
+pub fn light_chunk(world: &mut World, pos: ChunkPos) {
+    let mut queues = Queues::default();
+    let Some(open) = light_columns(world, pos, &mut queues) else {
+        return;
+    };
+    seed_from_around(world, pos, &open, &mut queues);
+    spread_all(world, queues);
+    light_below(world, pos);
+    // Sky light fills chunks above the terrain to one level, which takes
+    // no room once the darkness it replaced is dropped.
+    if let Some(chunk) = world.chunks.get_mut(&pos) {
+        chunk.compact();
+    }
+}
+
+/// Lights the chunks in and around `changed` again from scratch, for when
+/// so many blocks changed at once that relighting around each would take
+/// longer. Light never spreads as far as a chunk, so no change reaches
+/// further, except full sky light straight down, which the chunks below
+/// are fixed up for. Returns the chunks relit.
+pub fn relight_chunks(world: &mut World, changed: &HashSet<ChunkPos>) -> HashSet<ChunkPos> {
+    let area = changed.iter()
+        .flat_map(|pos| pos.around(1, 1))
+        .filter(|pos| world.chunks.contains_key(pos))
+        .collect::<HashSet<_>>();
+
+    // Every column first, so nothing is lit from what was left in a chunk
+    // not yet cleared, and light only spreads once, across all of them.
+    // They go from the top down, so each takes the sky light coming into
+    // it from the one above once that's relit.
+    for pos in &area {
+        if let Some(chunk) = world.chunks.get_mut(pos) {
+            chunk.clear_light();
+        }
+    }
+    let mut order = area.iter().copied().collect::<Vec<_>>();
+    order.sort_by_key(|pos| Reverse(pos.y));
+    let mut queues = Queues::default();
+    let columns = order.into_iter()
+        .filter_map(|pos| light_columns(world, pos, &mut queues).map(|open| (pos, open)))
+        .collect::<Vec<_>>();
+    for (pos, open) in &columns {
+        seed_from_around(world, *pos, open, &mut queues);
+    }
+    spread_all(world, queues);
+    for (pos, _) in &columns {
+        if !area.contains(&pos.offset(0, -1, 0)) {
+            light_below(world, *pos);
+        }
+    }
+    for pos in &area {
+        if let Some(chunk) = world.chunks.get_mut(pos) {
+            chunk.compact();
+        }
+    }
+    area
+}
+
+// Lights each column of a chunk on its own, with sky light down it and the
+// light of whatever in it glows, and queues where that spreads from.
+// Returns how far up the chunk each column's lowest block with open sky
+// above it is, or `CHUNK_SIZE` for none, by `z * CHUNK_SIZE + x`, or `None`
+// if the chunk isn't loaded.
+fn light_columns(world: &mut World, pos: ChunkPos, queues: &mut Queues) -> Option<[i32 ; CHUNK_SIZE * CHUNK_SIZE]> {
+    let [origin_x, origin_y, origin_z] = pos.origin();
+    let entering = sky_entering(world, pos);
+    let chunk = world.chunks.get_mut(&pos)?;
+    let mut open = [CHUNK_SIZE as i32 ; CHUNK_SIZE * CHUNK_SIZE];
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            let position = |y: usize| [origin_x + x as i32, origin_y + y as i32, origin_z + z as i32];
+            let mut level = entering[z * CHUNK_SIZE + x];
+            for y in (0..CHUNK_SIZE).rev() {
+                let state = chunk.get_state(x, y, z);
+                level = arriving(Channel::Sky, level, DOWN, state);
+                if level == MAX_LIGHT {
+                    open[z * CHUNK_SIZE + x] = y as i32;
+                } else if level > 0 {
+                    // Dimmed by leaves or water, which still lets it
+                    // spread sideways.
+                    queues[Channel::Sky.index()].push_back(position(y));
+                }
+                let emitted = state.emission();
+                for (color, &emitted) in emitted.iter().enumerate() {
+                    if emitted > 0 {
+                        queues[Channel::Block(color).index()].push_back(position(y));
+                    }
+                }
+                chunk.set_light(x, y, z, Light { sky: level, block: emitted });
+            }
+        }
+    }
+    Some(open)
+}
+
+// The sky light coming down into the top of each column of a chunk, by
+// `z * CHUNK_SIZE + x`: what's at the bottom of the chunk above, or while
+// that isn't loaded, full sky over the terrain and none under it.
+fn sky_entering(world: &World, pos: ChunkPos) -> [u8 ; CHUNK_SIZE * CHUNK_SIZE] {
+    let [origin_x, origin_y, origin_z] = pos.origin();
+    let top = origin_y + CHUNK_SIZE as i32;
+    let above = world.chunks.get(&pos.offset(0, 1, 0));
+    std::array::from_fn(|i| {
+        let (x, z) = (i % CHUNK_SIZE, i / CHUNK_SIZE);
+        match above {
+            Some(chunk) => chunk.light(x, 0, z).sky,
+            None if top > world.generator.height_at(origin_x + x as i32, origin_z + z as i32) => MAX_LIGHT,
+            None => 0,
+        }
+    })
+}
+
+// Queues what spreads into a chunk whose columns are lit from around them:
+// open sky beside a column that's darker further up, and light already in
+// the neighbours across the border.
+fn seed_from_around(world: &World, pos: ChunkPos, open: &[i32 ; CHUNK_SIZE * CHUNK_SIZE], queues: &mut Queues) {
+    let [origin_x, origin_y, origin_z] = pos.origin();
+    let size = CHUNK_SIZE as i32;
+    for z in 0..size {
+        for x in 0..size {
+            let top = open[(z * size + x) as usize];
+            let mut highest = top;
+            for (dx, dz) in SIDES {
+                let (next_x, next_z) = (x + dx, z + dz);
+                let next_top = if (0..size).contains(&next_x) && (0..size).contains(&next_z) {
+                    open[(next_z * size + next_x) as usize]
+                } else {
+                    open_sky_from(world, [origin_x + next_x, origin_y, origin_z + next_z]).unwrap_or(top)
+                };
+                highest = highest.max(next_top);
+            }
+            queues[Channel::Sky.index()].extend((top..highest).map(|y| [origin_x + x, origin_y + y, origin_z + z]));
+        }
+    }
+
+    for offset in NEIGHBORS {
+        if !world.chunks.contains_key(&pos.offset(offset[0], offset[1], offset[2])) {
+            continue;
+        }
+        // The axis across the border and the two along it.
+        let axis = offset.iter().position(|&v| v != 0).unwrap_or(0);
+        let [u, v] = match axis {
+            0 => [1, 2],
+            1 => [0, 2],
+            _ => [0, 1],
+        };
+        for i in 0..size {
+            for j in 0..size {
+                // A block on this side of the border, and the neighbour's
+                // just over it.
+                let mut inside = [origin_x, origin_y, origin_z];
+                inside[u] += i;
+                inside[v] += j;
+                inside[axis] += if offset[axis] > 0 { size - 1 } else { 0 };
+                let outside = add(inside, offset);
+                let (Some(here), Some(there)) = (stored(world, inside), stored(world, outside)) else {
+                    continue;
+                };
+                let state = world.get_state(inside[0], inside[1], inside[2]);
+                let back = offset.map(|v| -v);
+                for channel in Channel::ALL {
+                    if arriving(channel, channel.get(there), back, state) > channel.get(here) {
+                        queues[channel.index()].push_back(outside);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Fixes up the chunk below one just lit, which took the sky light coming
+// into it from what was there before: darkening it where this one now
+// shades it, and lighting it where this one lets more through.
+fn light_below(world: &mut World, pos: ChunkPos) {
+    if !world.chunks.contains_key(&pos.offset(0, -1, 0)) {
+        return;
+    }
+    let [origin_x, origin_y, origin_z] = pos.origin();
+    let mut brighter = VecDeque::new();
+    for z in 0..CHUNK_SIZE as i32 {
+        for x in 0..CHUNK_SIZE as i32 {
+            let above = [origin_x + x, origin_y, origin_z + z];
+            let below = add(above, DOWN);
+            let (Some(light_above), Some(light_below)) = (stored(world, above), stored(world, below)) else {
+                continue;
+            };
+            let state = world.get_state(below[0], below[1], below[2]);
+            let level = arriving(Channel::Sky, light_above.sky, DOWN, state);
+            if light_below.sky == MAX_LIGHT && level < MAX_LIGHT {
+                set_level(world, below, Channel::Sky, 0);
+                darken(world, Channel::Sky, below, MAX_LIGHT);
+            } else if level > light_below.sky {
+                brighter.push_back(above);
+            }
+        }
+    }
+    spread(world, Channel::Sky, brighter);
+}
+
+fn spread_all(world: &mut World, queues: Queues) {
+    for (channel, queue) in Channel::ALL.into_iter().zip(queues) {
+        spread(world, channel, queue);
+    }
+}
+
+/// Whether a block changing from `old` to `new` changes the light around
+/// it: whether the new one gives off or lets through light differently.
+pub fn affects_light(old: BlockState, new: BlockState) -> bool {
+    new.emission() != old.emission() || new.block.info().opacity != old.block.info().opacity
+}
+
+/// Relights around a block that was `old` before it changed, if that
+/// affects the light.
+pub fn block_changed(world: &mut World, position: [i32 ; 3], old: BlockState) {
+    let [x, y, z] = position;
+    let new = world.get_state(x, y, z);
+    if !affects_light(old, new) {
+        return;
+    }
+
+    for channel in Channel::ALL {
+        let Some(light) = stored(world, position) else {
+            return;
+        };
+        let level = channel.get(light);
+        let emitted = channel.emitted(new);
+        world.set_light(x, y, z, channel.with(light, emitted));
+        if level > emitted {
+            darken(world, channel, position, level);
+        }
+        // Whatever reaches the block from around it now, and whatever it
+        // gives off itself.
+        let mut queue = VecDeque::from([position]);
+        queue.extend(NEIGHBORS.map(|offset| add(position, offset)));
+        spread(world, channel, queue);
+    }
+}
+
+// The light stored at a position, if its chunk is loaded.
+fn stored(world: &World, [x, y, z]: [i32 ; 3]) -> Option<Light> {
+    let chunk = world.chunks.get(&ChunkPos::from_block(x, y, z))?;
+    let [x, y, z] = [x, y, z].map(|v| v.rem_euclid(CHUNK_SIZE as i32) as usize);
+    Some(chunk.light(x, y, z))
+}
+
+fn set_level(world: &mut World, position: [i32 ; 3], channel: Channel, level: u8) {
+    if let Some(light) = stored(world, position) {
+        let [x, y, z] = position;
+        world.set_light(x, y, z, channel.with(light, level));
+    }
+}
+
+// How far up its chunk the lowest block of a loaded column with open sky
+// above it is, for the column through `[x, y, z]` starting at the bottom of
+// a chunk; `CHUNK_SIZE` if there's none.
+fn open_sky_from(world: &World, [x, y, z]: [i32 ; 3]) -> Option<i32> {
+    world.chunks.get(&ChunkPos::from_block(x, y, z))?;
+    let mut top = CHUNK_SIZE as i32;
+    while top > 0 && stored(world, [x, y + top - 1, z]).is_some_and(|light| light.sky == MAX_LIGHT) {
+        top -= 1;
+    }
+    Some(top)
+}
+
+fn add(position: [i32 ; 3], offset: [i32 ; 3]) -> [i32 ; 3] {
+    [position[0] + offset[0], position[1] + offset[1], position[2] + offset[2]]
+}
+
+// The level light at `level` has after moving by `offset` into a block of
+// `state`.
+fn arriving(channel: Channel, level: u8, offset: [i32 ; 3], state: BlockState) -> u8 {
+    let opacity = state.block.info().opacity;
+    if channel == Channel::Sky && level == MAX_LIGHT && offset == DOWN {
+        level.saturating_sub(opacity)
+    } else {
+        level.saturating_sub(1 + opacity)
+    }
+}
+
+// Spreads light out from every position in `queue`, brightening whatever
+// it reaches that's darker than the light arriving, and carrying on from
+// there.
+fn spread(world: &mut World, channel: Channel, mut queue: VecDeque<[i32 ; 3]>) {
+    while let Some(position) = queue.pop_front() {
+        let Some(level) = stored(world, position).map(|light| channel.get(light)).filter(|&level| level > 0) else {
+            continue;
+        };
+        for offset in NEIGHBORS {
+            let next = add(position, offset);
+            let Some(current) = stored(world, next).map(|light| channel.get(light)) else {
+                continue;
+            };
+            let [x, y, z] = next;
+            let level = arriving(channel, level, offset, world.get_state(x, y, z));
+            if level > current {
+                set_level(world, next, channel, level);
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+// Takes away the light that spread out from `start`, which was at `level`
+// and has already been set to what it is now, then spreads whatever light
+// is left around the darkened area back into it.
+fn darken(world: &mut World, channel: Channel, start: [i32 ; 3], level: u8) {
+    let mut removed = VecDeque::from([(start, level)]);
+    let mut refill = VecDeque::new();
+    while let Some((position, level)) = removed.pop_front() {
+        for offset in NEIGHBORS {
+            let next = add(position, offset);
+            let Some(current) = stored(world, next).map(|light| channel.get(light)).filter(|&current| current > 0) else {
+                continue;
+            };
+            // Dimmer light could have come from here, and so could full
+            // sky light straight below full sky light.
+            let from_here = current < level
+                || (channel == Channel::Sky && level == MAX_LIGHT && offset == DOWN && current == MAX_LIGHT);
+            if !from_here {
+                refill.push_back(next);
+                continue;
+            }
+            let [x, y, z] = next;
+            let emitted = channel.emitted(world.get_state(x, y, z));
+            set_level(world, next, channel, emitted);
+            removed.push_back((next, current));
+            if emitted > 0 {
+                refill.push_back(next);
+            }
+        }
+    }
+    spread(world, channel, refill);
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use crate::{
+    chunk::{ChunkPos, ColumnPos, CHUNK_SIZE},
+    mesher::{self, ChunkMeshBuffers, MeshPool},
+    worldgen::WorldGenerator,
+};
+
+// Enough to fill the rings within a few seconds without a visible hitch.
+const MESHES_PER_FRAME: usize = 16;
+
+/// Low-detail terrain for the columns of chunks between the fully detailed
+/// area and `lod_distance`. Each ring out from the player doubles the cell
+/// size, from 2 blocks up to 8, and rings are remeshed as the player moves.
+pub struct LodTerrain {
+    detail_distance: i32,
+    lod_distance: i32,
+    center: Option<ColumnPos>,
+    chunks: HashMap<ColumnPos, ChunkMeshBuffers>,
+    scales: HashMap<ColumnPos, i32>,
+    // The chunk holding the surface in the middle of each column in the
+    // rings, which once it has a full mesh replaces the column's.
+    surfaces: HashMap<ColumnPos, ChunkPos>,
+    // Columns to mesh at the given scale, furthest first so the nearest
+    // come off the end.
+    pending: Vec<(ColumnPos, i32)>,
+}
+
+impl LodTerrain {
+    pub fn new(detail_distance: i32, lod_distance: i32) -> Self {
+        Self {
+            detail_distance: detail_distance.max(1),
+            lod_distance,
+            center: None,
+            chunks: HashMap::new(),
+            scales: HashMap::new(),
+            surfaces: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Moves the inner edge of the rings, replanning them on the next
+    /// update.
+    pub fn set_detail_distance(&mut self, detail_distance: i32) {
+        self.detail_distance = detail_distance.max(1);
+        self.center = None;
+    }
+
+    pub fn chunks(&self) -> &HashMap<ColumnPos, ChunkMeshBuffers> {
+        &self.chunks
+    }
+
+    // Cell size for a chunk `distance` chunks from the player.
+    fn scale_for(&self, distance: i32) -> Option<i32> {
+        if distance > self.lod_distance {
+            None
+        } else if distance <= self.detail_distance * 2 {
+            Some(2)
+        } else if distance <= self.detail_distance * 4 {
+            Some(4)
+        } else {
+            Some(8)
+        }
+    }
+
+    /// Replans the rings when the player changes column and meshes a few
+    /// of the pending columns. Columns whose surface has a full `detailed`
+    /// mesh never get a LOD mesh, and lose theirs once the detailed one
+    /// exists.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pool: &mut MeshPool,
+        generator: &WorldGenerator,
+        center: ColumnPos,
+        detailed: &HashMap<ChunkPos, ChunkMeshBuffers>,
+    ) {
+        if self.lod_distance <= self.detail_distance {
+            return;
+        }
+
+        if self.center != Some(center) {
+            self.center = Some(center);
+            self.replan(center, generator, detailed, pool);
+        }
+
+        let replaced = self.chunks.keys().filter(|&&pos| self.is_detailed(pos, detailed)).copied().collect::<Vec<_>>();
+        for pos in replaced {
+            self.remove(pos, pool);
+        }
+
+        for _ in 0..MESHES_PER_FRAME {
+            let Some((pos, scale)) = self.pending.pop() else {
+                break;
+            };
+            if self.is_detailed(pos, detailed) {
+                continue;
+            }
+
+            let buffers = mesher::mesh_lod_chunk(generator, pos, scale).upload(device, queue, pool);
+            // The old mesh stays up until its replacement is ready, so the
+            // rings never have holes while the player moves.
+            if let Some(old) = self.chunks.insert(pos, buffers) {
+                pool.free(old);
+            }
+            self.scales.insert(pos, scale);
+        }
+    }
+
+    fn is_detailed(&self, pos: ColumnPos, detailed: &HashMap<ChunkPos, ChunkMeshBuffers>) -> bool {
+        self.surfaces.get(&pos).is_some_and(|surface| detailed.contains_key(surface))
+    }
+
+    fn replan(
+        &mut self,
+        center: ColumnPos,
+        generator: &WorldGenerator,
+        detailed: &HashMap<ChunkPos, ChunkMeshBuffers>,
+        pool: &mut MeshPool,
+    ) {
+        let mut wanted = HashMap::new();
+        for z in center.z - self.lod_distance..=center.z + self.lod_distance {
+            for x in center.x - self.lod_distance..=center.x + self.lod_distance {
+                let pos = ColumnPos::new(x, z);
+                let distance = (x - center.x).abs().max((z - center.z).abs());
+                if let Some(scale) = self.scale_for(distance) {
+                    self.surfaces.entry(pos).or_insert_with(|| surface_chunk(generator, pos));
+                    if !self.is_detailed(pos, detailed) {
+                        wanted.insert(pos, scale);
+                    }
+                }
+            }
+        }
+        let lod_distance = self.lod_distance;
+        self.surfaces.retain(|pos, _| (pos.x - center.x).abs().max((pos.z - center.z).abs()) <= lod_distance);
+
+        let out_of_range = self.chunks.keys().filter(|pos| !wanted.contains_key(pos)).copied().collect::<Vec<_>>();
+        for pos in out_of_range {
+            self.remove(pos, pool);
+        }
+
+        self.pending = wanted.into_iter()
+            .filter(|(pos, scale)| self.scales.get(pos) != Some(scale))
+            .collect();
+        self.pending.sort_by_key(|(pos, _)| {
+            let (dx, dz) = (pos.x - center.x, pos.z - center.z);
+            std::cmp::Reverse(dx * dx + dz * dz)
+        });
+    }
+
+    fn remove(&mut self, pos: ColumnPos, pool: &mut MeshPool) {
+        if let Some(buffers) = self.chunks.remove(&pos) {
+            pool.free(buffers);
+        }
+        self.scales.remove(&pos);
+    }
+}
+
+fn surface_chunk(generator: &WorldGenerator, column: ColumnPos) -> ChunkPos {
+    let (x, z) = column.origin();
+    let middle = CHUNK_SIZE as i32 / 2;
+    ChunkPos::from_block(x + middle, generator.height_at(x + middle, z + middle), z + middle)
+}
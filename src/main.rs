@@ -1,4 +1,8 @@
-use std::{process::exit};
+use std::{
+    collections::{HashMap, HashSet},
+    process::exit,
+    time::{Duration, Instant},
+};
 
 use wgpu::util::DeviceExt;
 use winit::{
@@ -9,177 +13,2429 @@ use winit::{
     keyboard::{KeyCode, PhysicalKey},
 };
 
-use log::{error, warn};
+use log::{error, info, warn};
 
 mod window;
+mod animation;
+mod assets;
+mod block;
+mod block_entity;
+mod border;
+mod breaking;
+mod audio;
+mod bench;
 mod camera;
+mod chat;
+mod chunk;
+mod chunk_debug;
+mod codec;
+mod commands;
+mod config;
+mod console;
+mod crack;
+mod crafting;
+mod culling;
+mod debug;
+mod dimension;
+mod ecs;
+mod entity_renderer;
+mod error;
+mod events;
+mod explosion;
+mod falling;
+mod fluid;
+mod fog;
+mod frame_limiter;
+mod furnace;
+mod game_mode;
+mod gpu;
+mod health;
+mod highlight;
+mod history;
+mod hot_reload;
+mod hunger;
+mod indirect;
+mod input;
+mod inventory;
+mod item;
+mod light;
+mod lod;
+mod map;
+mod menu;
+mod mesher;
+mod mob;
+mod net;
+mod particles;
+mod pathfind;
+mod physics;
+mod piston;
+mod player;
+mod portal;
+mod player_model;
+mod post;
+mod profiler;
+mod random;
+mod renderer;
+mod save;
+mod schematic;
+mod screenshot;
+mod scripting;
+mod shadow;
+mod signal;
+mod sky;
+mod sleep;
+mod text;
 mod texture;
+mod trace;
+mod ui;
+mod weather;
+mod world;
+mod world_edit;
+mod worldgen;
+
+const REACH_DISTANCE: f32 = 6.0;
+// How far below the terrain surface the ambience switches to caves.
+const CAVE_DEPTH: i32 = 8;
+// Thunder is heard from this far off in the lightning's direction.
+const THUNDER_DISTANCE: f32 = 6.0;
+// Ticks run in one frame at most; past that the game slows down rather
+// than stalling further trying to catch up.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+// Chunks each way around where the player comes out of a portal that are
+// loaded before they arrive; the rest stream in after.
+const ARRIVAL_RADIUS: i32 = 2;
+// How strongly the screen is tinted just before a portal takes the player.
+const PORTAL_TINT: f32 = 0.6;
+// The title screen looks around from high over an empty world, at a fixed
+// time in the morning.
+const MENU_EYE: [f32 ; 3] = [0.0, 100.0, 0.0];
+const MENU_PITCH: f32 = 0.3;
+const MENU_TURN_SPEED: f32 = 0.05;
+const MENU_TIME_OF_DAY: f32 = 0.15;
+// Limits and steps of the settings the options screen adjusts.
+const RENDER_DISTANCE_RANGE: (i32, i32) = (2, 16);
+const FOV_RANGE: (f32, f32) = (30.0, 110.0);
+const FOV_STEP: f32 = 5.0;
+const SENSITIVITY_RANGE: (f32, f32) = (0.1, 3.0);
+const SENSITIVITY_STEP: f32 = 0.1;
+const FLY_SPEED_RANGE: (f32, f32) = (2.0, 50.0);
+const FLY_SPEED_STEP: f32 = 1.0;
+const VOLUME_STEP: f32 = 0.1;
+const PARTICLE_BUDGET_RANGE: (u32, u32) = (0, 10_000);
+const PARTICLE_BUDGET_STEP: u32 = 1000;
+
+#[allow(unused)]
+struct App<'a> {
+    settings: config::Config,
+    error: Option<error::EngineError>,
+    state: menu::GameState,
+    menu: menu::Menu,
+    // Set by the menu's quit button; the event loop exits on the next frame.
+    exit_requested: bool,
+
+    surface: Option<wgpu::Surface<'a>>,
+    device: Option<wgpu::Device>,
+    queue: Option<wgpu::Queue>,
+    config: Option<wgpu::SurfaceConfiguration>,
+    present_modes: Vec<wgpu::PresentMode>,
+
+    render_pipeline: Option<wgpu::RenderPipeline>,
+    cutout_pipeline: Option<wgpu::RenderPipeline>,
+    translucent_pipeline: Option<wgpu::RenderPipeline>,
+    // Line-mode copies of the terrain pipelines, if the adapter can draw
+    // polygons as lines.
+    wireframe_pipelines: Option<[wgpu::RenderPipeline ; 3]>,
+    wireframe: bool,
+    chunk_debug: chunk_debug::ChunkDebugMode,
+    // Kept to rebuild the pipelines above when the MSAA sample count
+    // changes.
+    terrain_shader: Option<wgpu::ShaderModule>,
+    terrain_pipeline_layout: Option<wgpu::PipelineLayout>,
+    camera_bind_group_layout: Option<wgpu::BindGroupLayout>,
+    scene_format: Option<wgpu::TextureFormat>,
+    // Every MSAA sample count the adapter supports, from 1 up.
+    sample_counts: Vec<u32>,
+
+    camera: Option<camera::Camera>,
+    camera_uniform: Option<camera::CameraUniform>,
+    camera_buffer: Option<wgpu::Buffer>,
+    camera_bind_group: Option<wgpu::BindGroup>,
+    frame_uniform: Option<fog::FrameUniform>,
+    frame_buffer: Option<wgpu::Buffer>,
+    frame_bind_group: Option<wgpu::BindGroup>,
+    remesh_ages: Option<chunk_debug::RemeshAges>,
+
+    camera_controller: Option<camera::CameraController>,
+    camera_mode: camera::CameraMode,
+    camera_effects: camera::CameraEffects,
+
+    ecs: ecs::Ecs,
+    player: Option<ecs::Entity>,
+    inventory: inventory::Inventory,
+    breaking: breaking::BlockBreaking,
+    sleep: sleep::Sleep,
+    portal_timer: portal::PortalTimer,
+    world_edit: world_edit::WorldEdit,
+    history: history::EditHistory,
+    // The bed the player last used, which they respawn at while it stands.
+    bed: Option<[i32 ; 3]>,
+    last_update: Option<Instant>,
+    frame_limiter: frame_limiter::FrameLimiter,
+    // Whether the window has keyboard focus, and whether it's hidden
+    // behind others or minimized, where the platform says.
+    focused: bool,
+    occluded: bool,
+    // Time not simulated yet, less than a tick once caught up, and how far
+    // into the next tick that is, which frames are drawn at.
+    tick_time: Duration,
+    tick_alpha: f32,
+
+    world: Option<world::World>,
+    // The other dimensions the player has been to, with their chunks
+    // unloaded until they go back.
+    dimensions: HashMap<dimension::Dimension, world::World>,
+    world_save: save::WorldSave,
+    chunk_io: Option<save::ChunkIo>,
+    last_save: Instant,
+    // Set when playing on a server, which then owns the world.
+    server_address: Option<String>,
+    client: Option<net::Client>,
+    chunk_buffers: HashMap<chunk::ChunkPos, mesher::ChunkMeshBuffers>,
+    mesh_pool: mesher::MeshPool,
+    chunk_draws: Option<indirect::ChunkDraws>,
+    lod_terrain: lod::LodTerrain,
+
+    depth_texture: Option<texture::Texture>,
+
+    crack_renderer: Option<crack::CrackRenderer>,
+    highlight_renderer: Option<highlight::HighlightRenderer>,
+    selection_renderer: Option<highlight::HighlightRenderer>,
+    chunk_border_renderer: Option<chunk_debug::ChunkBorderRenderer>,
+    border_renderer: Option<border::BorderRenderer>,
+    sky_renderer: Option<sky::SkyRenderer>,
+    shadow_renderer: Option<shadow::ShadowRenderer>,
+    post_processor: Option<post::PostProcessor>,
+    entity_renderer: entity_renderer::EntityRenderer,
+    particle_renderer: Option<particles::ParticleRenderer>,
+
+    diffuse_bind_group: Option<wgpu::BindGroup>,
+    diffuse_texture: Option<texture::Texture>,
+    texture_animator: Option<animation::TextureAnimator>,
+
+    icon_bind_group: Option<wgpu::BindGroup>,
+    icon_atlas: Option<texture::Texture>,
+
+    hotbar: Option<ui::Hotbar>,
+    inventory_screen: ui::InventoryScreen,
+    ui_batch: ui::UiBatch,
+    ui_renderer: Option<ui::UiRenderer>,
+    text_renderer: Option<text::TextRenderer>,
+    map: map::MapCache,
+    map_renderer: Option<map::MapRenderer>,
+    map_screen: map::MapScreen,
+
+    input_map: input::InputMap,
+    gamepads: input::Gamepads,
+    debug_overlay: debug::DebugOverlay,
+    profiler: profiler::Profiler,
+    gpu_timer: Option<profiler::GpuTimer>,
+    console: console::Console,
+    chat: chat::ChatHud,
+    audio: audio::Audio,
+    footsteps: audio::Footsteps,
+    render_stats: debug::RenderStats,
+    screenshots: screenshot::Screenshots,
+    // Only watching when the `[dev]` setting asks for it.
+    hot_reload: Option<hot_reload::HotReload>,
+
+    fluids: fluid::FluidSimulator,
+    signals: signal::SignalSimulator,
+    particles: particles::Particles,
+    mob_spawner: mob::MobSpawner,
+    pathfinder: pathfind::Pathfinder,
+    mods: scripting::Mods,
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32 ; 3],
-    tex_coords: [f32 ; 2],
+    window: Option<window::Window<'a>>,
 }
 
-impl Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute ; 2]
-        = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+impl<'a> App<'a> {
+    fn init(settings: config::Config, assets: &assets::Assets, server_address: Option<String>, mods: scripting::Mods) -> Self {
+        let debug_key = settings.controls.binding(input::Action::DebugOverlay);
+        let profiler_key = settings.controls.binding(input::Action::Profiler);
+        let console_key = settings.controls.binding(input::Action::Console);
+        let inventory_key = settings.controls.binding(input::Action::Inventory);
+        let map_key = settings.controls.binding(input::Action::WorldMap);
+        let input_map = input::InputMap::new(&settings.controls);
+        let gamepads = input::Gamepads::new(settings.controls.gamepad.clone());
+        let world_save = save::WorldSave::new(&settings.world.name);
+        let render_distance = settings.graphics.render_distance;
+        let lod_distance = settings.graphics.lod_distance;
+        let particles = particles::Particles::new(settings.graphics.max_particles);
+        let audio = audio::Audio::new(settings.audio.clone(), assets);
+        let hot_reload = settings.dev.hot_reload.then(|| hot_reload::HotReload::new(assets));
+
+        Self {
+            settings,
+            error:              None,
+            state:              menu::GameState::MainMenu,
+            menu:               menu::Menu::new(),
+            exit_requested:     false,
+
+            surface:            None,
+            device:             None,
+            queue:              None,
+            config:             None,
+            present_modes:      Vec::new(),
+
+            render_pipeline:    None,
+            cutout_pipeline:    None,
+            translucent_pipeline: None,
+            wireframe_pipelines: None,
+            wireframe:          false,
+            chunk_debug:        chunk_debug::ChunkDebugMode::default(),
+            terrain_shader:     None,
+            terrain_pipeline_layout: None,
+            camera_bind_group_layout: None,
+            scene_format:       None,
+            sample_counts:      Vec::new(),
+            
+            camera:             None,
+            camera_uniform:     None,
+            camera_buffer:      None,
+            camera_bind_group:  None,
+            frame_uniform:      None,
+            frame_buffer:       None,
+            frame_bind_group:   None,
+            remesh_ages:        None,
+
+            camera_controller:  None,
+            camera_mode:        camera::CameraMode::default(),
+            camera_effects:     camera::CameraEffects::default(),
+
+            ecs:                ecs::Ecs::default(),
+            player:             None,
+            inventory:          inventory::Inventory::default(),
+            breaking:           breaking::BlockBreaking::default(),
+            sleep:              sleep::Sleep::default(),
+            portal_timer:       portal::PortalTimer::default(),
+            world_edit:         world_edit::WorldEdit::default(),
+            history:            history::EditHistory::default(),
+            bed:                None,
+            last_update:        None,
+            frame_limiter:      frame_limiter::FrameLimiter::default(),
+            focused:            true,
+            occluded:           false,
+            tick_time:          Duration::ZERO,
+            tick_alpha:         0.0,
+
+            world:              None,
+            dimensions:         HashMap::new(),
+            world_save,
+            chunk_io:           None,
+            last_save:          Instant::now(),
+            server_address,
+            client:             None,
+            chunk_buffers:      HashMap::new(),
+            mesh_pool:          mesher::MeshPool::new(),
+            chunk_draws:        None,
+            lod_terrain:        lod::LodTerrain::new(render_distance, lod_distance),
+
+            depth_texture:      None,
+
+            crack_renderer:     None,
+            highlight_renderer: None,
+            selection_renderer: None,
+            chunk_border_renderer: None,
+            border_renderer:    None,
+            sky_renderer:       None,
+            shadow_renderer:    None,
+            post_processor:     None,
+            entity_renderer:    entity_renderer::EntityRenderer::new(),
+            particle_renderer:  None,
+
+            diffuse_bind_group: None,
+            diffuse_texture:    None,
+            texture_animator:   None,
+
+            icon_bind_group:    None,
+            icon_atlas:         None,
+
+            hotbar:             None,
+            inventory_screen:   ui::InventoryScreen::new(inventory_key),
+            ui_batch:           ui::UiBatch::default(),
+            ui_renderer:        None,
+            text_renderer:      None,
+            map:                map::MapCache::new(),
+            map_renderer:       None,
+            map_screen:         map::MapScreen::new(map_key),
+
+            input_map,
+            gamepads,
+            debug_overlay:      debug::DebugOverlay::new(debug_key),
+            profiler:           profiler::Profiler::new(profiler_key),
+            gpu_timer:          None,
+            console:            console::Console::new(console_key),
+            chat:               chat::ChatHud::new(),
+            audio,
+            footsteps:          audio::Footsteps::default(),
+            render_stats:       debug::RenderStats::default(),
+            screenshots:        screenshot::Screenshots::default(),
+            hot_reload,
+
+            fluids:             fluid::FluidSimulator::default(),
+            signals:            signal::SignalSimulator::default(),
+            particles,
+            mob_spawner:        mob::MobSpawner::default(),
+            pathfinder:         pathfind::Pathfinder::new(),
+            mods,
+
+            window:             None,
+        }
+    }
+    
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        if self.state != menu::GameState::Playing {
+            if let Some(action) = self.menu.process_events(self.state, event) {
+                self.run_menu_action(action);
+            }
+            return matches!(event,
+                WindowEvent::KeyboardInput { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::MouseWheel { .. }
+            );
+        }
+
+        let console_was_open = self.console.open;
+        if self.console.process_events(event) {
+            if self.console.open != console_was_open {
+                self.update_cursor_grab();
+            }
+            if let Some(line) = self.console.take_submitted() {
+                self.run_console_line(&line);
+            }
+            return true;
+        }
+        // Keep clicks and mouse look from reaching the world behind the
+        // console.
+        if self.console.open && matches!(event, WindowEvent::MouseInput { .. } | WindowEvent::MouseWheel { .. }) {
+            return true;
+        }
+
+        let map_was_open = self.map_screen.open;
+        if self.map_screen.process_events(event, &mut self.map) {
+            if self.map_screen.open != map_was_open {
+                self.update_cursor_grab();
+            }
+            if let Some(position) = self.map_screen.take_teleport() {
+                let result = commands::Command::Teleport(position).execute(&mut commands::CommandContext {
+                    world: self.world.as_mut().unwrap(),
+                    ecs: &mut self.ecs,
+                    player: self.player.unwrap(),
+                    inventory: &mut self.inventory,
+                    edit: &mut self.world_edit,
+                    history: &mut self.history,
+                    save: &self.world_save,
+                    client: self.client.as_ref(),
+                });
+                if let Ok(output) = result {
+                    self.chat.push(chat::ChatLine::new(chat::ChatKind::Notice, output));
+                }
+            }
+            return true;
+        }
+
+        let inventory_was_open = self.inventory_screen.open;
+        if self.inventory_screen.process_events(event, &mut self.inventory) {
+            if self.inventory_screen.open != inventory_was_open {
+                self.update_cursor_grab();
+            }
+            return true;
+        }
+
+        // Escape only pauses once the console, map and inventory have had
+        // the chance to close on it.
+        if let WindowEvent::KeyboardInput {
+            event: KeyEvent {
+                state: ElementState::Pressed,
+                physical_key: PhysicalKey::Code(KeyCode::Escape),
+                repeat: false,
+                ..
+            },
+            ..
+        } = event {
+            self.state = menu::GameState::Paused;
+            self.update_cursor_grab();
+            return true;
+        }
+
+        // Any other key or click gets the player out of bed; looking
+        // around still works.
+        if self.sleep.in_bed() && matches!(
+            event,
+            WindowEvent::KeyboardInput { event: KeyEvent { state: ElementState::Pressed, repeat: false, .. }, .. }
+                | WindowEvent::MouseInput { state: ElementState::Pressed, .. }
+        ) {
+            self.get_up();
+            return true;
+        }
+
+        if self.debug_overlay.process_events(event) {
+            return true;
+        }
+
+        if self.profiler.process_events(event) {
+            return true;
+        }
+
+        if self.hotbar.as_mut().unwrap().process_events(event) {
+            return true;
+        }
+
+        let window = self.window.as_mut().unwrap();
+
+        match event {
+            // Looking comes from raw motion in `device_event`; where the
+            // cursor can only be confined rather than locked, it's kept in
+            // the middle so it's there when released.
+            WindowEvent::CursorMoved { position, .. } if window.cursor_grabbed => {
+                let center = window.center();
+                if *position != center {
+                    let _ = window.core_window.set_cursor_position(center);
+                }
+                true
+            },
+            WindowEvent::MouseInput { state: ElementState::Pressed, .. } if !window.cursor_grabbed => {
+                window.set_cursor_grabbed(true);
+                true
+            },
+            _ => {
+                let mut handled = false;
+                for action in self.input_map.events(event) {
+                    handled |= self.run_action(action);
+                }
+                handled
+            },
+        }
+    }
+
+    // Gamepad buttons do what their keyboard bindings do, except that the
+    // hotbar is cycled and the game paused with buttons of their own. The
+    // sticks are read every frame rather than through events.
+    fn update_gamepads(&mut self) {
+        for event in self.gamepads.poll() {
+            let playing = self.state == menu::GameState::Playing && !self.console.open;
+            match event {
+                input::PadEvent::Connected(name) => {
+                    self.chat.push(chat::ChatLine::new(chat::ChatKind::Notice, format!("Controller connected: {name}")));
+                },
+                input::PadEvent::Disconnected(name) => {
+                    self.chat.push(chat::ChatLine::new(chat::ChatKind::Notice, format!("Controller disconnected: {name}")));
+                },
+                input::PadEvent::Pause => {
+                    match self.state {
+                        menu::GameState::Playing => self.state = menu::GameState::Paused,
+                        menu::GameState::Paused => self.state = menu::GameState::Playing,
+                        _ => continue,
+                    }
+                    self.update_cursor_grab();
+                },
+                _ if !playing => (),
+                input::PadEvent::HotbarNext => self.hotbar.as_mut().unwrap().scroll(1),
+                input::PadEvent::HotbarPrevious => self.hotbar.as_mut().unwrap().scroll(-1),
+                input::PadEvent::Action(action) if self.sleep.in_bed() => {
+                    if action.pressed {
+                        self.get_up();
+                    }
+                },
+                input::PadEvent::Action(action) if action.action == input::Action::Inventory => {
+                    if action.pressed {
+                        self.inventory_screen.toggle(&mut self.inventory);
+                        self.update_cursor_grab();
+                    }
+                },
+                input::PadEvent::Action(action) => {
+                    if !self.inventory_screen.open && !self.map_screen.open {
+                        self.run_action(action);
+                    }
+                },
+            }
+        }
+
+        let sticks = self.gamepads.sticks();
+        let look_speed = self.settings.controls.gamepad.look_speed;
+        let controls_world = self.state == menu::GameState::Playing
+            && !self.console.open
+            && !self.map_screen.open
+            && !self.inventory_screen.open
+            && !self.sleep.in_bed();
+        let camera_controller = self.camera_controller.as_mut().unwrap();
+        if controls_world {
+            camera_controller.analog_move = sticks.movement;
+            camera_controller.analog_look = sticks.look.map(|rate| rate * look_speed);
+        } else {
+            camera_controller.analog_move = [0.0, 0.0];
+            camera_controller.analog_look = [0.0, 0.0];
+        }
+    }
+
+    // The console, inventory, debug overlay and profiler handle their own
+    // toggles.
+    fn run_action(&mut self, event: input::ActionEvent) -> bool {
+        if !event.repeat {
+            self.ecs.events.publish(events::Event::KeyAction { action: event.action, pressed: event.pressed });
+        }
+        let pressed = event.pressed && !event.repeat;
+        match event.action {
+            input::Action::ToggleVsync => {
+                if pressed {
+                    self.set_vsync(!self.settings.graphics.vsync);
+                }
+            },
+            input::Action::ToggleFullscreen => {
+                if pressed {
+                    self.toggle_fullscreen();
+                }
+            },
+            input::Action::Screenshot => {
+                if pressed {
+                    self.screenshots.request();
+                }
+            },
+            input::Action::ToggleWireframe => {
+                if pressed {
+                    self.toggle_wireframe();
+                }
+            },
+            input::Action::ChunkBorders => {
+                if pressed {
+                    self.chunk_debug = self.chunk_debug.next();
+                }
+            },
+            input::Action::CycleCamera => {
+                if pressed {
+                    self.camera_mode = self.camera_mode.next();
+                }
+            },
+            input::Action::MinimapZoom => {
+                if pressed {
+                    let hud = &mut self.settings.hud;
+                    hud.minimap_zoom = (hud.minimap_zoom + 1) % map::MINIMAP_ZOOMS.len();
+                    self.save_settings();
+                }
+            },
+            input::Action::Break => {
+                if pressed && self.use_wand(0) {
+                    return true;
+                }
+                if pressed && self.attack_mob() {
+                    return true;
+                }
+                if !event.repeat {
+                    self.breaking.set_held(event.pressed);
+                }
+            },
+            input::Action::Place => {
+                if pressed {
+                    player_model::swing_arm(&mut self.ecs, self.player.unwrap());
+                    self.use_item();
+                }
+            },
+            input::Action::Undo | input::Action::Redo => {
+                if pressed {
+                    self.step_history(event.action == input::Action::Redo);
+                }
+            },
+            _ => return self.camera_controller.as_mut().unwrap().process_action(event),
+        }
+        true
+    }
+
+    // Losing focus lets go of the cursor, and of keys whose release now
+    // goes to another window, and pauses a singleplayer game; a server's
+    // keeps going. Clicking back in grabs the cursor again.
+    fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        self.audio.set_background(!focused);
+        if focused {
+            return;
+        }
+        if self.state == menu::GameState::Playing && self.client.is_none() {
+            self.state = menu::GameState::Paused;
+        }
+        self.camera_controller.as_mut().unwrap().release_all();
+        self.window.as_mut().unwrap().set_cursor_grabbed(false);
+    }
+
+    // The shortest a frame may take, if the frame rate is capped. Out of
+    // sight or in the background, frames are only drawn now and then,
+    // which is plenty to keep a visible window from looking frozen.
+    fn frame_time_limit(&self) -> Option<Duration> {
+        let graphics = &self.settings.graphics;
+        let window = &self.window.as_ref().unwrap().core_window;
+        let refresh = (graphics.frame_limit == frame_limiter::FrameLimit::Monitor)
+            .then(|| window.current_monitor()?.refresh_rate_millihertz())
+            .flatten();
+        let limit = graphics.frame_limit.frame_time(refresh);
+        let background = !self.focused || self.occluded || window.is_minimized() == Some(true);
+        if !background || graphics.background_fps == 0 {
+            return limit;
+        }
+        let background_limit = Duration::from_secs(1) / graphics.background_fps;
+        Some(limit.map_or(background_limit, |limit| limit.max(background_limit)))
+    }
+
+    // The cursor is free while any screen that takes input is open.
+    fn update_cursor_grab(&mut self) {
+        let ui_open = self.state != menu::GameState::Playing || self.console.open || self.map_screen.open || self.inventory_screen.open;
+        self.camera_controller.as_mut().unwrap().release_all();
+        self.window.as_mut().unwrap().set_cursor_grabbed(!ui_open);
+    }
+
+    fn run_menu_action(&mut self, action: menu::MenuAction) {
+        match action {
+            menu::MenuAction::Play(name) => self.enter_world(&name, self.settings.world.clone()),
+            menu::MenuAction::Create { name, seed, world_type, game_mode } => self.enter_world(&name, config::WorldConfig {
+                seed,
+                world_type,
+                game_mode,
+                ..self.settings.world.clone()
+            }),
+            menu::MenuAction::Resume => self.state = menu::GameState::Playing,
+            menu::MenuAction::Respawn => self.respawn(),
+            menu::MenuAction::OpenSettings => {
+                self.state = menu::GameState::Settings { in_game: self.state == menu::GameState::Paused };
+            },
+            menu::MenuAction::CloseSettings => {
+                self.state = match self.state {
+                    menu::GameState::Settings { in_game: true } => menu::GameState::Paused,
+                    _ => menu::GameState::MainMenu,
+                };
+            },
+            menu::MenuAction::QuitToTitle => self.leave_game(),
+            menu::MenuAction::Quit => self.exit_requested = true,
+            menu::MenuAction::ToggleVsync => self.set_vsync(!self.settings.graphics.vsync),
+            menu::MenuAction::CycleFrameLimit => {
+                self.settings.graphics.frame_limit = self.settings.graphics.frame_limit.next();
+                self.save_settings();
+            },
+            menu::MenuAction::ToggleFullscreen => self.toggle_fullscreen(),
+            menu::MenuAction::ToggleGamepad => {
+                self.settings.controls.gamepad.enabled = !self.settings.controls.gamepad.enabled;
+                self.gamepads.set_settings(self.settings.controls.gamepad.clone());
+                self.save_settings();
+            },
+            menu::MenuAction::ToggleInvertMouse => {
+                self.settings.controls.invert_mouse = !self.settings.controls.invert_mouse;
+                self.camera_controller.as_mut().unwrap().invert_mouse = self.settings.controls.invert_mouse;
+                self.save_settings();
+            },
+            menu::MenuAction::ToggleSmoothLighting => {
+                self.settings.graphics.smooth_lighting = !self.settings.graphics.smooth_lighting;
+                if let Some(world) = self.world.as_mut() {
+                    world.mark_all_dirty();
+                }
+                self.save_settings();
+            },
+            menu::MenuAction::ToggleCameraEffect(effect) => {
+                self.settings.graphics.camera_effects.toggle(effect);
+                self.save_settings();
+            },
+            menu::MenuAction::ToggleHudElement(element) => {
+                self.settings.hud.toggle(element);
+                self.save_settings();
+            },
+            menu::MenuAction::Adjust(setting, step) => self.adjust_setting(setting, step),
+            menu::MenuAction::Rebind(action, binding) => {
+                self.settings.controls.bindings.insert(action, binding);
+                self.apply_bindings();
+            },
+            menu::MenuAction::ResetBindings => {
+                self.settings.controls.bindings = config::ControlsConfig::default_bindings();
+                self.apply_bindings();
+            },
+        }
+        self.update_cursor_grab();
+    }
+
+    // Lines starting with '/' are commands; anything else is chat.
+    fn run_console_line(&mut self, line: &str) {
+        let Some(command_line) = line.strip_prefix('/') else {
+            // On a server the message shows up once it comes back.
+            match &self.client {
+                Some(client) => client.send_chat(line),
+                None => {
+                    let text = format!("<{}> {line}", self.settings.network.player_name);
+                    self.chat.push(chat::ChatLine::new(chat::ChatKind::Message, text));
+                },
+            }
+            return;
+        };
+
+        self.console.print(format!("> {line}"));
+
+        let player = self.player.unwrap();
+        let origin = self.ecs.transforms.get(player).map_or([0.0 ; 3], |t| t.position.into());
+        let result = commands::Command::parse(command_line, origin).and_then(|command| {
+            // The backup only has what's been written out.
+            if matches!(command, commands::Command::Backup) {
+                self.save_world();
+            }
+            command.execute(&mut commands::CommandContext {
+                world: self.world.as_mut().unwrap(),
+                ecs: &mut self.ecs,
+                player,
+                inventory: &mut self.inventory,
+                edit: &mut self.world_edit,
+                history: &mut self.history,
+                save: &self.world_save,
+                client: self.client.as_ref(),
+            })
+        });
+
+        match result {
+            Ok(output) => self.console.print(output),
+            Err(e) => self.console.print_error(e.to_string()),
+        }
+    }
+
+    // Fifo is the only mode every adapter must support, so it doubles as
+    // the fallback when the uncapped modes are unavailable.
+    fn choose_present_mode(supported: &[wgpu::PresentMode], vsync: bool) -> wgpu::PresentMode {
+        if vsync {
+            return wgpu::PresentMode::Fifo;
+        }
+
+        [wgpu::PresentMode::Mailbox, wgpu::PresentMode::Immediate]
+            .into_iter()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(wgpu::PresentMode::Fifo)
+    }
+
+    fn set_vsync(&mut self, vsync: bool) {
+        let present_mode = Self::choose_present_mode(&self.present_modes, vsync);
+
+        let config = self.config.as_mut().unwrap();
+        config.present_mode = present_mode;
+        self.surface.as_ref().unwrap().configure(self.device.as_ref().unwrap(), config);
+        info!("present mode set to {present_mode:?}");
+
+        self.settings.graphics.vsync = vsync;
+        self.save_settings();
+    }
+
+    // The surface is reconfigured by `resize` once the window reports its
+    // new size.
+    fn toggle_fullscreen(&mut self) {
+        let window = self.window.as_mut().unwrap();
+        let mode = match window.fullscreen {
+            window::FullscreenMode::Windowed => self.settings.window.preferred_fullscreen,
+            _ => window::FullscreenMode::Windowed,
+        };
+        window.set_fullscreen(mode);
+
+        self.settings.window.fullscreen = mode;
+        self.save_settings();
+    }
+
+    // Points everything that reads input at the current bindings, and
+    // saves them.
+    fn apply_bindings(&mut self) {
+        let controls = &self.settings.controls;
+        self.input_map = input::InputMap::new(controls);
+        self.console.toggle = controls.binding(input::Action::Console);
+        self.debug_overlay.toggle = controls.binding(input::Action::DebugOverlay);
+        self.profiler.toggle = controls.binding(input::Action::Profiler);
+        self.inventory_screen.toggle = controls.binding(input::Action::Inventory);
+        self.map_screen.toggle = controls.binding(input::Action::WorldMap);
+        self.camera_controller.as_mut().unwrap().release_all();
+        self.save_settings();
+    }
+
+    // Steps a setting up or down, applies it straight away and saves it.
+    fn adjust_setting(&mut self, setting: menu::Setting, step: i32) {
+        // Stepping from the nearest notch keeps hand-edited values from
+        // leaving every later value off the grid.
+        let notch = |value: f32, size: f32| ((value / size).round() + step as f32) * size;
+        let graphics = &mut self.settings.graphics;
+        match setting {
+            menu::Setting::RenderDistance => {
+                let (min, max) = RENDER_DISTANCE_RANGE;
+                graphics.render_distance = (graphics.render_distance + step).clamp(min, max);
+                // Chunk streaming reads the new distance every frame; on a
+                // server it's only sent when joining.
+                self.lod_terrain.set_detail_distance(graphics.render_distance);
+                self.frame_uniform = Some(fog::FrameUniform::new(
+                    graphics.fog,
+                    graphics.render_distance.max(graphics.lod_distance),
+                ));
+            },
+            menu::Setting::Fov => {
+                let (min, max) = FOV_RANGE;
+                graphics.fov = notch(graphics.fov, FOV_STEP).clamp(min, max);
+                self.camera.as_mut().unwrap().fov = graphics.fov;
+            },
+            menu::Setting::Sensitivity => {
+                let (min, max) = SENSITIVITY_RANGE;
+                let controls = &mut self.settings.controls;
+                controls.mouse_sensitivity = notch(controls.mouse_sensitivity, SENSITIVITY_STEP).clamp(min, max);
+                self.camera_controller.as_mut().unwrap().mouse_sensitivity = controls.mouse_sensitivity;
+            },
+            menu::Setting::FlySpeed => {
+                let (min, max) = FLY_SPEED_RANGE;
+                let controls = &mut self.settings.controls;
+                controls.fly_speed = notch(controls.fly_speed, FLY_SPEED_STEP).clamp(min, max);
+                if let Some(player) = self.player.and_then(|player| self.ecs.players.get_mut(player)) {
+                    player.fly_speed = controls.fly_speed;
+                }
+            },
+            menu::Setting::Volume => {
+                let audio = &mut self.settings.audio;
+                audio.master_volume = notch(audio.master_volume, VOLUME_STEP).clamp(0.0, 1.0);
+                self.audio.set_settings(audio.clone());
+            },
+            menu::Setting::Msaa => {
+                let current = post::closest_sample_count(&self.sample_counts, graphics.msaa_samples);
+                let index = self.sample_counts.iter().position(|&count| count == current).unwrap_or(0);
+                let index = (index as i32 + step).clamp(0, self.sample_counts.len() as i32 - 1);
+                let sample_count = self.sample_counts[index as usize];
+                graphics.msaa_samples = sample_count;
+                if sample_count != self.post_processor.as_ref().unwrap().sample_count {
+                    self.create_scene_targets(sample_count);
+                }
+            },
+            menu::Setting::Particles => {
+                let (min, max) = PARTICLE_BUDGET_RANGE;
+                let notches = (graphics.max_particles as f32 / PARTICLE_BUDGET_STEP as f32).round() as i32 + step;
+                graphics.max_particles = (notches.max(0) as u32 * PARTICLE_BUDGET_STEP).clamp(min, max);
+                self.particles.set_budget(graphics.max_particles);
+            },
+        }
+        self.save_settings();
+    }
+
+    fn toggle_wireframe(&mut self) {
+        if self.wireframe_pipelines.is_none() {
+            warn!("wireframe rendering is not supported by this adapter");
+            return;
+        }
+        self.wireframe = !self.wireframe;
+    }
+
+    fn save_settings(&self) {
+        if let Err(e) = self.settings.save(config::CONFIG_PATH) {
+            warn!("could not save settings: {e}");
+        }
+    }
+
+    // The seed, world type and game mode in `new_world` are only used if
+    // the world is new; a saved world keeps its own.
+    fn load_world(&mut self, new_world: config::WorldConfig) -> error::Result<(world::World, cgmath::Point3<f32>, Option<save::PlayerData>)> {
+        if let Some(backup) = self.world_save.upgrade()? {
+            info!("backed up the world from another version of the game as {}", backup.file_name());
+        }
+        let level = self.world_save.load_level()?;
+        let mut new_world = new_world;
+        self.mods.apply_worldgen(&mut new_world);
+        let world_settings = config::WorldConfig {
+            seed: level.as_ref().map_or(new_world.seed, |level| level.seed),
+            world_type: level.as_ref().map_or(new_world.world_type, |level| level.world_type),
+            game_mode: level.as_ref().map_or(new_world.game_mode, |level| level.game_mode),
+            ..new_world
+        };
+        let mut world = world::World::new(&world_settings);
+        if let Some(level) = &level {
+            world.time = level.time;
+            world.time_speed = level.time_speed;
+            world.daylight_cycle = level.daylight_cycle;
+            world.spawn_point = level.spawn_point.unwrap_or(world.spawn_point);
+            world.weather.set(level.weather, level.weather_remaining);
+            world.border = level.border.unwrap_or_default();
+        }
+
+        let saved_player = self.world_save.load_player()?;
+        let dimension = saved_player.as_ref().map_or(dimension::Dimension::Overworld, |data| data.dimension);
+        if dimension != world.dimension() {
+            let overworld = world;
+            world = overworld.new_dimension(dimension);
+            self.dimensions.insert(overworld.dimension(), overworld);
+        }
+        let spawn: cgmath::Point3<f32> = saved_player.as_ref().map_or(world.spawn_point, |data| data.position).into();
+        let [x, y, z] = [spawn.x, spawn.y, spawn.z].map(|v| v.floor() as i32);
+        // The area around spawn is loaded up front so the player doesn't
+        // fall through missing ground; everything after that streams in.
+        let graphics = &self.settings.graphics;
+        let area = (graphics.render_distance, graphics.vertical_distance);
+        self.load_area(&mut world, chunk::ChunkPos::from_block(x, y, z), area);
+
+        // A broken map only loses what was explored, not the world.
+        if let Err(e) = self.world_save.load_map(&mut self.map) {
+            warn!("could not load the map: {e}");
+        }
+
+        self.chunk_io = Some(save::ChunkIo::new(self.world_save.dimension(dimension), world.generator.clone()));
+        Ok((world, spawn, saved_player))
+    }
+
+    // Loads or generates the chunks `radius` across and `vertical` up or
+    // down around `center` that aren't loaded yet straight away, for
+    // somewhere the player is about to be.
+    fn load_area(&mut self, world: &mut world::World, center: chunk::ChunkPos, (radius, vertical): (i32, i32)) {
+        let world_save = self.world_save.dimension(world.dimension());
+        for pos in center.around(radius, vertical) {
+            if world.chunks.contains_key(&pos) {
+                continue;
+            }
+            match world_save.load_chunk(pos) {
+                Ok(Some(chunk)) => world.insert_chunk(chunk, Vec::new(), false),
+                Ok(None) => world.generate_chunk(pos),
+                Err(e) => {
+                    tracing::warn!(x = pos.x, y = pos.y, z = pos.z, "{e}; regenerating the chunk");
+                    world.generate_chunk(pos);
+                },
+            }
+            self.ecs.events.publish(events::Event::ChunkLoaded { pos });
+        }
+    }
+
+    // Moves the player's game into `to`, saving and unloading the world
+    // they leave and everything in it but them, and loads the chunks
+    // around `center` there. Where in it they end up is up to the caller.
+    fn enter_dimension(&mut self, to: dimension::Dimension, center: chunk::ChunkPos) {
+        let mut world = self.world.take().unwrap();
+        piston::finish_moves(&mut self.ecs, &mut world);
+        let world_save = self.world_save.dimension(world.dimension());
+        for pos in world.take_modified() {
+            if let Err(e) = world_save.save_chunk(&world.chunks[&pos]) {
+                tracing::warn!(x = pos.x, y = pos.y, z = pos.z, "could not save chunk: {e}");
+            }
+        }
+        if let Some(chunk_io) = &self.chunk_io {
+            chunk_io.flush();
+        }
+        world.unload_all();
+
+        // Entities aren't saved, so mobs and items left behind are gone.
+        let player = self.player.unwrap();
+        let left_behind = self.ecs.transforms.iter()
+            .map(|(entity, _)| entity)
+            .filter(|&entity| entity != player)
+            .collect::<Vec<_>>();
+        for entity in left_behind {
+            self.ecs.despawn(entity);
+        }
+        self.fluids = fluid::FluidSimulator::default();
+        self.signals = signal::SignalSimulator::default();
+        self.particles.clear();
+        self.mob_spawner = mob::MobSpawner::default();
+        self.pathfinder = pathfind::Pathfinder::new();
+        self.chunk_buffers.clear();
+        self.mesh_pool = mesher::MeshPool::new();
+        self.lod_terrain = lod::LodTerrain::new(self.settings.graphics.render_distance, self.settings.graphics.lod_distance);
+        self.crack_renderer.as_mut().unwrap().set_target(self.queue.as_ref().unwrap(), None, &[]);
+        self.breaking.set_held(false);
+        self.world_edit.change_world();
+        self.history.clear();
+
+        let mut next = self.dimensions.remove(&to).unwrap_or_else(|| world.new_dimension(to));
+        next.carry_over(&world);
+        self.dimensions.insert(world.dimension(), world);
+        self.load_area(&mut next, center, (ARRIVAL_RADIUS, ARRIVAL_RADIUS));
+        self.chunk_io = Some(save::ChunkIo::new(self.world_save.dimension(to), next.generator.clone()));
+        self.world = Some(next);
+    }
+
+    // Counts the time the player stands in a portal, and takes them
+    // through it once it's long enough. Servers only have the one
+    // dimension.
+    fn update_portal(&mut self, dt: f32) {
+        let player = self.player.unwrap();
+        let world = self.world.as_ref().unwrap();
+        let inside = self.client.is_none() && portal::in_portal(&self.ecs, world, player);
+        if !self.portal_timer.update(inside, dt) {
+            return;
+        }
+        let Some(position) = self.ecs.transforms.get(player).map(|transform| transform.position) else {
+            return;
+        };
+
+        let (from, to) = (world.dimension(), world.dimension().portal_destination());
+        let target = portal::destination(position, from, to, world.border);
+        let [x, y, z] = target;
+        self.enter_dimension(to, chunk::ChunkPos::from_block(x, y, z));
+        // Coming out on the surface can be well above or below where the
+        // player went in, out of what was loaded.
+        let mut world = self.world.take().unwrap();
+        let landing = portal::landing(&world, target);
+        let [x, y, z] = landing;
+        self.load_area(&mut world, chunk::ChunkPos::from_block(x, y, z), (ARRIVAL_RADIUS, ARRIVAL_RADIUS));
+        let arrival = portal::arrive(&mut world, landing);
+        self.world = Some(world);
+        if let Some(transform) = self.ecs.transforms.get_mut(player) {
+            transform.teleport(arrival);
+        }
+        if let Some(velocity) = self.ecs.velocities.get_mut(player) {
+            *velocity = ecs::Velocity::default();
+        }
+        self.audio.play_at(audio::Sound::Portal, arrival);
+    }
+
+    // The world starts out empty; the server streams chunks in, and the
+    // generator is only there for distant terrain.
+    fn join_server(&mut self, address: &str) -> error::Result<(world::World, cgmath::Point3<f32>, Option<save::PlayerData>)> {
+        let address = net::with_default_port(address);
+        info!("connecting to {address}");
+        let (client, welcome) = net::Client::connect(
+            &address,
+            &self.settings.network.player_name,
+            self.settings.graphics.render_distance,
+            self.settings.graphics.vertical_distance,
+        )?;
+
+        let mut world = world::World::new(&config::WorldConfig {
+            seed: welcome.seed,
+            world_type: welcome.world_type,
+            game_mode: welcome.game_mode,
+            border_radius: welcome.border,
+            ..self.settings.world.clone()
+        });
+        world.time = welcome.time;
+        world.spawn_point = welcome.spawn.into();
+
+        self.client = Some(client);
+        Ok((world, welcome.spawn, None))
+    }
+
+    fn enter_world(&mut self, name: &str, new_world: config::WorldConfig) {
+        self.world_save = save::WorldSave::new(name);
+        if let Err(e) = self.start_game(new_world) {
+            error!("could not load world `{name}`: {e}");
+            self.menu.message = Some(e.to_string());
+        }
+    }
+
+    // Joins the server if there is one, or loads `world_save`, and spawns
+    // the player into it.
+    fn start_game(&mut self, new_world: config::WorldConfig) -> error::Result<()> {
+        let (world, spawn, saved_player) = match self.server_address.clone() {
+            Some(address) => self.join_server(&address)?,
+            None => self.load_world(new_world)?,
+        };
+
+        let player = player::spawn(&mut self.ecs, spawn);
+        if let Some(state) = self.ecs.players.get_mut(player) {
+            let (min, max) = FLY_SPEED_RANGE;
+            state.fly_speed = self.settings.controls.fly_speed.clamp(min, max);
+        }
+        // Only drawn in third person.
+        self.ecs.meshes.insert(player, ecs::MeshRef::Player);
+        self.ecs.animations.insert(player, player_model::PlayerAnimation::default());
+        match saved_player {
+            Some(data) => {
+                player::set_flying(&mut self.ecs, player, data.flying);
+                self.ecs.healths.insert(player, health::Health::new(data.health));
+                self.ecs.hungers.insert(player, hunger::Hunger::new(data.food));
+                self.inventory = data.inventory;
+                self.bed = data.bed;
+            },
+            None => {
+                self.ecs.healths.insert(player, health::Health::new(health::MAX_HEALTH));
+                self.ecs.hungers.insert(player, hunger::Hunger::new(hunger::MAX_FOOD));
+                self.inventory = inventory::Inventory::starter();
+                self.bed = None;
+            },
+        }
+        player::set_game_mode(&mut self.ecs, player, world.game_mode);
+
+        let camera = self.camera.as_mut().unwrap();
+        camera.eye = player::eye_position(&self.ecs, player);
+        camera.yaw = cgmath::Rad(-std::f32::consts::FRAC_PI_2);
+        camera.pitch = cgmath::Rad(0.0);
+
+        self.player = Some(player);
+        self.world = Some(world);
+        self.state = menu::GameState::Playing;
+        self.menu.message = None;
+        self.update_cursor_grab();
+        // A new world is written out straight away so it shows up in the
+        // world list even if the game never gets to save it properly.
+        self.save_world();
+        Ok(())
+    }
+
+    // Saves and drops the world, disconnecting from the server if playing
+    // on one, and goes back to the title screen.
+    fn leave_game(&mut self) {
+        self.save_world();
+
+        self.world = None;
+        self.dimensions.clear();
+        self.world_edit = world_edit::WorldEdit::default();
+        self.history.clear();
+        self.player = None;
+        self.client = None;
+        self.server_address = None;
+        self.chunk_io = None;
+        self.ecs = ecs::Ecs::default();
+        self.tick_time = Duration::ZERO;
+        self.fluids = fluid::FluidSimulator::default();
+        self.signals = signal::SignalSimulator::default();
+        self.particles.clear();
+        self.mob_spawner = mob::MobSpawner::default();
+        // Searches still running are for entities that are gone now.
+        self.pathfinder = pathfind::Pathfinder::new();
+        self.chunk_buffers.clear();
+        self.map.clear();
+        self.mesh_pool = mesher::MeshPool::new();
+        self.lod_terrain = lod::LodTerrain::new(self.settings.graphics.render_distance, self.settings.graphics.lod_distance);
+        self.chat = chat::ChatHud::new();
+        self.console.open = false;
+        self.map_screen.close();
+        self.inventory_screen.close(&mut self.inventory);
+        self.prepare_world_draws();
+        self.highlight_renderer.as_mut().unwrap().set_target(self.queue.as_ref().unwrap(), None);
+        self.selection_renderer.as_mut().unwrap().set_target(self.queue.as_ref().unwrap(), None);
+        self.chunk_border_renderer.as_mut().unwrap().set_center(self.queue.as_ref().unwrap(), None);
+        self.border_renderer.as_mut().unwrap().clear();
+        self.crack_renderer.as_mut().unwrap().set_target(self.queue.as_ref().unwrap(), None, &[]);
+        self.breaking.set_held(false);
+        self.sleep = sleep::Sleep::default();
+        self.portal_timer = portal::PortalTimer::default();
+        self.bed = None;
+        self.audio.set_ambient(None);
+
+        let camera = self.camera.as_mut().unwrap();
+        camera.eye = MENU_EYE.into();
+        camera.pitch = cgmath::Rad(MENU_PITCH);
+        self.camera_effects.reset(camera);
+        self.menu.reset();
+        self.state = menu::GameState::MainMenu;
+        self.update_cursor_grab();
+    }
+
+    fn save_world(&mut self) {
+        // The server saves its own world.
+        if self.client.is_some() {
+            return;
+        }
+        let Some(world) = &mut self.world else {
+            return;
+        };
+
+        piston::finish_moves(&mut self.ecs, world);
+        // Shutting down, so blocking on the writes is fine.
+        let world_save = self.world_save.dimension(world.dimension());
+        for pos in world.take_modified() {
+            if let Err(e) = world_save.save_chunk(&world.chunks[&pos]) {
+                tracing::warn!(x = pos.x, y = pos.y, z = pos.z, "could not save chunk: {e}");
+            }
+        }
+        if let Some(chunk_io) = &self.chunk_io {
+            chunk_io.flush();
+        }
+        self.save_level_and_player();
+    }
+
+    // Saves while playing: modified chunks are queued for the chunk IO
+    // thread to write, as many as it has room for each tick until they all
+    // are. Only once they've been written are the level, player and map
+    // written, so the ones on disk are never newer than the chunks; a crash
+    // in between can't leave the player holding blocks the world still
+    // has. Pistons mid-move are left alone, so the blocks they're moving
+    // are only saved once they land, at the next save.
+    fn autosave(&mut self) {
+        let (Some(world), Some(chunk_io)) = (&mut self.world, &mut self.chunk_io) else {
+            return;
+        };
+        // Tried again next tick, as the time of the last save only moves
+        // on once it's done.
+        if !chunk_io.save_modified(world) {
+            return;
+        }
+        chunk_io.flush();
+        self.save_level_and_player();
+    }
+
+    fn save_level_and_player(&mut self) {
+        self.last_save = Instant::now();
+        let (Some(world), Some(player)) = (&self.world, self.player) else {
+            return;
+        };
+
+        let level = save::LevelData {
+            seed: world.generator.seed,
+            time: world.time,
+            time_speed: world.time_speed,
+            daylight_cycle: world.daylight_cycle,
+            world_type: world.generator.world_type,
+            game_mode: world.game_mode,
+            spawn_point: Some(world.spawn_point),
+            last_played: save::timestamp(),
+            weather: world.weather.weather,
+            weather_remaining: Some(world.weather.remaining),
+            border: Some(world.border),
+            game_version: save::GAME_VERSION.to_string(),
+            format_version: save::FORMAT_VERSION,
+        };
+        if let Err(e) = self.world_save.save_level(&level) {
+            warn!("could not save the world: {e}");
+        }
+        if let Err(e) = self.world_save.save_map(&self.map) {
+            warn!("could not save the map: {e}");
+        }
+
+        let Some(transform) = self.ecs.transforms.get(player) else {
+            return;
+        };
+        let data = save::PlayerData {
+            position: transform.position.into(),
+            flying: self.ecs.players.get(player).is_some_and(|p| p.flying),
+            health: self.ecs.healths.get(player).map_or(health::MAX_HEALTH, |health| health.points),
+            food: self.ecs.hungers.get(player).map_or(hunger::MAX_FOOD, |hunger| hunger.food),
+            inventory: self.inventory.clone(),
+            bed: self.bed,
+            dimension: world.dimension(),
+        };
+        if let Err(e) = self.world_save.save_player(&data) {
+            warn!("could not save the player: {e}");
+        }
+    }
+
+    // Reach is measured from the player's eyes, wherever the camera is.
+    fn target_block(&self) -> Option<world::RaycastHit> {
+        let camera = self.camera.as_ref().unwrap();
+        let head = player::eye_position(&self.ecs, self.player.unwrap());
+        self.world.as_ref().unwrap().raycast(head, camera.forward(), REACH_DISTANCE)
+    }
+
+    // The nearest mob under the crosshair within reach, and how far away
+    // it is.
+    fn target_mob(&self) -> Option<(ecs::Entity, f32)> {
+        let camera = self.camera.as_ref().unwrap();
+        let head = player::eye_position(&self.ecs, self.player.unwrap());
+        mob::raycast(&self.ecs, head, camera.forward(), REACH_DISTANCE)
+    }
+
+    // Hits the aimed-at mob, unless a block is in the way. Returns whether
+    // there was one to hit.
+    fn attack_mob(&mut self) -> bool {
+        let Some((target, distance)) = self.target_mob() else {
+            return false;
+        };
+        if self.target_block().is_some_and(|hit| hit.distance < distance) {
+            return false;
+        }
+        let player = self.player.unwrap();
+        player_model::swing_arm(&mut self.ecs, player);
+        if mob::hit(&mut self.ecs, target, player) {
+            hunger::exhaust(&mut self.ecs, player, hunger::ATTACK_EXHAUSTION);
+        }
+        true
+    }
+
+    // Puts up the death screen once the player runs out of health.
+    fn check_death(&mut self) {
+        let Some(health) = self.ecs.healths.get(self.player.unwrap()) else {
+            return;
+        };
+        if !health.is_dead() || self.state == menu::GameState::Dead {
+            return;
+        }
+
+        let message = health.last_damage.map_or("You died", health::DamageSource::death_message);
+        self.state = menu::GameState::Dead;
+        self.menu.message = Some(message.to_string());
+        self.console.open = false;
+        self.map_screen.close();
+        self.inventory_screen.close(&mut self.inventory);
+        self.breaking.set_held(false);
+        self.update_cursor_grab();
+    }
+
+    // Brings the player back to life at their bed, or the world's spawn
+    // point if they have none, both of which are in the overworld.
+    fn respawn(&mut self) {
+        let player = self.player.unwrap();
+        let world = self.world.as_ref().unwrap();
+        if world.dimension() != dimension::Dimension::Overworld {
+            let [x, y, z] = self.bed.map_or(world.spawn_point, |bed| bed.map(|v| v as f32)).map(|v| v.floor() as i32);
+            let center = chunk::ChunkPos::from_block(x, y, z);
+            self.enter_dimension(dimension::Dimension::Overworld, center);
+        }
+        let world = self.world.as_ref().unwrap();
+        let bed = self.bed.map(|bed| sleep::bed_spawn(world, bed));
+        if bed == Some(None) {
+            self.bed = None;
+            self.chat.push(chat::ChatLine::new(chat::ChatKind::Notice, "Your bed was missing"));
+        }
+        let spawn = bed.flatten().unwrap_or(world.spawn_point);
+        if let Some(transform) = self.ecs.transforms.get_mut(player) {
+            transform.teleport(spawn.into());
+        }
+        if let Some(velocity) = self.ecs.velocities.get_mut(player) {
+            *velocity = ecs::Velocity::default();
+        }
+        if let Some(health) = self.ecs.healths.get_mut(player) {
+            health.restore();
+        }
+        if let Some(hunger) = self.ecs.hungers.get_mut(player) {
+            hunger.restore();
+        }
+        self.camera_effects.reset(self.camera.as_mut().unwrap());
+        self.sleep = sleep::Sleep::default();
+        self.menu.message = None;
+        self.state = menu::GameState::Playing;
+    }
+
+    // Makes `bed` where the player respawns and, at night, puts them in
+    // it. Alone they fall asleep straight away; on a server they wait for
+    // everyone else.
+    fn use_bed(&mut self, bed: [i32 ; 3]) {
+        let world = self.world.as_ref().unwrap();
+        // There's no night to sleep through, and no respawning, anywhere
+        // else.
+        if world.dimension() != dimension::Dimension::Overworld {
+            self.chat.push(chat::ChatLine::new(chat::ChatKind::Notice, "You can't sleep here"));
+            return;
+        }
+        if self.bed != Some(bed) {
+            self.bed = Some(bed);
+            self.chat.push(chat::ChatLine::new(chat::ChatKind::Notice, "Respawn point set"));
+        }
+        if !world.is_night() {
+            self.chat.push(chat::ChatLine::new(chat::ChatKind::Notice, "You can only sleep at night"));
+            return;
+        }
+        let Some(position) = sleep::bed_spawn(world, bed) else {
+            return;
+        };
+        let player = self.player.unwrap();
+        if let Some(transform) = self.ecs.transforms.get_mut(player) {
+            transform.teleport(position.into());
+        }
+        if let Some(velocity) = self.ecs.velocities.get_mut(player) {
+            *velocity = ecs::Velocity::default();
+        }
+        self.camera_controller.as_mut().unwrap().release_all();
+        self.sleep.get_in();
+        match &self.client {
+            Some(client) => client.send_sleep(bed),
+            None => self.sleep.fall_asleep(),
+        }
+    }
+
+    // Opens or closes the door, trapdoor or gate at `block`, and the other
+    // half of a door with it.
+    fn toggle_open(&mut self, block: [i32 ; 3]) {
+        let world = self.world.as_mut().unwrap();
+        let [x, y, z] = block;
+        let used = world.get_state(x, y, z);
+        let open = !used.properties.open();
+        for [x, y, z] in std::iter::once(block).chain(used.other_part(block)) {
+            let state = world.get_state(x, y, z);
+            if state.block != used.block {
+                continue;
+            }
+            let state = block::BlockState::new(state.block, state.properties.with_open(open));
+            if let (true, Some(client)) = (world.set_state(x, y, z, state), &self.client) {
+                client.send_block([x, y, z], state);
+            }
+        }
+        self.audio.play_at(audio::Sound::Place(used.block.info().sound), block_center(block));
+    }
+
+    // Turns the lever at `block` on or off, or presses the button there,
+    // which pops back out by itself.
+    fn flip_switch(&mut self, block: [i32 ; 3]) {
+        let world = self.world.as_mut().unwrap();
+        let [x, y, z] = block;
+        let state = world.get_state(x, y, z);
+        let on = state.block.info().model == block::BlockModel::Button || !state.properties.powered();
+        let state = block::BlockState::new(state.block, state.properties.with_powered(on));
+        if world.set_state(x, y, z, state) {
+            if let Some(client) = &self.client {
+                client.send_block(block, state);
+            }
+            self.audio.play_at(audio::Sound::Place(state.block.info().sound), block_center(block));
+        }
+    }
+
+    fn get_up(&mut self) {
+        if let (true, Some(client)) = (self.sleep.get_up(), &self.client) {
+            client.send_get_up();
+        }
+    }
+
+    // Works on breaking the aimed-at block while the break button is held,
+    // and marks how far along it is.
+    fn update_breaking(&mut self, dt: f32) {
+        // Screens in front of the world take the button's release, so it
+        // counts as let go while they're open.
+        let controls_world = self.state == menu::GameState::Playing
+            && !self.console.open
+            && !self.map_screen.open
+            && !self.inventory_screen.open
+            && !self.sleep.in_bed();
+        if !controls_world {
+            self.breaking.set_held(false);
+        }
+
+        let world = self.world.as_ref().unwrap();
+        let target = self.target_block().map(|hit| {
+            let [x, y, z] = hit.block;
+            let hardness = if world.game_mode.instant_break() { 0.0 } else { world.get_block(x, y, z).info().hardness };
+            (hit.block, hardness)
+        });
+        let step = self.breaking.update(target, dt);
+        if step.swing {
+            player_model::swing_arm(&mut self.ecs, self.player.unwrap());
+        }
+        if let Some(block) = step.broken {
+            self.break_block(block);
+        }
+
+        let crack = self.breaking.crack();
+        let shape = crack.map_or_else(Vec::new, |([x, y, z], _)| self.world.as_ref().unwrap().block_shape(x, y, z));
+        self.crack_renderer.as_mut().unwrap().set_target(self.queue.as_ref().unwrap(), crack, &shape);
+    }
+
+    fn break_block(&mut self, [x, y, z]: [i32 ; 3]) {
+        let world = self.world.as_mut().unwrap();
+        let broken = world.get_block(x, y, z);
+        let other_part = world.get_state(x, y, z).other_part([x, y, z]);
+        let contents = world.block_entity(x, y, z).map(|entity| entity.contents());
+        let mut previous = vec![world.snapshot([x, y, z])];
+        if !world.set_block(x, y, z, block::BlockId::AIR) {
+            return;
+        }
+        // A door or an extended piston goes as a whole, though only one part
+        // drops.
+        let attached = |[ox, oy, oz]: [i32 ; 3]| world.get_state(ox, oy, oz).other_part([ox, oy, oz]) == Some([x, y, z]);
+        if let Some([x, y, z]) = other_part.filter(|&other| attached(other)) {
+            previous.push(world.snapshot([x, y, z]));
+            world.set_block(x, y, z, block::BlockId::AIR);
+            if let Some(client) = &self.client {
+                client.send_block([x, y, z], block::BlockId::AIR.into());
+            }
+        }
+        if !world.game_mode.infinite_blocks() {
+            if let Some(drop) = broken.info().drops {
+                item::spawn_dropped_item(&mut self.ecs, drop, x, y, z);
+            }
+            item::spawn_dropped_stacks(&mut self.ecs, &contents.unwrap_or_default(), x, y, z);
+        }
+        hunger::exhaust(&mut self.ecs, self.player.unwrap(), hunger::BREAK_EXHAUSTION);
+        self.ecs.events.publish(events::Event::BlockBroken { block: [x, y, z], id: broken });
+        match &self.client {
+            Some(client) => client.send_block([x, y, z], block::BlockId::AIR.into()),
+            None => {
+                self.history.record(previous);
+            },
+        }
+    }
+
+    // Uses the aimed-at block if it does something, and otherwise eats the
+    // selected item if it's food or places it.
+    fn use_item(&mut self) {
+        if self.use_wand(1) || self.interact_with_block() {
+            return;
+        }
+        let slot = self.hotbar.as_ref().unwrap().selected;
+        let Some(stack) = self.inventory.get(slot) else {
+            return;
+        };
+        let food = stack.block.info().food;
+        if food == 0 {
+            self.place_block();
+            return;
+        }
+
+        let player = self.player.unwrap();
+        let world = self.world.as_ref().unwrap();
+        let Some(hunger) = self.ecs.hungers.get_mut(player).filter(|_| world.game_mode.takes_damage()) else {
+            return;
+        };
+        if hunger.eat(food) {
+            if !world.game_mode.infinite_blocks() {
+                self.inventory.remove_one(slot);
+            }
+            let head = player::eye_position(&self.ecs, player);
+            self.audio.play_at(audio::Sound::Eat, head);
+        }
+    }
+
+    // Marks the first (0) or second (1) corner of the world-edit selection
+    // at the aimed-at block, if the player holds the wand where it works.
+    // Returns whether the wand took the click.
+    fn use_wand(&mut self, corner: usize) -> bool {
+        let world = self.world.as_ref().unwrap();
+        let slot = self.hotbar.as_ref().unwrap().selected;
+        let holding = self.inventory.get(slot).is_some_and(|stack| world_edit::is_wand(stack.block));
+        if !holding || !world.game_mode.world_edit() {
+            return false;
+        }
+        let Some(hit) = self.target_block() else {
+            return true;
+        };
+
+        self.world_edit.set_corner(corner, hit.block);
+        let [x, y, z] = hit.block;
+        let mut text = format!("{} corner set to {x} {y} {z}", if corner == 0 { "First" } else { "Second" });
+        if let Some((min, max)) = self.world_edit.selection() {
+            let volume = (0..3).map(|axis| (max[axis] - min[axis] + 1) as i64).product::<i64>();
+            text += &format!(" ({volume} blocks selected)");
+        }
+        self.chat.push(chat::ChatLine::new(chat::ChatKind::Notice, text));
+        true
+    }
+
+    // Undoes or redoes the latest edit, for the keys. Servers keep no
+    // history to step through.
+    fn step_history(&mut self, redo: bool) {
+        let world = self.world.as_mut().unwrap();
+        if !world.game_mode.world_edit() || self.client.is_some() {
+            return;
+        }
+        let text = match (redo, if redo { self.history.redo(world, 1) } else { self.history.undo(world, 1) }) {
+            (false, (0, _)) => "Nothing to undo".to_string(),
+            (true, (0, _)) => "Nothing to redo".to_string(),
+            (false, (_, changed)) => format!("Undid an edit, changing {changed} blocks"),
+            (true, (_, changed)) => format!("Redid an edit, changing {changed} blocks"),
+        };
+        self.chat.push(chat::ChatLine::new(chat::ChatKind::Notice, text));
+    }
+
+    // Opens whatever the aimed-at block opens. Returns whether it did.
+    fn interact_with_block(&mut self) -> bool {
+        let Some(hit) = self.target_block() else {
+            return false;
+        };
+        let [x, y, z] = hit.block;
+        match self.world.as_ref().unwrap().get_block(x, y, z) {
+            // Explosions change too much of the world to send block by
+            // block, so only singleplayer worlds have them for now.
+            block::BlockId::TNT if self.client.is_none() => {
+                if self.world.as_mut().unwrap().set_block(x, y, z, block::BlockId::AIR) {
+                    explosion::light(&mut self.ecs, hit.block);
+                    self.audio.play_at(audio::Sound::Fuse, block_center(hit.block));
+                }
+                return true;
+            },
+            block::BlockId::CRAFTING_TABLE => self.inventory_screen.open_crafting_table(),
+            block::BlockId::CHEST | block::BlockId::FURNACE => match self.world.as_ref().unwrap().block_entity(x, y, z) {
+                Some(entity) => self.inventory_screen.open_container(hit.block, entity.clone()),
+                None => return false,
+            },
+            block if block.is_bed() => self.use_bed(hit.block),
+            block if block.opens() => self.toggle_open(hit.block),
+            block if block.is_switch() => self.flip_switch(hit.block),
+            _ => return false,
+        }
+        self.breaking.set_held(false);
+        self.update_cursor_grab();
+        true
+    }
+
+    // Writes back what the player moved in and out of the open container,
+    // then catches the screen up with changes made by anyone else, closing
+    // it if the block is gone.
+    fn update_container(&mut self) {
+        let world = self.world.as_mut().unwrap();
+        if let Some(([x, y, z], items)) = self.inventory_screen.take_container_change() {
+            world.set_block_entity_items(x, y, z, items);
+            if let (Some(client), Some(entity)) = (&self.client, world.block_entity(x, y, z)) {
+                client.send_block_entity([x, y, z], entity);
+            }
+        }
+
+        let Some([x, y, z]) = self.inventory_screen.container() else {
+            return;
+        };
+        match world.block_entity(x, y, z) {
+            Some(entity) => self.inventory_screen.sync_container(entity),
+            None => {
+                self.inventory_screen.close(&mut self.inventory);
+                self.update_cursor_grab();
+            },
+        }
+    }
+
+    fn place_block(&mut self) {
+        let Some(hit) = self.target_block() else {
+            return;
+        };
+
+        let [x, y, z] = [
+            hit.block[0] + hit.normal[0],
+            hit.block[1] + hit.normal[1],
+            hit.block[2] + hit.normal[2],
+        ];
+        if !self.world.as_ref().unwrap().border.contains_block(x, z) {
+            return;
+        }
+
+        let slot = self.hotbar.as_ref().unwrap().selected;
+        let Some(stack) = self.inventory.get(slot) else {
+            return;
+        };
+        // Items that aren't drawn as blocks can't be placed.
+        if stack.block.info().model == block::BlockModel::None {
+            return;
+        }
+        // How far up the clicked face the aim landed, for blocks that can
+        // sit in either half.
+        let player = self.player.unwrap();
+        let camera = self.camera.as_ref().unwrap();
+        let head = player::eye_position(&self.ecs, player);
+        let hit_y = head.y + camera.forward().y * hit.distance - hit.block[1] as f32;
+        let state = block::BlockState::placed(stack.block, hit.normal, camera.yaw.0, hit_y);
+
+        // Don't let the player entomb themselves.
+        let (Some(transform), Some(collider)) = (self.ecs.transforms.get(player), self.ecs.colliders.get(player)) else {
+            return;
+        };
+        let player_box = collider.aabb(transform.position);
+        let blocked = state.collision_shape([false ; 4]).iter()
+            .any(|cuboid| player_box.intersects(&physics::Aabb::of_cuboid([x, y, z], cuboid)));
+        if collider.collides && blocked {
+            return;
+        }
+
+        let world = self.world.as_mut().unwrap();
+        // A door needs room for its top half too.
+        let top = state.other_part([x, y, z]);
+        if top.is_some_and(|[x, y, z]| world.get_block(x, y, z) != block::BlockId::AIR) {
+            return;
+        }
+        let mut previous = vec![world.snapshot([x, y, z])];
+        if world.set_state(x, y, z, state) {
+            if let Some([x, y, z]) = top {
+                let top_state = block::BlockState::new(state.block, state.properties.with_half(block::Half::Top));
+                previous.push(world.snapshot([x, y, z]));
+                world.set_state(x, y, z, top_state);
+                if let Some(client) = &self.client {
+                    client.send_block([x, y, z], top_state);
+                }
+            }
+            if !world.game_mode.infinite_blocks() {
+                self.inventory.remove_one(slot);
+            }
+            self.ecs.events.publish(events::Event::BlockPlaced { block: [x, y, z], id: state.block });
+            match &self.client {
+                Some(client) => client.send_block([x, y, z], state),
+                None => {
+                    self.history.record(previous);
+                },
+            }
+        }
+    }
+
+    // Loads chunks coming into range and unloads, saving if modified, the
+    // ones left behind. One chunk beyond the render distance stays loaded
+    // so walking back and forth over a border doesn't thrash.
+    fn stream_chunks(&mut self) {
+        let world = self.world.as_mut().unwrap();
+        let chunk_io = self.chunk_io.as_mut().unwrap();
+        let eye = self.camera.as_ref().unwrap().eye;
+        let [x, y, z] = [eye.x, eye.y, eye.z].map(|v| v.floor() as i32);
+        let center = chunk::ChunkPos::from_block(x, y, z);
+        let (radius, vertical) = (self.settings.graphics.render_distance, self.settings.graphics.vertical_distance);
+        let in_range = |pos: chunk::ChunkPos| {
+            let (across, up) = pos.distance(center);
+            across <= radius + 1 && up <= vertical + 1
+        };
+
+        chunk_io.set_center(center);
+        chunk_io.retain_loads(in_range);
+        for loaded in chunk_io.poll() {
+            if in_range(loaded.pos) && !world.chunks.contains_key(&loaded.pos) {
+                world.insert_chunk(loaded.chunk, loaded.overflow, loaded.unsaved);
+                self.ecs.events.publish(events::Event::ChunkLoaded { pos: loaded.pos });
+            } else if loaded.unsaved {
+                chunk_io.request_save(loaded.chunk);
+            }
+        }
+
+        let out_of_range = world.chunks.keys().copied().filter(|&pos| !in_range(pos)).collect::<Vec<_>>();
+        for pos in out_of_range {
+            if !world.is_modified(pos) {
+                world.unload_chunk(pos);
+            } else if chunk_io.can_save() {
+                chunk_io.request_save(world.unload_chunk(pos).unwrap());
+            }
+        }
+
+        let mut missing = center.around(radius, vertical)
+            .filter(|&pos| !world.chunks.contains_key(&pos) && !chunk_io.is_pending(pos))
+            .collect::<Vec<_>>();
+        missing.sort_by_key(|pos| pos.distance_squared(center));
+        for pos in missing {
+            if !chunk_io.request_load(pos) {
+                break;
+            }
+        }
+    }
+
+    fn remesh_dirty_chunks(&mut self) {
+        let world = self.world.as_mut().unwrap();
+        let device = self.device.as_ref().unwrap();
+        let queue = self.queue.as_ref().unwrap();
+
+        let dirty = world.take_dirty();
+        // The map is of the overworld's surface, which any chunk in a
+        // column may have changed.
+        if world.dimension().has_sky() {
+            for column in dirty.iter().map(|pos| pos.column()).collect::<HashSet<_>>() {
+                self.map.update(world, column);
+            }
+        }
+        for pos in dirty {
+            self.profiler.begin(profiler::Section::Mesh);
+            let meshes = mesher::mesh_chunk(world, pos, self.settings.graphics.smooth_lighting);
+            self.profiler.end();
+            let old = match meshes {
+                Some(meshes) => {
+                    self.remesh_ages.as_mut().unwrap().record(pos);
+                    self.profiler.begin(profiler::Section::Upload);
+                    let buffers = meshes.upload(device, queue, &mut self.mesh_pool);
+                    self.profiler.end();
+                    self.chunk_buffers.insert(pos, buffers)
+                },
+                None => self.chunk_buffers.remove(&pos),
+            };
+            if let Some(old) = old {
+                self.mesh_pool.free(old);
+            }
+        }
+    }
+
+    fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        if new_size.width > 0 && new_size.height > 0 {
+            self.window.as_mut().unwrap().size = new_size;
+            
+            if let Some(config) = &mut self.config {
+                config.width = new_size.width;
+                config.height = new_size.height;
+            }
+            
+            if let Some(surface) = &mut self.surface {
+                surface.configure(
+                    self.device.as_ref().unwrap(),
+                    self.config.as_ref().unwrap()
+                );
+            }
+
+            if let Some(post_processor) = &mut self.post_processor {
+                post_processor.resize(self.device.as_ref().unwrap(), new_size.width, new_size.height);
+                self.depth_texture = Some(texture::Texture::create_depth_texture(
+                    self.device.as_ref().unwrap(),
+                    self.config.as_ref().unwrap(),
+                    post_processor.sample_count,
+                    "depth_texture",
+                ));
+            }
+
+            if let Some(camera) = &mut self.camera {
+                camera.aspect = new_size.width as f32 / new_size.height as f32;
+            }
+        }
+    }
+
+    fn update(&mut self) {
+        let _span = tracing::trace_span!("update").entered();
+        let now = Instant::now();
+        let frame_time = self.last_update.map_or(Duration::ZERO, |last| now - last);
+        let dt = frame_time.as_secs_f32();
+        self.last_update = Some(now);
+        self.debug_overlay.record_frame(frame_time);
+        self.profiler.end_frame(frame_time);
+        if let Some(timer) = &mut self.gpu_timer {
+            for passes in timer.poll(self.device.as_ref().unwrap()) {
+                self.profiler.record_gpu(&passes);
+            }
+        }
+        self.profiler.begin(profiler::Section::Update);
+        self.screenshots.poll(self.device.as_ref().unwrap());
+        self.update_gamepads();
+        self.update_hot_reload();
+
+        // A singleplayer world stands still behind the menus; a server's
+        // keeps going.
+        let simulate = self.world.is_some()
+            && (self.state == menu::GameState::Playing || self.client.is_some());
+        if simulate {
+            if let Err(e) = self.update_game(frame_time) {
+                self.leave_game();
+                self.menu.message = Some(e.to_string());
+            }
+        }
+        if self.world.is_none() {
+            let camera = self.camera.as_mut().unwrap();
+            camera.yaw.0 += dt * MENU_TURN_SPEED;
+            self.audio.update_music(audio::MusicContext::Menu, dt);
+        }
+        self.update_title();
+
+        // Looked up before the text renderer is borrowed below.
+        let looking = self.debug_overlay.visible && self.world.is_some();
+        let target = looking.then(|| self.target_block()).flatten().map(|hit| {
+            let [x, y, z] = hit.block;
+            (hit.block, self.world.as_ref().unwrap().get_state(x, y, z))
+        });
+
+        let config = self.config.as_ref().unwrap();
+        let screen_size = (config.width, config.height);
+        self.ui_batch.clear();
+        let map_renderer = self.map_renderer.as_mut().unwrap();
+        map_renderer.begin(&mut self.map);
+        let text_renderer = self.text_renderer.as_mut().unwrap();
+        if let Some(world) = &self.world {
+            if let Some(client) = &self.client {
+                let view_proj = self.camera.as_ref().unwrap().build_view_proj_matrix();
+                for (name, entity) in client.remote_players() {
+                    let Some(transform) = self.ecs.transforms.get(entity) else {
+                        continue;
+                    };
+                    let head = transform.position + cgmath::Vector3::unit_y() * player::PLAYER_HEIGHT;
+                    ui::draw_name_tag(&mut self.ui_batch, text_renderer, view_proj, screen_size, head, name);
+                }
+            }
+            if self.map_screen.open {
+                let camera = self.camera.as_ref().unwrap();
+                self.map_screen.draw(
+                    map_renderer,
+                    self.queue.as_ref().unwrap(),
+                    &self.map,
+                    &mut self.ui_batch,
+                    text_renderer,
+                    camera.eye,
+                    camera.yaw.0,
+                    world.game_mode.map_teleport(),
+                    screen_size,
+                );
+            } else if self.inventory_screen.open {
+                self.inventory_screen.draw(&mut self.ui_batch, text_renderer, &self.inventory, screen_size);
+            } else {
+                let hotbar = self.hotbar.as_ref().unwrap();
+                ui::draw_crosshair(&mut self.ui_batch, screen_size);
+                hotbar.draw(&mut self.ui_batch, text_renderer, &self.inventory, screen_size);
+                hotbar.draw_selected_name(text_renderer, &self.inventory, screen_size);
+                // The profiler takes the same corner.
+                if self.settings.hud.minimap && !self.profiler.visible {
+                    let camera = self.camera.as_ref().unwrap();
+                    map::draw_minimap(
+                        map_renderer,
+                        self.queue.as_ref().unwrap(),
+                        &self.map,
+                        &mut self.ui_batch,
+                        text_renderer,
+                        camera.eye,
+                        camera.yaw.0,
+                        self.settings.hud.minimap_zoom,
+                        screen_size,
+                    );
+                }
+                if self.settings.hud.compass {
+                    if let Some(transform) = self.ecs.transforms.get(self.player.unwrap()) {
+                        let yaw = self.camera.as_ref().unwrap().yaw.0;
+                        ui::draw_compass(&mut self.ui_batch, text_renderer, transform.position, yaw, screen_size);
+                    }
+                }
+                if world.game_mode.takes_damage() {
+                    let player = self.player.unwrap();
+                    if let Some(health) = self.ecs.healths.get(player) {
+                        ui::draw_health(&mut self.ui_batch, health.points, screen_size);
+                    }
+                    if let Some(hunger) = self.ecs.hungers.get(player) {
+                        ui::draw_food(&mut self.ui_batch, hunger.food, screen_size);
+                    }
+                }
+            }
+            // Sleeping fades the world out, with chat still readable over it.
+            let screen = ui::Rect::new(0.0, 0.0, screen_size.0 as f32, screen_size.1 as f32);
+            if self.sleep.darkness() > 0.0 {
+                self.ui_batch.quad(screen, [0.0, 0.0, 0.0, self.sleep.darkness()]);
+            }
+            // Waiting in a portal washes the screen over in its color.
+            if self.portal_timer.progress() > 0.0 {
+                self.ui_batch.quad(screen, [0.45, 0.15, 0.7, self.portal_timer.progress() * PORTAL_TINT]);
+            }
+            self.chat.draw(&mut self.ui_batch, text_renderer, screen_size, self.console.open);
+            // The console covers the corner the debug overlay is drawn in.
+            if self.console.open {
+                self.console.draw(&mut self.ui_batch, text_renderer, screen_size);
+            } else {
+                let camera = self.camera.as_ref().unwrap();
+                // Biomes only make the overworld's surface.
+                let biome = match world.dimension() {
+                    dimension::Dimension::Overworld => world.generator
+                        .biome_at(camera.eye.x.floor() as i32, camera.eye.z.floor() as i32)
+                        .info()
+                        .name,
+                    dimension => dimension.name(),
+                };
+                self.debug_overlay.draw(text_renderer, &debug::FrameInfo {
+                    camera,
+                    biome,
+                    light: world.light(camera.eye.x.floor() as i32, camera.eye.y.floor() as i32, camera.eye.z.floor() as i32),
+                    target,
+                    loaded_chunks: world.chunks.len(),
+                    lod_chunks: self.lod_terrain.chunks().len(),
+                    chunk_debug: self.chunk_debug,
+                    visible_chunks: self.chunk_draws.as_ref().unwrap().visible_chunks(),
+                    chunk_memory: world.chunks.values().map(chunk::Chunk::memory_usage).sum(),
+                    mesh_memory: self.mesh_pool.memory_usage(),
+                    particles: (self.particles.len(), self.particles.budget()),
+                    present_mode: config.present_mode,
+                    render_stats: self.render_stats,
+                });
+            }
+        }
+        self.profiler.draw(&mut self.ui_batch, text_renderer, screen_size);
+        self.menu.draw(self.state, &mut self.ui_batch, text_renderer, screen_size, &self.settings, self.client.is_some());
+
+        self.profiler.begin(profiler::Section::Upload);
+        self.ui_renderer.as_mut().unwrap().prepare(
+            self.device.as_ref().unwrap(),
+            self.queue.as_ref().unwrap(),
+            &self.ui_batch,
+            screen_size,
+        );
+        map_renderer.prepare(self.device.as_ref().unwrap(), self.queue.as_ref().unwrap(), screen_size);
+        text_renderer.prepare(
+            self.device.as_ref().unwrap(),
+            self.queue.as_ref().unwrap(),
+            screen_size,
+        );
+
+        self.camera_uniform.as_mut().unwrap().update_view_proj(self.camera.as_ref().unwrap());
+        self.queue.as_ref().unwrap().write_buffer(
+            self.camera_buffer.as_ref().unwrap(),
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform.unwrap()]),
+        );
+
+        let time_of_day = self.world.as_ref().map_or(MENU_TIME_OF_DAY, world::World::time_of_day);
+        let dimension = self.world.as_ref().map_or(dimension::Dimension::Overworld, world::World::dimension);
+        let sky = dimension.sky();
+        let (daylight, wetness) = match &self.world {
+            // Without a sun it's neither day nor night.
+            Some(_) if !sky.sun => (1.0, 0.0),
+            Some(world) => (world.weather.daylight(sky::daylight(time_of_day)), world.weather.wetness()),
+            None => (sky::daylight(time_of_day), 0.0),
+        };
+        let frame_uniform = self.frame_uniform.as_mut().unwrap();
+        frame_uniform.update_camera(self.camera.as_ref().unwrap());
+        frame_uniform.update_weather(&sky, daylight, wetness);
+        let remesh_ages = self.remesh_ages.as_mut().unwrap();
+        frame_uniform.chunk_tint = self.chunk_debug.tint_id();
+        frame_uniform.debug_time = remesh_ages.now();
+        if self.chunk_debug == chunk_debug::ChunkDebugMode::RemeshAge {
+            remesh_ages.upload(self.queue.as_ref().unwrap());
+        }
+        self.queue.as_ref().unwrap().write_buffer(
+            self.frame_buffer.as_ref().unwrap(),
+            0,
+            bytemuck::cast_slice(&[self.frame_uniform.unwrap()]),
+        );
+
+        self.sky_renderer.as_mut().unwrap().update(
+            self.queue.as_ref().unwrap(),
+            self.camera.as_ref().unwrap(),
+            &sky,
+            time_of_day,
+            daylight,
+            wetness,
+        );
+        // With no sun, the shadows fade out as if under the thickest
+        // clouds.
+        self.shadow_renderer.as_mut().unwrap().update(
+            self.queue.as_ref().unwrap(),
+            self.camera.as_ref().unwrap(),
+            time_of_day,
+            if sky.sun { wetness } else { 1.0 },
+            fog::view_distance(self.settings.graphics.render_distance),
+        );
+        self.profiler.end();
+        self.profiler.end();
+    }
 
-    fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBS,
+    // Names the world being played, or the server, in the title bar, and
+    // the frame rate too if the settings ask for it.
+    fn update_title(&mut self) {
+        let mut parts = Vec::new();
+        if self.world.is_some() {
+            parts.push(match &self.server_address {
+                Some(address) => address.clone(),
+                None => self.world_save.name(),
+            });
+        }
+        if self.settings.window.title_fps {
+            parts.push(format!("{:.0} FPS", self.debug_overlay.fps()));
         }
+        let status = (!parts.is_empty()).then(|| parts.join(" - "));
+        self.window.as_mut().unwrap().set_status(status);
     }
-}
 
-const VERTICES: &[Vertex] = &[
-    // Front
-    Vertex { position: [0.0, 0.5, 0.0],    tex_coords: [1.0, 0.0] },
-    Vertex { position: [-0.5, 0.5, 0.0],   tex_coords: [0.0, 0.0] },
-    Vertex { position: [-0.5, -0.5, 0.0],  tex_coords: [0.0, 1.0] },
-    Vertex { position: [0.0, -0.5, 0.0],   tex_coords: [1.0, 1.0] },
-
-    // Right
-    Vertex { position: [0.0, 0.5, -0.5],    tex_coords: [1.0, 0.0] },
-    Vertex { position: [0.0, 0.5, 0.0],   tex_coords: [0.0, 0.0] },
-    Vertex { position: [0.0, -0.5, 0.0],  tex_coords: [0.0, 1.0] },
-    Vertex { position: [0.0, -0.5, -0.5],   tex_coords: [1.0, 1.0] },
-
-    // Back
-    Vertex { position: [0.0, -0.5, -0.5],    tex_coords: [1.0, 1.0] },
-    Vertex { position: [-0.5, -0.5, -0.5],   tex_coords: [0.0, 1.0] },
-    Vertex { position: [-0.5, 0.5, -0.5],  tex_coords: [0.0, 0.0] },
-    Vertex { position: [0.0, 0.5, -0.5],   tex_coords: [1.0, 0.0] },
-
-    // Left
-    Vertex { position: [-0.5, 0.5, 0.0],    tex_coords: [1.0, 0.0] },
-    Vertex { position: [-0.5, 0.5, -0.5],   tex_coords: [0.0, 0.0] },
-    Vertex { position: [-0.5, -0.5, -0.5],  tex_coords: [0.0, 1.0] },
-    Vertex { position: [-0.5, -0.5, 0.0],   tex_coords: [1.0, 1.0] },
-
-    // Top
-    Vertex { position: [0.0, 0.5, 0.0],    tex_coords: [1.0, 0.0] },
-    Vertex { position: [0.0, 0.5, -0.5],   tex_coords: [0.0, 0.0] },
-    Vertex { position: [-0.5, 0.5, -0.5],  tex_coords: [0.0, 1.0] },
-    Vertex { position: [-0.5, 0.5, 0.0],   tex_coords: [1.0, 1.0] },
-
-    // Bottom
-    Vertex { position: [0.0, -0.5, 0.0],    tex_coords: [1.0, 0.0] },
-    Vertex { position: [-0.5, -0.5, 0.0],   tex_coords: [0.0, 0.0] },
-    Vertex { position: [-0.5, -0.5, -0.5],  tex_coords: [0.0, 1.0] },
-    Vertex { position: [0.0, -0.5, -0.5],   tex_coords: [1.0, 1.0] },
-];
-
-const INDICES: &[u16] = &[
-    0, 1, 2,
-    2, 3, 0,
-    4, 5, 6,
-    6, 7, 4,
-    8, 9, 10,
-    10, 11, 8,
-    12, 13, 14,
-    14, 15, 12,
-    16, 17, 18,
-    18, 19, 16,
-    20, 21, 22,
-    22, 23, 20,
-];
+    // Picks up edits to the terrain shader and block textures, if hot
+    // reload is on.
+    fn update_hot_reload(&mut self) {
+        let Some(hot_reload) = &mut self.hot_reload else {
+            return;
+        };
+        let changes = hot_reload.poll();
+        if changes.shader {
+            self.reload_shader();
+        }
+        if changes.textures {
+            self.reload_textures();
+        }
+    }
 
-#[allow(unused)]
-struct App<'a> {
-    surface: Option<wgpu::Surface<'a>>,
-    device: Option<wgpu::Device>,
-    queue: Option<wgpu::Queue>,
-    config: Option<wgpu::SurfaceConfiguration>,
+    // Rebuilds the terrain pipelines from the shader on disk. A shader that
+    // doesn't compile or no longer fits the pipelines is logged and the old
+    // pipelines are kept.
+    fn reload_shader(&mut self) {
+        let source = match self.hot_reload.as_ref().unwrap().read_shader() {
+            Ok(source) => source,
+            Err(e) => {
+                warn!("could not reload the shader: {e}");
+                return;
+            },
+        };
 
-    render_pipeline: Option<wgpu::RenderPipeline>,
+        let device = self.device.as_ref().unwrap();
+        let sample_count = self.post_processor.as_ref().unwrap().sample_count;
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let (pipelines, wireframe_pipelines) = create_terrain_pipelines(
+            device,
+            self.terrain_pipeline_layout.as_ref().unwrap(),
+            &shader,
+            self.scene_format.unwrap(),
+            sample_count,
+        );
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            warn!("could not reload the shader: {error}");
+            return;
+        }
 
-    camera: Option<camera::Camera>,
-    camera_uniform: Option<camera::CameraUniform>,
-    camera_buffer: Option<wgpu::Buffer>,
-    camera_bind_group: Option<wgpu::BindGroup>,
+        let [render_pipeline, cutout_pipeline, translucent_pipeline] = pipelines;
+        self.terrain_shader     = Some(shader);
+        self.render_pipeline    = Some(render_pipeline);
+        self.cutout_pipeline    = Some(cutout_pipeline);
+        self.translucent_pipeline = Some(translucent_pipeline);
+        self.wireframe_pipelines = wireframe_pipelines;
+        info!("reloaded the shader");
+    }
 
-    camera_controller: Option<camera::CameraController>,
+    // Uploads the block textures again over the ones in use, which only
+    // works while they keep their size and number.
+    fn reload_textures(&mut self) {
+        let textures = match self.hot_reload.as_ref().unwrap().read_textures() {
+            Ok(textures) => textures,
+            Err(e) => {
+                warn!("could not reload the block textures: {e}");
+                return;
+            },
+        };
+        let layers = textures.iter().map(Vec::as_slice).collect::<Vec<_>>();
 
-    vertex_buffer: Option<wgpu::Buffer>,
-    num_vertices: Option<u32>,
+        let queue = self.queue.as_ref().unwrap();
+        let result = self.diffuse_texture.as_ref().unwrap()
+            .reload_layers(queue, &layers, "block_textures")
+            .and_then(|()| self.icon_atlas.as_ref().unwrap().reload_atlas_tiles(
+                queue,
+                &layers,
+                block::ATLAS_COLUMNS,
+                "block_icon_atlas",
+            ))
+            .and_then(|()| animation::TextureAnimator::new(&layers, &block::registry().animations, "block_textures"));
+        match result {
+            Ok(animator) => {
+                self.texture_animator = Some(animator);
+                info!("reloaded the block textures");
+            },
+            Err(e) => warn!("could not reload the block textures: {e}"),
+        }
+    }
 
-    index_buffer: Option<wgpu::Buffer>,
-    num_indices: Option<u32>,
+    // Moves the player and the world on by `dt` and prepares the world's
+    // draws. Fails when the connection to the server is lost.
+    // One step of the simulation, which runs the same however fast frames
+    // come. On a server only the player is simulated here; the server
+    // runs the rest.
+    fn tick(&mut self, dt: f32) {
+        let camera = self.camera.as_ref().unwrap();
+        let player = self.player.unwrap();
+        let world = self.world.as_mut().unwrap();
 
-    diffuse_bind_group: Option<wgpu::BindGroup>,
-    diffuse_texture: Option<texture::Texture>,
+        self.ecs.start_tick();
+        self.camera_controller.as_mut().unwrap().update_player(&mut self.ecs, player, camera, dt);
+        // Hold everything still until the ground under the player has
+        // loaded, rather than letting them fall through it.
+        let position = self.ecs.transforms.get(player).map_or(camera.eye, |t| t.position);
+        let [x, y, z] = [position.x, position.y, position.z].map(|v| v.floor() as i32);
+        if world.chunks.contains_key(&chunk::ChunkPos::from_block(x, y - 1, z)) {
+            ecs::physics_system(&mut self.ecs, world, dt);
+            health::health_system(&mut self.ecs, world, dt);
+            health::void_system(&mut self.ecs, world);
+            hunger::hunger_system(&mut self.ecs, world, dt);
+        }
+        item::item_system(&mut self.ecs, player, &mut self.inventory, dt);
+        // Online the clock runs here too, and the server corrects it now
+        // and then.
+        world.advance_time(dt);
+        if self.client.is_some() {
+            return;
+        }
 
-    window: Option<window::Window<'a>>,
-}
+        self.mods.tick(world, dt);
+        world.weather.update(dt);
+        self.fluids.update(world, dt);
+        let pushed = self.signals.update(world, dt);
+        piston::animate(&mut self.ecs, world, pushed);
+        piston::moving_block_system(&mut self.ecs, world, dt);
+        falling::falling_system(&mut self.ecs, world);
+        furnace::furnace_system(world, dt);
+        self.mob_spawner.update(&mut self.ecs, world, player, dt);
+        mob::mob_system(&mut self.ecs, world, &mut self.pathfinder, dt);
+        for (center, power) in explosion::fuse_system(&mut self.ecs, dt) {
+            explosion::explode(world, &mut self.ecs, center, power);
+            self.particles.explosion(center, power);
+            self.audio.play_at(audio::Sound::Explosion, center);
+        }
+    }
 
-impl<'a> App<'a> {
-    fn init() -> Self {
-        Self {
-            surface:            None,
-            device:             None,
-            queue:              None,
-            config:             None,
+    // Runs the ticks due since the last frame, then everything that
+    // follows the frame rate: looking around, the network, and what's
+    // drawn.
+    fn update_game(&mut self, frame_time: Duration) -> error::Result<()> {
+        let dt = frame_time.as_secs_f32();
+        self.camera_controller.as_mut().unwrap().update_camera(self.camera.as_mut().unwrap(), dt);
 
-            render_pipeline:    None,
-            
-            camera:             None,
-            camera_uniform:     None,
-            camera_buffer:      None,
-            camera_bind_group:  None,
+        let tick = self.settings.simulation.tick_interval();
+        self.tick_time += frame_time;
+        let mut ticks = 0;
+        while self.tick_time >= tick {
+            if ticks == MAX_TICKS_PER_FRAME {
+                self.tick_time = Duration::ZERO;
+                break;
+            }
+            self.tick(tick.as_secs_f32());
+            self.tick_time -= tick;
+            ticks += 1;
+        }
+        self.tick_alpha = self.tick_time.as_secs_f32() / tick.as_secs_f32();
+        self.update_portal(dt);
 
-            camera_controller:  None,
+        let camera = self.camera.as_mut().unwrap();
+        let player = self.player.unwrap();
+        let world = self.world.as_mut().unwrap();
+        if let Some(transform) = self.ecs.transforms.get_mut(player) {
+            transform.yaw = camera.yaw.0;
+        }
+        let head = player::interpolated_eye_position(&self.ecs, player, self.tick_alpha);
+        camera.eye = camera::camera_position(world, self.camera_mode, head, camera.forward());
 
-            vertex_buffer:      None,
-            num_vertices:       None,
+        match &mut self.client {
+            Some(client) => {
+                let position = self.ecs.transforms.get(player).map_or(camera.eye, |t| t.position);
+                client.send_move(position, camera.yaw.0, camera.pitch.0);
+                let lines = client.update(world, &mut self.ecs, player)?;
+                lines.into_iter().for_each(|line| self.chat.push(line));
+                if client.take_fall_asleep() {
+                    self.sleep.fall_asleep();
+                }
+            },
+            None => {
+                self.stream_chunks();
+                if self.last_save.elapsed() >= save::AUTOSAVE_INTERVAL {
+                    self.autosave();
+                }
+            },
+        }
+        // On a server the clock jumps when the server says so.
+        let world = self.world.as_mut().unwrap();
+        if self.sleep.update(dt, world.is_night()) && self.client.is_none() {
+            world.skip_night();
+        }
+        player_model::animation_system(&mut self.ecs, self.tick_alpha, dt);
+        let eye = self.camera.as_ref().unwrap().eye;
+        let world = self.world.as_mut().unwrap();
+        let thunder = world.weather.animate(dt).filter(|_| world.dimension().has_sky());
+        if let Some(angle) = thunder {
+            // Thunder comes from somewhere off towards the lightning.
+            let direction = cgmath::Vector3::new(angle.cos(), 0.5, angle.sin());
+            self.audio.play_at(audio::Sound::Thunder, eye + direction * THUNDER_DISTANCE);
+        }
+        let world = self.world.as_ref().unwrap();
+        self.particles.ambient(world, eye, dt);
+        self.particles.precipitation(world, eye, dt);
+        self.particles.update(world, dt);
+        self.texture_animator.as_mut().unwrap().update(
+            self.queue.as_ref().unwrap(),
+            self.diffuse_texture.as_ref().unwrap(),
+            dt,
+        );
+        self.update_container();
+        self.update_audio(dt);
+        self.update_camera_effects(dt);
+        self.remesh_dirty_chunks();
+        let camera = self.camera.as_ref().unwrap();
+        // Without a sky there's no surface to see from afar.
+        if self.world.as_ref().unwrap().dimension().has_sky() {
+            self.lod_terrain.update(
+                self.device.as_ref().unwrap(),
+                self.queue.as_ref().unwrap(),
+                &mut self.mesh_pool,
+                &self.world.as_ref().unwrap().generator,
+                chunk::ColumnPos::from_block(camera.eye.x.floor() as i32, camera.eye.z.floor() as i32),
+                &self.chunk_buffers,
+            );
+        }
+        self.prepare_world_draws();
+        self.update_breaking(dt);
+        self.handle_events();
+        self.check_death();
 
-            index_buffer:       None,
-            num_indices:        None,
+        let world = self.world.as_ref().unwrap();
+        let target = self.target_block().and_then(|hit| {
+            let [x, y, z] = hit.block;
+            physics::Aabb::bounds(world.block_shape(x, y, z).iter().map(|cuboid| physics::Aabb::of_cuboid(hit.block, cuboid)))
+        });
+        self.highlight_renderer.as_mut().unwrap().set_target(self.queue.as_ref().unwrap(), target);
+        let selection = self.world_edit.selection().map(|(min, max)| physics::Aabb::new(
+            cgmath::Point3::new(min[0] as f32, min[1] as f32, min[2] as f32),
+            cgmath::Point3::new(max[0] as f32 + 1.0, max[1] as f32 + 1.0, max[2] as f32 + 1.0),
+        ));
+        self.selection_renderer.as_mut().unwrap().set_target(self.queue.as_ref().unwrap(), selection);
+        let eye = self.camera.as_ref().unwrap().eye;
+        let center = (self.chunk_debug != chunk_debug::ChunkDebugMode::Off)
+            .then(|| chunk::ChunkPos::from_block(eye.x.floor() as i32, eye.y.floor() as i32, eye.z.floor() as i32));
+        self.chunk_border_renderer.as_mut().unwrap().set_center(self.queue.as_ref().unwrap(), center);
+        let border = self.world.as_ref().unwrap().border;
+        self.border_renderer.as_mut().unwrap().update(self.queue.as_ref().unwrap(), border, eye, dt);
+        Ok(())
+    }
 
-            diffuse_bind_group: None,
-            diffuse_texture:    None,
+    fn prepare_world_draws(&mut self) {
+        let camera = self.camera.as_ref().unwrap();
+        self.chunk_draws.as_mut().unwrap().prepare(
+            self.device.as_ref().unwrap(),
+            self.queue.as_ref().unwrap(),
+            &self.chunk_buffers,
+            self.lod_terrain.chunks(),
+            &camera.frustum(),
+            camera.eye,
+        );
+        self.entity_renderer.prepare(
+            self.device.as_ref().unwrap(),
+            self.queue.as_ref().unwrap(),
+            self.world.as_ref(),
+            &self.ecs,
+            self.player.filter(|_| self.camera_mode == camera::CameraMode::FirstPerson),
+            self.tick_alpha,
+        );
+        self.particle_renderer.as_mut().unwrap().prepare(
+            self.device.as_ref().unwrap(),
+            self.queue.as_ref().unwrap(),
+            &self.particles,
+            camera.eye,
+        );
+    }
 
-            window:             None,
+    // Hands what happened since last frame to everything that follows it.
+    // Mods only see it where the world is simulated here.
+    fn handle_events(&mut self) {
+        for event in self.ecs.events.take() {
+            match event {
+                events::Event::BlockPlaced { block, id } => {
+                    self.audio.play_at(audio::Sound::Place(id.info().sound), block_center(block));
+                },
+                events::Event::BlockBroken { block, id } => {
+                    self.audio.play_at(audio::Sound::Break(id.info().sound), block_center(block));
+                    self.particles.block_break(id, block);
+                },
+                events::Event::PlayerDamaged { entity, amount, .. } if Some(entity) == self.player => {
+                    self.camera_effects.hurt(amount);
+                },
+                _ => (),
+            }
+            if self.client.is_none() {
+                self.mods.handle(self.world.as_mut().unwrap(), &event);
+            }
         }
     }
-    
-    fn input(&mut self, event: &WindowEvent) -> bool {
-        self.camera_controller.as_mut().unwrap().process_events(event)
+
+    // Bobs, widens and shakes the view to go with what the player is doing.
+    fn update_camera_effects(&mut self, dt: f32) {
+        let player = self.player.unwrap();
+        let moving = self.ecs.velocities.get(player)
+            .is_some_and(|ecs::Velocity(velocity)| velocity.x.hypot(velocity.z) > camera::MOVING_SPEED);
+        let on_ground = self.ecs.colliders.get(player).is_some_and(|collider| collider.on_ground);
+        let inputs = camera::EffectInputs {
+            walking: moving && on_ground,
+            stride: self.footsteps.stride(),
+            sprinting: moving && self.ecs.players.get(player).is_some_and(|player| player.sprinting),
+            first_person: self.camera_mode == camera::CameraMode::FirstPerson,
+        };
+        let camera = self.camera.as_mut().unwrap();
+        self.camera_effects.update(camera, &self.settings.graphics.camera_effects, inputs, dt);
     }
 
-    fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.window.as_mut().unwrap().size = new_size;
-            
-            if let Some(config) = &mut self.config {
-                config.width = new_size.width;
-                config.height = new_size.height;
-            }
-            
-            if let Some(surface) = &mut self.surface {
-                surface.configure(
-                    &self.device.as_ref().unwrap(),
-                    &self.config.as_ref().unwrap()
-                );
+    // Follows the camera, plays footsteps for the block underfoot and picks
+    // the ambient loop and music for where the player is.
+    fn update_audio(&mut self, dt: f32) {
+        let camera = self.camera.as_ref().unwrap();
+        let world = self.world.as_ref().unwrap();
+        let player = self.player.unwrap();
+        self.audio.set_listener(camera.eye, camera.forward());
+
+        if let (Some(transform), Some(collider)) = (self.ecs.transforms.get(player), self.ecs.colliders.get(player)) {
+            let position = transform.position;
+            if self.footsteps.update(position, collider.on_ground) {
+                let below = world.get_block(position.x.floor() as i32, (position.y - 0.1).floor() as i32, position.z.floor() as i32);
+                self.audio.play_at(audio::Sound::Step(below.info().sound), position);
             }
         }
-    }
 
-    fn update(&mut self) {
-        self.camera_controller.as_ref().unwrap().update_camera(self.camera.as_mut().unwrap());
-        self.camera_uniform.as_mut().unwrap().update_view_proj(self.camera.as_ref().unwrap());
-        self.queue.as_ref().unwrap().write_buffer(
-            self.camera_buffer.as_ref().unwrap(),
-            0,
-            bytemuck::cast_slice(&[self.camera_uniform.unwrap()]),
-        );
+        let [x, y, z] = [camera.eye.x.floor() as i32, camera.eye.y.floor() as i32, camera.eye.z.floor() as i32];
+        // Well below the surface counts as a cave, whether or not it's
+        // open to the sky.
+        let underground = y < world.generator.height_at(x, z) - CAVE_DEPTH;
+        let ambient = if world.get_block(x, y, z).is_fluid() {
+            audio::Ambient::Underwater
+        } else if underground {
+            audio::Ambient::Cave
+        } else if world.weather.wetness() > 0.5
+            && world.generator.biome_at(x, z).info().precipitation == worldgen::biome::Precipitation::Rain
+        {
+            audio::Ambient::Rain
+        } else {
+            audio::Ambient::Surface
+        };
+        let music = if underground {
+            audio::MusicContext::Underground
+        } else if world.time_of_day() < 0.5 {
+            audio::MusicContext::Day
+        } else {
+            audio::MusicContext::Night
+        };
+        self.audio.set_ambient(Some(ambient));
+        self.audio.update_music(music, dt);
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.as_ref().unwrap().get_current_texture()?;
+        let _span = tracing::trace_span!("render").entered();
+        self.profiler.begin(profiler::Section::Present);
+        let output = self.surface.as_ref().unwrap().get_current_texture();
+        self.profiler.end();
+        let output = output?;
+        self.profiler.begin(profiler::Section::Render);
 
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
         
@@ -187,66 +2443,82 @@ impl<'a> App<'a> {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
-        
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(
-                                wgpu::Color {
-                                    r: 0.1,
-                                    g: 0.2,
-                                    b: 0.3,
-                                    a: 1.0,
-                                },
-                            ),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    }),
-                ],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
 
-            render_pass.set_pipeline(&self.render_pipeline.as_ref().unwrap());
-            render_pass.set_bind_group(0, self.diffuse_bind_group.as_ref().unwrap(), &[]);
-            render_pass.set_bind_group(1, self.camera_bind_group.as_ref().unwrap(), &[]);
-            render_pass.set_vertex_buffer(0, 
-                self.vertex_buffer.as_ref().unwrap().slice(..)
-            );
-            render_pass.set_index_buffer(
-                self.index_buffer.as_ref().unwrap().slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
-            render_pass.draw_indexed(0..self.num_indices.unwrap(), 0, 0..1);
+        let terrain = match &self.wireframe_pipelines {
+            Some([opaque, cutout, translucent]) if self.wireframe => {
+                renderer::TerrainPipelines { opaque, cutout, translucent }
+            },
+            _ => renderer::TerrainPipelines {
+                opaque:      self.render_pipeline.as_ref().unwrap(),
+                cutout:      self.cutout_pipeline.as_ref().unwrap(),
+                translucent: self.translucent_pipeline.as_ref().unwrap(),
+            },
+        };
+        let scene = renderer::Scene {
+            terrain,
+            block_bind_group:  self.diffuse_bind_group.as_ref().unwrap(),
+            camera_bind_group: self.camera_bind_group.as_ref().unwrap(),
+            frame_bind_group:  self.frame_bind_group.as_ref().unwrap(),
+            chunk_draws:       self.chunk_draws.as_ref().unwrap(),
+            mesh_pool:         &self.mesh_pool,
+            shadows:           self.shadow_renderer.as_ref().unwrap(),
+            post:              self.post_processor.as_ref().unwrap(),
+            sky:               self.sky_renderer.as_ref().unwrap(),
+            entities:          &self.entity_renderer,
+            particles:         self.particle_renderer.as_ref().unwrap(),
+            cracks:            self.crack_renderer.as_ref().unwrap(),
+            highlight:         self.highlight_renderer.as_ref().unwrap(),
+            selection:         self.selection_renderer.as_ref().unwrap(),
+            chunk_borders:     self.chunk_border_renderer.as_ref().unwrap(),
+            border:            self.border_renderer.as_ref().unwrap(),
+            map:               self.map_renderer.as_ref().unwrap(),
+            ui:                self.ui_renderer.as_ref().unwrap(),
+            icon_bind_group:   self.icon_bind_group.as_ref().unwrap(),
+            text:              self.text_renderer.as_ref().unwrap(),
+        };
+        let targets = renderer::FrameTargets {
+            surface: &view,
+            scene: self.post_processor.as_ref().unwrap().scene_view(),
+            scene_msaa: self.post_processor.as_ref().unwrap().msaa_view(),
+            depth: &self.depth_texture.as_ref().unwrap().view,
+            shadows: self.shadow_renderer.as_ref().unwrap(),
+        };
+        // Compute work sits outside the render graph; culling only has to
+        // finish before the first pass that draws chunks.
+        scene.chunk_draws.cull(&mut encoder);
+        // Passes are only timed on the GPU while someone's looking.
+        let mut gpu_timer = self.gpu_timer.as_mut().filter(|_| self.profiler.visible);
+        if let Some(timer) = &mut gpu_timer {
+            timer.start_frame();
+        }
+        self.render_stats = scene.frame_graph().execute(&mut encoder, &targets, gpu_timer.as_deref_mut());
+        if let Some(timer) = gpu_timer {
+            timer.resolve(&mut encoder);
         }
+        self.screenshots.capture(self.device.as_ref().unwrap(), &mut encoder, &output.texture);
+        self.profiler.end();
 
+        self.profiler.begin(profiler::Section::Present);
         self.queue.as_ref().unwrap().submit(std::iter::once(encoder.finish()));
         output.present();
+        self.profiler.end();
 
         Ok(())
     }
-}
 
-impl<'a> ApplicationHandler for App<'a> {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+    fn init_graphics(&mut self, event_loop: &ActiveEventLoop) -> error::Result<()> {
         let window = window::Window::build(
             "Voxel Game",
-            PhysicalSize { width: 720, height: 600 },
+            PhysicalSize { width: self.settings.window.width, height: self.settings.window.height },
+            self.settings.window.fullscreen,
             event_loop,
-        );
-        
+        )?;
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::GL,
             ..Default::default()
         });
         
-        let surface = instance.create_surface(window.core_window.clone()).unwrap();
+        let surface = instance.create_surface(window.core_window.clone())?;
 
         let adapter = pollster::block_on(
             async {
@@ -256,36 +2528,50 @@ impl<'a> ApplicationHandler for App<'a> {
                         compatible_surface: Some(&surface),
                         force_fallback_adapter: false,
                     },
-                ).await.unwrap()
+                ).await
             }
-        );
+        ).ok_or(error::EngineError::NoAdapter)?;
+
+        // Line polygons are only needed for the wireframe debug view,
+        // timestamps for the profiler, and multi-draw only saves draw calls,
+        // so all are optional.
+        let optional_features = adapter.features()
+            & (wgpu::Features::POLYGON_MODE_LINE
+                | wgpu::Features::TIMESTAMP_QUERY
+                | indirect::DrawMode::optional_features());
 
         let (device, queue) = pollster::block_on(
             async {
                 adapter.request_device(
                 &wgpu::DeviceDescriptor {
-                        required_features: wgpu::Features::empty(),
+                        required_features: optional_features,
                         required_limits: wgpu::Limits::default(),
                         label: None,
                         memory_hints: Default::default(),
                     },
                     None
-                ).await.unwrap()
+                ).await
             }
-        );
+        )?;
 
         let surface_caps = surface.get_capabilities(&adapter);
 
         let surface_format = surface_caps.formats.iter()
             .find(|f| f.is_srgb())
+            .or(surface_caps.formats.first())
             .copied()
-            .unwrap_or(surface_caps.formats[0]);
+            .ok_or(error::EngineError::UnsupportedSurface)?;
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Screenshots copy straight out of the surface texture.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: window.size.width,
             height: window.size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: Self::choose_present_mode(
+                &surface_caps.present_modes,
+                self.settings.graphics.vsync,
+            ),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
@@ -293,13 +2579,27 @@ impl<'a> ApplicationHandler for App<'a> {
 
         let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
 
-        let diffuse_bytes = include_bytes!("../assets/happy-tree.png");
-        let diffuse_texture = texture::Texture::from_bytes(
+        let block_textures = block::registry().textures.iter()
+            .map(|(_, bytes)| bytes.as_slice())
+            .collect::<Vec<_>>();
+        let diffuse_texture = texture::Texture::from_layers(
             &device,
             &queue,
-            diffuse_bytes,
-            "happy-tree.png"
-        ).unwrap();
+            &block_textures,
+            "block_textures"
+        )?;
+        let icon_atlas = texture::Texture::from_atlas_tiles(
+            &device,
+            &queue,
+            &block_textures,
+            block::ATLAS_COLUMNS,
+            "block_icon_atlas"
+        )?;
+        let texture_animator = animation::TextureAnimator::new(
+            &block_textures,
+            &block::registry().animations,
+            "block_textures"
+        )?;
 
         let texture_bind_group_layout
             = device.create_bind_group_layout(
@@ -326,9 +2626,11 @@ impl<'a> ApplicationHandler for App<'a> {
                 }
             );
 
+        let block_texture_bind_group_layout = create_block_texture_bind_group_layout(&device);
+
         let diffuse_bind_group = device.create_bind_group(
             &wgpu::BindGroupDescriptor {
-                layout: &texture_bind_group_layout,
+                layout: &block_texture_bind_group_layout,
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
@@ -343,14 +2645,33 @@ impl<'a> ApplicationHandler for App<'a> {
             }
         );
 
+        let icon_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout: &texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&icon_atlas.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&icon_atlas.sampler),
+                    }
+                ],
+                label: Some("icon_bind_group"),
+            }
+        );
+
         let camera = camera::Camera {
-            eye: (0.0, 1.0, 1.3).into(),
-            target: (0.0, 0.0, 0.0).into(),
+            eye: MENU_EYE.into(),
+            yaw: cgmath::Rad(0.0),
+            pitch: cgmath::Rad(MENU_PITCH),
             up: cgmath::Vector3::unit_y(),
             aspect: config.width as f32 / config.height as f32,
-            fov: 70.0,
+            fov: self.settings.graphics.fov,
             near: 0.1,
             far: 1000.0,
+            offset: camera::ViewOffset::default(),
         };
 
         let mut camera_uniform = camera::CameraUniform::new();
@@ -364,124 +2685,237 @@ impl<'a> ApplicationHandler for App<'a> {
             }
         );
 
-        let camera_bind_group_layout = device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor {
+        let camera_bind_group_layout = create_camera_bind_group_layout(&device);
+
+        let camera_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout: &camera_bind_group_layout,
                 entries: &[
-                    wgpu::BindGroupLayoutEntry {
+                    wgpu::BindGroupEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
+                        resource: camera_buffer.as_entire_binding(),
                     }
                 ],
-                label: Some("camera_bind_group_layout"),
+                label: Some("camera_bind_group"),
             }
         );
 
-        let camera_bind_group = device.create_bind_group(
+        // Low-detail terrain pushes the fog out to its edge.
+        let graphics = &self.settings.graphics;
+        let mut frame_uniform = fog::FrameUniform::new(
+            graphics.fog,
+            graphics.render_distance.max(graphics.lod_distance),
+        );
+        frame_uniform.update_camera(&camera);
+
+        let frame_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Frame Buffer"),
+                contents: bytemuck::cast_slice(&[frame_uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let frame_bind_group_layout = create_frame_bind_group_layout(&device);
+
+        let remesh_ages = chunk_debug::RemeshAges::new(&device);
+        let frame_bind_group = device.create_bind_group(
             &wgpu::BindGroupDescriptor {
-                layout: &camera_bind_group_layout,
+                layout: &frame_bind_group_layout,
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: camera_buffer.as_entire_binding(),
-                    }
+                        resource: frame_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&remesh_ages.view),
+                    },
                 ],
-                label: Some("camera_bind_group"),
+                label: Some("frame_bind_group"),
             }
         );
 
-        let camera_controller = camera::CameraController::new(0.2);
+        let camera_controller = camera::CameraController::new(
+            4.3,
+            self.settings.controls.mouse_sensitivity,
+            self.settings.controls.invert_mouse,
+        );
+
+        let scene_format = post::scene_format(&adapter);
+        let sample_counts = post::supported_sample_counts(&adapter, scene_format);
+        let requested_samples = self.settings.graphics.msaa_samples;
+        let sample_count = post::closest_sample_count(&sample_counts, requested_samples);
+        if sample_count != requested_samples.max(1) {
+            warn!("{requested_samples}x MSAA is not supported, using {sample_count}x");
+        }
+
+        let shadow_renderer = shadow::ShadowRenderer::new(
+            &device,
+            &block_texture_bind_group_layout,
+            self.settings.graphics.shadows,
+            self.settings.graphics.shadow_map_size,
+        );
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
                 bind_group_layouts: &[
-                    &texture_bind_group_layout,
+                    &block_texture_bind_group_layout,
                     &camera_bind_group_layout,
+                    &frame_bind_group_layout,
+                    &shadow_renderer.bind_group_layout,
                 ],
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[
-                    Vertex::desc(),
-                ],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
-
-        let vertex_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(VERTICES),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
+        let draw_mode = indirect::DrawMode::detect(&adapter, &device);
+        let gpu_culling = self.settings.graphics.gpu_culling && culling::GpuCuller::is_supported(&adapter);
+        info!("Chunk draw mode: {draw_mode:?}, GPU culling: {gpu_culling}");
+        let gpu_timer = profiler::GpuTimer::new(&device, &queue);
+        self.profiler.gpu_timing = gpu_timer.is_some();
+        let chunk_draws = indirect::ChunkDraws::new(
+            &device,
+            draw_mode,
+            gpu_culling.then(|| culling::GpuCuller::new(&device)),
         );
-        let num_vertices = VERTICES.len() as u32;
 
-        let index_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(INDICES),
-                usage: wgpu::BufferUsages::INDEX,
-            }
-        );
-        let num_indices = INDICES.len() as u32;
+        let ui_renderer = ui::UiRenderer::new(&device, config.format, &texture_bind_group_layout);
+        let text_renderer = text::TextRenderer::new(
+            &device,
+            &queue,
+            config.format,
+            &texture_bind_group_layout,
+        )?;
+        let map_renderer = map::MapRenderer::new(&device, &queue, config.format, &texture_bind_group_layout)?;
 
         self.surface            = Some(surface);
         self.device             = Some(device);
         self.queue              = Some(queue);
         self.config             = Some(config);
-        self.render_pipeline    = Some(render_pipeline);
+        self.present_modes      = surface_caps.present_modes;
+        self.terrain_shader     = Some(shader);
+        self.terrain_pipeline_layout = Some(render_pipeline_layout);
+        self.camera_bind_group_layout = Some(camera_bind_group_layout);
+        self.scene_format       = Some(scene_format);
+        self.sample_counts      = sample_counts;
         self.camera             = Some(camera);
         self.camera_uniform     = Some(camera_uniform);
         self.camera_buffer      = Some(camera_buffer);
         self.camera_bind_group  = Some(camera_bind_group);
+        self.frame_uniform      = Some(frame_uniform);
+        self.frame_buffer       = Some(frame_buffer);
+        self.frame_bind_group   = Some(frame_bind_group);
+        self.remesh_ages        = Some(remesh_ages);
         self.camera_controller  = Some(camera_controller);
-        self.vertex_buffer      = Some(vertex_buffer);
-        self.num_vertices       = Some(num_vertices);
-        self.index_buffer       = Some(index_buffer);
-        self.num_indices        = Some(num_indices);
+        self.shadow_renderer    = Some(shadow_renderer);
+        self.chunk_draws        = Some(chunk_draws);
+        self.gpu_timer          = gpu_timer;
         self.diffuse_bind_group = Some(diffuse_bind_group);
         self.diffuse_texture    = Some(diffuse_texture);
+        self.texture_animator   = Some(texture_animator);
+        self.icon_bind_group    = Some(icon_bind_group);
+        self.icon_atlas         = Some(icon_atlas);
+        self.hotbar             = Some(ui::Hotbar::new());
+        self.ui_renderer        = Some(ui_renderer);
+        self.text_renderer      = Some(text_renderer);
+        self.map_renderer       = Some(map_renderer);
         self.window             = Some(window);
+        self.create_scene_targets(sample_count);
+    
+        Ok(())
+    }
+
+    // Creates the scene render targets and everything that draws into them,
+    // which all depend on the MSAA sample count.
+    fn create_scene_targets(&mut self, sample_count: u32) {
+        let device = self.device.as_ref().unwrap();
+        let config = self.config.as_ref().unwrap();
+        let scene_format = self.scene_format.unwrap();
+        let layout = self.terrain_pipeline_layout.as_ref().unwrap();
+        let shader = self.terrain_shader.as_ref().unwrap();
+
+        let ([render_pipeline, cutout_pipeline, translucent_pipeline], wireframe_pipelines)
+            = create_terrain_pipelines(device, layout, shader, scene_format, sample_count);
+
+        let crack_renderer = crack::CrackRenderer::new(
+            device,
+            scene_format,
+            sample_count,
+            self.camera_bind_group_layout.as_ref().unwrap(),
+        );
+        let highlight_renderer = highlight::HighlightRenderer::new(
+            device,
+            scene_format,
+            sample_count,
+            self.camera_bind_group_layout.as_ref().unwrap(),
+            highlight::Outline::Target,
+        );
+        let selection_renderer = highlight::HighlightRenderer::new(
+            device,
+            scene_format,
+            sample_count,
+            self.camera_bind_group_layout.as_ref().unwrap(),
+            highlight::Outline::Selection,
+        );
+        let chunk_border_renderer = chunk_debug::ChunkBorderRenderer::new(
+            device,
+            scene_format,
+            sample_count,
+            self.camera_bind_group_layout.as_ref().unwrap(),
+        );
+        let border_renderer = border::BorderRenderer::new(
+            device,
+            scene_format,
+            sample_count,
+            self.camera_bind_group_layout.as_ref().unwrap(),
+        );
+        let particle_renderer = particles::ParticleRenderer::new(device, scene_format, sample_count, layout);
+        let sky_renderer = sky::SkyRenderer::new(device, scene_format, sample_count);
+        let post_processor = post::PostProcessor::new(
+            device,
+            scene_format,
+            sample_count,
+            config,
+            &self.settings.graphics.post_process,
+        );
+        let depth_texture = texture::Texture::create_depth_texture(device, config, sample_count, "depth_texture");
+
+        self.render_pipeline    = Some(render_pipeline);
+        self.cutout_pipeline    = Some(cutout_pipeline);
+        self.translucent_pipeline = Some(translucent_pipeline);
+        self.wireframe_pipelines = wireframe_pipelines;
+        self.crack_renderer     = Some(crack_renderer);
+        self.highlight_renderer = Some(highlight_renderer);
+        self.selection_renderer = Some(selection_renderer);
+        self.chunk_border_renderer = Some(chunk_border_renderer);
+        self.border_renderer    = Some(border_renderer);
+        self.particle_renderer  = Some(particle_renderer);
+        self.sky_renderer       = Some(sky_renderer);
+        self.post_processor     = Some(post_processor);
+        self.depth_texture      = Some(depth_texture);
+    }
+}
+
+impl<'a> ApplicationHandler for App<'a> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+
+        if let Err(e) = self.init_graphics(event_loop) {
+            self.error = Some(e);
+            event_loop.exit();
+            return;
+        }
+        // `--connect` goes straight into the game, skipping the menu.
+        if self.server_address.is_some() {
+            if let Err(e) = self.start_game(self.settings.world.clone()) {
+                error!("could not join the server: {e}");
+                self.server_address = None;
+                self.menu.message = Some(e.to_string());
+            }
+        }
     }
 
     fn window_event(
@@ -491,28 +2925,28 @@ impl<'a> ApplicationHandler for App<'a> {
             event: winit::event::WindowEvent,
         ) {
         match event {
-            _ if window_id == self.window.as_ref().unwrap().core_window.id() => if !self.input(&event) {
+            _ if self.window.as_ref().is_some_and(|window| window.core_window.id() == window_id)
+                && !self.input(&event) => {
                 match event {
-                    WindowEvent::CloseRequested | WindowEvent::KeyboardInput {
-                        event:
-                        KeyEvent {
-                            state: ElementState::Pressed,
-                            physical_key: PhysicalKey::Code(KeyCode::Escape),
-                            ..
-                        },
-                        ..
-                    } => {
+                    WindowEvent::CloseRequested => {
                         event_loop.exit();
                     },
                     
                     WindowEvent::Resized(new_size) => {
                         self.resize(new_size);
                     },
+
+                    WindowEvent::Focused(focused) => self.set_focused(focused),
+                    WindowEvent::Occluded(occluded) => self.occluded = occluded,
         
                     WindowEvent::RedrawRequested => {
                         self.window.as_ref().unwrap().core_window.request_redraw();
         
                         self.update();
+                        if self.error.is_some() || self.exit_requested {
+                            event_loop.exit();
+                            return;
+                        }
                         match self.render() {
                             Ok(_) => (),
         
@@ -529,6 +2963,8 @@ impl<'a> ApplicationHandler for App<'a> {
                                 warn!("Surface Timout!");
                             }
                         };
+                        let frame_time = self.frame_time_limit();
+                        self.frame_limiter.wait(frame_time);
                     },
 
                     _ => ()
@@ -539,19 +2975,273 @@ impl<'a> ApplicationHandler for App<'a> {
             _ => (),
         }
     }
+
+    // Raw motion, before the OS applies pointer acceleration, so how far
+    // the view turns depends only on how far the mouse moved.
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _device_id: winit::event::DeviceId, event: winit::event::DeviceEvent) {
+        let winit::event::DeviceEvent::MouseMotion { delta: (dx, dy) } = event else {
+            return;
+        };
+        // Motion arrives whichever window has focus, so it only counts
+        // while ours has the cursor.
+        if self.focused && self.window.as_ref().is_some_and(|window| window.cursor_grabbed) {
+            if let Some(controller) = &mut self.camera_controller {
+                controller.process_mouse(dx as f32, dy as f32);
+            }
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.save_world();
+    }
 }
 
-fn main() {
-    env_logger::init();
+fn block_center([x, y, z]: [i32 ; 3]) -> cgmath::Point3<f32> {
+    cgmath::Point3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5)
+}
+
+// The terrain shader's bind groups: block textures, the camera, and the
+// per-frame uniform with the remesh age texture.
+fn create_block_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(
+        &wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("block_texture_bind_group_layout"),
+        }
+    )
+}
 
-    let event_loop = EventLoop::new().unwrap();
+fn create_camera_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(
+        &wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+            label: Some("camera_bind_group_layout"),
+        }
+    )
+}
 
-    let mut app = App::init();
-    match event_loop.run_app(&mut app) {
-        Ok(_) => (),
-        Err(_) => {
-            error!("could not run app!");
-            exit(1);
+fn create_frame_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(
+        &wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("frame_bind_group_layout"),
         }
+    )
+}
+
+// The filled terrain pipelines for every render layer, and line-mode
+// copies of them if the adapter can draw those.
+fn create_terrain_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> ([wgpu::RenderPipeline ; 3], Option<[wgpu::RenderPipeline ; 3]>) {
+    let layers = [
+        block::RenderLayer::Opaque,
+        block::RenderLayer::Cutout,
+        block::RenderLayer::Translucent,
+    ];
+    let filled = layers.map(|layer| create_terrain_pipeline(
+        device,
+        layout,
+        shader,
+        format,
+        sample_count,
+        layer,
+        wgpu::PolygonMode::Fill,
+    ));
+    let wireframe = device.features().contains(wgpu::Features::POLYGON_MODE_LINE).then(|| {
+        layers.map(|layer| create_terrain_pipeline(
+            device,
+            layout,
+            shader,
+            format,
+            sample_count,
+            layer,
+            wgpu::PolygonMode::Line,
+        ))
+    });
+    (filled, wireframe)
+}
+
+fn create_terrain_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    layer: block::RenderLayer,
+    polygon_mode: wgpu::PolygonMode,
+) -> wgpu::RenderPipeline {
+    let (label, fragment_entry, cull_mode, blend, depth_write_enabled) = match layer {
+        block::RenderLayer::Opaque => {
+            ("Render Pipeline", "fs_main", Some(wgpu::Face::Back), wgpu::BlendState::REPLACE, true)
+        },
+        block::RenderLayer::Cutout => {
+            ("Cutout Pipeline", "fs_cutout", None, wgpu::BlendState::REPLACE, true)
+        },
+        // Fluid surfaces should be visible from below as well, and must not
+        // hide what's behind them in the depth buffer.
+        block::RenderLayer::Translucent => {
+            ("Translucent Pipeline", "fs_main", None, wgpu::BlendState::ALPHA_BLENDING, false)
+        },
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[
+                mesher::Vertex::desc(),
+            ],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(fragment_entry),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode,
+            polygon_mode,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+fn run() -> error::Result<()> {
+    let settings = config::Config::load_or_create(config::CONFIG_PATH);
+    let mut mods = scripting::Mods::load(scripting::MODS_DIR)?;
+    let mut assets = assets::Assets::new(&settings.resources.packs);
+    for dir in mods.dirs() {
+        assets.add_root(dir.clone());
+    }
+    block::load_registry(&assets, mods.take_blocks())?;
+    crafting::load(&assets)?;
+
+    // `--trace <file>` records a Chrome trace alongside any of the others,
+    // written out when the game exits.
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let _trace = match args.iter().position(|arg| arg == "--trace") {
+        Some(i) if i + 1 < args.len() => {
+            let path = args.drain(i..i + 2).nth(1).unwrap();
+            Some(trace::init(std::path::Path::new(&path))?)
+        },
+        Some(_) => usage(),
+        None => None,
+    };
+
+    // `--server [address]` runs headless; `--connect <address>` joins one;
+    // `--bench [seconds]` measures generation, meshing and drawing.
+    let server_address = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["--server"] => return net::run_server(&settings, &format!("0.0.0.0:{}", net::DEFAULT_PORT), mods),
+        ["--server", address] => return net::run_server(&settings, &net::with_default_port(address), mods),
+        ["--connect", address] => Some(address.to_string()),
+        ["--bench"] => return bench::run(&settings, bench::DEFAULT_SECONDS),
+        ["--bench", seconds] => match seconds.parse::<f32>() {
+            Ok(seconds) if seconds > 0.0 => return bench::run(&settings, seconds),
+            _ => usage(),
+        },
+        [] => None,
+        _ => usage(),
+    };
+
+    let event_loop = EventLoop::new()?;
+    let mut app = App::init(settings, &assets, server_address, mods);
+    event_loop.run_app(&mut app)?;
+
+    match app.error.take() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: voxel_game [--server [address] | --connect <address> | --bench [seconds]] [--trace <file>]");
+    exit(2);
+}
+
+fn main() {
+    env_logger::init();
+
+    if let Err(e) = run() {
+        error!("{e}");
+        exit(1);
     }
-}
\ No newline at end of file
+}
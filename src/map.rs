@@ -0,0 +1,516 @@
+//! The map of explored terrain, seen from above. Every column of chunks
+//! the player has had loaded keeps a tile of colors, one per block column,
+//! taken from the block on top and shaded by whether it stands above or
+//! below the column north of it, so slopes show. Only a top block with open
+//! sky over it counts, so caves dug far underground leave the map alone.
+//! Tiles are redrawn as their chunks are remeshed and stay after the chunks
+//! unload. For drawing they're
+//! copied into a texture as they come into view, which the least recently
+//! drawn make way in once it's full.
+//!
+//! The tiles are saved with the world, along with the waypoints the
+//! player marks on the full-screen map, so the map doesn't start over
+//! each time it's loaded.
+
+mod screen;
+
+use std::collections::HashMap;
+
+use cgmath::Point3;
+
+use crate::{
+    block::{self, BlockId, BlockModel},
+    chunk::{ChunkPos, ColumnPos, CHUNK_SIZE},
+    codec::{self, Reader, Writer},
+    error::Result,
+    light::MAX_LIGHT,
+    text::TextRenderer,
+    texture,
+    ui::{Rect, UiBatch, UiRenderer},
+    world::World,
+};
+
+pub use self::screen::MapScreen;
+
+const TILE_SIZE: u32 = CHUNK_SIZE as u32;
+const TILE_AREA: usize = CHUNK_SIZE * CHUNK_SIZE;
+// Tiles along each side of the texture they're drawn from; enough to
+// cover a 1440p screen with the full-screen map zoomed all the way out.
+const ATLAS_TILES: u32 = 128;
+// How much brighter or darker a column is for standing above or below
+// the one north of it.
+const RELIEF_SHADE: f32 = 0.12;
+// Water darkens this much for each block of it over the bottom, down to
+// `MAX_WATER_DEPTH`.
+const WATER_DEPTH_SHADE: f32 = 0.07;
+const MAX_WATER_DEPTH: i32 = 6;
+// Where nothing has been explored.
+const UNEXPLORED_COLOR: [f32 ; 4] = [0.05, 0.05, 0.07, 0.85];
+
+/// How many pixels a block takes on the minimap at each zoom level, from
+/// furthest out.
+pub const MINIMAP_ZOOMS: [f32 ; 3] = [1.0, 2.0, 4.0];
+const MINIMAP_SIZE: f32 = 160.0;
+const MINIMAP_MARGIN: f32 = 8.0;
+const MINIMAP_BORDER: f32 = 2.0;
+const BORDER_COLOR: [f32 ; 4] = [0.0, 0.0, 0.0, 0.8];
+const MARKER_SIZE: f32 = 5.0;
+// Dots in front of the marker, this far apart, show which way the player
+// faces.
+const FACING_DOTS: usize = 3;
+const FACING_SPACING: f32 = 4.0;
+const MARKER_COLOR: [f32 ; 4] = [1.0, 1.0, 1.0, 1.0];
+const NORTH_SIZE: f32 = 14.0;
+const WAYPOINT_SIZE: f32 = 7.0;
+const WAYPOINT_COLOR: [f32 ; 4] = [1.0, 0.8, 0.2, 1.0];
+
+const MAP_MAGIC: &[u8 ; 4] = b"VXMP";
+const MAP_VERSION: u8 = 2;
+
+// A chunk column as the map shows it, each column row by row from the
+// north-west corner.
+#[derive(Clone)]
+struct Tile {
+    colors: [[u8 ; 4] ; TILE_AREA],
+    // Where the top block of each column is; columns with nothing explored
+    // in them are clear and 0.
+    heights: [i32 ; TILE_AREA],
+}
+
+impl Tile {
+    fn new() -> Box<Self> {
+        Box::new(Self { colors: [[0 ; 4] ; TILE_AREA], heights: [0 ; TILE_AREA] })
+    }
+}
+
+/// A named place marked on the map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Waypoint {
+    pub name: String,
+    /// The block it stands on top of.
+    pub block: [i32 ; 3],
+}
+
+/// The tiles of every chunk explored in the current world, and the
+/// waypoints in it.
+pub struct MapCache {
+    tiles: HashMap<ColumnPos, Box<Tile>>,
+    pub waypoints: Vec<Waypoint>,
+    // Each block's color seen from above, by id; blocks that aren't drawn
+    // have none and are looked through.
+    colors: Vec<Option<[u8 ; 3]>>,
+    // Tiles redrawn since the renderer last took them.
+    changed: Vec<ColumnPos>,
+}
+
+impl MapCache {
+    /// Takes each block's color from the average of its top texture, so
+    /// the block registry has to be loaded.
+    pub fn new() -> Self {
+        let registry = block::registry();
+        let colors = registry.blocks.iter().map(|info| {
+            if info.model == BlockModel::None {
+                return None;
+            }
+            let (_, bytes) = registry.textures.get(info.textures.top as usize)?;
+            average_color(&image::load_from_memory(bytes).ok()?.to_rgba8())
+        }).collect();
+        Self { tiles: HashMap::new(), waypoints: Vec::new(), colors, changed: Vec::new() }
+    }
+
+    /// Forgets everything explored, for another world.
+    pub fn clear(&mut self) {
+        self.changed.extend(self.tiles.drain().map(|(pos, _)| pos));
+        self.waypoints.clear();
+    }
+
+    fn tile(&self, pos: ColumnPos) -> Option<&Tile> {
+        self.tiles.get(&pos).map(|tile| &**tile)
+    }
+
+    /// The height of the top block at `x`, `z`, if it's been explored and
+    /// there's anything there.
+    pub fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        let tile = self.tile(ColumnPos::from_block(x, z))?;
+        let index = z.rem_euclid(CHUNK_SIZE as i32) as usize * CHUNK_SIZE + x.rem_euclid(CHUNK_SIZE as i32) as usize;
+        (tile.colors[index][3] > 0).then_some(tile.heights[index])
+    }
+
+    /// Redraws the tile of a column from the chunks of it that are loaded.
+    /// Block columns without a top block under open sky among them keep
+    /// what they had.
+    pub fn update(&mut self, world: &World, pos: ColumnPos) {
+        if world.column(pos).next().is_none() {
+            return;
+        }
+        let (origin_x, origin_z) = pos.origin();
+
+        let mut tile = self.tiles.get(&pos).cloned().unwrap_or_else(Tile::new);
+        // The row north of the one being drawn, for the relief shading.
+        let mut north_row = [None ; CHUNK_SIZE];
+        for (x, surface) in north_row.iter_mut().enumerate() {
+            *surface = self.surface(world, origin_x + x as i32, origin_z - 1).map(|(y, _)| y);
+        }
+        for z in 0..CHUNK_SIZE {
+            for (x, north_y) in north_row.iter_mut().enumerate() {
+                let (world_x, world_z) = (origin_x + x as i32, origin_z + z as i32);
+                let surface = self.surface(world, world_x, world_z);
+                let north_y = std::mem::replace(north_y, surface.map(|(y, _)| y));
+                let Some((y, block)) = surface else {
+                    continue;
+                };
+                let Some(color) = self.colors[block.0 as usize] else {
+                    continue;
+                };
+
+                let shade = if block.is_fluid() {
+                    let depth = (y - MAX_WATER_DEPTH..y).rev()
+                        .take_while(|&below| world.get_block(world_x, below, world_z).is_fluid())
+                        .count();
+                    1.0 - depth as f32 * WATER_DEPTH_SHADE
+                } else {
+                    match north_y {
+                        Some(north_y) if y > north_y => 1.0 + RELIEF_SHADE,
+                        Some(north_y) if y < north_y => 1.0 - RELIEF_SHADE,
+                        _ => 1.0,
+                    }
+                };
+                let [r, g, b] = color.map(|channel| (channel as f32 * shade).round().min(255.0) as u8);
+                tile.colors[z * CHUNK_SIZE + x] = [r, g, b, 255];
+                tile.heights[z * CHUNK_SIZE + x] = y;
+            }
+        }
+
+        self.tiles.insert(pos, tile);
+        self.changed.push(pos);
+    }
+
+    // The height and block of the highest loaded block in a column that
+    // shows on the map, if there's open sky over it in a loaded chunk.
+    fn surface(&self, world: &World, x: i32, z: i32) -> Option<(i32, BlockId)> {
+        let (local_x, local_z) = (x.rem_euclid(CHUNK_SIZE as i32) as usize, z.rem_euclid(CHUNK_SIZE as i32) as usize);
+        let (y, block) = world.column(ColumnPos::from_block(x, z)).find_map(|chunk| {
+            (0..CHUNK_SIZE).rev().find_map(|y| {
+                let block = chunk.get(local_x, y, local_z);
+                self.colors[block.0 as usize].is_some().then_some((chunk.pos.origin()[1] + y as i32, block))
+            })
+        })?;
+        let above_loaded = world.chunks.contains_key(&ChunkPos::from_block(x, y + 1, z));
+        (above_loaded && world.light(x, y + 1, z).sky == MAX_LIGHT).then_some((y, block))
+    }
+
+    /// The columns whose tiles were redrawn since this was last called.
+    pub fn take_changed(&mut self) -> Vec<ColumnPos> {
+        std::mem::take(&mut self.changed)
+    }
+
+    // The tiles and waypoints as they're saved:
+    //
+    //   magic, version: u8, tile count: u32,
+    //   tiles: chunk x: i32, chunk z: i32, colors: 4 bytes a column,
+    //   heights: i32 a column,
+    //   waypoint count: u16, waypoints: name: string, x, y, z: i32
+    //
+    // Numbers are little-endian. Version 1 files have a byte a column for
+    // the heights.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(9 + self.tiles.len() * (8 + TILE_AREA * 8));
+        bytes.extend_from_slice(MAP_MAGIC);
+        bytes.put_u8(MAP_VERSION);
+        bytes.put_u32(self.tiles.len() as u32);
+        for (pos, tile) in &self.tiles {
+            bytes.put_i32(pos.x);
+            bytes.put_i32(pos.z);
+            bytes.extend_from_slice(tile.colors.as_flattened());
+            for height in tile.heights {
+                bytes.put_i32(height);
+            }
+        }
+        bytes.put_u16(self.waypoints.len() as u16);
+        for waypoint in &self.waypoints {
+            bytes.put_string(&waypoint.name);
+            for n in waypoint.block {
+                bytes.put_i32(n);
+            }
+        }
+        bytes
+    }
+
+    /// Adds the tiles `encode` wrote to the ones explored so far, and
+    /// replaces the waypoints.
+    pub fn decode(&mut self, bytes: &[u8]) -> codec::Result<()> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != MAP_MAGIC {
+            return Err("not a map file".to_string());
+        }
+        let version = reader.u8()?;
+        if version == 0 || version > MAP_VERSION {
+            return Err(format!("unsupported map version {version}"));
+        }
+
+        for _ in 0..reader.u32()? {
+            let pos = ColumnPos::new(reader.i32()?, reader.i32()?);
+            let mut tile = Tile::new();
+            tile.colors.as_flattened_mut().copy_from_slice(reader.take(TILE_AREA * 4)?);
+            for height in &mut tile.heights {
+                *height = if version >= 2 { reader.i32()? } else { reader.u8()? as i32 };
+            }
+            self.tiles.insert(pos, tile);
+            self.changed.push(pos);
+        }
+        let count = reader.u16()?;
+        self.waypoints = (0..count)
+            .map(|_| Ok(Waypoint { name: reader.string()?, block: [reader.i32()?, reader.i32()?, reader.i32()?] }))
+            .collect::<codec::Result<_>>()?;
+        Ok(())
+    }
+}
+
+// The average of the mostly opaque pixels of `image`, if it has any.
+fn average_color(image: &image::RgbaImage) -> Option<[u8 ; 3]> {
+    let mut sum = [0u64 ; 3];
+    let mut count = 0;
+    for pixel in image.pixels().filter(|pixel| pixel[3] >= 128) {
+        for (total, &channel) in sum.iter_mut().zip(&pixel.0[..3]) {
+            *total += channel as u64;
+        }
+        count += 1;
+    }
+    (count > 0).then(|| sum.map(|total| (total / count) as u8))
+}
+
+/// Draws map tiles in a batch of its own, under the rest of the UI, from
+/// a texture the tiles are copied into as they're needed.
+pub struct MapRenderer {
+    atlas: texture::Texture,
+    bind_group: wgpu::BindGroup,
+    // Which slot of the atlas each tile is in, and the frame it was last
+    // drawn in.
+    slots: HashMap<ColumnPos, (u32, u64)>,
+    free: Vec<u32>,
+    frame: u64,
+
+    batch: UiBatch,
+    renderer: UiRenderer,
+}
+
+impl MapRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<Self> {
+        let size = ATLAS_TILES * TILE_SIZE;
+        let atlas = texture::Texture::from_image(
+            device,
+            queue,
+            &image::DynamicImage::ImageRgba8(image::RgbaImage::new(size, size)),
+            Some("map_atlas"),
+        )?;
+        let bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout: texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&atlas.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&atlas.sampler),
+                    }
+                ],
+                label: Some("map_bind_group"),
+            }
+        );
+
+        Ok(Self {
+            atlas,
+            bind_group,
+            slots: HashMap::new(),
+            free: (0..ATLAS_TILES * ATLAS_TILES).rev().collect(),
+            frame: 0,
+
+            batch: UiBatch::default(),
+            renderer: UiRenderer::new(device, format, texture_bind_group_layout),
+        })
+    }
+
+    /// Starts the frame's map drawing, dropping the copies of tiles that
+    /// were redrawn so they're copied again.
+    pub fn begin(&mut self, cache: &mut MapCache) {
+        self.batch.clear();
+        self.frame += 1;
+        for pos in cache.take_changed() {
+            if let Some((slot, _)) = self.slots.remove(&pos) {
+                self.free.push(slot);
+            }
+        }
+    }
+
+    /// Draws the explored terrain in `rect`, north up, with the point
+    /// `center` (x and z, in blocks) in the middle and each block `scale`
+    /// pixels across.
+    pub fn draw(&mut self, queue: &wgpu::Queue, cache: &MapCache, rect: Rect, center: [f32 ; 2], scale: f32) {
+        self.batch.quad(rect, UNEXPLORED_COLOR);
+
+        let left = center[0] - rect.w / 2.0 / scale;
+        let top = center[1] - rect.h / 2.0 / scale;
+        let chunk = CHUNK_SIZE as f32;
+        let (min_x, max_x) = ((left / chunk).floor() as i32, ((left + rect.w / scale) / chunk).floor() as i32);
+        let (min_z, max_z) = ((top / chunk).floor() as i32, ((top + rect.h / scale) / chunk).floor() as i32);
+        for z in min_z..=max_z {
+            for x in min_x..=max_x {
+                let pos = ColumnPos::new(x, z);
+                let Some(slot) = self.slot(queue, cache, pos) else {
+                    continue;
+                };
+                let tile = Rect::new(
+                    rect.x + (x as f32 * chunk - left) * scale,
+                    rect.y + (z as f32 * chunk - top) * scale,
+                    chunk * scale,
+                    chunk * scale,
+                );
+                self.tile_quad(tile, rect, slot);
+            }
+        }
+    }
+
+    /// Any more to draw over the map, in the same batch.
+    pub fn quad(&mut self, rect: Rect, color: [f32 ; 4]) {
+        self.batch.quad(rect, color);
+    }
+
+    // Draws the part of `tile` inside `clip` from atlas slot `slot`.
+    fn tile_quad(&mut self, tile: Rect, clip: Rect, slot: u32) {
+        let x0 = tile.x.max(clip.x);
+        let y0 = tile.y.max(clip.y);
+        let x1 = (tile.x + tile.w).min(clip.x + clip.w);
+        let y1 = (tile.y + tile.h).min(clip.y + clip.h);
+        if x1 <= x0 || y1 <= y0 {
+            return;
+        }
+
+        let tile_uv = 1.0 / ATLAS_TILES as f32;
+        let u = (slot % ATLAS_TILES) as f32 * tile_uv;
+        let v = (slot / ATLAS_TILES) as f32 * tile_uv;
+        let uv = |x: f32, y: f32| [u + (x - tile.x) / tile.w * tile_uv, v + (y - tile.y) / tile.h * tile_uv];
+        self.batch.textured_quad(Rect::new(x0, y0, x1 - x0, y1 - y0), uv(x0, y0), uv(x1, y1), [1.0 ; 4]);
+    }
+
+    // The atlas slot holding the tile of `pos`, copying it in if it isn't
+    // there yet. None if there's no tile, or no slot free of tiles drawn
+    // this frame.
+    fn slot(&mut self, queue: &wgpu::Queue, cache: &MapCache, pos: ColumnPos) -> Option<u32> {
+        if let Some((slot, drawn)) = self.slots.get_mut(&pos) {
+            *drawn = self.frame;
+            return Some(*slot);
+        }
+        let tile = cache.tile(pos)?;
+
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None => {
+                let (&oldest, &(slot, drawn)) = self.slots.iter().min_by_key(|(_, (_, drawn))| *drawn)?;
+                if drawn == self.frame {
+                    return None;
+                }
+                self.slots.remove(&oldest);
+                slot
+            },
+        };
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.atlas.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: slot % ATLAS_TILES * TILE_SIZE,
+                    y: slot / ATLAS_TILES * TILE_SIZE,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            tile.colors.as_flattened(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(TILE_SIZE * 4),
+                rows_per_image: Some(TILE_SIZE),
+            },
+            wgpu::Extent3d { width: TILE_SIZE, height: TILE_SIZE, depth_or_array_layers: 1 },
+        );
+        self.slots.insert(pos, (slot, self.frame));
+        Some(slot)
+    }
+
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, screen_size: (u32, u32)) {
+        self.renderer.prepare(device, queue, &self.batch, screen_size);
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        self.renderer.render(render_pass, &self.bind_group);
+    }
+}
+
+/// Draws the minimap in the top right corner: the terrain around `eye`,
+/// north up, at zoom level `zoom` of `MINIMAP_ZOOMS`, with the player in
+/// the middle facing `yaw` and any waypoints in view.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_minimap(
+    map: &mut MapRenderer,
+    queue: &wgpu::Queue,
+    cache: &MapCache,
+    batch: &mut UiBatch,
+    text: &mut TextRenderer,
+    eye: Point3<f32>,
+    yaw: f32,
+    zoom: usize,
+    screen_size: (u32, u32),
+) {
+    let frame = Rect::new(screen_size.0 as f32 - MINIMAP_SIZE - MINIMAP_MARGIN, MINIMAP_MARGIN, MINIMAP_SIZE, MINIMAP_SIZE);
+    let scale = MINIMAP_ZOOMS[zoom.min(MINIMAP_ZOOMS.len() - 1)];
+    map.quad(frame, BORDER_COLOR);
+    let inner = frame.inset(MINIMAP_BORDER);
+    map.draw(queue, cache, inner, [eye.x, eye.z], scale);
+
+    // The map's y runs south, the same way as z.
+    let (cx, cy) = (inner.x + inner.w / 2.0, inner.y + inner.h / 2.0);
+    for waypoint in &cache.waypoints {
+        let [x, _, z] = waypoint.block;
+        let position = [cx + (x as f32 + 0.5 - eye.x) * scale, cy + (z as f32 + 0.5 - eye.z) * scale];
+        if inner.inset(WAYPOINT_SIZE / 2.0).contains(position) {
+            draw_waypoint(batch, position, false);
+        }
+    }
+    draw_player(batch, [cx, cy], yaw);
+
+    let (width, height) = text.measure("N", NORTH_SIZE);
+    let north = Rect::new((cx - width / 2.0).round() - 2.0, inner.y, width + 4.0, height);
+    batch.quad(north, BORDER_COLOR);
+    text.queue("N", [north.x + 2.0, north.y], NORTH_SIZE, MARKER_COLOR);
+}
+
+// The player's marker: a square at `center` with dots leading off the way
+// they're facing.
+fn draw_player(batch: &mut UiBatch, [cx, cy]: [f32 ; 2], yaw: f32) {
+    let half = MARKER_SIZE / 2.0;
+    batch.quad(Rect::new(cx - half - 1.0, cy - half - 1.0, MARKER_SIZE + 2.0, MARKER_SIZE + 2.0), BORDER_COLOR);
+    batch.quad(Rect::new(cx - half, cy - half, MARKER_SIZE, MARKER_SIZE), MARKER_COLOR);
+    let (sin_yaw, cos_yaw) = yaw.sin_cos();
+    for dot in 1..=FACING_DOTS {
+        let distance = half + dot as f32 * FACING_SPACING;
+        let (x, y) = ((cx + cos_yaw * distance).round(), (cy + sin_yaw * distance).round());
+        batch.quad(Rect::new(x - 1.0, y - 1.0, 2.0, 2.0), MARKER_COLOR);
+    }
+}
+
+// A waypoint's marker at `center`, ringed in white while it's selected.
+fn draw_waypoint(batch: &mut UiBatch, [x, y]: [f32 ; 2], selected: bool) {
+    let half = WAYPOINT_SIZE / 2.0;
+    let rect = Rect::new((x - half).round(), (y - half).round(), WAYPOINT_SIZE, WAYPOINT_SIZE);
+    batch.quad(rect.inset(-1.0), BORDER_COLOR);
+    batch.quad(rect, WAYPOINT_COLOR);
+    if selected {
+        batch.outline(rect.inset(-3.0), 2.0, MARKER_COLOR);
+    }
+}
@@ -0,0 +1,380 @@
+use cgmath::Point3;
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use super::{draw_player, draw_waypoint, MapCache, MapRenderer, Waypoint, WAYPOINT_SIZE};
+use crate::{
+    input::Binding,
+    text::TextRenderer,
+    ui::{self, Rect, UiBatch},
+};
+
+// Pixels per block; each notch of the wheel doubles or halves it.
+const MIN_SCALE: f32 = 1.0;
+const MAX_SCALE: f32 = 8.0;
+const DEFAULT_SCALE: f32 = 2.0;
+// How far the mouse can move with the button down and still be a click
+// rather than a drag.
+const CLICK_SLOP: f32 = 4.0;
+// How close to a waypoint's marker a click picks it.
+const PICK_RADIUS: f32 = 8.0;
+
+const LABEL_SIZE: f32 = 16.0;
+const HINT_SIZE: f32 = 16.0;
+const MARGIN: f32 = 12.0;
+const BUTTON_WIDTH: f32 = 140.0;
+const BUTTON_HEIGHT: f32 = 36.0;
+const BUTTON_GAP: f32 = 8.0;
+const FIELD_WIDTH: f32 = 320.0;
+const MAX_NAME_LENGTH: usize = 32;
+
+const LABEL_COLOR: [f32 ; 4] = [1.0, 1.0, 1.0, 1.0];
+const HINT_COLOR: [f32 ; 4] = [0.8, 0.8, 0.8, 1.0];
+const BACKDROP_COLOR: [f32 ; 4] = [0.0, 0.0, 0.0, 0.6];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Button {
+    Teleport,
+    Rename,
+    Delete,
+}
+
+impl Button {
+    fn label(self) -> &'static str {
+        match self {
+            Button::Teleport => "Teleport",
+            Button::Rename => "Rename",
+            Button::Delete => "Delete",
+        }
+    }
+}
+
+// A waypoint name being typed.
+struct Naming {
+    // The waypoint being renamed, or None for a new one at `block`.
+    waypoint: Option<usize>,
+    block: [i32 ; 3],
+    name: String,
+}
+
+/// The full-screen map, opened with its binding (M by default). It shows
+/// everything explored, dragged around with the left button and zoomed
+/// with the wheel, and starts on the player until moved; Space goes back.
+/// Right-clicking explored ground adds a waypoint there and asks for its
+/// name, and clicking a waypoint selects it, to rename, delete or, in
+/// creative, teleport to.
+pub struct MapScreen {
+    pub open: bool,
+    pub toggle: Binding,
+    // The point in the middle of the screen, x and z in blocks.
+    center: [f32 ; 2],
+    scale: f32,
+    // Whether the map stays centred on the player.
+    following: bool,
+    mouse_position: [f32 ; 2],
+    // Where the left button went down, while it's held, and whether it's
+    // since moved far enough to be a drag.
+    press: Option<([f32 ; 2], bool)>,
+    selected: Option<usize>,
+    naming: Option<Naming>,
+    teleport: Option<[f32 ; 3]>,
+    // Hit-testing needs the layout from the frame the user is looking at.
+    screen_size: (u32, u32),
+    creative: bool,
+}
+
+impl MapScreen {
+    pub fn new(toggle: Binding) -> Self {
+        Self {
+            open: false,
+            toggle,
+            center: [0.0, 0.0],
+            scale: DEFAULT_SCALE,
+            following: true,
+            mouse_position: [0.0, 0.0],
+            press: None,
+            selected: None,
+            naming: None,
+            teleport: None,
+            screen_size: (0, 0),
+            creative: false,
+        }
+    }
+
+    /// Closes the screen, dropping any unfinished waypoint name.
+    pub fn close(&mut self) {
+        self.open = false;
+        self.following = true;
+        self.press = None;
+        self.selected = None;
+        self.naming = None;
+    }
+
+    /// Where the player asked to teleport to since this was last called,
+    /// as feet position.
+    pub fn take_teleport(&mut self) -> Option<[f32 ; 3]> {
+        self.teleport.take()
+    }
+
+    pub fn process_events(&mut self, event: &WindowEvent, cache: &mut MapCache) -> bool {
+        // Typing a name takes every key, the binding and Escape included.
+        if self.naming.is_some() {
+            if let WindowEvent::KeyboardInput { event: key_event, .. } = event {
+                self.type_name(key_event, cache);
+                return true;
+            }
+        }
+        if self.toggle.just_pressed(event) {
+            if self.open {
+                self.close();
+            } else {
+                self.open = true;
+            }
+            return true;
+        }
+        if !self.open {
+            return false;
+        }
+
+        match event {
+            WindowEvent::KeyboardInput {
+                event: KeyEvent { state: ElementState::Pressed, physical_key: PhysicalKey::Code(keycode), repeat: false, .. },
+                ..
+            } => match keycode {
+                KeyCode::Escape => self.close(),
+                KeyCode::Space => self.following = true,
+                _ => (),
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = [position.x as f32, position.y as f32];
+                let [dx, dy] = [position[0] - self.mouse_position[0], position[1] - self.mouse_position[1]];
+                self.mouse_position = position;
+                if let Some((start, dragging)) = &mut self.press {
+                    *dragging |= (position[0] - start[0]).abs().max((position[1] - start[1]).abs()) > CLICK_SLOP;
+                    if *dragging {
+                        self.following = false;
+                        self.center[0] -= dx / self.scale;
+                        self.center[1] -= dy / self.scale;
+                    }
+                }
+            },
+            // Clicks wait until the name being typed is done.
+            WindowEvent::MouseInput { .. } if self.naming.is_some() => (),
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => match state {
+                ElementState::Pressed => self.press = Some((self.mouse_position, false)),
+                ElementState::Released => {
+                    if let Some((_, false)) = self.press.take() {
+                        self.click(cache);
+                    }
+                },
+            },
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Right, .. } => {
+                let [x, z] = self.block_at(self.mouse_position).map(|n| n.floor() as i32);
+                if let Some(y) = cache.surface_height(x, z) {
+                    self.naming = Some(Naming { waypoint: None, block: [x, y, z], name: String::new() });
+                }
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let amount = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                };
+                let scale = if amount > 0.0 {
+                    self.scale * 2.0
+                } else if amount < 0.0 {
+                    self.scale / 2.0
+                } else {
+                    self.scale
+                };
+                self.zoom_to(scale.clamp(MIN_SCALE, MAX_SCALE));
+            },
+            _ => (),
+        }
+        // Nothing should reach the player while the screen is open.
+        true
+    }
+
+    fn type_name(&mut self, key_event: &KeyEvent, cache: &mut MapCache) {
+        let Some(naming) = &mut self.naming else {
+            return;
+        };
+        if key_event.state == ElementState::Released {
+            return;
+        }
+        match key_event.physical_key {
+            PhysicalKey::Code(KeyCode::Escape) => self.naming = None,
+            PhysicalKey::Code(KeyCode::Enter | KeyCode::NumpadEnter) => {
+                let naming = self.naming.take().unwrap();
+                let name = match naming.name.trim() {
+                    "" => format!("Waypoint {}", cache.waypoints.len() + 1),
+                    name => name.to_string(),
+                };
+                match naming.waypoint {
+                    Some(index) => cache.waypoints[index].name = name,
+                    None => {
+                        cache.waypoints.push(Waypoint { name, block: naming.block });
+                        self.selected = Some(cache.waypoints.len() - 1);
+                    },
+                }
+            },
+            PhysicalKey::Code(KeyCode::Backspace) => {
+                naming.name.pop();
+            },
+            _ => {
+                if let Some(text) = &key_event.text {
+                    naming.name.extend(text.chars().filter(|c| !c.is_control()));
+                    naming.name = naming.name.chars().take(MAX_NAME_LENGTH).collect();
+                }
+            },
+        }
+    }
+
+    // Keeps the block under the mouse where it is while the scale changes.
+    fn zoom_to(&mut self, scale: f32) {
+        if scale == self.scale {
+            return;
+        }
+        let [x, z] = self.block_at(self.mouse_position);
+        self.scale = scale;
+        let [new_x, new_z] = self.block_at(self.mouse_position);
+        self.center[0] += x - new_x;
+        self.center[1] += z - new_z;
+        self.following = false;
+    }
+
+    // A click that didn't drag: a button of the selected waypoint, another
+    // waypoint, or nothing, which deselects.
+    fn click(&mut self, cache: &mut MapCache) {
+        let Some(index) = self.selected else {
+            self.selected = self.waypoint_at(cache, self.mouse_position);
+            return;
+        };
+        let button = self.buttons().into_iter().find(|(_, rect)| rect.contains(self.mouse_position));
+        match button.map(|(button, _)| button) {
+            Some(Button::Teleport) => {
+                let [x, y, z] = cache.waypoints[index].block;
+                self.teleport = Some([x as f32 + 0.5, (y + 1) as f32, z as f32 + 0.5]);
+                self.close();
+            },
+            Some(Button::Rename) => {
+                let waypoint = &cache.waypoints[index];
+                self.naming = Some(Naming { waypoint: Some(index), block: waypoint.block, name: waypoint.name.clone() });
+            },
+            Some(Button::Delete) => {
+                cache.waypoints.remove(index);
+                self.selected = None;
+            },
+            None => self.selected = self.waypoint_at(cache, self.mouse_position),
+        }
+    }
+
+    // The x and z, in blocks, of the point at `position` on the screen.
+    fn block_at(&self, [x, y]: [f32 ; 2]) -> [f32 ; 2] {
+        let (width, height) = (self.screen_size.0 as f32, self.screen_size.1 as f32);
+        [self.center[0] + (x - width / 2.0) / self.scale, self.center[1] + (y - height / 2.0) / self.scale]
+    }
+
+    // Where the point `x`, `z`, in blocks, is on the screen.
+    fn screen_position(&self, [x, z]: [f32 ; 2]) -> [f32 ; 2] {
+        let (width, height) = (self.screen_size.0 as f32, self.screen_size.1 as f32);
+        [width / 2.0 + (x - self.center[0]) * self.scale, height / 2.0 + (z - self.center[1]) * self.scale]
+    }
+
+    // The nearest waypoint to `position` close enough to pick.
+    fn waypoint_at(&self, cache: &MapCache, [x, y]: [f32 ; 2]) -> Option<usize> {
+        cache.waypoints.iter()
+            .enumerate()
+            .map(|(index, waypoint)| {
+                let [wx, wy] = self.screen_position(block_middle(waypoint));
+                (index, (wx - x).hypot(wy - y))
+            })
+            .filter(|(_, distance)| *distance <= PICK_RADIUS)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    // The selected waypoint's buttons, in a row along the bottom.
+    fn buttons(&self) -> Vec<(Button, Rect)> {
+        let mut buttons = vec![Button::Rename, Button::Delete];
+        if self.creative {
+            buttons.insert(0, Button::Teleport);
+        }
+        let (width, height) = (self.screen_size.0 as f32, self.screen_size.1 as f32);
+        let row = buttons.len() as f32 * (BUTTON_WIDTH + BUTTON_GAP) - BUTTON_GAP;
+        let left = ((width - row) / 2.0).round();
+        let top = height - MARGIN * 3.0 - BUTTON_HEIGHT - HINT_SIZE;
+        buttons.into_iter()
+            .enumerate()
+            .map(|(i, button)| (button, Rect::new(left + i as f32 * (BUTTON_WIDTH + BUTTON_GAP), top, BUTTON_WIDTH, BUTTON_HEIGHT)))
+            .collect()
+    }
+
+    /// Draws the screen over everything: the map, its waypoints, the
+    /// player at `eye` facing `yaw`, and what's under the mouse. `creative`
+    /// offers teleporting to waypoints.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        map: &mut MapRenderer,
+        queue: &wgpu::Queue,
+        cache: &MapCache,
+        batch: &mut UiBatch,
+        text: &mut TextRenderer,
+        eye: Point3<f32>,
+        yaw: f32,
+        creative: bool,
+        screen_size: (u32, u32),
+    ) {
+        self.screen_size = screen_size;
+        self.creative = creative;
+        if self.following {
+            self.center = [eye.x, eye.z];
+        }
+        // Waypoints can go with a world another screen just left.
+        self.selected = self.selected.filter(|&index| index < cache.waypoints.len());
+
+        let (width, height) = (screen_size.0 as f32, screen_size.1 as f32);
+        map.draw(queue, cache, Rect::new(0.0, 0.0, width, height), self.center, self.scale);
+
+        for (index, waypoint) in cache.waypoints.iter().enumerate() {
+            let [sx, sy] = self.screen_position(block_middle(waypoint));
+            draw_waypoint(batch, [sx, sy], self.selected == Some(index));
+            let (label_width, _) = text.measure(&waypoint.name, LABEL_SIZE);
+            text.queue(&waypoint.name, [(sx - label_width / 2.0).round(), sy + WAYPOINT_SIZE], LABEL_SIZE, LABEL_COLOR);
+        }
+        draw_player(batch, self.screen_position([eye.x, eye.z]), yaw);
+
+        // What's under the mouse, top left.
+        let [x, z] = self.block_at(self.mouse_position).map(|n| n.floor() as i32);
+        let position = match cache.surface_height(x, z) {
+            Some(y) => format!("{x}, {y}, {z}"),
+            None => format!("{x}, ?, {z}"),
+        };
+        let (position_width, position_height) = text.measure(&position, LABEL_SIZE);
+        batch.quad(Rect::new(MARGIN - 4.0, MARGIN - 2.0, position_width + 8.0, position_height + 4.0), BACKDROP_COLOR);
+        text.queue(position, [MARGIN, MARGIN], LABEL_SIZE, LABEL_COLOR);
+
+        let hint = "Drag to move, scroll to zoom, Space to find yourself, right-click to add a waypoint";
+        let (hint_width, hint_height) = text.measure(hint, HINT_SIZE);
+        let hint_position = [((width - hint_width) / 2.0).round(), height - MARGIN - hint_height];
+        batch.quad(Rect::new(hint_position[0] - 4.0, hint_position[1] - 2.0, hint_width + 8.0, hint_height + 4.0), BACKDROP_COLOR);
+        text.queue(hint, hint_position, HINT_SIZE, HINT_COLOR);
+
+        if let Some(naming) = &self.naming {
+            let field = Rect::new(((width - FIELD_WIDTH) / 2.0).round(), (height / 2.0).round(), FIELD_WIDTH, BUTTON_HEIGHT);
+            batch.quad(field.inset(-MARGIN), BACKDROP_COLOR);
+            ui::draw_text_field(batch, text, field, &naming.name, "Waypoint name", true);
+        } else if self.selected.is_some() {
+            for (button, rect) in self.buttons() {
+                ui::draw_button(batch, text, rect, button.label(), rect.contains(self.mouse_position));
+            }
+        }
+    }
+}
+
+// The middle of the top of a waypoint's block, x and z.
+fn block_middle(waypoint: &Waypoint) -> [f32 ; 2] {
+    [waypoint.block[0] as f32 + 0.5, waypoint.block[2] as f32 + 0.5]
+}
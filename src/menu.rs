@@ -0,0 +1,718 @@
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::{
+    camera::CameraEffect,
+    config::Config,
+    game_mode::GameMode,
+    input::{Action, Binding},
+    save::{self, WorldSummary},
+    text::TextRenderer,
+    ui::{self, HudElement, Rect, UiBatch},
+    worldgen::WorldType,
+};
+
+const BUTTON_WIDTH: f32 = 360.0;
+const BUTTON_HEIGHT: f32 = 40.0;
+const BUTTON_GAP: f32 = 10.0;
+// World list rows are wider to fit the world's details, with backup and
+// delete buttons beside each.
+const WORLD_BUTTON_WIDTH: f32 = 480.0;
+const DELETE_BUTTON_WIDTH: f32 = 90.0;
+const BACKUPS_BUTTON_WIDTH: f32 = 100.0;
+// The - and + buttons either side of a setting.
+const STEP_BUTTON_WIDTH: f32 = 40.0;
+const TITLE_SIZE: f32 = 48.0;
+const HEADING_SIZE: f32 = 28.0;
+const MESSAGE_SIZE: f32 = 18.0;
+// Worlds and bindings shown at once in their lists; the mouse wheel
+// scrolls the rest.
+const VISIBLE_WORLDS: usize = 6;
+const VISIBLE_BINDINGS: usize = 8;
+const MAX_WORLD_NAME: usize = 32;
+const MAX_SEED_LENGTH: usize = 32;
+
+/// What the game is showing. Only `Playing` sends input to the world;
+/// everything else is a menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameState {
+    MainMenu,
+    Playing,
+    Paused,
+    /// `in_game` says whether closing it returns to the pause menu or the
+    /// main menu.
+    Settings { in_game: bool },
+    /// The player died and is waiting to respawn.
+    Dead,
+}
+
+/// A setting on the options screen that's stepped up and down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    RenderDistance,
+    Fov,
+    Sensitivity,
+    FlySpeed,
+    Volume,
+    Msaa,
+    Particles,
+}
+
+/// Something the menu needs the game to do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MenuAction {
+    Play(String),
+    Create { name: String, seed: u32, world_type: WorldType, game_mode: GameMode },
+    Resume,
+    Respawn,
+    OpenSettings,
+    CloseSettings,
+    QuitToTitle,
+    Quit,
+    ToggleVsync,
+    CycleFrameLimit,
+    ToggleFullscreen,
+    ToggleGamepad,
+    ToggleInvertMouse,
+    ToggleSmoothLighting,
+    ToggleCameraEffect(CameraEffect),
+    ToggleHudElement(HudElement),
+    /// Steps a setting by one notch up (1) or down (-1).
+    Adjust(Setting, i32),
+    Rebind(Action, Binding),
+    ResetBindings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Page {
+    Title,
+    Worlds,
+    CreateWorld,
+    // Asks before deleting `Menu::deleting`.
+    ConfirmDelete,
+    // The backups of `Menu::backing_up`, and asking before restoring
+    // `Menu::restoring`.
+    Backups,
+    ConfirmRestore,
+    // Reached from the options screen rather than the title.
+    Controls,
+    CameraEffects,
+    Hud,
+}
+
+#[derive(Clone, Debug)]
+enum Click {
+    Action(MenuAction),
+    Page(Page),
+    Focus(usize),
+    CycleWorldType,
+    CycleGameMode,
+    Create,
+    AskDelete(String),
+    Delete,
+    ShowBackups(String),
+    AskRestore(save::Backup),
+    Restore,
+    // Waits for the next key or mouse button to bind to the action.
+    Rebind(Action),
+    // Not clickable; drawn as a button without hover.
+    Label,
+}
+
+// One widget in a row of the layout. Focus clicks are drawn as the text
+// field they focus.
+struct Item {
+    label: String,
+    detail: Option<String>,
+    click: Click,
+    width: f32,
+}
+
+impl Item {
+    fn new(label: impl Into<String>, click: Click) -> Self {
+        Self { label: label.into(), detail: None, click, width: BUTTON_WIDTH }
+    }
+
+    // A row that steps `setting` with buttons either side of its value.
+    fn stepper(label: String, setting: Setting) -> Vec<Self> {
+        vec![
+            Item { width: STEP_BUTTON_WIDTH, ..Item::new("-", Click::Action(MenuAction::Adjust(setting, -1))) },
+            Item { width: BUTTON_WIDTH - 2.0 * (STEP_BUTTON_WIDTH + BUTTON_GAP), ..Item::new(label, Click::Label) },
+            Item { width: STEP_BUTTON_WIDTH, ..Item::new("+", Click::Action(MenuAction::Adjust(setting, 1))) },
+        ]
+    }
+}
+
+struct Button {
+    rect: Rect,
+    click: Click,
+}
+
+/// The main menu, pause menu and settings screen. Each frame's `draw` lays
+/// out the buttons; clicks are hit-tested against that layout.
+pub struct Menu {
+    page: Page,
+    worlds: Vec<WorldSummary>,
+    scroll: usize,
+    // The create-world screen's name and seed fields.
+    fields: [String ; 2],
+    focused: usize,
+    world_type: WorldType,
+    game_mode: GameMode,
+    deleting: Option<String>,
+    backing_up: Option<String>,
+    backups: Vec<save::Backup>,
+    restoring: Option<save::Backup>,
+    rebinding: Option<Action>,
+    /// Shown under the title, such as why a world failed to load.
+    pub message: Option<String>,
+    buttons: Vec<Button>,
+    mouse_position: [f32 ; 2],
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        Self {
+            page: Page::Title,
+            worlds: Vec::new(),
+            scroll: 0,
+            fields: [String::new(), String::new()],
+            focused: 0,
+            world_type: WorldType::Normal,
+            game_mode: GameMode::Survival,
+            deleting: None,
+            backing_up: None,
+            backups: Vec::new(),
+            restoring: None,
+            rebinding: None,
+            message: None,
+            buttons: Vec::new(),
+            mouse_position: [0.0, 0.0],
+        }
+    }
+
+    /// Goes back to the title page of the main menu.
+    pub fn reset(&mut self) {
+        self.show(Page::Title);
+    }
+
+    fn show(&mut self, page: Page) {
+        self.page = page;
+        self.scroll = 0;
+        self.rebinding = None;
+        if page == Page::Worlds {
+            self.worlds = save::list_worlds();
+        }
+        if page == Page::Backups {
+            self.backups = self.backing_up.as_deref().map_or_else(Vec::new, |name| save::WorldSave::new(name).backups());
+        }
+        if page == Page::CreateWorld {
+            self.fields = [unused_world_name(), String::new()];
+            self.focused = 0;
+            self.world_type = WorldType::Normal;
+            self.game_mode = GameMode::Survival;
+        }
+    }
+
+    /// Takes every event while a menu is up, and returns what was chosen,
+    /// if anything.
+    pub fn process_events(&mut self, state: GameState, event: &WindowEvent) -> Option<MenuAction> {
+        if let Some(action) = self.rebinding {
+            return match Binding::from_event(event) {
+                // Escape can't be bound, since it's needed to leave menus.
+                Some((Binding::Key(KeyCode::Escape), true, _)) => {
+                    self.rebinding = None;
+                    None
+                },
+                Some((binding, true, false)) => {
+                    self.rebinding = None;
+                    Some(MenuAction::Rebind(action, binding))
+                },
+                _ => None,
+            };
+        }
+
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.mouse_position = [position.x as f32, position.y as f32];
+                None
+            },
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+                let click = self.buttons.iter()
+                    .find(|button| button.rect.contains(self.mouse_position))
+                    .map(|button| button.click.clone())?;
+                self.click(click)
+            },
+            WindowEvent::MouseWheel { delta, .. } if matches!(self.page, Page::Worlds | Page::Controls) => {
+                let amount = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                };
+                let max_scroll = match self.page {
+                    Page::Controls => Action::ALL.len().saturating_sub(VISIBLE_BINDINGS),
+                    _ => self.worlds.len().saturating_sub(VISIBLE_WORLDS),
+                };
+                if amount < 0.0 {
+                    self.scroll = (self.scroll + 1).min(max_scroll);
+                } else if amount > 0.0 {
+                    self.scroll = self.scroll.saturating_sub(1);
+                }
+                None
+            },
+            WindowEvent::KeyboardInput {
+                event: KeyEvent { state: ElementState::Pressed, physical_key, text, .. },
+                ..
+            } => self.key(state, *physical_key, text.as_deref()),
+            _ => None,
+        }
+    }
+
+    fn key(&mut self, state: GameState, key: PhysicalKey, text: Option<&str>) -> Option<MenuAction> {
+        let escape = key == PhysicalKey::Code(KeyCode::Escape);
+        match state {
+            GameState::Paused if escape => return Some(MenuAction::Resume),
+            GameState::Settings { .. } if escape && matches!(self.page, Page::Controls | Page::CameraEffects | Page::Hud) => {
+                return self.click(Click::Page(Page::Title));
+            },
+            GameState::Settings { .. } if escape => return Some(MenuAction::CloseSettings),
+            GameState::MainMenu => (),
+            _ => return None,
+        }
+
+        match self.page {
+            Page::Title | Page::Controls | Page::CameraEffects | Page::Hud => None,
+            Page::Worlds => {
+                if escape {
+                    self.click(Click::Page(Page::Title));
+                }
+                None
+            },
+            Page::ConfirmDelete | Page::Backups => {
+                if escape {
+                    self.click(Click::Page(Page::Worlds));
+                }
+                None
+            },
+            Page::ConfirmRestore => {
+                if escape {
+                    self.click(Click::Page(Page::Backups));
+                }
+                None
+            },
+            Page::CreateWorld => match key {
+                PhysicalKey::Code(KeyCode::Escape) => self.click(Click::Page(Page::Worlds)),
+                PhysicalKey::Code(KeyCode::Enter | KeyCode::NumpadEnter) => self.click(Click::Create),
+                PhysicalKey::Code(KeyCode::Tab) => {
+                    self.focused = (self.focused + 1) % self.fields.len();
+                    None
+                },
+                PhysicalKey::Code(KeyCode::Backspace) => {
+                    self.fields[self.focused].pop();
+                    None
+                },
+                _ => {
+                    let limit = if self.focused == 0 { MAX_WORLD_NAME } else { MAX_SEED_LENGTH };
+                    let field = &mut self.fields[self.focused];
+                    for c in text.unwrap_or_default().chars().filter(|c| !c.is_control()) {
+                        if field.chars().count() < limit {
+                            field.push(c);
+                        }
+                    }
+                    None
+                },
+            },
+        }
+    }
+
+    fn click(&mut self, click: Click) -> Option<MenuAction> {
+        match click {
+            Click::Action(action) => Some(action),
+            Click::Page(page) => {
+                self.message = None;
+                self.show(page);
+                None
+            },
+            Click::Focus(field) => {
+                self.focused = field;
+                None
+            },
+            Click::CycleWorldType => {
+                let index = WorldType::ALL.iter().position(|&world_type| world_type == self.world_type).unwrap_or(0);
+                self.world_type = WorldType::ALL[(index + 1) % WorldType::ALL.len()];
+                None
+            },
+            Click::CycleGameMode => {
+                let index = GameMode::ALL.iter().position(|&game_mode| game_mode == self.game_mode).unwrap_or(0);
+                self.game_mode = GameMode::ALL[(index + 1) % GameMode::ALL.len()];
+                None
+            },
+            Click::Create => {
+                let name = self.fields[0].trim().to_string();
+                if let Err(reason) = check_world_name(&name) {
+                    self.message = Some(reason);
+                    return None;
+                }
+                if save::WorldSave::new(&name).exists() {
+                    self.message = Some(format!("a world named {name} already exists"));
+                    return None;
+                }
+                self.message = None;
+                Some(MenuAction::Create {
+                    name,
+                    seed: parse_seed(&self.fields[1]),
+                    world_type: self.world_type,
+                    game_mode: self.game_mode,
+                })
+            },
+            Click::AskDelete(name) => {
+                self.message = None;
+                self.deleting = Some(name);
+                self.show(Page::ConfirmDelete);
+                None
+            },
+            Click::Delete => {
+                if let Some(name) = self.deleting.take() {
+                    self.message = save::WorldSave::new(&name).delete().err().map(|e| e.to_string());
+                }
+                self.show(Page::Worlds);
+                None
+            },
+            Click::ShowBackups(name) => {
+                self.message = None;
+                self.backing_up = Some(name);
+                self.show(Page::Backups);
+                None
+            },
+            Click::AskRestore(backup) => {
+                self.message = None;
+                self.restoring = Some(backup);
+                self.show(Page::ConfirmRestore);
+                None
+            },
+            Click::Restore => {
+                if let (Some(name), Some(backup)) = (&self.backing_up, self.restoring.take()) {
+                    self.message = match save::WorldSave::new(name).restore(&backup) {
+                        Ok(()) => Some(format!("Restored {name} from its backup")),
+                        Err(e) => Some(e.to_string()),
+                    };
+                }
+                self.show(Page::Backups);
+                None
+            },
+            Click::Rebind(action) => {
+                self.rebinding = Some(action);
+                None
+            },
+            Click::Label => None,
+        }
+    }
+
+    pub fn draw(&mut self, state: GameState, batch: &mut UiBatch, text: &mut TextRenderer, screen_size: (u32, u32), settings: &Config, online: bool) {
+        // Shown in grey where the message goes, when there isn't one.
+        let mut note = None;
+        let (heading, heading_size, rows) = match state {
+            GameState::MainMenu => match self.page {
+                Page::Title | Page::Controls | Page::CameraEffects | Page::Hud => ("Voxel Game".to_string(), TITLE_SIZE, vec![
+                    vec![Item::new("Singleplayer", Click::Page(Page::Worlds))],
+                    vec![Item::new("Options", Click::Action(MenuAction::OpenSettings))],
+                    vec![Item::new("Quit", Click::Action(MenuAction::Quit))],
+                ]),
+                Page::Worlds => {
+                    if self.worlds.is_empty() {
+                        note = Some("No worlds yet".to_string());
+                    }
+                    let now = save::timestamp();
+                    let mut rows = self.worlds.iter()
+                        .skip(self.scroll)
+                        .take(VISIBLE_WORLDS)
+                        .map(|world| {
+                            let world_type = world.level.world_type.name();
+                            let detail = match last_played(world.level.last_played, now) {
+                                Some(played) => format!("{world_type}, {played}"),
+                                None => world_type.to_string(),
+                            };
+                            vec![
+                                Item {
+                                    detail: Some(detail),
+                                    width: WORLD_BUTTON_WIDTH,
+                                    ..Item::new(world.name.clone(), Click::Action(MenuAction::Play(world.name.clone())))
+                                },
+                                Item {
+                                    width: BACKUPS_BUTTON_WIDTH,
+                                    ..Item::new("Backups", Click::ShowBackups(world.name.clone()))
+                                },
+                                Item {
+                                    width: DELETE_BUTTON_WIDTH,
+                                    ..Item::new("Delete", Click::AskDelete(world.name.clone()))
+                                },
+                            ]
+                        })
+                        .collect::<Vec<_>>();
+                    rows.push(vec![Item::new("Create New World", Click::Page(Page::CreateWorld))]);
+                    rows.push(vec![Item::new("Back", Click::Page(Page::Title))]);
+                    ("Select World".to_string(), HEADING_SIZE, rows)
+                },
+                Page::CreateWorld => ("Create World".to_string(), HEADING_SIZE, vec![
+                    vec![Item::new("", Click::Focus(0))],
+                    vec![Item::new("", Click::Focus(1))],
+                    vec![Item::new(format!("World Type: {}", self.world_type.name()), Click::CycleWorldType)],
+                    vec![Item::new(format!("Game Mode: {}", self.game_mode.name()), Click::CycleGameMode)],
+                    vec![Item::new("Create", Click::Create)],
+                    vec![Item::new("Cancel", Click::Page(Page::Worlds))],
+                ]),
+                Page::ConfirmDelete => {
+                    note = Some("It will be lost forever!".to_string());
+                    let half = (BUTTON_WIDTH - BUTTON_GAP) / 2.0;
+                    (format!("Delete {}?", self.deleting.as_deref().unwrap_or_default()), HEADING_SIZE, vec![vec![
+                        Item { width: half, ..Item::new("Delete", Click::Delete) },
+                        Item { width: half, ..Item::new("Cancel", Click::Page(Page::Worlds)) },
+                    ]])
+                },
+                Page::Backups => {
+                    if self.backups.is_empty() {
+                        note = Some("No backups yet; take one with /backup".to_string());
+                    }
+                    let now = save::timestamp();
+                    let mut rows = self.backups.iter()
+                        .map(|backup| vec![Item {
+                            detail: Some(format!("{} KB", backup.size.div_ceil(1024))),
+                            width: WORLD_BUTTON_WIDTH,
+                            ..Item::new(
+                                last_played(backup.created, now).unwrap_or_else(|| backup.file_name().to_string()),
+                                Click::AskRestore(backup.clone()),
+                            )
+                        }])
+                        .collect::<Vec<_>>();
+                    rows.push(vec![Item::new("Back", Click::Page(Page::Worlds))]);
+                    (format!("Backups of {}", self.backing_up.as_deref().unwrap_or_default()), HEADING_SIZE, rows)
+                },
+                Page::ConfirmRestore => {
+                    note = Some("Anything since the backup will be lost!".to_string());
+                    let half = (BUTTON_WIDTH - BUTTON_GAP) / 2.0;
+                    (format!("Restore {}?", self.backing_up.as_deref().unwrap_or_default()), HEADING_SIZE, vec![vec![
+                        Item { width: half, ..Item::new("Restore", Click::Restore) },
+                        Item { width: half, ..Item::new("Cancel", Click::Page(Page::Backups)) },
+                    ]])
+                },
+            },
+            GameState::Paused => ("Game Paused".to_string(), HEADING_SIZE, vec![
+                vec![Item::new("Back to Game", Click::Action(MenuAction::Resume))],
+                vec![Item::new("Options", Click::Action(MenuAction::OpenSettings))],
+                vec![Item::new(
+                    if online { "Disconnect" } else { "Save and Quit to Title" },
+                    Click::Action(MenuAction::QuitToTitle),
+                )],
+            ]),
+            GameState::Dead => ("You Died!".to_string(), TITLE_SIZE, vec![
+                vec![Item::new("Respawn", Click::Action(MenuAction::Respawn))],
+                vec![Item::new(
+                    if online { "Disconnect" } else { "Save and Quit to Title" },
+                    Click::Action(MenuAction::QuitToTitle),
+                )],
+            ]),
+            GameState::Settings { .. } if self.page == Page::Controls => {
+                note = Some(match self.rebinding {
+                    Some(action) => format!("Press a key or mouse button for {}, or Escape to cancel", action.name()),
+                    None => "Click an action to change its binding".to_string(),
+                });
+                let mut rows = Action::ALL.iter()
+                    .skip(self.scroll)
+                    .take(VISIBLE_BINDINGS)
+                    .map(|&action| {
+                        let detail = match self.rebinding {
+                            Some(rebinding) if rebinding == action => "...".to_string(),
+                            _ => settings.controls.binding(action).to_string(),
+                        };
+                        vec![Item { detail: Some(detail), ..Item::new(action.name(), Click::Rebind(action)) }]
+                    })
+                    .collect::<Vec<_>>();
+                rows.push(vec![Item::new(
+                    format!("Invert Mouse: {}", on_off(settings.controls.invert_mouse)),
+                    Click::Action(MenuAction::ToggleInvertMouse),
+                )]);
+                rows.push(Item::stepper(format!("Fly Speed: {}", settings.controls.fly_speed.round()), Setting::FlySpeed));
+                rows.push(vec![Item::new("Reset to Defaults", Click::Action(MenuAction::ResetBindings))]);
+                rows.push(vec![Item::new("Done", Click::Page(Page::Title))]);
+                ("Controls".to_string(), HEADING_SIZE, rows)
+            },
+            GameState::Settings { .. } if self.page == Page::CameraEffects => {
+                let mut rows = CameraEffect::ALL.iter()
+                    .map(|&effect| vec![Item::new(
+                        format!("{}: {}", effect.name(), on_off(settings.graphics.camera_effects.enabled(effect))),
+                        Click::Action(MenuAction::ToggleCameraEffect(effect)),
+                    )])
+                    .collect::<Vec<_>>();
+                rows.push(vec![Item::new("Done", Click::Page(Page::Title))]);
+                ("Camera Effects".to_string(), HEADING_SIZE, rows)
+            },
+            GameState::Settings { .. } if self.page == Page::Hud => {
+                let mut rows = HudElement::ALL.iter()
+                    .map(|&element| vec![Item::new(
+                        format!("{}: {}", element.name(), on_off(settings.hud.enabled(element))),
+                        Click::Action(MenuAction::ToggleHudElement(element)),
+                    )])
+                    .collect::<Vec<_>>();
+                rows.push(vec![Item::new("Done", Click::Page(Page::Title))]);
+                ("HUD".to_string(), HEADING_SIZE, rows)
+            },
+            GameState::Settings { .. } => {
+                // Short toggles share a row so the list fits a 720 pixel
+                // high window.
+                let half = (BUTTON_WIDTH - BUTTON_GAP) / 2.0;
+                ("Options".to_string(), HEADING_SIZE, vec![
+                    Item::stepper(format!("Render Distance: {}", settings.graphics.render_distance), Setting::RenderDistance),
+                    Item::stepper(format!("FOV: {}", settings.graphics.fov.round()), Setting::Fov),
+                    Item::stepper(format!("Sensitivity: {}%", percent(settings.controls.mouse_sensitivity)), Setting::Sensitivity),
+                    Item::stepper(format!("Volume: {}%", percent(settings.audio.master_volume)), Setting::Volume),
+                    Item::stepper(match settings.graphics.msaa_samples {
+                        0 | 1 => "MSAA: Off".to_string(),
+                        samples => format!("MSAA: {samples}x"),
+                    }, Setting::Msaa),
+                    Item::stepper(match settings.graphics.max_particles {
+                        0 => "Particles: Off".to_string(),
+                        budget => format!("Particles: {budget}"),
+                    }, Setting::Particles),
+                    vec![Item::new(
+                        format!("Smooth Lighting: {}", on_off(settings.graphics.smooth_lighting)),
+                        Click::Action(MenuAction::ToggleSmoothLighting),
+                    )],
+                    vec![
+                        Item {
+                            width: half,
+                            ..Item::new(format!("VSync: {}", on_off(settings.graphics.vsync)), Click::Action(MenuAction::ToggleVsync))
+                        },
+                        Item {
+                            width: half,
+                            ..Item::new(
+                                format!("FPS: {}", settings.graphics.frame_limit.name()),
+                                Click::Action(MenuAction::CycleFrameLimit),
+                            )
+                        },
+                    ],
+                    vec![Item::new(format!("Fullscreen: {:?}", settings.window.fullscreen), Click::Action(MenuAction::ToggleFullscreen))],
+                    vec![
+                        Item { width: half, ..Item::new("Controls", Click::Page(Page::Controls)) },
+                        Item { width: half, ..Item::new("Camera Effects", Click::Page(Page::CameraEffects)) },
+                    ],
+                    vec![
+                        Item {
+                            width: half,
+                            ..Item::new(
+                                format!("Controller: {}", on_off(settings.controls.gamepad.enabled)),
+                                Click::Action(MenuAction::ToggleGamepad),
+                            )
+                        },
+                        Item { width: half, ..Item::new("HUD", Click::Page(Page::Hud)) },
+                    ],
+                    vec![Item::new("Done", Click::Action(MenuAction::CloseSettings))],
+                ])
+            },
+            GameState::Playing => return,
+        };
+
+        let (width, height) = (screen_size.0 as f32, screen_size.1 as f32);
+        // In game the world stays visible, dimmed, behind the menu.
+        let backdrop = if state == GameState::MainMenu { 0.2 } else { 0.5 };
+        batch.quad(Rect::new(0.0, 0.0, width, height), [0.0, 0.0, 0.0, backdrop]);
+
+        let total = rows.len() as f32 * (BUTTON_HEIGHT + BUTTON_GAP) - BUTTON_GAP;
+        let mut y = ((height - total) / 2.0).round().max(heading_size * 3.0);
+
+        let (heading_width, heading_height) = text.measure(&heading, heading_size);
+        let heading_y = y - heading_height - heading_size;
+        text.queue(heading, [((width - heading_width) / 2.0).round(), heading_y], heading_size, [1.0, 1.0, 1.0, 1.0]);
+        let caption = match (&self.message, note) {
+            (Some(message), _) => Some((message.clone(), [1.0, 0.45, 0.4, 1.0])),
+            (None, Some(note)) => Some((note, [0.75, 0.75, 0.75, 1.0])),
+            (None, None) => None,
+        };
+        if let Some((caption, color)) = caption {
+            let (caption_width, _) = text.measure(&caption, MESSAGE_SIZE);
+            let position = [((width - caption_width) / 2.0).round(), y - MESSAGE_SIZE * 2.0];
+            text.queue(caption, position, MESSAGE_SIZE, color);
+        }
+
+        self.buttons.clear();
+        for row in rows {
+            let row_width = row.iter().map(|item| item.width).sum::<f32>() + (row.len() - 1) as f32 * BUTTON_GAP;
+            let mut x = ((width - row_width) / 2.0).round();
+            for item in row {
+                let rect = Rect::new(x, y, item.width, BUTTON_HEIGHT);
+                let hovered = rect.contains(self.mouse_position) && !matches!(item.click, Click::Label);
+                match (&item.click, &item.detail) {
+                    (Click::Focus(field), _) => {
+                        let placeholder = if *field == 0 { "World name" } else { "Seed (blank for random)" };
+                        ui::draw_text_field(batch, text, rect, &self.fields[*field], placeholder, self.focused == *field);
+                    },
+                    (_, Some(detail)) => ui::draw_list_button(batch, text, rect, &item.label, detail, hovered),
+                    (_, None) => ui::draw_button(batch, text, rect, &item.label, hovered),
+                }
+                self.buttons.push(Button { rect, click: item.click });
+                x += item.width + BUTTON_GAP;
+            }
+            y += BUTTON_HEIGHT + BUTTON_GAP;
+        }
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "On" } else { "Off" }
+}
+
+fn percent(value: f32) -> i32 {
+    (value * 100.0).round() as i32
+}
+
+fn unused_world_name() -> String {
+    (1..)
+        .map(|n| if n == 1 { "New World".to_string() } else { format!("New World {n}") })
+        .find(|name| !save::WorldSave::new(name).exists())
+        .unwrap()
+}
+
+// How long ago a world was played, or nothing for worlds saved before
+// that was recorded.
+fn last_played(last_played: u64, now: u64) -> Option<String> {
+    if last_played == 0 {
+        return None;
+    }
+    let ago = |n: u64, unit: &str| if n == 1 { format!("1 {unit} ago") } else { format!("{n} {unit}s ago") };
+    let seconds = now.saturating_sub(last_played);
+    Some(match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => ago(seconds / 60, "minute"),
+        3600..86400 => ago(seconds / 3600, "hour"),
+        _ => ago(seconds / 86400, "day"),
+    })
+}
+
+// Names become directory names, so they're kept to what's safe in one
+// on every platform.
+fn check_world_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("the world needs a name".to_string());
+    }
+    if !name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_')) {
+        return Err("world names can only use letters, digits, spaces, - and _".to_string());
+    }
+    Ok(())
+}
+
+/// Numbers are used as they are, any other text is hashed, and an empty
+/// field picks a seed at random.
+pub fn parse_seed(text: &str) -> u32 {
+    let text = text.trim();
+    if text.is_empty() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.subsec_nanos() ^ elapsed.as_secs() as u32);
+        return nanos;
+    }
+    if let Ok(seed) = text.parse::<i64>() {
+        return seed as u32;
+    }
+    // FNV-1a, which unlike the standard hasher is stable across versions.
+    text.bytes().fold(0x811c_9dc5, |hash: u32, byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193))
+}
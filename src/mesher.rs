@@ -0,0 +1,619 @@
+use std::borrow::Cow;
+
+use crate::{
+    block::{BlockId, BlockModel, BlockState, Cuboid, Face, RenderLayer},
+    chunk::{Chunk, ChunkPos, ColumnPos, CHUNK_SIZE},
+    fluid,
+    gpu::{Allocation, BufferPool},
+    indirect::DrawArgs,
+    light::Light,
+    world::World,
+    worldgen::{WorldGenerator, SEA_LEVEL},
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: [f32 ; 3],
+    pub tex_coords: [f32 ; 2],
+    pub layer: u32,
+    pub shade: f32,
+    // From `Light::vertex`.
+    pub light: [u8 ; 4],
+}
+
+impl Vertex {
+    const ATTRIBS: [wgpu::VertexAttribute ; 5]
+        = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Uint32, 3 => Float32, 4 => Unorm8x4];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// Corners of each face in bottom-left, bottom-right, top-right, top-left
+// order as seen from outside the block, so both triangles wind CCW.
+fn face_corners(face: Face) -> [[f32 ; 3] ; 4] {
+    match face {
+        Face::PosX => [[1.0, 0.0, 1.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0]],
+        Face::NegX => [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 1.0, 0.0]],
+        Face::PosY => [[0.0, 1.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+        Face::NegY => [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0]],
+        Face::PosZ => [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0]],
+        Face::NegZ => [[1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]],
+    }
+}
+
+fn face_shade(face: Face) -> f32 {
+    match face {
+        Face::PosY => 1.0,
+        Face::PosX | Face::NegX => 0.8,
+        Face::PosZ | Face::NegZ => 0.65,
+        Face::NegY => 0.5,
+    }
+}
+
+const CROSS_SHADE: f32 = 0.9;
+
+// How much a corner of a smooth-lit face is darkened by the opaque blocks
+// beside it in front of the face, from none of the three to all of them.
+const OCCLUSION_SHADE: [f32 ; 4] = [1.0, 0.8, 0.65, 0.5];
+
+// How each corner of a quad is lit, in the order of its corners.
+#[derive(Clone, Copy, Debug)]
+struct QuadLight {
+    // From `Light::vertex`, or an average of several.
+    light: [[u8 ; 4] ; 4],
+    shade: [f32 ; 4],
+}
+
+impl QuadLight {
+    // The same light and shade at every corner.
+    fn flat(light: Light, shade: f32) -> Self {
+        Self { light: [light.vertex() ; 4], shade: [shade ; 4] }
+    }
+
+    // How bright a corner comes out, near enough to compare corners by.
+    fn brightness(&self, corner: usize) -> f32 {
+        let brightest = self.light[corner].into_iter().max().unwrap_or(0);
+        self.shade[corner] * (1.0 + brightest as f32)
+    }
+}
+
+// The two diagonal planes of a cross model, corners in the same order
+// as `face_corners`.
+const CROSS_QUADS: [[[f32 ; 3] ; 4] ; 2] = [
+    [[0.0, 0.0, 0.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 0.0]],
+    [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [1.0, 1.0, 0.0]],
+];
+
+/// Geometry is built with 32-bit indices; `upload` narrows them to 16 bits
+/// whenever the mesh is small enough, which is nearly always.
+#[derive(Default)]
+pub struct ChunkMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl ChunkMesh {
+    fn push_quad(&mut self, position: [f32 ; 3], corners: &[[f32 ; 3] ; 4], layer: u32, lighting: QuadLight) {
+        self.push_tiled_quad(position, corners, [1.0, 1.0], layer, lighting);
+    }
+
+    // Like `push_quad`, with the texture repeated `tiles` times across and
+    // up the quad.
+    fn push_tiled_quad(&mut self, position: [f32 ; 3], corners: &[[f32 ; 3] ; 4], tiles: [f32 ; 2], layer: u32, lighting: QuadLight) {
+        let [u, v] = tiles;
+        self.push_vertices(position, corners, [[0.0, v], [u, v], [u, 0.0], [0.0, 0.0]], layer, lighting);
+    }
+
+    fn push_vertices(
+        &mut self,
+        position: [f32 ; 3],
+        corners: &[[f32 ; 3] ; 4],
+        tex_coords: [[f32 ; 2] ; 4],
+        layer: u32,
+        lighting: QuadLight,
+    ) {
+        let base = self.vertices.len() as u32;
+        for (i, (corner, tex_coords)) in corners.iter().zip(tex_coords).enumerate() {
+            self.vertices.push(Vertex {
+                position: [
+                    position[0] + corner[0],
+                    position[1] + corner[1],
+                    position[2] + corner[2],
+                ],
+                tex_coords,
+                layer,
+                shade: lighting.shade[i],
+                light: lighting.light[i],
+            });
+        }
+        // Splitting along the brighter diagonal keeps a single dark corner
+        // from smearing across the whole quad.
+        if lighting.brightness(0) + lighting.brightness(2) < lighting.brightness(1) + lighting.brightness(3) {
+            self.indices.extend_from_slice(&[base + 1, base + 2, base + 3, base + 3, base, base + 1]);
+        } else {
+            self.indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+        }
+    }
+
+    // A cube face, with the texture turned however the block's state asks.
+    fn push_face(&mut self, position: [f32 ; 3], face: Face, state: BlockState, lighting: QuadLight) {
+        let (layer, quarter_turns) = state.texture(face);
+        let mut tex_coords = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+        tex_coords.rotate_left(quarter_turns as usize % 4);
+        self.push_vertices(position, &face_corners(face), tex_coords, layer, lighting);
+    }
+
+    // One face of a box inside a block. Texture coordinates come from where
+    // the face sits in the block, so a slab shows the matching half of the
+    // texture rather than all of it squashed.
+    fn push_cuboid_face(&mut self, position: [f32 ; 3], cuboid: &Cuboid, face: Face, layer: u32, light: Light) {
+        let full = face_corners(face);
+        // Across the full face, u runs from the first corner to the second
+        // and v from the fourth back to the first.
+        let u_axis = (0..3).find(|&a| full[1][a] != full[0][a]).unwrap();
+        let v_axis = (0..3).find(|&a| full[0][a] != full[3][a]).unwrap();
+
+        let corners = full.map(|corner| {
+            let mut point = [0.0 ; 3];
+            for axis in 0..3 {
+                point[axis] = cuboid.min[axis] + corner[axis] * (cuboid.max[axis] - cuboid.min[axis]);
+            }
+            point
+        });
+        let tex_coords = corners.map(|point| [
+            (point[u_axis] - full[0][u_axis]) * (full[1][u_axis] - full[0][u_axis]),
+            1.0 - (point[v_axis] - full[0][v_axis]) * (full[3][v_axis] - full[0][v_axis]),
+        ]);
+        self.push_vertices(position, &corners, tex_coords, layer, QuadLight::flat(light, face_shade(face)));
+    }
+
+    fn push_fluid(&mut self, position: [f32 ; 3], face: Face, block: BlockId, height: f32, light: Light) {
+        let mut corners = face_corners(face);
+        for corner in &mut corners {
+            corner[1] *= height;
+        }
+        self.push_quad(position, &corners, block.texture(face), QuadLight::flat(light, face_shade(face)));
+    }
+
+    /// Adds a full cube of the block's textures with every corner passed
+    /// through `model`, for meshes that aren't aligned to the block grid.
+    /// Its shading is scaled by `brightness`, above 1 to make it glow. It's
+    /// lit by open sky until `relight` says otherwise.
+    pub fn push_transformed_cube(&mut self, block: BlockId, model: cgmath::Matrix4<f32>, brightness: f32) {
+        self.push_shaded_box(Face::ALL.map(|face| block.texture(face)), model, brightness);
+    }
+
+    /// Like `push_transformed_cube`, with a texture layer for each face in
+    /// the order of `Face::ALL`.
+    pub fn push_transformed_box(&mut self, textures: [u32 ; 6], model: cgmath::Matrix4<f32>) {
+        self.push_shaded_box(textures, model, 1.0);
+    }
+
+    fn push_shaded_box(&mut self, textures: [u32 ; 6], model: cgmath::Matrix4<f32>, brightness: f32) {
+        for (face, texture) in Face::ALL.into_iter().zip(textures) {
+            let corners = face_corners(face).map(|corner| {
+                let point = model * cgmath::Vector4::new(corner[0], corner[1], corner[2], 1.0);
+                [point.x, point.y, point.z]
+            });
+            self.push_quad([0.0 ; 3], &corners, texture, QuadLight::flat(Light::SKY, face_shade(face) * brightness));
+        }
+    }
+
+    /// Lights every vertex from `first` on with `light`, for models lit by
+    /// wherever they happen to be standing.
+    pub fn relight(&mut self, first: usize, light: Light) {
+        for vertex in &mut self.vertices[first..] {
+            vertex.light = light.vertex();
+        }
+    }
+
+    // One face of an axis-aligned box under open sky, with the texture
+    // tiled once per block.
+    fn push_box_face(&mut self, min: [f32 ; 3], size: [f32 ; 3], face: Face, block: BlockId) {
+        let corners = face_corners(face).map(|corner| [
+            corner[0] * size[0],
+            corner[1] * size[1],
+            corner[2] * size[2],
+        ]);
+        let length = |a: [f32 ; 3], b: [f32 ; 3]| (0..3).map(|axis| (b[axis] - a[axis]).abs()).sum::<f32>();
+        let tiles = [length(corners[0], corners[1]), length(corners[1], corners[2])];
+        self.push_tiled_quad(min, &corners, tiles, block.texture(face), QuadLight::flat(Light::SKY, face_shade(face)));
+    }
+
+    fn push_cross(&mut self, position: [f32 ; 3], block: BlockId, light: Light) {
+        for corners in &CROSS_QUADS {
+            self.push_quad(position, corners, block.texture(Face::PosZ), QuadLight::flat(light, CROSS_SHADE));
+        }
+    }
+
+    /// The narrowest index format that can address every vertex.
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        if self.vertices.len() <= u16::MAX as usize {
+            wgpu::IndexFormat::Uint16
+        } else {
+            wgpu::IndexFormat::Uint32
+        }
+    }
+
+    /// The index buffer contents in `index_format()`.
+    pub fn index_bytes(&self) -> Cow<'_, [u8]> {
+        match self.index_format() {
+            wgpu::IndexFormat::Uint16 => Cow::Owned(
+                self.indices.iter().flat_map(|&index| (index as u16).to_ne_bytes()).collect(),
+            ),
+            wgpu::IndexFormat::Uint32 => Cow::Borrowed(bytemuck::cast_slice(&self.indices)),
+        }
+    }
+
+    /// Copies the mesh into the pool, or returns `None` if there's nothing
+    /// to draw.
+    pub fn upload(&self, device: &wgpu::Device, queue: &wgpu::Queue, pool: &mut MeshPool) -> Option<ChunkBuffers> {
+        if self.indices.is_empty() {
+            return None;
+        }
+
+        Some(ChunkBuffers {
+            vertices: pool.vertices.allocate(device, queue, bytemuck::cast_slice(&self.vertices)),
+            indices: pool.indices.allocate(device, queue, &self.index_bytes()),
+            num_vertices: self.vertices.len() as u32,
+            num_indices: self.indices.len() as u32,
+            index_format: self.index_format(),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct ChunkMeshes {
+    pub opaque: ChunkMesh,
+    pub cutout: ChunkMesh,
+    pub translucent: ChunkMesh,
+}
+
+impl ChunkMeshes {
+    pub fn upload(&self, device: &wgpu::Device, queue: &wgpu::Queue, pool: &mut MeshPool) -> ChunkMeshBuffers {
+        let _span = tracing::trace_span!("upload_chunk").entered();
+        ChunkMeshBuffers {
+            opaque: self.opaque.upload(device, queue, pool),
+            cutout: self.cutout.upload(device, queue, pool),
+            translucent: self.translucent.upload(device, queue, pool),
+        }
+    }
+}
+
+// Room for a few dozen typical chunks per page.
+const VERTEX_PAGE_SIZE: wgpu::BufferAddress = 32 << 20;
+const INDEX_PAGE_SIZE: wgpu::BufferAddress = 8 << 20;
+
+/// Shared GPU storage for every chunk mesh. Remeshing a chunk frees its
+/// old regions, which the next upload reuses.
+pub struct MeshPool {
+    vertices: BufferPool,
+    indices: BufferPool,
+}
+
+impl MeshPool {
+    pub fn new() -> Self {
+        Self {
+            // Whole vertices, so indirect draws can address them with a
+            // base vertex into the page.
+            vertices: BufferPool::new(
+                "Chunk Vertex Pool",
+                wgpu::BufferUsages::VERTEX,
+                VERTEX_PAGE_SIZE,
+                std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            ),
+            indices: BufferPool::new("Chunk Index Pool", wgpu::BufferUsages::INDEX, INDEX_PAGE_SIZE, 4),
+        }
+    }
+
+    pub fn free(&mut self, buffers: ChunkMeshBuffers) {
+        for buffers in [buffers.opaque, buffers.cutout, buffers.translucent].into_iter().flatten() {
+            self.vertices.free(buffers.vertices);
+            self.indices.free(buffers.indices);
+        }
+    }
+
+    pub fn vertex_page(&self, page: usize) -> &wgpu::Buffer {
+        self.vertices.page(page)
+    }
+
+    pub fn index_page(&self, page: usize) -> &wgpu::Buffer {
+        self.indices.page(page)
+    }
+
+    /// Bytes in use and bytes reserved, across vertices and indices.
+    pub fn memory_usage(&self) -> (u64, u64) {
+        (
+            self.vertices.used() + self.indices.used(),
+            self.vertices.capacity() + self.indices.capacity(),
+        )
+    }
+}
+
+/// A chunk's meshes by render layer; layers with no geometry are `None`.
+pub struct ChunkMeshBuffers {
+    pub opaque: Option<ChunkBuffers>,
+    pub cutout: Option<ChunkBuffers>,
+    pub translucent: Option<ChunkBuffers>,
+}
+
+pub struct ChunkBuffers {
+    pub vertices: Allocation,
+    pub indices: Allocation,
+    pub num_vertices: u32,
+    pub num_indices: u32,
+    pub index_format: wgpu::IndexFormat,
+}
+
+impl ChunkBuffers {
+    /// Arguments drawing this mesh from its pool pages.
+    pub fn draw_args(&self) -> DrawArgs {
+        let index_size = match self.index_format {
+            wgpu::IndexFormat::Uint16 => 2,
+            wgpu::IndexFormat::Uint32 => 4,
+        };
+        DrawArgs {
+            index_count: self.num_indices,
+            instance_count: 1,
+            first_index: (self.indices.offset() / index_size) as u32,
+            base_vertex: (self.vertices.offset() / std::mem::size_of::<Vertex>() as u64) as i32,
+            first_instance: 0,
+        }
+    }
+}
+
+fn neighbor(chunk: &Chunk, world: &World, x: i32, y: i32, z: i32) -> BlockId {
+    if Chunk::in_bounds(x, y, z) {
+        return chunk.get(x as usize, y as usize, z as usize);
+    }
+
+    let [origin_x, origin_y, origin_z] = chunk.pos.origin();
+    world.get_block(origin_x + x, origin_y + y, origin_z + z)
+}
+
+// Faces are lit by the light in the block they look out into.
+fn neighbor_light(chunk: &Chunk, world: &World, x: i32, y: i32, z: i32) -> Light {
+    if Chunk::in_bounds(x, y, z) {
+        return chunk.light(x as usize, y as usize, z as usize);
+    }
+
+    let [origin_x, origin_y, origin_z] = chunk.pos.origin();
+    world.light(origin_x + x, origin_y + y, origin_z + z)
+}
+
+// Smooth lighting for the cube face of the block at `[x, y, z]`: each
+// corner takes the average light of the four blocks in front of the face
+// that meet there, leaving out the opaque ones, and is darkened by how many
+// of them are opaque.
+fn smooth_face_light(chunk: &Chunk, world: &World, [x, y, z]: [i32 ; 3], face: Face) -> QuadLight {
+    let normal = face.normal();
+    let front = [x + normal[0], y + normal[1], z + normal[2]];
+    let sample = |[x, y, z]: [i32 ; 3]| {
+        let opaque = neighbor(chunk, world, x, y, z).is_opaque();
+        (opaque, neighbor_light(chunk, world, x, y, z))
+    };
+    // The two directions across the face.
+    let axes = match face {
+        Face::PosX | Face::NegX => [1, 2],
+        Face::PosY | Face::NegY => [0, 2],
+        Face::PosZ | Face::NegZ => [0, 1],
+    };
+
+    let center = sample(front).1;
+    let mut lighting = QuadLight::flat(center, face_shade(face));
+    for (i, corner) in face_corners(face).iter().enumerate() {
+        let step = |position: [i32 ; 3], axis: usize| {
+            let mut position = position;
+            position[axis] += if corner[axis] > 0.5 { 1 } else { -1 };
+            position
+        };
+        let side_u = sample(step(front, axes[0]));
+        let side_v = sample(step(front, axes[1]));
+        // Light can't reach the diagonal block past two opaque sides.
+        let diagonal = if side_u.0 && side_v.0 {
+            (true, Light::default())
+        } else {
+            sample(step(step(front, axes[0]), axes[1]))
+        };
+
+        let (mut totals, mut count) = ([0u32 ; 4], 0);
+        let mut occluders = 0;
+        for (opaque, light) in [(false, center), side_u, side_v, diagonal] {
+            if opaque {
+                occluders += 1;
+                continue;
+            }
+            for (total, level) in totals.iter_mut().zip(light.vertex()) {
+                *total += level as u32;
+            }
+            count += 1;
+        }
+        lighting.light[i] = totals.map(|total| ((total + count / 2) / count) as u8);
+        lighting.shade[i] *= OCCLUSION_SHADE[occluders];
+    }
+    lighting
+}
+
+pub fn mesh_chunk(world: &World, pos: ChunkPos, smooth_lighting: bool) -> Option<ChunkMeshes> {
+    let _span = tracing::trace_span!("mesh_chunk", x = pos.x, y = pos.y, z = pos.z).entered();
+    let chunk = world.chunks.get(&pos)?;
+    let [origin_x, origin_y, origin_z] = pos.origin();
+
+    let mut meshes = ChunkMeshes::default();
+    // A chunk of nothing but air has nothing to draw.
+    if chunk.is_empty() {
+        return Some(meshes);
+    }
+
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let state = chunk.get_state(x, y, z);
+                let block = state.block;
+                let info = block.info();
+                let mesh = match info.layer {
+                    RenderLayer::Opaque => &mut meshes.opaque,
+                    RenderLayer::Cutout => &mut meshes.cutout,
+                    RenderLayer::Translucent => &mut meshes.translucent,
+                };
+                let position = [
+                    (origin_x + x as i32) as f32,
+                    (origin_y + y as i32) as f32,
+                    (origin_z + z as i32) as f32,
+                ];
+
+                // Faces inside the block take its own light.
+                let own_light = chunk.light(x, y, z);
+                match info.model {
+                    // A moving block is drawn by its entity until it lands.
+                    BlockModel::None | BlockModel::Moving => (),
+                    BlockModel::Cross => mesh.push_cross(position, block, own_light),
+                    BlockModel::Cube => {
+                        let (x, y, z) = (x as i32, y as i32, z as i32);
+                        for face in Face::ALL {
+                            let [nx, ny, nz] = face.normal();
+                            let other = neighbor(chunk, world, x + nx, y + ny, z + nz);
+                            if other.is_opaque() {
+                                continue;
+                            }
+
+                            let lighting = if smooth_lighting {
+                                smooth_face_light(chunk, world, [x, y, z], face)
+                            } else {
+                                QuadLight::flat(neighbor_light(chunk, world, x + nx, y + ny, z + nz), face_shade(face))
+                            };
+                            mesh.push_face(position, face, state, lighting);
+                        }
+                    },
+                    BlockModel::Torch | BlockModel::Slab | BlockModel::Stairs | BlockModel::Fence | BlockModel::Ladder
+                    | BlockModel::Bed
+                    | BlockModel::Door
+                    | BlockModel::Trapdoor
+                    | BlockModel::Gate
+                    | BlockModel::Wire
+                    | BlockModel::Lever
+                    | BlockModel::Button
+                    | BlockModel::Piston
+                    | BlockModel::PistonHead
+                    | BlockModel::Portal => {
+                        let (x, y, z) = (x as i32, y as i32, z as i32);
+                        let connections = world.fence_connections(origin_x + x, origin_y + y, origin_z + z);
+                        for cuboid in state.shape(connections) {
+                            for face in Face::ALL {
+                                let mut light = own_light;
+                                // Only faces on the edge of the block can
+                                // be covered by the neighbour.
+                                if cuboid.on_boundary(face) {
+                                    let [nx, ny, nz] = face.normal();
+                                    let other = neighbor(chunk, world, x + nx, y + ny, z + nz);
+                                    // Portals next to each other make one
+                                    // sheet, without edges showing through.
+                                    if other.is_opaque() || (info.model == BlockModel::Portal && other == block) {
+                                        continue;
+                                    }
+                                    light = neighbor_light(chunk, world, x + nx, y + ny, z + nz);
+                                }
+
+                                mesh.push_cuboid_face(position, &cuboid, face, state.texture(face).0, light);
+                            }
+                        }
+                    },
+                    BlockModel::Fluid => {
+                        let (x, y, z) = (x as i32, y as i32, z as i32);
+                        // Fluid under more of the same fills its block so
+                        // columns have no gaps.
+                        let height = if neighbor(chunk, world, x, y + 1, z) == block {
+                            1.0
+                        } else {
+                            fluid::surface_height(chunk.fluid_level(x as usize, y as usize, z as usize))
+                        };
+
+                        for face in Face::ALL {
+                            let [nx, ny, nz] = face.normal();
+                            let other = neighbor(chunk, world, x + nx, y + ny, z + nz);
+                            // The top face stays visible under a solid block
+                            // unless the fluid reaches all the way up.
+                            let hidden = other == block
+                                || (other.is_opaque() && (face != Face::PosY || height >= 1.0));
+                            if hidden {
+                                continue;
+                            }
+
+                            // A surface below the top of the block is lit
+                            // from inside it.
+                            let light = if face == Face::PosY && height < 1.0 {
+                                own_light
+                            } else {
+                                neighbor_light(chunk, world, x + nx, y + ny, z + nz)
+                            };
+                            mesh.push_fluid(position, face, block, height, light);
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    Some(meshes)
+}
+
+// How far each chunk-border side of a LOD mesh reaches below its own
+// surface, hiding cracks against neighbours sampled on another grid.
+const LOD_SKIRT_DEPTH: f32 = 12.0;
+
+/// Meshes a column of chunks straight from the generator's height field
+/// with cells of `scale` by `scale` blocks, for terrain too far away to
+/// generate in full. Each cell is a column topped with its surface block;
+/// caves, structures and plants are left out.
+pub fn mesh_lod_chunk(generator: &WorldGenerator, column: ColumnPos, scale: i32) -> ChunkMeshes {
+    let cells = CHUNK_SIZE as i32 / scale;
+    let (origin_x, origin_z) = column.origin();
+
+    // One extra ring of cells around the chunk for the sides facing its
+    // neighbours, sampled at the cell centres.
+    let stride = cells + 2;
+    let columns = (0..stride * stride)
+        .map(|i| {
+            let (cell_x, cell_z) = (i % stride - 1, i / stride - 1);
+            generator.surface_at(origin_x + cell_x * scale + scale / 2, origin_z + cell_z * scale + scale / 2)
+        })
+        .collect::<Vec<_>>();
+    let column = |cell_x: i32, cell_z: i32| columns[((cell_z + 1) * stride + cell_x + 1) as usize];
+
+    let water_top = SEA_LEVEL as f32 + fluid::surface_height(fluid::SOURCE_LEVEL);
+    let size = scale as f32;
+    let mut meshes = ChunkMeshes::default();
+
+    for cell_z in 0..cells {
+        for cell_x in 0..cells {
+            let surface = column(cell_x, cell_z);
+            let top = (surface.height + 1) as f32;
+            let (x, z) = ((origin_x + cell_x * scale) as f32, (origin_z + cell_z * scale) as f32);
+
+            meshes.opaque.push_box_face([x, 0.0, z], [size, top, size], Face::PosY, surface.surface);
+            for face in [Face::PosX, Face::NegX, Face::PosZ, Face::NegZ] {
+                let [dx, _, dz] = face.normal();
+                let (next_x, next_z) = (cell_x + dx, cell_z + dz);
+                let next_top = (column(next_x, next_z).height + 1) as f32;
+                let on_border = !(0..cells).contains(&next_x) || !(0..cells).contains(&next_z);
+                let bottom = if on_border { next_top.min(top - LOD_SKIRT_DEPTH) } else { next_top };
+                if bottom < top {
+                    meshes.opaque.push_box_face([x, bottom, z], [size, top - bottom, size], face, surface.subsurface);
+                }
+            }
+
+            if surface.height < SEA_LEVEL {
+                meshes.translucent.push_box_face([x, 0.0, z], [size, water_top, size], Face::PosY, BlockId::WATER);
+            }
+        }
+    }
+
+    meshes
+}
@@ -0,0 +1,629 @@
+//! Mobs: pigs that wander the grass by daylight and zombies that come out
+//! in the dark to chase players. They spawn around the player and despawn
+//! once left far behind, so none are saved, and only singleplayer worlds
+//! have them for now.
+
+use std::f32::consts::{PI, TAU};
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3};
+
+use crate::{
+    block::{registry, BlockId},
+    chunk::ChunkPos,
+    ecs::{Collider, Ecs, Entity, MeshRef, Transform, Velocity},
+    health::{self, DamageSource, Health},
+    inventory::ItemStack,
+    item,
+    mesher::ChunkMesh,
+    pathfind::{Agent, Path, Pathfinder},
+    player_model::{self, PlayerAnimation, Skin},
+    random::Random,
+    sky,
+    world::World,
+};
+
+// Passive mobs need at least this much light to spawn and hostile ones at
+// most this much, out of `light::MAX_LIGHT`.
+const PASSIVE_SPAWN_LIGHT: u8 = 9;
+const HOSTILE_SPAWN_LIGHT: u8 = 7;
+// How many of each sort of mob can be around the player at once.
+const PASSIVE_CAP: usize = 8;
+const HOSTILE_CAP: usize = 12;
+// Spawning is tried this often, this many times per kind, this far away
+// from the player horizontally and up to this far above or below them.
+const SPAWN_INTERVAL: f32 = 1.0;
+const SPAWN_ATTEMPTS: u32 = 4;
+const SPAWN_DISTANCE: (f32, f32) = (24.0, 48.0);
+const SPAWN_HEIGHT_RANGE: f32 = 16.0;
+// Mobs past this distance vanish at once, and past the nearer one they
+// have this chance each second of wandering off.
+const DESPAWN_DISTANCE: f32 = 96.0;
+const IDLE_DESPAWN_DISTANCE: f32 = 40.0;
+const IDLE_DESPAWN_CHANCE: f32 = 0.02;
+
+const STEP_HEIGHT: f32 = 0.6;
+const JUMP_SPEED: f32 = 8.5;
+// Rises this fast while in a fluid, so mobs bob along rather than drown.
+const SWIM_SPEED: f32 = 2.5;
+// How quickly mobs speed up towards where they're headed, and turn to
+// face it.
+const ACCELERATION: f32 = 10.0;
+const TURN_RATE: f32 = 8.0;
+// Drops of more than this many blocks are walked around.
+const MAX_DROP: i32 = 3;
+// Seconds between changes of mind, and the chance each time of setting
+// off for somewhere up to WANDER_RANGE away rather than standing around.
+const THINK_TIME: (f32, f32) = (2.0, 6.0);
+const WANDER_CHANCE: f32 = 0.6;
+const WANDER_RANGE: f32 = 8.0;
+const FLEE_TIME: f32 = 4.0;
+const FLEE_SPEED_FACTOR: f32 = 1.8;
+// Hostile mobs go after players this close, and hit them once this close
+// to touching.
+const CHASE_RANGE: f32 = 16.0;
+// Chasers look for a new path this often, and close enough in just head
+// straight for their target.
+const REPATH_TIME: f32 = 1.0;
+const DIRECT_RANGE: f32 = 2.0;
+const ATTACK_REACH: f32 = 0.5;
+const ATTACK_COOLDOWN: f32 = 1.0;
+// A player's bare-handed hit, in half hearts.
+const HIT_DAMAGE: u32 = 2;
+// Hits push their target back and up.
+const KNOCKBACK_SPEED: f32 = 6.0;
+const KNOCKBACK_LIFT: f32 = 5.0;
+
+// The pig model is laid out in pixels, with the feet at 0, and scaled to
+// sixteen pixels a block.
+const PIXEL: f32 = 1.0 / 16.0;
+// Models face +x, which comes first in `Face::ALL`.
+const FRONT: usize = 0;
+const PIG_LEG_ANGLE: f32 = 0.7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MobKind {
+    Pig,
+    Zombie,
+}
+
+impl MobKind {
+    pub const ALL: [MobKind ; 2] = [MobKind::Pig, MobKind::Zombie];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MobKind::Pig => "pig",
+            MobKind::Zombie => "zombie",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub fn is_hostile(self) -> bool {
+        match self {
+            MobKind::Pig => false,
+            MobKind::Zombie => true,
+        }
+    }
+
+    pub fn death_message(self) -> &'static str {
+        match self {
+            MobKind::Pig => "You were killed by a pig",
+            MobKind::Zombie => "You were killed by a zombie",
+        }
+    }
+
+    // In half hearts.
+    fn max_health(self) -> u32 {
+        match self {
+            MobKind::Pig => 10,
+            MobKind::Zombie => 20,
+        }
+    }
+
+    // In blocks per second.
+    fn walk_speed(self) -> f32 {
+        match self {
+            MobKind::Pig => 1.5,
+            MobKind::Zombie => 2.5,
+        }
+    }
+
+    // Half width and height of the collider.
+    fn size(self) -> (f32, f32) {
+        match self {
+            MobKind::Pig => (0.45, 0.9),
+            MobKind::Zombie => (0.3, 1.8),
+        }
+    }
+
+    // Half hearts taken by each hit on a player.
+    fn attack_damage(self) -> u32 {
+        match self {
+            MobKind::Pig => 0,
+            MobKind::Zombie => 3,
+        }
+    }
+
+    fn agent(self) -> Agent {
+        let (half_width, height) = self.size();
+        Agent { half_width, height: height.ceil() as i32, max_drop: MAX_DROP }
+    }
+
+    fn drops(self, random: &mut Random) -> Vec<ItemStack> {
+        match self {
+            MobKind::Pig => vec![ItemStack::new(BlockId::PORKCHOP, 1 + random.next() % 2)],
+            MobKind::Zombie => Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Goal {
+    Idle,
+    Walk { to: Point3<f32> },
+    Flee { yaw: f32 },
+    Chase(Entity),
+}
+
+/// A mob's mind: what it's up to and when it next thinks again.
+pub struct Mob {
+    pub kind: MobKind,
+    goal: Goal,
+    // The way to where it's walking or who it's chasing, once found.
+    path: Option<Path>,
+    think_timer: f32,
+    repath_timer: f32,
+    attack_cooldown: f32,
+    random: Random,
+}
+
+/// Adds a mob standing at `position`, facing a way picked from `seed`.
+pub fn spawn(ecs: &mut Ecs, kind: MobKind, position: Point3<f32>, seed: u32) -> Entity {
+    let (half_width, height) = kind.size();
+    let mut random = Random::new(seed);
+    let entity = ecs.spawn();
+    ecs.transforms.insert(entity, Transform { yaw: random.float() * TAU, ..Transform::new(position) });
+    ecs.velocities.insert(entity, Velocity::default());
+    ecs.colliders.insert(entity, Collider { step_height: STEP_HEIGHT, ..Collider::new(half_width, height) });
+    ecs.meshes.insert(entity, MeshRef::Mob(kind));
+    ecs.animations.insert(entity, PlayerAnimation::default());
+    ecs.healths.insert(entity, Health::new(kind.max_health()));
+    ecs.mobs.insert(entity, Mob {
+        kind,
+        goal: Goal::Idle,
+        path: None,
+        think_timer: 0.0,
+        repath_timer: 0.0,
+        attack_cooldown: 0.0,
+        random,
+    });
+    entity
+}
+
+/// Hits `mob` with a bare hand, knocking it away from `attacker`. Pigs run
+/// off; zombies were coming anyway. Returns whether it was hurt.
+pub fn hit(ecs: &mut Ecs, mob: Entity, attacker: Entity) -> bool {
+    if !health::damage(ecs, mob, HIT_DAMAGE, DamageSource::Player) {
+        return false;
+    }
+    let (Some(from), Some(to)) = (ecs.transforms.get(attacker), ecs.transforms.get(mob)) else {
+        return true;
+    };
+    let away = to.position - from.position;
+    knock_back(ecs, mob, away);
+
+    if let Some(mob) = ecs.mobs.get_mut(mob) {
+        if !mob.kind.is_hostile() {
+            mob.goal = Goal::Flee { yaw: away.z.atan2(away.x) };
+            mob.path = None;
+            mob.think_timer = FLEE_TIME;
+        }
+    }
+    true
+}
+
+// Pushes `entity` along the ground in `direction` and up off it.
+fn knock_back(ecs: &mut Ecs, entity: Entity, direction: Vector3<f32>) {
+    let Some(Velocity(velocity)) = ecs.velocities.get_mut(entity) else {
+        return;
+    };
+    let flat = Vector3::new(direction.x, 0.0, direction.z);
+    if flat.magnitude2() > 0.0 {
+        let push = flat.normalize() * KNOCKBACK_SPEED;
+        velocity.x = push.x;
+        velocity.z = push.z;
+    }
+    velocity.y = velocity.y.max(KNOCKBACK_LIFT);
+}
+
+/// The nearest mob a ray meets within `max_distance`, and how far along
+/// the ray it is.
+pub fn raycast(ecs: &Ecs, origin: Point3<f32>, direction: Vector3<f32>, max_distance: f32) -> Option<(Entity, f32)> {
+    if direction.magnitude2() == 0.0 {
+        return None;
+    }
+    let direction = direction.normalize();
+    ecs.mobs.iter()
+        .filter_map(|(entity, _)| {
+            let (transform, collider) = (ecs.transforms.get(entity)?, ecs.colliders.get(entity)?);
+            let distance = collider.aabb(transform.position).ray_distance(origin, direction)?;
+            (distance <= max_distance).then_some((entity, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Makes every mob think, walk and attack, and clears away the dead,
+/// leaving their drops behind. Mobs walk where `pathfinder` finds them a
+/// way.
+pub fn mob_system(ecs: &mut Ecs, world: &World, pathfinder: &mut Pathfinder, dt: f32) {
+    for result in pathfinder.poll() {
+        let Some(mob) = ecs.mobs.get_mut(result.entity) else {
+            continue;
+        };
+        // Paths for goals since given up on are no use.
+        match mob.goal {
+            Goal::Walk { to } if to == result.goal => {
+                if result.path.is_none() {
+                    mob.goal = Goal::Idle;
+                }
+                mob.path = result.path;
+            },
+            Goal::Chase(_) => mob.path = result.path,
+            _ => {},
+        }
+    }
+
+    // Only players who can be hurt are worth chasing.
+    let targets = ecs.players.iter()
+        .filter(|(entity, _)| world.game_mode.takes_damage() && ecs.healths.get(*entity).is_some_and(|h| !h.is_dead()))
+        .filter_map(|(entity, _)| Some((entity, ecs.transforms.get(entity)?.position)))
+        .collect::<Vec<_>>();
+
+    let entities = ecs.mobs.iter().map(|(entity, _)| entity).collect::<Vec<_>>();
+    for entity in entities {
+        if ecs.healths.get(entity).is_none_or(Health::is_dead) {
+            die(ecs, entity);
+            continue;
+        }
+        let (Some(transform), Some(collider)) = (ecs.transforms.get(entity).copied(), ecs.colliders.get(entity).copied()) else {
+            continue;
+        };
+        let position = transform.position;
+        let mob = ecs.mobs.get_mut(entity).unwrap();
+        let agent = mob.kind.agent();
+        mob.attack_cooldown = (mob.attack_cooldown - dt).max(0.0);
+        mob.think_timer -= dt;
+        mob.repath_timer -= dt;
+
+        let nearest = targets.iter()
+            .map(|&(target, at)| (target, at, horizontal_distance(position, at)))
+            .filter(|&(_, at, distance)| distance < CHASE_RANGE && (at.y - position.y).abs() < CHASE_RANGE)
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+        if mob.kind.is_hostile() {
+            match nearest {
+                Some((target, ..)) if !matches!(mob.goal, Goal::Chase(chased) if chased == target) => {
+                    mob.goal = Goal::Chase(target);
+                    mob.path = None;
+                    mob.repath_timer = 0.0;
+                },
+                Some(_) => {},
+                None if matches!(mob.goal, Goal::Chase(_)) => {
+                    mob.goal = Goal::Idle;
+                    mob.path = None;
+                },
+                None => {},
+            }
+        }
+        if mob.think_timer <= 0.0 && !matches!(mob.goal, Goal::Chase(_)) {
+            mob.path = None;
+            mob.goal = Goal::Idle;
+            if mob.random.float() < WANDER_CHANCE {
+                let angle = mob.random.float() * TAU;
+                let distance = mob.random.range((WANDER_RANGE / 2.0, WANDER_RANGE));
+                let to = position + Vector3::new(angle.cos(), 0.0, angle.sin()) * distance;
+                if pathfinder.request(world, entity, position, to, agent) {
+                    mob.goal = Goal::Walk { to };
+                }
+            }
+            mob.think_timer = mob.random.range(THINK_TIME);
+        }
+
+        let waypoint = mob.path.as_mut().and_then(|path| {
+            path.advance(position);
+            path.current()
+        });
+        let walk_speed = mob.kind.walk_speed();
+        let (heading, speed) = match mob.goal {
+            Goal::Idle => (None, 0.0),
+            // Stands still while the way there is worked out, and stops
+            // once it's walked.
+            Goal::Walk { .. } => match waypoint {
+                Some(waypoint) => (Some(heading_to(position, waypoint)), walk_speed),
+                None => {
+                    if !pathfinder.is_pending(entity) {
+                        mob.goal = Goal::Idle;
+                    }
+                    (None, 0.0)
+                },
+            },
+            Goal::Flee { yaw } => (Some(yaw), walk_speed * FLEE_SPEED_FACTOR),
+            Goal::Chase(target) => {
+                let at = targets.iter().find(|(entity, _)| *entity == target).map_or(position, |&(_, at)| at);
+                if mob.repath_timer <= 0.0 && horizontal_distance(position, at) > DIRECT_RANGE {
+                    pathfinder.request(world, entity, position, at, agent);
+                    mob.repath_timer = REPATH_TIME;
+                }
+                let reach = collider.half_width * 2.0 + ATTACK_REACH;
+                let touching = horizontal_distance(position, at) < reach && (at.y - position.y).abs() < collider.height;
+                let heading = match waypoint {
+                    Some(waypoint) if horizontal_distance(position, at) > DIRECT_RANGE => heading_to(position, waypoint),
+                    _ => heading_to(position, at),
+                };
+                if touching && mob.attack_cooldown == 0.0 {
+                    mob.attack_cooldown = ATTACK_COOLDOWN;
+                    let damage = mob.kind.attack_damage();
+                    let kind = mob.kind;
+                    if health::damage(ecs, target, damage, DamageSource::Mob(kind)) {
+                        knock_back(ecs, target, at - position);
+                    }
+                    player_model::swing_arm(ecs, entity);
+                }
+                (Some(heading), if touching { 0.0 } else { walk_speed })
+            },
+        };
+
+        let blocked = steer(ecs, world, entity, heading, speed, dt);
+        // Wanderers that come to a drop stop there and think again.
+        if blocked {
+            let mob = ecs.mobs.get_mut(entity).unwrap();
+            if matches!(mob.goal, Goal::Walk { .. }) {
+                mob.goal = Goal::Idle;
+                mob.path = None;
+            }
+        }
+    }
+}
+
+// Walks `entity` along `heading` at `speed`, or slows it to a stop
+// without one, jumping up ledges and swimming up through fluids. Returns
+// whether a drop ahead stopped it.
+fn steer(ecs: &mut Ecs, world: &World, entity: Entity, heading: Option<f32>, speed: f32, dt: f32) -> bool {
+    let (Some(transform), Some(collider), Some(Velocity(velocity))) = (
+        ecs.transforms.get_mut(entity),
+        ecs.colliders.get_mut(entity),
+        ecs.velocities.get_mut(entity),
+    ) else {
+        return false;
+    };
+    let position = transform.position;
+    let in_fluid = world.get_block(position.x.floor() as i32, (position.y + 0.5).floor() as i32, position.z.floor() as i32).is_fluid();
+    if in_fluid {
+        velocity.y = velocity.y.max(SWIM_SPEED);
+    }
+
+    let mut target = Vector3::new(0.0, 0.0, 0.0);
+    let mut blocked = false;
+    if let Some(heading) = heading {
+        let forward = Vector3::new(heading.cos(), 0.0, heading.sin());
+        let ahead = position + forward * (collider.half_width + 0.5);
+        let [x, y, z] = [ahead.x.floor() as i32, position.y.floor() as i32, ahead.z.floor() as i32];
+        if !in_fluid && !has_floor(world, x, y, z) {
+            blocked = true;
+        } else {
+            target = forward * speed;
+            if collider.on_ground && ledge_height(world, x, y, z) - position.y > STEP_HEIGHT {
+                velocity.y = JUMP_SPEED;
+                collider.on_ground = false;
+            }
+        }
+        transform.yaw += wrap_angle(heading - transform.yaw) * (TURN_RATE * dt).min(1.0);
+    }
+
+    // Off the ground mobs keep going the way they were, so knockback
+    // carries them.
+    if collider.on_ground || in_fluid {
+        let blend = (ACCELERATION * dt).min(1.0);
+        velocity.x += (target.x - velocity.x) * blend;
+        velocity.z += (target.z - velocity.z) * blend;
+    }
+    blocked
+}
+
+// Whether something to stand on is no more than MAX_DROP blocks below the
+// feet at this spot.
+fn has_floor(world: &World, x: i32, y: i32, z: i32) -> bool {
+    (y - MAX_DROP - 1..=y).rev().any(|y| {
+        let block = world.get_block(x, y, z);
+        block.is_solid() || block.is_fluid()
+    })
+}
+
+// The top of whatever has to be climbed at this spot, or its bottom if
+// nothing does.
+fn ledge_height(world: &World, x: i32, y: i32, z: i32) -> f32 {
+    if !world.get_block(x, y, z).is_solid() {
+        return y as f32;
+    }
+    let top = world.collision_shape(x, y, z).iter().map(|cuboid| cuboid.max[1]).fold(0.0, f32::max);
+    y as f32 + top
+}
+
+// Removes a dead mob, dropping what it leaves behind where it fell.
+fn die(ecs: &mut Ecs, entity: Entity) {
+    let (Some(mob), Some(transform)) = (ecs.mobs.get_mut(entity), ecs.transforms.get(entity)) else {
+        ecs.despawn(entity);
+        return;
+    };
+    let drops = mob.kind.drops(&mut mob.random);
+    let position = transform.position;
+    ecs.despawn(entity);
+    item::spawn_dropped_stacks(ecs, &drops, position.x.floor() as i32, position.y.floor() as i32, position.z.floor() as i32);
+}
+
+/// Spawns mobs around the player where the light suits them, and despawns
+/// them once they're left far enough behind.
+pub struct MobSpawner {
+    timer: f32,
+    random: Random,
+}
+
+impl Default for MobSpawner {
+    fn default() -> Self {
+        Self { timer: 0.0, random: Random::new(1) }
+    }
+}
+
+impl MobSpawner {
+    pub fn update(&mut self, ecs: &mut Ecs, world: &World, player: Entity, dt: f32) {
+        self.timer += dt;
+        if self.timer < SPAWN_INTERVAL {
+            return;
+        }
+        self.timer -= SPAWN_INTERVAL;
+        let Some(center) = ecs.transforms.get(player).map(|t| t.position) else {
+            return;
+        };
+
+        let mut gone = Vec::new();
+        let mut counts = [0 ; 2];
+        for (entity, mob) in ecs.mobs.iter() {
+            let Some(position) = ecs.transforms.get(entity).map(|t| t.position) else {
+                continue;
+            };
+            let distance = horizontal_distance(center, position);
+            let [x, y, z] = [position.x, position.y, position.z].map(|v| v.floor() as i32);
+            let unloaded = !world.chunks.contains_key(&ChunkPos::from_block(x, y, z));
+            if unloaded || distance > DESPAWN_DISTANCE || (distance > IDLE_DESPAWN_DISTANCE && self.random.float() < IDLE_DESPAWN_CHANCE) {
+                gone.push(entity);
+            } else {
+                counts[mob.kind.is_hostile() as usize] += 1;
+            }
+        }
+        for entity in gone {
+            ecs.despawn(entity);
+        }
+
+        for kind in MobKind::ALL {
+            let cap = if kind.is_hostile() { HOSTILE_CAP } else { PASSIVE_CAP };
+            if counts[kind.is_hostile() as usize] >= cap {
+                continue;
+            }
+            let spot = (0..SPAWN_ATTEMPTS).find_map(|_| self.spawn_spot(world, kind, center));
+            if let Some(spot) = spot {
+                let seed = self.random.next();
+                spawn(ecs, kind, spot, seed);
+                counts[kind.is_hostile() as usize] += 1;
+            }
+        }
+    }
+
+    // Picks a spot at random around `center` and looks down from there
+    // for somewhere `kind` could stand that has the right light.
+    fn spawn_spot(&mut self, world: &World, kind: MobKind, center: Point3<f32>) -> Option<Point3<f32>> {
+        let angle = self.random.float() * TAU;
+        let distance = self.random.range(SPAWN_DISTANCE);
+        let x = (center.x + angle.cos() * distance).floor() as i32;
+        let z = (center.z + angle.sin() * distance).floor() as i32;
+        let top = (center.y + self.random.range((-SPAWN_HEIGHT_RANGE, SPAWN_HEIGHT_RANGE))).floor() as i32;
+        if !world.chunks.contains_key(&ChunkPos::from_block(x, top, z)) {
+            return None;
+        }
+
+        let (_, height) = kind.size();
+        let clearance = height.ceil() as i32;
+        let y = (top - 2 * SPAWN_HEIGHT_RANGE as i32..=top).rev()
+            .find(|&y| world.get_block(x, y - 1, z).is_opaque() && (y..y + clearance).all(|y| {
+                let block = world.get_block(x, y, z);
+                !block.is_solid() && !block.is_fluid()
+            }))?;
+
+        let light = light_level(world, x, y, z);
+        let suits = if kind.is_hostile() {
+            light <= HOSTILE_SPAWN_LIGHT
+        } else {
+            light >= PASSIVE_SPAWN_LIGHT && world.get_block(x, y - 1, z) == BlockId::GRASS
+        };
+        suits.then(|| Point3::new(x as f32 + 0.5, y as f32, z as f32 + 0.5))
+    }
+}
+
+// How brightly lit the spot is, with sky light as bright as the sky is
+// right now.
+fn light_level(world: &World, x: i32, y: i32, z: i32) -> u8 {
+    world.light(x, y, z).level(world.weather.daylight(sky::daylight(world.time_of_day())))
+}
+
+fn horizontal_distance(a: Point3<f32>, b: Point3<f32>) -> f32 {
+    Vector3::new(b.x - a.x, 0.0, b.z - a.z).magnitude()
+}
+
+// The yaw that faces from `from` towards `to`.
+fn heading_to(from: Point3<f32>, to: Point3<f32>) -> f32 {
+    (to.z - from.z).atan2(to.x - from.x)
+}
+
+// The same angle, brought into [-PI, PI).
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+// The layer of an entity texture, falling back on the first layer if a
+// resource pack took it away.
+fn texture(name: &str) -> u32 {
+    registry().entity_texture(name).unwrap_or(0)
+}
+
+fn zombie_skin() -> Skin {
+    let skin = texture("zombie");
+    let mut head = [skin ; 6];
+    head[FRONT] = texture("zombie_face");
+    Skin {
+        head,
+        torso: [texture("zombie_shirt") ; 6],
+        arm: [skin ; 6],
+        leg: [texture("zombie_pants") ; 6],
+    }
+}
+
+/// Adds the model of a `kind` mob to `mesh`, standing at the transform.
+pub fn push_model(mesh: &mut ChunkMesh, kind: MobKind, transform: &Transform, animation: Option<&PlayerAnimation>) {
+    match kind {
+        MobKind::Zombie => player_model::push_model(mesh, transform, animation, &zombie_skin(), true),
+        MobKind::Pig => push_pig(mesh, transform, animation),
+    }
+}
+
+// A body on four legs that swing in pairs, with the head out in front
+// along +x.
+fn push_pig(mesh: &mut ChunkMesh, transform: &Transform, animation: Option<&PlayerAnimation>) {
+    let stride = animation.map_or(0.0, PlayerAnimation::stride);
+    let root = Matrix4::from_translation(transform.position.to_vec())
+        * Matrix4::from_angle_y(Rad(-transform.yaw))
+        * Matrix4::from_scale(PIXEL);
+
+    let skin = [texture("pig") ; 6];
+    let mut head = skin;
+    head[FRONT] = texture("pig_face");
+
+    let leg_angle = stride * PIG_LEG_ANGLE;
+    // Each part is (textures, pivot, angle about the model's side axis,
+    // box minimum relative to the pivot, box size).
+    let parts = [
+        (skin, [0.0, 6.0, 0.0], 0.0, [-7.0, 0.0, -5.0], [14.0, 7.0, 10.0]),
+        (head, [6.0, 7.0, 0.0], 0.0, [0.0, 0.0, -3.5], [7.0, 7.0, 7.0]),
+        (skin, [4.5, 6.0, -3.0], leg_angle, [-2.0, -6.0, -2.0], [4.0, 6.0, 4.0]),
+        (skin, [4.5, 6.0, 3.0], -leg_angle, [-2.0, -6.0, -2.0], [4.0, 6.0, 4.0]),
+        (skin, [-4.5, 6.0, -3.0], -leg_angle, [-2.0, -6.0, -2.0], [4.0, 6.0, 4.0]),
+        (skin, [-4.5, 6.0, 3.0], leg_angle, [-2.0, -6.0, -2.0], [4.0, 6.0, 4.0]),
+    ];
+    for (textures, pivot, angle, min, size) in parts {
+        let model = root
+            * Matrix4::from_translation(pivot.into())
+            * Matrix4::from_angle_z(Rad(angle))
+            * Matrix4::from_translation(min.into())
+            * Matrix4::from_nonuniform_scale(size[0], size[1], size[2]);
+        mesh.push_transformed_box(textures, model);
+    }
+}
@@ -0,0 +1,161 @@
+//! Multiplayer over TCP. The server (`--server`) owns the world and runs
+//! headless on the same world, chunk and save code the game uses; clients
+//! (`--connect`) receive chunks and block updates from it instead of
+//! loading their own, and only render and simulate their own player.
+
+mod client;
+mod interpolation;
+mod prediction;
+mod protocol;
+mod server;
+
+use std::{
+    io::{Read, Write},
+    marker::PhantomData,
+    net::{Shutdown, SocketAddr, TcpStream},
+    sync::mpsc,
+    time::Duration,
+};
+
+use log::{debug, warn};
+
+use crate::codec::Reader;
+
+pub use self::{client::Client, server::run_server};
+use self::protocol::Message;
+
+pub const DEFAULT_PORT: u16 = 24680;
+
+// Frames bigger than this are treated as a corrupt stream. A chunk in the
+// save format is at most a few hundred KiB.
+const MAX_FRAME_SIZE: usize = 4 * 1024 * 1024;
+
+/// Appends the default port to addresses that don't name one.
+pub fn with_default_port(address: &str) -> String {
+    if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:{DEFAULT_PORT}")
+    }
+}
+
+/// A message stream over a TCP socket. Reading and writing happen on their
+/// own threads, so neither a slow peer nor a half-received frame ever
+/// blocks the game loop. Each frame is a little-endian u32 length followed
+/// by one message.
+pub struct Connection<Out: Message, In: Message> {
+    address: SocketAddr,
+    outgoing: mpsc::Sender<Out>,
+    incoming: mpsc::Receiver<In>,
+    _messages: PhantomData<fn(Out) -> In>,
+}
+
+/// The peer closed the connection, or it broke.
+#[derive(Clone, Copy, Debug)]
+pub struct Disconnected;
+
+impl<Out: Message, In: Message> Connection<Out, In> {
+    pub fn new(stream: TcpStream) -> std::io::Result<Self> {
+        let address = stream.peer_addr()?;
+        // Block updates and movement are small and latency sensitive.
+        stream.set_nodelay(true)?;
+
+        let (outgoing, to_send) = mpsc::channel::<Out>();
+        let (received, incoming) = mpsc::channel::<In>();
+
+        let mut writer = stream.try_clone()?;
+        std::thread::Builder::new()
+            .name(format!("net-write-{address}"))
+            .spawn(move || {
+                let mut frame = Vec::new();
+                for message in to_send {
+                    frame.clear();
+                    frame.extend_from_slice(&[0 ; 4]);
+                    message.encode(&mut frame);
+                    let len = (frame.len() - 4) as u32;
+                    frame[..4].copy_from_slice(&len.to_le_bytes());
+                    if writer.write_all(&frame).is_err() {
+                        break;
+                    }
+                }
+                // Also ends the reader, so the other side of the channel
+                // sees the disconnect.
+                let _ = writer.shutdown(Shutdown::Both);
+            })?;
+
+        let mut reader = stream;
+        std::thread::Builder::new()
+            .name(format!("net-read-{address}"))
+            .spawn(move || {
+                let mut frame = Vec::new();
+                loop {
+                    let mut len = [0 ; 4];
+                    if reader.read_exact(&mut len).is_err() {
+                        break;
+                    }
+                    let len = u32::from_le_bytes(len) as usize;
+                    if len > MAX_FRAME_SIZE {
+                        warn!("{address} sent a frame of {len} bytes, disconnecting");
+                        break;
+                    }
+
+                    frame.resize(len, 0);
+                    if reader.read_exact(&mut frame).is_err() {
+                        break;
+                    }
+                    let mut message = Reader::new(&frame);
+                    let decoded = In::decode(&mut message).and_then(|decoded| match message.is_empty() {
+                        true => Ok(decoded),
+                        false => Err("trailing bytes after message".to_string()),
+                    });
+                    match decoded {
+                        Ok(message) => {
+                            if received.send(message).is_err() {
+                                break;
+                            }
+                        },
+                        Err(e) => {
+                            warn!("bad message from {address}: {e}");
+                            break;
+                        },
+                    }
+                }
+                debug!("connection to {address} closed");
+                let _ = reader.shutdown(Shutdown::Both);
+            })?;
+
+        Ok(Self {
+            address,
+            outgoing,
+            incoming,
+            _messages: PhantomData,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Queues a message. A broken connection is noticed on the next
+    /// `receive` instead of here.
+    pub fn send(&self, message: Out) {
+        let _ = self.outgoing.send(message);
+    }
+
+    /// The next message, if one has arrived.
+    pub fn receive(&self) -> Result<Option<In>, Disconnected> {
+        match self.incoming.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(Disconnected),
+        }
+    }
+
+    pub fn receive_timeout(&self, timeout: Duration) -> Result<Option<In>, Disconnected> {
+        match self.incoming.recv_timeout(timeout) {
+            Ok(message) => Ok(Some(message)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(Disconnected),
+        }
+    }
+}
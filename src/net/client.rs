@@ -0,0 +1,263 @@
+use std::{
+    collections::HashMap,
+    net::{TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use cgmath::Point3;
+
+use crate::{
+    block::{self, BlockState},
+    block_entity::BlockEntity,
+    chat::{ChatKind, ChatLine},
+    codec::Reader,
+    ecs::{Ecs, Entity, MeshRef, Transform},
+    error::{EngineError, Result},
+    events::Event,
+    game_mode::GameMode,
+    player_model::PlayerAnimation,
+    save,
+    world::World,
+    worldgen::WorldType,
+};
+
+use super::{
+    interpolation::{InterpolationBuffer, INTERPOLATION_DELAY},
+    prediction::Prediction,
+    protocol::{ClientMessage, ServerMessage, PROTOCOL_VERSION},
+    Connection,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// Movement is sent at the server's tick rate, not the frame rate.
+const MOVE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What the server tells a joining player.
+pub struct Welcome {
+    pub seed: u32,
+    pub world_type: WorldType,
+    pub game_mode: GameMode,
+    pub time: u64,
+    pub spawn: Point3<f32>,
+    pub border: u32,
+}
+
+struct RemotePlayer {
+    entity: Entity,
+    name: String,
+    movement: InterpolationBuffer,
+}
+
+/// A connection to a dedicated server. The server is authoritative for
+/// the world and for movement: edits and the local player's moves are
+/// applied right away and sent along, and whatever the server sends back
+/// wins.
+pub struct Client {
+    connection: Connection<ClientMessage, ServerMessage>,
+    remote_players: HashMap<u32, RemotePlayer>,
+    prediction: Prediction,
+    last_move: Option<Instant>,
+    // Local time minus server time, in milliseconds. The smallest seen is
+    // kept, which is the estimate least inflated by network delay.
+    clock_offset: i64,
+    started: Instant,
+    // The server said everyone is in bed, until taken.
+    fall_asleep: bool,
+}
+
+impl Client {
+    /// Connects and waits for the server to accept the player.
+    pub fn connect(address: &str, name: &str, view_distance: i32, vertical_distance: i32) -> Result<(Self, Welcome)> {
+        let network_error = |source| EngineError::Network { address: address.to_string(), source };
+        let socket_address = address.to_socket_addrs()
+            .map_err(network_error)?
+            .next()
+            .ok_or_else(|| network_error(std::io::ErrorKind::NotFound.into()))?;
+        let stream = TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT).map_err(network_error)?;
+        let connection = Connection::new(stream).map_err(network_error)?;
+
+        connection.send(ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            blocks: block::registry().names_hash(),
+            name: name.to_string(),
+            view_distance: view_distance.clamp(1, u8::MAX as i32) as u8,
+            vertical_distance: vertical_distance.clamp(1, u8::MAX as i32) as u8,
+        });
+        let lost = || EngineError::Disconnected("the connection closed during login".to_string());
+        let started = Instant::now();
+        let (welcome, server_time) = match connection.receive_timeout(HANDSHAKE_TIMEOUT).map_err(|_| lost())? {
+            Some(ServerMessage::Welcome { seed, world_type, game_mode, time, spawn, border, server_time, .. }) => {
+                (Welcome { seed, world_type, game_mode, time, spawn: spawn.into(), border }, server_time)
+            },
+            Some(ServerMessage::Disconnect { reason }) => return Err(EngineError::Disconnected(reason)),
+            Some(_) => return Err(EngineError::Disconnected("unexpected message during login".to_string())),
+            None => return Err(EngineError::Disconnected("the server did not answer".to_string())),
+        };
+
+        let mut client = Self {
+            connection,
+            remote_players: HashMap::new(),
+            prediction: Prediction::default(),
+            last_move: None,
+            clock_offset: i64::MAX,
+            started,
+            fall_asleep: false,
+        };
+        client.sync_clock(server_time);
+        Ok((client, welcome))
+    }
+
+    pub fn send_block(&self, [x, y, z]: [i32 ; 3], state: BlockState) {
+        self.connection.send(ClientMessage::SetBlock { block: [x, y, z], state });
+    }
+
+    pub fn send_block_entity(&self, block: [i32 ; 3], entity: &BlockEntity) {
+        let mut data = Vec::new();
+        entity.encode(&mut data);
+        self.connection.send(ClientMessage::SetBlockEntity { block, data });
+    }
+
+    pub fn send_chat(&self, text: &str) {
+        self.connection.send(ClientMessage::Chat { text: text.to_string() });
+    }
+
+    pub fn send_whisper(&self, to: &str, text: &str) {
+        self.connection.send(ClientMessage::Whisper { to: to.to_string(), text: text.to_string() });
+    }
+
+    pub fn send_sleep(&self, block: [i32 ; 3]) {
+        self.connection.send(ClientMessage::Sleep { block });
+    }
+
+    pub fn send_get_up(&self) {
+        self.connection.send(ClientMessage::GetUp);
+    }
+
+    /// Whether everyone got into bed since the last call, so the night is
+    /// about to be skipped.
+    pub fn take_fall_asleep(&mut self) -> bool {
+        std::mem::take(&mut self.fall_asleep)
+    }
+
+    /// The name and entity of every other player on the server.
+    pub fn remote_players(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.remote_players.values().map(|remote| (remote.name.as_str(), remote.entity))
+    }
+
+    pub fn send_move(&mut self, position: Point3<f32>, yaw: f32, pitch: f32) {
+        if self.last_move.is_some_and(|last| last.elapsed() < MOVE_INTERVAL) {
+            return;
+        }
+        self.last_move = Some(Instant::now());
+        let sequence = self.prediction.record(position);
+        self.connection.send(ClientMessage::Move { sequence, position: position.into(), yaw, pitch });
+    }
+
+    fn local_time(&self) -> i64 {
+        self.started.elapsed().as_millis() as i64
+    }
+
+    fn sync_clock(&mut self, server_time: u64) {
+        self.clock_offset = self.clock_offset.min(self.local_time() - server_time as i64);
+    }
+
+    /// Applies everything the server sent since the last call, correcting
+    /// the local `player` if the server disagreed with its movement, and
+    /// moves remote players along their interpolated paths. Returns chat
+    /// and lines such as players joining.
+    pub fn update(&mut self, world: &mut World, ecs: &mut Ecs, player: Entity) -> Result<Vec<ChatLine>> {
+        let mut chat = Vec::new();
+        loop {
+            let message = match self.connection.receive() {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(_) => return Err(EngineError::Disconnected("connection lost".to_string())),
+            };
+
+            match message {
+                ServerMessage::Welcome { .. } => (),
+                ServerMessage::Disconnect { reason } => return Err(EngineError::Disconnected(reason)),
+                ServerMessage::Chunk { pos, data } => match save::decode_chunk(pos, &data) {
+                    Ok(chunk) => {
+                        // A resent chunk replaces the old copy wholesale.
+                        world.unload_chunk(pos);
+                        world.insert_chunk(chunk, Vec::new(), false);
+                        ecs.events.publish(Event::ChunkLoaded { pos });
+                    },
+                    Err(e) => return Err(EngineError::Disconnected(format!("bad chunk data: {e}"))),
+                },
+                ServerMessage::UnloadChunk { pos } => {
+                    world.unload_chunk(pos);
+                },
+                ServerMessage::BlockUpdate { block: [x, y, z], state, level } => {
+                    world.set_voxel(x, y, z, state, level);
+                },
+                ServerMessage::PlayerJoined { id, name, position } => {
+                    let entity = ecs.spawn();
+                    ecs.transforms.insert(entity, Transform::new(position.into()));
+                    ecs.meshes.insert(entity, MeshRef::Player);
+                    ecs.animations.insert(entity, PlayerAnimation::default());
+                    chat.push(ChatLine::new(ChatKind::Notice, format!("{name} joined the game")));
+                    let remote = RemotePlayer { entity, name, movement: InterpolationBuffer::default() };
+                    if let Some(old) = self.remote_players.insert(id, remote) {
+                        ecs.despawn(old.entity);
+                    }
+                },
+                ServerMessage::PlayerLeft { id } => {
+                    if let Some(remote) = self.remote_players.remove(&id) {
+                        ecs.despawn(remote.entity);
+                        chat.push(ChatLine::new(ChatKind::Notice, format!("{} left the game", remote.name)));
+                    }
+                },
+                ServerMessage::PlayerMoved { id, server_time, position, yaw, .. } => {
+                    self.sync_clock(server_time);
+                    if let Some(remote) = self.remote_players.get_mut(&id) {
+                        remote.movement.push(server_time, position.into(), yaw);
+                    }
+                },
+                ServerMessage::MoveAck { sequence, position } => {
+                    let correction = self.prediction.reconcile(sequence, position.into());
+                    if let (Some(correction), Some(transform)) = (correction, ecs.transforms.get_mut(player)) {
+                        transform.position += correction;
+                    }
+                },
+                ServerMessage::Chat { from, text } => {
+                    chat.push(ChatLine::new(ChatKind::Message, format!("<{from}> {text}")));
+                },
+                ServerMessage::Whisper { from, to, text } => {
+                    chat.push(ChatLine::new(ChatKind::Whisper, format!("[{from} -> {to}] {text}")));
+                },
+                ServerMessage::Notice { text } => chat.push(ChatLine::new(ChatKind::Notice, text)),
+                ServerMessage::BlockEntity { block: [x, y, z], data } => match BlockEntity::decode(&mut Reader::new(&data)) {
+                    Ok(entity) => {
+                        world.set_block_entity(x, y, z, entity);
+                    },
+                    Err(e) => return Err(EngineError::Disconnected(format!("bad block entity data: {e}"))),
+                },
+                // The server keeps the clock; the remaining time is never
+                // run down here.
+                ServerMessage::Weather { weather } => world.weather.set(weather, Some(f32::INFINITY)),
+                ServerMessage::Time { time, speed, cycle } => {
+                    world.time = time;
+                    world.time_speed = speed;
+                    world.daylight_cycle = cycle;
+                },
+                ServerMessage::FallAsleep => self.fall_asleep = true,
+            }
+        }
+
+        let render_time = (self.local_time() - self.clock_offset).max(0) as u64;
+        let render_time = render_time.saturating_sub(INTERPOLATION_DELAY);
+        for remote in self.remote_players.values_mut() {
+            let (Some((position, yaw)), Some(transform)) = (remote.movement.sample(render_time), ecs.transforms.get_mut(remote.entity)) else {
+                continue;
+            };
+            // Already smoothed between updates from the server.
+            transform.teleport(position);
+            transform.yaw = yaw;
+        }
+        Ok(chat)
+    }
+}
+
@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+
+use cgmath::{EuclideanSpace, Point3, VectorSpace};
+
+/// How far behind the newest update remote entities are drawn, in
+/// milliseconds of server time. Two server ticks, so there's nearly always
+/// a later snapshot to move towards even if one arrives late.
+pub const INTERPOLATION_DELAY: u64 = 100;
+// Past the newest snapshot an entity keeps going for this long before it
+// stops, which hides a single lost or late update.
+const MAX_EXTRAPOLATION: u64 = 100;
+const MAX_SNAPSHOTS: usize = 32;
+
+#[derive(Clone, Copy, Debug)]
+struct Snapshot {
+    time: u64,
+    position: Point3<f32>,
+    yaw: f32,
+}
+
+/// Timestamped updates for one remote entity, replayed a little in the
+/// past so its motion is smooth however unevenly the updates arrive.
+#[derive(Default)]
+pub struct InterpolationBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl InterpolationBuffer {
+    /// Adds an update stamped with the server time it was sent at. Late
+    /// arrivals older than the newest snapshot are dropped.
+    pub fn push(&mut self, time: u64, position: Point3<f32>, yaw: f32) {
+        if self.snapshots.back().is_some_and(|last| last.time >= time) {
+            return;
+        }
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot { time, position, yaw });
+    }
+
+    /// Where the entity was at server time `time`.
+    pub fn sample(&mut self, time: u64) -> Option<(Point3<f32>, f32)> {
+        // Keep one snapshot at or before `time` to interpolate from.
+        while self.snapshots.len() > 2 && self.snapshots[1].time <= time {
+            self.snapshots.pop_front();
+        }
+
+        let (from, to) = match (self.snapshots.front(), self.snapshots.get(1)) {
+            (Some(&from), Some(&to)) => (from, to),
+            (Some(&only), None) => return Some((only.position, only.yaw)),
+            _ => return None,
+        };
+        if time <= from.time {
+            return Some((from.position, from.yaw));
+        }
+
+        // Beyond `to` the same formula extrapolates, up to a limit.
+        let time = time.min(to.time + MAX_EXTRAPOLATION);
+        let t = (time - from.time) as f32 / (to.time - from.time) as f32;
+        let position = Point3::from_vec(from.position.to_vec().lerp(to.position.to_vec(), t));
+        Some((position, lerp_angle(from.yaw, to.yaw, t)))
+    }
+}
+
+// Turns the short way round.
+fn lerp_angle(from: f32, to: f32, t: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+
+    let delta = (to - from + PI).rem_euclid(TAU) - PI;
+    from + delta * t
+}
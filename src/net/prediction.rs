@@ -0,0 +1,53 @@
+use std::collections::VecDeque;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+
+// Enough for a couple of seconds of moves at the send rate; anything older
+// than that has been acked or lost.
+const MAX_HISTORY: usize = 64;
+// Float noise and rounding on the server are not worth a visible snap.
+const TOLERANCE: f32 = 0.01;
+
+/// The local player's movement runs ahead of the server. Each move sent is
+/// remembered with its sequence number, and when the server acks one with
+/// the position it accepted, any difference is applied to where the player
+/// is now.
+#[derive(Default)]
+pub struct Prediction {
+    next_sequence: u32,
+    history: VecDeque<(u32, Point3<f32>)>,
+}
+
+impl Prediction {
+    /// Remembers a predicted position and returns the sequence number to
+    /// send it with.
+    pub fn record(&mut self, position: Point3<f32>) -> u32 {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+
+        if self.history.len() == MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back((sequence, position));
+        sequence
+    }
+
+    /// Takes the server's verdict on move `sequence`, returning how far the
+    /// player has to be moved to agree with it, if at all.
+    pub fn reconcile(&mut self, sequence: u32, server_position: Point3<f32>) -> Option<Vector3<f32>> {
+        // Acks arrive in order, so everything up to this one is settled.
+        let index = self.history.iter().position(|&(recorded, _)| recorded == sequence)?;
+        let (_, predicted) = self.history[index];
+        self.history.drain(..=index);
+
+        let error = server_position - predicted;
+        if error.magnitude() <= TOLERANCE {
+            return None;
+        }
+        // The moves still in flight carry on from the refused position, so
+        // the server will correct them as well; those acks are already
+        // accounted for by this correction.
+        self.history.clear();
+        Some(error)
+    }
+}
@@ -0,0 +1,331 @@
+use crate::{
+    block::{BlockId, BlockState, Properties},
+    chunk::ChunkPos,
+    codec::{Reader, Result, Writer},
+    game_mode::GameMode,
+    weather::Weather,
+    worldgen::WorldType,
+};
+
+/// Bumped whenever a message changes; client and server must match.
+pub const PROTOCOL_VERSION: u16 = 15;
+
+/// Something that can be sent in a frame.
+pub trait Message: Sized + Send + 'static {
+    fn encode(&self, bytes: &mut Vec<u8>);
+    fn decode(reader: &mut Reader) -> Result<Self>;
+}
+
+#[derive(Clone, Debug)]
+pub enum ClientMessage {
+    /// The first message on a connection. `blocks` is the registry's
+    /// `names_hash`, as blocks are sent by id.
+    Hello { version: u16, blocks: u64, name: String, view_distance: u8, vertical_distance: u8 },
+    /// Where the client predicts its player is; the server acks each
+    /// `sequence` with the position it accepted.
+    Move { sequence: u32, position: [f32 ; 3], yaw: f32, pitch: f32 },
+    SetBlock { block: [i32 ; 3], state: BlockState },
+    Chat { text: String },
+    /// A chat message only the named player sees.
+    Whisper { to: String, text: String },
+    /// New contents for a block entity, such as a chest, in the save file
+    /// format. Only the items are taken; the server keeps the rest, like
+    /// how far a furnace has got.
+    SetBlockEntity { block: [i32 ; 3], data: Vec<u8> },
+    /// The player lay down in the bed at `block` to sleep.
+    Sleep { block: [i32 ; 3] },
+    /// The player got out of bed before the night was skipped.
+    GetUp,
+}
+
+#[derive(Clone, Debug)]
+pub enum ServerMessage {
+    /// The answer to `Hello`; the world can be regenerated from the seed
+    /// for distant terrain, but blocks come from `Chunk` messages.
+    /// `server_time` is the server's clock in milliseconds, which
+    /// `PlayerMoved` is stamped with.
+    Welcome { player_id: u32, seed: u32, world_type: WorldType, game_mode: GameMode, time: u64, spawn: [f32 ; 3], border: u32, server_time: u64 },
+    Disconnect { reason: String },
+    /// A chunk in the save file format.
+    Chunk { pos: ChunkPos, data: Vec<u8> },
+    UnloadChunk { pos: ChunkPos },
+    BlockUpdate { block: [i32 ; 3], state: BlockState, level: u8 },
+    PlayerJoined { id: u32, name: String, position: [f32 ; 3] },
+    PlayerLeft { id: u32 },
+    PlayerMoved { id: u32, server_time: u64, position: [f32 ; 3], yaw: f32, pitch: f32 },
+    /// The newest of the client's moves the server has processed, and
+    /// where that left the player.
+    MoveAck { sequence: u32, position: [f32 ; 3] },
+    Chat { from: String, text: String },
+    /// Sent to both ends of a whisper.
+    Whisper { from: String, to: String, text: String },
+    /// A message from the server itself, such as why a whisper failed.
+    Notice { text: String },
+    /// A block entity another player or a furnace burning changed, in the
+    /// save file format.
+    BlockEntity { block: [i32 ; 3], data: Vec<u8> },
+    /// The weather now, sent on joining and whenever it changes.
+    Weather { weather: Weather },
+    /// The world's clock, sent on joining and every so often after, so
+    /// clients running it themselves don't drift.
+    Time { time: u64, speed: f32, cycle: bool },
+    /// Everyone is in bed, so the night is about to be skipped; clients
+    /// fade out, and the `Time` that follows wakes them up.
+    FallAsleep,
+}
+
+impl Message for ClientMessage {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match self {
+            ClientMessage::Hello { version, blocks, name, view_distance, vertical_distance } => {
+                bytes.put_u8(0);
+                bytes.put_u16(*version);
+                bytes.put_u64(*blocks);
+                bytes.put_string(name);
+                bytes.put_u8(*view_distance);
+                bytes.put_u8(*vertical_distance);
+            },
+            ClientMessage::Move { sequence, position, yaw, pitch } => {
+                bytes.put_u8(1);
+                bytes.put_u32(*sequence);
+                put_vec3(bytes, *position);
+                bytes.put_f32(*yaw);
+                bytes.put_f32(*pitch);
+            },
+            ClientMessage::SetBlock { block, state } => {
+                bytes.put_u8(2);
+                put_block_pos(bytes, *block);
+                put_block_state(bytes, *state);
+            },
+            ClientMessage::Chat { text } => {
+                bytes.put_u8(3);
+                bytes.put_string(text);
+            },
+            ClientMessage::Whisper { to, text } => {
+                bytes.put_u8(4);
+                bytes.put_string(to);
+                bytes.put_string(text);
+            },
+            ClientMessage::SetBlockEntity { block, data } => {
+                bytes.put_u8(5);
+                put_block_pos(bytes, *block);
+                bytes.put_bytes(data);
+            },
+            ClientMessage::Sleep { block } => {
+                bytes.put_u8(6);
+                put_block_pos(bytes, *block);
+            },
+            ClientMessage::GetUp => bytes.put_u8(7),
+        }
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self> {
+        Ok(match reader.u8()? {
+            0 => ClientMessage::Hello {
+                version: reader.u16()?,
+                blocks: reader.u64()?,
+                name: reader.string()?,
+                view_distance: reader.u8()?,
+                vertical_distance: reader.u8()?,
+            },
+            1 => ClientMessage::Move {
+                sequence: reader.u32()?,
+                position: vec3(reader)?,
+                yaw: reader.f32()?,
+                pitch: reader.f32()?,
+            },
+            2 => ClientMessage::SetBlock { block: block_pos(reader)?, state: block_state(reader)? },
+            3 => ClientMessage::Chat { text: reader.string()? },
+            4 => ClientMessage::Whisper { to: reader.string()?, text: reader.string()? },
+            5 => ClientMessage::SetBlockEntity { block: block_pos(reader)?, data: reader.bytes()?.to_vec() },
+            6 => ClientMessage::Sleep { block: block_pos(reader)? },
+            7 => ClientMessage::GetUp,
+            tag => return Err(format!("unknown client message {tag}")),
+        })
+    }
+}
+
+impl Message for ServerMessage {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match self {
+            ServerMessage::Welcome { player_id, seed, world_type, game_mode, time, spawn, border, server_time } => {
+                bytes.put_u8(0);
+                bytes.put_u32(*player_id);
+                bytes.put_u32(*seed);
+                bytes.put_u8(world_type.id());
+                bytes.put_u8(game_mode.id());
+                bytes.put_u64(*time);
+                put_vec3(bytes, *spawn);
+                bytes.put_u32(*border);
+                bytes.put_u64(*server_time);
+            },
+            ServerMessage::Disconnect { reason } => {
+                bytes.put_u8(1);
+                bytes.put_string(reason);
+            },
+            ServerMessage::Chunk { pos, data } => {
+                bytes.put_u8(2);
+                put_chunk_pos(bytes, *pos);
+                bytes.put_bytes(data);
+            },
+            ServerMessage::UnloadChunk { pos } => {
+                bytes.put_u8(3);
+                put_chunk_pos(bytes, *pos);
+            },
+            ServerMessage::BlockUpdate { block, state, level } => {
+                bytes.put_u8(4);
+                put_block_pos(bytes, *block);
+                put_block_state(bytes, *state);
+                bytes.put_u8(*level);
+            },
+            ServerMessage::PlayerJoined { id, name, position } => {
+                bytes.put_u8(5);
+                bytes.put_u32(*id);
+                bytes.put_string(name);
+                put_vec3(bytes, *position);
+            },
+            ServerMessage::PlayerLeft { id } => {
+                bytes.put_u8(6);
+                bytes.put_u32(*id);
+            },
+            ServerMessage::PlayerMoved { id, server_time, position, yaw, pitch } => {
+                bytes.put_u8(7);
+                bytes.put_u32(*id);
+                bytes.put_u64(*server_time);
+                put_vec3(bytes, *position);
+                bytes.put_f32(*yaw);
+                bytes.put_f32(*pitch);
+            },
+            ServerMessage::MoveAck { sequence, position } => {
+                bytes.put_u8(8);
+                bytes.put_u32(*sequence);
+                put_vec3(bytes, *position);
+            },
+            ServerMessage::Chat { from, text } => {
+                bytes.put_u8(9);
+                bytes.put_string(from);
+                bytes.put_string(text);
+            },
+            ServerMessage::Whisper { from, to, text } => {
+                bytes.put_u8(10);
+                bytes.put_string(from);
+                bytes.put_string(to);
+                bytes.put_string(text);
+            },
+            ServerMessage::Notice { text } => {
+                bytes.put_u8(11);
+                bytes.put_string(text);
+            },
+            ServerMessage::BlockEntity { block, data } => {
+                bytes.put_u8(12);
+                put_block_pos(bytes, *block);
+                bytes.put_bytes(data);
+            },
+            ServerMessage::Weather { weather } => {
+                bytes.put_u8(13);
+                bytes.put_u8(weather.id());
+            },
+            ServerMessage::Time { time, speed, cycle } => {
+                bytes.put_u8(14);
+                bytes.put_u64(*time);
+                bytes.put_f32(*speed);
+                bytes.put_u8(*cycle as u8);
+            },
+            ServerMessage::FallAsleep => bytes.put_u8(15),
+        }
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self> {
+        Ok(match reader.u8()? {
+            0 => ServerMessage::Welcome {
+                player_id: reader.u32()?,
+                seed: reader.u32()?,
+                world_type: world_type(reader)?,
+                game_mode: game_mode(reader)?,
+                time: reader.u64()?,
+                spawn: vec3(reader)?,
+                border: reader.u32()?,
+                server_time: reader.u64()?,
+            },
+            1 => ServerMessage::Disconnect { reason: reader.string()? },
+            2 => ServerMessage::Chunk { pos: chunk_pos(reader)?, data: reader.bytes()?.to_vec() },
+            3 => ServerMessage::UnloadChunk { pos: chunk_pos(reader)? },
+            4 => ServerMessage::BlockUpdate { block: block_pos(reader)?, state: block_state(reader)?, level: reader.u8()? },
+            5 => ServerMessage::PlayerJoined { id: reader.u32()?, name: reader.string()?, position: vec3(reader)? },
+            6 => ServerMessage::PlayerLeft { id: reader.u32()? },
+            7 => ServerMessage::PlayerMoved {
+                id: reader.u32()?,
+                server_time: reader.u64()?,
+                position: vec3(reader)?,
+                yaw: reader.f32()?,
+                pitch: reader.f32()?,
+            },
+            8 => ServerMessage::MoveAck { sequence: reader.u32()?, position: vec3(reader)? },
+            9 => ServerMessage::Chat { from: reader.string()?, text: reader.string()? },
+            10 => ServerMessage::Whisper { from: reader.string()?, to: reader.string()?, text: reader.string()? },
+            11 => ServerMessage::Notice { text: reader.string()? },
+            12 => ServerMessage::BlockEntity { block: block_pos(reader)?, data: reader.bytes()?.to_vec() },
+            13 => ServerMessage::Weather { weather: weather(reader)? },
+            14 => ServerMessage::Time { time: reader.u64()?, speed: reader.f32()?, cycle: reader.u8()? != 0 },
+            15 => ServerMessage::FallAsleep,
+            tag => return Err(format!("unknown server message {tag}")),
+        })
+    }
+}
+
+fn put_vec3(bytes: &mut Vec<u8>, value: [f32 ; 3]) {
+    value.iter().for_each(|&v| bytes.put_f32(v));
+}
+
+fn vec3(reader: &mut Reader) -> Result<[f32 ; 3]> {
+    Ok([reader.f32()?, reader.f32()?, reader.f32()?])
+}
+
+fn put_block_pos(bytes: &mut Vec<u8>, value: [i32 ; 3]) {
+    value.iter().for_each(|&v| bytes.put_i32(v));
+}
+
+fn block_pos(reader: &mut Reader) -> Result<[i32 ; 3]> {
+    Ok([reader.i32()?, reader.i32()?, reader.i32()?])
+}
+
+fn put_chunk_pos(bytes: &mut Vec<u8>, pos: ChunkPos) {
+    bytes.put_i32(pos.x);
+    bytes.put_i32(pos.y);
+    bytes.put_i32(pos.z);
+}
+
+fn chunk_pos(reader: &mut Reader) -> Result<ChunkPos> {
+    Ok(ChunkPos::new(reader.i32()?, reader.i32()?, reader.i32()?))
+}
+
+fn put_block_state(bytes: &mut Vec<u8>, state: BlockState) {
+    bytes.put_u16(state.block.0);
+    bytes.put_u8(state.properties.bits());
+}
+
+// Ids are only meaningful between games with the same blocks, which the
+// handshake checks, but a bad one would still index out of the block
+// table.
+fn block_state(reader: &mut Reader) -> Result<BlockState> {
+    let id = BlockId(reader.u16()?);
+    if id.0 as usize >= crate::block::registry().blocks.len() {
+        return Err(format!("unknown block id {}", id.0));
+    }
+    Ok(BlockState::new(id, Properties::from_bits(reader.u8()?)))
+}
+
+fn world_type(reader: &mut Reader) -> Result<WorldType> {
+    let id = reader.u8()?;
+    WorldType::from_id(id).ok_or_else(|| format!("unknown world type {id}"))
+}
+
+fn game_mode(reader: &mut Reader) -> Result<GameMode> {
+    let id = reader.u8()?;
+    GameMode::from_id(id).ok_or_else(|| format!("unknown game mode {id}"))
+}
+
+fn weather(reader: &mut Reader) -> Result<Weather> {
+    let id = reader.u8()?;
+    Weather::from_id(id).ok_or_else(|| format!("unknown weather {id}"))
+}
@@ -0,0 +1,676 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::TcpListener,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+
+use crate::{
+    block::{self, BlockId},
+    block_entity::BlockEntity,
+    chunk::ChunkPos,
+    codec::Reader,
+    config::Config,
+    error::{EngineError, Result},
+    events::{Event, EventBus},
+    falling,
+    fluid::FluidSimulator,
+    furnace,
+    save::{self, ChunkIo, LevelData, WorldSave},
+    scripting::Mods,
+    signal::SignalSimulator,
+    sleep,
+    world::World,
+};
+
+use super::{
+    protocol::{ClientMessage, ServerMessage, PROTOCOL_VERSION},
+    Connection,
+};
+
+// Ticks the server may fall behind before it gives up catching up and
+// carries on from now.
+const MAX_TICKS_BEHIND: u32 = 10;
+// Players can ask for less, but not more.
+const MAX_VIEW_DISTANCE: i32 = 12;
+// Chunks sent to one client per tick, so a join or teleport doesn't queue
+// up seconds of data ahead of everything else.
+const CHUNKS_PER_TICK: usize = 16;
+// A chunk with more changes than this in one tick is resent whole instead.
+const MAX_UPDATES_PER_CHUNK: usize = 64;
+// Clients run the world's clock themselves; it's corrected this often.
+const TIME_SYNC_INTERVAL: Duration = Duration::from_secs(10);
+// Fastest a player can legitimately move: flying sideways, and falling at
+// terminal velocity. Moves beyond this are refused.
+const MAX_HORIZONTAL_SPEED: f32 = 12.0;
+const MAX_VERTICAL_SPEED: f32 = 60.0;
+// How much unused movement a player can save up, in seconds, so moves
+// that arrive bunched together after a lag spike still pass.
+const MAX_SAVED_MOVEMENT: f32 = 1.0;
+// In characters, after trimming.
+const MAX_CHAT_LENGTH: usize = 256;
+
+type ServerConnection = Connection<ServerMessage, ClientMessage>;
+
+// Distance a player may still move, refilled over time at the speed
+// limits.
+struct MoveBudget {
+    horizontal: f32,
+    vertical: f32,
+    refilled: Instant,
+}
+
+impl MoveBudget {
+    fn new() -> Self {
+        Self {
+            horizontal: MAX_HORIZONTAL_SPEED * MAX_SAVED_MOVEMENT,
+            vertical: MAX_VERTICAL_SPEED * MAX_SAVED_MOVEMENT,
+            refilled: Instant::now(),
+        }
+    }
+
+    // Whether moving `from` `to` is within the budget; if so it's spent.
+    fn spend(&mut self, from: [f32 ; 3], to: [f32 ; 3]) -> bool {
+        let dt = self.refilled.elapsed().as_secs_f32();
+        self.refilled = Instant::now();
+        self.horizontal = (self.horizontal + MAX_HORIZONTAL_SPEED * dt).min(MAX_HORIZONTAL_SPEED * MAX_SAVED_MOVEMENT);
+        self.vertical = (self.vertical + MAX_VERTICAL_SPEED * dt).min(MAX_VERTICAL_SPEED * MAX_SAVED_MOVEMENT);
+
+        let horizontal = (to[0] - from[0]).hypot(to[2] - from[2]);
+        let vertical = (to[1] - from[1]).abs();
+        if horizontal > self.horizontal || vertical > self.vertical {
+            return false;
+        }
+        self.horizontal -= horizontal;
+        self.vertical -= vertical;
+        true
+    }
+}
+
+struct RemotePlayer {
+    connection: ServerConnection,
+    // Set by `Hello`; until then the client gets nothing else.
+    name: Option<String>,
+    view_distance: i32,
+    vertical_distance: i32,
+    position: [f32 ; 3],
+    yaw: f32,
+    pitch: f32,
+    budget: MoveBudget,
+    // The newest move to ack and relay this tick.
+    last_sequence: Option<u32>,
+    // Chunks the client has, which it receives updates for.
+    sent: HashSet<ChunkPos>,
+    // Lying in a bed, waiting for everyone else to be.
+    in_bed: bool,
+}
+
+impl RemotePlayer {
+    fn chunk(&self) -> ChunkPos {
+        let [x, y, z] = self.position.map(|v| v.floor() as i32);
+        ChunkPos::from_block(x, y, z)
+    }
+
+    // Whether `pos` is in the player's view, give or take `margin` chunks
+    // each way, matching how the client streams.
+    fn sees(&self, pos: ChunkPos, margin: i32) -> bool {
+        let (across, up) = pos.distance(self.chunk());
+        across <= self.view_distance + margin && up <= self.vertical_distance + margin
+    }
+}
+
+/// The dedicated server: owns the world, streams it to every connected
+/// player and relays their edits and movement to each other.
+struct Server {
+    listener: TcpListener,
+    world: World,
+    world_save: WorldSave,
+    chunk_io: ChunkIo,
+    fluids: FluidSimulator,
+    signals: SignalSimulator,
+    players: HashMap<u32, RemotePlayer>,
+    next_id: u32,
+    started: Instant,
+    tick_interval: Duration,
+    last_time_sync: Instant,
+    mods: Mods,
+    events: EventBus,
+    // Seconds since everyone got into bed, while their screens fade out
+    // before the night is skipped.
+    night_skip: Option<f32>,
+}
+
+/// Runs a headless server on `address` with `mods` until the process is
+/// killed. The world is autosaved every minute.
+pub fn run_server(settings: &Config, address: &str, mods: Mods) -> Result<()> {
+    let listener = TcpListener::bind(address)
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|source| EngineError::Network { address: address.to_string(), source })?;
+
+    let world_save = WorldSave::new(&settings.world.name);
+    if let Some(backup) = world_save.upgrade()? {
+        info!("backed up the world from another version of the game as {}", backup.file_name());
+    }
+    let level = world_save.load_level()?;
+    let mut new_world = settings.world.clone();
+    mods.apply_worldgen(&mut new_world);
+    let world_settings = crate::config::WorldConfig {
+        seed: level.as_ref().map_or(new_world.seed, |level| level.seed),
+        world_type: level.as_ref().map_or(new_world.world_type, |level| level.world_type),
+        game_mode: level.as_ref().map_or(new_world.game_mode, |level| level.game_mode),
+        ..new_world
+    };
+    let mut world = World::new(&world_settings);
+    if let Some(level) = &level {
+        world.time = level.time;
+        world.time_speed = level.time_speed;
+        world.daylight_cycle = level.daylight_cycle;
+        world.spawn_point = level.spawn_point.unwrap_or(world.spawn_point);
+        world.weather.set(level.weather, level.weather_remaining);
+        world.border = level.border.unwrap_or_default();
+    }
+    world.record_changes();
+
+    let chunk_io = ChunkIo::new(world_save.clone(), world.generator.clone());
+    let mut server = Server {
+        listener,
+        world,
+        world_save,
+        chunk_io,
+        fluids: FluidSimulator::default(),
+        signals: SignalSimulator::default(),
+        players: HashMap::new(),
+        next_id: 0,
+        started: Instant::now(),
+        tick_interval: settings.simulation.tick_interval(),
+        last_time_sync: Instant::now(),
+        mods,
+        events: EventBus::default(),
+        night_skip: None,
+    };
+    info!("serving world `{}` on {address}", settings.world.name);
+
+    // Ticks are scheduled on a fixed grid, so a slow one is made up for
+    // by the next ones coming sooner.
+    let mut last_save = Instant::now();
+    let mut next_tick = Instant::now();
+    loop {
+        server.tick();
+
+        if last_save.elapsed() >= save::AUTOSAVE_INTERVAL && server.save() {
+            last_save = Instant::now();
+        }
+        next_tick += server.tick_interval;
+        let now = Instant::now();
+        if now > next_tick + server.tick_interval * MAX_TICKS_BEHIND {
+            next_tick = now;
+        }
+        if let Some(rest) = next_tick.checked_duration_since(now) {
+            std::thread::sleep(rest);
+        }
+    }
+}
+
+fn time_message(world: &World) -> ServerMessage {
+    ServerMessage::Time { time: world.time, speed: world.time_speed, cycle: world.daylight_cycle }
+}
+
+impl Server {
+    fn tick(&mut self) {
+        let _span = tracing::trace_span!("tick").entered();
+        self.accept();
+        self.receive();
+        let dt = self.tick_interval.as_secs_f32();
+        self.world.advance_time(dt);
+        self.update_sleep(dt);
+        if self.last_time_sync.elapsed() >= TIME_SYNC_INTERVAL {
+            self.broadcast(time_message(&self.world));
+            self.last_time_sync = Instant::now();
+        }
+        self.mods.tick(&mut self.world, dt);
+        self.fluids.update(&mut self.world, dt);
+        // Pushed blocks are already in place; there's nothing to show
+        // them sliding or to drop what broke off.
+        self.signals.update(&mut self.world, dt);
+        falling::settle(&mut self.world);
+        if self.world.weather.update(dt) {
+            self.broadcast(ServerMessage::Weather { weather: self.world.weather.weather });
+        }
+        let furnaces = furnace::furnace_system(&mut self.world, dt);
+        self.stream_chunks();
+        for event in self.events.take() {
+            self.mods.handle(&mut self.world, &event);
+        }
+        self.send_changes();
+        for block in furnaces {
+            self.send_block_entity(block, None);
+        }
+        self.send_movement();
+        // Nothing is meshed here; dirty chunks only matter to clients.
+        self.world.take_dirty();
+    }
+
+    fn accept(&mut self) {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    warn!("could not accept a connection: {e}");
+                    return;
+                },
+            };
+            // The listener is non-blocking, but the connection threads
+            // want blocking reads.
+            let connection = stream.set_nonblocking(false).and_then(|_| Connection::new(stream));
+            match connection {
+                Ok(connection) => {
+                    self.players.insert(self.next_id, RemotePlayer {
+                        connection,
+                        name: None,
+                        view_distance: 0,
+                        vertical_distance: 0,
+                        position: self.world.spawn_point,
+                        yaw: 0.0,
+                        pitch: 0.0,
+                        budget: MoveBudget::new(),
+                        last_sequence: None,
+                        sent: HashSet::new(),
+                        in_bed: false,
+                    });
+                    self.next_id += 1;
+                },
+                Err(e) => warn!("could not set up a connection: {e}"),
+            }
+        }
+    }
+
+    fn receive(&mut self) {
+        let ids = self.players.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            loop {
+                let message = match self.players[&id].connection.receive() {
+                    Ok(Some(message)) => message,
+                    Ok(None) => break,
+                    Err(_) => {
+                        self.remove_player(id);
+                        break;
+                    },
+                };
+                if let Err(reason) = self.handle(id, message) {
+                    let player = self.players.get(&id).unwrap();
+                    warn!("dropping {}: {reason}", player.connection.address());
+                    player.connection.send(ServerMessage::Disconnect { reason });
+                    self.remove_player(id);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, id: u32, message: ClientMessage) -> std::result::Result<(), String> {
+        let player = self.players.get_mut(&id).unwrap();
+        match message {
+            ClientMessage::Hello { version, blocks, name, view_distance, vertical_distance } => {
+                if player.name.is_some() {
+                    return Err("said hello twice".to_string());
+                }
+                if version != PROTOCOL_VERSION {
+                    return Err(format!("server runs protocol {PROTOCOL_VERSION}, client {version}"));
+                }
+                if blocks != block::registry().names_hash() {
+                    return Err("the server has different blocks and items; join with the same mods it runs".to_string());
+                }
+                let name = name.trim().to_string();
+                if name.is_empty() {
+                    return Err("empty player name".to_string());
+                }
+                // Whispers find players by name.
+                let taken = self.players.values()
+                    .filter_map(|other| other.name.as_deref())
+                    .any(|other| other.eq_ignore_ascii_case(&name));
+                if taken {
+                    return Err(format!("a player named {name} is already online"));
+                }
+
+                let player = self.players.get_mut(&id).unwrap();
+                player.name = Some(name.clone());
+                player.view_distance = (view_distance as i32).clamp(1, MAX_VIEW_DISTANCE);
+                player.vertical_distance = (vertical_distance as i32).clamp(1, MAX_VIEW_DISTANCE);
+                player.connection.send(ServerMessage::Welcome {
+                    player_id: id,
+                    seed: self.world.generator.seed,
+                    world_type: self.world.generator.world_type,
+                    game_mode: self.world.game_mode,
+                    time: self.world.time,
+                    spawn: self.world.spawn_point,
+                    border: self.world.border.radius,
+                    server_time: self.started.elapsed().as_millis() as u64,
+                });
+                player.connection.send(ServerMessage::Weather { weather: self.world.weather.weather });
+                player.connection.send(time_message(&self.world));
+                info!("{name} joined from {}", player.connection.address());
+
+                let position = player.position;
+                for (&other_id, other) in &self.players {
+                    let Some(other_name) = &other.name else {
+                        continue;
+                    };
+                    if other_id != id {
+                        self.players[&id].connection.send(ServerMessage::PlayerJoined {
+                            id: other_id,
+                            name: other_name.clone(),
+                            position: other.position,
+                        });
+                    }
+                }
+                self.broadcast_except(id, ServerMessage::PlayerJoined { id, name, position });
+            },
+            _ if player.name.is_none() => return Err("expected hello".to_string()),
+            ClientMessage::Move { sequence, position, yaw, pitch } => {
+                if !position.iter().all(|v| v.is_finite()) {
+                    return Err("invalid position".to_string());
+                }
+                // A refused move is still acked, with the position the
+                // player is held at, which snaps the client back.
+                if self.world.border.contains(position) && player.budget.spend(player.position, position) {
+                    player.position = position;
+                }
+                player.yaw = yaw;
+                player.pitch = pitch;
+                player.last_sequence = Some(sequence);
+            },
+            ClientMessage::SetBlock { block: [x, y, z], state } => {
+                // Edits are only accepted where the client can see them,
+                // and only inside the border; anything else is stale or
+                // made up.
+                if !player.sent.contains(&ChunkPos::from_block(x, y, z)) || !self.world.border.contains_block(x, z) {
+                    return Ok(());
+                }
+                let previous = self.world.get_block(x, y, z);
+                if self.world.set_state(x, y, z, state) {
+                    self.events.publish(match state.block {
+                        BlockId::AIR => Event::BlockBroken { block: [x, y, z], id: previous },
+                        id => Event::BlockPlaced { block: [x, y, z], id },
+                    });
+                }
+            },
+            ClientMessage::SetBlockEntity { block: [x, y, z], data } => {
+                let pos = ChunkPos::from_block(x, y, z);
+                if !player.sent.contains(&pos) {
+                    return Ok(());
+                }
+                let Ok(entity) = BlockEntity::decode(&mut Reader::new(&data)) else {
+                    return Ok(());
+                };
+                if self.world.set_block_entity_items(x, y, z, entity.items().clone()) {
+                    self.send_block_entity([x, y, z], Some(id));
+                }
+            },
+            ClientMessage::Chat { text } => {
+                let (Some(from), Some(text)) = (player.name.clone(), clean_chat(&text)) else {
+                    return Ok(());
+                };
+                info!("<{from}> {text}");
+                // The sender gets its own message back, so everyone sees
+                // chat in the same order.
+                self.broadcast(ServerMessage::Chat { from, text });
+            },
+            ClientMessage::Whisper { to, text } => {
+                let (Some(from), Some(text)) = (player.name.clone(), clean_chat(&text)) else {
+                    return Ok(());
+                };
+                let recipient = self.players.iter().find_map(|(&other_id, other)| {
+                    let name = other.name.as_deref()?;
+                    name.eq_ignore_ascii_case(&to).then(|| (other_id, name.to_string()))
+                });
+                let Some((recipient, to)) = recipient else {
+                    self.players[&id].connection.send(ServerMessage::Notice { text: format!("no player named {to} is online") });
+                    return Ok(());
+                };
+                let message = ServerMessage::Whisper { from, to, text };
+                if recipient != id {
+                    self.players[&recipient].connection.send(message.clone());
+                }
+                self.players[&id].connection.send(message);
+            },
+            ClientMessage::Sleep { block: [x, y, z] } => {
+                if !player.sent.contains(&ChunkPos::from_block(x, y, z)) || !self.world.get_block(x, y, z).is_bed() {
+                    return Ok(());
+                }
+                if !self.world.is_night() {
+                    player.connection.send(ServerMessage::Notice { text: "You can only sleep at night".to_string() });
+                    return Ok(());
+                }
+                player.in_bed = true;
+                let name = player.name.clone().unwrap_or_default();
+                let players = self.players.values().filter(|player| player.name.is_some());
+                let (in_bed, total) = players.fold((0, 0), |(in_bed, total), player| (in_bed + player.in_bed as u32, total + 1));
+                self.broadcast(ServerMessage::Notice { text: format!("{name} is in bed ({in_bed}/{total})") });
+            },
+            ClientMessage::GetUp => player.in_bed = false,
+        }
+        Ok(())
+    }
+
+    // Skips the night once every player is in bed: they're all told to
+    // fall asleep, and the clock jumps once their screens have faded out.
+    fn update_sleep(&mut self, dt: f32) {
+        if let Some(elapsed) = &mut self.night_skip {
+            *elapsed += dt;
+            if *elapsed >= sleep::FADE_TIME {
+                self.night_skip = None;
+                self.world.skip_night();
+                self.broadcast(time_message(&self.world));
+                self.players.values_mut().for_each(|player| player.in_bed = false);
+            }
+            return;
+        }
+        // Morning can come while some are still waiting, and gets them up.
+        if !self.world.is_night() {
+            self.players.values_mut().for_each(|player| player.in_bed = false);
+            return;
+        }
+        let mut players = self.players.values().filter(|player| player.name.is_some()).peekable();
+        if players.peek().is_some() && players.all(|player| player.in_bed) {
+            self.night_skip = Some(0.0);
+            self.broadcast(ServerMessage::FallAsleep);
+        }
+    }
+
+    // Sends the block entity at `block` to every player who has its chunk,
+    // except one who already knows.
+    fn send_block_entity(&self, block: [i32 ; 3], except: Option<u32>) {
+        let [x, y, z] = block;
+        let Some(entity) = self.world.block_entity(x, y, z) else {
+            return;
+        };
+        let mut data = Vec::new();
+        entity.encode(&mut data);
+        let pos = ChunkPos::from_block(x, y, z);
+        let message = ServerMessage::BlockEntity { block, data };
+        for (_, player) in self.players.iter().filter(|&(&id, player)| Some(id) != except && player.sent.contains(&pos)) {
+            player.connection.send(message.clone());
+        }
+    }
+
+    fn broadcast(&self, message: ServerMessage) {
+        for player in self.players.values().filter(|player| player.name.is_some()) {
+            player.connection.send(message.clone());
+        }
+    }
+
+    fn broadcast_except(&self, except: u32, message: ServerMessage) {
+        for (&id, player) in &self.players {
+            if id != except && player.name.is_some() {
+                player.connection.send(message.clone());
+            }
+        }
+    }
+
+    fn remove_player(&mut self, id: u32) {
+        let Some(player) = self.players.remove(&id) else {
+            return;
+        };
+        if let Some(name) = player.name {
+            info!("{name} left");
+            self.broadcast_except(id, ServerMessage::PlayerLeft { id });
+        }
+    }
+
+    // Keeps every chunk within some player's view loaded, plus a margin,
+    // and sends each player what it's missing.
+    fn stream_chunks(&mut self) {
+        let players = self.players.values().filter(|player| player.name.is_some()).collect::<Vec<_>>();
+        let wanted = |pos: ChunkPos, margin: i32| players.iter().any(|player| player.sees(pos, margin));
+
+        if let Some(player) = players.first() {
+            self.chunk_io.set_center(player.chunk());
+        }
+        self.chunk_io.retain_loads(|pos| wanted(pos, 1));
+        for loaded in self.chunk_io.poll() {
+            if wanted(loaded.pos, 1) && !self.world.chunks.contains_key(&loaded.pos) {
+                self.world.insert_chunk(loaded.chunk, loaded.overflow, loaded.unsaved);
+                self.events.publish(Event::ChunkLoaded { pos: loaded.pos });
+            } else if loaded.unsaved {
+                self.chunk_io.request_save(loaded.chunk);
+            }
+        }
+
+        let unwanted = self.world.chunks.keys().copied().filter(|&pos| !wanted(pos, 1)).collect::<Vec<_>>();
+        for pos in unwanted {
+            if !self.world.is_modified(pos) {
+                self.world.unload_chunk(pos);
+            } else if self.chunk_io.can_save() {
+                self.chunk_io.request_save(self.world.unload_chunk(pos).unwrap());
+            }
+        }
+
+        for player in &players {
+            let center = player.chunk();
+            let mut missing = center.around(player.view_distance, player.vertical_distance)
+                .filter(|&pos| !self.world.chunks.contains_key(&pos) && !self.chunk_io.is_pending(pos))
+                .collect::<Vec<_>>();
+            missing.sort_by_key(|pos| pos.distance_squared(center));
+            for pos in missing {
+                if !self.chunk_io.request_load(pos) {
+                    break;
+                }
+            }
+        }
+
+        for player in self.players.values_mut().filter(|player| player.name.is_some()) {
+            let dropped = player.sent.iter().copied().filter(|&pos| !player.sees(pos, 1)).collect::<Vec<_>>();
+            for pos in dropped {
+                player.sent.remove(&pos);
+                player.connection.send(ServerMessage::UnloadChunk { pos });
+            }
+
+            let mut unsent = self.world.chunks.keys()
+                .copied()
+                .filter(|&pos| player.sees(pos, 0) && !player.sent.contains(&pos))
+                .collect::<Vec<_>>();
+            let center = player.chunk();
+            unsent.sort_by_key(|pos| pos.distance_squared(center));
+            for pos in unsent.into_iter().take(CHUNKS_PER_TICK) {
+                let data = save::encode_chunk(&self.world.chunks[&pos]);
+                player.connection.send(ServerMessage::Chunk { pos, data });
+                player.sent.insert(pos);
+            }
+        }
+    }
+
+    // Forwards this tick's block changes to the players that have the
+    // chunk.
+    fn send_changes(&mut self) {
+        let mut by_chunk = HashMap::<ChunkPos, Vec<_>>::new();
+        for change in self.world.take_changes() {
+            let [x, y, z] = change.block;
+            by_chunk.entry(ChunkPos::from_block(x, y, z)).or_default().push(change);
+        }
+
+        for (pos, changes) in by_chunk {
+            let Some(chunk) = self.world.chunks.get(&pos) else {
+                continue;
+            };
+            let resend = changes.len() > MAX_UPDATES_PER_CHUNK;
+            let data = resend.then(|| save::encode_chunk(chunk));
+
+            for player in self.players.values().filter(|player| player.sent.contains(&pos)) {
+                match &data {
+                    Some(data) => player.connection.send(ServerMessage::Chunk { pos, data: data.clone() }),
+                    None => {
+                        for change in &changes {
+                            player.connection.send(ServerMessage::BlockUpdate {
+                                block: change.block,
+                                state: change.state,
+                                level: change.level,
+                            });
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    // Movement is batched per tick and stamped with the server clock, which
+    // clients interpolate remote players by.
+    fn send_movement(&mut self) {
+        let server_time = self.started.elapsed().as_millis() as u64;
+        let mut moved = Vec::new();
+        for (&id, player) in &mut self.players {
+            let Some(sequence) = player.last_sequence.take() else {
+                continue;
+            };
+            player.connection.send(ServerMessage::MoveAck { sequence, position: player.position });
+            moved.push((id, ServerMessage::PlayerMoved {
+                id,
+                server_time,
+                position: player.position,
+                yaw: player.yaw,
+                pitch: player.pitch,
+            }));
+        }
+        for (id, message) in moved {
+            self.broadcast_except(id, message);
+        }
+    }
+
+    // Queues the modified chunks to be written, as many as there's room
+    // for, and once they all have been, writes the level after them so it's
+    // never newer than the chunks on disk. Returns whether it got that far;
+    // if not, it's called again next tick.
+    fn save(&mut self) -> bool {
+        if !self.chunk_io.save_modified(&mut self.world) {
+            return false;
+        }
+        self.chunk_io.flush();
+        let level = LevelData {
+            seed: self.world.generator.seed,
+            time: self.world.time,
+            time_speed: self.world.time_speed,
+            daylight_cycle: self.world.daylight_cycle,
+            world_type: self.world.generator.world_type,
+            game_mode: self.world.game_mode,
+            spawn_point: Some(self.world.spawn_point),
+            last_played: save::timestamp(),
+            weather: self.world.weather.weather,
+            weather_remaining: Some(self.world.weather.remaining),
+            border: Some(self.world.border),
+            game_version: save::GAME_VERSION.to_string(),
+            format_version: save::FORMAT_VERSION,
+        };
+        if let Err(e) = self.world_save.save_level(&level) {
+            warn!("could not save the world: {e}");
+        }
+        true
+    }
+}
+
+// Strips what a chat line can't display and enforces the length limit.
+fn clean_chat(text: &str) -> Option<String> {
+    let text = text.chars().filter(|c| !c.is_control()).collect::<String>();
+    let text = text.trim().chars().take(MAX_CHAT_LENGTH).collect::<String>();
+    (!text.is_empty()).then_some(text)
+}
@@ -0,0 +1,101 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct FrameUniform {
+    camera_position: vec3<f32>,
+    fog_mode: u32,
+    fog_color: vec3<f32>,
+    fog_start: f32,
+    fog_end: f32,
+    fog_density: f32,
+    // How bright sky light is, from dim at night to 1 by day.
+    daylight: f32,
+}
+
+@group(2) @binding(0)
+var<uniform> frame: FrameUniform;
+
+const FOG_LINEAR: u32 = 0u;
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d_array<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+// Texels more transparent than this are left out, so the clear parts of
+// a texture don't hide what's behind them.
+const ALPHA_CUTOFF: f32 = 0.05;
+
+struct InstanceInput {
+    @location(0) position: vec3<f32>,
+    @location(1) size: f32,
+    // Minimum UV in xy and maximum in zw.
+    @location(2) uv: vec4<f32>,
+    @location(3) color: vec4<f32>,
+    @location(4) layer: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) layer: u32,
+    @location(2) color: vec4<f32>,
+    @location(3) world_position: vec3<f32>,
+}
+
+// The two triangles of a quad, as corners from -0.5 to 0.5.
+const CORNERS = array<vec2<f32>, 6>(
+    vec2<f32>(-0.5, -0.5),
+    vec2<f32>(0.5, -0.5),
+    vec2<f32>(0.5, 0.5),
+    vec2<f32>(-0.5, -0.5),
+    vec2<f32>(0.5, 0.5),
+    vec2<f32>(-0.5, 0.5),
+);
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, instance: InstanceInput) -> VertexOutput {
+    // Turned to face the camera's position, upright unless seen from
+    // straight above or below.
+    let to_camera = normalize(frame.camera_position - instance.position);
+    var right = cross(vec3<f32>(0.0, 1.0, 0.0), to_camera);
+    if length(right) < 0.001 {
+        right = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    right = normalize(right);
+    let up = cross(to_camera, right);
+
+    let corner = CORNERS[vertex_index];
+    let position = instance.position + (right * corner.x + up * corner.y) * instance.size;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.tex_coords = mix(instance.uv.xy, instance.uv.zw, vec2<f32>(corner.x + 0.5, 0.5 - corner.y));
+    out.layer = instance.layer;
+    out.color = instance.color;
+    out.world_position = position;
+    return out;
+}
+
+fn fog_visibility(world_position: vec3<f32>) -> f32 {
+    let distance = length(world_position - frame.camera_position);
+    if frame.fog_mode == FOG_LINEAR {
+        return clamp((frame.fog_end - distance) / (frame.fog_end - frame.fog_start), 0.0, 1.0);
+    }
+    let d = distance * frame.fog_density;
+    return exp(-d * d);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.layer) * in.color;
+    if color.a < ALPHA_CUTOFF {
+        discard;
+    }
+    let fogged = mix(frame.fog_color, color.rgb, fog_visibility(in.world_position));
+    return vec4<f32>(fogged, color.a);
+}
@@ -0,0 +1,588 @@
+//! Particles: small quads turned to face the camera, for the debris of
+//! broken blocks, smoke, flames, falling leaves, rain and snow. They're simulated on
+//! the CPU and drawn in one instanced call with the block textures, and
+//! there are never more than the budget from the graphics settings.
+
+use std::f32::consts::TAU;
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+
+use crate::{
+    block::{registry, BlockId, BlockModel, Face},
+    debug::RenderStats,
+    gpu::write_growing,
+    light::MAX_LIGHT,
+    random::Random,
+    texture,
+    world::World,
+    worldgen::biome::Precipitation,
+};
+
+// Debris flies out of a broken block from this many points along each
+// axis, and falls like it weighs something.
+const DEBRIS_GRID: i32 = 4;
+const DEBRIS_GRAVITY: f32 = 16.0;
+const DEBRIS_LIFETIME: (f32, f32) = (0.4, 1.2);
+const DEBRIS_SIZE: (f32, f32) = (0.08, 0.14);
+// Pixels along each side of the piece of the block's texture that a bit
+// of debris shows.
+const DEBRIS_PIXELS: u32 = 4;
+const TEXTURE_PIXELS: u32 = 16;
+
+// Smoke rises slowly and spreads out as it fades.
+const SMOKE_LIFETIME: (f32, f32) = (1.0, 2.5);
+const SMOKE_RISE: f32 = 0.6;
+const SMOKE_GROWTH: f32 = 0.4;
+// Puffs of smoke per block of an explosion's radius.
+const EXPLOSION_PUFFS: f32 = 12.0;
+const FLAME_LIFETIME: (f32, f32) = (0.5, 0.9);
+const FLAME_SIZE: f32 = 0.14;
+
+const LEAF_LIFETIME: (f32, f32) = (3.0, 5.0);
+const LEAF_PIXELS: u32 = 3;
+
+// Random blocks this far around the camera are looked at this many times
+// a second, and show whatever particles they give off by themselves.
+const AMBIENT_RADIUS: i32 = 16;
+const AMBIENT_SAMPLES_PER_SECOND: f32 = 24_000.0;
+// Chance that a sampled leaf block with air below lets a leaf go.
+const LEAF_CHANCE: f32 = 0.01;
+
+// Rain and snow fall in columns this far around the camera, starting
+// between these heights above and below it, and stop at the first thing
+// that keeps the sky off, which they look this far down for.
+const PRECIPITATION_RADIUS: f32 = 16.0;
+const PRECIPITATION_HEIGHT: (f32, f32) = (-6.0, 18.0);
+const PRECIPITATION_DEPTH: i32 = 48;
+// Drops and flakes started per second at full intensity, and how fast
+// they fall.
+const RAIN_PER_SECOND: f32 = 1200.0;
+const RAIN_SPEED: f32 = 14.0;
+const RAIN_SIZE: f32 = 0.6;
+const SNOW_PER_SECOND: f32 = 300.0;
+const SNOW_SPEED: f32 = 1.5;
+const SNOW_SIZE: f32 = 0.12;
+
+// How many of a particle's last seconds it spends fading out, for those
+// that fade.
+const FADE_TIME: f32 = 0.5;
+// Horizontal speed kept per second while lying on a block.
+const GROUND_FRICTION: f32 = 0.02;
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    position: Point3<f32>,
+    velocity: Vector3<f32>,
+    // Blocks per second squared pulling it down; negative rises.
+    gravity: f32,
+    // Fraction of its speed lost per second to the air.
+    drag: f32,
+    age: f32,
+    lifetime: f32,
+    // Width of the quad in blocks, and how much that changes per second.
+    size: f32,
+    growth: f32,
+    layer: u32,
+    // The part of the texture shown, as its minimum and maximum UVs.
+    uv: [f32 ; 4],
+    color: [f32 ; 4],
+    // Whether it stops against blocks instead of passing through them.
+    collides: bool,
+    fades: bool,
+}
+
+impl Particle {
+    fn new(position: Point3<f32>, layer: u32, lifetime: f32) -> Self {
+        Self {
+            position,
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+            gravity: 0.0,
+            drag: 0.0,
+            age: 0.0,
+            lifetime,
+            size: 0.1,
+            growth: 0.0,
+            layer,
+            uv: [0.0, 0.0, 1.0, 1.0],
+            color: [1.0 ; 4],
+            collides: false,
+            fades: false,
+        }
+    }
+}
+
+/// Every live particle and the emitters that add them. Once the budget is
+/// full, new particles are dropped until old ones die out.
+pub struct Particles {
+    particles: Vec<Particle>,
+    budget: usize,
+    random: Random,
+    // Samples owed to the ambient emitters, and drops owed to the weather,
+    // from frames too short for a whole one.
+    ambient_samples: f32,
+    precipitation: f32,
+}
+
+impl Particles {
+    pub fn new(budget: u32) -> Self {
+        Self {
+            particles: Vec::new(),
+            budget: budget as usize,
+            random: Random::new(1),
+            ambient_samples: 0.0,
+            precipitation: 0.0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Changes the budget, dropping the oldest particles over it.
+    pub fn set_budget(&mut self, budget: u32) {
+        self.budget = budget as usize;
+        let excess = self.particles.len().saturating_sub(self.budget);
+        self.particles.drain(..excess);
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.ambient_samples = 0.0;
+        self.precipitation = 0.0;
+    }
+
+    fn spawn(&mut self, particle: Particle) {
+        if self.particles.len() < self.budget {
+            self.particles.push(particle);
+        }
+    }
+
+    /// Bursts `block` into bits of its side texture flying out from
+    /// `[x, y, z]`.
+    pub fn block_break(&mut self, block: BlockId, [x, y, z]: [i32 ; 3]) {
+        if block.info().model == BlockModel::None {
+            return;
+        }
+        let layer = block.texture(Face::PosX);
+        let step = 1.0 / DEBRIS_GRID as f32;
+        for i in 0..DEBRIS_GRID {
+            for j in 0..DEBRIS_GRID {
+                for k in 0..DEBRIS_GRID {
+                    let offset = Vector3::new(i, j, k).map(|n| (n as f32 + 0.5) * step);
+                    let position = Point3::new(x as f32, y as f32, z as f32) + offset;
+                    // Outwards from the middle, and up a little more.
+                    let outwards = offset - Vector3::new(0.5, 0.5, 0.5);
+                    let velocity = outwards * 3.0
+                        + Vector3::new(self.random.range((-0.5, 0.5)), self.random.range((0.5, 2.5)), self.random.range((-0.5, 0.5)));
+                    let lifetime = self.random.range(DEBRIS_LIFETIME);
+                    let shade = self.random.range((0.75, 1.0));
+                    let particle = Particle {
+                        velocity,
+                        gravity: DEBRIS_GRAVITY,
+                        drag: 0.5,
+                        size: self.random.range(DEBRIS_SIZE),
+                        uv: self.texture_piece(DEBRIS_PIXELS),
+                        color: [shade, shade, shade, 1.0],
+                        collides: true,
+                        ..Particle::new(position, layer, lifetime)
+                    };
+                    self.spawn(particle);
+                }
+            }
+        }
+    }
+
+    /// Clouds of smoke filling a sphere of `radius` around `center`.
+    pub fn explosion(&mut self, center: Point3<f32>, radius: f32) {
+        let Some(layer) = registry().particle_texture("smoke") else {
+            return;
+        };
+        let puffs = (radius * EXPLOSION_PUFFS).ceil() as u32;
+        for _ in 0..puffs {
+            let direction = self.random_direction();
+            let distance = self.random.float().cbrt() * radius;
+            let lifetime = self.random.range(SMOKE_LIFETIME);
+            let grey = self.random.range((0.5, 0.85));
+            let particle = Particle {
+                velocity: direction * self.random.range((1.0, 4.0)),
+                gravity: -SMOKE_RISE,
+                drag: 2.0,
+                size: self.random.range((0.6, 1.2)),
+                growth: SMOKE_GROWTH,
+                color: [grey, grey, grey, 0.9],
+                fades: true,
+                ..Particle::new(center + direction * distance, layer, lifetime)
+            };
+            self.spawn(particle);
+        }
+    }
+
+    /// A flicker of flame at `position`, now and then with smoke rising
+    /// off it.
+    pub fn flame(&mut self, position: Point3<f32>) {
+        let Some(layer) = registry().particle_texture("flame") else {
+            return;
+        };
+        let lifetime = self.random.range(FLAME_LIFETIME);
+        let particle = Particle {
+            velocity: Vector3::new(0.0, self.random.range((0.02, 0.1)), 0.0),
+            size: FLAME_SIZE,
+            growth: -FLAME_SIZE / lifetime,
+            ..Particle::new(position, layer, lifetime)
+        };
+        self.spawn(particle);
+        if self.random.float() < 0.25 {
+            self.smoke(position + Vector3::new(0.0, FLAME_SIZE, 0.0));
+        }
+    }
+
+    // A small wisp of dark smoke drifting up from `position`.
+    fn smoke(&mut self, position: Point3<f32>) {
+        let Some(layer) = registry().particle_texture("smoke") else {
+            return;
+        };
+        let lifetime = self.random.range(SMOKE_LIFETIME);
+        let grey = self.random.range((0.2, 0.4));
+        let particle = Particle {
+            velocity: Vector3::new(self.random.range((-0.05, 0.05)), 0.3, self.random.range((-0.05, 0.05))),
+            gravity: -SMOKE_RISE * 0.2,
+            drag: 0.5,
+            size: 0.15,
+            growth: SMOKE_GROWTH * 0.25,
+            color: [grey, grey, grey, 0.8],
+            fades: true,
+            ..Particle::new(position, layer, lifetime)
+        };
+        self.spawn(particle);
+    }
+
+    // A leaf drifting down from under a leaf block.
+    fn falling_leaf(&mut self, position: Point3<f32>) {
+        let layer = BlockId::LEAVES.texture(Face::NegY);
+        let lifetime = self.random.range(LEAF_LIFETIME);
+        let particle = Particle {
+            velocity: Vector3::new(self.random.range((-0.3, 0.3)), 0.0, self.random.range((-0.3, 0.3))),
+            gravity: 1.5,
+            // Drag this strong lets it settle at a slow fall.
+            drag: 3.0,
+            size: 0.1,
+            uv: self.texture_piece(LEAF_PIXELS),
+            collides: true,
+            fades: true,
+            ..Particle::new(position, layer, lifetime)
+        };
+        self.spawn(particle);
+    }
+
+    /// Looks at random blocks around `center` and lets those that give off
+    /// particles by themselves do so: leaves fall from under leaf blocks,
+    /// lit furnaces flicker at their front and torches at their tip.
+    pub fn ambient(&mut self, world: &World, center: Point3<f32>, dt: f32) {
+        self.ambient_samples += AMBIENT_SAMPLES_PER_SECOND * dt;
+        let [cx, cy, cz] = [center.x, center.y, center.z].map(|v| v.floor() as i32);
+        while self.ambient_samples >= 1.0 {
+            self.ambient_samples -= 1.0;
+            let mut offset = || (self.random.next() % (2 * AMBIENT_RADIUS + 1) as u32) as i32 - AMBIENT_RADIUS;
+            let [x, y, z] = [cx + offset(), cy + offset(), cz + offset()];
+            let state = world.get_state(x, y, z);
+            match state.block {
+                BlockId::LEAVES if self.random.float() < LEAF_CHANCE && world.get_block(x, y - 1, z).is_air() => {
+                    let position = Point3::new(x as f32 + self.random.float(), y as f32 - 0.05, z as f32 + self.random.float());
+                    self.falling_leaf(position);
+                },
+                BlockId::FURNACE if state.properties.lit() => {
+                    // Just off the front face, low down where the fire is.
+                    let [fx, _, fz] = state.properties.facing().offset();
+                    let across = self.random.range((-0.3, 0.3));
+                    let position = Point3::new(
+                        x as f32 + 0.5 + fx as f32 * 0.52 + fz as f32 * across,
+                        y as f32 + self.random.range((0.1, 0.4)),
+                        z as f32 + 0.5 + fz as f32 * 0.52 + fx as f32 * across,
+                    );
+                    self.flame(position);
+                },
+                // On the tip of the stick.
+                BlockId::TORCH => self.flame(Point3::new(x as f32 + 0.5, y as f32 + 0.7, z as f32 + 0.5)),
+                _ => {},
+            }
+        }
+    }
+
+    /// Lets rain or snow fall around `center` for as long as the weather
+    /// and the biome under each column call for, only where the sky is
+    /// open above and down to the first thing that shelters from it, and
+    /// only in a dimension with a sky.
+    pub fn precipitation(&mut self, world: &World, center: Point3<f32>, dt: f32) {
+        let intensity = if world.dimension().has_sky() { world.weather.intensity() } else { 0.0 };
+        if intensity <= 0.0 {
+            self.precipitation = 0.0;
+            return;
+        }
+        // Enough for whichever falls more often; snow skips some of them.
+        self.precipitation += RAIN_PER_SECOND * intensity * dt;
+        while self.precipitation >= 1.0 {
+            self.precipitation -= 1.0;
+            let x = center.x + self.random.range((-PRECIPITATION_RADIUS, PRECIPITATION_RADIUS));
+            let z = center.z + self.random.range((-PRECIPITATION_RADIUS, PRECIPITATION_RADIUS));
+            let y = center.y + self.random.range(PRECIPITATION_HEIGHT);
+            let [block_x, block_y, block_z] = [x, y, z].map(|v| v.floor() as i32);
+
+            let kind = world.generator.biome_at(block_x, block_z).info().precipitation;
+            if kind == Precipitation::None || (kind == Precipitation::Snow && self.random.float() >= SNOW_PER_SECOND / RAIN_PER_SECOND) {
+                continue;
+            }
+            // Under a roof or underground.
+            let open = |y: i32| world.light(block_x, y, block_z).sky == MAX_LIGHT && !world.get_block(block_x, y, block_z).is_solid();
+            if !open(block_y) {
+                continue;
+            }
+            let mut ground = block_y;
+            while ground > block_y - PRECIPITATION_DEPTH && open(ground - 1) {
+                ground -= 1;
+            }
+
+            let position = Point3::new(x, y, z);
+            let fall = y - ground as f32;
+            match kind {
+                Precipitation::Snow => self.snowflake(position, fall),
+                _ => self.raindrop(position, fall),
+            }
+        }
+    }
+
+    // A drop of rain falling `fall` blocks straight down.
+    fn raindrop(&mut self, position: Point3<f32>, fall: f32) {
+        let Some(layer) = registry().particle_texture("rain") else {
+            return;
+        };
+        let particle = Particle {
+            velocity: Vector3::new(0.0, -RAIN_SPEED, 0.0),
+            size: RAIN_SIZE,
+            color: [0.7, 0.75, 0.85, 0.6],
+            ..Particle::new(position, layer, fall / RAIN_SPEED)
+        };
+        self.spawn(particle);
+    }
+
+    // A flake of snow drifting `fall` blocks down, swaying a little.
+    fn snowflake(&mut self, position: Point3<f32>, fall: f32) {
+        let Some(layer) = registry().particle_texture("snow") else {
+            return;
+        };
+        let particle = Particle {
+            velocity: Vector3::new(self.random.range((-0.3, 0.3)), -SNOW_SPEED, self.random.range((-0.3, 0.3))),
+            size: SNOW_SIZE,
+            collides: true,
+            ..Particle::new(position, layer, fall / SNOW_SPEED)
+        };
+        self.spawn(particle);
+    }
+
+    /// Moves every particle on by `dt` seconds and removes the ones that
+    /// have lived out their lifetime.
+    pub fn update(&mut self, world: &World, dt: f32) {
+        self.particles.retain_mut(|particle| {
+            particle.age += dt;
+            if particle.age >= particle.lifetime {
+                return false;
+            }
+            particle.size = (particle.size + particle.growth * dt).max(0.0);
+            particle.velocity.y -= particle.gravity * dt;
+            particle.velocity *= (1.0 - particle.drag * dt).max(0.0);
+
+            if !particle.collides {
+                particle.position += particle.velocity * dt;
+                return true;
+            }
+            // One axis at a time, so a particle hitting the ground keeps
+            // sliding along it.
+            for axis in 0..3 {
+                let mut moved = particle.position;
+                moved[axis] += particle.velocity[axis] * dt;
+                if is_inside_block(world, moved) {
+                    particle.velocity[axis] = 0.0;
+                    if axis == 1 {
+                        let friction = GROUND_FRICTION.powf(dt);
+                        particle.velocity.x *= friction;
+                        particle.velocity.z *= friction;
+                    }
+                } else {
+                    particle.position = moved;
+                }
+            }
+            true
+        });
+    }
+
+    // A random square of `pixels` on a side from a block texture, as UVs.
+    fn texture_piece(&mut self, pixels: u32) -> [f32 ; 4] {
+        let span = TEXTURE_PIXELS - pixels + 1;
+        let u = (self.random.next() % span) as f32 / TEXTURE_PIXELS as f32;
+        let v = (self.random.next() % span) as f32 / TEXTURE_PIXELS as f32;
+        let size = pixels as f32 / TEXTURE_PIXELS as f32;
+        [u, v, u + size, v + size]
+    }
+
+    fn random_direction(&mut self) -> Vector3<f32> {
+        let yaw = self.random.float() * TAU;
+        let y = self.random.range((-1.0, 1.0));
+        let across = (1.0 - y * y).sqrt();
+        Vector3::new(yaw.cos() * across, y, yaw.sin() * across)
+    }
+}
+
+fn is_inside_block(world: &World, position: Point3<f32>) -> bool {
+    let [x, y, z] = [position.x, position.y, position.z].map(|v| v.floor() as i32);
+    if !world.get_block(x, y, z).is_solid() {
+        return false;
+    }
+    let local = [position.x - x as f32, position.y - y as f32, position.z - z as f32];
+    world.collision_shape(x, y, z).iter()
+        .any(|cuboid| (0..3).all(|axis| local[axis] >= cuboid.min[axis] && local[axis] <= cuboid.max[axis]))
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleInstance {
+    position: [f32 ; 3],
+    size: f32,
+    uv: [f32 ; 4],
+    color: [f32 ; 4],
+    layer: u32,
+}
+
+/// Draws the particles as camera-facing quads, one instance each, through
+/// the terrain's pipeline layout so they share its bind groups.
+pub struct ParticleRenderer {
+    pipeline: wgpu::RenderPipeline,
+    instances: Vec<ParticleInstance>,
+    instance_buffer: Option<wgpu::Buffer>,
+    instance_count: u32,
+}
+
+impl ParticleRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        terrain_pipeline_layout: &wgpu::PipelineLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("particle.wgsl"));
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Particle Pipeline"),
+            layout: Some(terrain_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ParticleInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x3,
+                        1 => Float32,
+                        2 => Float32x4,
+                        3 => Float32x4,
+                        4 => Uint32,
+                    ],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Tested against the world but not written, so particles
+            // don't cut holes in each other.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            instances: Vec::new(),
+            instance_buffer: None,
+            instance_count: 0,
+        }
+    }
+
+    /// Uploads the particles, furthest from `eye` first so the blending
+    /// comes out right.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, particles: &Particles, eye: Point3<f32>) {
+        let mut sorted = particles.particles.iter()
+            .filter(|particle| particle.size > 0.0)
+            .map(|particle| ((particle.position - eye).magnitude2(), particle))
+            .collect::<Vec<_>>();
+        sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        self.instances.clear();
+        self.instances.extend(sorted.into_iter().map(|(_, particle)| {
+            let remaining = particle.lifetime - particle.age;
+            let fade = if particle.fades { (remaining / FADE_TIME).min(1.0) } else { 1.0 };
+            let [r, g, b, a] = particle.color;
+            ParticleInstance {
+                position: particle.position.to_vec().into(),
+                size: particle.size,
+                uv: particle.uv,
+                color: [r, g, b, a * fade],
+                layer: particle.layer,
+            }
+        }));
+        self.instance_count = self.instances.len() as u32;
+        if self.instance_count == 0 {
+            return;
+        }
+
+        write_growing(
+            device,
+            queue,
+            &mut self.instance_buffer,
+            &self.instances,
+            wgpu::BufferUsages::VERTEX,
+            "Particle Instance Buffer",
+        );
+    }
+
+    /// Draws the particles. Expects the terrain's bind groups to be set.
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, stats: &mut RenderStats) {
+        let Some(instance_buffer) = &self.instance_buffer else {
+            return;
+        };
+        if self.instance_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.instance_count);
+
+        stats.draw_calls += 1;
+        stats.vertices += 6 * self.instance_count;
+    }
+}
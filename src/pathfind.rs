@@ -0,0 +1,370 @@
+//! A* pathfinding over the voxel terrain, so mobs can find their way
+//! around walls, up hills and down ledges. A request copies the blocks
+//! around the route into a small grid and searches it on a worker thread,
+//! so long searches never stall a frame; paths come back from `poll`.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    sync::mpsc,
+    thread::JoinHandle,
+};
+
+use cgmath::{Point3, Vector3};
+
+use crate::{ecs::Entity, world::World};
+
+// Routes are only searched between points this close together, and over
+// the blocks this far around them to the sides and above and below.
+const MAX_DISTANCE: f32 = 32.0;
+const MARGIN: [i32 ; 3] = [8, 4, 8];
+// Searches give up after this many nodes and go with the node that got
+// closest, so an unreachable goal costs a bounded amount of work.
+const MAX_NODES: usize = 4000;
+// Requests that may be in flight at once.
+const MAX_PENDING: usize = 16;
+
+// Move costs, in tenths of a block walked.
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+const JUMP_COST: u32 = 15;
+const DROP_COST_PER_BLOCK: u32 = 5;
+
+// How finely a straight line is checked for walkability when smoothing.
+const SMOOTH_STEP: f32 = 0.25;
+// How close a walker has to get to a waypoint before heading for the next.
+const WAYPOINT_REACH: f32 = 0.35;
+
+/// The size and abilities of whatever is walking the path.
+#[derive(Clone, Copy, Debug)]
+pub struct Agent {
+    pub half_width: f32,
+    /// Blocks of headroom it needs.
+    pub height: i32,
+    /// The furthest it will drop down.
+    pub max_drop: i32,
+}
+
+/// A route to follow, as the feet positions to walk through in turn.
+#[derive(Clone, Debug)]
+pub struct Path {
+    waypoints: Vec<Point3<f32>>,
+    next: usize,
+}
+
+impl Path {
+    /// The waypoint to head for, or `None` once the path is walked.
+    pub fn current(&self) -> Option<Point3<f32>> {
+        self.waypoints.get(self.next).copied()
+    }
+
+    /// Moves on past every waypoint `position` has reached.
+    pub fn advance(&mut self, position: Point3<f32>) {
+        while let Some(waypoint) = self.current() {
+            let offset = Vector3::new(waypoint.x - position.x, 0.0, waypoint.z - position.z);
+            let reached = offset.x.abs() < WAYPOINT_REACH && offset.z.abs() < WAYPOINT_REACH && (waypoint.y - position.y).abs() < 1.0;
+            if !reached {
+                return;
+            }
+            self.next += 1;
+        }
+    }
+}
+
+/// A finished search, for the entity that asked. `path` is `None` if not
+/// even a step towards `goal` could be found.
+pub struct PathResult {
+    pub entity: Entity,
+    pub goal: Point3<f32>,
+    pub path: Option<Path>,
+}
+
+struct Job {
+    entity: Entity,
+    goal: Point3<f32>,
+    grid: Grid,
+    start: [i32 ; 3],
+    end: [i32 ; 3],
+    agent: Agent,
+}
+
+/// Runs path searches on a worker thread.
+pub struct Pathfinder {
+    jobs: Option<mpsc::Sender<Job>>,
+    results: mpsc::Receiver<PathResult>,
+    // Entities whose result hasn't been collected yet.
+    pending: HashSet<Entity>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Pathfinder {
+    pub fn new() -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (sender, results) = mpsc::channel();
+        let worker = std::thread::Builder::new()
+            .name("pathfinder".to_string())
+            .spawn(move || {
+                for job in queue {
+                    let path = find_path(&job.grid, job.start, job.end, job.agent);
+                    if sender.send(PathResult { entity: job.entity, goal: job.goal, path }).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("could not start the pathfinder thread");
+
+        Self {
+            jobs: Some(jobs),
+            results,
+            pending: HashSet::new(),
+            worker: Some(worker),
+        }
+    }
+
+    /// Whether `entity` is still waiting on a path.
+    pub fn is_pending(&self, entity: Entity) -> bool {
+        self.pending.contains(&entity)
+    }
+
+    /// Starts a search for a path from `start` to `goal` for `entity`.
+    /// Returns false if it already has one in flight, too many others do,
+    /// or the goal is too far away to try.
+    pub fn request(&mut self, world: &World, entity: Entity, start: Point3<f32>, goal: Point3<f32>, agent: Agent) -> bool {
+        if self.pending.contains(&entity) || self.pending.len() >= MAX_PENDING {
+            return false;
+        }
+        let offset = goal - start;
+        if offset.x.abs().max(offset.z.abs()) > MAX_DISTANCE {
+            return false;
+        }
+        let (start_block, end_block) = (block_of(start), block_of(goal));
+        let min = [0, 1, 2].map(|axis| start_block[axis].min(end_block[axis]) - MARGIN[axis]);
+        let max = [0, 1, 2].map(|axis| start_block[axis].max(end_block[axis]) + MARGIN[axis]);
+
+        let job = Job {
+            entity,
+            goal,
+            grid: Grid::capture(world, min, max),
+            start: start_block,
+            end: end_block,
+            agent,
+        };
+        if self.jobs.as_ref().is_none_or(|jobs| jobs.send(job).is_err()) {
+            return false;
+        }
+        self.pending.insert(entity);
+        true
+    }
+
+    /// Searches finished since the last call.
+    pub fn poll(&mut self) -> Vec<PathResult> {
+        let results = self.results.try_iter().collect::<Vec<_>>();
+        for result in &results {
+            self.pending.remove(&result.entity);
+        }
+        results
+    }
+}
+
+impl Drop for Pathfinder {
+    // Closing the job queue lets the worker finish its search and stop.
+    fn drop(&mut self) {
+        self.jobs = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    // Nothing to bump into.
+    Open,
+    Fluid,
+    // Something solid no taller than a block, which can be stood on.
+    Solid,
+    // Taller than a block, like a fence, so neither stood on nor passed.
+    Tall,
+}
+
+// A copy of the blocks in a box of the world, as far as walking goes.
+// Everything outside the box counts as solid.
+struct Grid {
+    min: [i32 ; 3],
+    size: [i32 ; 3],
+    cells: Vec<Cell>,
+}
+
+impl Grid {
+    fn capture(world: &World, min: [i32 ; 3], max: [i32 ; 3]) -> Self {
+        let size = [0, 1, 2].map(|axis| max[axis] - min[axis] + 1);
+        let mut cells = Vec::with_capacity((size[0] * size[1] * size[2]) as usize);
+        for y in min[1]..=max[1] {
+            for z in min[2]..=max[2] {
+                for x in min[0]..=max[0] {
+                    let block = world.get_block(x, y, z);
+                    let cell = if block.is_opaque() {
+                        Cell::Solid
+                    } else if block.is_solid() {
+                        let top = world.collision_shape(x, y, z).iter().map(|cuboid| cuboid.max[1]).fold(0.0, f32::max);
+                        if top > 1.0 { Cell::Tall } else { Cell::Solid }
+                    } else if block.is_fluid() {
+                        Cell::Fluid
+                    } else {
+                        Cell::Open
+                    };
+                    cells.push(cell);
+                }
+            }
+        }
+        Self { min, size, cells }
+    }
+
+    fn get(&self, [x, y, z]: [i32 ; 3]) -> Cell {
+        let [lx, ly, lz] = [x - self.min[0], y - self.min[1], z - self.min[2]];
+        if lx < 0 || ly < 0 || lz < 0 || lx >= self.size[0] || ly >= self.size[1] || lz >= self.size[2] {
+            return Cell::Solid;
+        }
+        self.cells[((ly * self.size[2] + lz) * self.size[0] + lx) as usize]
+    }
+
+    // Whether something `height` blocks tall fits with its feet in this
+    // block.
+    fn is_clear(&self, [x, y, z]: [i32 ; 3], height: i32) -> bool {
+        (y..y + height).all(|y| matches!(self.get([x, y, z]), Cell::Open | Cell::Fluid))
+    }
+
+    // Whether the agent can stand here: room to fit, and ground or water
+    // to hold it up.
+    fn is_standable(&self, [x, y, z]: [i32 ; 3], agent: Agent) -> bool {
+        self.is_clear([x, y, z], agent.height)
+            && (self.get([x, y - 1, z]) == Cell::Solid || self.get([x, y, z]) == Cell::Fluid || self.get([x, y - 1, z]) == Cell::Fluid)
+    }
+}
+
+fn block_of(point: Point3<f32>) -> [i32 ; 3] {
+    [point.x.floor() as i32, point.y.floor() as i32, point.z.floor() as i32]
+}
+
+const DIRECTIONS: [[i32 ; 2] ; 8] = [[1, 0], [-1, 0], [0, 1], [0, -1], [1, 1], [1, -1], [-1, 1], [-1, -1]];
+
+// The nodes one move away from `node`, with what each move costs.
+fn neighbours(grid: &Grid, [x, y, z]: [i32 ; 3], agent: Agent) -> Vec<([i32 ; 3], u32)> {
+    let mut moves = Vec::with_capacity(8);
+    for [dx, dz] in DIRECTIONS {
+        let (nx, nz) = (x + dx, z + dz);
+        if dx != 0 && dz != 0 {
+            // Diagonals stay level and can't cut corners.
+            let corners_clear = grid.is_clear([x + dx, y, z], agent.height) && grid.is_clear([x, y, z + dz], agent.height);
+            if corners_clear && grid.is_standable([nx, y, nz], agent) {
+                moves.push(([nx, y, nz], DIAGONAL_COST));
+            }
+            continue;
+        }
+
+        if grid.is_standable([nx, y, nz], agent) {
+            moves.push(([nx, y, nz], STRAIGHT_COST));
+        } else if grid.is_standable([nx, y + 1, nz], agent) && grid.is_clear([x, y + agent.height, z], 1) {
+            // Jumping needs headroom above where it takes off.
+            moves.push(([nx, y + 1, nz], JUMP_COST));
+        } else if grid.is_clear([nx, y, nz], agent.height) {
+            let landing = (1..=agent.max_drop)
+                .take_while(|&drop| grid.is_clear([nx, y - drop, nz], 1))
+                .find(|&drop| grid.is_standable([nx, y - drop, nz], agent));
+            if let Some(drop) = landing {
+                moves.push(([nx, y - drop, nz], STRAIGHT_COST + DROP_COST_PER_BLOCK * drop as u32));
+            }
+        }
+    }
+    moves
+}
+
+// A lower bound on the cost between two nodes: diagonal moves across the
+// ground, straight ones for the rest, and a block's walk per level.
+fn heuristic(a: [i32 ; 3], b: [i32 ; 3]) -> u32 {
+    let [dx, dy, dz] = [0, 1, 2].map(|axis| (a[axis] - b[axis]).unsigned_abs());
+    let (long, short) = (dx.max(dz), dx.min(dz));
+    DIAGONAL_COST * short + STRAIGHT_COST * (long - short) + STRAIGHT_COST * dy
+}
+
+// Searches from `start` to `end`, ending at whichever node got closest if
+// `end` can't be reached.
+fn find_path(grid: &Grid, start: [i32 ; 3], end: [i32 ; 3], agent: Agent) -> Option<Path> {
+    let mut open = BinaryHeap::new();
+    let mut costs = HashMap::new();
+    let mut came_from = HashMap::new();
+    open.push(Reverse((heuristic(start, end), start)));
+    costs.insert(start, 0);
+    let mut closest = (heuristic(start, end), start);
+
+    let mut expanded = 0;
+    while let Some(Reverse((_, node))) = open.pop() {
+        if node == end {
+            closest = (0, node);
+            break;
+        }
+        expanded += 1;
+        if expanded > MAX_NODES {
+            break;
+        }
+
+        let cost = costs[&node];
+        for (next, step) in neighbours(grid, node, agent) {
+            let next_cost = cost + step;
+            if costs.get(&next).is_some_and(|&known| known <= next_cost) {
+                continue;
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, node);
+            let remaining = heuristic(next, end);
+            closest = closest.min((remaining, next));
+            open.push(Reverse((next_cost + remaining, next)));
+        }
+    }
+
+    let (_, mut node) = closest;
+    if node == start {
+        return None;
+    }
+    let mut nodes = vec![node];
+    while let Some(&previous) = came_from.get(&node) {
+        node = previous;
+        nodes.push(node);
+    }
+    nodes.reverse();
+    Some(Path { waypoints: smooth(grid, &nodes, agent), next: 0 })
+}
+
+// Turns nodes into waypoints at block centres, skipping any a straight
+// walk along the same level can cut past.
+fn smooth(grid: &Grid, nodes: &[[i32 ; 3]], agent: Agent) -> Vec<Point3<f32>> {
+    let centre = |[x, y, z]: [i32 ; 3]| Point3::new(x as f32 + 0.5, y as f32, z as f32 + 0.5);
+    let mut waypoints = Vec::new();
+    let mut from = 0;
+    while from + 1 < nodes.len() {
+        let mut to = from + 1;
+        while to + 1 < nodes.len() && nodes[to + 1][1] == nodes[from][1] && line_is_walkable(grid, centre(nodes[from]), centre(nodes[to + 1]), agent) {
+            to += 1;
+        }
+        waypoints.push(centre(nodes[to]));
+        from = to;
+    }
+    waypoints
+}
+
+// Whether the agent can walk straight from `a` to `b` on one level,
+// checking its whole width along the way.
+fn line_is_walkable(grid: &Grid, a: Point3<f32>, b: Point3<f32>, agent: Agent) -> bool {
+    let offset = b - a;
+    let steps = ((offset.x.abs().max(offset.z.abs())) / SMOOTH_STEP).ceil().max(1.0) as i32;
+    let y = a.y.floor() as i32;
+    (0..=steps).all(|step| {
+        let point = a + offset * (step as f32 / steps as f32);
+        [-agent.half_width, agent.half_width].into_iter().all(|dx| {
+            [-agent.half_width, agent.half_width].into_iter().all(|dz| {
+                let [x, z] = [(point.x + dx).floor() as i32, (point.z + dz).floor() as i32];
+                grid.is_standable([x, y, z], agent)
+            })
+        })
+    })
+}
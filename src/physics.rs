@@ -0,0 +1,230 @@
+use cgmath::{Point3, Vector3};
+
+use crate::{
+    block::{BlockId, Cuboid},
+    world::World,
+};
+
+// Keeps resolved boxes from resting exactly on a block boundary, where
+// the next overlap test would count them as touching.
+const SKIN: f32 = 0.001;
+const MAX_STEP: f32 = 0.45;
+// Fences reach up into the block above their own, so boxes are gathered
+// from one block lower than the region being tested.
+const TALLEST_SHAPE: i32 = 1;
+// How much a sneaking step is pulled back at a time until there's ground
+// under it again.
+const EDGE_STEP: f32 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// A box of a block's shape, placed at the block's position.
+    pub fn of_cuboid([x, y, z]: [i32 ; 3], cuboid: &Cuboid) -> Self {
+        let origin = Vector3::new(x as f32, y as f32, z as f32);
+        Self::new(Point3::from(cuboid.min) + origin, Point3::from(cuboid.max) + origin)
+    }
+
+    /// The smallest box around all of `boxes`, if there are any.
+    pub fn bounds(boxes: impl IntoIterator<Item = Aabb>) -> Option<Self> {
+        boxes.into_iter().reduce(|a, b| Self::new(
+            Point3::new(a.min.x.min(b.min.x), a.min.y.min(b.min.y), a.min.z.min(b.min.z)),
+            Point3::new(a.max.x.max(b.max.x), a.max.y.max(b.max.y), a.max.z.max(b.max.z)),
+        ))
+    }
+
+    pub fn translated(&self, offset: Vector3<f32>) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x < other.max.x && self.max.x > other.min.x
+            && self.min.y < other.max.y && self.max.y > other.min.y
+            && self.min.z < other.max.z && self.max.z > other.min.z
+    }
+
+    /// How far along a ray the box is first met, if it is at all. A ray
+    /// starting inside meets it at once.
+    pub fn ray_distance(&self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, f32::INFINITY);
+        for axis in 0..3 {
+            if direction[axis] == 0.0 {
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let a = (self.min[axis] - origin[axis]) / direction[axis];
+            let b = (self.max[axis] - origin[axis]) / direction[axis];
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+// The collision boxes of every block that could reach into `region`.
+fn world_boxes(world: &World, region: &Aabb) -> Vec<Aabb> {
+    let (x0, x1) = (region.min.x.floor() as i32, region.max.x.ceil() as i32);
+    let (y0, y1) = (region.min.y.floor() as i32 - TALLEST_SHAPE, region.max.y.ceil() as i32);
+    let (z0, z1) = (region.min.z.floor() as i32, region.max.z.ceil() as i32);
+
+    let mut boxes = Vec::new();
+    for y in y0..y1 {
+        for z in z0..z1 {
+            for x in x0..x1 {
+                if !world.get_block(x, y, z).is_solid() {
+                    continue;
+                }
+                boxes.extend(world.collision_shape(x, y, z).iter().map(|cuboid| Aabb::of_cuboid([x, y, z], cuboid)));
+            }
+        }
+    }
+    boxes
+}
+
+/// Whether any block `aabb` reaches into passes `test`.
+pub fn touches(world: &World, aabb: &Aabb, test: impl Fn(BlockId) -> bool) -> bool {
+    let (x0, x1) = (aabb.min.x.floor() as i32, aabb.max.x.ceil() as i32);
+    let (y0, y1) = (aabb.min.y.floor() as i32, aabb.max.y.ceil() as i32);
+    let (z0, z1) = (aabb.min.z.floor() as i32, aabb.max.z.ceil() as i32);
+    (y0..y1).any(|y| (z0..z1).any(|z| (x0..x1).any(|x| test(world.get_block(x, y, z)))))
+}
+
+/// How much of `aabb`'s height is in a fluid, from 0 to 1, going by the
+/// column of blocks through its middle.
+pub fn submersion(world: &World, aabb: &Aabb) -> f32 {
+    let height = aabb.max.y - aabb.min.y;
+    if height <= 0.0 {
+        return 0.0;
+    }
+    let (x, z) = (((aabb.min.x + aabb.max.x) / 2.0).floor() as i32, ((aabb.min.z + aabb.max.z) / 2.0).floor() as i32);
+    let wet = (aabb.min.y.floor() as i32..aabb.max.y.ceil() as i32)
+        .filter(|&y| world.get_block(x, y, z).is_fluid())
+        .map(|y| (aabb.max.y.min(y as f32 + 1.0) - aabb.min.y.max(y as f32)).max(0.0))
+        .sum::<f32>();
+    (wet / height).min(1.0)
+}
+
+/// Pulls the sideways part of `delta` back, an axis at a time, until
+/// `aabb` would still have something within `drop` below it to stand on,
+/// so sneaking never walks off an edge.
+pub fn keep_on_edge(world: &World, aabb: Aabb, delta: Vector3<f32>, drop: f32) -> Vector3<f32> {
+    // A thin box under the feet, which only finds what's below them.
+    let supported = |dx: f32, dz: f32| {
+        let below = Aabb::new(
+            Point3::new(aabb.min.x + dx, aabb.min.y - drop, aabb.min.z + dz),
+            Point3::new(aabb.max.x + dx, aabb.min.y, aabb.max.z + dz),
+        );
+        world_boxes(world, &below).iter().any(|other| below.intersects(other))
+    };
+    let shrink = |d: f32| if d.abs() <= EDGE_STEP { 0.0 } else { d - EDGE_STEP * d.signum() };
+
+    let (mut dx, mut dz) = (delta.x, delta.z);
+    while dx != 0.0 && !supported(dx, 0.0) {
+        dx = shrink(dx);
+    }
+    while dz != 0.0 && !supported(0.0, dz) {
+        dz = shrink(dz);
+    }
+    // Each on its own can keep to the edge while both together go past
+    // a corner.
+    while dx != 0.0 && dz != 0.0 && !supported(dx, dz) {
+        dx = shrink(dx);
+        dz = shrink(dz);
+    }
+    Vector3::new(dx, delta.y, dz)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MoveResult {
+    pub offset: Vector3<f32>,
+    pub blocked: [bool ; 3],
+}
+
+// Moves `aabb` by `delta`, one axis at a time, stopping flush against any
+// solid block in the way. Large moves are split into steps shorter than a
+// block so nothing can tunnel through thin walls.
+pub fn move_and_collide(world: &World, aabb: Aabb, delta: Vector3<f32>) -> MoveResult {
+    use cgmath::InnerSpace;
+
+    let steps = (delta.magnitude() / MAX_STEP).ceil().max(1.0) as usize;
+    let step_delta = delta / steps as f32;
+
+    let mut current = aabb;
+    let mut blocked = [false ; 3];
+
+    for _ in 0..steps {
+        for axis in [1, 0, 2] {
+            let d = step_delta[axis];
+            if d == 0.0 || blocked[axis] {
+                continue;
+            }
+
+            let mut offset = Vector3::new(0.0, 0.0, 0.0);
+            offset[axis] = d;
+            let moved = current.translated(offset);
+
+            let boxes = world_boxes(world, &moved);
+            if !boxes.iter().any(|other| moved.intersects(other)) {
+                current = moved;
+                continue;
+            }
+
+            // Snap against the nearest face in the way.
+            let mut allowed = d;
+            for other in &boxes {
+                let beside = (0..3).filter(|&a| a != axis).all(|a| current.min[a] < other.max[a] && current.max[a] > other.min[a]);
+                if !beside {
+                    continue;
+                }
+                if d > 0.0 && other.min[axis] >= current.max[axis] - SKIN {
+                    allowed = allowed.min(other.min[axis] - current.max[axis] - SKIN);
+                } else if d < 0.0 && other.max[axis] <= current.min[axis] + SKIN {
+                    allowed = allowed.max(other.max[axis] - current.min[axis] + SKIN);
+                }
+            }
+            offset[axis] = if d > 0.0 { allowed.clamp(0.0, d) } else { allowed.clamp(d, 0.0) };
+            current = current.translated(offset);
+            blocked[axis] = true;
+        }
+    }
+
+    MoveResult {
+        offset: current.min - aabb.min,
+        blocked,
+    }
+}
+
+/// Like `move_and_collide`, but first lifts the box by up to `height` and
+/// sets it back down after, so walking into a low ledge such as a slab or
+/// a stair climbs it. Whichever of the two gets further sideways wins.
+pub fn move_and_step(world: &World, aabb: Aabb, delta: Vector3<f32>, height: f32) -> MoveResult {
+    let plain = move_and_collide(world, aabb, delta);
+    if height <= 0.0 || !(plain.blocked[0] || plain.blocked[2]) {
+        return plain;
+    }
+
+    let up = move_and_collide(world, aabb, Vector3::new(0.0, height, 0.0));
+    let raised = aabb.translated(up.offset);
+    let across = move_and_collide(world, raised, Vector3::new(delta.x, 0.0, delta.z));
+    let moved = raised.translated(across.offset);
+    let down = move_and_collide(world, moved, Vector3::new(0.0, delta.y.min(0.0) - up.offset.y, 0.0));
+
+    let sideways = |offset: Vector3<f32>| offset.x * offset.x + offset.z * offset.z;
+    if sideways(across.offset) <= sideways(plain.offset) {
+        return plain;
+    }
+    MoveResult {
+        offset: up.offset + across.offset + down.offset,
+        blocked: [across.blocked[0], down.blocked[1], across.blocked[2]],
+    }
+}
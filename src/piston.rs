@@ -0,0 +1,262 @@
+//! Pistons. Powered, a piston pushes the line of blocks in front of it one
+//! block along and puts its head out into the space they leave; once the
+//! power goes, it draws the head back in, leaving the blocks where they
+//! are.
+//!
+//! A push moves at most `MAX_PUSH` blocks, and nothing at all if the line
+//! runs into a block that can't move, like a chest or a door. Small things
+//! the line runs into, like torches and wire, break off to make room, as
+//! do any that hang on or stand on a block being moved.
+//!
+//! Blocks get where they're going straight away. The client then shows
+//! them sliding there, with an invisible placeholder holding their place
+//! until they arrive.
+
+use std::collections::HashSet;
+
+use cgmath::Point3;
+
+use crate::{
+    block::{Axis, BlockId, BlockModel, BlockState, Properties},
+    chunk::ChunkPos,
+    ecs::{Ecs, Entity, MeshRef, Transform},
+    item,
+    world::World,
+};
+
+/// The most blocks one piston can push.
+pub const MAX_PUSH: usize = 12;
+
+// Seconds a pushed block takes to slide into its new place.
+const MOVE_TIME: f32 = 0.15;
+
+const NEIGHBORS: [[i32 ; 3] ; 6] = [[1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+
+/// A block a piston moved one block along.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockMove {
+    pub state: BlockState,
+    pub from: [i32 ; 3],
+    pub to: [i32 ; 3],
+}
+
+/// What pistons did: the blocks they moved, and those that broke off to
+/// make way.
+#[derive(Debug, Default)]
+pub struct Pushed {
+    pub moves: Vec<BlockMove>,
+    pub broken: Vec<([i32 ; 3], BlockId)>,
+}
+
+impl Pushed {
+    pub fn extend(&mut self, other: Pushed) {
+        self.moves.extend(other.moves);
+        self.broken.extend(other.broken);
+    }
+}
+
+/// A block sliding from where a piston pushed it to `to`.
+#[derive(Clone, Copy, Debug)]
+pub struct MovingBlock {
+    pub state: BlockState,
+    pub from: [i32 ; 3],
+    pub to: [i32 ; 3],
+    elapsed: f32,
+}
+
+// Where a line of pushed blocks stops: an empty space, or a block that
+// breaks off rather than being pushed.
+fn ends_line(block: BlockId) -> bool {
+    block.is_air() || block.is_fluid() || breaks_off(block)
+}
+
+// Small blocks that break when pushed, or when what holds them up moves.
+fn breaks_off(block: BlockId) -> bool {
+    !block.is_solid()
+        || matches!(
+            block.info().model,
+            BlockModel::Cross | BlockModel::Torch | BlockModel::Ladder | BlockModel::Wire | BlockModel::Lever | BlockModel::Button
+        )
+}
+
+// Blocks with a block entity, the parts of two-block things, and blocks
+// already on the move stay put, and stop any push that reaches them.
+fn is_movable(world: &World, [x, y, z]: [i32 ; 3]) -> bool {
+    let state = world.get_state(x, y, z);
+    let fixed = match state.block.info().model {
+        BlockModel::Piston => state.properties.powered(),
+        BlockModel::PistonHead | BlockModel::Moving | BlockModel::Door => true,
+        _ => false,
+    };
+    !fixed && world.block_entity(x, y, z).is_none()
+}
+
+// The block something that breaks off hangs on or stands on: the wall
+// behind a ladder or a switch mounted on one, and otherwise the block
+// below.
+fn support(state: BlockState, [x, y, z]: [i32 ; 3]) -> [i32 ; 3] {
+    let on_wall = state.block.is_climbable() || (state.block.is_switch() && state.properties.axis() != Axis::Y);
+    if on_wall {
+        let [dx, _, dz] = state.properties.facing().offset();
+        return [x - dx, y, z - dz];
+    }
+    [x, y - 1, z]
+}
+
+fn is_loaded(world: &World, [x, y, z]: [i32 ; 3]) -> bool {
+    world.chunks.contains_key(&ChunkPos::from_block(x, y, z))
+}
+
+/// Extends the piston at `piston` if it's `powered` and retracts it if it
+/// isn't, unless it already is. Returns what moved, which is nothing if a
+/// push was blocked; the piston stays retracted then, until a change near
+/// it gives it another try.
+pub fn update(world: &mut World, piston: [i32 ; 3], powered: bool) -> Pushed {
+    let [x, y, z] = piston;
+    let state = world.get_state(x, y, z);
+    if state.block.info().model != BlockModel::Piston || state.properties.powered() == powered {
+        return Pushed::default();
+    }
+    let facing = state.properties.facing();
+    let [dx, _, dz] = facing.offset();
+    let ahead = |[x, y, z]: [i32 ; 3]| [x + dx, y, z + dz];
+    let head_id = BlockId::from_name("piston_head");
+
+    if !powered {
+        let [hx, hy, hz] = ahead(piston);
+        let head = world.get_state(hx, hy, hz);
+        if Some(head.block) == head_id && head.properties.facing() == facing {
+            world.set_block(hx, hy, hz, BlockId::AIR);
+        }
+        world.set_state(x, y, z, BlockState::new(state.block, state.properties.with_powered(false)));
+        return Pushed::default();
+    }
+
+    let Some(head_id) = head_id else {
+        return Pushed::default();
+    };
+    let mut line = Vec::new();
+    let mut end = ahead(piston);
+    loop {
+        if !is_loaded(world, end) {
+            return Pushed::default();
+        }
+        let [ex, ey, ez] = end;
+        if ends_line(world.get_block(ex, ey, ez)) {
+            break;
+        }
+        if line.len() == MAX_PUSH || !is_movable(world, end) {
+            return Pushed::default();
+        }
+        line.push(end);
+        end = ahead(end);
+    }
+
+    // Whatever hangs on or stands on a block that's moving breaks off, as
+    // does whatever the line ends at.
+    let mut pushed = Pushed::default();
+    let mut breaking = HashSet::new();
+    for &[bx, by, bz] in &line {
+        for [nx, ny, nz] in NEIGHBORS {
+            let neighbor = [bx + nx, by + ny, bz + nz];
+            let attached = world.get_state(neighbor[0], neighbor[1], neighbor[2]);
+            if !line.contains(&neighbor) && breaks_off(attached.block) && !attached.block.is_air()
+                && support(attached, neighbor) == [bx, by, bz] {
+                breaking.insert(neighbor);
+            }
+        }
+    }
+    let [ex, ey, ez] = end;
+    let end_block = world.get_block(ex, ey, ez);
+    if !end_block.is_air() && !end_block.is_fluid() {
+        breaking.insert(end);
+    }
+    for [bx, by, bz] in breaking {
+        let block = world.get_block(bx, by, bz);
+        if world.set_block(bx, by, bz, BlockId::AIR) {
+            pushed.broken.push(([bx, by, bz], block));
+        }
+    }
+
+    // Farthest first, so each goes into the space the last one left.
+    for &from in line.iter().rev() {
+        let [fx, fy, fz] = from;
+        let moved = world.get_state(fx, fy, fz);
+        let [tx, ty, tz] = ahead(from);
+        world.set_state(tx, ty, tz, moved);
+        pushed.moves.push(BlockMove { state: moved, from, to: [tx, ty, tz] });
+    }
+    let [hx, hy, hz] = ahead(piston);
+    world.set_state(hx, hy, hz, BlockState::new(head_id, Properties::default().with_facing(facing)));
+    world.set_state(x, y, z, BlockState::new(state.block, state.properties.with_powered(true)));
+    pushed
+}
+
+/// Shows what pistons did: drops what broke off, and has each block that
+/// moved slide to where it went, holding its place with a placeholder
+/// until it gets there.
+pub fn animate(ecs: &mut Ecs, world: &mut World, pushed: Pushed) {
+    if !world.game_mode.infinite_blocks() {
+        for ([x, y, z], block) in pushed.broken {
+            if let Some(drop) = block.info().drops {
+                item::spawn_dropped_item(ecs, drop, x, y, z);
+            }
+        }
+    }
+
+    let Some(placeholder) = BlockId::from_name("moving_block") else {
+        return;
+    };
+    for BlockMove { state, from, to } in pushed.moves {
+        let [x, y, z] = to;
+        // Something else may have happened to it already.
+        if world.get_state(x, y, z) != state || !world.set_block(x, y, z, placeholder) {
+            continue;
+        }
+        let entity = ecs.spawn();
+        let [fx, fy, fz] = from;
+        ecs.transforms.insert(entity, Transform::new(Point3::new(fx as f32 + 0.5, fy as f32, fz as f32 + 0.5)));
+        ecs.meshes.insert(entity, MeshRef::State(state));
+        ecs.moving_blocks.insert(entity, MovingBlock { state, from, to, elapsed: 0.0 });
+    }
+}
+
+/// Slides moving blocks along, and puts each back in the world as a block
+/// once it arrives.
+pub fn moving_block_system(ecs: &mut Ecs, world: &mut World, dt: f32) {
+    let mut arrived = Vec::new();
+    for (entity, moving) in ecs.moving_blocks.iter_mut() {
+        moving.elapsed += dt;
+        let t = (moving.elapsed / MOVE_TIME).min(1.0);
+        let [from, to] = [moving.from, moving.to].map(|[x, y, z]| Point3::new(x as f32 + 0.5, y as f32, z as f32 + 0.5));
+        if let Some(transform) = ecs.transforms.get_mut(entity) {
+            transform.position = from + (to - from) * t;
+        }
+        if t >= 1.0 {
+            arrived.push(entity);
+        }
+    }
+    for entity in arrived {
+        land(ecs, world, entity);
+    }
+}
+
+/// Puts every moving block straight into its place, for before the world
+/// is saved.
+pub fn finish_moves(ecs: &mut Ecs, world: &mut World) {
+    let moving: Vec<_> = ecs.moving_blocks.iter().map(|(entity, _)| entity).collect();
+    for entity in moving {
+        land(ecs, world, entity);
+    }
+}
+
+fn land(ecs: &mut Ecs, world: &mut World, entity: Entity) {
+    let Some(&MovingBlock { state, to: [x, y, z], .. }) = ecs.moving_blocks.get(entity) else {
+        return;
+    };
+    ecs.despawn(entity);
+    // Unless its placeholder was broken or replaced on the way.
+    if world.get_block(x, y, z).info().model == BlockModel::Moving {
+        world.set_state(x, y, z, state);
+    }
+}
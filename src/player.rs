@@ -0,0 +1,158 @@
+use cgmath::{Point3, Vector3};
+
+use crate::{
+    ecs::{Collider, Ecs, Entity, Transform, Velocity},
+    game_mode::GameMode,
+    hunger,
+};
+
+pub const PLAYER_HALF_WIDTH: f32 = 0.3;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+// Enough to walk up slabs and stairs but not full blocks.
+const STEP_HEIGHT: f32 = 0.6;
+// How much faster sprinting, and slower sneaking and swimming, are than
+// walking.
+const SPRINT_FACTOR: f32 = 1.3;
+const SNEAK_FACTOR: f32 = 0.3;
+const SWIM_FACTOR: f32 = 0.6;
+// How high the eyes are standing and sneaking, and how many blocks a
+// second they move between the two.
+const EYE_HEIGHT: f32 = 1.62;
+const SNEAK_EYE_HEIGHT: f32 = 1.32;
+const EYE_SPEED: f32 = 2.0;
+/// How much of the player has to be in a fluid for them to swim rather
+/// than wade.
+pub const SWIM_DEPTH: f32 = 0.4;
+/// How fast the player swims up or dives while holding jump or sneak.
+pub const SWIM_SPEED: f32 = 4.0;
+/// How fast the player climbs ladders, and slides down them when not
+/// climbing or holding on by sneaking.
+pub const CLIMB_SPEED: f32 = 3.0;
+pub const LADDER_SLIDE_SPEED: f32 = 1.5;
+/// Looking further down than this, in radians, climbs down a ladder
+/// rather than up it.
+pub const CLIMB_DOWN_PITCH: f32 = -0.5;
+
+/// Player-specific state; position, velocity and collision live in the
+/// shared components.
+pub struct Player {
+    pub eye_height: f32,
+    pub jump_speed: f32,
+    pub fly_speed: f32,
+    pub flying: bool,
+    /// Whether the game mode lets the player take off.
+    pub can_fly: bool,
+    pub sprinting: bool,
+    /// Sneaking is slower and lower, and keeps to the edges of what the
+    /// player stands on.
+    pub sneaking: bool,
+    pub swimming: bool,
+}
+
+impl Player {
+    pub fn new() -> Self {
+        Self {
+            eye_height: EYE_HEIGHT,
+            jump_speed: 8.5,
+            fly_speed: 10.0,
+            flying: false,
+            can_fly: false,
+            sprinting: false,
+            sneaking: false,
+            swimming: false,
+        }
+    }
+
+    /// Walking speed scaled up while sprinting, and down while sneaking
+    /// or swimming.
+    pub fn ground_speed(&self, walk_speed: f32) -> f32 {
+        let mut speed = walk_speed;
+        if self.sprinting {
+            speed *= SPRINT_FACTOR;
+        }
+        if self.sneaking {
+            speed *= SNEAK_FACTOR;
+        }
+        if self.swimming {
+            speed *= SWIM_FACTOR;
+        }
+        speed
+    }
+
+    /// Moves the eyes `dt` seconds' worth towards the height for sneaking
+    /// or standing.
+    pub fn update_eye_height(&mut self, dt: f32) {
+        let target = if self.sneaking { SNEAK_EYE_HEIGHT } else { EYE_HEIGHT };
+        let step = EYE_SPEED * dt;
+        self.eye_height += (target - self.eye_height).clamp(-step, step);
+    }
+}
+
+pub fn spawn(ecs: &mut Ecs, position: Point3<f32>) -> Entity {
+    let entity = ecs.spawn();
+    ecs.transforms.insert(entity, Transform::new(position));
+    ecs.velocities.insert(entity, Velocity::default());
+    ecs.colliders.insert(entity, Collider {
+        step_height: STEP_HEIGHT,
+        swims: true,
+        climbs: true,
+        ..Collider::new(PLAYER_HALF_WIDTH, PLAYER_HEIGHT)
+    });
+    ecs.players.insert(entity, Player::new());
+    entity
+}
+
+pub fn eye_position(ecs: &Ecs, entity: Entity) -> Point3<f32> {
+    interpolated_eye_position(ecs, entity, 1.0)
+}
+
+/// Where the eyes are drawn from, `alpha` of the way through the current
+/// tick.
+pub fn interpolated_eye_position(ecs: &Ecs, entity: Entity, alpha: f32) -> Point3<f32> {
+    let position = ecs.transforms.get(entity).map_or(Point3::new(0.0, 0.0, 0.0), |t| t.interpolated(alpha));
+    let eye_height = ecs.players.get(entity).map_or(0.0, |p| p.eye_height);
+    position + Vector3::unit_y() * eye_height
+}
+
+// Fly mode ignores gravity and collision entirely.
+pub fn set_flying(ecs: &mut Ecs, entity: Entity, flying: bool) {
+    if let Some(player) = ecs.players.get_mut(entity) {
+        player.flying = flying;
+    }
+    if let Some(collider) = ecs.colliders.get_mut(entity) {
+        collider.gravity = !flying;
+        collider.collides = !flying;
+    }
+    if let Some(velocity) = ecs.velocities.get_mut(entity) {
+        *velocity = Velocity::default();
+    }
+}
+
+/// Lets the player fly or not as `mode` allows, landing them if they're in
+/// the air and no longer can be.
+pub fn set_game_mode(ecs: &mut Ecs, entity: Entity, mode: GameMode) {
+    let Some(player) = ecs.players.get_mut(entity) else {
+        return;
+    };
+    player.can_fly = mode.can_fly();
+    if player.flying && !player.can_fly {
+        set_flying(ecs, entity, false);
+    }
+}
+
+pub fn jump(ecs: &mut Ecs, entity: Entity) {
+    let (Some(player), Some(collider), Some(Velocity(velocity))) = (
+        ecs.players.get(entity),
+        ecs.colliders.get_mut(entity),
+        ecs.velocities.get_mut(entity),
+    ) else {
+        return;
+    };
+
+    if collider.on_ground && !player.flying {
+        velocity.y = player.jump_speed;
+        collider.on_ground = false;
+        let exhaustion = if player.sprinting { hunger::SPRINT_JUMP_EXHAUSTION } else { hunger::JUMP_EXHAUSTION };
+        hunger::exhaust(ecs, entity, exhaustion);
+    }
+}
@@ -0,0 +1,144 @@
+//! The blocky player model drawn for other players and for yourself in
+//! third person: a head, torso, arms and legs, each a box with a block's
+//! textures. The limbs swing while walking and the right arm swings when
+//! breaking or placing. Mobs shaped like people use it with their own
+//! skins.
+
+use std::f32::consts::PI;
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3};
+
+use crate::{
+    block::{BlockId, Face},
+    ecs::{Ecs, Entity, Transform},
+    mesher::ChunkMesh,
+    player::PLAYER_HEIGHT,
+};
+
+// The model is laid out in pixels, with the feet at 0 and the top of the
+// head at MODEL_HEIGHT, then scaled to the player's height.
+const MODEL_HEIGHT: f32 = 32.0;
+
+const HEAD_BLOCK: BlockId = BlockId::SAND;
+const TORSO_BLOCK: BlockId = BlockId::PLANKS;
+const ARM_BLOCK: BlockId = BlockId::SAND;
+const LEG_BLOCK: BlockId = BlockId::LOG;
+
+// Walking speed, in blocks per second, at which the limbs swing fully.
+const FULL_WALK_SPEED: f32 = 4.3;
+// Radians of the walk cycle per block walked.
+const STRIDE_RATE: f32 = 2.2;
+const MAX_LEG_ANGLE: f32 = 0.7;
+const MAX_ARM_ANGLE: f32 = 0.55;
+// How quickly the swing eases in and out when starting and stopping.
+const WALK_BLEND_RATE: f32 = 8.0;
+const ARM_SWING_TIME: f32 = 0.3;
+const ARM_SWING_ANGLE: f32 = 1.4;
+
+/// The texture layers on the model's parts, one per face in the order of
+/// `Face::ALL`. The model faces +x, so that face is the front.
+#[derive(Clone, Copy, Debug)]
+pub struct Skin {
+    pub head: [u32 ; 6],
+    pub torso: [u32 ; 6],
+    pub arm: [u32 ; 6],
+    pub leg: [u32 ; 6],
+}
+
+impl Skin {
+    /// How players look, made of block textures.
+    pub fn player() -> Self {
+        let block = |block: BlockId| Face::ALL.map(|face| block.texture(face));
+        Self {
+            head: block(HEAD_BLOCK),
+            torso: block(TORSO_BLOCK),
+            arm: block(ARM_BLOCK),
+            leg: block(LEG_BLOCK),
+        }
+    }
+}
+
+/// Animation state for an entity drawn with the player model.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlayerAnimation {
+    last_position: Option<Point3<f32>>,
+    walk_phase: f32,
+    // From 0 standing still to 1 walking at full speed.
+    walk_amount: f32,
+    // Counts down from ARM_SWING_TIME while the arm swings.
+    arm_swing: f32,
+}
+
+/// Swings the right arm, as when hitting or placing a block.
+pub fn swing_arm(ecs: &mut Ecs, entity: Entity) {
+    if let Some(animation) = ecs.animations.get_mut(entity) {
+        animation.arm_swing = ARM_SWING_TIME;
+    }
+}
+
+impl PlayerAnimation {
+    /// Where the walk cycle is, from -1 to 1, scaled down towards 0 when
+    /// standing still. Legs swing this far forward and back.
+    pub fn stride(&self) -> f32 {
+        self.walk_phase.sin() * self.walk_amount
+    }
+}
+
+/// Advances every player animation from how far its entity was drawn
+/// moving since the last frame, `alpha` of the way through the current
+/// tick, so remote players, which have no velocity of their own, animate
+/// the same way as the local one.
+pub fn animation_system(ecs: &mut Ecs, alpha: f32, dt: f32) {
+    let Ecs { transforms, animations, .. } = ecs;
+
+    for (entity, animation) in animations.iter_mut() {
+        let Some(transform) = transforms.get(entity) else {
+            continue;
+        };
+        let position = transform.interpolated(alpha);
+        let moved = animation.last_position.replace(position).map_or(0.0, |last| {
+            Vector3::new(position.x - last.x, 0.0, position.z - last.z).magnitude()
+        });
+
+        let speed = if dt > 0.0 { moved / dt } else { 0.0 };
+        let target = (speed / FULL_WALK_SPEED).min(1.0);
+        animation.walk_amount += (target - animation.walk_amount) * (WALK_BLEND_RATE * dt).min(1.0);
+        animation.walk_phase = (animation.walk_phase + moved * STRIDE_RATE) % (2.0 * PI);
+        animation.arm_swing = (animation.arm_swing - dt).max(0.0);
+    }
+}
+
+/// Adds the model's boxes to `mesh`, standing at the transform and facing
+/// along its yaw. Raised arms reach straight forward, the way zombies walk.
+pub fn push_model(mesh: &mut ChunkMesh, transform: &Transform, animation: Option<&PlayerAnimation>, skin: &Skin, arms_raised: bool) {
+    let animation = animation.copied().unwrap_or_default();
+    // The model faces +x; yaw is measured the same way as the camera's,
+    // which turns the other way around y from cgmath's rotations.
+    let root = Matrix4::from_translation(transform.position.to_vec())
+        * Matrix4::from_angle_y(Rad(-transform.yaw))
+        * Matrix4::from_scale(PLAYER_HEIGHT / MODEL_HEIGHT);
+
+    let stride = animation.stride();
+    let arm_angle = if arms_raised { PI / 2.0 } else { 0.0 };
+    let swing_progress = 1.0 - animation.arm_swing / ARM_SWING_TIME;
+    let swing = if animation.arm_swing > 0.0 { (swing_progress * PI).sin() * ARM_SWING_ANGLE } else { 0.0 };
+
+    // Each part is (textures, pivot, angle about the model's side axis,
+    // box minimum relative to the pivot, box size).
+    let parts = [
+        (skin.head, [0.0, 24.0, 0.0], 0.0, [-4.0, 0.0, -4.0], [8.0, 8.0, 8.0]),
+        (skin.torso, [0.0, 12.0, 0.0], 0.0, [-2.0, 0.0, -4.0], [4.0, 12.0, 8.0]),
+        (skin.arm, [0.0, 22.0, -6.0], arm_angle - stride * MAX_ARM_ANGLE, [-2.0, -10.0, -2.0], [4.0, 12.0, 4.0]),
+        (skin.arm, [0.0, 22.0, 6.0], arm_angle + stride * MAX_ARM_ANGLE + swing, [-2.0, -10.0, -2.0], [4.0, 12.0, 4.0]),
+        (skin.leg, [0.0, 12.0, -2.0], stride * MAX_LEG_ANGLE, [-2.0, -12.0, -2.0], [4.0, 12.0, 4.0]),
+        (skin.leg, [0.0, 12.0, 2.0], -stride * MAX_LEG_ANGLE, [-2.0, -12.0, -2.0], [4.0, 12.0, 4.0]),
+    ];
+    for (textures, pivot, angle, min, size) in parts {
+        let model = root
+            * Matrix4::from_translation(pivot.into())
+            * Matrix4::from_angle_z(Rad(angle))
+            * Matrix4::from_translation(min.into())
+            * Matrix4::from_nonuniform_scale(size[0], size[1], size[2]);
+        mesh.push_transformed_box(textures, model);
+    }
+}
@@ -0,0 +1,165 @@
+//! Portals. Standing in a portal block for a moment takes the player to
+//! the other dimension, to the same place scaled by how far apart places
+//! are there: into a portal already close by if there is one, or else
+//! onto the nearest ground with room to stand, where a portal is put to
+//! come back through.
+
+use cgmath::Point3;
+
+use crate::{
+    block::{BlockId, BlockModel, BlockState, Facing, Properties},
+    border::WorldBorder,
+    dimension::Dimension,
+    ecs::{Ecs, Entity},
+    physics,
+    world::World,
+};
+
+/// Seconds of standing in a portal before it takes the player.
+pub const TRAVEL_TIME: f32 = 2.0;
+/// How far around where the player comes out to look for a portal to
+/// arrive in, or ground to stand on, in blocks each way.
+pub const SEARCH_RADIUS: i32 = 8;
+
+/// How long the player has stood in a portal.
+#[derive(Debug, Default)]
+pub struct PortalTimer {
+    time: f32,
+    // Set on arriving in a portal, until the player steps out of it, so
+    // they aren't sent straight back.
+    arrived: bool,
+}
+
+impl PortalTimer {
+    /// Counts the time the player is `inside` a portal, and says when
+    /// they've been in one long enough to go through it.
+    pub fn update(&mut self, inside: bool, dt: f32) -> bool {
+        if !inside {
+            *self = Self::default();
+            return false;
+        }
+        if self.arrived {
+            return false;
+        }
+        self.time += dt;
+        if self.time < TRAVEL_TIME {
+            return false;
+        }
+        self.arrive();
+        true
+    }
+
+    /// How far through the wait to go through the portal the player is,
+    /// from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        self.time / TRAVEL_TIME
+    }
+
+    /// Starts over for a player who's just come out in a portal.
+    pub fn arrive(&mut self) {
+        self.time = 0.0;
+        self.arrived = true;
+    }
+}
+
+pub fn is_portal(block: BlockId) -> bool {
+    block.info().model == BlockModel::Portal
+}
+
+/// Whether `entity` is in a portal block.
+pub fn in_portal(ecs: &Ecs, world: &World, entity: Entity) -> bool {
+    let (Some(transform), Some(collider)) = (ecs.transforms.get(entity), ecs.colliders.get(entity)) else {
+        return false;
+    };
+    physics::touches(world, &collider.aabb(transform.position), is_portal)
+}
+
+/// Where `position` in `from` leads to in `to`: the same height, and
+/// across by as far as it is in overworld blocks, kept inside `border`.
+pub fn destination(position: Point3<f32>, from: Dimension, to: Dimension, border: WorldBorder) -> [i32 ; 3] {
+    let scale = from.scale() / to.scale();
+    let mut target = Point3::new(position.x * scale, position.y, position.z * scale);
+    border.clamp(&mut target, SEARCH_RADIUS as f32);
+    [target.x.floor() as i32, target.y.floor() as i32, target.z.floor() as i32]
+}
+
+/// Where around `target` the player arrives: under open sky, on the
+/// surface rather than in a cave.
+pub fn landing(world: &World, [x, y, z]: [i32 ; 3]) -> [i32 ; 3] {
+    let y = if world.dimension().has_sky() { world.generator.height_at(x, z) + 1 } else { y };
+    [x, y, z]
+}
+
+/// Where the player comes out, at their feet, when they arrive at their
+/// `landing` through a portal. The chunks `SEARCH_RADIUS` around it need
+/// to be loaded.
+pub fn arrive(world: &mut World, landing: [i32 ; 3]) -> Point3<f32> {
+    let [x, y, z] = find_portal(world, landing).unwrap_or_else(|| make_portal(world, landing));
+    Point3::new(x as f32 + 0.5, y as f32, z as f32 + 0.5)
+}
+
+// The bottom of the nearest portal around `target`.
+fn find_portal(world: &World, [tx, ty, tz]: [i32 ; 3]) -> Option<[i32 ; 3]> {
+    let mut nearest = None;
+    let mut nearest_distance = i32::MAX;
+    for z in tz - SEARCH_RADIUS..=tz + SEARCH_RADIUS {
+        for x in tx - SEARCH_RADIUS..=tx + SEARCH_RADIUS {
+            for y in ty - SEARCH_RADIUS..=ty + SEARCH_RADIUS {
+                if !is_portal(world.get_block(x, y, z)) || is_portal(world.get_block(x, y - 1, z)) {
+                    continue;
+                }
+                let distance = (x - tx).pow(2) + (y - ty).pow(2) + (z - tz).pow(2);
+                if distance < nearest_distance {
+                    nearest = Some([x, y, z]);
+                    nearest_distance = distance;
+                }
+            }
+        }
+    }
+    nearest
+}
+
+// Puts a portal two blocks tall on the nearest ground with room for it
+// around `target`, or, failing that, hollows out room at `target` on a
+// platform of its own.
+fn make_portal(world: &mut World, [tx, ty, tz]: [i32 ; 3]) -> [i32 ; 3] {
+    // Under the roof, in the nether, with room for the portal and a head.
+    let top = world.generator.height_at(tx, tz) - 2;
+    let mut nearest = None;
+    let mut nearest_distance = i32::MAX;
+    for z in tz - SEARCH_RADIUS..=tz + SEARCH_RADIUS {
+        for x in tx - SEARCH_RADIUS..=tx + SEARCH_RADIUS {
+            for y in ty - SEARCH_RADIUS..=ty + SEARCH_RADIUS {
+                if !has_room(world, [x, y, z]) {
+                    continue;
+                }
+                let distance = (x - tx).pow(2) + (y - ty).pow(2) + (z - tz).pow(2);
+                if distance < nearest_distance {
+                    nearest = Some([x, y, z]);
+                    nearest_distance = distance;
+                }
+            }
+        }
+    }
+
+    let [x, y, z] = nearest.unwrap_or_else(|| {
+        let y = ty.min(top).max(2);
+        world.set_block(tx, y - 1, tz, BlockId::COBBLESTONE);
+        for dy in 0..3 {
+            world.set_block(tx, y + dy, tz, BlockId::AIR);
+        }
+        [tx, y, tz]
+    });
+    if let Some(portal) = BlockId::from_name("portal") {
+        let state = BlockState::new(portal, Properties::default().with_facing(Facing::North));
+        world.set_state(x, y, z, state);
+        world.set_state(x, y + 1, z, state);
+    }
+    [x, y, z]
+}
+
+// Solid ground underfoot, and open air for a portal and a head above it.
+fn has_room(world: &World, [x, y, z]: [i32 ; 3]) -> bool {
+    let ground = world.get_block(x, y - 1, z);
+    ground.is_solid() && !ground.is_fluid() && (0..3).all(|dy| world.get_block(x, y + dy, z).is_air())
+}
@@ -0,0 +1,284 @@
+use wgpu::util::DeviceExt;
+
+use crate::{config::PostProcessConfig, texture};
+
+const FLAG_TONEMAP: u32 = 1;
+const FLAG_VIGNETTE: u32 = 2;
+const FLAG_FXAA: u32 = 4;
+const FLAG_ENCODE_SRGB: u32 = 8;
+
+/// The format the scene is rendered in before post-processing. Float
+/// targets keep bright values like the sun's glow above 1 for the
+/// tonemapper; adapters that can't render to them fall back to 8 bits.
+pub fn scene_format(adapter: &wgpu::Adapter) -> wgpu::TextureFormat {
+    let hdr = wgpu::TextureFormat::Rgba16Float;
+    let usages = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+    if adapter.get_texture_format_features(hdr).allowed_usages.contains(usages) {
+        hdr
+    } else {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    }
+}
+
+/// Every sample count the adapter can render the scene and its depth
+/// buffer with, from 1 up.
+pub fn supported_sample_counts(adapter: &wgpu::Adapter, format: wgpu::TextureFormat) -> Vec<u32> {
+    let color = adapter.get_texture_format_features(format).flags;
+    let depth = adapter.get_texture_format_features(texture::Texture::DEPTH_FORMAT).flags;
+    [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&count| count == 1 || (color.sample_count_supported(count) && depth.sample_count_supported(count)))
+        .collect()
+}
+
+/// The closest of the `supported` sample counts to `requested`, never
+/// rounding up.
+pub fn closest_sample_count(supported: &[u32], requested: u32) -> u32 {
+    supported.iter().copied().filter(|&count| count <= requested).max().unwrap_or(1)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    exposure: f32,
+    gamma: f32,
+    vignette_strength: f32,
+    flags: u32,
+}
+
+impl PostUniform {
+    fn new(settings: &PostProcessConfig, encode_srgb: bool) -> Self {
+        let mut flags = 0;
+        for (enabled, flag) in [
+            (settings.tonemapping, FLAG_TONEMAP),
+            (settings.vignette, FLAG_VIGNETTE),
+            (settings.fxaa, FLAG_FXAA),
+            (encode_srgb, FLAG_ENCODE_SRGB),
+        ] {
+            if enabled {
+                flags |= flag;
+            }
+        }
+
+        Self {
+            exposure: settings.exposure,
+            gamma: settings.gamma.max(0.1),
+            vignette_strength: settings.vignette_strength,
+            flags,
+        }
+    }
+}
+
+/// Owns the offscreen scene target and the fullscreen pass that tonemaps
+/// it onto the surface, applying gamma, vignette and FXAA on the way.
+pub struct PostProcessor {
+    pub scene_format: wgpu::TextureFormat,
+    pub sample_count: u32,
+    scene_view: wgpu::TextureView,
+    // Multisampled color the scene is drawn into when MSAA is on; it's
+    // resolved into `scene_view` before post-processing.
+    msaa_view: Option<wgpu::TextureView>,
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl PostProcessor {
+    pub fn new(
+        device: &wgpu::Device,
+        scene_format: wgpu::TextureFormat,
+        sample_count: u32,
+        config: &wgpu::SurfaceConfiguration,
+        settings: &PostProcessConfig,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("post.wgsl"));
+
+        // An sRGB surface encodes on write, so the shader only does it
+        // itself for linear ones.
+        let uniform = PostUniform::new(settings, !config.format.is_srgb());
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Process Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Process Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("post_process_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Process Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post Process Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let scene_view = Self::create_scene_view(device, scene_format, 1, config.width, config.height);
+        let msaa_view = (sample_count > 1).then(|| {
+            Self::create_scene_view(device, scene_format, sample_count, config.width, config.height)
+        });
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &scene_view, &sampler, &buffer);
+
+        Self {
+            scene_format,
+            sample_count,
+            scene_view,
+            msaa_view,
+            sampler,
+            buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_scene_view(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        width: u32,
+        height: u32,
+    ) -> wgpu::TextureView {
+        // The multisampled target is only ever resolved, never sampled.
+        let (label, usage) = if sample_count > 1 {
+            ("Scene Color (MSAA)", wgpu::TextureUsages::RENDER_ATTACHMENT)
+        } else {
+            ("Scene Color", wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        scene_view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(scene_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("post_process_bind_group"),
+        })
+    }
+
+    /// The scene targets have to match the surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.scene_view = Self::create_scene_view(device, self.scene_format, 1, width, height);
+        if self.msaa_view.is_some() {
+            self.msaa_view = Some(Self::create_scene_view(device, self.scene_format, self.sample_count, width, height));
+        }
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.scene_view,
+            &self.sampler,
+            &self.buffer,
+        );
+    }
+
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.scene_view
+    }
+
+    pub fn msaa_view(&self) -> Option<&wgpu::TextureView> {
+        self.msaa_view.as_ref()
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
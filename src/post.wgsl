@@ -0,0 +1,129 @@
+struct PostUniform {
+    exposure: f32,
+    gamma: f32,
+    vignette_strength: f32,
+    flags: u32,
+}
+
+const FLAG_TONEMAP: u32 = 1u;
+const FLAG_VIGNETTE: u32 = 2u;
+const FLAG_FXAA: u32 = 4u;
+// Set when the surface doesn't encode sRGB itself.
+const FLAG_ENCODE_SRGB: u32 = 8u;
+
+@group(0) @binding(0)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(1)
+var s_scene: sampler;
+@group(0) @binding(2)
+var<uniform> post: PostUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A single triangle that covers the whole screen.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Texture space has y pointing down.
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+fn has_flag(flag: u32) -> bool {
+    return (post.flags & flag) != 0u;
+}
+
+// Narkowicz's fit of the ACES filmic curve.
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Scene color brought into displayable [0, 1] range.
+fn sample_ldr(uv: vec2<f32>) -> vec3<f32> {
+    let hdr = textureSampleLevel(t_scene, s_scene, uv, 0.0).rgb * post.exposure;
+    if has_flag(FLAG_TONEMAP) {
+        return aces(hdr);
+    }
+    return clamp(hdr, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+const FXAA_REDUCE_MIN: f32 = 1.0 / 128.0;
+const FXAA_REDUCE_MUL: f32 = 1.0 / 8.0;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+// The classic single-pass FXAA: estimate the edge direction from the
+// diagonal neighbours and blur along it, falling back to a shorter blur
+// when the longer one picks up colors from across the edge.
+fn fxaa(uv: vec2<f32>, texel: vec2<f32>) -> vec3<f32> {
+    let rgb_m = sample_ldr(uv);
+    let luma_nw = luma(sample_ldr(uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_ldr(uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample_ldr(uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample_ldr(uv + vec2<f32>(1.0, 1.0) * texel));
+    let luma_m = luma(rgb_m);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX)) * texel;
+
+    let rgb_a = 0.5 * (sample_ldr(uv + dir * (1.0 / 3.0 - 0.5)) + sample_ldr(uv + dir * (2.0 / 3.0 - 0.5)));
+    let rgb_b = rgb_a * 0.5 + 0.25 * (sample_ldr(uv - dir * 0.5) + sample_ldr(uv + dir * 0.5));
+    let luma_b = luma(rgb_b);
+    if luma_b < luma_min || luma_b > luma_max {
+        return rgb_a;
+    }
+    return rgb_b;
+}
+
+fn encode_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_scene));
+
+    var color: vec3<f32>;
+    if has_flag(FLAG_FXAA) {
+        color = fxaa(in.uv, texel);
+    } else {
+        color = sample_ldr(in.uv);
+    }
+
+    color = pow(color, vec3<f32>(1.0 / post.gamma));
+
+    if has_flag(FLAG_VIGNETTE) {
+        // Distance from the center, 1 at the corners.
+        let edge = length(in.uv - 0.5) * 1.41421356;
+        color *= 1.0 - post.vignette_strength * edge * edge;
+    }
+
+    if has_flag(FLAG_ENCODE_SRGB) {
+        color = encode_srgb(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
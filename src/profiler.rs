@@ -0,0 +1,267 @@
+//! A lightweight frame profiler. Parts of the frame are timed with
+//! `begin` and `end` around them, which may nest; each part is charged
+//! only for the time not spent in parts inside it. Where the adapter can
+//! write timestamps, each render pass is timed on the GPU as well. The
+//! overlay shows the averages as bars and the last few seconds of frame
+//! times as a graph.
+
+mod gpu;
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use winit::event::WindowEvent;
+
+use crate::{
+    input::Binding,
+    text::TextRenderer,
+    ui::{Rect, UiBatch},
+};
+
+pub use self::gpu::GpuTimer;
+
+// Averages are taken over this long, so the numbers hold still long
+// enough to read.
+const SAMPLE_WINDOW: Duration = Duration::from_millis(500);
+// Frames kept for the graph.
+const HISTORY: usize = 180;
+// A frame this long fills a bar, and the graph is twice as tall, with a
+// line where a 60 fps frame ends.
+const FRAME_BUDGET_MS: f32 = 1000.0 / 60.0;
+
+const TEXT_SIZE: f32 = 16.0;
+const PANEL_WIDTH: f32 = 300.0;
+const PANEL_MARGIN: f32 = 8.0;
+const PANEL_PADDING: f32 = 6.0;
+const BAR_WIDTH: f32 = 110.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+const PANEL_COLOR: [f32 ; 4] = [0.0, 0.0, 0.0, 0.5];
+const TEXT_COLOR: [f32 ; 4] = [1.0, 1.0, 1.0, 1.0];
+const BUDGET_COLOR: [f32 ; 4] = [1.0, 1.0, 1.0, 0.4];
+const GPU_COLOR: [f32 ; 4] = [0.3, 0.85, 0.85, 1.0];
+
+/// A part of the frame that's timed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    /// Input, simulation and preparing what's drawn.
+    Update,
+    /// Building chunk meshes.
+    Mesh,
+    /// Copying meshes, uniforms and the UI to the GPU.
+    Upload,
+    /// Recording the frame's render passes.
+    Render,
+    /// Getting a frame to draw into and handing it back, which is where
+    /// waiting on the GPU and vsync shows up.
+    Present,
+}
+
+impl Section {
+    pub const ALL: [Section ; 5] = [Section::Update, Section::Mesh, Section::Upload, Section::Render, Section::Present];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Section::Update => "Update",
+            Section::Mesh => "Mesh",
+            Section::Upload => "Upload",
+            Section::Render => "Render",
+            Section::Present => "Present",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn color(self) -> [f32 ; 4] {
+        match self {
+            Section::Update => [0.3, 0.6, 1.0, 1.0],
+            Section::Mesh => [0.4, 0.85, 0.3, 1.0],
+            Section::Upload => [0.95, 0.75, 0.2, 1.0],
+            Section::Render => [0.9, 0.35, 0.3, 1.0],
+            Section::Present => [0.7, 0.45, 0.9, 1.0],
+        }
+    }
+}
+
+// A timed part still running, and how much of it went to parts inside it.
+struct Open {
+    section: Section,
+    start: Instant,
+    nested: Duration,
+}
+
+pub struct Profiler {
+    pub visible: bool,
+    pub toggle: Binding,
+    /// Whether render passes are timed on the GPU.
+    pub gpu_timing: bool,
+
+    open: Vec<Open>,
+    // Time charged to each section so far this frame.
+    frame: [Duration ; Section::ALL.len()],
+    // Totals over the current sample window.
+    totals: [Duration ; Section::ALL.len()],
+    frames: u32,
+    elapsed: Duration,
+    // Milliseconds a frame each section took, averaged over the last
+    // window.
+    averages: [f32 ; Section::ALL.len()],
+    // Milliseconds each of the last frames took, oldest first.
+    history: VecDeque<f32>,
+    // The same for render passes on the GPU, by pass, in the order they
+    // were drawn; their timings arrive a few frames late, so they're
+    // counted separately.
+    gpu_totals: Vec<(&'static str, f32)>,
+    gpu_frames: u32,
+    gpu_averages: Vec<(&'static str, f32)>,
+}
+
+impl Profiler {
+    pub fn new(toggle: Binding) -> Self {
+        Self {
+            visible: false,
+            toggle,
+            gpu_timing: false,
+
+            open: Vec::new(),
+            frame: [Duration::ZERO ; Section::ALL.len()],
+            totals: [Duration::ZERO ; Section::ALL.len()],
+            frames: 0,
+            elapsed: Duration::ZERO,
+            averages: [0.0 ; Section::ALL.len()],
+            history: VecDeque::with_capacity(HISTORY),
+            gpu_totals: Vec::new(),
+            gpu_frames: 0,
+            gpu_averages: Vec::new(),
+        }
+    }
+
+    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
+        if !self.toggle.just_pressed(event) {
+            return false;
+        }
+        self.visible = !self.visible;
+        true
+    }
+
+    /// Starts timing `section`, until the matching `end`.
+    pub fn begin(&mut self, section: Section) {
+        self.open.push(Open { section, start: Instant::now(), nested: Duration::ZERO });
+    }
+
+    /// Stops timing the section begun last.
+    pub fn end(&mut self) {
+        let Some(open) = self.open.pop() else {
+            return;
+        };
+        let total = open.start.elapsed();
+        self.frame[open.section.index()] += total.saturating_sub(open.nested);
+        if let Some(parent) = self.open.last_mut() {
+            parent.nested += total;
+        }
+    }
+
+    /// Closes the frame that took `frame_time` in all, adding what was
+    /// timed in it to the averages.
+    pub fn end_frame(&mut self, frame_time: Duration) {
+        for (total, time) in self.totals.iter_mut().zip(&mut self.frame) {
+            *total += std::mem::take(time);
+        }
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame_time.as_secs_f32() * 1000.0);
+
+        self.frames += 1;
+        self.elapsed += frame_time;
+        if self.elapsed >= SAMPLE_WINDOW {
+            for (average, total) in self.averages.iter_mut().zip(&mut self.totals) {
+                *average = std::mem::take(total).as_secs_f32() * 1000.0 / self.frames as f32;
+            }
+            self.frames = 0;
+            self.elapsed = Duration::ZERO;
+
+            if self.gpu_frames > 0 {
+                let frames = std::mem::take(&mut self.gpu_frames) as f32;
+                self.gpu_averages = self.gpu_totals.drain(..).map(|(pass, total)| (pass, total / frames)).collect();
+            }
+        }
+    }
+
+    /// Adds how many milliseconds each render pass of a frame took on the
+    /// GPU.
+    pub fn record_gpu(&mut self, passes: &[(&'static str, f32)]) {
+        for &(pass, ms) in passes {
+            match self.gpu_totals.iter_mut().find(|(name, _)| *name == pass) {
+                Some((_, total)) => *total += ms,
+                None => self.gpu_totals.push((pass, ms)),
+            }
+        }
+        self.gpu_frames += 1;
+    }
+
+    /// Draws the overlay in the top right corner.
+    pub fn draw(&self, batch: &mut UiBatch, text: &mut TextRenderer, screen_size: (u32, u32)) {
+        if !self.visible {
+            return;
+        }
+
+        let line_height = text.line_height(TEXT_SIZE);
+        let gpu_lines = if self.gpu_averages.is_empty() { 1 } else { self.gpu_averages.len() + 1 };
+        let lines = Section::ALL.len() + 1 + gpu_lines;
+        let height = PANEL_PADDING * 4.0 + line_height * lines as f32 + GRAPH_HEIGHT;
+        let panel = Rect::new(screen_size.0 as f32 - PANEL_WIDTH - PANEL_MARGIN, PANEL_MARGIN, PANEL_WIDTH, height);
+        batch.quad(panel, PANEL_COLOR);
+
+        let left = panel.x + PANEL_PADDING;
+        let bar_left = panel.x + panel.w - PANEL_PADDING - BAR_WIDTH;
+        let mut y = panel.y + PANEL_PADDING;
+        let mut timing = |name: &str, ms: f32, color: [f32 ; 4], y: f32| {
+            text.queue(name, [left, y], TEXT_SIZE, TEXT_COLOR);
+            let time = format!("{ms:.2} ms");
+            let (time_width, _) = text.measure(&time, TEXT_SIZE);
+            text.queue(time, [bar_left - PANEL_PADDING - time_width, y], TEXT_SIZE, TEXT_COLOR);
+            let width = (ms / FRAME_BUDGET_MS).min(1.0) * BAR_WIDTH;
+            batch.quad(Rect::new(bar_left, y + line_height * 0.2, width.max(1.0), line_height * 0.6), color);
+        };
+        for section in Section::ALL {
+            timing(section.name(), self.averages[section.index()], section.color(), y);
+            y += line_height;
+        }
+        y += PANEL_PADDING;
+        if self.gpu_averages.is_empty() {
+            let note = if self.gpu_timing { "GPU: waiting for timings" } else { "GPU: timestamps unsupported" };
+            text.queue(note, [left, y], TEXT_SIZE, TEXT_COLOR);
+            y += line_height;
+        } else {
+            let total = self.gpu_averages.iter().map(|(_, ms)| ms).sum();
+            timing("GPU", total, GPU_COLOR, y);
+            y += line_height;
+            for &(pass, ms) in &self.gpu_averages {
+                timing(&format!("  {}", pass.trim_end_matches(" Pass")), ms, GPU_COLOR, y);
+                y += line_height;
+            }
+        }
+        let average = self.history.iter().sum::<f32>() / self.history.len().max(1) as f32;
+        let slowest = self.history.iter().copied().fold(0.0, f32::max);
+        text.queue(format!("Frame {average:.2} ms, slowest {slowest:.2} ms"), [left, y], TEXT_SIZE, TEXT_COLOR);
+        y += line_height + PANEL_PADDING;
+
+        // One column per frame, newest on the right, going green to red
+        // as frames run over budget.
+        let graph = Rect::new(left, y, panel.w - PANEL_PADDING * 2.0, GRAPH_HEIGHT);
+        let column = graph.w / HISTORY as f32;
+        let scale = GRAPH_HEIGHT / (FRAME_BUDGET_MS * 2.0);
+        let skipped = HISTORY - self.history.len();
+        for (i, &ms) in self.history.iter().enumerate() {
+            let h = (ms * scale).min(GRAPH_HEIGHT);
+            let over = (ms / FRAME_BUDGET_MS - 1.0).clamp(0.0, 1.0);
+            let x = graph.x + (skipped + i) as f32 * column;
+            batch.quad(Rect::new(x, graph.y + GRAPH_HEIGHT - h, column.max(1.0), h), [0.3 + 0.7 * over, 0.9 - 0.6 * over, 0.3, 0.9]);
+        }
+        batch.quad(Rect::new(graph.x, graph.y + GRAPH_HEIGHT - FRAME_BUDGET_MS * scale, graph.w, 1.0), BUDGET_COLOR);
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::mpsc;
+
+// Passes a frame can time; any past this go untimed.
+const MAX_PASSES: usize = 16;
+// Frames whose timings can be waiting to be read back at once. A frame
+// that finds none free goes untimed rather than waiting on the GPU.
+const READBACKS: usize = 3;
+
+const TIMESTAMP_SIZE: wgpu::BufferAddress = std::mem::size_of::<u64>() as wgpu::BufferAddress;
+
+// The passes a frame timed, waiting for their timestamps to be mapped.
+struct Pending {
+    passes: Vec<&'static str>,
+    mapped: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+// A buffer the timestamps of one frame are copied into.
+struct Readback {
+    buffer: wgpu::Buffer,
+    pending: Option<Pending>,
+}
+
+/// Times each render pass on the GPU with timestamp queries, written at
+/// the start and end of the pass and read back a few frames later.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    // Nanoseconds a timestamp tick lasts.
+    period: f32,
+    // The readback this frame's timings go to, if one was free, and the
+    // passes timed so far.
+    current: Option<usize>,
+    passes: Vec<&'static str>,
+}
+
+impl GpuTimer {
+    /// A timer, if the device can write timestamps.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let count = MAX_PASSES as u32 * 2;
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pass Timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count,
+        });
+        let size = count as wgpu::BufferAddress * TIMESTAMP_SIZE;
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readbacks = (0..READBACKS)
+            .map(|_| Readback {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp Readback Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                pending: None,
+            })
+            .collect();
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readbacks,
+            period: queue.get_timestamp_period(),
+            current: None,
+            passes: Vec::new(),
+        })
+    }
+
+    /// Gets ready to time a new frame's passes.
+    pub fn start_frame(&mut self) {
+        self.current = self.readbacks.iter().position(|readback| readback.pending.is_none());
+        self.passes.clear();
+    }
+
+    /// Where the pass called `name` writes its timestamps, if there's room
+    /// to time it.
+    pub fn pass_writes(&mut self, name: &'static str) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        if self.current.is_none() || self.passes.len() == MAX_PASSES {
+            return None;
+        }
+        let first = self.passes.len() as u32 * 2;
+        self.passes.push(name);
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(first),
+            end_of_pass_write_index: Some(first + 1),
+        })
+    }
+
+    /// Records copying the frame's timestamps out into `encoder`, to be
+    /// read back once the GPU is done with them.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(index) = self.current.take() else {
+            return;
+        };
+        if self.passes.is_empty() {
+            return;
+        }
+
+        let count = self.passes.len() as u32 * 2;
+        let size = count as wgpu::BufferAddress * TIMESTAMP_SIZE;
+        let readback = &mut self.readbacks[index];
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &readback.buffer, 0, size);
+
+        // Mapping only starts once the copy is submitted, which happens
+        // when the encoder is.
+        let (sender, mapped) = mpsc::channel();
+        readback.buffer.slice(..size).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        readback.pending = Some(Pending { passes: std::mem::take(&mut self.passes), mapped });
+    }
+
+    /// The frames read back since the last call, each as how many
+    /// milliseconds its passes took on the GPU.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Vec<Vec<(&'static str, f32)>> {
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut frames = Vec::new();
+        for readback in &mut self.readbacks {
+            let Some(pending) = &readback.pending else {
+                continue;
+            };
+            let result = match pending.mapped.try_recv() {
+                Ok(result) => result,
+                Err(mpsc::TryRecvError::Empty) => continue,
+                Err(mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+            };
+            let passes = readback.pending.take().unwrap().passes;
+            if result.is_err() {
+                continue;
+            }
+
+            let size = passes.len() as wgpu::BufferAddress * 2 * TIMESTAMP_SIZE;
+            let timings = {
+                let data = readback.buffer.slice(..size).get_mapped_range();
+                let timestamps: &[u64] = bytemuck::cast_slice(&data);
+                passes.into_iter()
+                    .zip(timestamps.chunks_exact(2))
+                    .map(|(name, pair)| (name, pair[1].saturating_sub(pair[0]) as f32 * self.period / 1_000_000.0))
+                    .collect()
+            };
+            readback.buffer.unmap();
+            frames.push(timings);
+        }
+        frames
+    }
+}
@@ -0,0 +1,28 @@
+//! A small xorshift generator for gameplay choices that don't need to be
+//! good random numbers, only cheap ones that can start from a seed.
+
+#[derive(Clone, Copy, Debug)]
+pub struct Random(u32);
+
+impl Random {
+    pub fn new(seed: u32) -> Self {
+        // Spreads out nearby seeds; xorshift can't start from zero.
+        Self(seed.wrapping_mul(0x9e37_79b9) | 1)
+    }
+
+    pub fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    /// Uniform in [0, 1).
+    pub fn float(&mut self) -> f32 {
+        (self.next() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    pub fn range(&mut self, (min, max): (f32, f32)) -> f32 {
+        min + self.float() * (max - min)
+    }
+}
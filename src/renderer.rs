@@ -0,0 +1,264 @@
+use crate::{
+    border::BorderRenderer,
+    crack::CrackRenderer,
+    debug::RenderStats,
+    entity_renderer::EntityRenderer,
+    chunk_debug::ChunkBorderRenderer,
+    highlight::HighlightRenderer,
+    indirect::{ChunkDraws, DrawList},
+    map::MapRenderer,
+    mesher::MeshPool,
+    particles::ParticleRenderer,
+    post::PostProcessor,
+    profiler::GpuTimer,
+    shadow::{self, ShadowRenderer},
+    sky::SkyRenderer,
+    text::TextRenderer,
+    ui::UiRenderer,
+};
+
+/// The textures a pass can draw into. Passes name targets instead of
+/// holding views so the graph can hand out whatever backs them this frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Surface,
+    // The offscreen color target the world is drawn into before
+    // post-processing.
+    Scene,
+    Depth,
+    ShadowCascade(usize),
+}
+
+pub struct FrameTargets<'a> {
+    pub surface: &'a wgpu::TextureView,
+    pub scene: &'a wgpu::TextureView,
+    // Drawn into instead of `scene` when MSAA is on.
+    pub scene_msaa: Option<&'a wgpu::TextureView>,
+    pub depth: &'a wgpu::TextureView,
+    pub shadows: &'a ShadowRenderer,
+}
+
+impl FrameTargets<'_> {
+    fn view(&self, target: Target) -> &wgpu::TextureView {
+        match target {
+            Target::Surface => self.surface,
+            Target::Scene => self.scene,
+            Target::Depth => self.depth,
+            Target::ShadowCascade(cascade) => self.shadows.cascade_view(cascade),
+        }
+    }
+}
+
+/// A pass's name and the attachments it draws into. Every attachment is
+/// stored; `load` decides whether the pass starts from a clear value or
+/// from what earlier passes left behind.
+pub struct PassDesc {
+    pub name: &'static str,
+    pub color: Option<(Target, wgpu::LoadOp<wgpu::Color>)>,
+    pub depth: Option<(Target, wgpu::LoadOp<f32>)>,
+}
+
+impl PassDesc {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            color: None,
+            depth: None,
+        }
+    }
+
+    pub fn color(mut self, target: Target, load: wgpu::LoadOp<wgpu::Color>) -> Self {
+        self.color = Some((target, load));
+        self
+    }
+
+    pub fn depth(mut self, target: Target, load: wgpu::LoadOp<f32>) -> Self {
+        self.depth = Some((target, load));
+        self
+    }
+}
+
+type RecordPass<'a> = Box<dyn FnOnce(&mut wgpu::RenderPass, &mut RenderStats) + 'a>;
+
+/// An ordered list of passes making up a frame. Each pass records its draw
+/// calls into a render pass the graph begins from its `PassDesc`.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    passes: Vec<(PassDesc, RecordPass<'a>)>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn add_pass(&mut self, desc: PassDesc, record: impl FnOnce(&mut wgpu::RenderPass, &mut RenderStats) + 'a) {
+        self.passes.push((desc, Box::new(record)));
+    }
+
+    /// Records every pass into `encoder` and returns the combined draw
+    /// statistics. With a `timer`, each pass is timed on the GPU too.
+    pub fn execute(self, encoder: &mut wgpu::CommandEncoder, targets: &FrameTargets, mut timer: Option<&mut GpuTimer>) -> RenderStats {
+        let mut stats = RenderStats::default();
+
+        // With MSAA the scene is drawn multisampled and only the last pass
+        // drawing into it resolves the samples.
+        let last_scene_pass = self.passes.iter()
+            .rposition(|(desc, _)| matches!(desc.color, Some((Target::Scene, _))));
+
+        for (index, (desc, record)) in self.passes.into_iter().enumerate() {
+            let color_attachment = desc.color.map(|(target, load)| {
+                let (view, resolve_target) = match (target, targets.scene_msaa) {
+                    (Target::Scene, Some(msaa)) => (msaa, (Some(index) == last_scene_pass).then_some(targets.scene)),
+                    _ => (targets.view(target), None),
+                };
+                wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                }
+            });
+            let depth_attachment = desc.depth.map(|(target, load)| wgpu::RenderPassDepthStencilAttachment {
+                view: targets.view(target),
+                depth_ops: Some(wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(desc.name),
+                color_attachments: &[color_attachment],
+                depth_stencil_attachment: depth_attachment,
+                occlusion_query_set: None,
+                timestamp_writes: timer.as_mut().and_then(|timer| timer.pass_writes(desc.name)),
+            });
+            record(&mut render_pass, &mut stats);
+        }
+
+        stats
+    }
+}
+
+pub struct TerrainPipelines<'a> {
+    pub opaque: &'a wgpu::RenderPipeline,
+    pub cutout: &'a wgpu::RenderPipeline,
+    pub translucent: &'a wgpu::RenderPipeline,
+}
+
+/// Everything the frame's passes read from, borrowed from the app for the
+/// duration of one frame.
+pub struct Scene<'a> {
+    pub terrain: TerrainPipelines<'a>,
+    pub block_bind_group: &'a wgpu::BindGroup,
+    pub camera_bind_group: &'a wgpu::BindGroup,
+    pub frame_bind_group: &'a wgpu::BindGroup,
+    pub chunk_draws: &'a ChunkDraws,
+    pub mesh_pool: &'a MeshPool,
+    pub shadows: &'a ShadowRenderer,
+    pub post: &'a PostProcessor,
+    pub sky: &'a SkyRenderer,
+    pub entities: &'a EntityRenderer,
+    pub particles: &'a ParticleRenderer,
+    pub cracks: &'a CrackRenderer,
+    pub highlight: &'a HighlightRenderer,
+    pub selection: &'a HighlightRenderer,
+    pub chunk_borders: &'a ChunkBorderRenderer,
+    pub border: &'a BorderRenderer,
+    pub map: &'a MapRenderer,
+    pub ui: &'a UiRenderer,
+    pub icon_bind_group: &'a wgpu::BindGroup,
+    pub text: &'a TextRenderer,
+}
+
+impl<'a> Scene<'a> {
+    /// The passes drawn every frame: shadow cascades while the sun is up,
+    /// sky, opaque and transparent world geometry into the scene target,
+    /// post-processing onto the surface, then the UI on top.
+    pub fn frame_graph(&'a self) -> RenderGraph<'a> {
+        use wgpu::LoadOp::{Clear, Load};
+
+        let mut graph = RenderGraph::default();
+        if self.shadows.active() {
+            for cascade in 0..shadow::CASCADE_COUNT {
+                graph.add_pass(
+                    PassDesc::new(shadow::CASCADE_PASS_NAMES[cascade])
+                        .depth(Target::ShadowCascade(cascade), Clear(1.0)),
+                    move |pass, stats| self.draw_shadow_casters(pass, cascade, stats),
+                );
+            }
+        }
+        graph.add_pass(
+            PassDesc::new("Sky Pass").color(Target::Scene, Clear(wgpu::Color::BLACK)),
+            |pass, _| self.sky.render(pass),
+        );
+        graph.add_pass(
+            PassDesc::new("Opaque Pass")
+                .color(Target::Scene, Load)
+                .depth(Target::Depth, Clear(1.0)),
+            |pass, stats| self.draw_opaque(pass, stats),
+        );
+        graph.add_pass(
+            PassDesc::new("Transparent Pass")
+                .color(Target::Scene, Load)
+                .depth(Target::Depth, Load),
+            |pass, stats| self.draw_transparent(pass, stats),
+        );
+        graph.add_pass(
+            PassDesc::new("Post Process Pass").color(Target::Surface, Clear(wgpu::Color::BLACK)),
+            |pass, _| self.post.render(pass),
+        );
+        graph.add_pass(
+            PassDesc::new("UI Pass").color(Target::Surface, Load),
+            |pass, _| {
+                self.map.render(pass);
+                self.ui.render(pass, self.icon_bind_group);
+                self.text.render(pass);
+            },
+        );
+        graph
+    }
+
+    fn bind_terrain(&self, pass: &mut wgpu::RenderPass) {
+        pass.set_bind_group(0, self.block_bind_group, &[]);
+        pass.set_bind_group(1, self.camera_bind_group, &[]);
+        pass.set_bind_group(2, self.frame_bind_group, &[]);
+        pass.set_bind_group(3, &self.shadows.bind_group, &[]);
+    }
+
+    fn draw_shadow_casters(&self, pass: &mut wgpu::RenderPass, cascade: usize, stats: &mut RenderStats) {
+        self.shadows.bind(pass, cascade, self.block_bind_group);
+        self.chunk_draws.draw(pass, self.mesh_pool, DrawList::ShadowOpaque, stats);
+        self.entities.render(pass, stats);
+
+        self.shadows.bind_cutout(pass);
+        self.chunk_draws.draw(pass, self.mesh_pool, DrawList::ShadowCutout, stats);
+    }
+
+    fn draw_opaque(&self, pass: &mut wgpu::RenderPass, stats: &mut RenderStats) {
+        pass.set_pipeline(self.terrain.opaque);
+        self.bind_terrain(pass);
+        self.chunk_draws.draw(pass, self.mesh_pool, DrawList::Opaque, stats);
+        self.entities.render(pass, stats);
+
+        // Cutout geometry goes after the opaque pass so early depth
+        // testing can reject the foliage hidden behind terrain.
+        pass.set_pipeline(self.terrain.cutout);
+        self.chunk_draws.draw(pass, self.mesh_pool, DrawList::Cutout, stats);
+    }
+
+    fn draw_transparent(&self, pass: &mut wgpu::RenderPass, stats: &mut RenderStats) {
+        pass.set_pipeline(self.terrain.translucent);
+        self.bind_terrain(pass);
+        self.chunk_draws.draw(pass, self.mesh_pool, DrawList::Translucent, stats);
+        // Before the cracks and highlight, which rebind group 0 to the
+        // camera.
+        self.particles.render(pass, stats);
+
+        self.border.render(pass, self.camera_bind_group);
+        self.cracks.render(pass, self.camera_bind_group);
+        self.highlight.render(pass, self.camera_bind_group);
+        self.selection.render(pass, self.camera_bind_group);
+        self.chunk_borders.render(pass, self.camera_bind_group);
+    }
+}
@@ -0,0 +1,566 @@
+mod backup;
+mod chunk_io;
+mod migrate;
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    block::{BlockId, BlockState, Properties},
+    block_entity::BlockEntity,
+    border::WorldBorder,
+    chunk::{Chunk, ChunkPos, CHUNK_SIZE},
+    codec::{self, Reader, Writer},
+    dimension::Dimension,
+    error::{EngineError, Result},
+    game_mode::GameMode,
+    health::MAX_HEALTH,
+    hunger::MAX_FOOD,
+    inventory::Inventory,
+    map::MapCache,
+    weather::Weather,
+    worldgen::WorldType,
+};
+
+pub use self::{backup::Backup, chunk_io::ChunkIo};
+
+pub const SAVES_DIR: &str = "saves";
+/// The version of the game saving, recorded so a world can be backed up
+/// before another version first saves over it.
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The version of the level and player files' layout. Changing it needs a
+/// migration from the one before in `migrate.rs`.
+pub const FORMAT_VERSION: u32 = 2;
+/// How often a world being played is saved, in singleplayer and on a
+/// server, so a crash loses at most this much.
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+const LEVEL_FILE: &str = "level.toml";
+const PLAYER_FILE: &str = "player.toml";
+const MAP_FILE: &str = "map.bin";
+const CHUNKS_DIR: &str = "chunks";
+
+const CHUNK_MAGIC: &[u8 ; 4] = b"VXCK";
+const CHUNK_VERSION: u8 = 5;
+// Files before this version hold a whole column of chunks from y 0 up,
+// which upgrading the world splits into a file for each.
+const FIRST_CUBE_VERSION: u8 = 5;
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// World-wide state that can't be regenerated from the seed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LevelData {
+    pub seed: u32,
+    pub time: u64,
+    /// How many times faster than normal the day goes by.
+    #[serde(default = "normal_time_speed")]
+    pub time_speed: f32,
+    #[serde(default = "daylight_cycle_on")]
+    pub daylight_cycle: bool,
+    #[serde(default)]
+    pub world_type: WorldType,
+    #[serde(default)]
+    pub game_mode: GameMode,
+    /// Left out until it's been set, in which case it's worked out from
+    /// the terrain.
+    #[serde(default)]
+    pub spawn_point: Option<[f32 ; 3]>,
+    /// Seconds since the Unix epoch when the world was last saved; 0 if
+    /// it never has been since this was recorded.
+    #[serde(default)]
+    pub last_played: u64,
+    #[serde(default)]
+    pub weather: Weather,
+    /// Seconds until the weather changes; left out, it lasts a random
+    /// while.
+    #[serde(default)]
+    pub weather_remaining: Option<f32>,
+    /// Left out by saves from before there was a border, which get the
+    /// default one.
+    #[serde(default)]
+    pub border: Option<WorldBorder>,
+    /// The version of the game that last saved the world; empty for saves
+    /// from before this was recorded.
+    #[serde(default)]
+    pub game_version: String,
+    /// `FORMAT_VERSION` when the world was last saved; 0 for saves from
+    /// before there was one.
+    #[serde(default)]
+    pub format_version: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerData {
+    pub position: [f32 ; 3],
+    #[serde(default)]
+    pub flying: bool,
+    #[serde(default = "full_health")]
+    pub health: u32,
+    #[serde(default = "full_food")]
+    pub food: u32,
+    #[serde(default)]
+    pub inventory: Inventory,
+    #[serde(default)]
+    pub bed: Option<[i32 ; 3]>,
+    /// Which dimension the player is in; left out by saves from before
+    /// there were any but the overworld.
+    #[serde(default)]
+    pub dimension: Dimension,
+}
+
+fn full_health() -> u32 {
+    MAX_HEALTH
+}
+
+fn full_food() -> u32 {
+    MAX_FOOD
+}
+
+fn normal_time_speed() -> f32 {
+    1.0
+}
+
+fn daylight_cycle_on() -> bool {
+    true
+}
+
+/// A saved world as the world list shows it.
+#[derive(Clone, Debug)]
+pub struct WorldSummary {
+    pub name: String,
+    pub level: LevelData,
+}
+
+/// The saved worlds, most recently played first. Worlds whose level file
+/// can't be read are left out.
+pub fn list_worlds() -> Vec<WorldSummary> {
+    let Ok(entries) = fs::read_dir(SAVES_DIR) else {
+        return Vec::new();
+    };
+    let mut worlds = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|name| match WorldSave::new(&name).load_level() {
+            Ok(level) => Some(WorldSummary { level: level?, name }),
+            Err(e) => {
+                warn!("skipping world `{name}`: {e}");
+                None
+            },
+        })
+        .collect::<Vec<_>>();
+    worlds.sort_by(|a, b| b.level.last_played.cmp(&a.level.last_played).then_with(|| a.name.cmp(&b.name)));
+    worlds
+}
+
+/// The current time in seconds since the Unix epoch, for `last_played`.
+pub fn timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Replaces `path` with `contents` all at once: they're written to a file
+/// beside it, flushed to disk, and renamed over it, so a crash or power
+/// loss partway through leaves the old file rather than half of the new.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let io_error = |source| EngineError::Io { path: path.to_path_buf(), source };
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+
+    let mut file = File::create(&temp).map_err(io_error)?;
+    file.write_all(contents).and_then(|()| file.sync_all()).map_err(io_error)?;
+    drop(file);
+    fs::rename(&temp, path).map_err(io_error)?;
+    // The rename itself only lasts once the directory is flushed too.
+    // Not every platform can open a directory to do that, so it's left to
+    // the OS where it fails.
+    if let Some(dir) = path.parent().and_then(|dir| File::open(dir).ok()) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// A world's directory under `saves/`. Only chunks that were changed after
+/// generation are stored; the rest are regenerated from the seed.
+#[derive(Clone, Debug)]
+pub struct WorldSave {
+    dir: PathBuf,
+    // Where the chunks of the dimension this is for go.
+    chunks_dir: PathBuf,
+}
+
+impl WorldSave {
+    pub fn new(name: &str) -> Self {
+        let dir = Path::new(SAVES_DIR).join(name);
+        Self {
+            chunks_dir: dir.join(CHUNKS_DIR),
+            dir,
+        }
+    }
+
+    /// The same world, saving and loading `dimension`'s chunks.
+    pub fn dimension(&self, dimension: Dimension) -> Self {
+        let base = match dimension.save_dir() {
+            Some(sub) => self.dir.join(sub),
+            None => self.dir.clone(),
+        };
+        Self {
+            dir: self.dir.clone(),
+            chunks_dir: base.join(CHUNKS_DIR),
+        }
+    }
+
+    /// The world's name, which is its directory's.
+    pub fn name(&self) -> String {
+        self.dir.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+    }
+
+    /// Whether a world has been saved here.
+    pub fn exists(&self) -> bool {
+        self.dir.join(LEVEL_FILE).exists()
+    }
+
+    /// Removes the world's directory and everything in it.
+    pub fn delete(&self) -> Result<()> {
+        fs::remove_dir_all(&self.dir)
+            .map_err(|source| EngineError::Io { path: self.dir.clone(), source })
+    }
+
+    // A missing file means the world (or player) is new, which isn't an
+    // error.
+    fn read<T: DeserializeOwned>(&self, file: &str) -> Result<Option<T>> {
+        let path = self.dir.join(file);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|source| EngineError::Io { path: path.clone(), source })?;
+        toml::from_str(&contents)
+            .map(Some)
+            .map_err(|source| EngineError::SaveParse { path, source })
+    }
+
+    fn write<T: Serialize>(&self, file: &str, data: &T) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|source| EngineError::Io { path: self.dir.clone(), source })?;
+
+        let path = self.dir.join(file);
+        let contents = toml::to_string_pretty(data)
+            .map_err(|source| EngineError::SaveSerialize { path: path.clone(), source })?;
+        write_atomic(&path, contents.as_bytes())
+    }
+
+    pub fn load_level(&self) -> Result<Option<LevelData>> {
+        self.read(LEVEL_FILE)
+    }
+
+    pub fn save_level(&self, level: &LevelData) -> Result<()> {
+        self.write(LEVEL_FILE, level)
+    }
+
+    pub fn load_player(&self) -> Result<Option<PlayerData>> {
+        self.read(PLAYER_FILE)
+    }
+
+    pub fn save_player(&self, player: &PlayerData) -> Result<()> {
+        self.write(PLAYER_FILE, player)
+    }
+
+    /// Adds what was explored of the world, and its waypoints, to `map`.
+    /// A world that's never been mapped leaves it alone.
+    pub fn load_map(&self, map: &mut MapCache) -> Result<()> {
+        let path = self.dir.join(MAP_FILE);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(source) => return Err(EngineError::Io { path, source }),
+        };
+        map.decode(&bytes).map_err(|reason| EngineError::CorruptMap { path, reason })
+    }
+
+    pub fn save_map(&self, map: &MapCache) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|source| EngineError::Io { path: self.dir.clone(), source })?;
+        let path = self.dir.join(MAP_FILE);
+        write_atomic(&path, &map.encode())
+    }
+
+    fn chunk_path(&self, pos: ChunkPos) -> PathBuf {
+        self.chunks_dir.join(format!("{}_{}_{}.bin", pos.x, pos.y, pos.z))
+    }
+
+    /// Reads a saved chunk, or `None` if it was never saved. This blocks on
+    /// disk IO; during play, go through `ChunkIo` instead.
+    pub fn load_chunk(&self, pos: ChunkPos) -> Result<Option<Chunk>> {
+        let path = self.chunk_path(pos);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(EngineError::Io { path, source }),
+        };
+        decode_chunk(pos, &bytes)
+            .map(Some)
+            .map_err(|reason| EngineError::CorruptChunk { path, reason })
+    }
+
+    pub fn save_chunk(&self, chunk: &Chunk) -> Result<()> {
+        let path = self.chunk_path(chunk.pos);
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir)
+            .map_err(|source| EngineError::Io { path: dir.to_path_buf(), source })?;
+        write_atomic(&path, &encode_chunk(chunk))
+    }
+}
+
+// Blocks are stored by name so the file survives blocks being added or
+// reordered:
+//
+//   magic, version: u8, palette length: u16,
+//   palette entries: name length: u8, name, fluid level: u8, properties: u8,
+//   run count: u32, runs: length: u16, palette index: u16,
+//   block entity count: u16, block entities: voxel index: u32, entity
+//
+// The runs cover every voxel in the chunk's y, z, x order, which voxel
+// indices count in too. Numbers are little-endian. Files before version 5
+// hold a column of chunks from y 0 up instead, with its height after the
+// version from version 4 on and 128 blocks before; version 1 files have no
+// properties byte, and versions before 3 no block entities.
+pub fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+    let mut palette = Vec::<(BlockState, u8)>::new();
+    let mut lookup = HashMap::new();
+    let mut runs = Vec::<(u16, u16)>::new();
+
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let voxel = (chunk.get_state(x, y, z), chunk.fluid_level(x, y, z));
+                let index = *lookup.entry(voxel).or_insert_with(|| {
+                    palette.push(voxel);
+                    palette.len() as u16 - 1
+                });
+                match runs.last_mut() {
+                    Some((length, last)) if *last == index && *length < u16::MAX => *length += 1,
+                    _ => runs.push((1, index)),
+                }
+            }
+        }
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(CHUNK_MAGIC);
+    bytes.put_u8(CHUNK_VERSION);
+    bytes.put_u16(palette.len() as u16);
+    for (state, fluid_level) in palette {
+        let name = state.block.info().name.as_bytes();
+        bytes.put_u8(name.len() as u8);
+        bytes.extend_from_slice(name);
+        bytes.put_u8(fluid_level);
+        bytes.put_u8(state.properties.bits());
+    }
+    bytes.put_u32(runs.len() as u32);
+    for (length, index) in runs {
+        bytes.put_u16(length);
+        bytes.put_u16(index);
+    }
+
+    let entities = chunk.block_entities().collect::<Vec<_>>();
+    bytes.put_u16(entities.len() as u16);
+    for ([x, y, z], entity) in entities {
+        bytes.put_u32(((y * CHUNK_SIZE + z) * CHUNK_SIZE + x) as u32);
+        entity.encode(&mut bytes);
+    }
+    bytes
+}
+
+pub fn decode_chunk(pos: ChunkPos, bytes: &[u8]) -> codec::Result<Chunk> {
+    let mut reader = Reader::new(bytes);
+    let version = read_chunk_version(&mut reader)?;
+    if version < FIRST_CUBE_VERSION {
+        return Err(format!("chunk version {version} holds a whole column, which upgrading the world splits up"));
+    }
+
+    let mut chunk = Chunk::new(pos);
+    decode_voxels(&mut reader, version, CHUNK_VOLUME, |i, state, fluid_level| {
+        let [x, y, z] = voxel_position(i);
+        chunk.set_voxel(x, y, z, state, fluid_level);
+    })?;
+    decode_block_entities(&mut reader, version, CHUNK_VOLUME, |i, entity| {
+        let [x, y, z] = voxel_position(i);
+        if chunk.set_block_entity(x, y, z, entity) {
+            Ok(())
+        } else {
+            Err(format!("block entity on `{}`, which can't have it", chunk.get(x, y, z).info().name))
+        }
+    })?;
+
+    chunk.compact();
+    Ok(chunk)
+}
+
+// Checks a chunk file is one and returns its version.
+fn read_chunk_version(reader: &mut Reader) -> codec::Result<u8> {
+    if reader.take(4)? != CHUNK_MAGIC {
+        return Err("not a chunk file".to_string());
+    }
+    let version = reader.u8()?;
+    if version > CHUNK_VERSION {
+        return Err(format!("chunk version {version} is from a newer version of the game, which reads up to {CHUNK_VERSION}"));
+    }
+    if version == 0 {
+        return Err("unsupported chunk version 0".to_string());
+    }
+    Ok(version)
+}
+
+// Where voxel index `i` is in a chunk, or for a column file, in the whole
+// column with y counting up from its bottom.
+fn voxel_position(i: usize) -> [usize ; 3] {
+    [i % CHUNK_SIZE, i / (CHUNK_SIZE * CHUNK_SIZE), i / CHUNK_SIZE % CHUNK_SIZE]
+}
+
+// Reads the palette and runs of a chunk file's `volume` voxels, handing
+// each one that isn't air to `set` by its index; air is what a new chunk
+// holds already.
+fn decode_voxels(
+    reader: &mut Reader,
+    version: u8,
+    volume: usize,
+    mut set: impl FnMut(usize, BlockState, u8),
+) -> codec::Result<()> {
+    let palette_len = reader.u16()?;
+    let mut palette = Vec::with_capacity(palette_len as usize);
+    for _ in 0..palette_len {
+        let name_len = reader.u8()?;
+        let name = std::str::from_utf8(reader.take(name_len as usize)?)
+            .map_err(|_| "block name is not UTF-8".to_string())?;
+        let block = BlockId::from_name(name).ok_or_else(|| format!("unknown block `{name}`"))?;
+        let fluid_level = reader.u8()?;
+        let properties = if version >= 2 { Properties::from_bits(reader.u8()?) } else { Properties::default() };
+        palette.push((BlockState::new(block, properties), fluid_level));
+    }
+
+    let mut index = 0;
+    for _ in 0..reader.u32()? {
+        let length = reader.u16()? as usize;
+        let entry = reader.u16()? as usize;
+        let &(state, fluid_level) = palette.get(entry).ok_or("palette index out of range")?;
+        if index + length > volume {
+            return Err("more voxels than fit in a chunk".to_string());
+        }
+
+        if !state.block.is_air() {
+            for i in index..index + length {
+                set(i, state, fluid_level);
+            }
+        }
+        index += length;
+    }
+    if index != volume {
+        return Err("chunk data ends early".to_string());
+    }
+    Ok(())
+}
+
+// Reads the block entities at the end of a chunk file, handing each to
+// `set` by its voxel index.
+fn decode_block_entities(
+    reader: &mut Reader,
+    version: u8,
+    volume: usize,
+    mut set: impl FnMut(usize, BlockEntity) -> codec::Result<()>,
+) -> codec::Result<()> {
+    if version < 3 {
+        return Ok(());
+    }
+    for _ in 0..reader.u16()? {
+        let i = reader.u32()? as usize;
+        let entity = BlockEntity::decode(reader)?;
+        if i >= volume {
+            return Err("block entity outside the chunk".to_string());
+        }
+        set(i, entity)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assets::Assets,
+        block::{self, BlockId, BlockState, Facing, Properties},
+        chunk::{Chunk, ChunkPos, CHUNK_SIZE},
+    };
+
+    use super::{decode_chunk, encode_chunk};
+
+    /// The game's own blocks, which chunk files name theirs by.
+    pub(super) fn load_blocks() {
+        block::load_registry(&Assets::new(&[]), Vec::new()).unwrap();
+    }
+
+    pub(super) fn block(name: &str) -> BlockId {
+        BlockId::from_name(name).unwrap()
+    }
+
+    // Every voxel and block entity of two chunks are the same.
+    pub(super) fn assert_same(a: &Chunk, b: &Chunk) {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    assert_eq!(a.get_state(x, y, z), b.get_state(x, y, z), "block at {x} {y} {z}");
+                    assert_eq!(a.fluid_level(x, y, z), b.fluid_level(x, y, z), "fluid at {x} {y} {z}");
+                }
+            }
+        }
+        // Block entities come out in no particular order.
+        let entities = |chunk: &Chunk| {
+            let mut entities = chunk.block_entities().map(|(at, entity)| (at, entity.clone())).collect::<Vec<_>>();
+            entities.sort_by_key(|&(at, _)| at);
+            entities
+        };
+        assert_eq!(entities(a), entities(b));
+    }
+
+    #[test]
+    fn chunk_round_trip() {
+        load_blocks();
+        let pos = ChunkPos::new(3, -2, 7);
+        let mut chunk = Chunk::new(pos);
+        for y in 0..6 {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set(x, y, z, BlockId::STONE);
+                }
+            }
+        }
+        chunk.set_voxel(4, 6, 9, BlockState::new(block("water"), Properties::default()), 3);
+        let facing = Properties::default().with_facing(Facing::East);
+        chunk.set_voxel(15, 15, 15, BlockState::new(block("furnace"), facing), 0);
+        chunk.set_voxel(0, 10, 2, BlockState::new(block("chest"), Properties::default()), 0);
+
+        let decoded = decode_chunk(pos, &encode_chunk(&chunk)).unwrap();
+        assert_eq!(decoded.pos, pos);
+        assert_same(&chunk, &decoded);
+        assert_eq!(decoded.block_entities().count(), 2);
+    }
+
+    #[test]
+    fn rejects_bad_chunk_files() {
+        load_blocks();
+        let pos = ChunkPos::new(0, 0, 0);
+        let bytes = encode_chunk(&Chunk::new(pos));
+        assert!(decode_chunk(pos, &bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_chunk(pos, b"nope").is_err());
+
+        let mut newer = bytes.clone();
+        newer[4] = super::CHUNK_VERSION + 1;
+        assert!(decode_chunk(pos, &newer).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Backups: zipped copies of a world kept in its own `backups/` directory,
+//! taken with `/backup` and before a new version of the game first saves
+//! over the world, and restored from the world list.
+
+use std::{
+    cmp::Reverse,
+    fs::{self, File},
+    io::{self, Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::error::{EngineError, Result};
+
+use super::{timestamp, write_atomic, WorldSave};
+
+// Backups kept of each world; taking another deletes the oldest.
+const MAX_BACKUPS: usize = 5;
+
+const BACKUPS_DIR: &str = "backups";
+// Where a backup is unpacked before it replaces the world, so a broken one
+// leaves the world alone.
+const RESTORE_DIR: &str = "restoring";
+
+/// One of a world's backups, as the backup list shows it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backup {
+    file: String,
+    /// Seconds since the Unix epoch when it was taken.
+    pub created: u64,
+    /// In bytes, zipped.
+    pub size: u64,
+}
+
+impl Backup {
+    /// The zip's name in `backups/`.
+    pub fn file_name(&self) -> &str {
+        &self.file
+    }
+}
+
+impl WorldSave {
+    fn backups_dir(&self) -> PathBuf {
+        self.dir.join(BACKUPS_DIR)
+    }
+
+    /// Zips up everything the world has saved, and deletes the oldest
+    /// backups past `MAX_BACKUPS`. Anything not written out yet isn't in
+    /// it, so save first.
+    pub fn backup(&self) -> Result<Backup> {
+        let dir = self.backups_dir();
+        fs::create_dir_all(&dir).map_err(|source| EngineError::Io { path: dir.clone(), source })?;
+        // Names are the time they were taken, so two in the same second
+        // make the second one a second later.
+        let mut created = timestamp();
+        while dir.join(backup_file(created)).exists() {
+            created += 1;
+        }
+        let path = dir.join(backup_file(created));
+
+        let mut files = Vec::new();
+        self.list_files(&self.dir, &mut files)?;
+        let zip_error = |source| EngineError::Backup { path: path.clone(), source };
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for file in files {
+            let bytes = fs::read(&file).map_err(|source| EngineError::Io { path: file.clone(), source })?;
+            // Zips always separate with '/', whatever the platform does.
+            let name = file.strip_prefix(&self.dir).unwrap_or(&file).iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            zip.start_file(name, options).map_err(zip_error)?;
+            zip.write_all(&bytes).map_err(|source| EngineError::Io { path: path.clone(), source })?;
+        }
+        let bytes = zip.finish().map_err(zip_error)?.into_inner();
+        write_atomic(&path, &bytes)?;
+
+        for old in self.backups().into_iter().skip(MAX_BACKUPS) {
+            let old_path = dir.join(&old.file);
+            if let Err(e) = fs::remove_file(&old_path) {
+                warn!("could not delete old backup {}: {e}", old_path.display());
+            }
+        }
+        Ok(Backup { file: backup_file(created), created, size: bytes.len() as u64 })
+    }
+
+    // Every file under `dir` but the backups themselves, and files left
+    // half-written by a crash.
+    fn list_files(&self, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        let entries = fs::read_dir(dir).map_err(|source| EngineError::Io { path: dir.to_path_buf(), source })?;
+        for entry in entries {
+            let path = entry.map_err(|source| EngineError::Io { path: dir.to_path_buf(), source })?.path();
+            if path == self.backups_dir() || path == self.dir.join(RESTORE_DIR) {
+                continue;
+            }
+            if path.is_dir() {
+                self.list_files(&path, files)?;
+            } else if path.extension().is_none_or(|extension| extension != "tmp") {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// The world's backups, newest first.
+    pub fn backups(&self) -> Vec<Backup> {
+        let Ok(entries) = fs::read_dir(self.backups_dir()) else {
+            return Vec::new();
+        };
+        let mut backups = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let file = entry.file_name().into_string().ok()?;
+                let created = file.strip_suffix(".zip")?.parse().ok()?;
+                Some(Backup { file, created, size: entry.metadata().ok()?.len() })
+            })
+            .collect::<Vec<_>>();
+        backups.sort_by_key(|backup| Reverse(backup.created));
+        backups
+    }
+
+    /// Puts the world back the way it was when `backup` was taken. Its
+    /// other backups are kept, `backup` included.
+    pub fn restore(&self, backup: &Backup) -> Result<()> {
+        let path = self.backups_dir().join(&backup.file);
+        let staging = self.dir.join(RESTORE_DIR);
+        remove(&staging)?;
+        if let Err(e) = unpack(&path, &staging) {
+            let _ = remove(&staging);
+            return Err(e);
+        }
+
+        let io_error = |source| EngineError::Io { path: self.dir.clone(), source };
+        for entry in fs::read_dir(&self.dir).map_err(io_error)? {
+            let entry = entry.map_err(io_error)?.path();
+            if entry != self.backups_dir() && entry != staging {
+                remove(&entry)?;
+            }
+        }
+        for entry in fs::read_dir(&staging).map_err(io_error)? {
+            let entry = entry.map_err(io_error)?;
+            fs::rename(entry.path(), self.dir.join(entry.file_name())).map_err(io_error)?;
+        }
+        fs::remove_dir(&staging).map_err(io_error)
+    }
+}
+
+fn backup_file(created: u64) -> String {
+    format!("{created}.zip")
+}
+
+// Unzips `path` into `dir`, refusing entries that would land outside it.
+fn unpack(path: &Path, dir: &Path) -> Result<()> {
+    let zip_error = |source| EngineError::Backup { path: path.to_path_buf(), source };
+    let file = File::open(path).map_err(|source| EngineError::Io { path: path.to_path_buf(), source })?;
+    let mut zip = ZipArchive::new(file).map_err(zip_error)?;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(zip_error)?;
+        let Some(name) = entry.enclosed_name() else {
+            return Err(zip_error(zip::result::ZipError::InvalidArchive("file outside the world")));
+        };
+        let target = dir.join(name);
+        let created = if entry.is_dir() { &target } else { target.parent().unwrap_or(dir) };
+        fs::create_dir_all(created).map_err(|source| EngineError::Io { path: created.to_path_buf(), source })?;
+        if entry.is_file() {
+            let mut out = File::create(&target).map_err(|source| EngineError::Io { path: target.clone(), source })?;
+            io::copy(&mut entry, &mut out).map_err(|source| EngineError::Io { path: target.clone(), source })?;
+        }
+    }
+    Ok(())
+}
+
+// Removes a file or directory, if there is one.
+fn remove(path: &Path) -> Result<()> {
+    let removed = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+    match removed {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(EngineError::Io { path: path.to_path_buf(), source: e }),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread::JoinHandle,
+};
+
+use tracing::error;
+
+use crate::{
+    chunk::{Chunk, ChunkPos},
+    world::World,
+    worldgen::{structure::PendingBlock, WorldGenerator},
+};
+
+use super::WorldSave;
+
+// Loads that may be queued or finished but not yet collected. Past this,
+// `request_load` refuses, so teleporting far only ever holds a bounded
+// number of chunks in flight.
+const MAX_PENDING_LOADS: usize = 256;
+// Unloaded chunks waiting to be written. Past this, `can_save` says no and
+// the world keeps modified chunks loaded until the worker catches up.
+const MAX_PENDING_SAVES: usize = 512;
+
+/// A chunk coming back from the worker.
+pub struct LoadedChunk {
+    pub pos: ChunkPos,
+    pub chunk: Chunk,
+    /// Structure blocks spilling into neighbours, if the chunk was freshly
+    /// generated.
+    pub overflow: Vec<PendingBlock>,
+    /// The chunk was still waiting to be saved, so it differs from disk.
+    pub unsaved: bool,
+}
+
+#[derive(Default)]
+struct Queues {
+    center: Option<ChunkPos>,
+    loads: HashSet<ChunkPos>,
+    saves: HashMap<ChunkPos, Chunk>,
+    // Set while the worker is writing a chunk it took off `saves`.
+    saving: bool,
+    shutdown: bool,
+}
+
+impl Queues {
+    // Nearest queued load to the player, so what's in front of them shows
+    // up first.
+    fn nearest_load(&self) -> Option<ChunkPos> {
+        let center = self.center.unwrap_or(ChunkPos::new(0, 0, 0));
+        self.loads.iter().copied().min_by_key(|pos| pos.distance_squared(center))
+    }
+}
+
+struct Shared {
+    queues: Mutex<Queues>,
+    wake: Condvar,
+}
+
+/// Loads and saves chunks on a worker thread, so disk IO and generation
+/// never stall a frame. Chunks without a save file are generated on the
+/// worker too.
+pub struct ChunkIo {
+    shared: Arc<Shared>,
+    results: mpsc::Receiver<LoadedChunk>,
+    // Requested loads whose result hasn't been collected yet.
+    pending: HashSet<ChunkPos>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl ChunkIo {
+    pub fn new(save: WorldSave, generator: WorldGenerator) -> Self {
+        let shared = Arc::new(Shared {
+            queues: Mutex::new(Queues::default()),
+            wake: Condvar::new(),
+        });
+        let (sender, results) = mpsc::channel();
+
+        let worker = {
+            let shared = Arc::clone(&shared);
+            std::thread::Builder::new()
+                .name("chunk-io".to_string())
+                .spawn(move || run_worker(&shared, &save, &generator, &sender))
+                .expect("could not start the chunk IO thread")
+        };
+
+        Self {
+            shared,
+            results,
+            pending: HashSet::new(),
+            worker: Some(worker),
+        }
+    }
+
+    /// Whether a load for `pos` is queued or its result not yet collected.
+    pub fn is_pending(&self, pos: ChunkPos) -> bool {
+        self.pending.contains(&pos)
+    }
+
+    /// Queues a load. Returns false when too many loads are in flight; try
+    /// again on a later frame.
+    pub fn request_load(&mut self, pos: ChunkPos) -> bool {
+        if self.pending.contains(&pos) {
+            return true;
+        }
+        if self.pending.len() >= MAX_PENDING_LOADS {
+            return false;
+        }
+
+        self.pending.insert(pos);
+        self.shared.queues.lock().unwrap().loads.insert(pos);
+        // `flush` waits on the same condvar, so wake everyone.
+        self.shared.wake.notify_all();
+        true
+    }
+
+    /// Whether the save queue has room. While it doesn't, modified chunks
+    /// should stay loaded rather than pile up waiting to be written.
+    pub fn can_save(&self) -> bool {
+        self.shared.queues.lock().unwrap().saves.len() < MAX_PENDING_SAVES
+    }
+
+    /// Queues a chunk to be written. Always accepted; check `can_save` to
+    /// keep the queue bounded.
+    pub fn request_save(&mut self, chunk: Chunk) {
+        self.shared.queues.lock().unwrap().saves.insert(chunk.pos, chunk);
+        self.shared.wake.notify_all();
+    }
+
+    /// Queues copies of the world's modified chunks to be written while
+    /// there's room, leaving the rest modified for a later call. Returns
+    /// whether they all fit.
+    pub fn save_modified(&mut self, world: &mut World) -> bool {
+        let mut left = Vec::new();
+        for pos in world.take_modified() {
+            if self.can_save() {
+                self.request_save(world.chunks[&pos].clone());
+            } else {
+                left.push(pos);
+            }
+        }
+        let all = left.is_empty();
+        world.keep_modified(left);
+        all
+    }
+
+    /// Loads nearest `center` are served first.
+    pub fn set_center(&mut self, center: ChunkPos) {
+        self.shared.queues.lock().unwrap().center = Some(center);
+    }
+
+    /// Drops queued loads for chunks no longer wanted, such as ones the
+    /// player has since moved away from.
+    pub fn retain_loads(&mut self, mut keep: impl FnMut(ChunkPos) -> bool) {
+        let mut queues = self.shared.queues.lock().unwrap();
+        let cancelled = queues.loads.iter().copied().filter(|&pos| !keep(pos)).collect::<Vec<_>>();
+        for pos in cancelled {
+            queues.loads.remove(&pos);
+            self.pending.remove(&pos);
+        }
+    }
+
+    /// Chunks the worker has finished since the last call.
+    pub fn poll(&mut self) -> Vec<LoadedChunk> {
+        let loaded = self.results.try_iter().collect::<Vec<_>>();
+        for chunk in &loaded {
+            self.pending.remove(&chunk.pos);
+        }
+        loaded
+    }
+
+    /// Blocks until every queued save has been written.
+    pub fn flush(&self) {
+        let mut queues = self.shared.queues.lock().unwrap();
+        while !queues.saves.is_empty() || queues.saving {
+            queues = self.shared.wake.wait(queues).unwrap();
+        }
+    }
+}
+
+impl Drop for ChunkIo {
+    // Queued saves are still written; queued loads are dropped.
+    fn drop(&mut self) {
+        self.shared.queues.lock().unwrap().shutdown = true;
+        self.shared.wake.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+enum Job {
+    Load(ChunkPos),
+    Save(Chunk),
+}
+
+fn run_worker(shared: &Shared, save: &WorldSave, generator: &WorldGenerator, results: &mpsc::Sender<LoadedChunk>) {
+    loop {
+        let job = {
+            let mut queues = shared.queues.lock().unwrap();
+            queues.saving = false;
+            // Wakes anyone in `flush`.
+            shared.wake.notify_all();
+
+            loop {
+                // A load for a chunk still waiting to be saved gets the
+                // queued copy straight back, which is newer than the file.
+                let reclaimed = queues.loads.iter().find(|pos| queues.saves.contains_key(pos)).copied();
+                if let Some(pos) = reclaimed {
+                    queues.loads.remove(&pos);
+                    let chunk = queues.saves.remove(&pos).unwrap();
+                    let _ = results.send(LoadedChunk { pos, chunk, overflow: Vec::new(), unsaved: true });
+                    continue;
+                }
+
+                // Loads come first unless saves are backing up, since they're
+                // what the player is waiting to see.
+                let saves_backed_up = queues.saves.len() >= MAX_PENDING_SAVES / 2;
+                let next_save = queues.saves.keys().next().copied();
+                match (queues.nearest_load(), next_save) {
+                    (Some(pos), _) if !saves_backed_up && !queues.shutdown => {
+                        queues.loads.remove(&pos);
+                        break Job::Load(pos);
+                    },
+                    (_, Some(pos)) => {
+                        queues.saving = true;
+                        break Job::Save(queues.saves.remove(&pos).unwrap());
+                    },
+                    _ if queues.shutdown => return,
+                    _ => queues = shared.wake.wait(queues).unwrap(),
+                }
+            }
+        };
+
+        match job {
+            Job::Load(pos) => {
+                let saved = save.load_chunk(pos).unwrap_or_else(|e| {
+                    error!(x = pos.x, y = pos.y, z = pos.z, "{e}; regenerating the chunk");
+                    None
+                });
+                let loaded = match saved {
+                    Some(chunk) => LoadedChunk { pos, chunk, overflow: Vec::new(), unsaved: false },
+                    None => {
+                        let (chunk, overflow) = generator.generate(pos);
+                        LoadedChunk { pos, chunk, overflow, unsaved: false }
+                    },
+                };
+                if results.send(loaded).is_err() {
+                    return;
+                }
+            },
+            Job::Save(chunk) => {
+                if let Err(e) = save.save_chunk(&chunk) {
+                    error!(x = chunk.pos.x, y = chunk.pos.y, z = chunk.pos.z, "could not save chunk: {e}");
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Upgrades worlds saved in an older format before they load. The level
+//! and player files are read as plain TOML tables and taken through each
+//! migration from their version up, so a migration can rename or reshape
+//! fields that would no longer deserialize. A migration can also rewrite
+//! chunk files, such as splitting the columns saves before format 2 kept
+//! into a file for each chunk. Otherwise chunk files carry their own
+//! version and are upgraded one at a time as they're read, in
+//! `decode_chunk`.
+
+use std::{fs, io::ErrorKind, path::Path};
+
+use log::{info, warn};
+use serde::Deserialize;
+use toml::Table;
+
+use crate::{
+    chunk::{Chunk, ColumnPos, CHUNK_SIZE},
+    codec::{self, Reader},
+    dimension::Dimension,
+    error::{EngineError, Result},
+};
+
+use super::{
+    decode_block_entities, decode_voxels, read_chunk_version, voxel_position, Backup, WorldSave,
+    FIRST_CUBE_VERSION, FORMAT_VERSION, GAME_VERSION, LEVEL_FILE, PLAYER_FILE,
+};
+
+// How tall columns were before version 4 chunk files started saying.
+const OLD_COLUMN_HEIGHT: usize = 128;
+
+// One step up in format. Every part gets run again if the game stops
+// partway through an upgrade, so each has to leave what it's already
+// upgraded as it is.
+struct Migration {
+    // What changed, for the log.
+    summary: &'static str,
+    level: fn(&mut Table),
+    player: fn(&mut Table),
+    chunks: fn(&WorldSave) -> Result<()>,
+}
+
+// `MIGRATIONS[n]` takes a save from format `n` to `n + 1`.
+const MIGRATIONS: [Migration ; FORMAT_VERSION as usize] = [
+    Migration {
+        summary: "settings older saves left out are written in full",
+        level: |level| {
+            level.entry("time_speed").or_insert(1.0.into());
+            level.entry("daylight_cycle").or_insert(true.into());
+        },
+        player: |player| {
+            player.entry("dimension").or_insert("overworld".into());
+        },
+        chunks: |_| Ok(()),
+    },
+    Migration {
+        summary: "chunk files holding a whole column are split into one for each chunk",
+        level: |_| (),
+        player: |_| (),
+        chunks: split_columns,
+    },
+];
+
+// Just what's needed to tell whether a level file needs upgrading.
+#[derive(Deserialize)]
+struct Versions {
+    #[serde(default)]
+    format_version: u32,
+    #[serde(default)]
+    game_version: String,
+}
+
+impl WorldSave {
+    /// Gets a saved world ready to load. One saved in a newer format than
+    /// this version of the game reads is refused; one saved in an older
+    /// format, or by another version of the game, is backed up first and
+    /// returns the backup, and an older format is then upgraded.
+    pub fn upgrade(&self) -> Result<Option<Backup>> {
+        let Some(versions) = self.read::<Versions>(LEVEL_FILE)? else {
+            return Ok(None);
+        };
+        if versions.format_version > FORMAT_VERSION {
+            return Err(EngineError::FutureSave {
+                path: self.dir.join(LEVEL_FILE),
+                version: versions.format_version,
+                supported: FORMAT_VERSION,
+            });
+        }
+        if versions.format_version == FORMAT_VERSION && versions.game_version == GAME_VERSION {
+            return Ok(None);
+        }
+
+        let backup = self.backup()?;
+        if versions.format_version < FORMAT_VERSION {
+            let mut level = self.read::<Table>(LEVEL_FILE)?.unwrap_or_default();
+            let mut player = self.read::<Table>(PLAYER_FILE)?;
+            for (from, migration) in MIGRATIONS.iter().enumerate().skip(versions.format_version as usize) {
+                info!("upgrading the world from save format {from}: {}", migration.summary);
+                (migration.level)(&mut level);
+                if let Some(player) = &mut player {
+                    (migration.player)(player);
+                }
+                (migration.chunks)(self)?;
+            }
+            level.insert("format_version".to_string(), i64::from(FORMAT_VERSION).into());
+            // The level goes last, so if the game stops before it's
+            // written the upgrade starts over from the top next time.
+            if let Some(player) = &player {
+                self.write(PLAYER_FILE, player)?;
+            }
+            self.write(LEVEL_FILE, &level)?;
+        }
+        Ok(Some(backup))
+    }
+}
+
+// Writes each chunk of every column file, in every dimension, to a file of
+// its own, then removes the column's. A column file that can't be read is
+// left where it is, and its chunks are generated again.
+fn split_columns(save: &WorldSave) -> Result<()> {
+    for dimension in Dimension::ALL {
+        let save = save.dimension(dimension);
+        let entries = match fs::read_dir(&save.chunks_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(source) => return Err(EngineError::Io { path: save.chunks_dir.clone(), source }),
+        };
+        for entry in entries {
+            let path = entry.map_err(|source| EngineError::Io { path: save.chunks_dir.clone(), source })?.path();
+            let Some(column) = column_file(&path) else {
+                continue;
+            };
+            let bytes = fs::read(&path).map_err(|source| EngineError::Io { path: path.clone(), source })?;
+            match decode_column(column, &bytes) {
+                Ok(chunks) => {
+                    for chunk in &chunks {
+                        save.save_chunk(chunk)?;
+                    }
+                    fs::remove_file(&path).map_err(|source| EngineError::Io { path: path.clone(), source })?;
+                },
+                Err(reason) => warn!("leaving {} as it is: {reason}", path.display()),
+            }
+        }
+    }
+    Ok(())
+}
+
+// The column a file is named for, if it's named `{x}_{z}.bin` like the
+// ones that held a whole column.
+fn column_file(path: &Path) -> Option<ColumnPos> {
+    if path.extension()? != "bin" {
+        return None;
+    }
+    let (x, z) = path.file_stem()?.to_str()?.split_once('_')?;
+    Some(ColumnPos::new(x.parse().ok()?, z.parse().ok()?))
+}
+
+// Reads a chunk file from before each chunk had its own, which holds the
+// column from y 0 up, as the chunks it's made of.
+fn decode_column(column: ColumnPos, bytes: &[u8]) -> codec::Result<Vec<Chunk>> {
+    let mut reader = Reader::new(bytes);
+    let version = read_chunk_version(&mut reader)?;
+    if version >= FIRST_CUBE_VERSION {
+        return Err(format!("chunk version {version} holds a single chunk, not a column"));
+    }
+    let height = if version >= 4 { reader.u16()? as usize } else { OLD_COLUMN_HEIGHT };
+    let volume = CHUNK_SIZE * height * CHUNK_SIZE;
+
+    let mut chunks = (0..height.div_ceil(CHUNK_SIZE))
+        .map(|y| Chunk::new(column.chunk(y as i32)))
+        .collect::<Vec<_>>();
+    decode_voxels(&mut reader, version, volume, |i, state, fluid_level| {
+        let [x, y, z] = voxel_position(i);
+        chunks[y / CHUNK_SIZE].set_voxel(x, y % CHUNK_SIZE, z, state, fluid_level);
+    })?;
+    decode_block_entities(&mut reader, version, volume, |i, entity| {
+        let [x, y, z] = voxel_position(i);
+        let chunk = &mut chunks[y / CHUNK_SIZE];
+        if chunk.set_block_entity(x, y % CHUNK_SIZE, z, entity) {
+            Ok(())
+        } else {
+            Err(format!("block entity on `{}`, which can't have it", chunk.get(x, y % CHUNK_SIZE, z).info().name))
+        }
+    })?;
+
+    for chunk in &mut chunks {
+        chunk.compact();
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        block::BlockId,
+        block_entity::BlockEntity,
+        chunk::{Chunk, ChunkPos, ColumnPos, CHUNK_SIZE},
+        codec::Writer,
+    };
+
+    use super::{
+        super::{
+            decode_chunk, encode_chunk,
+            tests::{assert_same, block, load_blocks},
+            CHUNK_MAGIC,
+        },
+        decode_column,
+        OLD_COLUMN_HEIGHT,
+    };
+
+    // A column file as versions before chunks had one each wrote it, from
+    // runs of blocks by name and block entities by voxel index.
+    fn old_column(version: u8, height: Option<u16>, runs: &[(u16, &str)], entities: &[(u32, BlockEntity)]) -> Vec<u8> {
+        let mut names = Vec::new();
+        for &(_, name) in runs {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        let mut bytes = CHUNK_MAGIC.to_vec();
+        bytes.put_u8(version);
+        if let Some(height) = height {
+            bytes.put_u16(height);
+        }
+        bytes.put_u16(names.len() as u16);
+        for name in &names {
+            bytes.put_u8(name.len() as u8);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.put_u8(0);
+            if version >= 2 {
+                bytes.put_u8(0);
+            }
+        }
+        bytes.put_u32(runs.len() as u32);
+        for &(length, name) in runs {
+            bytes.put_u16(length);
+            bytes.put_u16(names.iter().position(|&n| n == name).unwrap() as u16);
+        }
+        if version >= 3 {
+            bytes.put_u16(entities.len() as u16);
+            for (index, entity) in entities {
+                bytes.put_u32(*index);
+                entity.encode(&mut bytes);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn splits_a_column_into_chunks() {
+        load_blocks();
+        let layer = (CHUNK_SIZE * CHUNK_SIZE) as u16;
+        // Stone up to y 20, then air with a chest at 3, 20, 5.
+        let chest = (20 * CHUNK_SIZE as u16 + 5) * CHUNK_SIZE as u16 + 3;
+        let runs = [
+            (20 * layer, "stone"),
+            (chest - 20 * layer, "air"),
+            (1, "chest"),
+            (32 * layer - chest - 1, "air"),
+        ];
+        let entity = BlockEntity::for_block(block("chest")).unwrap();
+        let bytes = old_column(4, Some(32), &runs, &[(chest as u32, entity.clone())]);
+
+        let column = ColumnPos::new(2, -3);
+        let chunks = decode_column(column, &bytes).unwrap();
+        assert_eq!(chunks.iter().map(|chunk| chunk.pos).collect::<Vec<_>>(), [column.chunk(0), column.chunk(1)]);
+        let [bottom, top] = &chunks[..] else {
+            unreachable!();
+        };
+        assert_eq!(bottom.get(0, 0, 0), BlockId::STONE);
+        assert_eq!(bottom.get(15, 15, 15), BlockId::STONE);
+        assert_eq!(top.get(7, 3, 9), BlockId::STONE);
+        assert_eq!(top.get(7, 4, 9), BlockId::AIR);
+        assert_eq!(top.get(3, 4, 5), block("chest"));
+        assert_eq!(top.block_entities().collect::<Vec<_>>(), [([3, 4, 5], &entity)]);
+        assert_eq!(bottom.block_entities().count(), 0);
+
+        // Each comes back the same from a file of its own.
+        for chunk in &chunks {
+            assert_same(chunk, &decode_chunk(chunk.pos, &encode_chunk(chunk)).unwrap());
+        }
+    }
+
+    #[test]
+    fn splits_a_first_version_column() {
+        load_blocks();
+        let volume = (CHUNK_SIZE * OLD_COLUMN_HEIGHT * CHUNK_SIZE) as u16;
+        let bytes = old_column(1, None, &[(volume / 2, "dirt"), (volume / 2, "air")], &[]);
+        let chunks = decode_column(ColumnPos::new(0, 0), &bytes).unwrap();
+        assert_eq!(chunks.len(), OLD_COLUMN_HEIGHT / CHUNK_SIZE);
+        assert_eq!(chunks[3].get(0, 15, 0), BlockId::DIRT);
+        assert_eq!(chunks[4].get(0, 0, 0), BlockId::AIR);
+        assert_eq!(chunks[7].pos, ChunkPos::new(0, 7, 0));
+    }
+
+    #[test]
+    fn rejects_short_or_current_files() {
+        load_blocks();
+        let bytes = old_column(4, Some(32), &[(100, "stone")], &[]);
+        assert!(decode_column(ColumnPos::new(0, 0), &bytes).is_err());
+
+        let current = encode_chunk(&Chunk::new(ChunkPos::new(0, 0, 0)));
+        assert!(decode_column(ColumnPos::new(0, 0), &current).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+//! Schematics: a box of blocks copied out of the world, with their states,
+//! fluid levels and block entities, that can be saved under a name in
+//! `schematics/` and pasted back anywhere, in any world.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    block::{BlockId, BlockState, Properties},
+    block_entity::BlockEntity,
+    codec::{self, Reader, Writer},
+    error::{EngineError, Result},
+    save,
+    world::{BlockSnapshot, World},
+};
+
+pub const SCHEMATICS_DIR: &str = "schematics";
+
+const MAGIC: &[u8 ; 4] = b"VXSC";
+const VERSION: u8 = 1;
+
+/// A copied box of blocks, `size` across in x, y and z.
+#[derive(Clone, Debug)]
+pub struct Schematic {
+    size: [usize ; 3],
+    palette: Vec<(BlockState, u8)>,
+    // Palette indices, in y, z, x order like chunks.
+    voxels: Vec<u16>,
+    block_entities: Vec<(usize, BlockEntity)>,
+}
+
+impl Schematic {
+    /// Copies the blocks from `min` to `max`, both included. Anything in
+    /// chunks that aren't loaded comes out as air.
+    pub fn capture(world: &World, min: [i32 ; 3], max: [i32 ; 3]) -> Self {
+        let size = [0, 1, 2].map(|axis| (max[axis] - min[axis] + 1).max(0) as usize);
+        let mut palette = Vec::new();
+        let mut lookup = HashMap::new();
+        let mut voxels = Vec::with_capacity(size.iter().product());
+        let mut block_entities = Vec::new();
+
+        for y in 0..size[1] {
+            for z in 0..size[2] {
+                for x in 0..size[0] {
+                    let [wx, wy, wz] = [min[0] + x as i32, min[1] + y as i32, min[2] + z as i32];
+                    let voxel = (world.get_state(wx, wy, wz), world.fluid_level(wx, wy, wz));
+                    let index = *lookup.entry(voxel).or_insert_with(|| {
+                        palette.push(voxel);
+                        palette.len() as u16 - 1
+                    });
+                    if let Some(entity) = world.block_entity(wx, wy, wz) {
+                        block_entities.push((voxels.len(), entity.clone()));
+                    }
+                    voxels.push(index);
+                }
+            }
+        }
+
+        Self { size, palette, voxels, block_entities }
+    }
+
+    pub fn size(&self) -> [usize ; 3] {
+        self.size
+    }
+
+    /// Sets every block of the schematic, air included, with its lowest
+    /// corner at `origin`, through `World::set_voxels`, and returns what
+    /// was there before each block it changed.
+    pub fn place(&self, world: &mut World, origin: [i32 ; 3]) -> Vec<BlockSnapshot> {
+        let mut entities = self.block_entities.iter().peekable();
+        let blocks = self.voxels.iter().enumerate().map(|(i, &index)| {
+            let (state, level) = self.palette[index as usize];
+            let entity = entities.next_if(|(at, _)| *at == i).map(|(_, entity)| entity.clone());
+            BlockSnapshot { block: self.position(i, origin), state, level, entity }
+        });
+        world.set_voxels(blocks)
+    }
+
+    fn position(&self, i: usize, origin: [i32 ; 3]) -> [i32 ; 3] {
+        let [width, _, depth] = self.size;
+        let (x, z, y) = (i % width, i / width % depth, i / (width * depth));
+        [origin[0] + x as i32, origin[1] + y as i32, origin[2] + z as i32]
+    }
+
+    fn path(name: &str) -> PathBuf {
+        Path::new(SCHEMATICS_DIR).join(format!("{name}.bin"))
+    }
+
+    /// Whether a schematic has been saved as `name`.
+    pub fn exists(name: &str) -> bool {
+        Self::path(name).exists()
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Self::path(name);
+        let bytes = fs::read(&path).map_err(|source| EngineError::Io { path: path.clone(), source })?;
+        Self::decode(&bytes).map_err(|reason| EngineError::CorruptSchematic { path, reason })
+    }
+
+    pub fn save(&self, name: &str) -> Result<()> {
+        fs::create_dir_all(SCHEMATICS_DIR)
+            .map_err(|source| EngineError::Io { path: SCHEMATICS_DIR.into(), source })?;
+        save::write_atomic(&Self::path(name), &self.encode())
+    }
+
+    // Laid out like a chunk file, with blocks by name and runs of the same
+    // voxel stored once:
+    //
+    //   magic, version: u8, size: u16 x, y and z, palette length: u16,
+    //   palette entries: name: string, fluid level: u8, properties: u8,
+    //   run count: u32, runs: length: u16, palette index: u16,
+    //   block entity count: u32, block entities: voxel index: u32, entity
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.put_u8(VERSION);
+        for size in self.size {
+            bytes.put_u16(size as u16);
+        }
+        bytes.put_u16(self.palette.len() as u16);
+        for (state, fluid_level) in &self.palette {
+            bytes.put_string(state.block.info().name.as_str());
+            bytes.put_u8(*fluid_level);
+            bytes.put_u8(state.properties.bits());
+        }
+
+        let mut runs = Vec::<(u16, u16)>::new();
+        for &index in &self.voxels {
+            match runs.last_mut() {
+                Some((length, last)) if *last == index && *length < u16::MAX => *length += 1,
+                _ => runs.push((1, index)),
+            }
+        }
+        bytes.put_u32(runs.len() as u32);
+        for (length, index) in runs {
+            bytes.put_u16(length);
+            bytes.put_u16(index);
+        }
+
+        bytes.put_u32(self.block_entities.len() as u32);
+        for (i, entity) in &self.block_entities {
+            bytes.put_u32(*i as u32);
+            entity.encode(&mut bytes);
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> codec::Result<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != MAGIC {
+            return Err("not a schematic file".to_string());
+        }
+        let version = reader.u8()?;
+        if version == 0 || version > VERSION {
+            return Err(format!("unsupported schematic version {version}"));
+        }
+        let size = [reader.u16()?, reader.u16()?, reader.u16()?].map(usize::from);
+        let volume = size.iter().product::<usize>();
+
+        let mut palette = Vec::new();
+        for _ in 0..reader.u16()? {
+            let name = reader.string()?;
+            let block = BlockId::from_name(&name).ok_or_else(|| format!("unknown block `{name}`"))?;
+            let fluid_level = reader.u8()?;
+            let properties = Properties::from_bits(reader.u8()?);
+            palette.push((BlockState::new(block, properties), fluid_level));
+        }
+
+        let mut voxels = Vec::with_capacity(volume);
+        for _ in 0..reader.u32()? {
+            let length = reader.u16()? as usize;
+            let index = reader.u16()?;
+            if index as usize >= palette.len() {
+                return Err("palette index out of range".to_string());
+            }
+            if voxels.len() + length > volume {
+                return Err("more voxels than fit in the schematic".to_string());
+            }
+            voxels.extend(std::iter::repeat_n(index, length));
+        }
+        if voxels.len() != volume {
+            return Err("schematic data ends early".to_string());
+        }
+
+        let mut block_entities = Vec::new();
+        for _ in 0..reader.u32()? {
+            let i = reader.u32()? as usize;
+            if i >= volume {
+                return Err("block entity outside the schematic".to_string());
+            }
+            block_entities.push((i, BlockEntity::decode(&mut reader)?));
+        }
+
+        Ok(Self { size, palette, voxels, block_entities })
+    }
+}
+
+/// Whether `name` can be a schematic's file name: letters, digits, `_`
+/// and `-` only, so it can't reach outside `schematics/`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
@@ -0,0 +1,188 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+// A frame copied into a readback buffer, waiting for the GPU to finish
+// and the buffer to be mapped.
+struct PendingCapture {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    bgra: bool,
+    mapped: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+/// Copies frames to PNG files. A capture is requested by input, encoded
+/// into the frame's command buffer, read back once the GPU is done with it
+/// and written out on a worker thread, so neither step blocks a frame.
+#[derive(Default)]
+pub struct Screenshots {
+    requested: bool,
+    pending: Option<PendingCapture>,
+}
+
+impl Screenshots {
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// Records a copy of `texture` into `encoder` if a screenshot was
+    /// requested.
+    pub fn capture(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        if !self.requested || self.pending.is_some() {
+            return;
+        }
+        self.requested = false;
+
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            warn!("this surface can't be copied from, so screenshots are unavailable");
+            return;
+        }
+
+        let bgra = match texture.format() {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => {
+                warn!("screenshots of {format:?} surfaces are not supported");
+                return;
+            },
+        };
+
+        let (width, height) = (texture.width(), texture.height());
+        // Rows in a texture copy must be aligned to 256 bytes.
+        let padded_bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Screenshot Buffer"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+
+        // Mapping only starts once the copy is submitted, which happens
+        // when the encoder is.
+        let (sender, mapped) = mpsc::channel();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+        self.pending = Some(PendingCapture {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            bgra,
+            mapped,
+        });
+    }
+
+    /// Checks whether a capture has been read back and, if so, hands it to
+    /// a thread that writes the PNG.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some(capture) = &self.pending else {
+            return;
+        };
+
+        device.poll(wgpu::Maintain::Poll);
+        let result = match capture.mapped.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+        };
+        let capture = self.pending.take().unwrap();
+        if let Err(e) = result {
+            warn!("could not read back screenshot: {e}");
+            return;
+        }
+
+        // Strip the row padding while copying out so the buffer can be
+        // released right away.
+        let row_bytes = capture.width as usize * 4;
+        let mut pixels = Vec::with_capacity(row_bytes * capture.height as usize);
+        {
+            let data = capture.buffer.slice(..).get_mapped_range();
+            for row in data.chunks(capture.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..row_bytes]);
+            }
+        }
+        capture.buffer.unmap();
+
+        let (width, height, bgra) = (capture.width, capture.height, capture.bgra);
+        thread::spawn(move || {
+            if bgra {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            match write_png(pixels, width, height) {
+                Ok(path) => info!("saved screenshot to {}", path.display()),
+                Err(e) => warn!("could not save screenshot: {e}"),
+            }
+        });
+    }
+}
+
+fn write_png(pixels: Vec<u8>, width: u32, height: u32) -> Result<PathBuf, String> {
+    let dir = Path::new(SCREENSHOT_DIR);
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let image = image::RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| "screenshot buffer has the wrong size".to_string())?;
+
+    // Screenshots taken within the same second get a counter.
+    let stem = timestamp();
+    let mut path = dir.join(format!("{stem}.png"));
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("{stem}_{n}.png"));
+        n += 1;
+    }
+
+    image.save(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+// The current UTC time as `YYYY-MM-DD_HH-MM-SS`.
+fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, time) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // Days since the epoch to a civil date (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+    )
+}
@@ -0,0 +1,333 @@
+//! Mods written in Lua. Every directory under `mods/` with an `init.lua`
+//! in it is a mod, and they run in name order when the game starts.
+//! Scripts get a `game` table to add blocks and items, adjust world
+//! generation and subscribe to events:
+//!
+//! ```lua
+//! game.register_block { name = "marble", textures = { All = "marble" }, sound = "Stone" }
+//! game.register_item { name = "apple", texture = "apple", food = 4 }
+//! game.worldgen { cave_density = 0.5 }
+//! game.on("block_placed", function(world, x, y, z, block)
+//!     if block == "marble" then world.set_block(x, y + 1, z, "torch") end
+//! end)
+//! ```
+//!
+//! Blocks take the same fields as `blocks.ron`, and items the same but
+//! with one `texture` and no model. A mod directory is laid out like
+//! `assets/` for the files it brings, such as its textures, and can hold
+//! block and recipe files too. Handlers are called with the world and:
+//!
+//! - `tick`: dt
+//! - `block_placed`, `block_broken`: x, y, z, block
+//! - `chunk_loaded`: chunk x, chunk y, chunk z
+//! - `player_damaged`: half hearts lost, what hurt them
+//! - `key_action`: action as the controls settings name it, pressed
+//!
+//! They run wherever the world is simulated, so in singleplayer and on
+//! servers, which see ticks, blocks and chunks, but not on clients.
+//! Servers and the players joining them need the same mods, as blocks are
+//! sent by their place in the registry; a player whose blocks and items
+//! don't match the server's is turned away when joining.
+//!
+//! Scripts can't reach files or the OS, their memory is capped and each
+//! call into them is cut off if it runs too long, so a broken mod can
+//! stop the game loading but can't hang it.
+
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use log::{info, warn};
+use mlua::{ChunkMode, Function, HookTriggers, Lua, LuaOptions, LuaSerdeExt, RegistryKey, StdLib, Table, Value, Variadic};
+use serde::Deserialize;
+
+use crate::{
+    block::{BlockDef, BlockId},
+    config::WorldConfig,
+    error::{EngineError, Result},
+    events::Event,
+    world::World,
+};
+
+pub const MODS_DIR: &str = "mods";
+const ENTRY_FILE: &str = "init.lua";
+
+// Between all mods.
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+// Lua instructions one call into a mod may run, checked every
+// `HOOK_INTERVAL`; plenty for real work, while an endless loop is cut
+// off in well under a second.
+const INSTRUCTION_LIMIT: u32 = 20_000_000;
+const HOOK_INTERVAL: u32 = 10_000;
+
+// What mods can subscribe to: ticks, and everything on the event bus.
+const EVENTS: [&str ; 6] = ["tick", "block_placed", "block_broken", "chunk_loaded", "player_damaged", "key_action"];
+
+// What a handler is called with after the world.
+#[derive(Clone, Copy)]
+enum Call {
+    Tick(f32),
+    Event(Event),
+}
+
+impl Call {
+    fn name(self) -> &'static str {
+        match self {
+            Call::Tick(_) => "tick",
+            Call::Event(event) => event.name(),
+        }
+    }
+}
+
+// World generation settings mods can change; later mods win.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WorldgenDef {
+    cave_density: Option<f64>,
+}
+
+struct Handler {
+    mod_name: String,
+    event: &'static str,
+    function: RegistryKey,
+}
+
+// What the `game` functions gather while mods load.
+#[derive(Default)]
+struct Registrations {
+    // Whether mods are still loading; registering anything after is an
+    // error, as the registry is fixed by then.
+    loading: bool,
+    // The mod being run, for registrations and log messages.
+    current: String,
+    blocks: Vec<(PathBuf, BlockDef)>,
+    handlers: Vec<Handler>,
+    worldgen: WorldgenDef,
+}
+
+pub struct Mods {
+    lua: Lua,
+    state: Rc<RefCell<Registrations>>,
+    // Instructions the running call has left.
+    budget: Rc<Cell<u32>>,
+    handlers: Vec<Handler>,
+    dirs: Vec<PathBuf>,
+}
+
+impl Mods {
+    /// Runs the `init.lua` of every mod in `dir`, failing on the first
+    /// that doesn't load. No directory means no mods.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let mut mod_dirs = match fs::read_dir(dir.as_ref()) {
+            Ok(entries) => entries.filter_map(|entry| Some(entry.ok()?.path())).filter(|path| path.join(ENTRY_FILE).is_file()).collect(),
+            Err(_) => Vec::new(),
+        };
+        mod_dirs.sort();
+
+        let mut mods = Self::new().map_err(|e| EngineError::Mod { name: "(setup)".to_string(), reason: e.to_string() })?;
+        mods.state.borrow_mut().loading = true;
+        for mod_dir in &mod_dirs {
+            let name = mod_dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let path = mod_dir.join(ENTRY_FILE);
+            let source = fs::read(&path).map_err(|source| EngineError::Io { path: path.clone(), source })?;
+            mods.state.borrow_mut().current = name.clone();
+            mods.budget.set(INSTRUCTION_LIMIT);
+            mods.lua.load(source)
+                .set_name(format!("@{}", path.display()))
+                .set_mode(ChunkMode::Text)
+                .exec()
+                .map_err(|e| EngineError::Mod { name: name.clone(), reason: e.to_string() })?;
+            info!("loaded mod `{name}`");
+        }
+
+        let mut state = mods.state.borrow_mut();
+        state.loading = false;
+        mods.handlers = std::mem::take(&mut state.handlers);
+        drop(state);
+        mods.dirs = mod_dirs;
+        Ok(mods)
+    }
+
+    fn new() -> mlua::Result<Self> {
+        // No `io`, `os`, `package` or `debug`, so scripts can only touch
+        // what they're handed.
+        let lua = Lua::new_with(StdLib::COROUTINE | StdLib::TABLE | StdLib::STRING | StdLib::UTF8 | StdLib::MATH, LuaOptions::new())?;
+        lua.set_memory_limit(MEMORY_LIMIT)?;
+        let budget = Rc::new(Cell::new(INSTRUCTION_LIMIT));
+        let hook_budget = budget.clone();
+        lua.set_hook(HookTriggers::new().every_nth_instruction(HOOK_INTERVAL), move |_, _| {
+            let left = hook_budget.get().saturating_sub(HOOK_INTERVAL);
+            hook_budget.set(left);
+            if left == 0 {
+                return Err(mlua::Error::runtime("ran for too long"));
+            }
+            Ok(())
+        });
+
+        let state = Rc::new(RefCell::new(Registrations::default()));
+        let globals = lua.globals();
+        // Both read files, and `load` could run bytecode, which isn't safe.
+        for name in ["dofile", "loadfile", "load"] {
+            globals.set(name, Value::Nil)?;
+        }
+        let print_state = state.clone();
+        globals.set("print", lua.create_function(move |_, values: Variadic<Value>| {
+            let text = values.iter().map(Value::to_string).collect::<mlua::Result<Vec<_>>>()?.join("\t");
+            info!("[{}] {text}", print_state.borrow().current);
+            Ok(())
+        })?)?;
+        globals.set("game", game_table(&lua, &state)?)?;
+        drop(globals);
+
+        Ok(Self { lua, state, budget, handlers: Vec::new(), dirs: Vec::new() })
+    }
+
+    /// The directories of the loaded mods, to search for their files.
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// The blocks and items mods registered, to add to the registry.
+    pub fn take_blocks(&mut self) -> Vec<(PathBuf, BlockDef)> {
+        std::mem::take(&mut self.state.borrow_mut().blocks)
+    }
+
+    /// Applies what mods changed about world generation to `settings`.
+    pub fn apply_worldgen(&self, settings: &mut WorldConfig) {
+        let state = self.state.borrow();
+        if let Some(cave_density) = state.worldgen.cave_density {
+            settings.cave_density = cave_density;
+        }
+    }
+
+    pub fn tick(&mut self, world: &mut World, dt: f32) {
+        self.dispatch(world, Call::Tick(dt));
+    }
+
+    pub fn handle(&mut self, world: &mut World, event: &Event) {
+        self.dispatch(world, Call::Event(*event));
+    }
+
+    // Calls every handler mods registered for `call`, handing them
+    // `world`. One that fails is logged and dropped, so a broken mod
+    // doesn't flood the log every tick.
+    fn dispatch(&mut self, world: &mut World, call: Call) {
+        let event = call.name();
+        if !self.handlers.iter().any(|handler| handler.event == event) {
+            return;
+        }
+
+        let world = RefCell::new(world);
+        let mut failed = Vec::new();
+        let result = self.lua.scope(|scope| {
+            let api = self.lua.create_table()?;
+            api.set("time", world.borrow().time)?;
+            api.set("get_block", scope.create_function(|_, (x, y, z): (i32, i32, i32)| {
+                Ok(world.borrow().get_block(x, y, z).info().name.clone())
+            })?)?;
+            api.set("set_block", scope.create_function(|_, (x, y, z, name): (i32, i32, i32, String)| {
+                let block = BlockId::from_name(&name).ok_or_else(|| mlua::Error::runtime(format!("unknown block `{name}`")))?;
+                Ok(world.borrow_mut().set_block(x, y, z, block))
+            })?)?;
+
+            for (index, handler) in self.handlers.iter().enumerate().filter(|(_, handler)| handler.event == event) {
+                self.state.borrow_mut().current = handler.mod_name.clone();
+                self.budget.set(INSTRUCTION_LIMIT);
+                let function: Function = self.lua.registry_value(&handler.function)?;
+                let result = match call {
+                    Call::Tick(dt) => function.call::<_, ()>((api.clone(), dt)),
+                    Call::Event(Event::BlockPlaced { block: [x, y, z], id } | Event::BlockBroken { block: [x, y, z], id }) => {
+                        function.call::<_, ()>((api.clone(), x, y, z, id.info().name.as_str()))
+                    },
+                    Call::Event(Event::ChunkLoaded { pos }) => function.call::<_, ()>((api.clone(), pos.x, pos.y, pos.z)),
+                    Call::Event(Event::PlayerDamaged { amount, source, .. }) => {
+                        function.call::<_, ()>((api.clone(), amount, source.name()))
+                    },
+                    Call::Event(Event::KeyAction { action, pressed }) => {
+                        function.call::<_, ()>((api.clone(), self.lua.to_value(&action)?, pressed))
+                    },
+                };
+                if let Err(e) = result {
+                    warn!("mod `{}` failed handling {event}, and won't be called for it again: {e}", handler.mod_name);
+                    failed.push(index);
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            warn!("could not call mods for {event}: {e}");
+        }
+
+        for index in failed.into_iter().rev() {
+            let handler = self.handlers.remove(index);
+            let _ = self.lua.remove_registry_value(handler.function);
+        }
+    }
+}
+
+// The `game` table scripts register through.
+fn game_table<'lua>(lua: &'lua Lua, state: &Rc<RefCell<Registrations>>) -> mlua::Result<Table<'lua>> {
+    let game = lua.create_table()?;
+
+    let block_state = state.clone();
+    game.set("register_block", lua.create_function(move |lua, def: Table| {
+        register_block(lua, &block_state, def)
+    })?)?;
+
+    // Items are blocks that are never placed, drawn as their texture.
+    let item_state = state.clone();
+    game.set("register_item", lua.create_function(move |lua, def: Table| {
+        let texture: String = def.get("texture")?;
+        def.set("texture", Value::Nil)?;
+        def.set("textures", lua.create_table_from([("All", texture)])?)?;
+        def.set("model", "None")?;
+        def.set("solid", false)?;
+        register_block(lua, &item_state, def)
+    })?)?;
+
+    let worldgen_state = state.clone();
+    game.set("worldgen", lua.create_function(move |lua, def: Table| {
+        let mut state = loading(&worldgen_state)?;
+        let def: WorldgenDef = lua.from_value(Value::Table(def))?;
+        if let Some(cave_density) = def.cave_density {
+            if !cave_density.is_finite() || cave_density < 0.0 {
+                return Err(mlua::Error::runtime(format!("cave density {cave_density} must be 0 or more")));
+            }
+            state.worldgen.cave_density = Some(cave_density);
+        }
+        Ok(())
+    })?)?;
+
+    let on_state = state.clone();
+    game.set("on", lua.create_function(move |lua, (event, function): (String, Function)| {
+        let mut state = loading(&on_state)?;
+        let event = EVENTS.into_iter().find(|&name| name == event).ok_or_else(|| {
+            mlua::Error::runtime(format!("unknown event `{event}`; there are {}", EVENTS.join(", ")))
+        })?;
+        let handler = Handler { mod_name: state.current.clone(), event, function: lua.create_registry_value(function)? };
+        state.handlers.push(handler);
+        Ok(())
+    })?)?;
+
+    Ok(game)
+}
+
+fn register_block(lua: &Lua, state: &Rc<RefCell<Registrations>>, def: Table) -> mlua::Result<()> {
+    let mut state = loading(state)?;
+    let def: BlockDef = lua.from_value(Value::Table(def))?;
+    let path = Path::new(MODS_DIR).join(&state.current).join(ENTRY_FILE);
+    state.blocks.push((path, def));
+    Ok(())
+}
+
+// The registrations, as long as mods are still loading.
+fn loading(state: &Rc<RefCell<Registrations>>) -> mlua::Result<std::cell::RefMut<'_, Registrations>> {
+    let state = state.borrow_mut();
+    if !state.loading {
+        return Err(mlua::Error::runtime("blocks, items, world generation and events can only be set up while mods load"));
+    }
+    Ok(state)
+}
@@ -5,14 +5,49 @@ struct CameraUniform {
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+struct FrameUniform {
+    camera_position: vec3<f32>,
+    fog_mode: u32,
+    fog_color: vec3<f32>,
+    fog_start: f32,
+    fog_end: f32,
+    fog_density: f32,
+    // How bright sky light is, from dim at night to 1 by day.
+    daylight: f32,
+    // How the chunk debug view tints terrain, and the time remesh ages
+    // count up to.
+    chunk_tint: u32,
+    debug_time: f32,
+    // The least light anything has, in dimensions without a sun.
+    ambient_light: f32,
+}
+
+@group(2) @binding(0)
+var<uniform> frame: FrameUniform;
+// Each chunk's coordinates and when it was last remeshed, if it has
+// been, in the slot its coordinates wrap around to.
+@group(2) @binding(1)
+var t_remesh: texture_2d<f32>;
+
+const FOG_LINEAR: u32 = 0u;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) layer: u32,
+    @location(3) shade: f32,
+    // Sky light, then red, green and blue block light, as fractions of
+    // the brightest.
+    @location(4) light: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) layer: u32,
+    @location(2) shade: f32,
+    @location(3) world_position: vec3<f32>,
+    @location(4) light: vec4<f32>,
 }
 
 @vertex
@@ -22,17 +57,192 @@ fn vs_main(
     var out: VertexOutput;
     
     out.tex_coords = model.tex_coords;
+    out.layer = model.layer;
+    out.shade = model.shade;
+    out.light = model.light;
+    out.world_position = model.position;
     out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
 
     return out;
 }
 
 @group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
+var t_diffuse: texture_2d_array<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+// Fraction of the surface color that survives the fog, in [0, 1].
+fn fog_visibility(world_position: vec3<f32>) -> f32 {
+    let distance = length(world_position - frame.camera_position);
+    if frame.fog_mode == FOG_LINEAR {
+        return clamp((frame.fog_end - distance) / (frame.fog_end - frame.fog_start), 0.0, 1.0);
+    }
+    let d = distance * frame.fog_density;
+    return exp(-d * d);
+}
+
+fn apply_fog(color: vec3<f32>, world_position: vec3<f32>) -> vec3<f32> {
+    return mix(frame.fog_color, color, fog_visibility(world_position));
+}
+
+const CASCADE_COUNT: u32 = 3u;
+// How dark fully shadowed surfaces get in full daylight.
+const SHADOW_DARKNESS: f32 = 0.55;
+
+struct ShadowUniform {
+    cascades: array<mat4x4<f32>, 3>,
+    // Distance from the camera where each cascade ends.
+    splits: vec4<f32>,
+    // World-space size of one shadow map texel in each cascade.
+    texel_sizes: vec4<f32>,
+    sun_direction: vec3<f32>,
+    // 0 when the sun is down and shadows are off.
+    strength: f32,
+}
+
+@group(3) @binding(0)
+var<uniform> shadow: ShadowUniform;
+@group(3) @binding(1)
+var t_shadow: texture_depth_2d_array;
+@group(3) @binding(2)
+var s_shadow: sampler_comparison;
+
+// The mesh has no normals, so the face normal is rebuilt from screen-space
+// derivatives and turned towards the camera, which also gives both sides
+// of a plant quad a sensible normal.
+fn face_normal(world_position: vec3<f32>) -> vec3<f32> {
+    let normal = normalize(cross(dpdx(world_position), dpdy(world_position)));
+    if dot(normal, frame.camera_position - world_position) < 0.0 {
+        return -normal;
+    }
+    return normal;
+}
+
+// Fraction of sunlight reaching the surface, in [0, 1], filtered over a
+// 3x3 texel neighbourhood.
+fn shadow_visibility(world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if shadow.strength <= 0.0 {
+        return 1.0;
+    }
+    // Faces turned away from the sun are in their own shadow.
+    if dot(normal, shadow.sun_direction) <= 0.0 {
+        return 0.0;
+    }
+
+    let distance = length(world_position - frame.camera_position);
+    var cascade = 0u;
+    while cascade < CASCADE_COUNT && distance > shadow.splits[cascade] {
+        cascade += 1u;
+    }
+    if cascade == CASCADE_COUNT {
+        return 1.0;
+    }
+
+    // Pushing the lookup out along the normal keeps flat ground from
+    // shadowing itself.
+    let offset_position = world_position + normal * shadow.texel_sizes[cascade] * 1.5;
+    let light = shadow.cascades[cascade] * vec4<f32>(offset_position, 1.0);
+    let ndc = light.xyz / light.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, cascade, ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+// How bright a light level looks, as a fraction of the brightest. Each
+// level down is dimmer than the last by more, like light fading away from
+// a torch, and even complete darkness keeps a little to see by.
+const MIN_BRIGHTNESS: f32 = 0.04;
+
+fn brightness(level: f32) -> f32 {
+    return mix(MIN_BRIGHTNESS, 1.0, level / (4.0 - 3.0 * level));
+}
+
+// Sky light dims at night and in the sun's shadow, but never below the
+// ambient light; block light doesn't, and whichever is brighter wins in
+// each color.
+fn apply_light(color: vec3<f32>, light: vec4<f32>, world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let shadowed = (1.0 - shadow_visibility(world_position, normal)) * shadow.strength;
+    let sunlit = brightness(light.x * frame.daylight) * mix(1.0, SHADOW_DARKNESS, shadowed);
+    let sky = max(sunlit, brightness(frame.ambient_light));
+    let block = vec3<f32>(brightness(light.y), brightness(light.z), brightness(light.w));
+    return color * max(vec3<f32>(sky), block);
+}
+
+const TINT_COORDINATES: u32 = 1u;
+const TINT_REMESH_AGE: u32 = 2u;
+const CHUNK_SIZE: f32 = 16.0;
+const REMESH_SLOTS: i32 = 64;
+// Grids of slots stacked down the texture, one for each chunk height in
+// turn.
+const REMESH_LAYERS: i32 = 16;
+// Seconds for a remeshed chunk's tint to go from red to blue.
+const REMESH_FADE: f32 = 10.0;
+
+// A color of its own for each chunk, so neighbours stand apart.
+fn chunk_color(chunk: vec3<i32>) -> vec3<f32> {
+    var h = bitcast<u32>(chunk.x) * 73856093u ^ bitcast<u32>(chunk.y) * 83492791u ^ bitcast<u32>(chunk.z) * 19349663u;
+    h ^= h >> 13u;
+    h *= 0x5bd1e995u;
+    h ^= h >> 15u;
+    return vec3<f32>(f32(h & 255u), f32((h >> 8u) & 255u), f32((h >> 16u) & 255u)) / 255.0;
+}
+
+fn remesh_color(chunk: vec3<i32>) -> vec3<f32> {
+    let across = ((chunk.xz % REMESH_SLOTS) + REMESH_SLOTS) % REMESH_SLOTS;
+    let layer = ((chunk.y % REMESH_LAYERS) + REMESH_LAYERS) % REMESH_LAYERS;
+    let texel = textureLoad(t_remesh, vec2<i32>(across.x, layer * REMESH_SLOTS + across.y), 0);
+    // Grey for chunks not remeshed since the game started.
+    if texel.w < 0.0 || any(vec3<i32>(texel.xyz) != chunk) {
+        return vec3<f32>(0.5);
+    }
+    let age = clamp((frame.debug_time - texel.w) / REMESH_FADE, 0.0, 1.0);
+    return mix(vec3<f32>(1.0, 0.1, 0.1), vec3<f32>(0.1, 0.3, 1.0), age);
+}
+
+fn apply_chunk_tint(color: vec3<f32>, world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    if frame.chunk_tint != TINT_COORDINATES && frame.chunk_tint != TINT_REMESH_AGE {
+        return color;
+    }
+    // Stepping back from the face finds the block it belongs to, even
+    // where it lies on a chunk's edge.
+    let inside = world_position - normal * 0.01;
+    let chunk = vec3<i32>(floor(inside / CHUNK_SIZE));
+    if frame.chunk_tint == TINT_COORDINATES {
+        return mix(color, chunk_color(chunk), 0.5);
+    }
+    return mix(color, remesh_color(chunk), 0.5);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
-}
\ No newline at end of file
+    let normal = face_normal(in.world_position);
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.layer);
+    let lit = apply_light(color.rgb * in.shade, in.light, in.world_position, normal);
+    let tinted = apply_chunk_tint(lit, in.world_position, normal);
+    return vec4<f32>(apply_fog(tinted, in.world_position), color.a);
+}
+
+// Alpha-tested variant for foliage: texels below the threshold are
+// discarded instead of blended, so no sorting is needed.
+@fragment
+fn fs_cutout(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = face_normal(in.world_position);
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.layer);
+    if color.a < 0.5 {
+        discard;
+    }
+    let lit = apply_light(color.rgb * in.shade, in.light, in.world_position, normal);
+    let tinted = apply_chunk_tint(lit, in.world_position, normal);
+    return vec4<f32>(apply_fog(tinted, in.world_position), 1.0);
+}
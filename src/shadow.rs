@@ -0,0 +1,386 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Transform, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::Camera,
+    mesher,
+    sky,
+    texture,
+};
+
+pub const CASCADE_COUNT: usize = 3;
+
+// Where each cascade ends, as a fraction of the shadow distance. The near
+// cascades are small so shadows close to the player stay sharp.
+const CASCADE_SPLITS: [f32 ; CASCADE_COUNT] = [0.12, 0.35, 1.0];
+
+// How far beyond a cascade's bounds towards the sun casters are still
+// rendered, so mountains outside the view can shade it.
+const CASTER_MARGIN: f32 = 128.0;
+
+// Remaps OpenGL's [-1, 1] clip depth to wgpu's [0, 1].
+#[rustfmt::skip]
+const GL_TO_WGPU_DEPTH: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+// Pass names have to outlive the frame graph.
+pub const CASCADE_PASS_NAMES: [&str ; CASCADE_COUNT] = ["Shadow Cascade 0", "Shadow Cascade 1", "Shadow Cascade 2"];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    cascades: [[[f32 ; 4] ; 4] ; CASCADE_COUNT],
+    splits: [f32 ; 4],
+    texel_sizes: [f32 ; 4],
+    sun_direction: [f32 ; 3],
+    strength: f32,
+}
+
+/// Cascaded shadow maps for the sun. Each frame the view frustum is cut
+/// into `CASCADE_COUNT` slices, each covered by an orthographic depth map
+/// rendered from the sun, and the terrain shader picks the slice by
+/// distance and filters it with PCF.
+pub struct ShadowRenderer {
+    pub enabled: bool,
+    size: u32,
+    cascade_views: Vec<wgpu::TextureView>,
+    cascade_buffers: Vec<wgpu::Buffer>,
+    cascade_bind_groups: Vec<wgpu::BindGroup>,
+    opaque_pipeline: wgpu::RenderPipeline,
+    cutout_pipeline: wgpu::RenderPipeline,
+
+    uniform: ShadowUniform,
+    buffer: wgpu::Buffer,
+    /// Layout of `bind_group`, group 3 of the terrain pipelines.
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ShadowRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        block_texture_layout: &wgpu::BindGroupLayout,
+        enabled: bool,
+        size: u32,
+    ) -> Self {
+        let size = size.clamp(256, device.limits().max_texture_dimension_2d);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: CASCADE_COUNT as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: texture::Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let cascade_views = (0..CASCADE_COUNT as u32)
+            .map(|layer| texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Shadow Cascade View"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            }))
+            .collect();
+
+        // Linear filtering on a comparison sampler blends the four nearest
+        // depth tests, smoothing the PCF kernel for free.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniform = ShadowUniform {
+            cascades: [Matrix4::from_scale(1.0).into() ; CASCADE_COUNT],
+            splits: [0.0 ; 4],
+            texel_sizes: [0.0 ; 4],
+            sun_direction: [0.0, 1.0, 0.0],
+            strength: 0.0,
+        };
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+            ],
+            label: Some("shadow_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&array_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("shadow_bind_group"),
+        });
+
+        let cascade_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("shadow_cascade_bind_group_layout"),
+        });
+
+        let cascade_buffers = (0..CASCADE_COUNT)
+            .map(|_| device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow Cascade Buffer"),
+                contents: bytemuck::cast_slice(&uniform.cascades[0]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }))
+            .collect::<Vec<_>>();
+        let cascade_bind_groups = cascade_buffers.iter()
+            .map(|buffer| device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &cascade_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+                label: Some("shadow_cascade_bind_group"),
+            }))
+            .collect();
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shadow.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[block_texture_layout, &cascade_layout],
+            push_constant_ranges: &[],
+        });
+        let opaque_pipeline = create_shadow_pipeline(device, &pipeline_layout, &shader, None);
+        let cutout_pipeline = create_shadow_pipeline(device, &pipeline_layout, &shader, Some("fs_cutout"));
+
+        Self {
+            enabled,
+            size,
+            cascade_views,
+            cascade_buffers,
+            cascade_bind_groups,
+            opaque_pipeline,
+            cutout_pipeline,
+            uniform,
+            buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub fn cascade_view(&self, cascade: usize) -> &wgpu::TextureView {
+        &self.cascade_views[cascade]
+    }
+
+    /// Fits the cascades to the camera's view out to `max_distance` and
+    /// points them along the sun for this time of day, fading them as far
+    /// as clouds are `overcast` over it.
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, time_of_day: f32, overcast: f32, max_distance: f32) {
+        let sun = sky::sun_direction(time_of_day);
+        // Fade shadows out as the sun sets instead of letting them stretch
+        // across the whole world, and as clouds cover it.
+        let strength = if self.enabled { smoothstep(0.0, 0.15, sun.y) * (1.0 - overcast) } else { 0.0 };
+
+        self.uniform.sun_direction = sun.into();
+        self.uniform.strength = strength;
+
+        if strength > 0.0 {
+            // The sun never points straight along Z, so that works as the
+            // light's up vector all day.
+            let light_view = Matrix4::look_to_rh(Point3::origin(), -sun, Vector3::unit_z());
+
+            let mut near = camera.near;
+            for (cascade, split) in CASCADE_SPLITS.into_iter().enumerate() {
+                let far = max_distance * split;
+                let (center, radius) = frustum_slice_bounds(camera, near, far);
+                let (view_proj, texel_size) = cascade_view_proj(light_view, center, radius, self.size);
+
+                self.uniform.cascades[cascade] = view_proj.into();
+                self.uniform.splits[cascade] = far;
+                self.uniform.texel_sizes[cascade] = texel_size;
+                queue.write_buffer(&self.cascade_buffers[cascade], 0, bytemuck::cast_slice(&self.uniform.cascades[cascade]));
+                near = far;
+            }
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    /// Whether the cascades need rendering this frame.
+    pub fn active(&self) -> bool {
+        self.uniform.strength > 0.0
+    }
+
+    /// Binds the cascade for opaque casters; follow with `bind_cutout`
+    /// before drawing alpha-tested geometry.
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass, cascade: usize, block_bind_group: &wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.opaque_pipeline);
+        render_pass.set_bind_group(0, block_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.cascade_bind_groups[cascade], &[]);
+    }
+
+    pub fn bind_cutout(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_pipeline(&self.cutout_pipeline);
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// A bounding sphere around the part of the view frustum between `near`
+// and `far`. A sphere's size doesn't change as the camera turns, so the
+// cascade's texel size stays fixed too.
+fn frustum_slice_bounds(camera: &Camera, near: f32, far: f32) -> (Point3<f32>, f32) {
+    let forward = camera.forward();
+    let right = forward.cross(camera.up).normalize();
+    let up = right.cross(forward);
+
+    let tan_y = (camera.view_fov().to_radians() / 2.0).tan();
+    let tan_x = tan_y * camera.aspect;
+
+    let mut corners = Vec::with_capacity(8);
+    for distance in [near, far] {
+        let center = camera.eye + forward * distance;
+        for (sx, sy) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            corners.push(center + right * (sx * tan_x * distance) + up * (sy * tan_y * distance));
+        }
+    }
+
+    let center = Point3::from_vec(corners.iter().map(|c| c.to_vec()).sum::<Vector3<f32>>() / 8.0);
+    let radius = corners.iter()
+        .map(|corner| (corner - center).magnitude())
+        .fold(0.0, f32::max)
+        .ceil();
+    (center, radius)
+}
+
+// An orthographic projection from the sun covering the sphere, and the
+// world-space size of one of its texels.
+fn cascade_view_proj(light_view: Matrix4<f32>, center: Point3<f32>, radius: f32, size: u32) -> (Matrix4<f32>, f32) {
+    // Snapping the center to whole texels keeps shadow edges from
+    // shimmering as the camera moves.
+    let texel_size = radius * 2.0 / size as f32;
+    let mut center = light_view.transform_point(center);
+    center.x = (center.x / texel_size).floor() * texel_size;
+    center.y = (center.y / texel_size).floor() * texel_size;
+
+    // Light space looks down -Z.
+    let projection = cgmath::ortho(
+        center.x - radius,
+        center.x + radius,
+        center.y - radius,
+        center.y + radius,
+        -center.z - radius - CASTER_MARGIN,
+        -center.z + radius,
+    );
+    (GL_TO_WGPU_DEPTH * projection * light_view, texel_size)
+}
+
+fn create_shadow_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    fragment_entry: Option<&str>,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(if fragment_entry.is_some() { "Shadow Cutout Pipeline" } else { "Shadow Pipeline" }),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[mesher::Vertex::desc()],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: fragment_entry.map(|entry_point| wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(entry_point),
+            targets: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        // Both sides cast: chunk meshes aren't closed from the sun's point
+        // of view, and plants are single quads.
+        primitive: wgpu::PrimitiveState {
+            cull_mode: None,
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState {
+                constant: 2,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,45 @@
+// Depth-only pass rendering world geometry from the sun into one shadow
+// cascade.
+
+struct CascadeUniform {
+    light_view_proj: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> cascade: CascadeUniform;
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d_array<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) layer: u32,
+    @location(3) shade: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) layer: u32,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.layer = model.layer;
+    out.clip_position = cascade.light_view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// Foliage casts shadows in the shape of its leaves rather than its quads.
+@fragment
+fn fs_cutout(in: VertexOutput) {
+    let alpha = textureSampleLevel(t_diffuse, s_diffuse, in.tex_coords, in.layer, 0.0).a;
+    if alpha < 0.5 {
+        discard;
+    }
+}
@@ -0,0 +1,168 @@
+//! Signal circuits. Levers and buttons power what they touch: wire, which
+//! carries the signal on at one less strength each block, and blocks with
+//! the Powered property, which respond to it. Those open if they have the
+//! Open property and light up if they have Lit, and pistons push.
+//!
+//! Changes are worked through on a fixed tick. Wire goes first, until the
+//! signals in it have settled, and only then does anything respond, so
+//! nothing flickers while a change is still spreading.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    block::{BlockModel, BlockState, Property},
+    piston::{self, Pushed},
+    world::World,
+};
+
+/// The strength of a signal straight from a switch, which wire carries
+/// this many blocks.
+pub const MAX_SIGNAL: u8 = 15;
+
+const TICK_INTERVAL: f32 = 0.1;
+// Don't try to catch up on more than this many ticks after a long frame.
+const MAX_TICKS_PER_UPDATE: u32 = 4;
+// Wire re-evaluated in a tick at most; a circuit bigger than this takes
+// a few ticks to settle rather than stalling one.
+const UPDATES_PER_TICK: usize = 4096;
+// Seconds a pressed button stays on.
+const BUTTON_TIME: f32 = 1.0;
+
+const NEIGHBORS: [[i32 ; 3] ; 6] = [[1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+const HORIZONTAL: [(i32, i32) ; 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Steps signal circuits at a fixed rate, and lets go of pressed buttons.
+#[derive(Default)]
+pub struct SignalSimulator {
+    accumulator: f32,
+    // Pressed buttons, and the seconds until they pop back out.
+    buttons: HashMap<[i32 ; 3], f32>,
+}
+
+impl SignalSimulator {
+    /// Runs the ticks due, returning what pistons pushed in them.
+    pub fn update(&mut self, world: &mut World, dt: f32) -> Pushed {
+        self.accumulator = (self.accumulator + dt).min(TICK_INTERVAL * MAX_TICKS_PER_UPDATE as f32);
+
+        let mut pushed = Pushed::default();
+        while self.accumulator >= TICK_INTERVAL {
+            self.accumulator -= TICK_INTERVAL;
+            self.release_buttons(world, TICK_INTERVAL);
+            pushed.extend(self.tick(world));
+        }
+        pushed
+    }
+
+    fn release_buttons(&mut self, world: &mut World, dt: f32) {
+        let mut released = Vec::new();
+        self.buttons.retain(|&block, remaining| {
+            *remaining -= dt;
+            if *remaining > 0.0 {
+                return true;
+            }
+            released.push(block);
+            false
+        });
+        for [x, y, z] in released {
+            let state = world.get_state(x, y, z);
+            if state.block.info().model == BlockModel::Button {
+                world.set_state(x, y, z, BlockState::new(state.block, state.properties.with_powered(false)));
+            }
+        }
+    }
+
+    fn tick(&mut self, world: &mut World) -> Pushed {
+        let mut queue = VecDeque::from(world.take_signal_updates());
+        let mut responders = HashSet::new();
+        let mut budget = UPDATES_PER_TICK;
+        while let Some([x, y, z]) = queue.pop_front() {
+            let state = world.get_state(x, y, z);
+            match state.block.info().model {
+                BlockModel::Wire if budget == 0 => world.schedule_signal_update(x, y, z),
+                BlockModel::Wire => {
+                    budget -= 1;
+                    let strength = wire_strength(world, [x, y, z]);
+                    if strength != world.signal_strength(x, y, z) {
+                        let state = BlockState::new(state.block, state.properties.with_lit(strength > 0));
+                        world.set_voxel(x, y, z, state, strength);
+                        // The wire's neighbours go round again this tick.
+                        queue.extend(world.take_signal_updates());
+                    }
+                },
+                BlockModel::Button if state.properties.powered() => {
+                    self.buttons.entry([x, y, z]).or_insert(BUTTON_TIME);
+                },
+                BlockModel::Lever | BlockModel::Button => (),
+                _ if state.block.info().properties.contains(&Property::Powered) => {
+                    responders.insert([x, y, z]);
+                },
+                _ => (),
+            }
+        }
+
+        let mut pushed = Pushed::default();
+        for block in responders {
+            pushed.extend(respond(world, block));
+        }
+        pushed
+    }
+}
+
+fn is_switched_on(state: BlockState) -> bool {
+    state.block.is_switch() && state.properties.powered()
+}
+
+// Full strength next to a switch that's on, or one less than the
+// strongest wire it joins, beside it or a block up or down.
+fn wire_strength(world: &World, [x, y, z]: [i32 ; 3]) -> u8 {
+    let switched_on = NEIGHBORS.iter().any(|[dx, dy, dz]| is_switched_on(world.get_state(x + dx, y + dy, z + dz)));
+    if switched_on {
+        return MAX_SIGNAL;
+    }
+    let mut strength = 0;
+    for (dx, dz) in HORIZONTAL {
+        for dy in -1..=1 {
+            strength = strength.max(world.signal_strength(x + dx, y + dy, z + dz).saturating_sub(1));
+        }
+    }
+    strength
+}
+
+// Whether a switch that's on or wire carrying a signal touches `block`.
+fn is_powered(world: &World, [x, y, z]: [i32 ; 3]) -> bool {
+    NEIGHBORS.iter().any(|[dx, dy, dz]| {
+        let (x, y, z) = (x + dx, y + dy, z + dz);
+        is_switched_on(world.get_state(x, y, z)) || world.signal_strength(x, y, z) > 0
+    })
+}
+
+// Opens or lights up a block that's just started receiving a signal, and
+// closes or darkens it once the signal stops. Only a change does it, so a
+// door opened by hand stays open until a signal comes and goes. A door's
+// two halves go together, powered from either. Pistons push or pull back.
+fn respond(world: &mut World, block: [i32 ; 3]) -> Pushed {
+    let [x, y, z] = block;
+    let state = world.get_state(x, y, z);
+    if state.block.info().model == BlockModel::Piston {
+        let powered = is_powered(world, block);
+        return piston::update(world, block, powered);
+    }
+    let other_half = state.other_part(block).filter(|&[x, y, z]| world.get_block(x, y, z) == state.block);
+    let powered = is_powered(world, block) || other_half.is_some_and(|other| is_powered(world, other));
+    if powered == state.properties.powered() {
+        return Pushed::default();
+    }
+
+    let has = |property| state.block.info().properties.contains(&property);
+    for [x, y, z] in std::iter::once(block).chain(other_half) {
+        let mut properties = world.get_state(x, y, z).properties.with_powered(powered);
+        if has(Property::Open) {
+            properties = properties.with_open(powered);
+        }
+        if has(Property::Lit) {
+            properties = properties.with_lit(powered);
+        }
+        world.set_state(x, y, z, BlockState::new(state.block, properties));
+    }
+    Pushed::default()
+}
@@ -0,0 +1,183 @@
+use cgmath::{InnerSpace, SquareMatrix};
+use wgpu::util::DeviceExt;
+
+use crate::{camera::Camera, fog};
+
+/// The overworld's sky straight up on a clear day.
+pub const ZENITH_COLOR: [f32 ; 3] = [0.28, 0.48, 0.85];
+// How bright the sky still is in the middle of the night, as a fraction of
+// full daylight.
+const NIGHT_BRIGHTNESS: f32 = 0.2;
+
+// The sun rises in the east (+X) at time 0 and arcs overhead, tilted a
+// little towards -Z so it isn't exactly at the zenith at noon.
+pub fn sun_direction(time_of_day: f32) -> cgmath::Vector3<f32> {
+    let angle = time_of_day * std::f32::consts::TAU;
+    cgmath::Vector3::new(angle.cos(), angle.sin(), -0.3).normalize()
+}
+
+/// How bright the sky is, as a fraction of full daylight. It's fully
+/// bright once the sun is a little way up, and at its darkest soon after
+/// it sets.
+pub fn daylight(time_of_day: f32) -> f32 {
+    (sun_direction(time_of_day).y * 3.0 + 0.4).clamp(NIGHT_BRIGHTNESS, 1.0)
+}
+
+/// How a dimension's sky and fog look, and how light it is under them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkySettings {
+    pub zenith_color: [f32 ; 3],
+    /// What the sky fades to at the horizon, and the terrain into the fog.
+    pub horizon_color: [f32 ; 3],
+    /// Whether the sun crosses the sky; without it the sky stays as bright
+    /// as it is, whatever the time.
+    pub sun: bool,
+    /// The light level, as a fraction of the brightest, that everything
+    /// has at least, however dark it is.
+    pub ambient_light: f32,
+    /// Where the fog starts in clear weather, as a fraction of the way to
+    /// where it's thickest.
+    pub fog_start: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+    inv_view_proj: [[f32 ; 4] ; 4],
+    camera_position: [f32 ; 4],
+    sun_direction: [f32 ; 4],
+    zenith_color: [f32 ; 4],
+    horizon_color: [f32 ; 4],
+}
+
+/// Procedural gradient sky with a sun disc, drawn as a fullscreen triangle
+/// in its own pass before the terrain.
+pub struct SkyRenderer {
+    pipeline: wgpu::RenderPipeline,
+    uniform: SkyUniform,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl SkyRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("sky.wgsl"));
+
+        let [hr, hg, hb] = fog::FOG_COLOR;
+        let [zr, zg, zb] = ZENITH_COLOR;
+        let uniform = SkyUniform {
+            inv_view_proj: cgmath::Matrix4::identity().into(),
+            camera_position: [0.0; 4],
+            sun_direction: sun_direction(0.0).extend(1.0).into(),
+            zenith_color: [zr, zg, zb, 1.0],
+            horizon_color: [hr, hg, hb, 1.0],
+        };
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("sky_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("sky_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            uniform,
+            buffer,
+            bind_group,
+        }
+    }
+
+    /// Follows the camera and the sun, in `settings`' colors, with the sky
+    /// lit to `daylight` and clouded over as far as rain has set in, by
+    /// `wetness`.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        camera: &Camera,
+        settings: &SkySettings,
+        time_of_day: f32,
+        daylight: f32,
+        wetness: f32,
+    ) {
+        let view_proj = camera.build_view_proj_matrix();
+        // A degenerate projection (e.g. a zero-sized window) has no inverse;
+        // keep last frame's sky rather than drawing garbage.
+        if let Some(inv_view_proj) = view_proj.invert() {
+            self.uniform.inv_view_proj = inv_view_proj.into();
+        }
+        self.uniform.camera_position = camera.eye.to_homogeneous().into();
+        // Clouds hide the sun.
+        let sun = if settings.sun { 1.0 - wetness } else { 0.0 };
+        self.uniform.sun_direction = sun_direction(time_of_day).extend(sun).into();
+        let [hr, hg, hb] = fog::rain_tinted(settings.horizon_color, wetness).map(|channel| channel * daylight);
+        let [zr, zg, zb] = fog::rain_tinted(settings.zenith_color, wetness).map(|channel| channel * daylight);
+        self.uniform.horizon_color = [hr, hg, hb, 1.0];
+        self.uniform.zenith_color = [zr, zg, zb, 1.0];
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
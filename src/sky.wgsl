@@ -0,0 +1,46 @@
+struct SkyUniform {
+    inv_view_proj: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    // How visible the sun is through the clouds in w.
+    sun_direction: vec4<f32>,
+    zenith_color: vec4<f32>,
+    horizon_color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> sky: SkyUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// A single triangle that covers the whole screen.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let far = sky.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = normalize(far.xyz / far.w - sky.camera_position.xyz);
+
+    // Blend from the horizon (matching the fog) up to the zenith, and keep
+    // the horizon color below it so the ground fades into the same tone.
+    let height = clamp(direction.y, 0.0, 1.0);
+    var color = mix(sky.horizon_color.rgb, sky.zenith_color.rgb, pow(height, 0.5));
+
+    let sun_dot = dot(direction, sky.sun_direction.xyz);
+    let glow = pow(max(sun_dot, 0.0), 64.0) * 0.4;
+    let disc = smoothstep(0.9990, 0.9995, sun_dot);
+    color = color + (vec3<f32>(1.0, 0.9, 0.7) * glow + vec3<f32>(1.0, 0.98, 0.9) * disc) * sky.sun_direction.w;
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,100 @@
+//! Beds. Using one makes it where the player respawns, and at night gets
+//! them into it. Once everyone is in bed the night is skipped: the screen
+//! fades out, the clock jumps to morning and it fades back in. Alone that
+//! happens straight away; on a server whoever is in bed waits for the
+//! rest, and can get up again until they're all in.
+
+use crate::{block::BED_HEIGHT, chunk::ChunkPos, world::World};
+
+/// Seconds the screen takes to fade out, and again to fade back in.
+pub const FADE_TIME: f32 = 1.5;
+// How dark the screen goes while waiting in bed for other players.
+const WAITING_DARKNESS: f32 = 0.4;
+
+/// Where a player whose bed is at `bed` respawns, on top of it, or None
+/// if it's been broken. A bed in a chunk that isn't loaded is taken to
+/// still be there.
+pub fn bed_spawn(world: &World, bed: [i32 ; 3]) -> Option<[f32 ; 3]> {
+    let [x, y, z] = bed;
+    let loaded = world.chunks.contains_key(&ChunkPos::from_block(x, y, z));
+    if loaded && !world.get_block(x, y, z).is_bed() {
+        return None;
+    }
+    Some([x as f32 + 0.5, y as f32 + BED_HEIGHT, z as f32 + 0.5])
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Phase {
+    #[default]
+    Awake,
+    // Lying down, waiting for the rest of the players.
+    InBed,
+    // Everyone is in bed and the screen is fading out.
+    FallingAsleep,
+    // Morning, or out of bed early; the screen is fading back in.
+    Waking,
+}
+
+/// The local player's side of sleeping, and how dark it makes the screen.
+#[derive(Debug, Default)]
+pub struct Sleep {
+    phase: Phase,
+    darkness: f32,
+}
+
+impl Sleep {
+    /// Whether the player is lying in bed, and so held still.
+    pub fn in_bed(&self) -> bool {
+        matches!(self.phase, Phase::InBed | Phase::FallingAsleep)
+    }
+
+    pub fn get_in(&mut self) {
+        self.phase = Phase::InBed;
+    }
+
+    /// Gets out of bed, unless the night is already being skipped.
+    /// Returns whether the player got up.
+    pub fn get_up(&mut self) -> bool {
+        if self.phase != Phase::InBed {
+            return false;
+        }
+        self.phase = Phase::Waking;
+        true
+    }
+
+    /// Starts skipping the night, if the player is in bed for it.
+    pub fn fall_asleep(&mut self) {
+        if self.phase == Phase::InBed {
+            self.phase = Phase::FallingAsleep;
+        }
+    }
+
+    /// Moves the fade on by `dt` seconds. Morning, however it comes, gets
+    /// the player up. Returns whether the screen has gone fully dark with
+    /// the night still to skip, which is when to skip it.
+    pub fn update(&mut self, dt: f32, night: bool) -> bool {
+        if !night && self.in_bed() {
+            self.phase = Phase::Waking;
+        }
+        let target = match self.phase {
+            Phase::Awake | Phase::Waking => 0.0,
+            Phase::InBed => WAITING_DARKNESS,
+            Phase::FallingAsleep => 1.0,
+        };
+        let step = dt / FADE_TIME;
+        self.darkness = if self.darkness < target {
+            (self.darkness + step).min(target)
+        } else {
+            (self.darkness - step).max(target)
+        };
+        if self.phase == Phase::Waking && self.darkness == 0.0 {
+            self.phase = Phase::Awake;
+        }
+        self.phase == Phase::FallingAsleep && self.darkness == 1.0
+    }
+
+    /// How much of the screen to black out, from 0 to 1.
+    pub fn darkness(&self) -> f32 {
+        self.darkness
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::{EngineError, Result},
+    texture,
+    ui::{Rect, UiBatch, UiRenderer},
+};
+
+const FONT_BYTES: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+
+// Glyphs are rasterized once at this size and scaled for other sizes.
+const BASE_SIZE: f32 = 32.0;
+const ATLAS_SIZE: u32 = 512;
+const GLYPH_PADDING: u32 = 1;
+
+struct Glyph {
+    uv_min: [f32 ; 2],
+    uv_max: [f32 ; 2],
+    width: f32,
+    height: f32,
+    xmin: f32,
+    ymin: f32,
+    advance: f32,
+}
+
+pub struct TextSection {
+    pub text: String,
+    pub position: [f32 ; 2],
+    pub size: f32,
+    pub color: [f32 ; 4],
+}
+
+pub struct TextRenderer {
+    glyphs: HashMap<char, Glyph>,
+    ascent: f32,
+    line_height: f32,
+
+    // Only read through the bind group, but kept so the GPU texture lives
+    // as long as the renderer.
+    _atlas: texture::Texture,
+    atlas_bind_group: wgpu::BindGroup,
+
+    sections: Vec<TextSection>,
+    batch: UiBatch,
+    renderer: UiRenderer,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<Self> {
+        let font = fontdue::Font::from_bytes(FONT_BYTES, fontdue::FontSettings::default())
+            .map_err(|e| EngineError::Font(e.to_string()))?;
+        let line_metrics = font.horizontal_line_metrics(BASE_SIZE)
+            .ok_or_else(|| EngineError::Font("font has no horizontal line metrics".to_string()))?;
+
+        let mut atlas = image::RgbaImage::new(ATLAS_SIZE, ATLAS_SIZE);
+        let mut glyphs = HashMap::new();
+        let (mut pen_x, mut pen_y, mut row_height) = (GLYPH_PADDING, GLYPH_PADDING, 0);
+
+        for c in (' '..='~').chain(['°']) {
+            let (metrics, coverage) = font.rasterize(c, BASE_SIZE);
+            let (w, h) = (metrics.width as u32, metrics.height as u32);
+
+            if pen_x + w + GLYPH_PADDING > ATLAS_SIZE {
+                pen_x = GLYPH_PADDING;
+                pen_y += row_height + GLYPH_PADDING;
+                row_height = 0;
+            }
+            if pen_y + h + GLYPH_PADDING > ATLAS_SIZE {
+                return Err(EngineError::Font("glyph atlas is full".to_string()));
+            }
+
+            // Glyphs are stored as white with the coverage in alpha so the
+            // UI shader can tint them with the vertex color.
+            for (i, alpha) in coverage.iter().enumerate() {
+                let (x, y) = (i as u32 % w, i as u32 / w);
+                atlas.put_pixel(pen_x + x, pen_y + y, image::Rgba([255, 255, 255, *alpha]));
+            }
+
+            glyphs.insert(c, Glyph {
+                uv_min: [pen_x as f32 / ATLAS_SIZE as f32, pen_y as f32 / ATLAS_SIZE as f32],
+                uv_max: [(pen_x + w) as f32 / ATLAS_SIZE as f32, (pen_y + h) as f32 / ATLAS_SIZE as f32],
+                width: w as f32,
+                height: h as f32,
+                xmin: metrics.xmin as f32,
+                ymin: metrics.ymin as f32,
+                advance: metrics.advance_width,
+            });
+
+            pen_x += w + GLYPH_PADDING;
+            row_height = row_height.max(h);
+        }
+
+        let mut atlas = texture::Texture::from_image(
+            device,
+            queue,
+            &image::DynamicImage::ImageRgba8(atlas),
+            Some("glyph_atlas"),
+        )?;
+        atlas.sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }
+        );
+
+        let atlas_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout: texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&atlas.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&atlas.sampler),
+                    }
+                ],
+                label: Some("glyph_atlas_bind_group"),
+            }
+        );
+
+        Ok(Self {
+            glyphs,
+            ascent: line_metrics.ascent,
+            line_height: line_metrics.new_line_size,
+
+            _atlas: atlas,
+            atlas_bind_group,
+
+            sections: Vec::new(),
+            batch: UiBatch::default(),
+            renderer: UiRenderer::new(device, format, texture_bind_group_layout),
+        })
+    }
+
+    pub fn queue(&mut self, text: impl Into<String>, position: [f32 ; 2], size: f32, color: [f32 ; 4]) {
+        self.sections.push(TextSection {
+            text: text.into(),
+            position,
+            size,
+            color,
+        });
+    }
+
+    pub fn line_height(&self, size: f32) -> f32 {
+        self.line_height * size / BASE_SIZE
+    }
+
+    pub fn measure(&self, text: &str, size: f32) -> (f32, f32) {
+        let scale = size / BASE_SIZE;
+        let mut width: f32 = 0.0;
+        let mut lines = 0;
+
+        for line in text.split('\n') {
+            let line_width = line.chars()
+                .filter_map(|c| self.glyphs.get(&c))
+                .map(|glyph| glyph.advance)
+                .sum::<f32>();
+            width = width.max(line_width * scale);
+            lines += 1;
+        }
+
+        (width, lines as f32 * self.line_height(size))
+    }
+
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, screen_size: (u32, u32)) {
+        self.batch.clear();
+
+        for section in self.sections.drain(..) {
+            let scale = section.size / BASE_SIZE;
+            let mut pen_x = section.position[0];
+            let mut baseline = section.position[1] + self.ascent * scale;
+
+            for c in section.text.chars() {
+                if c == '\n' {
+                    pen_x = section.position[0];
+                    baseline += self.line_height * scale;
+                    continue;
+                }
+
+                let Some(glyph) = self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?')) else {
+                    continue;
+                };
+
+                if glyph.width > 0.0 && glyph.height > 0.0 {
+                    let rect = Rect::new(
+                        pen_x + glyph.xmin * scale,
+                        baseline - (glyph.ymin + glyph.height) * scale,
+                        glyph.width * scale,
+                        glyph.height * scale,
+                    );
+                    self.batch.textured_quad(rect, glyph.uv_min, glyph.uv_max, section.color);
+                }
+
+                pen_x += glyph.advance * scale;
+            }
+        }
+
+        self.renderer.prepare(device, queue, &self.batch, screen_size);
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
+        self.renderer.render(render_pass, &self.atlas_bind_group);
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(unused)]
 
 use image::GenericImageView;
-use anyhow::*;
+use crate::error::{EngineError, Result};
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -16,7 +16,8 @@ impl Texture {
         bytes: &[u8],
         label: &str,
     ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
+        let img = image::load_from_memory(bytes)
+            .map_err(|source| EngineError::Texture { label: label.to_string(), source })?;
         Self::from_image(device, queue, &img, Some(label))
     }
 
@@ -37,7 +38,7 @@ impl Texture {
         let texture = device.create_texture(
             &wgpu::TextureDescriptor {
                 label,
-                size: size,
+                size,
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
@@ -69,7 +70,98 @@ impl Texture {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
                 address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Linear,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        Ok(Self { texture, view, sampler })
+    }
+
+    pub fn from_atlas_tiles(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tiles: &[&[u8]],
+        columns: u32,
+        label: &str,
+    ) -> Result<Self> {
+        let atlas = atlas_image(tiles, columns, label)?;
+        Self::from_image(device, queue, &image::DynamicImage::ImageRgba8(atlas), Some(label))
+    }
+
+    /// Replaces the contents of an atlas made by `from_atlas_tiles`. The
+    /// new tiles have to come out the same size as the old ones.
+    pub fn reload_atlas_tiles(&self, queue: &wgpu::Queue, tiles: &[&[u8]], columns: u32, label: &str) -> Result<()> {
+        let atlas = atlas_image(tiles, columns, label)?;
+        let size = self.texture.size();
+        if atlas.dimensions() != (size.width, size.height) {
+            return Err(EngineError::InvalidTexture {
+                label: label.to_string(),
+                reason: "atlas size changed".to_string(),
+            });
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &atlas,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+        Ok(())
+    }
+
+    // Builds a 2D array texture with one layer per image and a full mip
+    // chain. Mips are downsampled on the CPU before upload.
+    pub fn from_layers(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layers: &[&[u8]],
+        label: &str,
+    ) -> Result<Self> {
+        let (images, width, height) = decode_layers(layers, label)?;
+
+        let mip_level_count = width.max(height).ilog2() + 1;
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: images.len() as u32,
+        };
+        let texture = device.create_texture(
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }
+        );
+
+        write_layer_mips(queue, &texture, &images, 0, mip_level_count);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
                 min_filter: wgpu::FilterMode::Nearest,
                 mipmap_filter: wgpu::FilterMode::Nearest,
                 ..Default::default()
@@ -78,4 +170,183 @@ impl Texture {
 
         Ok(Self { texture, view, sampler })
     }
-}
\ No newline at end of file
+
+    /// Replaces the contents of a texture made by `from_layers`, mips and
+    /// all. There have to be as many layers as before, at the same size.
+    pub fn reload_layers(&self, queue: &wgpu::Queue, layers: &[&[u8]], label: &str) -> Result<()> {
+        let (images, width, height) = decode_layers(layers, label)?;
+        let size = self.texture.size();
+        if (width, height, images.len() as u32) != (size.width, size.height, size.depth_or_array_layers) {
+            return Err(EngineError::InvalidTexture {
+                label: label.to_string(),
+                reason: "layer size or count changed".to_string(),
+            });
+        }
+
+        write_layer_mips(queue, &self.texture, &images, 0, self.texture.mip_level_count());
+        Ok(())
+    }
+
+    /// Replaces one layer of a texture made by `from_layers`, mips and
+    /// all, with an image the same size as the others.
+    pub fn write_layer(&self, queue: &wgpu::Queue, layer: u32, image: &image::RgbaImage) {
+        write_layer_mips(queue, &self.texture, std::slice::from_ref(image), layer, self.texture.mip_level_count());
+    }
+
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            }
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                lod_min_clamp: 0.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            }
+        );
+
+        Self { texture, view, sampler }
+    }
+}
+
+/// Splits an image that's a strip of square frames, top to bottom, into
+/// its frames. Any other image is a single frame.
+pub fn split_frames(image: &image::RgbaImage) -> Vec<image::RgbaImage> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height <= width || height % width != 0 {
+        return vec![image.clone()];
+    }
+    (0..height / width)
+        .map(|frame| image::imageops::crop_imm(image, 0, frame * width, width, width).to_image())
+        .collect()
+}
+
+// Decodes each image, keeping only the first frame of animated ones.
+fn decode_first_frames(layers: &[&[u8]], label: &str) -> Result<Vec<image::RgbaImage>> {
+    layers.iter()
+        .map(|bytes| image::load_from_memory(bytes).map(|img| split_frames(&img.to_rgba8()).swap_remove(0)))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|source| EngineError::Texture { label: label.to_string(), source })
+}
+
+// Decodes each layer and checks they're all the same size, which is
+// returned along with them.
+fn decode_layers(layers: &[&[u8]], label: &str) -> Result<(Vec<image::RgbaImage>, u32, u32)> {
+    let images = decode_first_frames(layers, label)?;
+
+    let (width, height) = images.first()
+        .map(|img| img.dimensions())
+        .ok_or_else(|| EngineError::InvalidTexture {
+            label: label.to_string(),
+            reason: "texture array has no layers".to_string(),
+        })?;
+    if let Some(i) = images.iter().position(|img| img.dimensions() != (width, height)) {
+        return Err(EngineError::InvalidTexture {
+            label: label.to_string(),
+            reason: format!("layer {i} has mismatched size"),
+        });
+    }
+    Ok((images, width, height))
+}
+
+// Uploads every image, to the layers from `first_layer` on, along with
+// `mip_level_count` levels downsampled from it.
+fn write_layer_mips(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    images: &[image::RgbaImage],
+    first_layer: u32,
+    mip_level_count: u32,
+) {
+    for (layer, img) in (first_layer..).zip(images) {
+        let (width, height) = img.dimensions();
+        let mut level_image = img.clone();
+
+        for mip_level in 0..mip_level_count {
+            if mip_level > 0 {
+                level_image = image::imageops::resize(
+                    &level_image,
+                    (width >> mip_level).max(1),
+                    (height >> mip_level).max(1),
+                    image::imageops::FilterType::Triangle,
+                );
+            }
+            let (level_w, level_h) = level_image.dimensions();
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: layer },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &level_image,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * level_w),
+                    rows_per_image: Some(level_h),
+                },
+                wgpu::Extent3d { width: level_w, height: level_h, depth_or_array_layers: 1 },
+            );
+        }
+    }
+}
+
+// Lays the tiles out left to right, `columns` to a row, with animated
+// ones standing still on their first frame.
+fn atlas_image(tiles: &[&[u8]], columns: u32, label: &str) -> Result<image::RgbaImage> {
+    let images = decode_first_frames(tiles, label)?;
+
+    let (tile_w, tile_h) = images.first()
+        .map(|img| img.dimensions())
+        .ok_or_else(|| EngineError::InvalidTexture {
+            label: label.to_string(),
+            reason: "atlas has no tiles".to_string(),
+        })?;
+    let rows = (images.len() as u32).div_ceil(columns);
+
+    let mut atlas = image::RgbaImage::new(tile_w * columns, tile_h * rows);
+    for (i, img) in images.iter().enumerate() {
+        if img.dimensions() != (tile_w, tile_h) {
+            return Err(EngineError::InvalidTexture {
+                label: label.to_string(),
+                reason: format!("atlas tile {i} has mismatched size"),
+            });
+        }
+
+        let x = (i as u32 % columns) * tile_w;
+        let y = (i as u32 / columns) * tile_h;
+        image::imageops::replace(&mut atlas, img, x as i64, y as i64);
+    }
+    Ok(atlas)
+}
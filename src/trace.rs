@@ -0,0 +1,248 @@
+//! Frame spans for deep performance work. Chunk generation, meshing,
+//! uploads and each frame's update and render run inside `tracing` spans.
+//! Normally nothing listens; with `--trace <file>` every span and event
+//! is written to a Chrome trace, which `chrome://tracing` and Perfetto
+//! open as a timeline per thread. Chunks that fail to load or save are
+//! reported as `tracing` events, so they show up on that timeline too, and
+//! they still reach the log whether or not a trace is being recorded.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Write as _},
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use log::{info, warn};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+use crate::error::{EngineError, Result};
+
+// Only the game's own spans are traced, not its libraries'.
+const TARGET: &str = "voxel_game";
+
+// The trace file, shared by the subscriber and the guard that closes it.
+struct Output {
+    writer: BufWriter<File>,
+    // Whether an event has been written, so the next needs a comma.
+    written: bool,
+    // Set once the file is finished; anything traced after is dropped.
+    closed: bool,
+}
+
+impl Output {
+    fn write(&mut self, record: &str) {
+        if self.closed {
+            return;
+        }
+        let separator = if self.written { ",\n" } else { "\n" };
+        self.written = true;
+        // A failed write loses the trace, not the game.
+        let _ = write!(self.writer, "{separator}{record}");
+    }
+}
+
+// A span that's open, by its id.
+struct SpanInfo {
+    name: &'static str,
+    args: String,
+    // Handles to it still alive; it's forgotten when the last one goes.
+    references: usize,
+}
+
+struct ChromeTrace {
+    output: Arc<Mutex<Output>>,
+    started: Instant,
+    spans: Mutex<HashMap<u64, SpanInfo>>,
+    next_id: AtomicU64,
+}
+
+impl ChromeTrace {
+    fn timestamp(&self) -> f64 {
+        self.started.elapsed().as_secs_f64() * 1_000_000.0
+    }
+
+    fn write_event(&self, name: &str, phase: char, args: &str) {
+        let record = format!(
+            r#"{{"name":"{}","cat":"{TARGET}","ph":"{phase}","ts":{:.3},"pid":1,"tid":{},"args":{{{args}}}}}"#,
+            escape(name),
+            self.timestamp(),
+            thread_id(),
+        );
+        self.output.lock().unwrap().write(&record);
+    }
+
+    fn span_event(&self, id: &span::Id, phase: char) {
+        let spans = self.spans.lock().unwrap();
+        if let Some(span) = spans.get(&id.into_u64()) {
+            self.write_event(span.name, phase, &span.args);
+        }
+    }
+}
+
+impl Subscriber for ChromeTrace {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target().starts_with(TARGET)
+    }
+
+    fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+        let mut args = Args::default();
+        attributes.record(&mut args);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.spans.lock().unwrap().insert(id, SpanInfo {
+            name: attributes.metadata().name(),
+            args: args.0,
+            references: 1,
+        });
+        span::Id::from_u64(id)
+    }
+
+    fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&id.into_u64()) {
+            let mut args = Args(std::mem::take(&mut span.args));
+            values.record(&mut args);
+            span.args = args.0;
+        }
+    }
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut args = Args::default();
+        event.record(&mut args);
+        self.write_event(event.metadata().name(), 'i', &args.0);
+    }
+
+    fn enter(&self, id: &span::Id) {
+        self.span_event(id, 'B');
+    }
+
+    fn exit(&self, id: &span::Id) {
+        self.span_event(id, 'E');
+    }
+
+    fn clone_span(&self, id: &span::Id) -> span::Id {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&id.into_u64()) {
+            span.references += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: span::Id) -> bool {
+        let mut spans = self.spans.lock().unwrap();
+        let Some(span) = spans.get_mut(&id.into_u64()) else {
+            return false;
+        };
+        span.references -= 1;
+        if span.references > 0 {
+            return false;
+        }
+        spans.remove(&id.into_u64());
+        true
+    }
+}
+
+// A span's or event's fields as the body of a JSON object.
+#[derive(Default)]
+struct Args(String);
+
+impl Args {
+    fn push(&mut self, field: &Field, value: fmt::Arguments) {
+        if !self.0.is_empty() {
+            self.0.push(',');
+        }
+        let _ = write!(self.0, r#""{}":{value}"#, escape(field.name()));
+    }
+}
+
+impl Visit for Args {
+    // Numbers stay numbers, so viewers can sort and sum them.
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, format_args!("{value}"));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, format_args!("{value}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, format_args!(r#""{}""#, escape(value)));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format_args!(r#""{}""#, escape(&format!("{value:?}"))));
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            },
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// A small number for the calling thread, which is what trace viewers
+// group spans by.
+fn thread_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static ID: u64 = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    ID.with(|id| *id)
+}
+
+/// Keeps the trace file open; dropping it finishes the file, after which
+/// anything still traced is lost.
+pub struct TraceGuard {
+    output: Arc<Mutex<Output>>,
+    path: PathBuf,
+}
+
+impl Drop for TraceGuard {
+    fn drop(&mut self) {
+        let mut output = self.output.lock().unwrap();
+        output.closed = true;
+        let _ = write!(output.writer, "\n]\n");
+        match output.writer.flush() {
+            Ok(()) => info!("wrote a trace to {}", self.path.display()),
+            Err(e) => warn!("could not write the trace to {}: {e}", self.path.display()),
+        }
+    }
+}
+
+/// Starts writing every span and event to a Chrome trace at `path`, until
+/// the guard is dropped.
+pub fn init(path: &Path) -> Result<TraceGuard> {
+    let io_error = |source| EngineError::Io { path: path.to_path_buf(), source };
+    let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+    writer.write_all(b"[").map_err(io_error)?;
+
+    let output = Arc::new(Mutex::new(Output { writer, written: false, closed: false }));
+    let subscriber = ChromeTrace {
+        output: output.clone(),
+        started: Instant::now(),
+        spans: Mutex::new(HashMap::new()),
+        next_id: AtomicU64::new(1),
+    };
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        warn!("tracing is already set up elsewhere, so nothing will be written to {}", path.display());
+    }
+    Ok(TraceGuard { output, path: path.to_path_buf() })
+}
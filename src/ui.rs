@@ -0,0 +1,1053 @@
+use cgmath::{Matrix4, Point3, Vector3};
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::{
+    block::{self, BlockId, Face},
+    block_entity::BlockEntity,
+    crafting::{self, CraftingGrid},
+    furnace,
+    health::MAX_HEALTH,
+    hunger::MAX_FOOD,
+    input::Binding,
+    inventory::{ClickButton, Inventory, ItemStack, HOTBAR_SLOTS, INVENTORY_SLOTS, MAIN_SLOTS, MAX_STACK},
+    text::TextRenderer,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UiVertex {
+    pub position: [f32 ; 2],
+    pub tex_coords: [f32 ; 2],
+    pub color: [f32 ; 4],
+    pub textured: f32,
+}
+
+impl UiVertex {
+    const ATTRIBS: [wgpu::VertexAttribute ; 4] = wgpu::vertex_attr_array![
+        0 => Float32x2, 1 => Float32x2, 2 => Float32x4, 3 => Float32
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<UiVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    pub fn inset(&self, amount: f32) -> Self {
+        Self::new(self.x + amount, self.y + amount, self.w - amount * 2.0, self.h - amount * 2.0)
+    }
+
+    pub fn contains(&self, [x, y]: [f32 ; 2]) -> bool {
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+}
+
+#[derive(Default)]
+pub struct UiBatch {
+    vertices: Vec<UiVertex>,
+    indices: Vec<u32>,
+}
+
+impl UiBatch {
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    fn push_quad(&mut self, rect: Rect, uv_min: [f32 ; 2], uv_max: [f32 ; 2], color: [f32 ; 4], textured: f32) {
+        let base = self.vertices.len() as u32;
+        let corners = [
+            ([rect.x, rect.y], [uv_min[0], uv_min[1]]),
+            ([rect.x, rect.y + rect.h], [uv_min[0], uv_max[1]]),
+            ([rect.x + rect.w, rect.y + rect.h], [uv_max[0], uv_max[1]]),
+            ([rect.x + rect.w, rect.y], [uv_max[0], uv_min[1]]),
+        ];
+        for (position, tex_coords) in corners {
+            self.vertices.push(UiVertex { position, tex_coords, color, textured });
+        }
+        self.indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+    }
+
+    pub fn quad(&mut self, rect: Rect, color: [f32 ; 4]) {
+        self.push_quad(rect, [0.0, 0.0], [0.0, 0.0], color, 0.0);
+    }
+
+    pub fn textured_quad(&mut self, rect: Rect, uv_min: [f32 ; 2], uv_max: [f32 ; 2], color: [f32 ; 4]) {
+        self.push_quad(rect, uv_min, uv_max, color, 1.0);
+    }
+
+    pub fn outline(&mut self, rect: Rect, thickness: f32, color: [f32 ; 4]) {
+        let t = thickness;
+        self.quad(Rect::new(rect.x, rect.y, rect.w, t), color);
+        self.quad(Rect::new(rect.x, rect.y + rect.h - t, rect.w, t), color);
+        self.quad(Rect::new(rect.x, rect.y + t, t, rect.h - t * 2.0), color);
+        self.quad(Rect::new(rect.x + rect.w - t, rect.y + t, t, rect.h - t * 2.0), color);
+    }
+}
+
+pub struct UiRenderer {
+    pipeline: wgpu::RenderPipeline,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+}
+
+impl UiRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("ui.wgsl"));
+
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("UI Screen Buffer"),
+            size: std::mem::size_of::<[f32 ; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let screen_bind_group_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }
+                ],
+                label: Some("ui_screen_bind_group_layout"),
+            }
+        );
+
+        let screen_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout: &screen_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: screen_buffer.as_entire_binding(),
+                    }
+                ],
+                label: Some("ui_screen_bind_group"),
+            }
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("UI Pipeline Layout"),
+            bind_group_layouts: &[
+                texture_bind_group_layout,
+                &screen_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("UI Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[UiVertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let (vertex_buffer, index_buffer) = Self::create_buffers(device, 256);
+
+        Self {
+            pipeline,
+            screen_buffer,
+            screen_bind_group,
+            vertex_buffer,
+            index_buffer,
+            num_indices: 0,
+        }
+    }
+
+    fn create_buffers(device: &wgpu::Device, max_quads: usize) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("UI Vertex Buffer"),
+            size: (max_quads * 4 * std::mem::size_of::<UiVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("UI Index Buffer"),
+            size: (max_quads * 6 * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        (vertex_buffer, index_buffer)
+    }
+
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        batch: &UiBatch,
+        screen_size: (u32, u32),
+    ) {
+        let vertex_bytes = std::mem::size_of_val(batch.vertices.as_slice()) as wgpu::BufferAddress;
+        if vertex_bytes > self.vertex_buffer.size() {
+            let max_quads = (batch.vertices.len() / 4).next_power_of_two();
+            (self.vertex_buffer, self.index_buffer) = Self::create_buffers(device, max_quads);
+        }
+
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[screen_size.0 as f32, screen_size.1 as f32, 0.0, 0.0]),
+        );
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&batch.vertices));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&batch.indices));
+        self.num_indices = batch.indices.len() as u32;
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, texture_bind_group: &wgpu::BindGroup) {
+        if self.num_indices == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, texture_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.screen_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+}
+
+/// A part of the HUD that can be turned off in the settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HudElement {
+    /// The map of the terrain around the player, in the top right corner.
+    Minimap,
+    /// The way the player is facing on a compass strip along the top,
+    /// with the block they're in under it.
+    Compass,
+}
+
+impl HudElement {
+    pub const ALL: [HudElement ; 2] = [HudElement::Minimap, HudElement::Compass];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HudElement::Minimap => "Minimap",
+            HudElement::Compass => "Compass",
+        }
+    }
+}
+
+const CROSSHAIR_SIZE: f32 = 18.0;
+const CROSSHAIR_THICKNESS: f32 = 2.0;
+
+pub fn draw_crosshair(batch: &mut UiBatch, screen_size: (u32, u32)) {
+    let (cx, cy) = ((screen_size.0 / 2) as f32, (screen_size.1 / 2) as f32);
+    let half = CROSSHAIR_SIZE / 2.0;
+    let t = CROSSHAIR_THICKNESS / 2.0;
+    let color = [1.0, 1.0, 1.0, 0.85];
+
+    batch.quad(Rect::new(cx - half, cy - t, CROSSHAIR_SIZE, CROSSHAIR_THICKNESS), color);
+    batch.quad(Rect::new(cx - t, cy - half, CROSSHAIR_THICKNESS, half - t), color);
+    batch.quad(Rect::new(cx - t, cy + t, CROSSHAIR_THICKNESS, half - t), color);
+}
+
+const NAME_TAG_SIZE: f32 = 16.0;
+const NAME_TAG_PADDING: f32 = 3.0;
+// Further away than this, in blocks, a tag would only be clutter.
+const NAME_TAG_DISTANCE: f32 = 64.0;
+// Lifts the tag clear of the head.
+const NAME_TAG_LIFT: f32 = 0.5;
+
+/// Draws `name` centred above `head`, a point in the world, unless it's
+/// behind the camera or too far away.
+pub fn draw_name_tag(
+    batch: &mut UiBatch,
+    text: &mut TextRenderer,
+    view_proj: Matrix4<f32>,
+    screen_size: (u32, u32),
+    head: Point3<f32>,
+    name: &str,
+) {
+    let clip = view_proj * (head + Vector3::unit_y() * NAME_TAG_LIFT).to_homogeneous();
+    // `w` is the distance along the view direction.
+    if clip.w <= 0.0 || clip.w > NAME_TAG_DISTANCE {
+        return;
+    }
+    let x = (clip.x / clip.w * 0.5 + 0.5) * screen_size.0 as f32;
+    let y = (0.5 - clip.y / clip.w * 0.5) * screen_size.1 as f32;
+
+    let (width, height) = text.measure(name, NAME_TAG_SIZE);
+    let left = (x - width / 2.0).round();
+    let top = (y - height).round();
+    batch.quad(
+        Rect::new(left - NAME_TAG_PADDING, top - NAME_TAG_PADDING, width + NAME_TAG_PADDING * 2.0, height + NAME_TAG_PADDING * 2.0),
+        [0.0, 0.0, 0.0, 0.4],
+    );
+    text.queue(name, [left, top], NAME_TAG_SIZE, [1.0, 1.0, 1.0, 1.0]);
+}
+
+const COMPASS_WIDTH: f32 = 240.0;
+const COMPASS_HEIGHT: f32 = 22.0;
+const COMPASS_MARGIN: f32 = 8.0;
+// Degrees either side of the facing direction the strip shows.
+const COMPASS_SPAN: f32 = 60.0;
+const COMPASS_TICK: f32 = 15.0;
+const COMPASS_TEXT_SIZE: f32 = 14.0;
+const COMPASS_COORDINATES_SIZE: f32 = 16.0;
+// Clockwise from north, every 45 degrees.
+const COMPASS_POINTS: [&str ; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+const COMPASS_NORTH_COLOR: [f32 ; 4] = [1.0, 0.4, 0.35, 1.0];
+
+/// Draws the compass at the top of the screen: a strip of the directions
+/// around `yaw` with the way the player faces marked in the middle, and
+/// under it the bearing and the block `position` is in.
+pub fn draw_compass(batch: &mut UiBatch, text: &mut TextRenderer, position: Point3<f32>, yaw: f32, screen_size: (u32, u32)) {
+    let strip = Rect::new(((screen_size.0 as f32 - COMPASS_WIDTH) / 2.0).round(), COMPASS_MARGIN, COMPASS_WIDTH, COMPASS_HEIGHT);
+    batch.quad(strip, [0.0, 0.0, 0.0, 0.45]);
+
+    // A yaw of 0 faces east, +x, and north is -z, so the bearing is a
+    // quarter turn on from the yaw.
+    let bearing = (yaw.to_degrees() + 90.0).rem_euclid(360.0);
+    let pixels_per_degree = COMPASS_WIDTH / 2.0 / COMPASS_SPAN;
+    let middle = strip.x + COMPASS_WIDTH / 2.0;
+    let first = ((bearing - COMPASS_SPAN) / COMPASS_TICK).ceil() as i32;
+    let last = ((bearing + COMPASS_SPAN) / COMPASS_TICK).floor() as i32;
+    for tick in first..=last {
+        let degrees = tick as f32 * COMPASS_TICK;
+        let x = (middle + (degrees - bearing) * pixels_per_degree).round();
+        let point = tick.rem_euclid(24);
+        if point % 3 != 0 {
+            batch.quad(Rect::new(x - 1.0, strip.y + strip.h - 6.0, 2.0, 4.0), [1.0, 1.0, 1.0, 0.6]);
+            continue;
+        }
+        let label = COMPASS_POINTS[point as usize / 3];
+        let (width, height) = text.measure(label, COMPASS_TEXT_SIZE);
+        // Labels that would hang over the ends are left out.
+        if x - width / 2.0 < strip.x || x + width / 2.0 > strip.x + strip.w {
+            continue;
+        }
+        let color = if point == 0 { COMPASS_NORTH_COLOR } else { [1.0, 1.0, 1.0, 1.0] };
+        text.queue(label, [(x - width / 2.0).round(), (strip.y + (strip.h - height) / 2.0).round()], COMPASS_TEXT_SIZE, color);
+    }
+    batch.quad(Rect::new(middle - 1.0, strip.y - 3.0, 2.0, 5.0), COMPASS_NORTH_COLOR);
+    batch.quad(Rect::new(middle - 1.0, strip.y + strip.h - 2.0, 2.0, 5.0), COMPASS_NORTH_COLOR);
+
+    let facing = COMPASS_POINTS[(bearing / 45.0).round() as usize % COMPASS_POINTS.len()];
+    let [x, y, z] = [position.x, position.y, position.z].map(|n| n.floor() as i32);
+    let line = format!("{facing} {bearing:.0}°   {x}, {y}, {z}");
+    let (width, height) = text.measure(&line, COMPASS_COORDINATES_SIZE);
+    let left = (middle - width / 2.0).round();
+    let top = strip.y + strip.h + 6.0;
+    batch.quad(Rect::new(left - 4.0, top - 2.0, width + 8.0, height + 4.0), [0.0, 0.0, 0.0, 0.45]);
+    text.queue(line, [left, top], COMPASS_COORDINATES_SIZE, [1.0, 1.0, 1.0, 1.0]);
+}
+
+const WIDGET_TEXT_SIZE: f32 = 20.0;
+const WIDGET_DETAIL_SIZE: f32 = 16.0;
+const WIDGET_PADDING: f32 = 8.0;
+
+fn draw_button_frame(batch: &mut UiBatch, rect: Rect, hovered: bool) {
+    let fill = if hovered { [0.35, 0.35, 0.4, 0.9] } else { [0.15, 0.15, 0.18, 0.85] };
+    batch.quad(rect, fill);
+    batch.outline(rect, 2.0, if hovered { [1.0, 1.0, 1.0, 0.9] } else { [0.5, 0.5, 0.5, 0.9] });
+}
+
+/// A clickable box with a centred label, highlighted under the mouse.
+pub fn draw_button(batch: &mut UiBatch, text: &mut TextRenderer, rect: Rect, label: &str, hovered: bool) {
+    draw_button_frame(batch, rect, hovered);
+    let (width, height) = text.measure(label, WIDGET_TEXT_SIZE);
+    let position = [(rect.x + (rect.w - width) / 2.0).round(), (rect.y + (rect.h - height) / 2.0).round()];
+    text.queue(label, position, WIDGET_TEXT_SIZE, [1.0, 1.0, 1.0, 1.0]);
+}
+
+/// A button for an entry in a list, with the label on the left and
+/// smaller, greyed `detail` on the right.
+pub fn draw_list_button(batch: &mut UiBatch, text: &mut TextRenderer, rect: Rect, label: &str, detail: &str, hovered: bool) {
+    draw_button_frame(batch, rect, hovered);
+    let height = text.line_height(WIDGET_TEXT_SIZE);
+    let position = [rect.x + WIDGET_PADDING, (rect.y + (rect.h - height) / 2.0).round()];
+    text.queue(label, position, WIDGET_TEXT_SIZE, [1.0, 1.0, 1.0, 1.0]);
+
+    let (width, height) = text.measure(detail, WIDGET_DETAIL_SIZE);
+    let position = [(rect.x + rect.w - WIDGET_PADDING - width).round(), (rect.y + (rect.h - height) / 2.0).round()];
+    text.queue(detail, position, WIDGET_DETAIL_SIZE, [0.65, 0.65, 0.65, 1.0]);
+}
+
+/// A single-line text box. `placeholder` is shown greyed out while it's
+/// empty, and a focused box has a caret.
+pub fn draw_text_field(batch: &mut UiBatch, text: &mut TextRenderer, rect: Rect, value: &str, placeholder: &str, focused: bool) {
+    batch.quad(rect, [0.0, 0.0, 0.0, 0.8]);
+    batch.outline(rect, 2.0, if focused { [1.0, 1.0, 1.0, 0.9] } else { [0.5, 0.5, 0.5, 0.9] });
+
+    let (shown, color) = match (value.is_empty(), focused) {
+        (true, false) => (placeholder.to_string(), [0.6, 0.6, 0.6, 1.0]),
+        (_, true) => (format!("{value}_"), [1.0, 1.0, 1.0, 1.0]),
+        (false, false) => (value.to_string(), [1.0, 1.0, 1.0, 1.0]),
+    };
+    let height = text.line_height(WIDGET_TEXT_SIZE);
+    let position = [rect.x + WIDGET_PADDING, (rect.y + (rect.h - height) / 2.0).round()];
+    text.queue(shown, position, WIDGET_TEXT_SIZE, color);
+}
+
+const SLOT_SIZE: f32 = 44.0;
+const SLOT_GAP: f32 = 4.0;
+const HOTBAR_MARGIN: f32 = 12.0;
+const COUNT_TEXT_SIZE: f32 = 16.0;
+
+// Hearts are drawn a pixel at a time from this shape, each pixel this big.
+const HEART: [&str ; 6] = [
+    ".##.##.",
+    "#######",
+    "#######",
+    ".#####.",
+    "..###..",
+    "...#...",
+];
+// Food is drawn the same way, as drumsticks.
+const DRUMSTICK: [&str ; 6] = [
+    "...###.",
+    "..#####",
+    "..#####",
+    ".####..",
+    "##.....",
+    "#......",
+];
+const HEART_PIXEL: f32 = 2.0;
+const HEART_GAP: f32 = 2.0;
+// The row of hearts over the hotbar, with the gap under it, which the
+// drumsticks share.
+const HEALTH_ROW_HEIGHT: f32 = HEART.len() as f32 * HEART_PIXEL + 6.0;
+
+// Draws a slot's background, item icon and, for more than one item, its
+// count in the bottom-right corner.
+fn draw_slot(batch: &mut UiBatch, text: &mut TextRenderer, slot: Rect, stack: Option<ItemStack>) {
+    batch.quad(slot, [0.2, 0.2, 0.2, 0.6]);
+    if let Some(stack) = stack {
+        draw_stack(batch, text, slot, stack);
+    }
+}
+
+fn draw_stack(batch: &mut UiBatch, text: &mut TextRenderer, slot: Rect, stack: ItemStack) {
+    let (uv_min, uv_max) = block::atlas_uv(stack.block.texture(Face::PosZ));
+    batch.textured_quad(slot.inset(6.0), uv_min, uv_max, [1.0, 1.0, 1.0, 1.0]);
+
+    if stack.count > 1 {
+        let count = stack.count.to_string();
+        let (width, height) = text.measure(&count, COUNT_TEXT_SIZE);
+        let position = [slot.x + slot.w - width - 3.0, slot.y + slot.h - height - 1.0];
+        text.queue(count, position, COUNT_TEXT_SIZE, [1.0, 1.0, 1.0, 1.0]);
+    }
+}
+
+/// Which hotbar slot is selected. The items themselves live in the
+/// player's `Inventory`.
+pub struct Hotbar {
+    pub selected: usize,
+}
+
+impl Hotbar {
+    pub fn new() -> Self {
+        Self { selected: 0 }
+    }
+
+    pub fn selected_block(&self, inventory: &Inventory) -> Option<BlockId> {
+        inventory.get(self.selected).map(|stack| stack.block)
+    }
+
+    pub fn scroll(&mut self, steps: i32) {
+        self.selected = (self.selected as i32 + steps).rem_euclid(HOTBAR_SLOTS as i32) as usize;
+    }
+
+    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(keycode),
+                        ..
+                    },
+                ..
+            } => {
+                let slot = match keycode {
+                    KeyCode::Digit1 => 0,
+                    KeyCode::Digit2 => 1,
+                    KeyCode::Digit3 => 2,
+                    KeyCode::Digit4 => 3,
+                    KeyCode::Digit5 => 4,
+                    KeyCode::Digit6 => 5,
+                    KeyCode::Digit7 => 6,
+                    KeyCode::Digit8 => 7,
+                    KeyCode::Digit9 => 8,
+                    _ => return false,
+                };
+                self.selected = slot;
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let amount = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                };
+                // Scrolling down moves the selection to the right.
+                if amount < 0.0 {
+                    self.scroll(1);
+                } else if amount > 0.0 {
+                    self.scroll(-1);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn draw(&self, batch: &mut UiBatch, text: &mut TextRenderer, inventory: &Inventory, screen_size: (u32, u32)) {
+        let (width, height) = (screen_size.0 as f32, screen_size.1 as f32);
+        let total_width = HOTBAR_SLOTS as f32 * SLOT_SIZE + (HOTBAR_SLOTS - 1) as f32 * SLOT_GAP;
+        let left = ((width - total_width) / 2.0).round();
+        let top = height - SLOT_SIZE - HOTBAR_MARGIN;
+
+        let background = Rect::new(left - SLOT_GAP, top - SLOT_GAP, total_width + SLOT_GAP * 2.0, SLOT_SIZE + SLOT_GAP * 2.0);
+        batch.quad(background, [0.0, 0.0, 0.0, 0.45]);
+
+        for i in 0..HOTBAR_SLOTS {
+            let slot = Rect::new(left + i as f32 * (SLOT_SIZE + SLOT_GAP), top, SLOT_SIZE, SLOT_SIZE);
+            draw_slot(batch, text, slot, inventory.get(i));
+
+            if i == self.selected {
+                batch.outline(slot.inset(-2.0), 3.0, [1.0, 1.0, 1.0, 0.9]);
+            }
+        }
+    }
+
+    pub fn draw_selected_name(&self, text: &mut TextRenderer, inventory: &Inventory, screen_size: (u32, u32)) {
+        let Some(block) = self.selected_block(inventory) else {
+            return;
+        };
+
+        let name = &block.info().name;
+        let size = 18.0;
+        let (width, height) = text.measure(name, size);
+        let x = ((screen_size.0 as f32 - width) / 2.0).round();
+        // Kept clear of the hearts whether they're shown or not, so the
+        // name doesn't jump about between game modes.
+        let y = screen_size.1 as f32 - SLOT_SIZE - HOTBAR_MARGIN - SLOT_GAP * 2.0 - HEALTH_ROW_HEIGHT - height;
+
+        text.queue(name, [x, y], size, [1.0, 1.0, 1.0, 1.0]);
+    }
+}
+
+/// Draws `points` of health as a row of hearts over the left of the
+/// hotbar, two points to a heart.
+pub fn draw_health(batch: &mut UiBatch, points: u32, screen_size: (u32, u32)) {
+    draw_point_row(batch, &HEART, [0.85, 0.1, 0.1, 1.0], points, MAX_HEALTH, false, screen_size);
+}
+
+/// Draws `points` of food as a row of drumsticks over the right of the
+/// hotbar, two points to a drumstick, emptying from the left.
+pub fn draw_food(batch: &mut UiBatch, points: u32, screen_size: (u32, u32)) {
+    draw_point_row(batch, &DRUMSTICK, [0.75, 0.45, 0.2, 1.0], points, MAX_FOOD, true, screen_size);
+}
+
+// Draws a row of `shape`s, each worth two points, lined up with the left
+// edge of the hotbar or, with `from_right`, its right edge. A shape with
+// one point left is only filled on the half nearest the row's edge.
+fn draw_point_row(
+    batch: &mut UiBatch,
+    shape: &[&str ; 6],
+    color: [f32 ; 4],
+    points: u32,
+    max_points: u32,
+    from_right: bool,
+    screen_size: (u32, u32),
+) {
+    let (width, height) = (screen_size.0 as f32, screen_size.1 as f32);
+    let hotbar_width = HOTBAR_SLOTS as f32 * SLOT_SIZE + (HOTBAR_SLOTS - 1) as f32 * SLOT_GAP;
+    let left = ((width - hotbar_width) / 2.0).round();
+    let top = height - SLOT_SIZE - HOTBAR_MARGIN - SLOT_GAP * 2.0 - HEALTH_ROW_HEIGHT;
+    let columns = shape[0].len();
+    let icon_width = columns as f32 * HEART_PIXEL;
+
+    for icon in 0..max_points.div_ceil(2) {
+        let x = if from_right {
+            left + hotbar_width - icon_width - icon as f32 * (icon_width + HEART_GAP)
+        } else {
+            left + icon as f32 * (icon_width + HEART_GAP)
+        };
+        let filled = match points.saturating_sub(icon * 2) {
+            0 => 0..0,
+            1 if from_right => columns / 2..columns,
+            1 => 0..columns / 2 + 1,
+            _ => 0..columns,
+        };
+        for (row, line) in shape.iter().enumerate() {
+            for (column, _) in line.char_indices().filter(|&(_, c)| c == '#') {
+                let color = if filled.contains(&column) { color } else { [0.15, 0.15, 0.15, 0.7] };
+                let pixel = Rect::new(x + column as f32 * HEART_PIXEL, top + row as f32 * HEART_PIXEL, HEART_PIXEL, HEART_PIXEL);
+                batch.quad(pixel, color);
+            }
+        }
+    }
+}
+
+const INVENTORY_COLUMNS: usize = 9;
+const INVENTORY_PADDING: f32 = 12.0;
+// Extra space between the main rows and the hotbar row.
+const HOTBAR_ROW_GAP: f32 = 12.0;
+// Between the crafting grid or container and the main rows.
+const SECTION_ROW_GAP: f32 = 16.0;
+// Room for the arrow between a crafting grid or furnace and its output.
+const ARROW_WIDTH: f32 = 48.0;
+
+// What the screen shows above the player's inventory.
+enum Section {
+    // Two by two when opened from the inventory, three by three at a
+    // crafting table.
+    Crafting(CraftingGrid),
+    // A block entity with items, such as a chest, as last synced with the
+    // world.
+    Container { block: [i32 ; 3], entity: BlockEntity },
+}
+
+// A slot the inventory screen shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScreenSlot {
+    Inventory(usize),
+    Grid(usize),
+    // What the crafting grid makes.
+    Result,
+    Container(usize),
+}
+
+/// The full inventory, opened with its binding (E by default), with a
+/// crafting grid or an opened chest or furnace above it. Items are moved around
+/// with the mouse: left click picks up or puts down a stack, right click
+/// splits one or drops a single item, shift-click moves a stack between
+/// the hotbar and the main inventory or between the inventory and the
+/// container, and a number key sends the hovered stack to that hotbar
+/// slot. Clicking the crafting result takes it, and shift-clicking crafts
+/// as many as fit straight into the inventory.
+pub struct InventoryScreen {
+    pub open: bool,
+    pub toggle: Binding,
+    section: Section,
+    // The container's items were moved around and need writing back to
+    // the world.
+    container_changed: bool,
+    // The stack picked up with the mouse.
+    cursor: Option<ItemStack>,
+    mouse_position: [f32 ; 2],
+    shift_held: bool,
+    screen_size: (u32, u32),
+}
+
+impl InventoryScreen {
+    pub fn new(toggle: Binding) -> Self {
+        Self {
+            open: false,
+            toggle,
+            section: Section::Crafting(CraftingGrid::new(crafting::INVENTORY_GRID_SIZE)),
+            container_changed: false,
+            cursor: None,
+            mouse_position: [0.0, 0.0],
+            shift_held: false,
+            screen_size: (0, 0),
+        }
+    }
+
+    pub fn toggle(&mut self, inventory: &mut Inventory) {
+        if self.open {
+            self.close(inventory);
+        } else {
+            self.open_with(Section::Crafting(CraftingGrid::new(crafting::INVENTORY_GRID_SIZE)));
+        }
+    }
+
+    /// Opens the screen with a crafting table's bigger grid.
+    pub fn open_crafting_table(&mut self) {
+        self.open_with(Section::Crafting(CraftingGrid::new(crafting::MAX_GRID_SIZE)));
+    }
+
+    /// Opens the screen on the block entity at `block`.
+    pub fn open_container(&mut self, block: [i32 ; 3], entity: BlockEntity) {
+        self.open_with(Section::Container { block, entity });
+    }
+
+    fn open_with(&mut self, section: Section) {
+        self.open = true;
+        self.section = section;
+        self.container_changed = false;
+    }
+
+    /// Closes the screen, putting whatever is held or left in the crafting
+    /// grid back into the inventory; items that no longer fit are lost,
+    /// which only happens if the inventory filled up while the screen was
+    /// open.
+    pub fn close(&mut self, inventory: &mut Inventory) {
+        self.open = false;
+        if let Some(stack) = self.cursor.take() {
+            inventory.add(stack.block, stack.count);
+        }
+        if let Section::Crafting(grid) = &mut self.section {
+            grid.clear_into(inventory);
+        }
+    }
+
+    /// Where the open container is, if one is open.
+    pub fn container(&self) -> Option<[i32 ; 3]> {
+        match &self.section {
+            Section::Container { block, .. } if self.open => Some(*block),
+            _ => None,
+        }
+    }
+
+    /// The open container's items, if they've changed since last asked.
+    pub fn take_container_change(&mut self) -> Option<([i32 ; 3], Inventory)> {
+        match &self.section {
+            Section::Container { block, entity } if std::mem::take(&mut self.container_changed) => Some((*block, entity.items().clone())),
+            _ => None,
+        }
+    }
+
+    /// Catches the open container up with `world_entity`, what it is in
+    /// the world, which someone else or a burning furnace may have changed.
+    pub fn sync_container(&mut self, world_entity: &BlockEntity) {
+        if let Section::Container { entity, .. } = &mut self.section {
+            if entity != world_entity && !self.container_changed {
+                *entity = world_entity.clone();
+            }
+        }
+    }
+
+    pub fn process_events(&mut self, event: &WindowEvent, inventory: &mut Inventory) -> bool {
+        if self.toggle.just_pressed(event) {
+            self.toggle(inventory);
+            return true;
+        }
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state,
+                        physical_key: PhysicalKey::Code(keycode),
+                        repeat,
+                        ..
+                    },
+                ..
+            } => {
+                if matches!(keycode, KeyCode::ShiftLeft | KeyCode::ShiftRight) {
+                    self.shift_held = *state == ElementState::Pressed;
+                }
+
+                let pressed = *state == ElementState::Pressed && !repeat;
+                if pressed && self.open && *keycode == KeyCode::Escape {
+                    self.close(inventory);
+                    return true;
+                }
+                if !self.open {
+                    return false;
+                }
+
+                // A number key sends the hovered stack to that hotbar slot.
+                let hotbar_slot = match keycode {
+                    KeyCode::Digit1 => Some(0),
+                    KeyCode::Digit2 => Some(1),
+                    KeyCode::Digit3 => Some(2),
+                    KeyCode::Digit4 => Some(3),
+                    KeyCode::Digit5 => Some(4),
+                    KeyCode::Digit6 => Some(5),
+                    KeyCode::Digit7 => Some(6),
+                    KeyCode::Digit8 => Some(7),
+                    KeyCode::Digit9 => Some(8),
+                    _ => None,
+                };
+                if let (true, Some(to), Some(ScreenSlot::Inventory(from))) = (pressed, hotbar_slot, self.slot_at(self.mouse_position)) {
+                    inventory.move_stack(from, to);
+                }
+                // Nothing else should reach the player while the screen is
+                // open.
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } if self.open => {
+                self.mouse_position = [position.x as f32, position.y as f32];
+                true
+            }
+            WindowEvent::MouseInput { state: ElementState::Pressed, button, .. } if self.open => {
+                if let Some(slot) = self.slot_at(self.mouse_position) {
+                    self.click(slot, *button, inventory);
+                }
+                true
+            }
+            WindowEvent::MouseInput { .. } | WindowEvent::MouseWheel { .. } => self.open,
+            _ => false,
+        }
+    }
+
+    fn click(&mut self, slot: ScreenSlot, button: MouseButton, inventory: &mut Inventory) {
+        let click_button = match button {
+            MouseButton::Left => ClickButton::Primary,
+            MouseButton::Right => ClickButton::Secondary,
+            _ => return,
+        };
+        let quick = self.shift_held && self.cursor.is_none() && button == MouseButton::Left;
+
+        match (slot, &mut self.section) {
+            (ScreenSlot::Inventory(slot), Section::Container { entity, .. }) if quick => {
+                inventory.transfer(slot, entity.items_mut());
+                self.container_changed = true;
+            },
+            (ScreenSlot::Inventory(slot), _) if quick => inventory.quick_move(slot),
+            (ScreenSlot::Inventory(slot), _) => inventory.click(slot, &mut self.cursor, click_button),
+            (ScreenSlot::Grid(slot), Section::Crafting(grid)) if quick => grid.slots.transfer(slot, inventory),
+            (ScreenSlot::Grid(slot), Section::Crafting(grid)) => grid.slots.click(slot, &mut self.cursor, click_button),
+            (ScreenSlot::Result, Section::Crafting(grid)) if quick => craft_into(grid, inventory),
+            (ScreenSlot::Result, Section::Crafting(grid)) => craft_onto_cursor(grid, &mut self.cursor),
+            (ScreenSlot::Container(slot), Section::Container { entity, .. }) => {
+                let items = entity.items_mut();
+                if quick {
+                    items.transfer(slot, inventory);
+                } else {
+                    items.click(slot, &mut self.cursor, click_button);
+                }
+                self.container_changed = true;
+            },
+            _ => (),
+        }
+    }
+
+    fn panel(&self, screen_size: (u32, u32)) -> Rect {
+        let rows = MAIN_SLOTS / INVENTORY_COLUMNS + 1;
+        let width = INVENTORY_COLUMNS as f32 * (SLOT_SIZE + SLOT_GAP) - SLOT_GAP + INVENTORY_PADDING * 2.0;
+        let height = rows as f32 * (SLOT_SIZE + SLOT_GAP) - SLOT_GAP + HOTBAR_ROW_GAP
+            + self.section_height() + SECTION_ROW_GAP
+            + INVENTORY_PADDING * 2.0;
+        Rect::new(
+            ((screen_size.0 as f32 - width) / 2.0).round(),
+            ((screen_size.1 as f32 - height) / 2.0).round(),
+            width,
+            height,
+        )
+    }
+
+    fn section_height(&self) -> f32 {
+        let rows = match &self.section {
+            Section::Crafting(grid) => grid.size(),
+            // Input, flame and fuel.
+            Section::Container { entity: BlockEntity::Furnace(_), .. } => 3,
+            Section::Container { entity, .. } => entity.items().slot_count().div_ceil(INVENTORY_COLUMNS),
+        };
+        rows as f32 * (SLOT_SIZE + SLOT_GAP) - SLOT_GAP
+    }
+
+    // The crafting grid or furnace and their output sit centred along the
+    // top, or a chest's rows fill it, then the main inventory fills the
+    // rows under them and the hotbar sits in its own row at the bottom,
+    // matching the order of the HUD.
+    fn slot_rect(&self, panel: Rect, slot: ScreenSlot) -> Rect {
+        let top = panel.y + INVENTORY_PADDING;
+        let inventory_top = top + self.section_height() + SECTION_ROW_GAP;
+        let column_x = |column: usize| panel.x + INVENTORY_PADDING + column as f32 * (SLOT_SIZE + SLOT_GAP);
+        let row_y = |top: f32, row: usize| top + row as f32 * (SLOT_SIZE + SLOT_GAP);
+        let grid_size = match &self.section {
+            Section::Crafting(grid) => grid.size(),
+            Section::Container { .. } => 1,
+        };
+        let grid_width = grid_size as f32 * (SLOT_SIZE + SLOT_GAP) - SLOT_GAP;
+        let input_left = panel.x + ((panel.w - grid_width - ARROW_WIDTH - SLOT_SIZE) / 2.0).round();
+        let output = (input_left + grid_width + ARROW_WIDTH, top + ((self.section_height() - SLOT_SIZE) / 2.0).round());
+
+        let (x, y) = match slot {
+            ScreenSlot::Grid(slot) => (
+                input_left + (slot % grid_size) as f32 * (SLOT_SIZE + SLOT_GAP),
+                row_y(top, slot / grid_size),
+            ),
+            ScreenSlot::Result => output,
+            ScreenSlot::Container(slot) => match &self.section {
+                Section::Container { entity: BlockEntity::Furnace(_), .. } => match slot {
+                    furnace::INPUT_SLOT => (input_left, row_y(top, 0)),
+                    furnace::FUEL_SLOT => (input_left, row_y(top, 2)),
+                    _ => output,
+                },
+                _ => (column_x(slot % INVENTORY_COLUMNS), row_y(top, slot / INVENTORY_COLUMNS)),
+            },
+            ScreenSlot::Inventory(slot) if slot < HOTBAR_SLOTS => {
+                (column_x(slot), row_y(inventory_top, MAIN_SLOTS / INVENTORY_COLUMNS) + HOTBAR_ROW_GAP)
+            },
+            ScreenSlot::Inventory(slot) => {
+                let slot = slot - HOTBAR_SLOTS;
+                (column_x(slot % INVENTORY_COLUMNS), row_y(inventory_top, slot / INVENTORY_COLUMNS))
+            },
+        };
+        Rect::new(x, y, SLOT_SIZE, SLOT_SIZE)
+    }
+
+    fn slots(&self) -> Vec<ScreenSlot> {
+        let mut slots = (0..INVENTORY_SLOTS).map(ScreenSlot::Inventory).collect::<Vec<_>>();
+        match &self.section {
+            Section::Crafting(grid) => {
+                slots.extend((0..grid.slots.slot_count()).map(ScreenSlot::Grid));
+                slots.push(ScreenSlot::Result);
+            },
+            Section::Container { entity, .. } => slots.extend((0..entity.items().slot_count()).map(ScreenSlot::Container)),
+        }
+        slots
+    }
+
+    fn slot_at(&self, position: [f32 ; 2]) -> Option<ScreenSlot> {
+        let panel = self.panel(self.screen_size);
+        self.slots().into_iter().find(|&slot| self.slot_rect(panel, slot).contains(position))
+    }
+
+    fn stack(&self, inventory: &Inventory, slot: ScreenSlot) -> Option<ItemStack> {
+        match (slot, &self.section) {
+            (ScreenSlot::Inventory(slot), _) => inventory.get(slot),
+            (ScreenSlot::Grid(slot), Section::Crafting(grid)) => grid.slots.get(slot),
+            (ScreenSlot::Result, Section::Crafting(grid)) => grid.result(),
+            (ScreenSlot::Container(slot), Section::Container { entity, .. }) => entity.items().get(slot),
+            _ => None,
+        }
+    }
+
+    pub fn draw(&mut self, batch: &mut UiBatch, text: &mut TextRenderer, inventory: &Inventory, screen_size: (u32, u32)) {
+        // Hit-testing needs the layout from the frame the user is looking
+        // at.
+        self.screen_size = screen_size;
+
+        let (width, height) = (screen_size.0 as f32, screen_size.1 as f32);
+        batch.quad(Rect::new(0.0, 0.0, width, height), [0.0, 0.0, 0.0, 0.35]);
+
+        let panel = self.panel(screen_size);
+        batch.quad(panel, [0.08, 0.08, 0.08, 0.85]);
+        batch.outline(panel, 2.0, [0.6, 0.6, 0.6, 0.9]);
+
+        let hovered = self.slot_at(self.mouse_position);
+        for slot in self.slots() {
+            let rect = self.slot_rect(panel, slot);
+            draw_slot(batch, text, rect, self.stack(inventory, slot));
+            if hovered == Some(slot) {
+                batch.outline(rect.inset(-1.0), 2.0, [1.0, 1.0, 1.0, 0.7]);
+            }
+        }
+
+        match &self.section {
+            Section::Crafting(_) => draw_arrow(batch, self.slot_rect(panel, ScreenSlot::Result), 0.0),
+            Section::Container { entity: BlockEntity::Furnace(furnace), .. } => {
+                draw_arrow(batch, self.slot_rect(panel, ScreenSlot::Container(furnace::OUTPUT_SLOT)), furnace.progress_fraction());
+                let input = self.slot_rect(panel, ScreenSlot::Container(furnace::INPUT_SLOT));
+                draw_flame(batch, Rect::new(input.x, input.y + SLOT_SIZE + SLOT_GAP, SLOT_SIZE, SLOT_SIZE), furnace.burn_fraction());
+            },
+            Section::Container { .. } => (),
+        }
+
+        if let Some(stack) = self.cursor {
+            let [x, y] = self.mouse_position;
+            let rect = Rect::new(x - SLOT_SIZE / 2.0, y - SLOT_SIZE / 2.0, SLOT_SIZE, SLOT_SIZE);
+            draw_stack(batch, text, rect, stack);
+        } else if let Some(stack) = hovered.and_then(|slot| self.stack(inventory, slot)) {
+            let name = &stack.block.info().name;
+            let [x, y] = self.mouse_position;
+            text.queue(name, [x + 14.0, y - 20.0], 16.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+}
+
+// An arrow pointing left to right at `target`, the first `fill` of it, from
+// 0 to 1, lit up to show progress.
+fn draw_arrow(batch: &mut UiBatch, target: Rect, fill: f32) {
+    let (left, middle) = (target.x - ARROW_WIDTH + 10.0, target.y + SLOT_SIZE / 2.0);
+    let filled_right = left + (ARROW_WIDTH - 14.0) * fill;
+    let color = |x: f32| if x < filled_right { [1.0, 1.0, 1.0, 1.0] } else { [0.6, 0.6, 0.6, 0.9] };
+
+    let shaft = ARROW_WIDTH - 26.0;
+    let lit = (filled_right - left).clamp(0.0, shaft);
+    batch.quad(Rect::new(left, middle - 2.0, lit, 4.0), color(left));
+    batch.quad(Rect::new(left + lit, middle - 2.0, shaft - lit, 4.0), color(left + shaft));
+    for step in 0..6 {
+        let (x, half) = (target.x - 16.0 + step as f32 * 2.0, 6.0 - step as f32);
+        batch.quad(Rect::new(x, middle - half, 2.0, half * 2.0), color(x));
+    }
+}
+
+// A flame in the middle of `area` that burns down from the top as `fuel`
+// goes from 1 to 0.
+fn draw_flame(batch: &mut UiBatch, area: Rect, fuel: f32) {
+    let flame = Rect::new(area.x + (area.w - 12.0) / 2.0, area.y + 6.0, 12.0, area.h - 12.0);
+    batch.quad(flame, [0.25, 0.25, 0.25, 0.9]);
+    let burning = (flame.h * fuel).round();
+    batch.quad(Rect::new(flame.x, flame.y + flame.h - burning, flame.w, burning), [1.0, 0.55, 0.1, 1.0]);
+}
+
+// Crafts once onto the stack held by the mouse, if it's empty or the same
+// item with room for the result.
+fn craft_onto_cursor(grid: &mut CraftingGrid, cursor: &mut Option<ItemStack>) {
+    let Some(result) = grid.result() else {
+        return;
+    };
+    match cursor {
+        None => *cursor = grid.craft(),
+        Some(held) if held.block == result.block && held.count + result.count <= MAX_STACK => {
+            held.count += result.count;
+            grid.craft();
+        },
+        Some(_) => (),
+    }
+}
+
+// Crafts as many times as the grid allows and the inventory has room for.
+fn craft_into(grid: &mut CraftingGrid, inventory: &mut Inventory) {
+    while let Some(result) = grid.result() {
+        if inventory.room_for(result.block) < result.count {
+            return;
+        }
+        grid.craft();
+        inventory.add(result.block, result.count);
+    }
+}
@@ -0,0 +1,45 @@
+struct ScreenUniform {
+    size: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) textured: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) textured: f32,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Positions are in pixels with the origin in the top-left corner.
+    let ndc = in.position / screen.size * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+    out.textured = in.textured;
+
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sampled = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return in.color * mix(vec4<f32>(1.0), sampled, in.textured);
+}
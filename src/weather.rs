@@ -0,0 +1,155 @@
+//! Weather: each world is clear, raining or storming, and moves on to the
+//! next of them after a while. Rain and storms darken the sky and thicken
+//! the fog as they set in rather than all at once, and storms flash with
+//! lightning. Whether it rains or snows, or stays dry, is up to the biome.
+
+use serde::{Deserialize, Serialize};
+
+use crate::random::Random;
+
+// Seconds each kind of weather lasts, between these.
+const CLEAR_DURATION: (f32, f32) = (600.0, 1800.0);
+const RAIN_DURATION: (f32, f32) = (240.0, 720.0);
+const STORM_DURATION: (f32, f32) = (120.0, 360.0);
+// Chance that clear weather turns into a storm rather than rain.
+const STORM_CHANCE: f32 = 0.3;
+// Seconds rain takes to set in fully or clear up.
+const TRANSITION_TIME: f32 = 10.0;
+// How much of the daylight full rain and, on top of it, a storm take away.
+const RAIN_DIMMING: f32 = 0.3;
+const STORM_DIMMING: f32 = 0.2;
+// Lightning strikes this often on average during a full storm, and its
+// flash fades over this many seconds.
+const LIGHTNING_PER_SECOND: f32 = 1.0 / 12.0;
+const FLASH_TIME: f32 = 0.4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weather {
+    #[default]
+    Clear,
+    Rain,
+    /// Heavier rain, with thunder and lightning.
+    Storm,
+}
+
+impl Weather {
+    pub const ALL: [Weather ; 3] = [Weather::Clear, Weather::Rain, Weather::Storm];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Weather::Clear => "Clear",
+            Weather::Rain => "Rain",
+            Weather::Storm => "Storm",
+        }
+    }
+
+    /// Looks the weather up by its name in any case, as typed in commands.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|weather| weather.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    fn duration(self) -> (f32, f32) {
+        match self {
+            Weather::Clear => CLEAR_DURATION,
+            Weather::Rain => RAIN_DURATION,
+            Weather::Storm => STORM_DURATION,
+        }
+    }
+}
+
+/// A world's weather, how long until it changes, and how far the change
+/// to it has got on screen.
+#[derive(Clone, Debug)]
+pub struct WeatherState {
+    pub weather: Weather,
+    /// Seconds until the weather changes by itself.
+    pub remaining: f32,
+    // How far rain and storms have set in, from 0 to 1.
+    wetness: f32,
+    storminess: f32,
+    // Brightness left from the last lightning flash.
+    flash: f32,
+    random: Random,
+}
+
+impl WeatherState {
+    pub fn new(seed: u32) -> Self {
+        let mut random = Random::new(seed);
+        let remaining = random.range(CLEAR_DURATION);
+        Self {
+            weather: Weather::Clear,
+            remaining,
+            wetness: 0.0,
+            storminess: 0.0,
+            flash: 0.0,
+            random,
+        }
+    }
+
+    /// Changes the weather for `duration` seconds, or a random while for
+    /// that weather if `None`.
+    pub fn set(&mut self, weather: Weather, duration: Option<f32>) {
+        self.weather = weather;
+        self.remaining = duration.unwrap_or_else(|| self.random.range(weather.duration()));
+    }
+
+    /// Runs the clock down by `dt` seconds, moving on to the next weather
+    /// once it runs out. Returns whether it did. Only the world that owns
+    /// the weather calls this; a client is told when it changes.
+    pub fn update(&mut self, dt: f32) -> bool {
+        self.remaining -= dt;
+        if self.remaining > 0.0 {
+            return false;
+        }
+        let next = match self.weather {
+            Weather::Clear if self.random.float() < STORM_CHANCE => Weather::Storm,
+            Weather::Clear => Weather::Rain,
+            Weather::Rain | Weather::Storm => Weather::Clear,
+        };
+        self.set(next, None);
+        true
+    }
+
+    /// Eases the look of the weather towards what it is by `dt` seconds,
+    /// and strikes lightning now and then in a storm. Returns the angle
+    /// around the sky, in radians, that lightning struck from, if it did.
+    pub fn animate(&mut self, dt: f32) -> Option<f32> {
+        let step = dt / TRANSITION_TIME;
+        let ease = |value: f32, target: f32| value + (target - value).clamp(-step, step);
+        self.wetness = ease(self.wetness, if self.weather == Weather::Clear { 0.0 } else { 1.0 });
+        self.storminess = ease(self.storminess, if self.weather == Weather::Storm { 1.0 } else { 0.0 });
+        self.flash = (self.flash - dt / FLASH_TIME).max(0.0);
+
+        if self.random.float() >= LIGHTNING_PER_SECOND * self.storminess * dt {
+            return None;
+        }
+        self.flash = 1.0;
+        Some(self.random.range((0.0, std::f32::consts::TAU)))
+    }
+
+    /// How far rain has set in, from 0 when it's clear to 1 in full rain or
+    /// a storm.
+    pub fn wetness(&self) -> f32 {
+        self.wetness
+    }
+
+    /// How much rain or snow falls, from 0 to 1; storms bring the most.
+    pub fn intensity(&self) -> f32 {
+        (self.wetness + self.storminess) / 2.0
+    }
+
+    /// `daylight` dimmed by the clouds, or lit up by a lightning flash.
+    pub fn daylight(&self, daylight: f32) -> f32 {
+        let dimming = 1.0 - self.wetness * RAIN_DIMMING - self.storminess * STORM_DIMMING;
+        (daylight * dimming).max(self.flash)
+    }
+}
@@ -1,28 +1,148 @@
 use std::sync::Arc;
 
+use log::warn;
+use serde::{Deserialize, Serialize};
 use winit::{
-    window::{Window as WinitWindow, WindowAttributes},
-    dpi::PhysicalSize,
+    window::{CursorGrabMode, Fullscreen, Icon, Window as WinitWindow, WindowAttributes},
+    dpi::{PhysicalPosition, PhysicalSize},
     event_loop::ActiveEventLoop,
 };
 
+use crate::error::Result;
+
+// Embedded so the icon is there however the game is started.
+const ICON: &[u8] = include_bytes!("../assets/icon.png");
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FullscreenMode {
+    #[default]
+    Windowed,
+    Borderless,
+    Exclusive,
+}
+
 #[allow(unused)]
 pub struct Window<'a> {
     pub title: &'a str,
     pub size: PhysicalSize<u32>,
     pub core_window: Arc<WinitWindow>,
+    pub fullscreen: FullscreenMode,
+    pub cursor_grabbed: bool,
+    // Shown after the title, such as the world being played.
+    status: Option<String>,
 }
 
 impl<'a> Window<'a> {
-    pub fn build(title: &'a str, size: PhysicalSize<u32>, event_loop: &ActiveEventLoop) -> Self {
+    pub fn build(
+        title: &'a str,
+        size: PhysicalSize<u32>,
+        fullscreen: FullscreenMode,
+        event_loop: &ActiveEventLoop,
+    ) -> Result<Self> {
         let window_attributes = WindowAttributes::default()
             .with_title(title)
-            .with_inner_size(size);
+            .with_inner_size(size)
+            .with_window_icon(load_icon());
 
-        Self {
+        let core_window = Arc::new(event_loop.create_window(window_attributes)?);
+        let size = core_window.inner_size();
+
+        let mut window = Self {
             title,
             size,
-            core_window: Arc::new(event_loop.create_window(window_attributes).unwrap()),
+            core_window,
+            fullscreen: FullscreenMode::Windowed,
+            cursor_grabbed: false,
+            status: None,
+        };
+        window.set_fullscreen(fullscreen);
+
+        Ok(window)
+    }
+
+    pub fn set_fullscreen(&mut self, mode: FullscreenMode) {
+        let fullscreen = match mode {
+            FullscreenMode::Windowed => None,
+            FullscreenMode::Borderless => Some(Fullscreen::Borderless(None)),
+            FullscreenMode::Exclusive => {
+                // Exclusive mode needs a concrete video mode; use the
+                // largest, fastest one the current monitor offers.
+                let video_mode = self.core_window.current_monitor().and_then(|monitor| {
+                    monitor.video_modes().max_by_key(|mode| {
+                        let size = mode.size();
+                        (size.width * size.height, mode.refresh_rate_millihertz())
+                    })
+                });
+
+                match video_mode {
+                    Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                    None => {
+                        warn!("no video modes available, falling back to borderless fullscreen");
+                        Some(Fullscreen::Borderless(None))
+                    }
+                }
+            }
+        };
+
+        self.core_window.set_fullscreen(fullscreen);
+        self.fullscreen = mode;
+
+        // Some platforms drop the cursor grab when the window changes mode.
+        if self.cursor_grabbed {
+            self.set_cursor_grabbed(true);
+        }
+    }
+
+    /// Sets what the title shows after the game's name, leaving the title
+    /// alone if it hasn't changed.
+    pub fn set_status(&mut self, status: Option<String>) {
+        if self.status == status {
+            return;
         }
+        match &status {
+            Some(status) => self.core_window.set_title(&format!("{} - {status}", self.title)),
+            None => self.core_window.set_title(self.title),
+        }
+        self.status = status;
+    }
+
+    pub fn center(&self) -> PhysicalPosition<f64> {
+        PhysicalPosition::new(self.size.width as f64 / 2.0, self.size.height as f64 / 2.0)
+    }
+
+    pub fn set_cursor_grabbed(&mut self, grabbed: bool) {
+        let result = if grabbed {
+            self.core_window.set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.core_window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            self.core_window.set_cursor_grab(CursorGrabMode::None)
+        };
+
+        if let Err(e) = result {
+            warn!("could not change cursor grab: {e}");
+            return;
+        }
+
+        self.core_window.set_cursor_visible(!grabbed);
+        self.cursor_grabbed = grabbed;
     }
-}
\ No newline at end of file
+}
+
+fn load_icon() -> Option<Icon> {
+    let image = match image::load_from_memory(ICON) {
+        Ok(image) => image.to_rgba8(),
+        Err(e) => {
+            warn!("could not decode the window icon: {e}");
+            return None;
+        }
+    };
+    let (width, height) = image.dimensions();
+    match Icon::from_rgba(image.into_raw(), width, height) {
+        Ok(icon) => Some(icon),
+        Err(e) => {
+            warn!("could not set the window icon: {e}");
+            None
+        }
+    }
+}
@@ -0,0 +1,700 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use cgmath::{Point3, Vector3};
+
+use crate::{
+    block::{BlockId, BlockModel, BlockState, Cuboid, Facing},
+    block_entity::BlockEntity,
+    border::WorldBorder,
+    chunk::{Chunk, ChunkPos, ColumnPos, CHUNK_SIZE},
+    config::WorldConfig,
+    dimension::Dimension,
+    fluid::{self, FluidQueue},
+    game_mode::GameMode,
+    inventory::Inventory,
+    light::{self, Light},
+    weather::WeatherState,
+    worldgen::{
+        structure::{self, PendingBlock},
+        WorldGenerator,
+    },
+};
+
+/// Length of a full day in world ticks; 0 is sunrise and half of it sunset.
+pub const DAY_LENGTH: u64 = 24000;
+const DEFAULT_TIME: u64 = 3000;
+// Where sleeping through the night leaves the clock: just after sunrise,
+// once the sky is fully light.
+const WAKE_TIME: u64 = 1000;
+// Ticks of world time a second at normal speed, for a 20 minute day.
+const TIME_PER_SECOND: f32 = 20.0;
+// Past this many blocks in one `set_voxels` changing the light, relighting
+// the chunks around them whole is quicker than relighting around each.
+const RELIGHT_CHUNKS_AFTER: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub block: [i32 ; 3],
+    pub normal: [i32 ; 3],
+    pub distance: f32,
+}
+
+/// A block set through `World::set_voxel`.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockChange {
+    pub block: [i32 ; 3],
+    pub state: BlockState,
+    pub level: u8,
+}
+
+/// A block with everything kept along with it, as `World::set_voxels`
+/// sets and hands back.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockSnapshot {
+    pub block: [i32 ; 3],
+    pub state: BlockState,
+    pub level: u8,
+    /// What the block holds, or `None` for whatever a new one of it starts
+    /// with.
+    pub entity: Option<BlockEntity>,
+}
+
+pub struct World {
+    pub chunks: HashMap<ChunkPos, Chunk>,
+    // The heights of the chunks loaded in each column, for going down one
+    // from the top.
+    columns: HashMap<ColumnPos, BTreeSet<i32>>,
+    pub generator: WorldGenerator,
+    pub time: u64,
+    /// How many times faster than normal the day goes by.
+    pub time_speed: f32,
+    /// Whether time passes at all.
+    pub daylight_cycle: bool,
+    // Time passed that doesn't add up to a whole tick of it yet.
+    time_fraction: f32,
+    pub game_mode: GameMode,
+    /// Where players start out and come back after dying, at their feet.
+    pub spawn_point: [f32 ; 3],
+    pub border: WorldBorder,
+    pub weather: WeatherState,
+    dirty: HashSet<ChunkPos>,
+    // Chunks changed since they were generated or loaded, which need saving
+    // when they're unloaded.
+    modified: HashSet<ChunkPos>,
+    // Structure blocks generated for chunks that don't exist yet.
+    pending: HashMap<ChunkPos, Vec<PendingBlock>>,
+    fluid_updates: FluidQueue,
+    // Blocks that may have lost what held them up and need to fall.
+    fall_checks: HashSet<[i32 ; 3]>,
+    // Wire, switches and powered blocks next to a change, which may need
+    // to pass a signal on or respond to it.
+    signal_updates: HashSet<[i32 ; 3]>,
+    // Every block change since the last `take_changes`, if anyone listens.
+    changes: Option<Vec<BlockChange>>,
+}
+
+impl World {
+    /// A new game's overworld.
+    pub fn new(settings: &WorldConfig) -> Self {
+        let generator = WorldGenerator::new(settings.seed, settings.world_type, settings.cave_density);
+        Self::with_generator(generator, settings.game_mode, WorldBorder::new(settings.border_radius))
+    }
+
+    /// The world of another dimension in the same game, with the same seed
+    /// and everything `carry_over` takes from this one.
+    pub fn new_dimension(&self, dimension: Dimension) -> Self {
+        let generator = self.generator.clone().with_dimension(dimension);
+        let mut world = Self::with_generator(generator, self.game_mode, self.border);
+        world.carry_over(self);
+        world
+    }
+
+    fn with_generator(generator: WorldGenerator, game_mode: GameMode, border: WorldBorder) -> Self {
+        let spawn_point = [8.5, (generator.height_at(8, 8) + 1) as f32, 8.5];
+        let seed = generator.seed;
+        Self {
+            chunks: HashMap::new(),
+            columns: HashMap::new(),
+            generator,
+            time: DEFAULT_TIME,
+            time_speed: 1.0,
+            daylight_cycle: true,
+            time_fraction: 0.0,
+            game_mode,
+            spawn_point,
+            border,
+            weather: WeatherState::new(seed),
+            dirty: HashSet::new(),
+            modified: HashSet::new(),
+            pending: HashMap::new(),
+            fluid_updates: FluidQueue::default(),
+            fall_checks: HashSet::new(),
+            signal_updates: HashSet::new(),
+            changes: None,
+        }
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        self.generator.dimension
+    }
+
+    /// Takes on the state that belongs to the whole game rather than one
+    /// dimension from `other`, the world the player is leaving for this
+    /// one: the clock, game mode, spawn point, border and weather.
+    pub fn carry_over(&mut self, other: &World) {
+        self.time = other.time;
+        self.time_fraction = other.time_fraction;
+        self.time_speed = other.time_speed;
+        self.daylight_cycle = other.daylight_cycle;
+        self.game_mode = other.game_mode;
+        self.spawn_point = other.spawn_point;
+        self.border = other.border;
+        self.weather = other.weather.clone();
+    }
+
+    /// Removes every chunk, for a world the player has left. Modified ones
+    /// should be saved first. Structure blocks waiting for chunks are kept
+    /// for when the player comes back.
+    pub fn unload_all(&mut self) {
+        self.chunks.clear();
+        self.columns.clear();
+        self.modified.clear();
+        self.dirty.clear();
+        self.fluid_updates = FluidQueue::default();
+        self.fall_checks.clear();
+        self.signal_updates.clear();
+    }
+
+    pub fn generate_chunk(&mut self, pos: ChunkPos) {
+        let (chunk, overflow) = self.generator.generate(pos);
+        self.insert_chunk(chunk, overflow, false);
+    }
+
+    /// Adds a generated or loaded chunk, applying structure blocks that were
+    /// waiting for it and spilling its own `overflow` into its neighbours.
+    /// `modified` chunks differ from what's on disk.
+    pub fn insert_chunk(&mut self, chunk: Chunk, overflow: Vec<PendingBlock>, modified: bool) {
+        let pos = chunk.pos;
+        let _span = tracing::trace_span!("insert_chunk", x = pos.x, y = pos.y, z = pos.z).entered();
+        self.chunks.insert(pos, chunk);
+        self.columns.entry(pos.column()).or_default().insert(pos.y);
+        self.mark_dirty_with_neighbors(pos);
+        light::light_chunk(self, pos);
+        if modified {
+            self.modified.insert(pos);
+        }
+
+        for pending in self.pending.remove(&pos).unwrap_or_default() {
+            self.place_pending(pending);
+        }
+        for pending in overflow {
+            self.place_pending(pending);
+        }
+    }
+
+    /// Removes a chunk, leaving it to the caller to save it if
+    /// `is_modified` said so.
+    pub fn unload_chunk(&mut self, pos: ChunkPos) -> Option<Chunk> {
+        let chunk = self.chunks.remove(&pos)?;
+        if let Some(column) = self.columns.get_mut(&pos.column()) {
+            column.remove(&pos.y);
+            if column.is_empty() {
+                self.columns.remove(&pos.column());
+            }
+        }
+        self.modified.remove(&pos);
+        // Drops its mesh and exposes the neighbours' border faces.
+        self.dirty.insert(pos);
+        self.mark_dirty_with_neighbors(pos);
+        Some(chunk)
+    }
+
+    /// The loaded chunks of a column, from the top down.
+    pub fn column(&self, column: ColumnPos) -> impl Iterator<Item = &Chunk> {
+        self.columns.get(&column)
+            .into_iter()
+            .flat_map(|heights| heights.iter().rev())
+            .filter_map(move |&y| self.chunks.get(&column.chunk(y)))
+    }
+
+    pub fn is_modified(&self, pos: ChunkPos) -> bool {
+        self.modified.contains(&pos)
+    }
+
+    /// Loaded chunks that need saving, which are considered saved from here
+    /// on.
+    pub fn take_modified(&mut self) -> Vec<ChunkPos> {
+        self.modified.drain().collect()
+    }
+
+    /// Marks chunks `take_modified` returned as needing saving again, for
+    /// when they couldn't be saved yet.
+    pub fn keep_modified(&mut self, chunks: impl IntoIterator<Item = ChunkPos>) {
+        self.modified.extend(chunks.into_iter().filter(|pos| self.chunks.contains_key(pos)));
+    }
+
+    // Applies a structure block now if its chunk is loaded, otherwise keeps
+    // it until that chunk is loaded.
+    fn place_pending(&mut self, pending: PendingBlock) {
+        let PendingBlock { x, y, z, block } = pending;
+        let pos = ChunkPos::from_block(x, y, z);
+        if !self.chunks.contains_key(&pos) {
+            self.pending.entry(pos).or_default().push(pending);
+            return;
+        }
+
+        if structure::can_replace(self.get_block(x, y, z)) {
+            self.set_block(x, y, z, block);
+        }
+    }
+
+    /// Fraction of the day that has passed, in [0, 1).
+    pub fn time_of_day(&self) -> f32 {
+        ((self.time % DAY_LENGTH) as f32 + self.time_fraction) / DAY_LENGTH as f32
+    }
+
+    /// Whether the sun is down, from sunset until the next sunrise.
+    pub fn is_night(&self) -> bool {
+        self.time % DAY_LENGTH >= DAY_LENGTH / 2
+    }
+
+    /// Moves the clock on to the next morning, as sleeping does.
+    pub fn skip_night(&mut self) {
+        self.time = (self.time / DAY_LENGTH + 1) * DAY_LENGTH + WAKE_TIME;
+        self.time_fraction = 0.0;
+    }
+
+    /// Moves the clock on by `dt` seconds, unless the daylight cycle is
+    /// off.
+    pub fn advance_time(&mut self, dt: f32) {
+        if !self.daylight_cycle {
+            return;
+        }
+        self.time_fraction += dt * TIME_PER_SECOND * self.time_speed;
+        let whole = self.time_fraction.floor();
+        self.time += whole as u64;
+        self.time_fraction -= whole;
+    }
+
+    fn mark_dirty_with_neighbors(&mut self, pos: ChunkPos) {
+        for [dx, dy, dz] in [[0, 0, 0], [1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]] {
+            let neighbor = pos.offset(dx, dy, dz);
+            if self.chunks.contains_key(&neighbor) {
+                self.dirty.insert(neighbor);
+            }
+        }
+    }
+
+    /// Starts logging block changes for `take_changes`, which the server
+    /// forwards to clients.
+    pub fn record_changes(&mut self) {
+        self.changes.get_or_insert_with(Vec::new);
+    }
+
+    pub fn take_changes(&mut self) -> Vec<BlockChange> {
+        self.changes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Marks every loaded chunk for remeshing, for when something changes
+    /// how all of them look.
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.extend(self.chunks.keys().copied());
+    }
+
+    pub fn take_dirty(&mut self) -> Vec<ChunkPos> {
+        self.dirty.drain().collect()
+    }
+
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> BlockId {
+        self.get_state(x, y, z).block
+    }
+
+    /// Air wherever the chunk isn't loaded.
+    pub fn get_state(&self, x: i32, y: i32, z: i32) -> BlockState {
+        let (pos, [x, y, z]) = locate(x, y, z);
+        match self.chunks.get(&pos) {
+            Some(chunk) => chunk.get_state(x, y, z),
+            None => BlockState::default(),
+        }
+    }
+
+    /// Which horizontal neighbours, in `Facing::ALL` order, a fence at the
+    /// position joins; none if there's no fence there.
+    pub fn fence_connections(&self, x: i32, y: i32, z: i32) -> [bool ; 4] {
+        if self.get_block(x, y, z).info().model != BlockModel::Fence {
+            return [false ; 4];
+        }
+        Facing::ALL.map(|facing| {
+            let [dx, _, dz] = facing.offset();
+            self.get_block(x + dx, y, z + dz).joins_fences()
+        })
+    }
+
+    /// The boxes the block at the position is drawn and aimed at with.
+    pub fn block_shape(&self, x: i32, y: i32, z: i32) -> Vec<Cuboid> {
+        self.get_state(x, y, z).shape(self.fence_connections(x, y, z))
+    }
+
+    pub fn collision_shape(&self, x: i32, y: i32, z: i32) -> Vec<Cuboid> {
+        self.get_state(x, y, z).collision_shape(self.fence_connections(x, y, z))
+    }
+
+    pub fn fluid_level(&self, x: i32, y: i32, z: i32) -> u8 {
+        let (pos, [x, y, z]) = locate(x, y, z);
+        match self.chunks.get(&pos) {
+            Some(chunk) => chunk.fluid_level(x, y, z),
+            None => 0,
+        }
+    }
+
+    pub fn block_entity(&self, x: i32, y: i32, z: i32) -> Option<&BlockEntity> {
+        let (pos, [x, y, z]) = locate(x, y, z);
+        self.chunks.get(&pos)?.block_entity(x, y, z)
+    }
+
+    /// Replaces the block entity at a position, such as a furnace that's
+    /// burnt for a while. Fails if the block there doesn't have one of the
+    /// same kind.
+    pub fn set_block_entity(&mut self, x: i32, y: i32, z: i32, entity: BlockEntity) -> bool {
+        let (pos, [x, y, z]) = locate(x, y, z);
+        let Some(chunk) = self.chunks.get_mut(&pos) else {
+            return false;
+        };
+        let set = chunk.set_block_entity(x, y, z, entity);
+        if set {
+            self.modified.insert(pos);
+        }
+        set
+    }
+
+    /// Replaces the items in the block entity at a position, such as a
+    /// chest's contents after they've been moved around, keeping the rest
+    /// of its state. Fails if there's no entity there with that many slots.
+    pub fn set_block_entity_items(&mut self, x: i32, y: i32, z: i32, items: Inventory) -> bool {
+        let Some(mut entity) = self.block_entity(x, y, z).cloned() else {
+            return false;
+        };
+        if entity.items().slot_count() != items.slot_count() {
+            return false;
+        }
+        *entity.items_mut() = items;
+        self.set_block_entity(x, y, z, entity)
+    }
+
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: BlockId) -> bool {
+        self.set_state(x, y, z, block.into())
+    }
+
+    pub fn set_state(&mut self, x: i32, y: i32, z: i32, state: BlockState) -> bool {
+        let level = if state.block.is_fluid() { fluid::SOURCE_LEVEL } else { 0 };
+        self.set_voxel(x, y, z, state, level)
+    }
+
+    pub fn set_block_with_level(&mut self, x: i32, y: i32, z: i32, block: BlockId, level: u8) -> bool {
+        self.set_voxel(x, y, z, block.into(), level)
+    }
+
+    pub fn set_voxel(&mut self, x: i32, y: i32, z: i32, state: BlockState, level: u8) -> bool {
+        let (pos, local) = locate(x, y, z);
+        let Some(chunk) = self.chunks.get_mut(&pos) else {
+            return false;
+        };
+        let [local_x, local_y, local_z] = local;
+        let old = chunk.get_state(local_x, local_y, local_z);
+        chunk.set_voxel(local_x, local_y, local_z, state, level);
+
+        self.mark_block_dirty(pos, local);
+        self.modified.insert(pos);
+        if let Some(changes) = &mut self.changes {
+            changes.push(BlockChange { block: [x, y, z], state, level });
+        }
+        self.schedule_fluid_updates(x, y, z);
+        self.schedule_fall_checks(x, y, z);
+        self.schedule_signal_updates(x, y, z);
+        light::block_changed(self, [x, y, z], old);
+
+        true
+    }
+
+    /// The block at a position with everything kept along with it, such as
+    /// to put it back after it's changed.
+    pub fn snapshot(&self, [x, y, z]: [i32 ; 3]) -> BlockSnapshot {
+        BlockSnapshot {
+            block: [x, y, z],
+            state: self.get_state(x, y, z),
+            level: self.fluid_level(x, y, z),
+            entity: self.block_entity(x, y, z).cloned(),
+        }
+    }
+
+    /// Sets many blocks at once, for edits too big to go one by one: the
+    /// light is fixed up once they're all in, chunk by chunk if there are
+    /// many, and each chunk they touch is remeshed once. Blocks in chunks
+    /// that aren't loaded are left out, and so are blocks that already
+    /// hold the requested state. Returns what was at each block that
+    /// changed, in the same order, which setting again puts back.
+    pub fn set_voxels(&mut self, blocks: impl IntoIterator<Item = BlockSnapshot>) -> Vec<BlockSnapshot> {
+        let mut previous = Vec::new();
+        let mut touched = HashSet::new();
+        let mut light_changes = 0;
+        for snapshot in blocks {
+            let [x, y, z] = snapshot.block;
+            let (pos, [local_x, local_y, local_z]) = locate(x, y, z);
+            let Some(chunk) = self.chunks.get_mut(&pos) else {
+                continue;
+            };
+            let old = BlockSnapshot {
+                block: snapshot.block,
+                state: chunk.get_state(local_x, local_y, local_z),
+                level: chunk.fluid_level(local_x, local_y, local_z),
+                entity: chunk.block_entity(local_x, local_y, local_z).cloned(),
+            };
+            let same_entity = snapshot.entity.is_none() || snapshot.entity == old.entity;
+            if old.state == snapshot.state && old.level == snapshot.level && same_entity {
+                continue;
+            }
+
+            chunk.set_voxel(local_x, local_y, local_z, snapshot.state, snapshot.level);
+            if let Some(entity) = snapshot.entity {
+                chunk.set_block_entity(local_x, local_y, local_z, entity);
+            }
+            touched.insert(pos);
+            if light::affects_light(old.state, snapshot.state) {
+                light_changes += 1;
+            }
+            if let Some(changes) = &mut self.changes {
+                changes.push(BlockChange { block: snapshot.block, state: snapshot.state, level: snapshot.level });
+            }
+            self.schedule_fluid_updates(x, y, z);
+            self.schedule_fall_checks(x, y, z);
+            self.schedule_signal_updates(x, y, z);
+            previous.push(old);
+        }
+
+        if light_changes > RELIGHT_CHUNKS_AFTER {
+            for pos in light::relight_chunks(self, &touched) {
+                self.dirty.insert(pos);
+            }
+        } else {
+            for old in &previous {
+                light::block_changed(self, old.block, old.state);
+            }
+        }
+        for pos in touched {
+            self.modified.insert(pos);
+            self.mark_dirty_with_neighbors(pos);
+        }
+        previous
+    }
+
+    /// Open sky wherever the chunk isn't loaded.
+    pub fn light(&self, x: i32, y: i32, z: i32) -> Light {
+        let (pos, [x, y, z]) = locate(x, y, z);
+        match self.chunks.get(&pos) {
+            Some(chunk) => chunk.light(x, y, z),
+            None => Light::SKY,
+        }
+    }
+
+    /// Stores the light at a position for `light` and remeshes around it.
+    pub fn set_light(&mut self, x: i32, y: i32, z: i32, light: Light) -> bool {
+        let (pos, local) = locate(x, y, z);
+        let Some(chunk) = self.chunks.get_mut(&pos) else {
+            return false;
+        };
+        let [local_x, local_y, local_z] = local;
+        chunk.set_light(local_x, local_y, local_z, light);
+        self.mark_block_dirty(pos, local);
+        true
+    }
+
+    // Marks the chunk holding a block for remeshing, along with the
+    // neighbours it borders if it's on the edge.
+    fn mark_block_dirty(&mut self, pos: ChunkPos, local: [usize ; 3]) {
+        self.dirty.insert(pos);
+
+        // Faces on the chunk border belong to the neighbor's mesh too.
+        let edge = CHUNK_SIZE - 1;
+        for axis in 0..3 {
+            for (on_edge, step) in [(local[axis] == 0, -1), (local[axis] == edge, 1)] {
+                let mut offset = [0 ; 3];
+                offset[axis] = step;
+                let neighbor = pos.offset(offset[0], offset[1], offset[2]);
+                if on_edge && self.chunks.contains_key(&neighbor) {
+                    self.dirty.insert(neighbor);
+                }
+            }
+        }
+    }
+
+    // Any fluid at or next to a changed block may need to flow or drain.
+    fn schedule_fluid_updates(&mut self, x: i32, y: i32, z: i32) {
+        let offsets = [[0, 0, 0], [1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+        for [dx, dy, dz] in offsets {
+            let (x, y, z) = (x + dx, y + dy, z + dz);
+            if self.get_block(x, y, z).is_fluid() {
+                self.fluid_updates.schedule(x, y, z);
+            }
+        }
+    }
+
+    pub fn take_fluid_updates(&mut self, budget: usize) -> Vec<[i32 ; 3]> {
+        self.fluid_updates.take(budget)
+    }
+
+    // A block that falls may have been put somewhere with nothing under
+    // it, or had what was under it taken away.
+    fn schedule_fall_checks(&mut self, x: i32, y: i32, z: i32) {
+        for y in [y, y + 1] {
+            if self.get_block(x, y, z).info().falls {
+                self.fall_checks.insert([x, y, z]);
+            }
+        }
+    }
+
+    /// Every block that falls and was changed, or had the block under it
+    /// changed, since this was last called.
+    pub fn take_fall_checks(&mut self) -> Vec<[i32 ; 3]> {
+        self.fall_checks.drain().collect()
+    }
+
+    // Wire joins wire a block up or down as well as beside it, so a change
+    // can reach the blocks beside, above and below it, and beside those.
+    fn schedule_signal_updates(&mut self, x: i32, y: i32, z: i32) {
+        for dy in -1..=1 {
+            for (dx, dz) in [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let (x, y, z) = (x + dx, y + dy, z + dz);
+                if self.get_block(x, y, z).carries_signals() {
+                    self.signal_updates.insert([x, y, z]);
+                }
+            }
+        }
+    }
+
+    pub fn schedule_signal_update(&mut self, x: i32, y: i32, z: i32) {
+        self.signal_updates.insert([x, y, z]);
+    }
+
+    pub fn take_signal_updates(&mut self) -> Vec<[i32 ; 3]> {
+        self.signal_updates.drain().collect()
+    }
+
+    /// The strength of the signal in the wire at a position, which keeps
+    /// it where fluids keep their level; 0 for anything but wire.
+    pub fn signal_strength(&self, x: i32, y: i32, z: i32) -> u8 {
+        if self.get_block(x, y, z).info().model != BlockModel::Wire {
+            return 0;
+        }
+        self.fluid_level(x, y, z)
+    }
+
+    // Walks the voxel grid along the ray (Amanatides & Woo) and returns
+    // the first block hit within `max_distance`. Fluids are passed through.
+    pub fn raycast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<RaycastHit> {
+        use cgmath::InnerSpace;
+
+        if direction.magnitude2() == 0.0 {
+            return None;
+        }
+        let direction = direction.normalize();
+
+        let origin = [origin.x, origin.y, origin.z];
+        let direction = [direction.x, direction.y, direction.z];
+
+        let mut block = origin.map(|v| v.floor() as i32);
+        let mut step = [0 ; 3];
+        let mut t_max = [f32::INFINITY ; 3];
+        let mut t_delta = [f32::INFINITY ; 3];
+
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                t_max[axis] = (block[axis] as f32 + 1.0 - origin[axis]) / direction[axis];
+                t_delta[axis] = 1.0 / direction[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                t_max[axis] = (origin[axis] - block[axis] as f32) / -direction[axis];
+                t_delta[axis] = 1.0 / -direction[axis];
+            }
+        }
+
+        let mut normal = [0 ; 3];
+        let mut distance = 0.0;
+
+        while distance <= max_distance {
+            let hit = self.get_block(block[0], block[1], block[2]);
+            if !hit.is_air() && !hit.is_fluid() {
+                let shape = self.block_shape(block[0], block[1], block[2]);
+                if shape == [Cuboid::FULL] {
+                    return Some(RaycastHit { block, normal, distance });
+                }
+                // Partial blocks are only hit where the ray meets one of
+                // their boxes; otherwise it carries on through.
+                let nearest = shape.iter()
+                    .filter_map(|cuboid| ray_cuboid(origin, direction, block, cuboid))
+                    .min_by(|a, b| a.0.total_cmp(&b.0));
+                if let Some((distance, normal)) = nearest.filter(|(d, _)| *d <= max_distance) {
+                    return Some(RaycastHit { block, normal, distance });
+                }
+            }
+
+            let axis = if t_max[0] < t_max[1] {
+                if t_max[0] < t_max[2] { 0 } else { 2 }
+            } else if t_max[1] < t_max[2] {
+                1
+            } else {
+                2
+            };
+
+            distance = t_max[axis];
+            t_max[axis] += t_delta[axis];
+            block[axis] += step[axis];
+
+            normal = [0 ; 3];
+            normal[axis] = -step[axis];
+        }
+
+        None
+    }
+}
+
+// The chunk holding a block and where the block is in it.
+fn locate(x: i32, y: i32, z: i32) -> (ChunkPos, [usize ; 3]) {
+    let local = [x, y, z].map(|v| v.rem_euclid(CHUNK_SIZE as i32) as usize);
+    (ChunkPos::from_block(x, y, z), local)
+}
+
+// Where a ray enters a box inside `block`, as the distance along it and the
+// normal of the face it went through. A ray starting inside hits at once.
+fn ray_cuboid(origin: [f32 ; 3], direction: [f32 ; 3], block: [i32 ; 3], cuboid: &Cuboid) -> Option<(f32, [i32 ; 3])> {
+    let mut near = (f32::NEG_INFINITY, [0 ; 3]);
+    let mut far = f32::INFINITY;
+
+    for axis in 0..3 {
+        let min = block[axis] as f32 + cuboid.min[axis];
+        let max = block[axis] as f32 + cuboid.max[axis];
+        if direction[axis] == 0.0 {
+            if origin[axis] < min || origin[axis] > max {
+                return None;
+            }
+            continue;
+        }
+
+        let (entry, exit) = if direction[axis] > 0.0 { (min, max) } else { (max, min) };
+        let t_entry = (entry - origin[axis]) / direction[axis];
+        let t_exit = (exit - origin[axis]) / direction[axis];
+        if t_entry > near.0 {
+            let mut normal = [0 ; 3];
+            normal[axis] = if direction[axis] > 0.0 { -1 } else { 1 };
+            near = (t_entry, normal);
+        }
+        far = far.min(t_exit);
+    }
+
+    (near.0 <= far && far >= 0.0).then(|| (near.0.max(0.0), near.1))
+}
@@ -0,0 +1,95 @@
+//! Creative tools for editing the world a box at a time. The wand marks
+//! the box's corners, the first with a left click and the second with a
+//! right click; `/fill`, `/replace` and `/copy` work on what it marks, and
+//! `/paste` puts down what was copied. Edits go through
+//! `World::set_voxels`, so however many blocks they change, each chunk is
+//! relit and remeshed once, and hand back what they changed for the edit
+//! history.
+
+use crate::{
+    block::{BlockId, BlockState},
+    fluid,
+    schematic::Schematic,
+    world::{BlockSnapshot, World},
+};
+
+/// Whether `block` is the wand that marks out selections.
+pub fn is_wand(block: BlockId) -> bool {
+    block.info().name == "wand"
+}
+
+/// The selection and clipboard of the player editing the world.
+#[derive(Debug, Default)]
+pub struct WorldEdit {
+    corners: [Option<[i32 ; 3]> ; 2],
+    clipboard: Option<Schematic>,
+}
+
+impl WorldEdit {
+    /// Forgets the selection, which belongs to the world being left, but
+    /// keeps the clipboard to paste into the next.
+    pub fn change_world(&mut self) {
+        self.corners = [None ; 2];
+    }
+
+    /// Marks the first (0) or second (1) corner of the selection.
+    pub fn set_corner(&mut self, corner: usize, block: [i32 ; 3]) {
+        self.corners[corner] = Some(block);
+    }
+
+    /// The selected box, lowest corner first, once both corners are marked.
+    pub fn selection(&self) -> Option<([i32 ; 3], [i32 ; 3])> {
+        let [Some(a), Some(b)] = self.corners else {
+            return None;
+        };
+        Some((
+            [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+            [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+        ))
+    }
+
+    /// Sets every block from `min` to `max` to `block`, and returns what
+    /// was there before each that changed.
+    pub fn fill(&self, world: &mut World, min: [i32 ; 3], max: [i32 ; 3], block: BlockId) -> Vec<BlockSnapshot> {
+        world.set_voxels(positions(min, max).map(|position| snapshot(position, block)))
+    }
+
+    /// Turns every `from` block from `min` to `max` into `to`, and returns
+    /// what was there before each that changed.
+    pub fn replace(&self, world: &mut World, min: [i32 ; 3], max: [i32 ; 3], from: BlockId, to: BlockId) -> Vec<BlockSnapshot> {
+        let blocks = positions(min, max)
+            .filter(|&[x, y, z]| world.get_block(x, y, z) == from)
+            .map(|position| snapshot(position, to))
+            .collect::<Vec<_>>();
+        world.set_voxels(blocks)
+    }
+
+    /// Copies the blocks from `min` to `max`, replacing whatever was copied
+    /// before.
+    pub fn copy(&mut self, world: &World, min: [i32 ; 3], max: [i32 ; 3]) -> [usize ; 3] {
+        let copied = Schematic::capture(world, min, max);
+        let size = copied.size();
+        self.clipboard = Some(copied);
+        size
+    }
+
+    /// Puts down what was copied with its lowest corner at `origin`, and
+    /// returns what was there before each block that changed, or `None`
+    /// with nothing copied.
+    pub fn paste(&self, world: &mut World, origin: [i32 ; 3]) -> Option<Vec<BlockSnapshot>> {
+        Some(self.clipboard.as_ref()?.place(world, origin))
+    }
+}
+
+// Every block from `min` to `max`, in the y, z, x order chunks keep them.
+fn positions(min: [i32 ; 3], max: [i32 ; 3]) -> impl Iterator<Item = [i32 ; 3]> {
+    (min[1]..=max[1]).flat_map(move |y| {
+        (min[2]..=max[2]).flat_map(move |z| (min[0]..=max[0]).map(move |x| [x, y, z]))
+    })
+}
+
+// A new `block` at `position`, as `World::set_block` would leave it.
+fn snapshot(position: [i32 ; 3], block: BlockId) -> BlockSnapshot {
+    let level = if block.is_fluid() { fluid::SOURCE_LEVEL } else { 0 };
+    BlockSnapshot { block: position, state: BlockState::from(block), level, entity: None }
+}
@@ -0,0 +1,331 @@
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::BlockId,
+    chunk::{Chunk, ChunkPos, CHUNK_SIZE},
+    dimension::Dimension,
+};
+
+pub mod biome;
+pub mod nether;
+pub mod structure;
+
+use biome::{BiomeId, BIOMES};
+use structure::{PendingBlock, Structure, StructureWriter};
+
+pub const SEA_LEVEL: i32 = 48;
+
+// Climate noise varies much more slowly than terrain so biomes span
+// several chunks.
+const CLIMATE_SCALE: f64 = 320.0;
+
+// Width of the climate-space falloff used to blend terrain shape between
+// neighbouring biomes; smaller values give sharper transitions.
+const BLEND_WIDTH_SQ: f64 = 0.02;
+
+// Solid blocks kept between caves and the surface, so caves never cut
+// holes under trees or open into the sky.
+const CAVE_CRUST: i32 = 4;
+
+// How far below sea level the sea floor turns from sand to gravel.
+const GRAVEL_DEPTH: i32 = 6;
+
+// Surface height of flat worlds.
+const FLAT_HEIGHT: i32 = SEA_LEVEL + 8;
+
+/// The overall shape of a world's terrain, chosen when it's created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorldType {
+    #[default]
+    Normal,
+    /// Level ground at one height everywhere, with no caves or oceans.
+    Flat,
+}
+
+impl WorldType {
+    pub const ALL: [WorldType ; 2] = [WorldType::Normal, WorldType::Flat];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WorldType::Normal => "Normal",
+            WorldType::Flat => "Flat",
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceColumn {
+    pub height: i32,
+    pub surface: BlockId,
+    pub subsurface: BlockId,
+}
+
+#[derive(Clone)]
+pub struct WorldGenerator {
+    pub seed: u32,
+    pub world_type: WorldType,
+    /// Which dimension's terrain this makes; the world type only shapes
+    /// the overworld.
+    pub dimension: Dimension,
+    height_noise: Perlin,
+    temperature_noise: Perlin,
+    humidity_noise: Perlin,
+    cave_noise: [Perlin ; 3],
+    cave_density: f64,
+}
+
+impl WorldGenerator {
+    pub fn new(seed: u32, world_type: WorldType, cave_density: f64) -> Self {
+        Self {
+            seed,
+            world_type,
+            dimension: Dimension::Overworld,
+            height_noise: Perlin::new(seed),
+            temperature_noise: Perlin::new(seed.wrapping_add(1)),
+            humidity_noise: Perlin::new(seed.wrapping_add(2)),
+            cave_noise: [
+                Perlin::new(seed.wrapping_add(3)),
+                Perlin::new(seed.wrapping_add(4)),
+                Perlin::new(seed.wrapping_add(5)),
+            ],
+            cave_density: if world_type == WorldType::Flat { 0.0 } else { cave_density.max(0.0) },
+        }
+    }
+
+    /// The same generator, seed and all, making `dimension`'s terrain.
+    pub fn with_dimension(mut self, dimension: Dimension) -> Self {
+        self.dimension = dimension;
+        self
+    }
+
+    /// Temperature and humidity at a column, both in [0, 1].
+    fn climate_at(&self, x: i32, z: i32) -> (f64, f64) {
+        let point = [x as f64 / CLIMATE_SCALE, z as f64 / CLIMATE_SCALE];
+        let temperature = self.temperature_noise.get(point) * 0.5 + 0.5;
+        let humidity = self.humidity_noise.get(point) * 0.5 + 0.5;
+        (temperature.clamp(0.0, 1.0), humidity.clamp(0.0, 1.0))
+    }
+
+    pub fn biome_at(&self, x: i32, z: i32) -> BiomeId {
+        let (temperature, humidity) = self.climate_at(x, z);
+        let index = BIOMES.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.climate_distance_sq(temperature, humidity)
+                    .total_cmp(&b.climate_distance_sq(temperature, humidity))
+            })
+            .map_or(0, |(index, _)| index);
+
+        BiomeId(index as u8)
+    }
+
+    /// The height of the terrain's surface; in the nether, that's the
+    /// roof.
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        if self.dimension == Dimension::Nether {
+            return nether::ROOF_HEIGHT;
+        }
+        if self.world_type == WorldType::Flat {
+            return FLAT_HEIGHT;
+        }
+
+        // Blend every biome's terrain shape by climate proximity so biome
+        // borders slope instead of forming cliffs.
+        let (temperature, humidity) = self.climate_at(x, z);
+        let (mut offset, mut amplitude, mut total) = (0.0, 0.0, 0.0);
+        for biome in BIOMES {
+            let weight = (-biome.climate_distance_sq(temperature, humidity) / BLEND_WIDTH_SQ).exp();
+            offset += biome.height_offset * weight;
+            amplitude += biome.amplitude * weight;
+            total += weight;
+        }
+        let (offset, amplitude) = (offset / total, amplitude / total);
+
+        let (x, z) = (x as f64, z as f64);
+        let base = self.height_noise.get([x / 96.0, z / 96.0]) * 14.0;
+        let detail = self.height_noise.get([x / 24.0 + 100.0, z / 24.0 + 100.0]) * 4.0;
+
+        (SEA_LEVEL as f64 + offset + (base + detail) * amplitude).round() as i32
+    }
+
+    /// A column's terrain height and blocks, ignoring caves and structures.
+    /// Cheap enough to sample for terrain too far away to generate.
+    pub fn surface_at(&self, x: i32, z: i32) -> SurfaceColumn {
+        let height = self.height_at(x, z);
+        if height <= SEA_LEVEL + 1 {
+            return SurfaceColumn { height, surface: sea_floor(height), subsurface: BlockId::SAND };
+        }
+
+        let biome = self.biome_at(x, z).info();
+        SurfaceColumn { height, surface: biome.surface, subsurface: biome.subsurface }
+    }
+
+    /// How far below the bottom of the world something has to fall before
+    /// the void takes it, if the terrain has a bottom at all.
+    pub fn void_depth(&self) -> Option<i32> {
+        (self.dimension == Dimension::Nether).then_some(nether::VOID_DEPTH)
+    }
+
+    /// Generates a chunk along with any structure blocks that spilled over
+    /// into its neighbours.
+    pub fn generate(&self, pos: ChunkPos) -> (Chunk, Vec<PendingBlock>) {
+        if self.dimension == Dimension::Nether {
+            return (nether::generate(self, pos), Vec::new());
+        }
+        let _span = tracing::trace_span!("generate_chunk", x = pos.x, y = pos.y, z = pos.z).entered();
+        let mut chunk = Chunk::new(pos);
+        let [origin_x, origin_y, origin_z] = pos.origin();
+        // The heights in the chunk, by world y.
+        let layers = origin_y..origin_y + CHUNK_SIZE as i32;
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let world_x = origin_x + x as i32;
+                let world_z = origin_z + z as i32;
+                let height = self.height_at(world_x, world_z);
+                // Nothing reaches a chunk wholly above the surface and the
+                // sea but plants and structures, which start one above.
+                if origin_y > height.max(SEA_LEVEL) + 1 {
+                    continue;
+                }
+                let biome = self.biome_at(world_x, world_z).info();
+                let beach = height <= SEA_LEVEL + 1;
+
+                for y in layers.clone() {
+                    let block = if y > height {
+                        // Everything below sea level that the terrain
+                        // doesn't fill is still water.
+                        if y <= SEA_LEVEL { BlockId::WATER } else { continue }
+                    } else if y == height {
+                        if beach { sea_floor(height) } else { biome.surface }
+                    } else if y + 4 > height {
+                        if beach { BlockId::SAND } else { biome.subsurface }
+                    } else {
+                        BlockId::STONE
+                    };
+                    chunk.set(x, (y - origin_y) as usize, z, block);
+                }
+
+                if !beach && biome.surface == BlockId::GRASS && layers.contains(&(height + 1)) {
+                    let roll = self.column_random(world_x, world_z) as f64 / u32::MAX as f64;
+                    if roll < biome.plant_density {
+                        // Roughly one in ten plants is a flower and one in
+                        // twenty a berry bush.
+                        let plant = match self.column_random(world_z, world_x) % 20 {
+                            0 => BlockId::FLOWER_RED,
+                            1 => BlockId::FLOWER_YELLOW,
+                            2 => BlockId::BERRY_BUSH,
+                            _ => BlockId::TALL_GRASS,
+                        };
+                        chunk.set(x, (height + 1 - origin_y) as usize, z, plant);
+                    }
+                }
+            }
+        }
+
+        self.carve_caves(&mut chunk);
+        let overflow = self.place_structures(&mut chunk);
+        chunk.compact();
+        (chunk, overflow)
+    }
+
+    fn carve_caves(&self, chunk: &mut Chunk) {
+        if self.cave_density == 0.0 {
+            return;
+        }
+
+        // Tunnels follow the lines where two noise fields both cross zero,
+        // which gives long connected worms; caverns are the peaks of a
+        // third, slower field.
+        let tunnel_width = 0.08 * self.cave_density;
+        let cavern_threshold = 0.7 - 0.15 * self.cave_density;
+
+        let [origin_x, origin_y, origin_z] = chunk.pos.origin();
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let world_x = origin_x + x as i32;
+                let world_z = origin_z + z as i32;
+                let height = self.height_at(world_x, world_z);
+
+                for y in origin_y..(height - CAVE_CRUST).min(origin_y + CHUNK_SIZE as i32) {
+                    let point = [world_x as f64 / 48.0, y as f64 / 28.0, world_z as f64 / 48.0];
+                    let a = self.cave_noise[0].get(point);
+                    let b = self.cave_noise[1].get(point);
+                    let tunnel = a.abs() < tunnel_width && b.abs() < tunnel_width;
+
+                    let cavern_point = point.map(|v| v * 0.5);
+                    let cavern = self.cave_noise[2].get(cavern_point) > cavern_threshold;
+
+                    if tunnel || cavern {
+                        chunk.set(x, (y - origin_y) as usize, z, BlockId::AIR);
+                    }
+                }
+            }
+        }
+    }
+
+    // Runs after the terrain pass so structures can grow over neighbouring
+    // columns without being overwritten by them. Each is placed by the chunk
+    // its base is in.
+    fn place_structures(&self, chunk: &mut Chunk) -> Vec<PendingBlock> {
+        let [origin_x, origin_y, origin_z] = chunk.pos.origin();
+        let mut writer = StructureWriter::new(chunk);
+
+        for z in 0..CHUNK_SIZE as i32 {
+            for x in 0..CHUNK_SIZE as i32 {
+                let (world_x, world_z) = (origin_x + x, origin_z + z);
+                let height = self.height_at(world_x, world_z);
+                let base = height + 1;
+                if height <= SEA_LEVEL + 1 || !(origin_y..origin_y + CHUNK_SIZE as i32).contains(&base) {
+                    continue;
+                }
+
+                let biome = self.biome_at(world_x, world_z).info();
+                // Rolls use different bits of a salted hash so they don't
+                // correlate with the plant placement above.
+                let random = self.column_random(world_x ^ 0x5F35, world_z ^ 0x3A7C);
+                let roll = (random >> 8) as f64 / (u32::MAX >> 8) as f64;
+
+                let structure = if biome.surface == BlockId::GRASS && roll < biome.tree_density {
+                    Structure::Tree { height: 4 + (random % 3) as i32 }
+                } else if roll < biome.tree_density + biome.boulder_density {
+                    Structure::Boulder { radius: 1 + (random % 2) as i32 }
+                } else {
+                    continue;
+                };
+                structure.place(&mut writer, world_x, base, world_z);
+            }
+        }
+
+        writer.overflow
+    }
+
+    // Cheap deterministic per-column hash, so decorations land in the same
+    // spots every time a chunk is generated.
+    fn column_random(&self, x: i32, z: i32) -> u32 {
+        let mut h = self.seed
+            ^ (x as u32).wrapping_mul(0x9E37_79B9)
+            ^ (z as u32).wrapping_mul(0x85EB_CA6B);
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x7FEB_352D);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x846C_A68B);
+        h ^ (h >> 16)
+    }
+}
+
+// The top block of a column at or below the shore, sand in the shallows and
+// gravel out in deeper water.
+fn sea_floor(height: i32) -> BlockId {
+    if height < SEA_LEVEL - GRAVEL_DEPTH { BlockId::GRAVEL } else { BlockId::SAND }
+}
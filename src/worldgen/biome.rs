@@ -0,0 +1,101 @@
+use crate::block::BlockId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BiomeId(pub u8);
+
+impl BiomeId {
+    pub fn info(self) -> &'static Biome {
+        &BIOMES[self.0 as usize]
+    }
+}
+
+/// What falls from the sky in a biome when it rains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precipitation {
+    None,
+    Rain,
+    Snow,
+}
+
+pub struct Biome {
+    pub name: &'static str,
+    /// Climate this biome is centered on, both in [0, 1]. Each column picks
+    /// the biome whose center is closest to its sampled climate.
+    pub temperature: f64,
+    pub humidity: f64,
+    pub surface: BlockId,
+    pub subsurface: BlockId,
+    /// Offset and scale applied to the shared height noise.
+    pub height_offset: f64,
+    pub amplitude: f64,
+    /// Chance per column, in [0, 1].
+    pub tree_density: f64,
+    pub boulder_density: f64,
+    pub plant_density: f64,
+    pub precipitation: Precipitation,
+}
+
+/// Every biome the generator can produce, indexed by `BiomeId`. Adding a
+/// biome only needs a new entry here.
+pub const BIOMES: &[Biome] = &[
+    Biome {
+        name: "plains",
+        temperature: 0.55,
+        humidity: 0.45,
+        surface: BlockId::GRASS,
+        subsurface: BlockId::DIRT,
+        height_offset: 6.0,
+        amplitude: 0.6,
+        tree_density: 0.002,
+        boulder_density: 0.001,
+        plant_density: 0.2,
+        precipitation: Precipitation::Rain,
+    },
+    Biome {
+        name: "forest",
+        temperature: 0.5,
+        humidity: 0.8,
+        surface: BlockId::GRASS,
+        subsurface: BlockId::DIRT,
+        height_offset: 8.0,
+        amplitude: 1.0,
+        tree_density: 0.03,
+        boulder_density: 0.0005,
+        plant_density: 0.12,
+        precipitation: Precipitation::Rain,
+    },
+    Biome {
+        name: "desert",
+        temperature: 0.9,
+        humidity: 0.1,
+        surface: BlockId::SAND,
+        subsurface: BlockId::SAND,
+        height_offset: 5.0,
+        amplitude: 0.4,
+        tree_density: 0.0,
+        boulder_density: 0.0,
+        plant_density: 0.0,
+        precipitation: Precipitation::None,
+    },
+    Biome {
+        name: "mountains",
+        temperature: 0.2,
+        humidity: 0.4,
+        surface: BlockId::GRASS,
+        subsurface: BlockId::STONE,
+        height_offset: 16.0,
+        amplitude: 2.6,
+        tree_density: 0.004,
+        boulder_density: 0.004,
+        plant_density: 0.06,
+        precipitation: Precipitation::Snow,
+    },
+];
+
+impl Biome {
+    pub fn climate_distance_sq(&self, temperature: f64, humidity: f64) -> f64 {
+        let dt = self.temperature - temperature;
+        let dh = self.humidity - humidity;
+        dt * dt + dh * dh
+    }
+}
@@ -0,0 +1,106 @@
+//! The nether's terrain: rock from a solid floor up to a solid roof, hollowed
+//! into caverns by 3D noise, with glowing clusters hanging from the
+//! ceilings and gravel drifted over the floors.
+
+use noise::NoiseFn;
+
+use super::WorldGenerator;
+use crate::{
+    block::BlockId,
+    chunk::{Chunk, ChunkPos, CHUNK_SIZE},
+};
+
+/// The lowest block of the roof's top layer; above it is open sky nobody
+/// sees.
+pub const ROOF_HEIGHT: i32 = 127;
+/// Where the void starts under the floor, which is all there is below it.
+pub const VOID_DEPTH: i32 = -64;
+// How thick the roof is, always solid however the caverns run.
+const ROOF_DEPTH: i32 = 4;
+// Below and above these heights the caverns close up, so there's a floor
+// to walk on and the roof isn't worn thin.
+const FLOOR_FALLOFF: i32 = 32;
+const CEILING_FALLOFF: i32 = 96;
+// Noise above this is rock; higher leaves more room.
+const ROCK_THRESHOLD: f64 = 0.1;
+// One in this many cavern ceilings has a glowing cluster hanging from it.
+const GLOW_CHANCE: u32 = 40;
+// Floors this far down where the drift noise is high are gravel.
+const GRAVEL_HEIGHT: i32 = 40;
+
+pub fn generate(generator: &WorldGenerator, pos: ChunkPos) -> Chunk {
+    let _span = tracing::trace_span!("generate_nether_chunk", x = pos.x, y = pos.y, z = pos.z).entered();
+    let netherrack = BlockId::from_name("netherrack").unwrap_or(BlockId::STONE);
+    let glowstone = BlockId::from_name("glowstone").unwrap_or(BlockId::STONE);
+    let mut chunk = Chunk::new(pos);
+    let [origin_x, origin_y, origin_z] = pos.origin();
+    // Only the band from the floor to the roof has anything in it.
+    let layers = origin_y.max(0)..(origin_y + CHUNK_SIZE as i32).min(ROOF_HEIGHT + 1);
+    if layers.is_empty() {
+        return chunk;
+    }
+
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            let world_x = origin_x + x as i32;
+            let world_z = origin_z + z as i32;
+            let drift = generator.height_noise.get([world_x as f64 / 24.0, world_z as f64 / 24.0]);
+            let local = |y: i32| (y - origin_y) as usize;
+
+            for y in layers.clone() {
+                if is_rock(generator, world_x, y, world_z) {
+                    chunk.set(x, local(y), z, netherrack);
+                }
+            }
+
+            // Worked out from the rock rather than read back, as the block
+            // above or below may be in another chunk.
+            let block_at = |y: i32| if (0..=ROOF_HEIGHT).contains(&y) && is_rock(generator, world_x, y, world_z) {
+                netherrack
+            } else {
+                BlockId::AIR
+            };
+            for y in layers.clone().filter(|&y| (1..ROOF_HEIGHT).contains(&y)) {
+                let (here, below, above) = (block_at(y), block_at(y - 1), block_at(y + 1));
+                if here == netherrack && above.is_air() && y < GRAVEL_HEIGHT && drift > 0.3 {
+                    chunk.set(x, local(y), z, BlockId::GRAVEL);
+                }
+
+                // Clusters hang a block or two down from where rock meets
+                // the air beneath it.
+                if here.is_air() && above == netherrack && below.is_air() && y > FLOOR_FALLOFF {
+                    let random = generator.column_random(world_x ^ y, world_z ^ (y << 8));
+                    if random.is_multiple_of(GLOW_CHANCE) {
+                        chunk.set(x, local(y), z, glowstone);
+                    }
+                }
+                // The cluster's second block, under one that hangs from
+                // the rock above it.
+                let hangs_above = above.is_air() && block_at(y + 2) == netherrack && y + 1 > FLOOR_FALLOFF;
+                if here.is_air() && hangs_above {
+                    let random = generator.column_random(world_x ^ (y + 1), world_z ^ ((y + 1) << 8));
+                    if random.is_multiple_of(GLOW_CHANCE) && random & (1 << 16) != 0 {
+                        chunk.set(x, local(y), z, glowstone);
+                    }
+                }
+            }
+        }
+    }
+
+    chunk.compact();
+    chunk
+}
+
+fn is_rock(generator: &WorldGenerator, x: i32, y: i32, z: i32) -> bool {
+    if y == 0 || y > ROOF_HEIGHT - ROOF_DEPTH {
+        return true;
+    }
+
+    let point = [x as f64 / 48.0, y as f64 / 24.0, z as f64 / 48.0];
+    let shape = generator.cave_noise[0].get(point);
+    let detail = generator.cave_noise[1].get(point.map(|v| v * 3.0)) * 0.3;
+    // Solid rock fades in towards the floor and the roof.
+    let closing = ((FLOOR_FALLOFF - y).max(0) as f64 / FLOOR_FALLOFF as f64)
+        + ((y - CEILING_FALLOFF).max(0) as f64 / (ROOF_HEIGHT - ROOF_DEPTH - CEILING_FALLOFF) as f64);
+    shape + detail + closing > ROCK_THRESHOLD
+}
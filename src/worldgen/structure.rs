@@ -0,0 +1,96 @@
+use crate::{block::BlockId, chunk::Chunk};
+
+/// A structure block that landed outside the chunk being generated, in
+/// world coordinates. The world applies it once the owning chunk exists.
+#[derive(Clone, Copy, Debug)]
+pub struct PendingBlock {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub block: BlockId,
+}
+
+/// Structures grow into air and plants but never carve into terrain or
+/// through each other's trunks.
+pub fn can_replace(existing: BlockId) -> bool {
+    !existing.is_solid() || existing == BlockId::LEAVES
+}
+
+/// Routes structure blocks either into the chunk being generated or into
+/// an overflow list for the chunks around it.
+pub struct StructureWriter<'a> {
+    chunk: &'a mut Chunk,
+    origin: [i32 ; 3],
+    pub overflow: Vec<PendingBlock>,
+}
+
+impl<'a> StructureWriter<'a> {
+    pub fn new(chunk: &'a mut Chunk) -> Self {
+        let origin = chunk.pos.origin();
+        Self {
+            chunk,
+            origin,
+            overflow: Vec::new(),
+        }
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, z: i32, block: BlockId) {
+        let [local_x, local_y, local_z] = [x - self.origin[0], y - self.origin[1], z - self.origin[2]];
+        if !Chunk::in_bounds(local_x, local_y, local_z) {
+            self.overflow.push(PendingBlock { x, y, z, block });
+            return;
+        }
+
+        let (local_x, local_y, local_z) = (local_x as usize, local_y as usize, local_z as usize);
+        if can_replace(self.chunk.get(local_x, local_y, local_z)) {
+            self.chunk.set(local_x, local_y, local_z, block);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Structure {
+    Tree { height: i32 },
+    Boulder { radius: i32 },
+}
+
+impl Structure {
+    /// Places the structure with its base at the given world position, the
+    /// first air block above the surface.
+    pub fn place(self, writer: &mut StructureWriter, x: i32, y: i32, z: i32) {
+        match self {
+            Structure::Tree { height } => {
+                // Two wide layers under the top, then a narrow cap.
+                let top = y + height;
+                for leaf_y in top - 2..=top + 1 {
+                    let radius: i32 = if leaf_y < top { 2 } else { 1 };
+                    for dz in -radius..=radius {
+                        for dx in -radius..=radius {
+                            // Trim the corners so the canopy reads as round.
+                            if radius > 1 && dx.abs() == radius && dz.abs() == radius {
+                                continue;
+                            }
+                            writer.set(x + dx, leaf_y, z + dz, BlockId::LEAVES);
+                        }
+                    }
+                }
+
+                for trunk_y in y..top {
+                    writer.set(x, trunk_y, z, BlockId::LOG);
+                }
+            }
+            Structure::Boulder { radius } => {
+                let radius_sq = radius * radius + radius;
+                for dy in -radius..=radius {
+                    for dz in -radius..=radius {
+                        for dx in -radius..=radius {
+                            if dx * dx + dy * dy + dz * dz <= radius_sq {
+                                writer.set(x + dx, y + dy, z + dz, BlockId::COBBLESTONE);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}